@@ -310,7 +310,12 @@
 //! - `compilation`
 #![cfg_attr(feature = "compiler", doc = "(enabled),")]
 #![cfg_attr(not(feature = "compiler"), doc = "(disabled),")]
-//!   enables compilation with the wasmer engine.
+//!   enables compilation with the wasmer engine,
+//! - `component-model`
+#![cfg_attr(feature = "component-model", doc = "(enabled),")]
+#![cfg_attr(not(feature = "component-model"), doc = "(disabled),")]
+//!   enables the support of the WebAssembly component model, in the
+//!   `component` module.
 //!
 //! The features that set defaults come in sets that are mutually exclusive.
 //!
@@ -0,0 +1,995 @@
+//! The canonical ABI, i.e. how component values are lifted from and
+//! lowered to core WebAssembly values and linear memory.
+//!
+//! This follows the definitions of the [canonical ABI explainer],
+//! with UTF-8 as the only supported string encoding.
+//!
+//! [canonical ABI explainer]: https://github.com/WebAssembly/component-model/blob/main/design/mvp/CanonicalABI.md
+
+use super::{ComponentFuncType, ComponentValue, InterfaceType};
+use crate::sys::store::{AsStoreMut, AsStoreRef};
+use crate::sys::{Function, Memory, RuntimeError, Type, Value};
+use std::convert::TryInto;
+
+/// The maximum number of flat values passed as parameters before
+/// they are spilled to linear memory.
+pub(crate) const MAX_FLAT_PARAMS: usize = 16;
+
+/// The maximum number of flat values returned as results before they
+/// are spilled to linear memory.
+pub(crate) const MAX_FLAT_RESULTS: usize = 1;
+
+/// The canonical options of a lifted or lowered function.
+#[derive(Clone, Default)]
+pub(crate) struct CanonicalOptions {
+    pub(crate) memory: Option<Memory>,
+    pub(crate) realloc: Option<Function>,
+    pub(crate) post_return: Option<Function>,
+}
+
+fn error(message: impl Into<String>) -> RuntimeError {
+    RuntimeError::new(message)
+}
+
+fn type_mismatch(ty: &InterfaceType, value: &ComponentValue) -> RuntimeError {
+    error(format!(
+        "expected a value of type `{}`, found a value of kind `{}`",
+        ty,
+        value.kind()
+    ))
+}
+
+fn align_to(offset: u32, align: u32) -> u32 {
+    (offset + align - 1) / align * align
+}
+
+/// The cases of a variant-like type, i.e. the optional payload type
+/// of each case.
+fn cases(ty: &InterfaceType) -> Option<Vec<Option<&InterfaceType>>> {
+    Some(match ty {
+        InterfaceType::Variant(cases) => cases.iter().map(|(_, ty)| ty.as_ref()).collect(),
+        InterfaceType::Enum(names) => names.iter().map(|_| None).collect(),
+        InterfaceType::Union(types) => types.iter().map(Some).collect(),
+        InterfaceType::Option(ty) => vec![None, Some(&**ty)],
+        InterfaceType::Result { ok, err } => vec![ok.as_deref(), err.as_deref()],
+        _ => return None,
+    })
+}
+
+fn discriminant_size(cases: usize) -> u32 {
+    if cases <= 1 << 8 {
+        1
+    } else if cases <= 1 << 16 {
+        2
+    } else {
+        4
+    }
+}
+
+fn max_case_align(cases: &[Option<&InterfaceType>]) -> u32 {
+    cases
+        .iter()
+        .flatten()
+        .map(|ty| align(ty))
+        .max()
+        .unwrap_or(1)
+}
+
+fn flags_size(flags: usize) -> u32 {
+    match flags {
+        0 => 0,
+        1..=8 => 1,
+        9..=16 => 2,
+        _ => 4 * ((flags as u32 + 31) / 32),
+    }
+}
+
+/// The alignment of a value of type `ty` in linear memory.
+pub(crate) fn align(ty: &InterfaceType) -> u32 {
+    match ty {
+        InterfaceType::Bool | InterfaceType::S8 | InterfaceType::U8 => 1,
+        InterfaceType::S16 | InterfaceType::U16 => 2,
+        InterfaceType::S32 | InterfaceType::U32 | InterfaceType::Float32 | InterfaceType::Char => 4,
+        InterfaceType::S64 | InterfaceType::U64 | InterfaceType::Float64 => 8,
+        InterfaceType::String | InterfaceType::List(_) => 4,
+        InterfaceType::Record(fields) => fields.iter().map(|(_, ty)| align(ty)).max().unwrap_or(1),
+        InterfaceType::Tuple(types) => types.iter().map(align).max().unwrap_or(1),
+        InterfaceType::Flags(names) => flags_size(names.len()).clamp(1, 4),
+        _ => {
+            let cases = cases(ty).unwrap();
+            discriminant_size(cases.len()).max(max_case_align(&cases))
+        }
+    }
+}
+
+/// The size of a value of type `ty` in linear memory.
+pub(crate) fn size(ty: &InterfaceType) -> u32 {
+    match ty {
+        InterfaceType::Bool | InterfaceType::S8 | InterfaceType::U8 => 1,
+        InterfaceType::S16 | InterfaceType::U16 => 2,
+        InterfaceType::S32 | InterfaceType::U32 | InterfaceType::Float32 | InterfaceType::Char => 4,
+        InterfaceType::S64 | InterfaceType::U64 | InterfaceType::Float64 => 8,
+        InterfaceType::String | InterfaceType::List(_) => 8,
+        InterfaceType::Record(fields) => tuple_size(fields.iter().map(|(_, ty)| ty)),
+        InterfaceType::Tuple(types) => tuple_size(types.iter()),
+        InterfaceType::Flags(names) => flags_size(names.len()),
+        _ => {
+            let cases = cases(ty).unwrap();
+            let mut size = discriminant_size(cases.len());
+            size = align_to(size, max_case_align(&cases));
+            size += cases
+                .iter()
+                .flatten()
+                .map(|ty| self::size(ty))
+                .max()
+                .unwrap_or(0);
+            align_to(size, align(ty))
+        }
+    }
+}
+
+fn tuple_size<'a>(types: impl Iterator<Item = &'a InterfaceType> + Clone) -> u32 {
+    let align = types.clone().map(align).max().unwrap_or(1);
+    let mut size = 0;
+    for ty in types {
+        size = align_to(size, self::align(ty));
+        size += self::size(ty);
+    }
+    align_to(size, align)
+}
+
+/// The offsets of the fields of a record or tuple.
+fn tuple_offsets<'a>(types: impl Iterator<Item = &'a InterfaceType>) -> Vec<u32> {
+    let mut offset = 0;
+    types
+        .map(|ty| {
+            offset = align_to(offset, align(ty));
+            let field_offset = offset;
+            offset += size(ty);
+            field_offset
+        })
+        .collect()
+}
+
+fn join(a: Type, b: Type) -> Type {
+    match (a, b) {
+        _ if a == b => a,
+        (Type::I32, Type::F32) | (Type::F32, Type::I32) => Type::I32,
+        _ => Type::I64,
+    }
+}
+
+/// Appends the core types of the flat representation of `ty` to
+/// `out`.
+pub(crate) fn flatten(ty: &InterfaceType, out: &mut Vec<Type>) {
+    match ty {
+        InterfaceType::Bool
+        | InterfaceType::S8
+        | InterfaceType::U8
+        | InterfaceType::S16
+        | InterfaceType::U16
+        | InterfaceType::S32
+        | InterfaceType::U32
+        | InterfaceType::Char => out.push(Type::I32),
+        InterfaceType::S64 | InterfaceType::U64 => out.push(Type::I64),
+        InterfaceType::Float32 => out.push(Type::F32),
+        InterfaceType::Float64 => out.push(Type::F64),
+        InterfaceType::String | InterfaceType::List(_) => out.extend([Type::I32, Type::I32]),
+        InterfaceType::Record(fields) => fields.iter().for_each(|(_, ty)| flatten(ty, out)),
+        InterfaceType::Tuple(types) => types.iter().for_each(|ty| flatten(ty, out)),
+        InterfaceType::Flags(names) => {
+            out.extend(std::iter::repeat(Type::I32).take((names.len() + 31) / 32))
+        }
+        _ => out.extend(flatten_variant(&cases(ty).unwrap())),
+    }
+}
+
+fn flatten_variant(cases: &[Option<&InterfaceType>]) -> Vec<Type> {
+    let mut flat = vec![Type::I32];
+    for ty in cases.iter().flatten() {
+        let mut case = Vec::new();
+        flatten(ty, &mut case);
+        for (i, ty) in case.into_iter().enumerate() {
+            match flat.get_mut(i + 1) {
+                Some(joined) => *joined = join(*joined, ty),
+                None => flat.push(ty),
+            }
+        }
+    }
+    flat
+}
+
+/// The core types of the flat representation of a list of types.
+pub(crate) fn flatten_all<'a>(types: impl Iterator<Item = &'a InterfaceType>) -> Vec<Type> {
+    let mut flat = Vec::new();
+    types.for_each(|ty| flatten(ty, &mut flat));
+    flat
+}
+
+/// The core function type of a lifted or lowered component function.
+pub(crate) fn core_signature(ty: &ComponentFuncType, lowered: bool) -> (Vec<Type>, Vec<Type>) {
+    let mut params = flatten_all(ty.param_types());
+    if params.len() > MAX_FLAT_PARAMS {
+        params = vec![Type::I32];
+    }
+    let mut results = flatten_all(ty.result_types());
+    if results.len() > MAX_FLAT_RESULTS {
+        if lowered {
+            params.push(Type::I32);
+            results = vec![];
+        } else {
+            results = vec![Type::I32];
+        }
+    }
+    (params, results)
+}
+
+/// The index of the case of a variant-like value and its payload.
+fn case_of<'a>(
+    ty: &InterfaceType,
+    value: &'a ComponentValue,
+) -> Result<(usize, Option<&'a ComponentValue>), RuntimeError> {
+    let case = match (ty, value) {
+        (InterfaceType::Variant(cases), ComponentValue::Variant(name, payload)) => cases
+            .iter()
+            .position(|(case, _)| case == name)
+            .map(|index| (index, payload.as_deref())),
+        (InterfaceType::Enum(names), ComponentValue::Enum(name)) => names
+            .iter()
+            .position(|case| case == name)
+            .map(|i| (i, None)),
+        (InterfaceType::Union(types), ComponentValue::Union(index, payload)) => {
+            Some((*index as usize, Some(&**payload))).filter(|(i, _)| *i < types.len())
+        }
+        (InterfaceType::Option(_), ComponentValue::Option(None)) => Some((0, None)),
+        (InterfaceType::Option(_), ComponentValue::Option(Some(payload))) => {
+            Some((1, Some(&**payload)))
+        }
+        (InterfaceType::Result { .. }, ComponentValue::Result(Ok(payload))) => {
+            Some((0, payload.as_deref()))
+        }
+        (InterfaceType::Result { .. }, ComponentValue::Result(Err(payload))) => {
+            Some((1, payload.as_deref()))
+        }
+        _ => return Err(type_mismatch(ty, value)),
+    };
+    let (index, payload) = case.ok_or_else(|| type_mismatch(ty, value))?;
+    let cases = cases(ty).unwrap();
+    if cases[index].is_some() != payload.is_some() {
+        return Err(type_mismatch(ty, value));
+    }
+    Ok((index, payload))
+}
+
+/// Builds a variant-like value from the index of its case and its
+/// payload.
+fn make_case(
+    ty: &InterfaceType,
+    index: usize,
+    payload: Option<ComponentValue>,
+) -> Result<ComponentValue, RuntimeError> {
+    let payload = payload.map(Box::new);
+    Ok(match ty {
+        InterfaceType::Variant(cases) => match cases.get(index) {
+            Some((name, _)) => ComponentValue::Variant(name.clone(), payload),
+            None => return Err(error("invalid variant discriminant")),
+        },
+        InterfaceType::Enum(names) => match names.get(index) {
+            Some(name) => ComponentValue::Enum(name.clone()),
+            None => return Err(error("invalid enum discriminant")),
+        },
+        InterfaceType::Union(types) if index < types.len() => {
+            ComponentValue::Union(index as u32, payload.unwrap())
+        }
+        InterfaceType::Option(_) if index == 0 => ComponentValue::Option(None),
+        InterfaceType::Option(_) if index == 1 => ComponentValue::Option(payload),
+        InterfaceType::Result { .. } if index == 0 => ComponentValue::Result(Ok(payload)),
+        InterfaceType::Result { .. } if index == 1 => ComponentValue::Result(Err(payload)),
+        _ => return Err(error(format!("invalid discriminant for type `{}`", ty))),
+    })
+}
+
+fn flags_to_bits(names: &[String], set: &[String]) -> Result<Vec<u32>, RuntimeError> {
+    let mut bits = vec![0u32; (names.len() + 31) / 32];
+    for flag in set {
+        let index = names
+            .iter()
+            .position(|name| name == flag)
+            .ok_or_else(|| error(format!("unknown flag `{}`", flag)))?;
+        bits[index / 32] |= 1 << (index % 32);
+    }
+    Ok(bits)
+}
+
+fn bits_to_flags(names: &[String], bits: &[u32]) -> Vec<String> {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| bits[i / 32] & (1 << (i % 32)) != 0)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+fn char_from_u32(code: u32) -> Result<char, RuntimeError> {
+    char::from_u32(code).ok_or_else(|| error(format!("invalid char code point {:#x}", code)))
+}
+
+/// Converts a flat value of type `from` to type `to`, when reading
+/// the payload of a variant case from its joined representation.
+fn coerce_lift(value: Value, to: Type) -> Value {
+    match (value, to) {
+        (Value::I32(x), Type::F32) => Value::F32(f32::from_bits(x as u32)),
+        (Value::I64(x), Type::I32) => Value::I32(x as i32),
+        (Value::I64(x), Type::F32) => Value::F32(f32::from_bits(x as u32)),
+        (Value::I64(x), Type::F64) => Value::F64(f64::from_bits(x as u64)),
+        (value, _) => value,
+    }
+}
+
+/// Converts a flat value to type `to`, when writing the payload of a
+/// variant case to its joined representation.
+fn coerce_lower(value: Value, to: Type) -> Value {
+    match (value, to) {
+        (Value::F32(x), Type::I32) => Value::I32(x.to_bits() as i32),
+        (Value::I32(x), Type::I64) => Value::I64(x as u32 as i64),
+        (Value::F32(x), Type::I64) => Value::I64(x.to_bits() as i64),
+        (Value::F64(x), Type::I64) => Value::I64(x.to_bits() as i64),
+        (value, _) => value,
+    }
+}
+
+fn zero(ty: Type) -> Value {
+    match ty {
+        Type::I64 => Value::I64(0),
+        Type::F32 => Value::F32(0.0),
+        Type::F64 => Value::F64(0.0),
+        _ => Value::I32(0),
+    }
+}
+
+fn next(values: &mut dyn Iterator<Item = Value>) -> Result<Value, RuntimeError> {
+    values
+        .next()
+        .ok_or_else(|| error("not enough core values for the canonical ABI"))
+}
+
+fn next_i32(values: &mut dyn Iterator<Item = Value>) -> Result<i32, RuntimeError> {
+    match next(values)? {
+        Value::I32(x) => Ok(x),
+        _ => Err(error("expected an `i32` core value")),
+    }
+}
+
+fn next_i64(values: &mut dyn Iterator<Item = Value>) -> Result<i64, RuntimeError> {
+    match next(values)? {
+        Value::I64(x) => Ok(x),
+        _ => Err(error("expected an `i64` core value")),
+    }
+}
+
+/// Reads the flat representation of values from core values.
+pub(crate) struct Lifter<'a, S: AsStoreRef> {
+    pub(crate) store: &'a S,
+    pub(crate) options: &'a CanonicalOptions,
+}
+
+impl<'a, S: AsStoreRef> Lifter<'a, S> {
+    fn memory(&self) -> Result<&'a Memory, RuntimeError> {
+        self.options
+            .memory
+            .as_ref()
+            .ok_or_else(|| error("the canonical ABI requires a memory"))
+    }
+
+    fn read<const N: usize>(&self, ptr: u32) -> Result<[u8; N], RuntimeError> {
+        let mut bytes = [0; N];
+        self.memory()?
+            .view(self.store)
+            .read(ptr as u64, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Lifts a value of type `ty` from its flat representation.
+    pub(crate) fn lift_flat(
+        &self,
+        ty: &InterfaceType,
+        values: &mut dyn Iterator<Item = Value>,
+    ) -> Result<ComponentValue, RuntimeError> {
+        Ok(match ty {
+            InterfaceType::Bool => ComponentValue::Bool(next_i32(values)? != 0),
+            InterfaceType::S8 => ComponentValue::S8(next_i32(values)? as i8),
+            InterfaceType::U8 => ComponentValue::U8(next_i32(values)? as u8),
+            InterfaceType::S16 => ComponentValue::S16(next_i32(values)? as i16),
+            InterfaceType::U16 => ComponentValue::U16(next_i32(values)? as u16),
+            InterfaceType::S32 => ComponentValue::S32(next_i32(values)?),
+            InterfaceType::U32 => ComponentValue::U32(next_i32(values)? as u32),
+            InterfaceType::S64 => ComponentValue::S64(next_i64(values)?),
+            InterfaceType::U64 => ComponentValue::U64(next_i64(values)? as u64),
+            InterfaceType::Float32 => match next(values)? {
+                Value::F32(x) => ComponentValue::Float32(x),
+                _ => return Err(error("expected an `f32` core value")),
+            },
+            InterfaceType::Float64 => match next(values)? {
+                Value::F64(x) => ComponentValue::Float64(x),
+                _ => return Err(error("expected an `f64` core value")),
+            },
+            InterfaceType::Char => ComponentValue::Char(char_from_u32(next_i32(values)? as u32)?),
+            InterfaceType::String => {
+                let ptr = next_i32(values)? as u32;
+                let len = next_i32(values)? as u32;
+                ComponentValue::String(self.load_string(ptr, len)?)
+            }
+            InterfaceType::List(ty) => {
+                let ptr = next_i32(values)? as u32;
+                let len = next_i32(values)? as u32;
+                ComponentValue::List(self.load_list(ty, ptr, len)?)
+            }
+            InterfaceType::Record(fields) => ComponentValue::Record(
+                fields
+                    .iter()
+                    .map(|(name, ty)| Ok((name.clone(), self.lift_flat(ty, values)?)))
+                    .collect::<Result<_, RuntimeError>>()?,
+            ),
+            InterfaceType::Tuple(types) => ComponentValue::Tuple(
+                types
+                    .iter()
+                    .map(|ty| self.lift_flat(ty, values))
+                    .collect::<Result<_, _>>()?,
+            ),
+            InterfaceType::Flags(names) => {
+                let bits = (0..(names.len() + 31) / 32)
+                    .map(|_| Ok(next_i32(values)? as u32))
+                    .collect::<Result<Vec<_>, RuntimeError>>()?;
+                ComponentValue::Flags(bits_to_flags(names, &bits))
+            }
+            _ => {
+                let cases = cases(ty).unwrap();
+                let flat = flatten_variant(&cases);
+                let index = next_i32(values)? as u32 as usize;
+                let joined = flat[1..]
+                    .iter()
+                    .map(|_| next(values))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let payload = match cases.get(index) {
+                    Some(Some(case)) => {
+                        let mut case_flat = Vec::new();
+                        flatten(case, &mut case_flat);
+                        let mut case_values = joined
+                            .into_iter()
+                            .zip(case_flat)
+                            .map(|(value, ty)| coerce_lift(value, ty));
+                        Some(self.lift_flat(case, &mut case_values)?)
+                    }
+                    Some(None) => None,
+                    None => return Err(error(format!("invalid discriminant for `{}`", ty))),
+                };
+                make_case(ty, index, payload)?
+            }
+        })
+    }
+
+    fn load_string(&self, ptr: u32, len: u32) -> Result<String, RuntimeError> {
+        let view = self.memory()?.view(self.store);
+        if ptr as u64 + len as u64 > view.data_size() {
+            return Err(error("string is out of bounds"));
+        }
+        let mut bytes = vec![0; len as usize];
+        view.read(ptr as u64, &mut bytes)?;
+        String::from_utf8(bytes).map_err(|_| error("string is not valid UTF-8"))
+    }
+
+    fn load_list(
+        &self,
+        ty: &InterfaceType,
+        ptr: u32,
+        len: u32,
+    ) -> Result<Vec<ComponentValue>, RuntimeError> {
+        if ptr % align(ty) != 0 {
+            return Err(error("unaligned list pointer"));
+        }
+        let elem_size = size(ty) as u64;
+        let memory_size = self.memory()?.view(self.store).data_size();
+        // Zero-sized elements take no memory, but still have to be
+        // allocated by the host, so they are bounded as if they took a byte
+        if ptr as u64 + len as u64 * elem_size.max(1) > memory_size {
+            return Err(error("list is out of bounds"));
+        }
+        (0..len)
+            .map(|i| self.load(ty, ptr + i * elem_size as u32))
+            .collect()
+    }
+
+    /// Loads a value of type `ty` from linear memory.
+    pub(crate) fn load(
+        &self,
+        ty: &InterfaceType,
+        ptr: u32,
+    ) -> Result<ComponentValue, RuntimeError> {
+        if ptr % align(ty) != 0 {
+            return Err(error("unaligned pointer"));
+        }
+        Ok(match ty {
+            InterfaceType::Bool => ComponentValue::Bool(self.read::<1>(ptr)?[0] != 0),
+            InterfaceType::S8 => ComponentValue::S8(self.read::<1>(ptr)?[0] as i8),
+            InterfaceType::U8 => ComponentValue::U8(self.read::<1>(ptr)?[0]),
+            InterfaceType::S16 => ComponentValue::S16(i16::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::U16 => ComponentValue::U16(u16::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::S32 => ComponentValue::S32(i32::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::U32 => ComponentValue::U32(u32::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::S64 => ComponentValue::S64(i64::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::U64 => ComponentValue::U64(u64::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::Float32 => ComponentValue::Float32(f32::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::Float64 => ComponentValue::Float64(f64::from_le_bytes(self.read(ptr)?)),
+            InterfaceType::Char => {
+                ComponentValue::Char(char_from_u32(u32::from_le_bytes(self.read(ptr)?))?)
+            }
+            InterfaceType::String => {
+                let data = u32::from_le_bytes(self.read(ptr)?);
+                let len = u32::from_le_bytes(self.read(ptr + 4)?);
+                ComponentValue::String(self.load_string(data, len)?)
+            }
+            InterfaceType::List(ty) => {
+                let data = u32::from_le_bytes(self.read(ptr)?);
+                let len = u32::from_le_bytes(self.read(ptr + 4)?);
+                ComponentValue::List(self.load_list(ty, data, len)?)
+            }
+            InterfaceType::Record(fields) => {
+                let offsets = tuple_offsets(fields.iter().map(|(_, ty)| ty));
+                ComponentValue::Record(
+                    fields
+                        .iter()
+                        .zip(offsets)
+                        .map(|((name, ty), offset)| {
+                            Ok((name.clone(), self.load(ty, ptr + offset)?))
+                        })
+                        .collect::<Result<_, RuntimeError>>()?,
+                )
+            }
+            InterfaceType::Tuple(types) => {
+                let offsets = tuple_offsets(types.iter());
+                ComponentValue::Tuple(
+                    types
+                        .iter()
+                        .zip(offsets)
+                        .map(|(ty, offset)| self.load(ty, ptr + offset))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            InterfaceType::Flags(names) => {
+                let bits = match flags_size(names.len()) {
+                    0 => vec![],
+                    1 => vec![self.read::<1>(ptr)?[0] as u32],
+                    2 => vec![u16::from_le_bytes(self.read(ptr)?) as u32],
+                    size => (0..size / 4)
+                        .map(|i| Ok(u32::from_le_bytes(self.read(ptr + 4 * i)?)))
+                        .collect::<Result<_, RuntimeError>>()?,
+                };
+                ComponentValue::Flags(bits_to_flags(names, &bits))
+            }
+            _ => {
+                let cases = cases(ty).unwrap();
+                let disc_size = discriminant_size(cases.len());
+                let index = match disc_size {
+                    1 => self.read::<1>(ptr)?[0] as usize,
+                    2 => u16::from_le_bytes(self.read(ptr)?) as usize,
+                    _ => u32::from_le_bytes(self.read(ptr)?) as usize,
+                };
+                let payload_offset = align_to(disc_size, max_case_align(&cases));
+                let payload = match cases.get(index) {
+                    Some(Some(case)) => Some(self.load(case, ptr + payload_offset)?),
+                    Some(None) => None,
+                    None => return Err(error(format!("invalid discriminant for `{}`", ty))),
+                };
+                make_case(ty, index, payload)?
+            }
+        })
+    }
+}
+
+/// Writes the flat representation of values to core values.
+pub(crate) struct Lowerer<'a, S: AsStoreMut> {
+    pub(crate) store: &'a mut S,
+    pub(crate) options: &'a CanonicalOptions,
+}
+
+impl<'a, S: AsStoreMut> Lowerer<'a, S> {
+    fn memory(&self) -> Result<&'a Memory, RuntimeError> {
+        self.options
+            .memory
+            .as_ref()
+            .ok_or_else(|| error("the canonical ABI requires a memory"))
+    }
+
+    fn write(&mut self, ptr: u32, bytes: &[u8]) -> Result<(), RuntimeError> {
+        self.memory()?.view(&*self.store).write(ptr as u64, bytes)?;
+        Ok(())
+    }
+
+    /// Allocates `size` bytes aligned to `align` in the guest's
+    /// memory, with the `realloc` function of the canonical options.
+    pub(crate) fn realloc(&mut self, align: u32, size: u32) -> Result<u32, RuntimeError> {
+        let realloc = self
+            .options
+            .realloc
+            .as_ref()
+            .ok_or_else(|| error("the canonical ABI requires a `realloc` function"))?;
+        let ptr = match *realloc.call(
+            self.store,
+            &[
+                Value::I32(0),
+                Value::I32(0),
+                Value::I32(align as i32),
+                Value::I32(size as i32),
+            ],
+        )? {
+            [Value::I32(ptr)] => ptr as u32,
+            _ => return Err(error("`realloc` must return an `i32`")),
+        };
+        if ptr % align != 0 {
+            return Err(error("`realloc` returned an unaligned pointer"));
+        }
+        let memory_size = self.memory()?.view(&*self.store).data_size();
+        if ptr as u64 + size as u64 > memory_size {
+            return Err(error("`realloc` returned an out of bounds pointer"));
+        }
+        Ok(ptr)
+    }
+
+    fn store_string(&mut self, string: &str) -> Result<(u32, u32), RuntimeError> {
+        let len: u32 = string
+            .len()
+            .try_into()
+            .map_err(|_| error("string is too long"))?;
+        let ptr = self.realloc(1, len)?;
+        self.write(ptr, string.as_bytes())?;
+        Ok((ptr, len))
+    }
+
+    fn store_list(
+        &mut self,
+        ty: &InterfaceType,
+        values: &[ComponentValue],
+    ) -> Result<(u32, u32), RuntimeError> {
+        let elem_size = size(ty);
+        let len: u32 = values
+            .len()
+            .try_into()
+            .map_err(|_| error("list is too long"))?;
+        let byte_len = elem_size
+            .checked_mul(len)
+            .ok_or_else(|| error("list is too long"))?;
+        let ptr = self.realloc(align(ty), byte_len)?;
+        for (i, value) in values.iter().enumerate() {
+            self.store(ty, value, ptr + i as u32 * elem_size)?;
+        }
+        Ok((ptr, len))
+    }
+
+    /// Appends the flat representation of `value` to `out`.
+    pub(crate) fn lower_flat(
+        &mut self,
+        ty: &InterfaceType,
+        value: &ComponentValue,
+        out: &mut Vec<Value>,
+    ) -> Result<(), RuntimeError> {
+        match (ty, value) {
+            (InterfaceType::Bool, ComponentValue::Bool(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::S8, ComponentValue::S8(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::U8, ComponentValue::U8(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::S16, ComponentValue::S16(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::U16, ComponentValue::U16(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::S32, ComponentValue::S32(x)) => out.push(Value::I32(*x)),
+            (InterfaceType::U32, ComponentValue::U32(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::S64, ComponentValue::S64(x)) => out.push(Value::I64(*x)),
+            (InterfaceType::U64, ComponentValue::U64(x)) => out.push(Value::I64(*x as i64)),
+            (InterfaceType::Float32, ComponentValue::Float32(x)) => out.push(Value::F32(*x)),
+            (InterfaceType::Float64, ComponentValue::Float64(x)) => out.push(Value::F64(*x)),
+            (InterfaceType::Char, ComponentValue::Char(x)) => out.push(Value::I32(*x as i32)),
+            (InterfaceType::String, ComponentValue::String(x)) => {
+                let (ptr, len) = self.store_string(x)?;
+                out.extend([Value::I32(ptr as i32), Value::I32(len as i32)]);
+            }
+            (InterfaceType::List(ty), ComponentValue::List(x)) => {
+                let (ptr, len) = self.store_list(ty, x)?;
+                out.extend([Value::I32(ptr as i32), Value::I32(len as i32)]);
+            }
+            (InterfaceType::Record(fields), ComponentValue::Record(values))
+                if fields.len() == values.len()
+                    && fields.iter().zip(values).all(|((a, _), (b, _))| a == b) =>
+            {
+                for ((_, ty), (_, value)) in fields.iter().zip(values) {
+                    self.lower_flat(ty, value, out)?;
+                }
+            }
+            (InterfaceType::Tuple(types), ComponentValue::Tuple(values))
+                if types.len() == values.len() =>
+            {
+                for (ty, value) in types.iter().zip(values) {
+                    self.lower_flat(ty, value, out)?;
+                }
+            }
+            (InterfaceType::Flags(names), ComponentValue::Flags(set)) => {
+                let bits = flags_to_bits(names, set)?;
+                out.extend(bits.into_iter().map(|bits| Value::I32(bits as i32)));
+            }
+            (InterfaceType::Variant(_), _)
+            | (InterfaceType::Enum(_), _)
+            | (InterfaceType::Union(_), _)
+            | (InterfaceType::Option(_), _)
+            | (InterfaceType::Result { .. }, _) => {
+                let (index, payload) = case_of(ty, value)?;
+                let cases = cases(ty).unwrap();
+                let flat = flatten_variant(&cases);
+                out.push(Value::I32(index as i32));
+                let mut payload_values = Vec::new();
+                if let (Some(case), Some(payload)) = (cases[index], payload) {
+                    self.lower_flat(case, payload, &mut payload_values)?;
+                }
+                let mut payload_values = payload_values.into_iter();
+                for ty in &flat[1..] {
+                    out.push(match payload_values.next() {
+                        Some(value) => coerce_lower(value, *ty),
+                        None => zero(*ty),
+                    });
+                }
+            }
+            _ => return Err(type_mismatch(ty, value)),
+        }
+        Ok(())
+    }
+
+    /// Stores `value` of type `ty` in linear memory.
+    pub(crate) fn store(
+        &mut self,
+        ty: &InterfaceType,
+        value: &ComponentValue,
+        ptr: u32,
+    ) -> Result<(), RuntimeError> {
+        if ptr % align(ty) != 0 {
+            return Err(error("unaligned pointer"));
+        }
+        match (ty, value) {
+            (InterfaceType::Bool, ComponentValue::Bool(x)) => self.write(ptr, &[*x as u8]),
+            (InterfaceType::S8, ComponentValue::S8(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::U8, ComponentValue::U8(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::S16, ComponentValue::S16(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::U16, ComponentValue::U16(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::S32, ComponentValue::S32(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::U32, ComponentValue::U32(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::S64, ComponentValue::S64(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::U64, ComponentValue::U64(x)) => self.write(ptr, &x.to_le_bytes()),
+            (InterfaceType::Float32, ComponentValue::Float32(x)) => {
+                self.write(ptr, &x.to_le_bytes())
+            }
+            (InterfaceType::Float64, ComponentValue::Float64(x)) => {
+                self.write(ptr, &x.to_le_bytes())
+            }
+            (InterfaceType::Char, ComponentValue::Char(x)) => {
+                self.write(ptr, &(*x as u32).to_le_bytes())
+            }
+            (InterfaceType::String, ComponentValue::String(x)) => {
+                let (data, len) = self.store_string(x)?;
+                self.write(ptr, &data.to_le_bytes())?;
+                self.write(ptr + 4, &len.to_le_bytes())
+            }
+            (InterfaceType::List(ty), ComponentValue::List(x)) => {
+                let (data, len) = self.store_list(ty, x)?;
+                self.write(ptr, &data.to_le_bytes())?;
+                self.write(ptr + 4, &len.to_le_bytes())
+            }
+            (InterfaceType::Record(fields), ComponentValue::Record(values))
+                if fields.len() == values.len()
+                    && fields.iter().zip(values).all(|((a, _), (b, _))| a == b) =>
+            {
+                let offsets = tuple_offsets(fields.iter().map(|(_, ty)| ty));
+                for (((_, ty), (_, value)), offset) in fields.iter().zip(values).zip(offsets) {
+                    self.store(ty, value, ptr + offset)?;
+                }
+                Ok(())
+            }
+            (InterfaceType::Tuple(types), ComponentValue::Tuple(values))
+                if types.len() == values.len() =>
+            {
+                let offsets = tuple_offsets(types.iter());
+                for ((ty, value), offset) in types.iter().zip(values).zip(offsets) {
+                    self.store(ty, value, ptr + offset)?;
+                }
+                Ok(())
+            }
+            (InterfaceType::Flags(names), ComponentValue::Flags(set)) => {
+                let bits = flags_to_bits(names, set)?;
+                match flags_size(names.len()) {
+                    0 => Ok(()),
+                    1 => self.write(ptr, &[bits[0] as u8]),
+                    2 => self.write(ptr, &(bits[0] as u16).to_le_bytes()),
+                    _ => {
+                        for (i, bits) in bits.into_iter().enumerate() {
+                            self.write(ptr + 4 * i as u32, &bits.to_le_bytes())?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            (InterfaceType::Variant(_), _)
+            | (InterfaceType::Enum(_), _)
+            | (InterfaceType::Union(_), _)
+            | (InterfaceType::Option(_), _)
+            | (InterfaceType::Result { .. }, _) => {
+                let (index, payload) = case_of(ty, value)?;
+                let cases = cases(ty).unwrap();
+                let disc_size = discriminant_size(cases.len());
+                match disc_size {
+                    1 => self.write(ptr, &[index as u8])?,
+                    2 => self.write(ptr, &(index as u16).to_le_bytes())?,
+                    _ => self.write(ptr, &(index as u32).to_le_bytes())?,
+                }
+                if let (Some(case), Some(payload)) = (cases[index], payload) {
+                    let offset = align_to(disc_size, max_case_align(&cases));
+                    self.store(case, payload, ptr + offset)?;
+                }
+                Ok(())
+            }
+            _ => Err(type_mismatch(ty, value)),
+        }
+    }
+}
+
+fn check_arity(expected: usize, found: usize, what: &str) -> Result<(), RuntimeError> {
+    if expected != found {
+        return Err(error(format!(
+            "expected {} {}, found {}",
+            expected, what, found
+        )));
+    }
+    Ok(())
+}
+
+/// Calls the core function of a lifted component function, i.e.
+/// lowers `params`, calls `func` and lifts its results.
+pub(crate) fn call_lifted(
+    store: &mut impl AsStoreMut,
+    func: &Function,
+    ty: &ComponentFuncType,
+    options: &CanonicalOptions,
+    params: &[ComponentValue],
+) -> Result<Vec<ComponentValue>, RuntimeError> {
+    check_arity(ty.params().len(), params.len(), "parameters")?;
+
+    let mut lowerer = Lowerer {
+        store: &mut *store,
+        options,
+    };
+    let mut core_params = Vec::new();
+    if flatten_all(ty.param_types()).len() > MAX_FLAT_PARAMS {
+        let tuple = InterfaceType::Tuple(ty.param_types().cloned().collect());
+        let ptr = lowerer.realloc(align(&tuple), size(&tuple))?;
+        let offsets = tuple_offsets(ty.param_types());
+        for ((ty, value), offset) in ty.param_types().zip(params).zip(offsets) {
+            lowerer.store(ty, value, ptr + offset)?;
+        }
+        core_params.push(Value::I32(ptr as i32));
+    } else {
+        for (ty, value) in ty.param_types().zip(params) {
+            lowerer.lower_flat(ty, value, &mut core_params)?;
+        }
+    }
+
+    let core_results = func.call(store, &core_params)?;
+
+    let lifter = Lifter {
+        store: &*store,
+        options,
+    };
+    let results = if flatten_all(ty.result_types()).len() > MAX_FLAT_RESULTS {
+        let ptr = match *core_results {
+            [Value::I32(ptr)] => ptr as u32,
+            _ => return Err(error("expected a pointer to the results")),
+        };
+        let offsets = tuple_offsets(ty.result_types());
+        ty.result_types()
+            .zip(offsets)
+            .map(|(ty, offset)| lifter.load(ty, ptr + offset))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        let mut values = core_results.iter().cloned();
+        ty.result_types()
+            .map(|ty| lifter.lift_flat(ty, &mut values))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    if let Some(post_return) = &options.post_return {
+        post_return.call(store, &core_results)?;
+    }
+
+    Ok(results)
+}
+
+/// Lifts the parameters of a lowered component function from the
+/// core values it was called with.
+pub(crate) fn lift_params(
+    store: &impl AsStoreRef,
+    ty: &ComponentFuncType,
+    options: &CanonicalOptions,
+    core_params: &[Value],
+) -> Result<Vec<ComponentValue>, RuntimeError> {
+    let lifter = Lifter { store, options };
+    if flatten_all(ty.param_types()).len() > MAX_FLAT_PARAMS {
+        let ptr = match core_params.first() {
+            Some(Value::I32(ptr)) => *ptr as u32,
+            _ => return Err(error("expected a pointer to the parameters")),
+        };
+        let offsets = tuple_offsets(ty.param_types());
+        ty.param_types()
+            .zip(offsets)
+            .map(|(ty, offset)| lifter.load(ty, ptr + offset))
+            .collect()
+    } else {
+        let mut values = core_params.iter().cloned();
+        ty.param_types()
+            .map(|ty| lifter.lift_flat(ty, &mut values))
+            .collect()
+    }
+}
+
+/// Lowers the results of a lowered component function to the core
+/// values it returns.
+pub(crate) fn lower_results(
+    store: &mut impl AsStoreMut,
+    ty: &ComponentFuncType,
+    options: &CanonicalOptions,
+    core_params: &[Value],
+    results: &[ComponentValue],
+) -> Result<Vec<Value>, RuntimeError> {
+    check_arity(ty.results().len(), results.len(), "results")?;
+
+    let mut lowerer = Lowerer { store, options };
+    let mut core_results = Vec::new();
+    if flatten_all(ty.result_types()).len() > MAX_FLAT_RESULTS {
+        let ptr = match core_params.last() {
+            Some(Value::I32(ptr)) => *ptr as u32,
+            _ => return Err(error("expected a pointer to store the results")),
+        };
+        let offsets = tuple_offsets(ty.result_types());
+        for ((ty, value), offset) in ty.result_types().zip(results).zip(offsets) {
+            lowerer.store(ty, value, ptr + offset)?;
+        }
+    } else {
+        for (ty, value) in ty.result_types().zip(results) {
+            lowerer.lower_flat(ty, value, &mut core_results)?;
+        }
+    }
+    Ok(core_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let record = InterfaceType::Record(vec![
+            ("a".to_string(), InterfaceType::U8),
+            ("b".to_string(), InterfaceType::U64),
+            ("c".to_string(), InterfaceType::U16),
+        ]);
+        assert_eq!(align(&record), 8);
+        assert_eq!(size(&record), 24);
+
+        let option = InterfaceType::Option(Box::new(InterfaceType::String));
+        assert_eq!(align(&option), 4);
+        assert_eq!(size(&option), 12);
+
+        let flags = InterfaceType::Flags((0..40).map(|i| i.to_string()).collect());
+        assert_eq!(align(&flags), 4);
+        assert_eq!(size(&flags), 8);
+
+        let result = InterfaceType::Result {
+            ok: Some(Box::new(InterfaceType::Float32)),
+            err: Some(Box::new(InterfaceType::U64)),
+        };
+        let mut flat = Vec::new();
+        flatten(&result, &mut flat);
+        assert_eq!(flat, vec![Type::I32, Type::I64]);
+        assert_eq!(size(&result), 16);
+    }
+}
@@ -0,0 +1,724 @@
+use super::types::{ComponentExternType, ComponentFuncType, ComponentInstanceType, InterfaceType};
+use crate::sys::store::AsStoreRef;
+use crate::sys::Module;
+use std::sync::Arc;
+use wasmer_types::{CompileError, WasmError};
+use wasmparser_components as wp;
+
+/// A WebAssembly component.
+///
+/// A component is made of core WebAssembly modules, and describes how
+/// to instantiate and link them together, and how to lift their
+/// functions to (and lower imported functions from) the interface
+/// types of the component model, see [`InterfaceType`].
+///
+/// Components are cheap to clone: the core modules are compiled once,
+/// when the component is created, and shared by all the instances of
+/// the component.
+///
+/// Only the subset of the component model needed to link core modules
+/// against host functions is supported: nested components, value
+/// imports and exports, start functions and string encodings other
+/// than UTF-8 are rejected when the component is created, and
+/// resources can't be parsed (see the [module
+/// documentation](crate::component#limitations)).
+#[derive(Clone)]
+pub struct Component {
+    pub(crate) inner: Arc<ComponentInner>,
+}
+
+pub(crate) struct ComponentInner {
+    pub(crate) modules: Vec<Module>,
+    pub(crate) initializers: Vec<Initializer>,
+    pub(crate) imports: Vec<(String, ComponentExternType)>,
+    pub(crate) exports: Vec<(String, Export, ComponentExternType)>,
+}
+
+/// A sort of core WebAssembly item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CoreSort {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+/// The canonical options of a `canon lift` or `canon lower`, as
+/// indices in the core index spaces.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CanonicalOptionIndices {
+    pub(crate) memory: Option<u32>,
+    pub(crate) realloc: Option<u32>,
+    pub(crate) post_return: Option<u32>,
+}
+
+/// A step of the instantiation of a component. Each initializer
+/// appends an item to one of the index spaces of the component.
+#[derive(Debug, Clone)]
+pub(crate) enum Initializer {
+    /// Instantiates a core module, with core instances as arguments.
+    CoreInstantiate {
+        module: u32,
+        args: Vec<(String, u32)>,
+    },
+    /// Creates a core instance from core items.
+    CoreInstanceFromExports(Vec<(String, CoreSort, u32)>),
+    /// Aliases the export of a core instance.
+    CoreAlias {
+        sort: CoreSort,
+        instance: u32,
+        name: String,
+    },
+    /// Imports a function.
+    ImportFunc { name: String, ty: ComponentFuncType },
+    /// Imports an instance.
+    ImportInstance {
+        name: String,
+        ty: ComponentInstanceType,
+    },
+    /// Aliases a function exported by an instance.
+    AliasFunc { instance: u32, name: String },
+    /// Aliases an instance exported by an instance.
+    AliasInstance { instance: u32, name: String },
+    /// Lifts a core function to a component function.
+    Lift {
+        core_func: u32,
+        ty: ComponentFuncType,
+        options: CanonicalOptionIndices,
+    },
+    /// Lowers a component function to a core function.
+    Lower {
+        func: u32,
+        ty: ComponentFuncType,
+        options: CanonicalOptionIndices,
+    },
+    /// Creates an instance from functions and instances.
+    InstanceFromExports(Vec<(String, Export)>),
+}
+
+/// An item exported by a component or by an instance created from
+/// exports.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Export {
+    Func(u32),
+    Instance(u32),
+}
+
+/// A type in the type index space of a component or instance type.
+#[derive(Debug, Clone)]
+enum TypeDef {
+    Value(InterfaceType),
+    Func(ComponentFuncType),
+    Instance(ComponentInstanceType),
+    /// A component or core type, which can't be used by the supported
+    /// subset of the component model.
+    Other,
+}
+
+fn unsupported(what: impl Into<String>) -> CompileError {
+    CompileError::UnsupportedFeature(what.into())
+}
+
+fn invalid(what: impl Into<String>) -> CompileError {
+    CompileError::Validate(what.into())
+}
+
+fn get<T: Clone>(items: &[T], index: u32, what: &str) -> Result<T, CompileError> {
+    items
+        .get(index as usize)
+        .cloned()
+        .ok_or_else(|| invalid(format!("unknown {} {}", what, index)))
+}
+
+fn primitive(ty: wp::PrimitiveValType) -> InterfaceType {
+    match ty {
+        wp::PrimitiveValType::Bool => InterfaceType::Bool,
+        wp::PrimitiveValType::S8 => InterfaceType::S8,
+        wp::PrimitiveValType::U8 => InterfaceType::U8,
+        wp::PrimitiveValType::S16 => InterfaceType::S16,
+        wp::PrimitiveValType::U16 => InterfaceType::U16,
+        wp::PrimitiveValType::S32 => InterfaceType::S32,
+        wp::PrimitiveValType::U32 => InterfaceType::U32,
+        wp::PrimitiveValType::S64 => InterfaceType::S64,
+        wp::PrimitiveValType::U64 => InterfaceType::U64,
+        wp::PrimitiveValType::Float32 => InterfaceType::Float32,
+        wp::PrimitiveValType::Float64 => InterfaceType::Float64,
+        wp::PrimitiveValType::Char => InterfaceType::Char,
+        wp::PrimitiveValType::String => InterfaceType::String,
+    }
+}
+
+/// Resolves types against a type index space.
+struct TypeResolver<'a> {
+    types: &'a [TypeDef],
+}
+
+impl TypeResolver<'_> {
+    fn val(&self, ty: &wp::ComponentValType) -> Result<InterfaceType, CompileError> {
+        match ty {
+            wp::ComponentValType::Primitive(ty) => Ok(primitive(*ty)),
+            wp::ComponentValType::Type(index) => match get(self.types, *index, "type")? {
+                TypeDef::Value(ty) => Ok(ty),
+                _ => Err(invalid(format!("type {} is not a value type", index))),
+            },
+        }
+    }
+
+    fn boxed(&self, ty: &wp::ComponentValType) -> Result<Box<InterfaceType>, CompileError> {
+        self.val(ty).map(Box::new)
+    }
+
+    fn defined(&self, ty: &wp::ComponentDefinedType) -> Result<InterfaceType, CompileError> {
+        Ok(match ty {
+            wp::ComponentDefinedType::Primitive(ty) => primitive(*ty),
+            wp::ComponentDefinedType::Record(fields) => InterfaceType::Record(
+                fields
+                    .iter()
+                    .map(|(name, ty)| Ok((name.to_string(), self.val(ty)?)))
+                    .collect::<Result<_, CompileError>>()?,
+            ),
+            wp::ComponentDefinedType::Variant(cases) => InterfaceType::Variant(
+                cases
+                    .iter()
+                    .map(|case| {
+                        let ty = case.ty.as_ref().map(|ty| self.val(ty)).transpose()?;
+                        Ok((case.name.to_string(), ty))
+                    })
+                    .collect::<Result<_, CompileError>>()?,
+            ),
+            wp::ComponentDefinedType::List(ty) => InterfaceType::List(self.boxed(ty)?),
+            wp::ComponentDefinedType::Tuple(types) => InterfaceType::Tuple(
+                types
+                    .iter()
+                    .map(|ty| self.val(ty))
+                    .collect::<Result<_, _>>()?,
+            ),
+            wp::ComponentDefinedType::Flags(names) => {
+                InterfaceType::Flags(names.iter().map(|name| name.to_string()).collect())
+            }
+            wp::ComponentDefinedType::Enum(names) => {
+                InterfaceType::Enum(names.iter().map(|name| name.to_string()).collect())
+            }
+            wp::ComponentDefinedType::Union(types) => InterfaceType::Union(
+                types
+                    .iter()
+                    .map(|ty| self.val(ty))
+                    .collect::<Result<_, _>>()?,
+            ),
+            wp::ComponentDefinedType::Option(ty) => InterfaceType::Option(self.boxed(ty)?),
+            wp::ComponentDefinedType::Result { ok, err } => InterfaceType::Result {
+                ok: ok.as_ref().map(|ty| self.boxed(ty)).transpose()?,
+                err: err.as_ref().map(|ty| self.boxed(ty)).transpose()?,
+            },
+        })
+    }
+
+    fn type_vec(&self, types: &wp::TypeVec) -> Result<Vec<(String, InterfaceType)>, CompileError> {
+        types
+            .iter()
+            .map(|(name, ty)| Ok((name.unwrap_or("").to_string(), self.val(ty)?)))
+            .collect()
+    }
+
+    fn func(&self, ty: &wp::ComponentFuncType) -> Result<ComponentFuncType, CompileError> {
+        Ok(ComponentFuncType::new(
+            self.type_vec(&ty.params)?,
+            self.type_vec(&ty.results)?,
+        ))
+    }
+
+    fn func_at(&self, index: u32) -> Result<ComponentFuncType, CompileError> {
+        match get(self.types, index, "type")? {
+            TypeDef::Func(ty) => Ok(ty),
+            _ => Err(invalid(format!("type {} is not a function type", index))),
+        }
+    }
+
+    fn instance_at(&self, index: u32) -> Result<ComponentInstanceType, CompileError> {
+        match get(self.types, index, "type")? {
+            TypeDef::Instance(ty) => Ok(ty),
+            _ => Err(invalid(format!("type {} is not an instance type", index))),
+        }
+    }
+
+    /// Resolves a type definition. `outer` is the type index space of
+    /// the enclosing component, if the type is declared in an
+    /// instance type.
+    fn component_type(
+        &self,
+        ty: &wp::ComponentType,
+        outer: Option<&[TypeDef]>,
+    ) -> Result<TypeDef, CompileError> {
+        Ok(match ty {
+            wp::ComponentType::Defined(ty) => TypeDef::Value(self.defined(ty)?),
+            wp::ComponentType::Func(ty) => TypeDef::Func(self.func(ty)?),
+            wp::ComponentType::Instance(decls) => {
+                TypeDef::Instance(instance_type(decls, outer.unwrap_or(self.types))?)
+            }
+            wp::ComponentType::Component(_) => TypeDef::Other,
+        })
+    }
+}
+
+/// Resolves the declarations of an instance type. `outer` is the type
+/// index space of the component declaring the instance type.
+fn instance_type(
+    decls: &[wp::InstanceTypeDeclaration],
+    outer: &[TypeDef],
+) -> Result<ComponentInstanceType, CompileError> {
+    let mut types = Vec::new();
+    let mut exports = Vec::new();
+    for decl in decls.iter() {
+        match decl {
+            wp::InstanceTypeDeclaration::CoreType(_) => {}
+            wp::InstanceTypeDeclaration::Type(ty) => {
+                let ty = TypeResolver { types: &types }.component_type(ty, Some(outer))?;
+                types.push(ty);
+            }
+            wp::InstanceTypeDeclaration::Alias(wp::ComponentAlias::Outer {
+                kind: wp::ComponentOuterAliasKind::Type,
+                count,
+                index,
+            }) => match count {
+                0 => types.push(get(&types, *index, "type")?),
+                1 => types.push(get(outer, *index, "type")?),
+                _ => return Err(unsupported("outer aliases of nested components")),
+            },
+            wp::InstanceTypeDeclaration::Alias(_) => {
+                return Err(unsupported("aliases in instance types"))
+            }
+            wp::InstanceTypeDeclaration::Export { name, ty } => {
+                let resolver = TypeResolver { types: &types };
+                match ty {
+                    wp::ComponentTypeRef::Func(index) => exports.push((
+                        name.to_string(),
+                        ComponentExternType::Func(resolver.func_at(*index)?),
+                    )),
+                    wp::ComponentTypeRef::Instance(index) => exports.push((
+                        name.to_string(),
+                        ComponentExternType::Instance(resolver.instance_at(*index)?),
+                    )),
+                    wp::ComponentTypeRef::Type(_, index) => {
+                        types.push(get(&types, *index, "type")?)
+                    }
+                    _ => {
+                        return Err(unsupported(format!(
+                            "exports of modules, components or values (`{}`) in instance types",
+                            name
+                        )))
+                    }
+                }
+            }
+        }
+    }
+    Ok(ComponentInstanceType::new(exports))
+}
+
+fn core_sort(kind: wp::ExternalKind) -> Result<CoreSort, CompileError> {
+    Ok(match kind {
+        wp::ExternalKind::Func => CoreSort::Func,
+        wp::ExternalKind::Table => CoreSort::Table,
+        wp::ExternalKind::Memory => CoreSort::Memory,
+        wp::ExternalKind::Global => CoreSort::Global,
+        wp::ExternalKind::Tag => return Err(unsupported("exception handling")),
+    })
+}
+
+fn canonical_options(
+    options: &[wp::CanonicalOption],
+) -> Result<CanonicalOptionIndices, CompileError> {
+    let mut indices = CanonicalOptionIndices::default();
+    for option in options.iter() {
+        match option {
+            wp::CanonicalOption::UTF8 => {}
+            wp::CanonicalOption::UTF16 | wp::CanonicalOption::CompactUTF16 => {
+                return Err(unsupported("string encodings other than UTF-8"))
+            }
+            wp::CanonicalOption::Memory(index) => indices.memory = Some(*index),
+            wp::CanonicalOption::Realloc(index) => indices.realloc = Some(*index),
+            wp::CanonicalOption::PostReturn(index) => indices.post_return = Some(*index),
+        }
+    }
+    Ok(indices)
+}
+
+/// The state of the translation of a component, i.e. the static view
+/// of its index spaces.
+#[derive(Default)]
+struct Translation {
+    modules: Vec<Module>,
+    initializers: Vec<Initializer>,
+    imports: Vec<(String, ComponentExternType)>,
+    exports: Vec<(String, Export, ComponentExternType)>,
+    types: Vec<TypeDef>,
+    funcs: Vec<ComponentFuncType>,
+    instances: Vec<ComponentInstanceType>,
+}
+
+impl Translation {
+    fn resolver(&self) -> TypeResolver<'_> {
+        TypeResolver { types: &self.types }
+    }
+
+    fn extern_type(&self, export: Export) -> Result<ComponentExternType, CompileError> {
+        Ok(match export {
+            Export::Func(index) => ComponentExternType::Func(get(&self.funcs, index, "function")?),
+            Export::Instance(index) => {
+                ComponentExternType::Instance(get(&self.instances, index, "instance")?)
+            }
+        })
+    }
+
+    fn export(&self, kind: wp::ComponentExternalKind, index: u32) -> Result<Export, CompileError> {
+        match kind {
+            wp::ComponentExternalKind::Func => Ok(Export::Func(index)),
+            wp::ComponentExternalKind::Instance => Ok(Export::Instance(index)),
+            _ => Err(unsupported(
+                "exports of modules, components, values or types from instances",
+            )),
+        }
+    }
+
+    fn import(&mut self, import: wp::ComponentImport) -> Result<(), CompileError> {
+        let name = import.name.to_string();
+        match import.ty {
+            wp::ComponentTypeRef::Func(index) => {
+                let ty = self.resolver().func_at(index)?;
+                self.funcs.push(ty.clone());
+                self.imports
+                    .push((name.clone(), ComponentExternType::Func(ty.clone())));
+                self.initializers.push(Initializer::ImportFunc { name, ty });
+            }
+            wp::ComponentTypeRef::Instance(index) => {
+                let ty = self.resolver().instance_at(index)?;
+                self.instances.push(ty.clone());
+                self.imports
+                    .push((name.clone(), ComponentExternType::Instance(ty.clone())));
+                self.initializers
+                    .push(Initializer::ImportInstance { name, ty });
+            }
+            wp::ComponentTypeRef::Type(_, index) => {
+                let ty = get(&self.types, index, "type")?;
+                self.types.push(ty);
+            }
+            wp::ComponentTypeRef::Module(_)
+            | wp::ComponentTypeRef::Component(_)
+            | wp::ComponentTypeRef::Value(_) => {
+                return Err(unsupported(format!(
+                    "imports of modules, components or values (`{}`)",
+                    name
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn alias(&mut self, alias: wp::ComponentAlias) -> Result<(), CompileError> {
+        match alias {
+            wp::ComponentAlias::InstanceExport {
+                kind,
+                instance_index,
+                name,
+            } => {
+                let instance = get(&self.instances, instance_index, "instance")?;
+                let ty = instance.get(name).cloned();
+                match (kind, ty) {
+                    (wp::ComponentExternalKind::Func, Some(ComponentExternType::Func(ty))) => {
+                        self.funcs.push(ty);
+                        self.initializers.push(Initializer::AliasFunc {
+                            instance: instance_index,
+                            name: name.to_string(),
+                        });
+                    }
+                    (
+                        wp::ComponentExternalKind::Instance,
+                        Some(ComponentExternType::Instance(ty)),
+                    ) => {
+                        self.instances.push(ty);
+                        self.initializers.push(Initializer::AliasInstance {
+                            instance: instance_index,
+                            name: name.to_string(),
+                        });
+                    }
+                    (wp::ComponentExternalKind::Type, _) => {
+                        // The types exported by instances are only used
+                        // by the type checker of the bindings generators.
+                        self.types.push(TypeDef::Other);
+                    }
+                    _ => {
+                        return Err(unsupported(format!(
+                            "alias of export `{}` of instance {}",
+                            name, instance_index
+                        )))
+                    }
+                }
+            }
+            wp::ComponentAlias::Outer {
+                kind: wp::ComponentOuterAliasKind::Type,
+                count: 0,
+                index,
+            } => {
+                let ty = get(&self.types, index, "type")?;
+                self.types.push(ty);
+            }
+            wp::ComponentAlias::Outer {
+                kind: wp::ComponentOuterAliasKind::CoreModule,
+                count: 0,
+                index,
+            } => {
+                let module = get(&self.modules, index, "module")?;
+                self.modules.push(module);
+            }
+            wp::ComponentAlias::Outer { .. } => {
+                return Err(unsupported("outer aliases of components and core types"))
+            }
+        }
+        Ok(())
+    }
+
+    fn canonical(&mut self, func: wp::CanonicalFunction) -> Result<(), CompileError> {
+        match func {
+            wp::CanonicalFunction::Lift {
+                core_func_index,
+                type_index,
+                options,
+            } => {
+                let ty = self.resolver().func_at(type_index)?;
+                self.funcs.push(ty.clone());
+                self.initializers.push(Initializer::Lift {
+                    core_func: core_func_index,
+                    ty,
+                    options: canonical_options(&options)?,
+                });
+            }
+            wp::CanonicalFunction::Lower {
+                func_index,
+                options,
+            } => {
+                let ty = get(&self.funcs, func_index, "function")?;
+                self.initializers.push(Initializer::Lower {
+                    func: func_index,
+                    ty,
+                    options: canonical_options(&options)?,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn translate(&mut self, store: &impl AsStoreRef, bytes: &[u8]) -> Result<(), CompileError> {
+        // Nested modules are yielded by the parser inline; they are
+        // compiled from their range, and their payloads are skipped.
+        let mut depth = 0;
+        for payload in wp::Parser::new(0).parse_all(bytes) {
+            let payload = payload.map_err(|e| invalid(e.to_string()))?;
+            if depth > 0 {
+                match payload {
+                    wp::Payload::ModuleSection { .. } | wp::Payload::ComponentSection { .. } => {
+                        depth += 1
+                    }
+                    wp::Payload::End(_) => depth -= 1,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match payload {
+                wp::Payload::Version { encoding, .. } => {
+                    if encoding != wp::Encoding::Component {
+                        return Err(invalid("expected a component, found a core module"));
+                    }
+                }
+                wp::Payload::ModuleSection { range, .. } => {
+                    self.modules.push(Module::new(store, &bytes[range])?);
+                    depth += 1;
+                }
+                wp::Payload::ComponentSection { .. } => {
+                    return Err(unsupported("nested components"));
+                }
+                wp::Payload::CoreTypeSection(_) => {}
+                wp::Payload::ComponentTypeSection(reader) => {
+                    for ty in reader {
+                        let ty = ty.map_err(|e| invalid(e.to_string()))?;
+                        let ty = self.resolver().component_type(&ty, None)?;
+                        self.types.push(ty);
+                    }
+                }
+                wp::Payload::ComponentImportSection(reader) => {
+                    for import in reader {
+                        self.import(import.map_err(|e| invalid(e.to_string()))?)?;
+                    }
+                }
+                wp::Payload::InstanceSection(reader) => {
+                    for instance in reader {
+                        let initializer = match instance.map_err(|e| invalid(e.to_string()))? {
+                            wp::Instance::Instantiate { module_index, args } => {
+                                Initializer::CoreInstantiate {
+                                    module: module_index,
+                                    args: args
+                                        .iter()
+                                        .map(|arg| (arg.name.to_string(), arg.index))
+                                        .collect(),
+                                }
+                            }
+                            wp::Instance::FromExports(exports) => {
+                                Initializer::CoreInstanceFromExports(
+                                    exports
+                                        .iter()
+                                        .map(|export| {
+                                            Ok((
+                                                export.name.to_string(),
+                                                core_sort(export.kind)?,
+                                                export.index,
+                                            ))
+                                        })
+                                        .collect::<Result<_, CompileError>>()?,
+                                )
+                            }
+                        };
+                        self.initializers.push(initializer);
+                    }
+                }
+                wp::Payload::AliasSection(reader) => {
+                    for alias in reader {
+                        match alias.map_err(|e| invalid(e.to_string()))? {
+                            wp::Alias::InstanceExport {
+                                kind,
+                                instance_index,
+                                name,
+                            } => self.initializers.push(Initializer::CoreAlias {
+                                sort: core_sort(kind)?,
+                                instance: instance_index,
+                                name: name.to_string(),
+                            }),
+                            wp::Alias::Outer { .. } => {}
+                        }
+                    }
+                }
+                wp::Payload::ComponentAliasSection(reader) => {
+                    for alias in reader {
+                        self.alias(alias.map_err(|e| invalid(e.to_string()))?)?;
+                    }
+                }
+                wp::Payload::ComponentCanonicalSection(reader) => {
+                    for func in reader {
+                        self.canonical(func.map_err(|e| invalid(e.to_string()))?)?;
+                    }
+                }
+                wp::Payload::ComponentInstanceSection(reader) => {
+                    for instance in reader {
+                        match instance.map_err(|e| invalid(e.to_string()))? {
+                            wp::ComponentInstance::Instantiate { .. } => {
+                                return Err(unsupported("instantiation of nested components"))
+                            }
+                            wp::ComponentInstance::FromExports(exports) => {
+                                let mut items = Vec::new();
+                                let mut types = Vec::new();
+                                for export in exports.iter() {
+                                    let item = self.export(export.kind, export.index)?;
+                                    items.push((export.name.to_string(), item));
+                                    types.push((export.name.to_string(), self.extern_type(item)?));
+                                }
+                                self.instances.push(ComponentInstanceType::new(types));
+                                self.initializers
+                                    .push(Initializer::InstanceFromExports(items));
+                            }
+                        }
+                    }
+                }
+                wp::Payload::ComponentExportSection(reader) => {
+                    for export in reader {
+                        let export = export.map_err(|e| invalid(e.to_string()))?;
+                        if export.kind == wp::ComponentExternalKind::Type {
+                            continue;
+                        }
+                        let item = self.export(export.kind, export.index)?;
+                        let ty = self.extern_type(item)?;
+                        self.exports.push((export.name.to_string(), item, ty));
+                    }
+                }
+                wp::Payload::ComponentStartSection(_) => {
+                    return Err(unsupported("component start functions"));
+                }
+                wp::Payload::CustomSection(_) | wp::Payload::End(_) => {}
+                _ => return Err(invalid("unexpected section in a component")),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Component {
+    /// Creates a new component from its binary representation, or
+    /// from its text representation if the `wat` feature is enabled.
+    ///
+    /// The core modules of the component are compiled with the engine
+    /// of the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CompileError`] if the component is invalid, if it
+    /// uses a feature of the component model that isn't supported, or
+    /// if one of its core modules fails to compile.
+    #[allow(unreachable_code)]
+    pub fn new(store: &impl AsStoreRef, bytes: impl AsRef<[u8]>) -> Result<Self, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        Self::from_binary(store, bytes.as_ref())
+    }
+
+    /// Creates a new component from its binary representation.
+    ///
+    /// Opposed to [`Component::new`], this function is not compatible
+    /// with the WebAssembly text format.
+    pub fn from_binary(store: &impl AsStoreRef, binary: &[u8]) -> Result<Self, CompileError> {
+        Self::validate(binary)?;
+
+        let mut translation = Translation::default();
+        translation.translate(store, binary)?;
+
+        Ok(Self {
+            inner: Arc::new(ComponentInner {
+                modules: translation.modules,
+                initializers: translation.initializers,
+                imports: translation.imports,
+                exports: translation.exports,
+            }),
+        })
+    }
+
+    /// Validates a component binary, including its core modules.
+    pub fn validate(binary: &[u8]) -> Result<(), CompileError> {
+        let mut validator = wp::Validator::new_with_features(wp::WasmFeatures {
+            component_model: true,
+            ..Default::default()
+        });
+        validator
+            .validate_all(binary)
+            .map(|_| ())
+            .map_err(|e| CompileError::Validate(e.to_string()))
+    }
+
+    /// Iterates over the names and types of the imports of the
+    /// component.
+    pub fn imports(&self) -> impl ExactSizeIterator<Item = (&str, &ComponentExternType)> + '_ {
+        self.inner
+            .imports
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty))
+    }
+
+    /// Iterates over the names and types of the exports of the
+    /// component.
+    pub fn exports(&self) -> impl ExactSizeIterator<Item = (&str, &ComponentExternType)> + '_ {
+        self.inner
+            .exports
+            .iter()
+            .map(|(name, _, ty)| (name.as_str(), ty))
+    }
+}
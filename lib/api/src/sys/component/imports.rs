@@ -0,0 +1,258 @@
+use super::abi::{self, CanonicalOptions};
+use super::typed::{ComponentParams, ComponentResults};
+use super::{ComponentFuncType, ComponentValue};
+use crate::sys::store::{AsStoreMut, StoreMut};
+use crate::sys::{Function, FunctionEnv, FunctionEnvMut, FunctionType, RuntimeError};
+use indexmap::IndexMap;
+use std::fmt;
+use std::sync::Arc;
+
+type LowerFn =
+    dyn Fn(&mut StoreMut<'_>, &ComponentFuncType, CanonicalOptions) -> Function + Send + Sync;
+
+/// A host function that can be imported by a component.
+///
+/// The host function is lowered to a core function for each `canon
+/// lower` of the component, with the canonical options of the lowering.
+#[derive(Clone)]
+pub(crate) struct HostFunc {
+    /// The type of the function, if it is statically known.
+    pub(crate) ty: Option<ComponentFuncType>,
+    pub(crate) lower: Arc<LowerFn>,
+}
+
+impl HostFunc {
+    pub(crate) fn lower(
+        &self,
+        store: &mut impl AsStoreMut,
+        ty: &ComponentFuncType,
+        options: CanonicalOptions,
+    ) -> Function {
+        (self.lower)(&mut store.as_store_mut(), ty, options)
+    }
+}
+
+/// A function environment captured by the lowering of a host
+/// function.
+struct CapturedEnv<T>(FunctionEnv<T>);
+
+// SAFETY: a `FunctionEnv` is only a handle to data owned by a store;
+// the data is only accessed through the store, which is not shared
+// between threads.
+unsafe impl<T: Send> Sync for CapturedEnv<T> {}
+
+#[derive(Clone)]
+pub(crate) enum ComponentImport {
+    Func(HostFunc),
+    Instance(ComponentImports),
+}
+
+/// The host functions and instances imported by a component when it
+/// is instantiated.
+///
+/// Host functions are dynamically typed: they take the
+/// [`ComponentValue`]s lifted from the arguments of the caller, and
+/// return the [`ComponentValue`]s to lower as results. Typed host
+/// functions can be defined with
+/// [`ComponentImports::typed_func_with_env`].
+///
+/// # Usage
+///
+/// ```
+/// # use wasmer::{Store, FunctionEnv};
+/// # use wasmer::component::{ComponentImports, ComponentValue};
+/// # let mut store = Store::default();
+/// let env = FunctionEnv::new(&mut store, Vec::<String>::new());
+/// let mut imports = ComponentImports::new();
+/// imports
+///     .instance("host")
+///     .typed_func_with_env(&env, "log", |mut env, (message,): (String,)| {
+///         env.data_mut().push(message);
+///         Ok(())
+///     });
+/// ```
+#[derive(Clone, Default)]
+pub struct ComponentImports {
+    items: IndexMap<String, ComponentImport>,
+}
+
+impl ComponentImports {
+    /// Creates a new, empty, set of imports.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the imports of the instance named `name`, creating it
+    /// if needed.
+    pub fn instance(&mut self, name: &str) -> &mut Self {
+        let item = self
+            .items
+            .entry(name.to_string())
+            .or_insert_with(|| ComponentImport::Instance(Self::new()));
+        if let ComponentImport::Func(_) = item {
+            *item = ComponentImport::Instance(Self::new());
+        }
+        match item {
+            ComponentImport::Instance(imports) => imports,
+            ComponentImport::Func(_) => unreachable!(),
+        }
+    }
+
+    /// Defines a dynamically typed host function named `name`.
+    pub fn func<F>(&mut self, name: &str, func: F) -> &mut Self
+    where
+        F: Fn(&[ComponentValue]) -> Result<Vec<ComponentValue>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let func = Arc::new(func);
+        self.define(
+            name,
+            None,
+            move |store: &mut StoreMut<'_>, ty: &ComponentFuncType, options| {
+                let env = FunctionEnv::new(store, ());
+                let func = func.clone();
+                lower_with_env(store, &env, ty, options, move |_env, params| func(params))
+            },
+        )
+    }
+
+    /// Defines a dynamically typed host function named `name`, with an
+    /// environment.
+    pub fn func_with_env<T, F>(&mut self, env: &FunctionEnv<T>, name: &str, func: F) -> &mut Self
+    where
+        T: Send + 'static,
+        F: Fn(FunctionEnvMut<T>, &[ComponentValue]) -> Result<Vec<ComponentValue>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let env = CapturedEnv(env.clone());
+        let func = Arc::new(func);
+        self.define(
+            name,
+            None,
+            move |store: &mut StoreMut<'_>, ty: &ComponentFuncType, options| {
+                let func = func.clone();
+                lower_with_env(store, &env.0, ty, options, move |env, params| {
+                    func(env, params)
+                })
+            },
+        )
+    }
+
+    /// Defines a typed host function named `name`, with an
+    /// environment.
+    ///
+    /// The parameters are passed as a tuple. The type of the function
+    /// is checked against the type of the import when the component is
+    /// instantiated.
+    pub fn typed_func_with_env<T, Params, Results, F>(
+        &mut self,
+        env: &FunctionEnv<T>,
+        name: &str,
+        func: F,
+    ) -> &mut Self
+    where
+        T: Send + 'static,
+        Params: ComponentParams,
+        Results: ComponentResults,
+        F: Fn(FunctionEnvMut<T>, Params) -> Result<Results, RuntimeError> + Send + Sync + 'static,
+    {
+        let ty = ComponentFuncType::new(
+            Params::types()
+                .into_iter()
+                .map(|ty| (String::new(), ty))
+                .collect(),
+            Results::types()
+                .into_iter()
+                .map(|ty| (String::new(), ty))
+                .collect(),
+        );
+        let env = CapturedEnv(env.clone());
+        let func = Arc::new(func);
+        self.define(
+            name,
+            Some(ty),
+            move |store: &mut StoreMut<'_>, ty: &ComponentFuncType, options| {
+                let func = func.clone();
+                lower_with_env(store, &env.0, ty, options, move |env, params| {
+                    let params = Params::from_values(params.to_vec())?;
+                    Ok(func(env, params)?.into_values())
+                })
+            },
+        )
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        ty: Option<ComponentFuncType>,
+        lower: impl Fn(&mut StoreMut<'_>, &ComponentFuncType, CanonicalOptions) -> Function
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.items.insert(
+            name.to_string(),
+            ComponentImport::Func(HostFunc {
+                ty,
+                lower: Arc::new(lower),
+            }),
+        );
+        self
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&ComponentImport> {
+        self.items.get(name)
+    }
+
+    /// Returns whether an import named `name` is defined.
+    pub fn contains(&self, name: &str) -> bool {
+        self.items.contains_key(name)
+    }
+}
+
+impl fmt::Debug for ComponentImports {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, item) in &self.items {
+            match item {
+                ComponentImport::Func(_) => map.entry(name, &"func"),
+                ComponentImport::Instance(imports) => map.entry(name, imports),
+            };
+        }
+        map.finish()
+    }
+}
+
+/// Lowers a component function implemented by `func` to a core
+/// function.
+pub(crate) fn lower_with_env<T, F>(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<T>,
+    ty: &ComponentFuncType,
+    options: CanonicalOptions,
+    func: F,
+) -> Function
+where
+    T: Send + 'static,
+    F: Fn(FunctionEnvMut<T>, &[ComponentValue]) -> Result<Vec<ComponentValue>, RuntimeError>
+        + Send
+        + Sync
+        + 'static,
+{
+    let ty = ty.clone();
+    let (params, results) = abi::core_signature(&ty, true);
+    Function::new_with_env(
+        store,
+        env,
+        FunctionType::new(params, results),
+        move |mut env, core_params| {
+            let params = abi::lift_params(&env, &ty, &options, core_params)?;
+            let results = func(env.as_mut(), &params)?;
+            abi::lower_results(&mut env, &ty, &options, core_params, &results)
+        },
+    )
+}
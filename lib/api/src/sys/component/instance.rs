@@ -0,0 +1,492 @@
+use super::abi::{self, CanonicalOptions};
+use super::component::{CanonicalOptionIndices, CoreSort, Export, Initializer};
+use super::imports::{lower_with_env, ComponentImport, HostFunc};
+use super::typed::{ComponentParams, ComponentResults, TypedComponentFunction};
+use super::{
+    Component, ComponentExternType, ComponentFuncType, ComponentImports, ComponentInstanceType,
+    ComponentValue,
+};
+use crate::sys::store::AsStoreMut;
+use crate::sys::{
+    ExportError, Exports, Extern, Function, FunctionEnv, Global, Imports, Instance,
+    InstantiationError, Memory, RuntimeError, Table,
+};
+use indexmap::IndexMap;
+use std::fmt;
+use thiserror::Error;
+
+/// An error while instantiating a component.
+#[derive(Error, Debug)]
+pub enum ComponentInstantiationError {
+    /// An import of the component isn't defined.
+    #[error("missing import `{0}`")]
+    MissingImport(String),
+
+    /// An import of the component is defined with an incompatible
+    /// type or kind.
+    #[error("incompatible import `{name}`: {reason}")]
+    IncompatibleImport {
+        /// The name of the import.
+        name: String,
+        /// Why the import is incompatible.
+        reason: String,
+    },
+
+    /// The component uses a feature of the component model that isn't
+    /// supported when it is instantiated.
+    #[error("unsupported component feature: {0}")]
+    Unsupported(String),
+
+    /// One of the core modules of the component failed to
+    /// instantiate.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+
+    /// The instance doesn't have the exports expected by the
+    /// bindings of a world.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+}
+
+/// A function exported by a component instance, i.e. a core function
+/// lifted to the component model.
+#[derive(Clone)]
+pub struct ComponentFunction {
+    func: Function,
+    ty: ComponentFuncType,
+    options: CanonicalOptions,
+}
+
+impl ComponentFunction {
+    /// The type of the function.
+    pub fn ty(&self) -> &ComponentFuncType {
+        &self.ty
+    }
+
+    /// Calls the function with dynamically typed parameters.
+    ///
+    /// The parameters are lowered to the core function with the
+    /// canonical ABI, and its results are lifted back; the parameters
+    /// are checked against the type of the function while they are
+    /// lowered.
+    pub fn call(
+        &self,
+        store: &mut impl AsStoreMut,
+        params: &[ComponentValue],
+    ) -> Result<Vec<ComponentValue>, RuntimeError> {
+        abi::call_lifted(store, &self.func, &self.ty, &self.options, params)
+    }
+
+    /// Returns a typed version of the function.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `Params` and `Results` don't match the type
+    /// of the function.
+    pub fn typed<Params, Results>(
+        &self,
+    ) -> Result<TypedComponentFunction<Params, Results>, RuntimeError>
+    where
+        Params: ComponentParams,
+        Results: ComponentResults,
+    {
+        TypedComponentFunction::new(self.clone())
+    }
+}
+
+impl fmt::Debug for ComponentFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentFunction")
+            .field("ty", &self.ty)
+            .finish()
+    }
+}
+
+/// An item exported by a component instance.
+#[derive(Clone, Debug)]
+pub enum ComponentExport {
+    /// A function.
+    Func(ComponentFunction),
+    /// An instance.
+    Instance(ComponentExports),
+}
+
+/// The exports of a component instance.
+#[derive(Clone, Debug, Default)]
+pub struct ComponentExports {
+    items: IndexMap<String, ComponentExport>,
+}
+
+impl ComponentExports {
+    /// Gets the export named `name`.
+    pub fn get(&self, name: &str) -> Option<&ComponentExport> {
+        self.items.get(name)
+    }
+
+    /// Gets the function named `name`.
+    pub fn get_func(&self, name: &str) -> Result<&ComponentFunction, ExportError> {
+        match self.items.get(name) {
+            Some(ComponentExport::Func(func)) => Ok(func),
+            Some(_) => Err(ExportError::IncompatibleType),
+            None => Err(ExportError::Missing(name.to_string())),
+        }
+    }
+
+    /// Gets the function named `name`, as a typed function.
+    pub fn get_typed_func<Params, Results>(
+        &self,
+        name: &str,
+    ) -> Result<TypedComponentFunction<Params, Results>, ExportError>
+    where
+        Params: ComponentParams,
+        Results: ComponentResults,
+    {
+        self.get_func(name)?
+            .typed()
+            .map_err(|_| ExportError::IncompatibleType)
+    }
+
+    /// Gets the instance named `name`.
+    pub fn get_instance(&self, name: &str) -> Result<&Self, ExportError> {
+        match self.items.get(name) {
+            Some(ComponentExport::Instance(instance)) => Ok(instance),
+            Some(_) => Err(ExportError::IncompatibleType),
+            None => Err(ExportError::Missing(name.to_string())),
+        }
+    }
+
+    /// Iterates over the names and items of the exports.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&str, &ComponentExport)> + '_ {
+        self.items.iter().map(|(name, item)| (name.as_str(), item))
+    }
+}
+
+/// An instance of a [`Component`].
+#[derive(Clone, Debug)]
+pub struct ComponentInstance {
+    /// The exports of the instance.
+    pub exports: ComponentExports,
+}
+
+/// A function in the index space of a component being instantiated.
+#[derive(Clone)]
+enum Func {
+    Host(HostFunc),
+    Lifted(ComponentFunction),
+}
+
+/// An instance in the index space of a component being instantiated.
+#[derive(Clone)]
+enum ComponentInstanceItem {
+    Host(ComponentImports),
+    Local(ComponentExports),
+}
+
+/// The index spaces of a component being instantiated.
+#[derive(Default)]
+struct IndexSpaces {
+    core_instances: Vec<Exports>,
+    core_funcs: Vec<Function>,
+    core_tables: Vec<Table>,
+    core_memories: Vec<Memory>,
+    core_globals: Vec<Global>,
+    funcs: Vec<Func>,
+    instances: Vec<ComponentInstanceItem>,
+}
+
+impl IndexSpaces {
+    fn core_item(&self, sort: CoreSort, index: u32) -> Extern {
+        let index = index as usize;
+        match sort {
+            CoreSort::Func => Extern::Function(self.core_funcs[index].clone()),
+            CoreSort::Table => Extern::Table(self.core_tables[index].clone()),
+            CoreSort::Memory => Extern::Memory(self.core_memories[index].clone()),
+            CoreSort::Global => Extern::Global(self.core_globals[index].clone()),
+        }
+    }
+
+    fn options(&self, indices: &CanonicalOptionIndices) -> CanonicalOptions {
+        CanonicalOptions {
+            memory: indices
+                .memory
+                .map(|index| self.core_memories[index as usize].clone()),
+            realloc: indices
+                .realloc
+                .map(|index| self.core_funcs[index as usize].clone()),
+            post_return: indices
+                .post_return
+                .map(|index| self.core_funcs[index as usize].clone()),
+        }
+    }
+
+    fn exports(
+        &self,
+        items: &[(String, Export)],
+    ) -> Result<ComponentExports, ComponentInstantiationError> {
+        let mut exports = ComponentExports::default();
+        for (name, item) in items {
+            let export = match *item {
+                Export::Func(index) => match &self.funcs[index as usize] {
+                    Func::Lifted(func) => ComponentExport::Func(func.clone()),
+                    Func::Host(_) => {
+                        return Err(ComponentInstantiationError::Unsupported(format!(
+                            "exporting the imported function `{}`",
+                            name
+                        )))
+                    }
+                },
+                Export::Instance(index) => match &self.instances[index as usize] {
+                    ComponentInstanceItem::Local(instance) => {
+                        ComponentExport::Instance(instance.clone())
+                    }
+                    ComponentInstanceItem::Host(_) => {
+                        return Err(ComponentInstantiationError::Unsupported(format!(
+                            "exporting the imported instance `{}`",
+                            name
+                        )))
+                    }
+                },
+            };
+            exports.items.insert(name.clone(), export);
+        }
+        Ok(exports)
+    }
+}
+
+fn incompatible(name: &str, reason: impl Into<String>) -> ComponentInstantiationError {
+    ComponentInstantiationError::IncompatibleImport {
+        name: name.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Checks that a host function can be imported as a function of type
+/// `ty`. Only the parameter and result types are compared, not their
+/// names.
+fn check_func(
+    name: &str,
+    func: &HostFunc,
+    ty: &ComponentFuncType,
+) -> Result<(), ComponentInstantiationError> {
+    match &func.ty {
+        Some(host_ty)
+            if !host_ty.param_types().eq(ty.param_types())
+                || !host_ty.result_types().eq(ty.result_types()) =>
+        {
+            Err(incompatible(
+                name,
+                format!("expected `{}`, found `{}`", ty, host_ty),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that host imports can be imported as an instance of type
+/// `ty`.
+fn check_instance(
+    name: &str,
+    imports: &ComponentImports,
+    ty: &ComponentInstanceType,
+) -> Result<(), ComponentInstantiationError> {
+    for (export, export_ty) in ty.exports() {
+        let path = format!("{}/{}", name, export);
+        match (imports.get(export), export_ty) {
+            (None, _) => return Err(ComponentInstantiationError::MissingImport(path)),
+            (Some(ComponentImport::Func(func)), ComponentExternType::Func(ty)) => {
+                check_func(&path, func, ty)?
+            }
+            (Some(ComponentImport::Instance(imports)), ComponentExternType::Instance(ty)) => {
+                check_instance(&path, imports, ty)?
+            }
+            (Some(ComponentImport::Func(_)), _) => {
+                return Err(incompatible(
+                    &path,
+                    "expected an instance, found a function",
+                ))
+            }
+            (Some(ComponentImport::Instance(_)), _) => {
+                return Err(incompatible(
+                    &path,
+                    "expected a function, found an instance",
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+impl ComponentInstance {
+    /// Instantiates a [`Component`] with host imports.
+    ///
+    /// The core modules of the component are instantiated and linked
+    /// together, and the imported host functions are lowered to core
+    /// functions.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ComponentInstantiationError`] if an import is
+    /// missing or incompatible, or if a core module fails to
+    /// instantiate.
+    pub fn new(
+        store: &mut impl AsStoreMut,
+        component: &Component,
+        imports: &ComponentImports,
+    ) -> Result<Self, ComponentInstantiationError> {
+        let inner = &component.inner;
+        let mut spaces = IndexSpaces::default();
+
+        for initializer in &inner.initializers {
+            match initializer {
+                Initializer::CoreInstantiate { module, args } => {
+                    let mut core_imports = Imports::new();
+                    for (name, instance) in args {
+                        core_imports.register_namespace(
+                            name,
+                            spaces.core_instances[*instance as usize]
+                                .iter()
+                                .map(|(name, item)| (name.clone(), item.clone())),
+                        );
+                    }
+                    let instance =
+                        Instance::new(store, &inner.modules[*module as usize], &core_imports)?;
+                    spaces.core_instances.push(instance.exports);
+                }
+                Initializer::CoreInstanceFromExports(items) => {
+                    let exports = items
+                        .iter()
+                        .map(|(name, sort, index)| (name.clone(), spaces.core_item(*sort, *index)))
+                        .collect();
+                    spaces.core_instances.push(exports);
+                }
+                Initializer::CoreAlias {
+                    sort,
+                    instance,
+                    name,
+                } => {
+                    let item = spaces.core_instances[*instance as usize]
+                        .get_extern(name)
+                        .cloned();
+                    match (sort, item) {
+                        (CoreSort::Func, Some(Extern::Function(func))) => {
+                            spaces.core_funcs.push(func)
+                        }
+                        (CoreSort::Table, Some(Extern::Table(table))) => {
+                            spaces.core_tables.push(table)
+                        }
+                        (CoreSort::Memory, Some(Extern::Memory(memory))) => {
+                            spaces.core_memories.push(memory)
+                        }
+                        (CoreSort::Global, Some(Extern::Global(global))) => {
+                            spaces.core_globals.push(global)
+                        }
+                        _ => {
+                            return Err(ComponentInstantiationError::Unsupported(format!(
+                                "alias of the missing core export `{}`",
+                                name
+                            )))
+                        }
+                    }
+                }
+                Initializer::ImportFunc { name, ty } => match imports.get(name) {
+                    Some(ComponentImport::Func(func)) => {
+                        check_func(name, func, ty)?;
+                        spaces.funcs.push(Func::Host(func.clone()));
+                    }
+                    Some(ComponentImport::Instance(_)) => {
+                        return Err(incompatible(name, "expected a function, found an instance"))
+                    }
+                    None => return Err(ComponentInstantiationError::MissingImport(name.clone())),
+                },
+                Initializer::ImportInstance { name, ty } => match imports.get(name) {
+                    Some(ComponentImport::Instance(instance)) => {
+                        check_instance(name, instance, ty)?;
+                        spaces
+                            .instances
+                            .push(ComponentInstanceItem::Host(instance.clone()));
+                    }
+                    Some(ComponentImport::Func(_)) => {
+                        return Err(incompatible(name, "expected an instance, found a function"))
+                    }
+                    None => return Err(ComponentInstantiationError::MissingImport(name.clone())),
+                },
+                Initializer::AliasFunc { instance, name } => {
+                    let func = match &spaces.instances[*instance as usize] {
+                        ComponentInstanceItem::Host(imports) => match imports.get(name) {
+                            Some(ComponentImport::Func(func)) => Some(Func::Host(func.clone())),
+                            _ => None,
+                        },
+                        ComponentInstanceItem::Local(exports) => match exports.get(name) {
+                            Some(ComponentExport::Func(func)) => Some(Func::Lifted(func.clone())),
+                            _ => None,
+                        },
+                    };
+                    spaces
+                        .funcs
+                        .push(func.expect("the instance was type checked"));
+                }
+                Initializer::AliasInstance { instance, name } => {
+                    let item = match &spaces.instances[*instance as usize] {
+                        ComponentInstanceItem::Host(imports) => match imports.get(name) {
+                            Some(ComponentImport::Instance(imports)) => {
+                                Some(ComponentInstanceItem::Host(imports.clone()))
+                            }
+                            _ => None,
+                        },
+                        ComponentInstanceItem::Local(exports) => match exports.get(name) {
+                            Some(ComponentExport::Instance(exports)) => {
+                                Some(ComponentInstanceItem::Local(exports.clone()))
+                            }
+                            _ => None,
+                        },
+                    };
+                    spaces
+                        .instances
+                        .push(item.expect("the instance was type checked"));
+                }
+                Initializer::Lift {
+                    core_func,
+                    ty,
+                    options,
+                } => {
+                    let func = ComponentFunction {
+                        func: spaces.core_funcs[*core_func as usize].clone(),
+                        ty: ty.clone(),
+                        options: spaces.options(options),
+                    };
+                    spaces.funcs.push(Func::Lifted(func));
+                }
+                Initializer::Lower { func, ty, options } => {
+                    let options = spaces.options(options);
+                    let core_func = match &spaces.funcs[*func as usize] {
+                        Func::Host(func) => func.lower(store, ty, options),
+                        Func::Lifted(func) => {
+                            // Calls between two core modules of the same
+                            // component go through the host.
+                            let func = func.clone();
+                            let env = FunctionEnv::new(store, ());
+                            lower_with_env(store, &env, ty, options, move |mut env, params| {
+                                func.call(&mut env, params)
+                            })
+                        }
+                    };
+                    spaces.core_funcs.push(core_func);
+                }
+                Initializer::InstanceFromExports(items) => {
+                    let exports = spaces.exports(items)?;
+                    spaces.instances.push(ComponentInstanceItem::Local(exports));
+                }
+            }
+        }
+
+        let exports = spaces.exports(
+            &inner
+                .exports
+                .iter()
+                .map(|(name, item, _)| (name.clone(), *item))
+                .collect::<Vec<_>>(),
+        )?;
+
+        Ok(Self { exports })
+    }
+}
@@ -0,0 +1,97 @@
+//! The `component` module contains the support for the
+//! [WebAssembly component model].
+//!
+//! A [`Component`] is instantiated with [`ComponentImports`], which
+//! contains host implementations of the functions it imports, into a
+//! [`ComponentInstance`]. Values cross the component boundary as
+//! [`ComponentValue`]s, or as Rust types implementing
+//! [`ComponentType`], and are lifted from and lowered to the linear
+//! memory of the core modules with the canonical ABI.
+//!
+//! Typed bindings for [WIT] interfaces and worlds can be generated
+//! with the [`bindgen!`] macro.
+//!
+//! # Limitations
+//!
+//! Only the subset of the component model needed to link core modules
+//! against host functions is implemented. In particular, resources
+//! (`resource` types, `own` and `borrow` handles and the
+//! `resource.new`, `resource.drop` and `resource.rep` built-ins) are
+//! not supported: components are parsed with a version of
+//! `wasmparser` that predates them, so a component using resources
+//! fails to load with a parse error, and [`bindgen!`] rejects WIT
+//! documents that declare them. The versions of `wasmparser` that
+//! parse resources need a newer Rust than the one Wasmer supports, so
+//! resources are left for when the parser is updated, along with the
+//! other unsupported features: nested components, component start
+//! functions, module imports and string encodings other than UTF-8.
+//!
+//! # Example
+//!
+//! ```
+//! # use wasmer::{Store, FunctionEnv};
+//! # use wasmer::component::{Component, ComponentImports, ComponentInstance};
+//! # fn main() -> anyhow::Result<()> {
+//! let mut store = Store::default();
+//! let component = Component::new(
+//!     &store,
+//!     r#"
+//! (component
+//!   (import "host" (instance $host
+//!     (export "double" (func (param u32) (result u32)))
+//!   ))
+//!   (core func $double (canon lower (func $host "double")))
+//!   (core module $m
+//!     (import "host" "double" (func $double (param i32) (result i32)))
+//!     (func (export "quadruple") (param i32) (result i32)
+//!       (call $double (call $double (local.get 0)))))
+//!   (core instance $i (instantiate $m
+//!     (with "host" (instance (export "double" (func $double))))
+//!   ))
+//!   (func (export "quadruple") (param u32) (result u32)
+//!     (canon lift (core func $i "quadruple")))
+//! )
+//! "#,
+//! )?;
+//!
+//! let env = FunctionEnv::new(&mut store, ());
+//! let mut imports = ComponentImports::new();
+//! imports
+//!     .instance("host")
+//!     .typed_func_with_env(&env, "double", |_env, (x,): (u32,)| Ok(x * 2));
+//!
+//! let instance = ComponentInstance::new(&mut store, &component, &imports)?;
+//! let quadruple = instance
+//!     .exports
+//!     .get_typed_func::<(u32,), u32>("quadruple")?;
+//! assert_eq!(quadruple.call(&mut store, (5,))?, 20);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [WebAssembly component model]: https://github.com/WebAssembly/component-model
+//! [WIT]: https://github.com/WebAssembly/component-model/blob/main/design/mvp/WIT.md
+
+mod abi;
+#[allow(clippy::module_inception)]
+mod component;
+mod imports;
+mod instance;
+mod typed;
+mod types;
+mod values;
+
+pub use self::component::Component;
+pub use self::imports::ComponentImports;
+pub use self::instance::{
+    ComponentExport, ComponentExports, ComponentFunction, ComponentInstance,
+    ComponentInstantiationError,
+};
+pub use self::typed::{
+    ComponentParams, ComponentPayload, ComponentResults, ComponentType, TypedComponentFunction,
+};
+pub use self::types::{
+    ComponentExternType, ComponentFuncType, ComponentInstanceType, InterfaceType,
+};
+pub use self::values::ComponentValue;
+pub use wasmer_derive::bindgen;
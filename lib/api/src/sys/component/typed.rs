@@ -0,0 +1,368 @@
+use super::{ComponentFunction, ComponentValue, InterfaceType};
+use crate::sys::store::AsStoreMut;
+use crate::sys::RuntimeError;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+
+fn unexpected(expected: &InterfaceType, value: &ComponentValue) -> RuntimeError {
+    RuntimeError::new(format!(
+        "expected a value of type `{}`, found a value of kind `{}`",
+        expected,
+        value.kind()
+    ))
+}
+
+/// A Rust type that can cross a component boundary, as a value of an
+/// [`InterfaceType`].
+///
+/// This is implemented for the Rust types corresponding to the
+/// primitive types, lists, tuples, options and results; the
+/// `bindgen!` macro implements it for the records, variants, enums,
+/// flags and unions of WIT interfaces.
+pub trait ComponentType: Sized {
+    /// The interface type of the Rust type.
+    fn ty() -> InterfaceType;
+
+    /// Converts the Rust value to a component value.
+    fn into_value(self) -> ComponentValue;
+
+    /// Converts a component value to the Rust value, failing if the
+    /// component value isn't of the right type.
+    fn from_value(value: ComponentValue) -> Result<Self, RuntimeError>;
+}
+
+macro_rules! primitive {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl ComponentType for $ty {
+                fn ty() -> InterfaceType {
+                    InterfaceType::$variant
+                }
+
+                fn into_value(self) -> ComponentValue {
+                    ComponentValue::$variant(self)
+                }
+
+                fn from_value(value: ComponentValue) -> Result<Self, RuntimeError> {
+                    match value {
+                        ComponentValue::$variant(value) => Ok(value),
+                        value => Err(unexpected(&Self::ty(), &value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+primitive! {
+    bool => Bool,
+    i8 => S8,
+    u8 => U8,
+    i16 => S16,
+    u16 => U16,
+    i32 => S32,
+    u32 => U32,
+    i64 => S64,
+    u64 => U64,
+    f32 => Float32,
+    f64 => Float64,
+    char => Char,
+    String => String,
+}
+
+impl<T: ComponentType> ComponentType for Vec<T> {
+    fn ty() -> InterfaceType {
+        InterfaceType::List(Box::new(T::ty()))
+    }
+
+    fn into_value(self) -> ComponentValue {
+        ComponentValue::List(self.into_iter().map(T::into_value).collect())
+    }
+
+    fn from_value(value: ComponentValue) -> Result<Self, RuntimeError> {
+        match value {
+            ComponentValue::List(values) => values.into_iter().map(T::from_value).collect(),
+            value => Err(unexpected(&Self::ty(), &value)),
+        }
+    }
+}
+
+impl<T: ComponentType> ComponentType for Option<T> {
+    fn ty() -> InterfaceType {
+        InterfaceType::Option(Box::new(T::ty()))
+    }
+
+    fn into_value(self) -> ComponentValue {
+        ComponentValue::Option(self.map(|value| Box::new(value.into_value())))
+    }
+
+    fn from_value(value: ComponentValue) -> Result<Self, RuntimeError> {
+        match value {
+            ComponentValue::Option(value) => value.map(|value| T::from_value(*value)).transpose(),
+            value => Err(unexpected(&Self::ty(), &value)),
+        }
+    }
+}
+
+/// The payload of a case of a variant, i.e. either a
+/// [`ComponentType`] or `()` for cases without a payload.
+pub trait ComponentPayload: Sized {
+    /// The interface type of the payload, if any.
+    fn payload_ty() -> Option<InterfaceType>;
+
+    /// Converts the Rust value to the payload.
+    fn into_payload(self) -> Option<ComponentValue>;
+
+    /// Converts a payload to the Rust value.
+    fn from_payload(value: Option<ComponentValue>) -> Result<Self, RuntimeError>;
+}
+
+impl ComponentPayload for () {
+    fn payload_ty() -> Option<InterfaceType> {
+        None
+    }
+
+    fn into_payload(self) -> Option<ComponentValue> {
+        None
+    }
+
+    fn from_payload(value: Option<ComponentValue>) -> Result<Self, RuntimeError> {
+        match value {
+            None => Ok(()),
+            Some(value) => Err(RuntimeError::new(format!(
+                "expected no payload, found a value of kind `{}`",
+                value.kind()
+            ))),
+        }
+    }
+}
+
+impl<T: ComponentType> ComponentPayload for T {
+    fn payload_ty() -> Option<InterfaceType> {
+        Some(T::ty())
+    }
+
+    fn into_payload(self) -> Option<ComponentValue> {
+        Some(self.into_value())
+    }
+
+    fn from_payload(value: Option<ComponentValue>) -> Result<Self, RuntimeError> {
+        match value {
+            Some(value) => T::from_value(value),
+            None => Err(RuntimeError::new(format!(
+                "expected a value of type `{}`, found no payload",
+                T::ty()
+            ))),
+        }
+    }
+}
+
+impl<T: ComponentPayload, E: ComponentPayload> ComponentType for Result<T, E> {
+    fn ty() -> InterfaceType {
+        InterfaceType::Result {
+            ok: T::payload_ty().map(Box::new),
+            err: E::payload_ty().map(Box::new),
+        }
+    }
+
+    fn into_value(self) -> ComponentValue {
+        ComponentValue::Result(match self {
+            Ok(value) => Ok(value.into_payload().map(Box::new)),
+            Err(value) => Err(value.into_payload().map(Box::new)),
+        })
+    }
+
+    fn from_value(value: ComponentValue) -> Result<Self, RuntimeError> {
+        match value {
+            ComponentValue::Result(Ok(value)) => Ok(Ok(T::from_payload(value.map(|v| *v))?)),
+            ComponentValue::Result(Err(value)) => Ok(Err(E::from_payload(value.map(|v| *v))?)),
+            value => Err(unexpected(&Self::ty(), &value)),
+        }
+    }
+}
+
+/// The parameters of a typed component function, as a tuple of
+/// [`ComponentType`]s.
+pub trait ComponentParams: Sized {
+    /// The interface types of the parameters.
+    fn types() -> Vec<InterfaceType>;
+
+    /// Converts the parameters to component values.
+    fn into_values(self) -> Vec<ComponentValue>;
+
+    /// Converts component values to the parameters.
+    fn from_values(values: Vec<ComponentValue>) -> Result<Self, RuntimeError>;
+}
+
+/// The results of a typed component function: either `()` for no
+/// results, or a single [`ComponentType`].
+pub trait ComponentResults: Sized {
+    /// The interface types of the results.
+    fn types() -> Vec<InterfaceType>;
+
+    /// Converts the results to component values.
+    fn into_values(self) -> Vec<ComponentValue>;
+
+    /// Converts component values to the results.
+    fn from_values(values: Vec<ComponentValue>) -> Result<Self, RuntimeError>;
+}
+
+impl ComponentResults for () {
+    fn types() -> Vec<InterfaceType> {
+        vec![]
+    }
+
+    fn into_values(self) -> Vec<ComponentValue> {
+        vec![]
+    }
+
+    fn from_values(values: Vec<ComponentValue>) -> Result<Self, RuntimeError> {
+        match values.len() {
+            0 => Ok(()),
+            n => Err(RuntimeError::new(format!(
+                "expected no results, found {}",
+                n
+            ))),
+        }
+    }
+}
+
+impl<T: ComponentType> ComponentResults for T {
+    fn types() -> Vec<InterfaceType> {
+        vec![T::ty()]
+    }
+
+    fn into_values(self) -> Vec<ComponentValue> {
+        vec![self.into_value()]
+    }
+
+    fn from_values(values: Vec<ComponentValue>) -> Result<Self, RuntimeError> {
+        let n = values.len();
+        match <[ComponentValue; 1]>::try_from(values) {
+            Ok([value]) => T::from_value(value),
+            Err(_) => Err(RuntimeError::new(format!("expected 1 result, found {}", n))),
+        }
+    }
+}
+
+macro_rules! tuples {
+    ($(($($name:ident)*))*) => {
+        $(
+            #[allow(non_snake_case)]
+            impl<$($name: ComponentType),*> ComponentParams for ($($name,)*) {
+                fn types() -> Vec<InterfaceType> {
+                    vec![$($name::ty()),*]
+                }
+
+                fn into_values(self) -> Vec<ComponentValue> {
+                    let ($($name,)*) = self;
+                    vec![$($name.into_value()),*]
+                }
+
+                #[allow(unused_mut, unused_variables)]
+                fn from_values(values: Vec<ComponentValue>) -> Result<Self, RuntimeError> {
+                    let expected = <Self as ComponentParams>::types().len();
+                    if values.len() != expected {
+                        return Err(RuntimeError::new(format!(
+                            "expected {} parameters, found {}",
+                            expected,
+                            values.len()
+                        )));
+                    }
+                    let mut values = values.into_iter();
+                    Ok(($($name::from_value(values.next().unwrap())?,)*))
+                }
+            }
+
+            tuples!(@type $($name)*);
+        )*
+    };
+    (@type) => {};
+    (@type $($name:ident)+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: ComponentType),*> ComponentType for ($($name,)*) {
+            fn ty() -> InterfaceType {
+                InterfaceType::Tuple(vec![$($name::ty()),*])
+            }
+
+            fn into_value(self) -> ComponentValue {
+                let ($($name,)*) = self;
+                ComponentValue::Tuple(vec![$($name.into_value()),*])
+            }
+
+            fn from_value(value: ComponentValue) -> Result<Self, RuntimeError> {
+                match value {
+                    ComponentValue::Tuple(values) => {
+                        <Self as ComponentParams>::from_values(values)
+                    }
+                    value => Err(unexpected(&Self::ty(), &value)),
+                }
+            }
+        }
+    };
+}
+
+tuples! {
+    ()
+    (A1)
+    (A1 A2)
+    (A1 A2 A3)
+    (A1 A2 A3 A4)
+    (A1 A2 A3 A4 A5)
+    (A1 A2 A3 A4 A5 A6)
+    (A1 A2 A3 A4 A5 A6 A7)
+    (A1 A2 A3 A4 A5 A6 A7 A8)
+    (A1 A2 A3 A4 A5 A6 A7 A8 A9)
+    (A1 A2 A3 A4 A5 A6 A7 A8 A9 A10)
+    (A1 A2 A3 A4 A5 A6 A7 A8 A9 A10 A11)
+    (A1 A2 A3 A4 A5 A6 A7 A8 A9 A10 A11 A12)
+}
+
+/// A component function with statically known parameter and result
+/// types, see [`ComponentFunction::typed`].
+pub struct TypedComponentFunction<Params, Results> {
+    func: ComponentFunction,
+    _phantom: PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params, Results> Clone for TypedComponentFunction<Params, Results> {
+    fn clone(&self) -> Self {
+        Self {
+            func: self.func.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Params: ComponentParams, Results: ComponentResults> TypedComponentFunction<Params, Results> {
+    pub(crate) fn new(func: ComponentFunction) -> Result<Self, RuntimeError> {
+        let ty = func.ty();
+        let params = Params::types();
+        let results = Results::types();
+        if !ty.param_types().eq(params.iter()) || !ty.result_types().eq(results.iter()) {
+            return Err(RuntimeError::new(format!(
+                "the function has type `{}`, which doesn't match the given parameter and result types",
+                ty
+            )));
+        }
+        Ok(Self {
+            func,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Calls the function.
+    pub fn call(
+        &self,
+        store: &mut impl AsStoreMut,
+        params: Params,
+    ) -> Result<Results, RuntimeError> {
+        let results = self.func.call(store, &params.into_values())?;
+        Results::from_values(results)
+    }
+
+    /// Returns the underlying dynamically typed function.
+    pub fn func(&self) -> &ComponentFunction {
+        &self.func
+    }
+}
@@ -0,0 +1,244 @@
+use indexmap::IndexMap;
+use std::fmt;
+
+/// The type of a value crossing a component boundary.
+///
+/// Interface types are the types of the [WIT] language and of the
+/// component model's canonical ABI. Unlike core WebAssembly types,
+/// they are structural: two records with the same field names and
+/// field types are the same type.
+///
+/// [WIT]: https://github.com/WebAssembly/component-model/blob/main/design/mvp/WIT.md
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterfaceType {
+    /// A boolean.
+    Bool,
+    /// A signed 8-bit integer.
+    S8,
+    /// An unsigned 8-bit integer.
+    U8,
+    /// A signed 16-bit integer.
+    S16,
+    /// An unsigned 16-bit integer.
+    U16,
+    /// A signed 32-bit integer.
+    S32,
+    /// An unsigned 32-bit integer.
+    U32,
+    /// A signed 64-bit integer.
+    S64,
+    /// An unsigned 64-bit integer.
+    U64,
+    /// A 32-bit float.
+    Float32,
+    /// A 64-bit float.
+    Float64,
+    /// A Unicode scalar value.
+    Char,
+    /// A UTF-8 string.
+    String,
+    /// A list of values of the same type.
+    List(Box<Self>),
+    /// A record with named fields.
+    Record(Vec<(String, Self)>),
+    /// A tuple.
+    Tuple(Vec<Self>),
+    /// A variant with named cases, each with an optional payload.
+    Variant(Vec<(String, Option<Self>)>),
+    /// An enum, i.e. a variant whose cases have no payload.
+    Enum(Vec<String>),
+    /// A set of named flags.
+    Flags(Vec<String>),
+    /// A union of types, discriminated by their index.
+    Union(Vec<Self>),
+    /// An optional value.
+    Option(Box<Self>),
+    /// Either a success or an error, each with an optional payload.
+    Result {
+        /// The payload of the success case.
+        ok: Option<Box<Self>>,
+        /// The payload of the error case.
+        err: Option<Box<Self>>,
+    },
+}
+
+impl fmt::Display for InterfaceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T>(
+            f: &mut fmt::Formatter<'_>,
+            items: impl IntoIterator<Item = T>,
+            mut item: impl FnMut(&mut fmt::Formatter<'_>, T) -> fmt::Result,
+        ) -> fmt::Result {
+            for (i, it) in items.into_iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                item(f, it)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Self::Bool => write!(f, "bool"),
+            Self::S8 => write!(f, "s8"),
+            Self::U8 => write!(f, "u8"),
+            Self::S16 => write!(f, "s16"),
+            Self::U16 => write!(f, "u16"),
+            Self::S32 => write!(f, "s32"),
+            Self::U32 => write!(f, "u32"),
+            Self::S64 => write!(f, "s64"),
+            Self::U64 => write!(f, "u64"),
+            Self::Float32 => write!(f, "float32"),
+            Self::Float64 => write!(f, "float64"),
+            Self::Char => write!(f, "char"),
+            Self::String => write!(f, "string"),
+            Self::List(ty) => write!(f, "list<{}>", ty),
+            Self::Record(fields) => {
+                write!(f, "record {{ ")?;
+                list(f, fields, |f, (name, ty)| write!(f, "{}: {}", name, ty))?;
+                write!(f, " }}")
+            }
+            Self::Tuple(types) => {
+                write!(f, "tuple<")?;
+                list(f, types, |f, ty| write!(f, "{}", ty))?;
+                write!(f, ">")
+            }
+            Self::Variant(cases) => {
+                write!(f, "variant {{ ")?;
+                list(f, cases, |f, (name, ty)| match ty {
+                    Some(ty) => write!(f, "{}({})", name, ty),
+                    None => write!(f, "{}", name),
+                })?;
+                write!(f, " }}")
+            }
+            Self::Enum(names) => {
+                write!(f, "enum {{ ")?;
+                list(f, names, |f, name| write!(f, "{}", name))?;
+                write!(f, " }}")
+            }
+            Self::Flags(names) => {
+                write!(f, "flags {{ ")?;
+                list(f, names, |f, name| write!(f, "{}", name))?;
+                write!(f, " }}")
+            }
+            Self::Union(types) => {
+                write!(f, "union {{ ")?;
+                list(f, types, |f, ty| write!(f, "{}", ty))?;
+                write!(f, " }}")
+            }
+            Self::Option(ty) => write!(f, "option<{}>", ty),
+            Self::Result { ok, err } => match (ok, err) {
+                (None, None) => write!(f, "result"),
+                (Some(ok), None) => write!(f, "result<{}>", ok),
+                (None, Some(err)) => write!(f, "result<_, {}>", err),
+                (Some(ok), Some(err)) => write!(f, "result<{}, {}>", ok, err),
+            },
+        }
+    }
+}
+
+/// The type of a component function.
+///
+/// Parameters and results are lists of named types. The binary
+/// format also allows a single unnamed parameter or result, which is
+/// represented by an empty name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ComponentFuncType {
+    params: Vec<(String, InterfaceType)>,
+    results: Vec<(String, InterfaceType)>,
+}
+
+impl ComponentFuncType {
+    /// Creates a new component function type.
+    pub fn new(
+        params: Vec<(String, InterfaceType)>,
+        results: Vec<(String, InterfaceType)>,
+    ) -> Self {
+        Self { params, results }
+    }
+
+    /// The names and types of the parameters.
+    pub fn params(&self) -> &[(String, InterfaceType)] {
+        &self.params
+    }
+
+    /// The names and types of the results.
+    pub fn results(&self) -> &[(String, InterfaceType)] {
+        &self.results
+    }
+
+    /// Iterates over the types of the parameters.
+    pub fn param_types(&self) -> impl ExactSizeIterator<Item = &InterfaceType> + '_ {
+        self.params.iter().map(|(_, ty)| ty)
+    }
+
+    /// Iterates over the types of the results.
+    pub fn result_types(&self) -> impl ExactSizeIterator<Item = &InterfaceType> + '_ {
+        self.results.iter().map(|(_, ty)| ty)
+    }
+}
+
+impl fmt::Display for ComponentFuncType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn named(f: &mut fmt::Formatter<'_>, items: &[(String, InterfaceType)]) -> fmt::Result {
+            for (i, (name, ty)) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                if name.is_empty() {
+                    write!(f, "{}", ty)?;
+                } else {
+                    write!(f, "{}: {}", name, ty)?;
+                }
+            }
+            Ok(())
+        }
+
+        write!(f, "func(")?;
+        named(f, &self.params)?;
+        write!(f, ")")?;
+        match self.results.as_slice() {
+            [] => Ok(()),
+            [(name, ty)] if name.is_empty() => write!(f, " -> {}", ty),
+            results => {
+                write!(f, " -> (")?;
+                named(f, results)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// The type of an import or an export of a component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentExternType {
+    /// A component function.
+    Func(ComponentFuncType),
+    /// A component instance.
+    Instance(ComponentInstanceType),
+}
+
+/// The type of a component instance, i.e. the types of its exports.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentInstanceType {
+    exports: IndexMap<String, ComponentExternType>,
+}
+
+impl ComponentInstanceType {
+    /// Creates a new instance type from the types of its exports.
+    pub fn new(exports: impl IntoIterator<Item = (String, ComponentExternType)>) -> Self {
+        Self {
+            exports: exports.into_iter().collect(),
+        }
+    }
+
+    /// The type of the export named `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&ComponentExternType> {
+        self.exports.get(name)
+    }
+
+    /// Iterates over the names and types of the exports.
+    pub fn exports(&self) -> impl ExactSizeIterator<Item = (&str, &ComponentExternType)> + '_ {
+        self.exports.iter().map(|(name, ty)| (name.as_str(), ty))
+    }
+}
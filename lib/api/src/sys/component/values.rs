@@ -0,0 +1,116 @@
+/// A value crossing a component boundary, i.e. a value of an
+/// [`InterfaceType`](super::InterfaceType).
+///
+/// Values are dynamically typed; they are checked against the type
+/// expected by the component when they are lowered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentValue {
+    /// A boolean.
+    Bool(bool),
+    /// A signed 8-bit integer.
+    S8(i8),
+    /// An unsigned 8-bit integer.
+    U8(u8),
+    /// A signed 16-bit integer.
+    S16(i16),
+    /// An unsigned 16-bit integer.
+    U16(u16),
+    /// A signed 32-bit integer.
+    S32(i32),
+    /// An unsigned 32-bit integer.
+    U32(u32),
+    /// A signed 64-bit integer.
+    S64(i64),
+    /// An unsigned 64-bit integer.
+    U64(u64),
+    /// A 32-bit float.
+    Float32(f32),
+    /// A 64-bit float.
+    Float64(f64),
+    /// A Unicode scalar value.
+    Char(char),
+    /// A string.
+    String(String),
+    /// A list of values of the same type.
+    List(Vec<ComponentValue>),
+    /// A record, with its field names and values in declaration order.
+    Record(Vec<(String, ComponentValue)>),
+    /// A tuple.
+    Tuple(Vec<ComponentValue>),
+    /// A variant, with the name of its case and the case's payload.
+    Variant(String, Option<Box<ComponentValue>>),
+    /// An enum, with the name of its case.
+    Enum(String),
+    /// A set of flags, with the names of the flags that are set.
+    Flags(Vec<String>),
+    /// A union, with the index of its case and the case's payload.
+    Union(u32, Box<ComponentValue>),
+    /// An optional value.
+    Option(Option<Box<ComponentValue>>),
+    /// Either a success or an error, each with an optional payload.
+    Result(Result<Option<Box<ComponentValue>>, Option<Box<ComponentValue>>>),
+}
+
+impl ComponentValue {
+    /// The name of the kind of this value, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::S8(_) => "s8",
+            Self::U8(_) => "u8",
+            Self::S16(_) => "s16",
+            Self::U16(_) => "u16",
+            Self::S32(_) => "s32",
+            Self::U32(_) => "u32",
+            Self::S64(_) => "s64",
+            Self::U64(_) => "u64",
+            Self::Float32(_) => "float32",
+            Self::Float64(_) => "float64",
+            Self::Char(_) => "char",
+            Self::String(_) => "string",
+            Self::List(_) => "list",
+            Self::Record(_) => "record",
+            Self::Tuple(_) => "tuple",
+            Self::Variant(..) => "variant",
+            Self::Enum(_) => "enum",
+            Self::Flags(_) => "flags",
+            Self::Union(..) => "union",
+            Self::Option(_) => "option",
+            Self::Result(_) => "result",
+        }
+    }
+}
+
+macro_rules! from_primitive {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for ComponentValue {
+                fn from(val: $ty) -> Self {
+                    Self::$variant(val)
+                }
+            }
+        )*
+    };
+}
+
+from_primitive! {
+    bool => Bool,
+    i8 => S8,
+    u8 => U8,
+    i16 => S16,
+    u16 => U16,
+    i32 => S32,
+    u32 => U32,
+    i64 => S64,
+    u64 => U64,
+    f32 => Float32,
+    f64 => Float64,
+    char => Char,
+    String => String,
+}
+
+impl From<&str> for ComponentValue {
+    fn from(val: &str) -> Self {
+        Self::String(val.to_string())
+    }
+}
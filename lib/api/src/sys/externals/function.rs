@@ -11,12 +11,14 @@ pub use inner::{FromToNativeWasmType, HostFunction, WasmTypeList, WithEnv, Witho
 use std::cell::UnsafeCell;
 use std::cmp::max;
 use std::ffi::c_void;
+use std::future::Future;
+use std::pin::Pin;
 use wasmer_types::RawValue;
 use wasmer_vm::{
-    on_host_stack, raise_user_trap, resume_panic, wasmer_call_trampoline, InternalStoreHandle,
-    MaybeInstanceOwned, StoreHandle, VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext,
-    VMExtern, VMFuncRef, VMFunction, VMFunctionBody, VMFunctionContext, VMFunctionKind,
-    VMTrampoline,
+    on_host_stack, raise_user_trap, resume_panic, wasmer_call_trampoline, AsyncCall,
+    InternalStoreHandle, MaybeInstanceOwned, StoreHandle, VMCallerCheckedAnyfunc, VMContext,
+    VMDynamicFunctionContext, VMExtern, VMFuncRef, VMFunction, VMFunctionBody, VMFunctionContext,
+    VMFunctionKind, VMTrampoline,
 };
 
 /// A WebAssembly `function` instance.
@@ -324,6 +326,128 @@ impl Function {
         }
     }
 
+    #[cfg(feature = "compiler")]
+    /// Creates a new host `Function` (dynamic) with the provided signature,
+    /// whose implementation is asynchronous.
+    ///
+    /// The function can only be called from [`Function::call_async`] or
+    /// [`TypedFunction::call_async`], which suspend the call until the
+    /// future returned by `func` completes. Other calls fail with a
+    /// [`RuntimeError`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Function, FunctionType, Type, Store, Value};
+    /// # let mut store = Store::default();
+    /// #
+    /// let signature = FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I32]);
+    ///
+    /// let f = Function::new_async(&mut store, &signature, |args| {
+    ///     let sum = args[0].unwrap_i32() + args[1].unwrap_i32();
+    ///     async move { Ok(vec![Value::I32(sum)]) }
+    /// });
+    /// ```
+    pub fn new_async<FT, F, Fut>(store: &mut impl AsStoreMut, ty: FT, func: F) -> Self
+    where
+        FT: Into<FunctionType>,
+        F: Fn(&[Value]) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<Vec<Value>, RuntimeError>> + 'static + Send,
+    {
+        Self::new(store, ty, move |args| block_on(func(args)))
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Creates a new host `Function` from a typed function whose
+    /// implementation is asynchronous, see [`Function::new_async`].
+    ///
+    /// The arguments are passed as a tuple, or as a single value if the
+    /// function has one parameter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Function, RuntimeError, Store};
+    /// # let mut store = Store::default();
+    /// #
+    /// let f = Function::new_typed_async(&mut store, |(a, b): (i32, i32)| async move {
+    ///     Ok::<_, RuntimeError>(a + b)
+    /// });
+    /// ```
+    pub fn new_typed_async<F, Args, Rets, Fut>(store: &mut impl AsStoreMut, func: F) -> Self
+    where
+        F: Fn(Args) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<Rets, RuntimeError>> + 'static + Send,
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        let env = FunctionEnv::new(store, ());
+        Self::new_typed_with_env_async(store, &env, move |_env: FunctionEnvMut<()>, args| {
+            Box::pin(func(args))
+        })
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Creates a new host `Function` with an environment from a typed
+    /// function whose implementation is asynchronous, see
+    /// [`Function::new_async`].
+    ///
+    /// The arguments are passed as a tuple, or as a single value if the
+    /// function has one parameter. The future borrows the environment, so
+    /// that it can use the environment and the memories of the instance
+    /// after each `.await`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Function, FunctionEnv, FunctionEnvMut, RuntimeError, Store};
+    /// # let mut store = Store::default();
+    /// let env = FunctionEnv::new(&mut store, 0);
+    ///
+    /// let f = Function::new_typed_with_env_async(
+    ///     &mut store,
+    ///     &env,
+    ///     |mut env: FunctionEnvMut<i32>, a: i32| {
+    ///         Box::pin(async move {
+    ///             *env.data_mut() += a;
+    ///             Ok::<_, RuntimeError>(*env.data())
+    ///         })
+    ///     },
+    /// );
+    /// ```
+    pub fn new_typed_with_env_async<T: Send + 'static, F, Args, Rets>(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<T>,
+        func: F,
+    ) -> Self
+    where
+        F: for<'a> Fn(
+                FunctionEnvMut<'a, T>,
+                Args,
+            )
+                -> Pin<Box<dyn Future<Output = Result<Rets, RuntimeError>> + Send + 'a>>
+            + 'static
+            + Send
+            + Sync,
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        let ty = FunctionType::new(Args::wasm_types(), Rets::wasm_types());
+        Self::new_with_env(store, env, ty, move |mut env, args| {
+            let args = args.iter().map(|arg| arg.as_raw(&env)).collect::<Vec<_>>();
+            // The arguments match the signature of the function.
+            let args = unsafe { Args::from_slice(&mut env, &args) }
+                .expect("the arguments don't match the function signature");
+            let rets = block_on(func(env.as_mut(), args))?;
+            let mut rets = unsafe { rets.into_array(&mut env) };
+            Ok(Rets::wasm_types()
+                .iter()
+                .zip(rets.as_mut().iter())
+                .map(|(ty, ret)| unsafe { Value::from_raw(&mut env, *ty, *ret) })
+                .collect())
+        })
+    }
+
     /// Returns the [`FunctionType`] of the `Function`.
     ///
     /// # Example
@@ -517,6 +641,49 @@ impl Function {
         Ok(results.into_boxed_slice())
     }
 
+    #[cfg(feature = "compiler")]
+    /// Call the `Function` function asynchronously.
+    ///
+    /// The call runs on its own stack, which is suspended while the futures
+    /// of the asynchronous host functions it calls are pending, see
+    /// [`Function::new_async`]. Dropping the returned future cancels the
+    /// call: the pending asynchronous host functions fail with a
+    /// [`RuntimeError`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmer::{imports, wat2wasm, Function, Instance, Module, Store, Type, Value};
+    /// # let mut store = Store::default();
+    /// # let wasm_bytes = wat2wasm(r#"
+    /// # (module
+    /// #   (func (export "sum") (param $x i32) (param $y i32) (result i32)
+    /// #     local.get $x
+    /// #     local.get $y
+    /// #     i32.add
+    /// #   ))
+    /// # "#.as_bytes()).unwrap();
+    /// # let module = Module::new(&store, wasm_bytes).unwrap();
+    /// # let import_object = imports! {};
+    /// # let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    /// #
+    /// let sum = instance.exports.get_function("sum").unwrap();
+    ///
+    /// # futures::executor::block_on(async {
+    /// let result = sum.call_async(&mut store, &[Value::I32(1), Value::I32(2)]).await;
+    /// assert_eq!(result.unwrap().to_vec(), vec![Value::I32(3)]);
+    /// # });
+    /// ```
+    pub async fn call_async(
+        &self,
+        store: &mut (impl AsStoreMut + Send),
+        params: &[Value],
+    ) -> Result<Box<[Value]>, RuntimeError> {
+        AsyncCall::new(move || self.call(store, params))
+            .await
+            .map_err(RuntimeError::from_trap)?
+    }
+
     pub(crate) fn vm_funcref(&self, store: &impl AsStoreRef) -> VMFuncRef {
         let vm_function = self.handle.get(store.as_store_ref().objects());
         if vm_function.kind == VMFunctionKind::Dynamic {
@@ -712,6 +879,13 @@ impl<'a> Exportable<'a> for Function {
     }
 }
 
+/// Polls the future of an asynchronous host function from the current
+/// [`AsyncCall`].
+#[cfg(feature = "compiler")]
+fn block_on<R>(future: impl Future<Output = Result<R, RuntimeError>>) -> Result<R, RuntimeError> {
+    wasmer_vm::block_on(future).map_err(|error| RuntimeError::user(Box::new(error)))?
+}
+
 /// Host state for a dynamic function.
 pub(crate) struct DynamicFunction<F> {
     func: F,
@@ -0,0 +1,157 @@
+//! Parsing of the `dylink.0` custom section of shared WebAssembly
+//! modules.
+//!
+//! The format is described in the [tool conventions].
+//!
+//! [tool conventions]: https://github.com/WebAssembly/tool-conventions/blob/main/DynamicLinking.md
+
+use std::collections::HashSet;
+
+/// The name of the custom section of shared modules.
+pub(crate) const DYLINK_SECTION: &str = "dylink.0";
+/// The name of the custom section of shared modules produced by older
+/// toolchains, which has no subsections.
+pub(crate) const LEGACY_DYLINK_SECTION: &str = "dylink";
+
+const WASM_DYLINK_MEM_INFO: u8 = 1;
+const WASM_DYLINK_NEEDED: u8 = 2;
+const WASM_DYLINK_EXPORT_INFO: u8 = 3;
+const WASM_DYLINK_IMPORT_INFO: u8 = 4;
+
+const WASM_SYMBOL_BINDING_WEAK: u32 = 0x1;
+const WASM_SYMBOL_TLS: u32 = 0x100;
+
+/// The dynamic linking information of a shared module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DylinkInfo {
+    /// The size of the memory region the module needs, in bytes.
+    pub memory_size: u32,
+    /// The alignment of the memory region, in bytes.
+    pub memory_align: u32,
+    /// The number of table slots the module needs.
+    pub table_size: u32,
+    /// The alignment of the table region, in slots.
+    pub table_align: u32,
+    /// The shared libraries the module depends on.
+    pub needed: Vec<String>,
+    /// The imports that may be left undefined, as `(module, name)`.
+    pub weak_imports: HashSet<(String, String)>,
+    /// The exported symbols that are thread-local.
+    pub tls_exports: HashSet<String>,
+}
+
+impl DylinkInfo {
+    /// Parses the payload of a `dylink.0` section.
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader::new(data);
+        let mut info = Self::default();
+        while !reader.is_empty() {
+            let kind = reader.u8()?;
+            let len = reader.u32()? as usize;
+            let mut sub = Reader::new(reader.bytes(len)?);
+            match kind {
+                WASM_DYLINK_MEM_INFO => {
+                    info.memory_size = sub.u32()?;
+                    info.memory_align = sub.alignment()?;
+                    info.table_size = sub.u32()?;
+                    info.table_align = sub.alignment()?;
+                }
+                WASM_DYLINK_NEEDED => info.needed = sub.needed()?,
+                WASM_DYLINK_EXPORT_INFO => {
+                    for _ in 0..sub.u32()? {
+                        let name = sub.string()?;
+                        if sub.u32()? & WASM_SYMBOL_TLS != 0 {
+                            info.tls_exports.insert(name);
+                        }
+                    }
+                }
+                WASM_DYLINK_IMPORT_INFO => {
+                    for _ in 0..sub.u32()? {
+                        let module = sub.string()?;
+                        let name = sub.string()?;
+                        if sub.u32()? & WASM_SYMBOL_BINDING_WEAK != 0 {
+                            info.weak_imports.insert((module, name));
+                        }
+                    }
+                }
+                // Unknown subsections are skipped, as the conventions
+                // require.
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    /// Parses the payload of a legacy `dylink` section.
+    pub fn parse_legacy(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader::new(data);
+        Ok(Self {
+            memory_size: reader.u32()?,
+            memory_align: reader.alignment()?,
+            table_size: reader.u32()?,
+            table_align: reader.alignment()?,
+            needed: reader.needed()?,
+            ..Self::default()
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.data.len() {
+            return Err("unexpected end of the dylink section".to_string());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut result = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift == 28 && byte >> 4 != 0 {
+                return Err("invalid LEB128 integer in the dylink section".to_string());
+            }
+            result |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads an alignment, which is encoded as a power of two.
+    fn alignment(&mut self) -> Result<u32, String> {
+        1u32.checked_shl(self.u32()?)
+            .ok_or_else(|| "invalid alignment in the dylink section".to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| "invalid UTF-8 string in the dylink section".to_string())
+    }
+
+    fn needed(&mut self) -> Result<Vec<String>, String> {
+        (0..self.u32()?).map(|_| self.string()).collect()
+    }
+}
@@ -0,0 +1,1169 @@
+use super::dylink::{DylinkInfo, DYLINK_SECTION, LEGACY_DYLINK_SECTION};
+use crate::sys::externals::{Extern, Function, Global, Memory, Table};
+use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
+use crate::sys::imports::Imports;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::mem_access::MemoryAccessError;
+use crate::sys::module::Module;
+use crate::sys::ptr::WasmPtr;
+use crate::sys::store::{AsStoreMut, AsStoreRef};
+use crate::sys::value::Value;
+use crate::sys::{LinkError, RuntimeError};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use thiserror::Error;
+use wasmer_types::{ExternType, ImportError, Mutability, Pages, WASM_PAGE_SIZE};
+use wasmer_vm::MemoryError;
+
+/// The default size of the stack of the main module, in bytes.
+pub const DEFAULT_STACK_SIZE: u32 = 5 * 1024 * 1024;
+
+/// The address the data of a relocatable main module is placed at.
+///
+/// Low addresses are left unused so that null pointer dereferences
+/// don't silently read the data of the program.
+const GLOBAL_BASE: u32 = 1024;
+
+/// The exports used to allocate the memory of side modules, in order
+/// of preference.
+const ALLOCATORS: &[&str] = &["malloc", "_malloc"];
+
+/// The export applying the data relocations of a shared module.
+const APPLY_DATA_RELOCS: &str = "__wasm_apply_data_relocs";
+
+/// The exports running the constructors of a side module, in order of
+/// preference.
+const CONSTRUCTORS: &[&str] = &["__wasm_call_ctors", "__post_instantiate"];
+
+/// An error while loading a module with a [`Linker`] or looking up
+/// one of its symbols.
+#[derive(Error, Debug)]
+pub enum LinkerError {
+    /// The library was not registered with
+    /// [`Linker::register_library`].
+    #[error("library `{0}` was not found")]
+    LibraryNotFound(String),
+
+    /// A side module doesn't have a `dylink.0` section.
+    #[error("`{0}` is not a shared module")]
+    NotSharedModule(String),
+
+    /// The `dylink.0` section of a module is malformed.
+    #[error("invalid dylink section in `{0}`: {1}")]
+    InvalidDylinkSection(String, String),
+
+    /// A module uses a feature of dynamic linking that is not
+    /// supported.
+    #[error("`{0}` is not supported: {1}")]
+    Unsupported(String, String),
+
+    /// The main module was already loaded.
+    #[error("the main module is already loaded")]
+    MainModuleAlreadyLoaded,
+
+    /// A side module was loaded before the main module.
+    #[error("the main module must be loaded first")]
+    MainModuleNotLoaded,
+
+    /// The main module neither imports nor exports the memory or the
+    /// table that would be shared with side modules.
+    #[error("the main module doesn't share its {0}")]
+    NotShared(&'static str),
+
+    /// A symbol is not defined by any of the loaded modules.
+    #[error("undefined symbol `{0}`")]
+    UndefinedSymbol(String),
+
+    /// A library handle doesn't refer to an open library.
+    #[error("invalid library handle {0}")]
+    InvalidHandle(u32),
+
+    /// The memory of a side module could not be allocated.
+    #[error("cannot allocate {0} bytes")]
+    Allocation(u32),
+
+    /// The table slots of a side module could not be allocated.
+    #[error("cannot allocate {0} table slots")]
+    TableAllocation(u32),
+
+    /// The shared memory could not be created or grown.
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+
+    /// The shared memory was accessed out of bounds.
+    #[error(transparent)]
+    MemoryAccess(#[from] MemoryAccessError),
+
+    /// A module could not be instantiated.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+
+    /// A trap occurred while relocating or initializing a module.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// A handle to a library loaded by a [`Linker`].
+///
+/// Handles are never zero, so that they can be returned to the guest
+/// from `dlopen`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LibraryHandle(u32);
+
+impl LibraryHandle {
+    /// The handle of the main module.
+    pub const MAIN: Self = Self(1);
+
+    /// Creates a handle from the value returned to the guest, or
+    /// `None` if it is zero.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        if raw == 0 {
+            None
+        } else {
+            Some(Self(raw))
+        }
+    }
+
+    /// Returns the value of the handle passed to the guest.
+    pub fn to_raw(self) -> u32 {
+        self.0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize - 1
+    }
+
+    fn from_index(index: usize) -> Self {
+        Self(index as u32 + 1)
+    }
+}
+
+/// A symbol defined by a loaded module.
+#[derive(Debug, Clone)]
+pub enum Symbol {
+    /// An exported function.
+    Function(Function),
+    /// The address of exported data in the shared memory.
+    Data(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GotKind {
+    Memory,
+    Function,
+}
+
+/// An entry of the global offset table, imported from `GOT.mem` or
+/// `GOT.func`.
+struct GotEntry {
+    global: Global,
+    kind: GotKind,
+    resolved: bool,
+}
+
+struct Library {
+    name: String,
+    instance: Instance,
+    dylink: Option<DylinkInfo>,
+    exports: HashMap<String, Symbol>,
+    /// The symbols of the global namespace defined by this library.
+    defined: Vec<String>,
+    needed: Vec<LibraryHandle>,
+}
+
+struct LinkerState {
+    imports: Imports,
+    available: HashMap<String, Module>,
+    stack_size: u32,
+    memory: Option<Memory>,
+    table: Option<Table>,
+    stack_pointer: Option<Global>,
+    allocator: Option<Function>,
+    env: Option<FunctionEnv<Linker>>,
+    libraries: Vec<Library>,
+    symbols: HashMap<String, Symbol>,
+    got: HashMap<String, GotEntry>,
+    function_slots: HashMap<String, u32>,
+    lazy_functions: HashMap<String, Global>,
+    last_error: Option<String>,
+    error_buffer: (u32, u32),
+}
+
+/// A dynamic linker, which loads a main module and the shared
+/// libraries it depends on or opens at runtime.
+///
+/// The modules share the memory, the table and the stack pointer of
+/// the main module. Each side module gets its own region of the memory
+/// and of the table, described by its `dylink.0` section, and its
+/// `GOT.mem` and `GOT.func` imports are resolved to the addresses of
+/// the data and functions exported by the other modules. Functions
+/// imported from `env` that are not defined yet are called through a
+/// slot, which is set once a module defining them is loaded.
+///
+/// Cloning a `Linker` returns a new handle to the same linker, so that
+/// it can be captured by host functions.
+#[derive(Clone)]
+pub struct Linker {
+    state: Arc<Mutex<LinkerState>>,
+}
+
+impl Linker {
+    /// Creates a new `Linker` that resolves the imports of the modules
+    /// it loads with `imports` first.
+    ///
+    /// The memory and the table of the main module are taken from
+    /// `env.memory` and `env.table` (or `env.__indirect_function_table`)
+    /// in `imports` when they are defined there.
+    pub fn new(imports: &Imports) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(LinkerState {
+                imports: imports.clone(),
+                available: HashMap::new(),
+                stack_size: DEFAULT_STACK_SIZE,
+                memory: None,
+                table: None,
+                stack_pointer: None,
+                allocator: None,
+                env: None,
+                libraries: Vec::new(),
+                symbols: HashMap::new(),
+                got: HashMap::new(),
+                function_slots: HashMap::new(),
+                lazy_functions: HashMap::new(),
+                last_error: None,
+                error_buffer: (0, 0),
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, LinkerState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Adds a host import used to instantiate the modules loaded
+    /// afterwards.
+    pub fn define(&self, ns: &str, name: &str, val: impl Into<Extern>) {
+        self.state().imports.define(ns, name, val);
+    }
+
+    /// Makes a shared library available to be loaded as a dependency
+    /// of another module or with [`Linker::dlopen`].
+    pub fn register_library(&self, name: &str, module: Module) {
+        self.state().available.insert(name.to_string(), module);
+    }
+
+    /// Sets the size of the stack of the main module, in bytes.
+    ///
+    /// It defaults to [`DEFAULT_STACK_SIZE`], and must be set before
+    /// the main module is loaded.
+    pub fn set_stack_size(&self, stack_size: u32) {
+        self.state().stack_size = stack_size;
+    }
+
+    /// Returns the memory shared by the loaded modules.
+    pub fn memory(&self) -> Option<Memory> {
+        self.state().memory.clone()
+    }
+
+    /// Returns the table shared by the loaded modules.
+    pub fn table(&self) -> Option<Table> {
+        self.state().table.clone()
+    }
+
+    /// Instantiates the main module and loads the libraries listed in
+    /// its `dylink.0` section.
+    ///
+    /// The main module can be relocatable, in which case its data is
+    /// placed at a fixed address followed by its stack, or a regular
+    /// module that exports its memory and its table (as
+    /// `__indirect_function_table`). Its constructors are not run:
+    /// they are run by its `_start` or `_initialize` export.
+    pub fn instantiate_main(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<Instance, LinkerError> {
+        if !self.state().libraries.is_empty() {
+            return Err(LinkerError::MainModuleAlreadyLoaded);
+        }
+        let result = self.load_main(store, module);
+        if result.is_err() {
+            self.state().unload_from(store, 0);
+        }
+        result
+    }
+
+    fn load_main(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<Instance, LinkerError> {
+        let name = module.name().unwrap_or("main").to_string();
+        let dylink = read_dylink(&name, module)?;
+        let (memory_base, externs) = {
+            let mut state = self.state();
+            state.create_shared(store, module)?;
+            let (memory_base, table_base) = match &dylink {
+                Some(dylink) => state.place_main(store, dylink)?,
+                None => (0, 0),
+            };
+            let externs = state.resolve_imports(store, self, module, memory_base, table_base)?;
+            (memory_base, externs)
+        };
+        let instance = Instance::new_by_index(store, module, &externs)?;
+
+        let needed = dylink
+            .as_ref()
+            .map(|dylink| dylink.needed.clone())
+            .unwrap_or_default();
+        let handle = {
+            let mut state = self.state();
+            if state.memory.is_none() {
+                state.memory = instance.exports.get_memory("memory").ok().cloned();
+            }
+            if state.table.is_none() {
+                state.table = instance
+                    .exports
+                    .get_table("__indirect_function_table")
+                    .ok()
+                    .cloned();
+            }
+            if state.stack_pointer.is_none() {
+                state.stack_pointer = instance.exports.get_global("__stack_pointer").ok().cloned();
+            }
+            state.allocator = ALLOCATORS
+                .iter()
+                .find_map(|name| instance.exports.get_function(name).ok())
+                .cloned();
+            state.register(store, name, instance.clone(), memory_base, dylink)
+        };
+        let mut pending = vec![handle];
+        self.instantiate_needed(store, handle, &needed, &mut pending)?;
+        self.initialize(store, &pending)?;
+        Ok(instance)
+    }
+
+    /// Loads a registered shared library and the libraries it depends
+    /// on, and returns a handle to it.
+    ///
+    /// Opening a library that is already loaded returns the same
+    /// handle. Libraries are looked up by the name they were registered
+    /// with, then by the file name of `name`.
+    pub fn dlopen(
+        &self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+    ) -> Result<LibraryHandle, LinkerError> {
+        let loaded = self.state().libraries.len();
+        let mut pending = Vec::new();
+        let result = self
+            .instantiate_library(store, name, &mut pending)
+            .and_then(|handle| self.initialize(store, &pending).map(|()| handle));
+        if result.is_err() {
+            self.state().unload_from(store, loaded);
+        }
+        self.record(result)
+    }
+
+    /// Returns the address of a symbol, which is the index of a
+    /// function in the shared table or the address of data in the
+    /// shared memory.
+    ///
+    /// The symbol is looked up in the library `handle` and its
+    /// dependencies, or in all the loaded modules if `handle` is
+    /// `None`.
+    pub fn dlsym(
+        &self,
+        store: &mut impl AsStoreMut,
+        handle: Option<LibraryHandle>,
+        name: &str,
+    ) -> Result<u32, LinkerError> {
+        let result = self
+            .lookup(handle, name)
+            .and_then(|symbol| self.state().address_of(store, name, symbol));
+        self.record(result)
+    }
+
+    /// Closes a library opened with [`Linker::dlopen`].
+    ///
+    /// Libraries stay loaded once they are closed, as other modules
+    /// may still hold pointers to their code and data, so this only
+    /// checks that `handle` is valid.
+    pub fn dlclose(&self, handle: LibraryHandle) -> Result<(), LinkerError> {
+        let loaded = self.state().libraries.len();
+        let result = if handle.index() < loaded {
+            Ok(())
+        } else {
+            Err(LinkerError::InvalidHandle(handle.to_raw()))
+        };
+        self.record(result)
+    }
+
+    /// Returns and clears the message of the last error of
+    /// [`Linker::dlopen`], [`Linker::dlsym`] or [`Linker::dlclose`].
+    pub fn dlerror(&self) -> Option<String> {
+        self.state().last_error.take()
+    }
+
+    /// Looks up a symbol in the library `handle` and its dependencies,
+    /// or in all the loaded modules if `handle` is `None`.
+    pub fn lookup(&self, handle: Option<LibraryHandle>, name: &str) -> Result<Symbol, LinkerError> {
+        let state = self.state();
+        let handle = match handle {
+            Some(handle) => handle,
+            None => {
+                return state
+                    .symbols
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| LinkerError::UndefinedSymbol(name.to_string()))
+            }
+        };
+        if handle.index() >= state.libraries.len() {
+            return Err(LinkerError::InvalidHandle(handle.to_raw()));
+        }
+        let mut queue = vec![handle];
+        let mut visited = vec![];
+        while let Some(handle) = queue.pop() {
+            if visited.contains(&handle) {
+                continue;
+            }
+            visited.push(handle);
+            let library = &state.libraries[handle.index()];
+            if let Some(symbol) = library.exports.get(name) {
+                return Ok(symbol.clone());
+            }
+            queue.extend(library.needed.iter().rev());
+        }
+        Err(LinkerError::UndefinedSymbol(name.to_string()))
+    }
+
+    /// Returns the function `name` exported by one of the loaded
+    /// modules.
+    pub fn get_function(&self, name: &str) -> Option<Function> {
+        match self.state().symbols.get(name) {
+            Some(Symbol::Function(function)) => Some(function.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the `dlopen`, `dlsym`, `dlclose` and `dlerror`
+    /// functions in the `env` namespace, to be imported by the guest.
+    ///
+    /// They follow the C signatures, with `RTLD_DEFAULT` being the
+    /// null handle. `dlopen(NULL, ...)` returns the handle of the main
+    /// module.
+    pub fn dl_imports(&self, store: &mut impl AsStoreMut) -> Imports {
+        let env = FunctionEnv::new(store, self.clone());
+        let mut imports = Imports::new();
+        imports.define(
+            "env",
+            "dlopen",
+            Function::new_typed_with_env(store, &env, guest_dlopen),
+        );
+        imports.define(
+            "env",
+            "dlsym",
+            Function::new_typed_with_env(store, &env, guest_dlsym),
+        );
+        imports.define(
+            "env",
+            "dlclose",
+            Function::new_typed_with_env(store, &env, guest_dlclose),
+        );
+        imports.define(
+            "env",
+            "dlerror",
+            Function::new_typed_with_env(store, &env, guest_dlerror),
+        );
+        imports
+    }
+
+    /// Reads a null-terminated string from the shared memory.
+    pub fn read_c_string(&self, store: &impl AsStoreRef, ptr: u32) -> Result<String, LinkerError> {
+        let memory = self.memory().ok_or(LinkerError::MainModuleNotLoaded)?;
+        let view = memory.view(store);
+        Ok(WasmPtr::<u8>::new(ptr).read_utf8_string_with_nul(&view)?)
+    }
+
+    /// Copies the last error into the shared memory as a
+    /// null-terminated string, and returns its address, or 0 if there
+    /// was no error.
+    ///
+    /// The buffer is reused by the following calls.
+    pub fn dlerror_ptr(&self, store: &mut impl AsStoreMut) -> Result<u32, LinkerError> {
+        let message = match self.dlerror() {
+            Some(message) => message,
+            None => return Ok(0),
+        };
+        let len = message.len() as u32 + 1;
+        let (mut ptr, capacity) = self.state().error_buffer;
+        if capacity < len {
+            ptr = self.allocate(store, len, 1)?;
+            self.state().error_buffer = (ptr, len);
+        }
+        let memory = self.memory().ok_or(LinkerError::MainModuleNotLoaded)?;
+        let view = memory.view(store);
+        view.write(ptr as u64, message.as_bytes())?;
+        view.write_u8(ptr as u64 + message.len() as u64, 0)?;
+        Ok(ptr)
+    }
+
+    fn record<T>(&self, result: Result<T, LinkerError>) -> Result<T, LinkerError> {
+        if let Err(error) = &result {
+            self.state().last_error = Some(error.to_string());
+        }
+        result
+    }
+
+    /// Instantiates a side module and, after it, the libraries it
+    /// depends on, without relocating or initializing them.
+    ///
+    /// The handles of the newly instantiated libraries are pushed to
+    /// `pending`, dependencies first.
+    fn instantiate_library(
+        &self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        pending: &mut Vec<LibraryHandle>,
+    ) -> Result<LibraryHandle, LinkerError> {
+        let (name, module) = {
+            let state = self.state();
+            if state.libraries.is_empty() {
+                return Err(LinkerError::MainModuleNotLoaded);
+            }
+            if let Some(handle) = state.find_library(name) {
+                return Ok(handle);
+            }
+            state.find_module(name)?
+        };
+        let dylink = read_dylink(&name, &module)?
+            .ok_or_else(|| LinkerError::NotSharedModule(name.clone()))?;
+        if let Some(symbol) = dylink.tls_exports.iter().next() {
+            return Err(LinkerError::Unsupported(
+                name,
+                format!("thread-local symbol `{}`", symbol),
+            ));
+        }
+
+        let imports_stack_pointer = module
+            .imports()
+            .any(|import| import.module() == "env" && import.name() == "__stack_pointer");
+        if imports_stack_pointer {
+            self.ensure_stack_pointer(store)?;
+        }
+        let memory_base = self.allocate(store, dylink.memory_size, dylink.memory_align)?;
+        let externs = {
+            let mut state = self.state();
+            let table_base = state.reserve_table(store, dylink.table_size, dylink.table_align)?;
+            state.resolve_imports(store, self, &module, memory_base, table_base)?
+        };
+        let instance = Instance::new_by_index(store, &module, &externs)?;
+
+        let needed = dylink.needed.clone();
+        let handle = self
+            .state()
+            .register(store, name, instance, memory_base, Some(dylink));
+        self.instantiate_needed(store, handle, &needed, pending)?;
+        pending.push(handle);
+        Ok(handle)
+    }
+
+    fn instantiate_needed(
+        &self,
+        store: &mut impl AsStoreMut,
+        handle: LibraryHandle,
+        needed: &[String],
+        pending: &mut Vec<LibraryHandle>,
+    ) -> Result<(), LinkerError> {
+        for name in needed {
+            let dependency = self.instantiate_library(store, name, pending)?;
+            self.state().libraries[handle.index()]
+                .needed
+                .push(dependency);
+        }
+        Ok(())
+    }
+
+    /// Resolves the global offset table, then applies the relocations
+    /// of the `pending` libraries and runs the constructors of the side
+    /// modules among them.
+    fn initialize(
+        &self,
+        store: &mut impl AsStoreMut,
+        pending: &[LibraryHandle],
+    ) -> Result<(), LinkerError> {
+        let instances = {
+            let mut state = self.state();
+            state.resolve_got(store)?;
+            for handle in pending {
+                state.check_undefined(*handle)?;
+            }
+            pending
+                .iter()
+                .map(|handle| {
+                    let library = &state.libraries[handle.index()];
+                    (*handle, library.instance.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+        for (_, instance) in &instances {
+            if let Ok(relocate) = instance.exports.get_function(APPLY_DATA_RELOCS) {
+                relocate.call(store, &[])?;
+            }
+        }
+        for (handle, instance) in &instances {
+            if *handle == LibraryHandle::MAIN {
+                continue;
+            }
+            let constructors = CONSTRUCTORS
+                .iter()
+                .find_map(|name| instance.exports.get_function(name).ok());
+            if let Some(constructors) = constructors {
+                constructors.call(store, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocates a zeroed region of the shared memory, with the
+    /// allocator exported by the main module if there is one, or by
+    /// growing the memory otherwise.
+    fn allocate(
+        &self,
+        store: &mut impl AsStoreMut,
+        size: u32,
+        align: u32,
+    ) -> Result<u32, LinkerError> {
+        if size == 0 {
+            return Ok(0);
+        }
+        let align = align.max(1);
+        let (memory, allocator) = {
+            let state = self.state();
+            let memory = state
+                .memory
+                .clone()
+                .ok_or(LinkerError::NotShared("memory"))?;
+            (memory, state.allocator.clone())
+        };
+        let padded = size
+            .checked_add(align - 1)
+            .ok_or(LinkerError::Allocation(size))?;
+        // The allocated region must end within the 32-bit address space
+        let fits = |start: u32| start.checked_add(size).map(|_| start);
+        let start = match allocator {
+            Some(allocator) => {
+                let result = allocator.call(store, &[Value::I32(padded as i32)])?;
+                match result.first() {
+                    Some(Value::I32(ptr)) if *ptr != 0 => {
+                        align_up(*ptr as u32, align).and_then(fits)
+                    }
+                    _ => None,
+                }
+                .ok_or(LinkerError::Allocation(size))?
+            }
+            None => {
+                let end = memory.view(store).data_size();
+                let start = u32::try_from(end)
+                    .ok()
+                    .and_then(|end| align_up(end, align))
+                    .and_then(fits)
+                    .ok_or(LinkerError::Allocation(size))?;
+                grow_to(store, &memory, start as u64 + size as u64)?;
+                start
+            }
+        };
+        memory
+            .view(store)
+            .write(start as u64, &vec![0; size as usize])?;
+        Ok(start)
+    }
+
+    /// Creates the stack pointer of the side modules when the main
+    /// module doesn't share its own, with a stack allocated from the
+    /// shared memory.
+    fn ensure_stack_pointer(&self, store: &mut impl AsStoreMut) -> Result<(), LinkerError> {
+        let stack_size = {
+            let state = self.state();
+            if state.stack_pointer.is_some()
+                || state.imports.get_export("env", "__stack_pointer").is_some()
+            {
+                return Ok(());
+            }
+            state.stack_size
+        };
+        let stack_low = self.allocate(store, stack_size, 16)?;
+        let stack_pointer = Global::new_mut(store, Value::I32((stack_low + stack_size) as i32));
+        self.state().stack_pointer = Some(stack_pointer);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Linker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("Linker")
+            .field(
+                "libraries",
+                &state
+                    .libraries
+                    .iter()
+                    .map(|library| &library.name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl LinkerState {
+    /// Takes the memory and the table to share from the host imports,
+    /// or creates them if the main module imports them.
+    fn create_shared(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<(), LinkerError> {
+        self.memory = match self.imports.get_export("env", "memory") {
+            Some(Extern::Memory(memory)) => Some(memory),
+            _ => None,
+        };
+        self.table = ["table", "__indirect_function_table"]
+            .iter()
+            .find_map(|name| match self.imports.get_export("env", name) {
+                Some(Extern::Table(table)) => Some(table),
+                _ => None,
+            });
+        for import in module.imports() {
+            if import.module() != "env" {
+                continue;
+            }
+            match (import.name(), import.ty()) {
+                ("memory", ExternType::Memory(ty)) if self.memory.is_none() => {
+                    self.memory = Some(Memory::new(store, *ty)?);
+                }
+                ("table" | "__indirect_function_table", ExternType::Table(ty))
+                    if self.table.is_none() =>
+                {
+                    let table = Table::new(store, *ty, Value::FuncRef(None))?;
+                    self.table = Some(table);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Places the data of a relocatable main module, followed by its
+    /// stack, and returns its memory and table bases.
+    fn place_main(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        dylink: &DylinkInfo,
+    ) -> Result<(u32, u32), LinkerError> {
+        let memory = self
+            .memory
+            .clone()
+            .ok_or(LinkerError::NotShared("memory"))?;
+        let memory_base = align_up(GLOBAL_BASE, dylink.memory_align)
+            .ok_or(LinkerError::Allocation(dylink.memory_size))?;
+        let stack_low = memory_base
+            .checked_add(dylink.memory_size)
+            .and_then(|end| align_up(end, 16))
+            .ok_or(LinkerError::Allocation(dylink.memory_size))?;
+        let stack_high = stack_low
+            .checked_add(self.stack_size)
+            .ok_or(LinkerError::Allocation(self.stack_size))?;
+        grow_to(store, &memory, stack_high as u64)?;
+
+        if self.stack_pointer.is_none()
+            && self.imports.get_export("env", "__stack_pointer").is_none()
+        {
+            let stack_pointer = Global::new_mut(store, Value::I32(stack_high as i32));
+            self.stack_pointer = Some(stack_pointer);
+        }
+        self.symbols
+            .insert("__stack_low".to_string(), Symbol::Data(stack_low));
+        self.symbols
+            .insert("__stack_high".to_string(), Symbol::Data(stack_high));
+        self.symbols
+            .insert("__heap_base".to_string(), Symbol::Data(stack_high));
+
+        let table_base = self.reserve_table(store, dylink.table_size, dylink.table_align)?;
+        Ok((memory_base, table_base))
+    }
+
+    /// Grows the shared table to make room for `size` slots aligned to
+    /// `align`, and returns the index of the first one.
+    ///
+    /// The slot 0 is never used, as it is the null function pointer.
+    fn reserve_table(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        size: u32,
+        align: u32,
+    ) -> Result<u32, LinkerError> {
+        let table = self.table.clone().ok_or(LinkerError::NotShared("table"))?;
+        let current = table.size(store);
+        let table_base = align_up(current.max(1), align.max(1))
+            .filter(|base| base.checked_add(size).is_some())
+            .ok_or(LinkerError::TableAllocation(size))?;
+        let delta = table_base - current + size;
+        if delta > 0 {
+            table.grow(store, delta, Value::FuncRef(None))?;
+        }
+        Ok(table_base)
+    }
+
+    fn find_library(&self, name: &str) -> Option<LibraryHandle> {
+        self.libraries
+            .iter()
+            .position(|library| library.name == name)
+            .map(LibraryHandle::from_index)
+    }
+
+    fn find_module(&self, name: &str) -> Result<(String, Module), LinkerError> {
+        if let Some(module) = self.available.get(name) {
+            return Ok((name.to_string(), module.clone()));
+        }
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        self.available
+            .get_key_value(file_name)
+            .map(|(name, module)| (name.clone(), module.clone()))
+            .ok_or_else(|| LinkerError::LibraryNotFound(name.to_string()))
+    }
+
+    /// Resolves the imports of a module, in the order they are
+    /// declared.
+    fn resolve_imports(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        linker: &Linker,
+        module: &Module,
+        memory_base: u32,
+        table_base: u32,
+    ) -> Result<Vec<Extern>, LinkerError> {
+        let mut externs = Vec::new();
+        for import in module.imports() {
+            if let Some(extern_) = self.imports.get_export(import.module(), import.name()) {
+                externs.push(extern_);
+                continue;
+            }
+            let extern_: Extern = match (import.module(), import.name(), import.ty()) {
+                ("env", "memory", ExternType::Memory(_)) => self
+                    .memory
+                    .clone()
+                    .ok_or(LinkerError::NotShared("memory"))?
+                    .into(),
+                ("env", "table" | "__indirect_function_table", ExternType::Table(_)) => self
+                    .table
+                    .clone()
+                    .ok_or(LinkerError::NotShared("table"))?
+                    .into(),
+                ("env", "__memory_base" | "memoryBase", ExternType::Global(_)) => {
+                    Global::new(store, Value::I32(memory_base as i32)).into()
+                }
+                ("env", "__table_base" | "tableBase", ExternType::Global(_)) => {
+                    Global::new(store, Value::I32(table_base as i32)).into()
+                }
+                ("env", "__stack_pointer", ExternType::Global(_)) => self
+                    .stack_pointer
+                    .clone()
+                    .ok_or(LinkerError::NotShared("stack pointer"))?
+                    .into(),
+                ("GOT.mem", name, ExternType::Global(_)) => {
+                    self.got_entry(store, name, GotKind::Memory).into()
+                }
+                ("GOT.func", name, ExternType::Global(_)) => {
+                    self.got_entry(store, name, GotKind::Function).into()
+                }
+                ("env", name, ExternType::Function(ty)) => match self.symbols.get(name) {
+                    Some(Symbol::Function(function)) => function.clone().into(),
+                    _ => {
+                        let slot = self.lazy_slot(store, name);
+                        let env = self.env(store, linker);
+                        lazy_function(store, &env, name, ty.clone(), slot).into()
+                    }
+                },
+                ("env", name, ExternType::Global(_)) => match self.symbols.get(name) {
+                    Some(Symbol::Data(address)) => {
+                        Global::new(store, Value::I32(*address as i32)).into()
+                    }
+                    _ => return Err(LinkerError::UndefinedSymbol(name.to_string())),
+                },
+                (ns, name, ty) => {
+                    return Err(InstantiationError::Link(LinkError::Import(
+                        ns.to_string(),
+                        name.to_string(),
+                        ImportError::UnknownImport(ty.clone()),
+                    ))
+                    .into())
+                }
+            };
+            externs.push(extern_);
+        }
+        Ok(externs)
+    }
+
+    fn env(&mut self, store: &mut impl AsStoreMut, linker: &Linker) -> FunctionEnv<Linker> {
+        self.env
+            .get_or_insert_with(|| FunctionEnv::new(store, linker.clone()))
+            .clone()
+    }
+
+    fn got_entry(&mut self, store: &mut impl AsStoreMut, name: &str, kind: GotKind) -> Global {
+        self.got
+            .entry(name.to_string())
+            .or_insert_with(|| GotEntry {
+                global: Global::new_mut(store, Value::I32(0)),
+                kind,
+                resolved: false,
+            })
+            .global
+            .clone()
+    }
+
+    fn lazy_slot(&mut self, store: &mut impl AsStoreMut, name: &str) -> Global {
+        self.lazy_functions
+            .entry(name.to_string())
+            .or_insert_with(|| Global::new_mut(store, Value::FuncRef(None)))
+            .clone()
+    }
+
+    /// Adds a loaded module and its exports to the symbol table.
+    fn register(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        name: String,
+        instance: Instance,
+        memory_base: u32,
+        dylink: Option<DylinkInfo>,
+    ) -> LibraryHandle {
+        let mut exports = HashMap::new();
+        let mut defined = Vec::new();
+        for (export_name, extern_) in instance.exports.iter() {
+            let symbol = match extern_ {
+                Extern::Function(function) => Symbol::Function(function.clone()),
+                Extern::Global(global) if global.ty(store).mutability == Mutability::Const => {
+                    match global.get(store) {
+                        Value::I32(offset) => Symbol::Data(memory_base.wrapping_add(offset as u32)),
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if !self.symbols.contains_key(export_name) {
+                self.symbols.insert(export_name.clone(), symbol.clone());
+                defined.push(export_name.clone());
+            }
+            exports.insert(export_name.clone(), symbol);
+        }
+        self.libraries.push(Library {
+            name,
+            instance,
+            dylink,
+            exports,
+            defined,
+            needed: Vec::new(),
+        });
+        LibraryHandle::from_index(self.libraries.len() - 1)
+    }
+
+    /// Forgets the libraries loaded after the first `loaded` ones,
+    /// after one of them failed to load.
+    ///
+    /// The symbols they defined are unresolved again, so that a library
+    /// loaded later can define them. The memory and the table regions
+    /// they were given are not reclaimed.
+    fn unload_from(&mut self, store: &mut impl AsStoreMut, loaded: usize) {
+        // The global offset table and the slots were set to the symbols
+        // of the libraries if it was resolved before one of them failed
+        for library in self.libraries.drain(loaded..) {
+            for name in library.defined {
+                self.symbols.remove(&name);
+                if let Some(slot) = self.lazy_functions.get(&name) {
+                    let _ = slot.set(store, Value::FuncRef(None));
+                }
+                if let Some(entry) = self.got.get_mut(&name) {
+                    let _ = entry.global.set(store, Value::I32(0));
+                    entry.resolved = false;
+                }
+                if let Some(slot) = self.function_slots.remove(&name) {
+                    if let Some(table) = &self.table {
+                        let _ = table.set(store, slot, Value::FuncRef(None));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets the entries of the global offset table and the slots of the
+    /// lazily bound functions whose symbols are now defined.
+    fn resolve_got(&mut self, store: &mut impl AsStoreMut) -> Result<(), LinkerError> {
+        for (name, slot) in &self.lazy_functions {
+            if let Some(Symbol::Function(function)) = self.symbols.get(name) {
+                slot.set(store, Value::FuncRef(Some(function.clone())))?;
+            }
+        }
+        let unresolved = self
+            .got
+            .iter()
+            .filter(|(_, entry)| !entry.resolved)
+            .map(|(name, entry)| (name.clone(), entry.kind))
+            .collect::<Vec<_>>();
+        for (name, kind) in unresolved {
+            let value = match (kind, self.symbols.get(&name).cloned()) {
+                (GotKind::Memory, Some(Symbol::Data(address))) => address,
+                (_, Some(Symbol::Function(function))) => {
+                    self.function_slot(store, &name, function)?
+                }
+                _ => continue,
+            };
+            let entry = self.got.get_mut(&name).unwrap();
+            entry.global.set(store, Value::I32(value as i32))?;
+            entry.resolved = true;
+        }
+        Ok(())
+    }
+
+    /// Fails if a library imports symbols from the global offset table
+    /// that are still undefined and not weak.
+    fn check_undefined(&self, handle: LibraryHandle) -> Result<(), LinkerError> {
+        let library = &self.libraries[handle.index()];
+        for import in library.instance.module().imports() {
+            if import.module() != "GOT.mem" && import.module() != "GOT.func" {
+                continue;
+            }
+            let resolved = self
+                .got
+                .get(import.name())
+                .map_or(false, |entry| entry.resolved);
+            let weak = library.dylink.as_ref().map_or(false, |dylink| {
+                dylink
+                    .weak_imports
+                    .contains(&(import.module().to_string(), import.name().to_string()))
+            });
+            if !resolved && !weak {
+                return Err(LinkerError::UndefinedSymbol(import.name().to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the address of a symbol, adding functions to the shared
+    /// table the first time their address is taken.
+    fn address_of(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        symbol: Symbol,
+    ) -> Result<u32, LinkerError> {
+        match symbol {
+            Symbol::Data(address) => Ok(address),
+            Symbol::Function(function) => self.function_slot(store, name, function),
+        }
+    }
+
+    fn function_slot(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        function: Function,
+    ) -> Result<u32, LinkerError> {
+        if let Some(slot) = self.function_slots.get(name) {
+            return Ok(*slot);
+        }
+        let table = self.table.clone().ok_or(LinkerError::NotShared("table"))?;
+        let slot = table.grow(store, 1, Value::FuncRef(Some(function)))?;
+        self.function_slots.insert(name.to_string(), slot);
+        Ok(slot)
+    }
+}
+
+/// Creates a function that calls the function `name` through `slot`,
+/// which the linker sets when a module defining `name` is loaded.
+fn lazy_function(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<Linker>,
+    name: &str,
+    ty: wasmer_types::FunctionType,
+    slot: Global,
+) -> Function {
+    let name = name.to_string();
+    Function::new_with_env(store, env, ty, move |mut env, args| {
+        let function = match slot.get(&mut env) {
+            Value::FuncRef(Some(function)) => function,
+            _ => return Err(RuntimeError::new(format!("undefined symbol `{}`", name))),
+        };
+        Ok(function.call(&mut env, args)?.into_vec())
+    })
+}
+
+fn guest_dlopen(mut env: FunctionEnvMut<Linker>, filename: u32, _flags: i32) -> u32 {
+    let linker = env.data().clone();
+    if filename == 0 {
+        return LibraryHandle::MAIN.to_raw();
+    }
+    linker
+        .read_c_string(&env, filename)
+        .and_then(|name| linker.dlopen(&mut env, &name))
+        .map_or(0, LibraryHandle::to_raw)
+}
+
+fn guest_dlsym(mut env: FunctionEnvMut<Linker>, handle: u32, symbol: u32) -> u32 {
+    let linker = env.data().clone();
+    linker
+        .read_c_string(&env, symbol)
+        .and_then(|name| linker.dlsym(&mut env, LibraryHandle::from_raw(handle), &name))
+        .unwrap_or(0)
+}
+
+fn guest_dlclose(env: FunctionEnvMut<Linker>, handle: u32) -> i32 {
+    let linker = env.data().clone();
+    let result = match LibraryHandle::from_raw(handle) {
+        Some(handle) => linker.dlclose(handle),
+        None => Err(LinkerError::InvalidHandle(handle)),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+fn guest_dlerror(mut env: FunctionEnvMut<Linker>) -> u32 {
+    let linker = env.data().clone();
+    linker.dlerror_ptr(&mut env).unwrap_or(0)
+}
+
+/// Reads the `dylink.0` section of a module, if it has one.
+fn read_dylink(name: &str, module: &Module) -> Result<Option<DylinkInfo>, LinkerError> {
+    let invalid = |error| LinkerError::InvalidDylinkSection(name.to_string(), error);
+    if let Some(section) = module.custom_sections(DYLINK_SECTION).next() {
+        return DylinkInfo::parse(&section).map(Some).map_err(invalid);
+    }
+    if let Some(section) = module.custom_sections(LEGACY_DYLINK_SECTION).next() {
+        return DylinkInfo::parse_legacy(&section)
+            .map(Some)
+            .map_err(invalid);
+    }
+    Ok(None)
+}
+
+/// Grows `memory` so that it is at least `size` bytes long.
+fn grow_to(store: &mut impl AsStoreMut, memory: &Memory, size: u64) -> Result<(), LinkerError> {
+    let current = memory.view(store).data_size();
+    if size > current {
+        let page_size = WASM_PAGE_SIZE as u64;
+        let delta = (size - current + page_size - 1) / page_size;
+        memory.grow(store, Pages(delta as u32))?;
+    }
+    Ok(())
+}
+
+/// Rounds `value` up to a multiple of `align`, unless the result doesn't
+/// fit in 32 bits.
+fn align_up(value: u32, align: u32) -> Option<u32> {
+    Some(value.checked_add(align - 1)? / align * align)
+}
@@ -0,0 +1,61 @@
+//! The `linker` module contains a dynamic linker for WebAssembly
+//! modules that follow the [dynamic linking conventions], as produced
+//! by Emscripten (`MAIN_MODULE` and `SIDE_MODULE`) or by `wasm-ld
+//! -shared`.
+//!
+//! A [`Linker`] instantiates a main module, then the side modules it
+//! depends on or that are opened at runtime with
+//! [`Linker::dlopen`]. All the modules share one memory, one table and
+//! one stack pointer. The guest can open libraries and look up symbols
+//! itself by importing the functions returned by
+//! [`Linker::dl_imports`].
+//!
+//! Thread-local storage in side modules is not supported.
+//!
+//! # Example
+//!
+//! ```
+//! # use wasmer::{imports, Module, Store};
+//! # use wasmer::linker::Linker;
+//! # fn main() -> anyhow::Result<()> {
+//! let mut store = Store::default();
+//! let main = Module::new(
+//!     &store,
+//!     r#"
+//! (module
+//!   (import "env" "add" (func $add (param i32 i32) (result i32)))
+//!   (memory (export "memory") 1)
+//!   (table (export "__indirect_function_table") 1 funcref)
+//!   (func (export "run") (result i32)
+//!     (call $add (i32.const 1) (i32.const 2))))
+//! "#,
+//! )?;
+//! let library = Module::new(
+//!     &store,
+//!     r#"
+//! (module
+//!   (@custom "dylink.0" "\01\04\00\00\00\00")
+//!   (func (export "add") (param i32 i32) (result i32)
+//!     (i32.add (local.get 0) (local.get 1))))
+//! "#,
+//! )?;
+//!
+//! let linker = Linker::new(&imports! {});
+//! linker.register_library("libadd.so", library);
+//! let instance = linker.instantiate_main(&mut store, &main)?;
+//! linker.dlopen(&mut store, "libadd.so")?;
+//!
+//! let run = instance.exports.get_typed_function::<(), i32>(&store, "run")?;
+//! assert_eq!(run.call(&mut store)?, 3);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [dynamic linking conventions]: https://github.com/WebAssembly/tool-conventions/blob/main/DynamicLinking.md
+
+mod dylink;
+#[allow(clippy::module_inception)]
+mod linker;
+
+pub use self::dylink::DylinkInfo;
+pub use self::linker::{LibraryHandle, Linker, LinkerError, Symbol, DEFAULT_STACK_SIZE};
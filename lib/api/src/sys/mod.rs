@@ -1,9 +1,13 @@
+#[cfg(feature = "component-model")]
+pub mod component;
 mod exports;
 mod extern_ref;
 mod externals;
 mod function_env;
 mod imports;
 mod instance;
+#[cfg(feature = "compiler")]
+pub mod linker;
 mod mem_access;
 mod module;
 mod native;
@@ -24,13 +28,15 @@ pub use crate::sys::imports::Imports;
 pub use crate::sys::instance::{Instance, InstantiationError};
 pub use crate::sys::mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
 pub use crate::sys::module::Module;
+#[cfg(feature = "compiler")]
+pub use crate::sys::module::StreamingModule;
 pub use crate::sys::native::TypedFunction;
 pub use crate::sys::native_type::NativeWasmTypeInto;
 pub use crate::sys::store::{AsStoreMut, AsStoreRef, StoreMut, StoreRef};
 
 pub use crate::sys::ptr::{Memory32, Memory64, MemorySize, WasmPtr, WasmPtr64};
 pub use crate::sys::store::Store;
-pub use crate::sys::tunables::BaseTunables;
+pub use crate::sys::tunables::{BaseTunables, LimitingTunables};
 pub use crate::sys::value::Value;
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
 #[cfg(feature = "compiler")]
@@ -38,7 +44,7 @@ pub use wasmer_compiler::{
     wasmparser, CompilerConfig, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware,
 };
 pub use wasmer_compiler::{Features, FrameInfo, LinkError, RuntimeError, Tunables};
-pub use wasmer_derive::ValueType;
+pub use wasmer_derive::{module_bindgen, ValueType};
 pub use wasmer_types::is_wasm;
 pub use wasmer_types::{
     CpuFeature, ExportType, ExternType, FunctionType, GlobalType, ImportType, MemoryType,
@@ -47,8 +53,8 @@ pub use wasmer_types::{
 
 pub use wasmer_types::{
     Bytes, CompileError, DeserializeError, ExportIndex, GlobalInit, LocalFunctionIndex,
-    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, ValueType, WasmError, WasmResult,
-    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    MiddlewareError, ModuleInfo, Pages, ParseCpuFeatureError, SerializeError, ValueType, WasmError,
+    WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 
 // TODO: should those be moved into wasmer::vm as well?
@@ -76,7 +82,8 @@ pub use wasmer_compiler_llvm::{LLVMOptLevel, LLVM};
 
 pub use wasmer_compiler::Engine;
 #[cfg(feature = "compiler")]
-pub use wasmer_compiler::{Artifact, EngineBuilder};
+pub use wasmer_compiler::{Artifact, EngineBuilder, FunctionCache, FunctionCacheStore};
+pub use wasmer_compiler::{ArtifactPublicKey, ArtifactSigningKey, TrustedKeys};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
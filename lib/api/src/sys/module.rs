@@ -1,6 +1,8 @@
 use crate::sys::InstantiationError;
 use crate::AsStoreMut;
 use crate::AsStoreRef;
+#[cfg(feature = "compiler")]
+use crate::StoreRef;
 use bytes::Bytes;
 use std::borrow::Cow;
 use std::fmt;
@@ -10,6 +12,9 @@ use std::sync::Arc;
 use thiserror::Error;
 use wasmer_compiler::Artifact;
 use wasmer_compiler::ArtifactCreate;
+#[cfg(feature = "compiler")]
+use wasmer_compiler::StreamingCompilation;
+use wasmer_compiler::{ArtifactSigningKey, TrustedKeys};
 #[cfg(feature = "wat")]
 use wasmer_types::WasmError;
 use wasmer_types::{
@@ -38,22 +43,13 @@ pub enum IoCompileError {
 /// contents rather than a deep copy.
 #[derive(Clone)]
 pub struct Module {
-    // The field ordering here is actually significant because of the drop
-    // order: we want to drop the artifact before dropping the engine.
-    //
-    // The reason for this is that dropping the Artifact will de-register the
-    // trap handling metadata from the global registry. This must be done before
-    // the code memory for the artifact is freed (which happens when the store
-    // is dropped) since there is a chance that this memory could be reused by
-    // another module which will try to register its own trap information.
-    //
-    // Note that in Rust, the drop order for struct fields is from top to
-    // bottom: the opposite of C++.
-    //
-    // In the future, this code should be refactored to properly describe the
-    // ownership of the code and its metadata.
+    // The artifact owns the code memory of the module, which is freed when
+    // the last reference to it is dropped. Stores keep a reference to the
+    // artifacts they instantiated, so the code outlives the instances.
     artifact: Arc<Artifact>,
     module_info: Arc<ModuleInfo>,
+    engine_id: Option<Arc<str>>,
+    engine_compatibility_id: Arc<str>,
 }
 
 pub trait IntoBytes {
@@ -213,6 +209,58 @@ impl Module {
         Ok(module)
     }
 
+    #[cfg(feature = "compiler")]
+    /// Creates a new WebAssembly module from a binary read from `reader`.
+    ///
+    /// The module is validated and its functions are compiled while it's
+    /// read, see [`Module::streaming`]. Like [`Module::from_binary`], this
+    /// function is not compatible with the WebAssembly text format.
+    pub fn from_reader(
+        store: &impl AsStoreRef,
+        mut reader: impl io::Read,
+    ) -> Result<Self, IoCompileError> {
+        let mut streaming = Self::streaming(store);
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => streaming.push(&buffer[..len])?,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(streaming.finish()?)
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Starts the compilation of a WebAssembly module whose binary arrives
+    /// in chunks, for instance from the network.
+    ///
+    /// Each chunk is validated as it's pushed, and the functions of the
+    /// module are compiled in the background as soon as their body has
+    /// arrived, so that compiling the module overlaps with receiving it.
+    ///
+    /// # Usage
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let bytes = wat2wasm(b"(module (func (export \"answer\") (result i32) i32.const 42))")?;
+    /// let mut streaming = Module::streaming(&store);
+    /// for chunk in bytes.chunks(8) {
+    ///     streaming.push(chunk)?;
+    /// }
+    /// let module = streaming.finish()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn streaming(store: &impl AsStoreRef) -> StreamingModule<'_> {
+        let store = store.as_store_ref();
+        let compilation = StreamingCompilation::new(store.engine());
+        StreamingModule { store, compilation }
+    }
+
     #[cfg(feature = "compiler")]
     /// Validates a new WebAssembly Module given the configuration
     /// in the Store.
@@ -230,7 +278,7 @@ impl Module {
             .as_store_ref()
             .engine()
             .compile(binary, store.as_store_ref().tunables())?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(store, artifact))
     }
 
     /// Serializes a module into a binary representation that the `Engine`
@@ -251,6 +299,35 @@ impl Module {
         self.artifact.serialize().map(|bytes| bytes.into())
     }
 
+    /// Serializes a module like [`Module::serialize`], followed by its
+    /// checksum and, if a `signing_key` is provided, by an Ed25519
+    /// signature of the checksum.
+    ///
+    /// Signed modules can be loaded safely with
+    /// [`Module::deserialize_checked`]. The checksum is verified by
+    /// [`Module::deserialize`] too.
+    ///
+    /// # Usage
+    ///
+    /// ```ignore
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// # let module = Module::from_file(&store, "path/to/foo.wasm")?;
+    /// let signing_key = ArtifactSigningKey::from_bytes(&secret_key);
+    /// let serialized = module.serialize_checked(Some(&signing_key))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn serialize_checked(
+        &self,
+        signing_key: Option<&ArtifactSigningKey>,
+    ) -> Result<Bytes, SerializeError> {
+        self.artifact
+            .serialize_checked(signing_key)
+            .map(|bytes| bytes.into())
+    }
+
     /// Serializes a module into a file that the `Engine`
     /// can later process via [`Module::deserialize_from_file`].
     ///
@@ -299,7 +376,45 @@ impl Module {
     ) -> Result<Self, DeserializeError> {
         let bytes = bytes.into_bytes();
         let artifact = store.as_store_ref().engine().deserialize(&bytes)?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(store, artifact))
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Deserializes a Module serialized with [`Module::serialize_checked`]
+    /// and signed by one of the `trusted_keys`.
+    ///
+    /// The checksum and the signature are verified before the serialized
+    /// module is read or any code is mapped as executable, so unlike
+    /// [`Module::deserialize`] this function is safe to use on bytes that
+    /// went through an untrusted channel. Modules that are not signed, or
+    /// that are signed by another key, are rejected with
+    /// [`DeserializeError::Untrusted`], and modules compiled for another
+    /// target than the one of the engine with
+    /// [`DeserializeError::Incompatible`].
+    ///
+    /// # Usage
+    ///
+    /// ```ignore
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// let mut trusted_keys = TrustedKeys::new();
+    /// trusted_keys.add(ArtifactPublicKey::from_bytes(&build_service_key)?);
+    /// let module = Module::deserialize_checked(&store, serialized_data, &trusted_keys)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn deserialize_checked(
+        store: &impl AsStoreRef,
+        bytes: impl IntoBytes,
+        trusted_keys: &TrustedKeys,
+    ) -> Result<Self, DeserializeError> {
+        let bytes = bytes.into_bytes();
+        let artifact = store
+            .as_store_ref()
+            .engine()
+            .deserialize_checked(&bytes, trusted_keys)?;
+        Ok(Self::from_artifact(store, artifact))
     }
 
     #[cfg(feature = "compiler")]
@@ -328,13 +443,17 @@ impl Module {
             .as_store_ref()
             .engine()
             .deserialize_from_file(path.as_ref())?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(store, artifact))
     }
 
-    fn from_artifact(artifact: Arc<Artifact>) -> Self {
+    fn from_artifact(store: &impl AsStoreRef, artifact: Arc<Artifact>) -> Self {
+        let store = store.as_store_ref();
+        let engine = store.engine();
         Self {
             module_info: Arc::new(artifact.create_module_info()),
             artifact,
+            engine_id: engine.deterministic_id().map(Into::into),
+            engine_compatibility_id: engine.compatibility_id().into(),
         }
     }
 
@@ -351,6 +470,7 @@ impl Module {
             }
         }
         let mut store_mut = store.as_store_mut();
+        store_mut.keep_artifact(&self.artifact);
         let (tunables, objects) = store_mut.tunables_and_objects_mut();
         unsafe {
             let mut instance_handle = self.artifact.instantiate(
@@ -376,6 +496,22 @@ impl Module {
         }
     }
 
+    /// Returns the [deterministic id] of the engine that compiled or
+    /// deserialized this module, if it has one.
+    ///
+    /// [deterministic id]: crate::Engine::deterministic_id
+    pub fn engine_id(&self) -> Option<&str> {
+        self.engine_id.as_deref()
+    }
+
+    /// Returns the [compatibility id] of the engine that compiled or
+    /// deserialized this module.
+    ///
+    /// [compatibility id]: crate::Engine::compatibility_id
+    pub fn engine_compatibility_id(&self) -> &str {
+        &self.engine_compatibility_id
+    }
+
     /// Returns the name of the current module.
     ///
     /// This name is normally set in the WebAssembly bytecode by some
@@ -509,3 +645,32 @@ impl fmt::Debug for Module {
             .finish()
     }
 }
+
+/// A WebAssembly module compiled while its binary arrives, created with
+/// [`Module::streaming`].
+#[cfg(feature = "compiler")]
+pub struct StreamingModule<'a> {
+    store: StoreRef<'a>,
+    compilation: StreamingCompilation,
+}
+
+#[cfg(feature = "compiler")]
+impl StreamingModule<'_> {
+    /// Validates and compiles the next bytes of the binary of the module.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<(), CompileError> {
+        self.compilation.push(bytes, self.store.tunables())
+    }
+
+    /// Compiles the module, once its whole binary was pushed.
+    pub fn finish(self) -> Result<Module, CompileError> {
+        let artifact = self.compilation.finish(self.store.tunables())?;
+        Ok(Module::from_artifact(&self.store, artifact))
+    }
+}
+
+#[cfg(feature = "compiler")]
+impl fmt::Debug for StreamingModule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamingModule").finish()
+    }
+}
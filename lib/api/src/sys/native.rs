@@ -53,6 +53,20 @@ macro_rules! impl_native_traits {
             $( $x: FromToNativeWasmType, )*
             Rets: WasmTypeList,
         {
+            /// Call the typed func asynchronously and return results, see
+            /// [`Function::call_async`].
+            #[cfg(feature = "compiler")]
+            #[allow(clippy::too_many_arguments)]
+            pub async fn call_async(&self, store: &mut (impl AsStoreMut + Send), $( $x: $x, )* ) -> Result<Rets, RuntimeError>
+            where
+                $( $x: Send, )*
+                Rets: Send,
+            {
+                wasmer_vm::AsyncCall::new(move || self.call(store, $( $x, )*))
+                    .await
+                    .map_err(RuntimeError::from_trap)?
+            }
+
             /// Call the typed func and return results.
             #[allow(unused_mut)]
             #[allow(clippy::too_many_arguments)]
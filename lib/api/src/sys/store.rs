@@ -2,7 +2,7 @@ use crate::sys::tunables::BaseTunables;
 use std::fmt;
 use std::sync::{Arc, RwLock};
 #[cfg(feature = "compiler")]
-use wasmer_compiler::{Engine, EngineBuilder, Tunables};
+use wasmer_compiler::{Artifact, Engine, EngineBuilder, Tunables};
 use wasmer_vm::{init_traps, TrapHandler, TrapHandlerFn};
 
 use wasmer_vm::StoreObjects;
@@ -12,6 +12,10 @@ use wasmer_vm::StoreObjects;
 /// wrap the actual context in a box.
 pub(crate) struct StoreInner {
     pub(crate) objects: StoreObjects,
+    /// The artifacts of the instantiated modules, which hold the code of
+    /// the instances. They are dropped after the objects.
+    #[cfg(feature = "compiler")]
+    pub(crate) artifacts: Vec<Arc<Artifact>>,
     #[cfg(feature = "compiler")]
     pub(crate) engine: Engine,
     #[cfg(feature = "compiler")]
@@ -19,6 +23,12 @@ pub(crate) struct StoreInner {
     pub(crate) trap_handler: Option<Box<TrapHandlerFn<'static>>>,
 }
 
+// The objects are only used through the `Store` that owns them, which is
+// `Send`. Borrowing the inner store mutably, like `StoreMut` does, is then
+// like borrowing the `Store`, which asynchronous host functions do across
+// suspension points.
+unsafe impl Send for StoreInner {}
+
 /// The store represents all global state that can be manipulated by
 /// WebAssembly programs. It consists of the runtime representation
 /// of all instances of functions, tables, memories, and globals that
@@ -75,6 +85,7 @@ impl Store {
         Self {
             inner: Box::new(StoreInner {
                 objects: Default::default(),
+                artifacts: Vec::new(),
                 engine: engine.cloned(),
                 tunables: Box::new(tunables),
                 trap_handler: None,
@@ -277,6 +288,20 @@ impl<'a> StoreMut<'a> {
         (self.inner.tunables.as_ref(), &mut self.inner.objects)
     }
 
+    /// Keeps the code of `artifact` alive as long as the store, as the
+    /// instances and functions of the store might point to it.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn keep_artifact(&mut self, artifact: &Arc<Artifact>) {
+        if !self
+            .inner
+            .artifacts
+            .iter()
+            .any(|kept| Arc::ptr_eq(kept, artifact))
+        {
+            self.inner.artifacts.push(artifact.clone());
+        }
+    }
+
     pub(crate) fn as_raw(&self) -> *mut StoreInner {
         self.inner as *const StoreInner as *mut StoreInner
     }
@@ -132,6 +132,104 @@ impl Tunables for BaseTunables {
     }
 }
 
+/// Tunables that cap the size of the memories.
+///
+/// Memories without a maximum get the limit as their maximum, so growing
+/// them past the limit fails. Memories that ask for more than the limit
+/// can not be created. All the other logic is delegated to the base
+/// tunables.
+pub struct LimitingTunables<T: Tunables> {
+    /// Maximum size of a memory
+    limit: Pages,
+    /// Tunables that the logic is delegated to
+    base: T,
+}
+
+impl<T: Tunables> LimitingTunables<T> {
+    /// Creates tunables that limit the memories to `limit` pages
+    pub fn new(base: T, limit: Pages) -> Self {
+        Self { limit, base }
+    }
+
+    /// Sets the limit as the maximum of memories that have none
+    fn adjust_memory(&self, requested: &MemoryType) -> MemoryType {
+        let mut adjusted = *requested;
+        if requested.maximum.is_none() {
+            adjusted.maximum = Some(self.limit);
+        }
+        adjusted
+    }
+
+    /// Checks that an adjusted memory type fits in the limit
+    fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        if ty.minimum > self.limit {
+            return Err(MemoryError::Generic(format!(
+                "the minimum size of the memory ({} pages) exceeds the limit ({} pages)",
+                ty.minimum.0, self.limit.0
+            )));
+        }
+        match ty.maximum {
+            Some(maximum) if maximum > self.limit => Err(MemoryError::Generic(format!(
+                "the maximum size of the memory ({} pages) exceeds the limit ({} pages)",
+                maximum.0, self.limit.0
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T: Tunables> Tunables for LimitingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(&self.adjust_memory(memory))
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn memory_limit(&self) -> Option<Pages> {
+        Some(match self.base.memory_limit() {
+            Some(limit) if limit < self.limit => limit,
+            _ => self.limit,
+        })
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        self.base.create_host_memory(&adjusted, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        self.base
+            .create_vm_memory(&adjusted, style, vm_definition_location)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,4 +406,37 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn limiting_tunables() {
+        let tunables = LimitingTunables::new(
+            BaseTunables {
+                static_memory_bound: Pages(2048),
+                static_memory_offset_guard_size: 128,
+                dynamic_memory_offset_guard_size: 256,
+            },
+            Pages(16),
+        );
+        let style = MemoryStyle::Dynamic {
+            offset_guard_size: 0,
+        };
+
+        // Memories without a maximum get the limit
+        let memory = tunables
+            .create_host_memory(&MemoryType::new(1, None, false), &style)
+            .unwrap();
+        assert_eq!(memory.ty().maximum, Some(Pages(16)));
+
+        assert!(tunables
+            .create_host_memory(&MemoryType::new(17, None, false), &style)
+            .is_err());
+        assert!(tunables
+            .create_host_memory(&MemoryType::new(1, Some(32), false), &style)
+            .is_err());
+
+        // The smallest limit is enforced
+        assert_eq!(tunables.memory_limit(), Some(Pages(16)));
+        let tunables = LimitingTunables::new(tunables, Pages(32));
+        assert_eq!(tunables.memory_limit(), Some(Pages(16)));
+    }
 }
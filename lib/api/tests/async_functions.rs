@@ -0,0 +1,241 @@
+#![cfg(feature = "sys")]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::executor::block_on;
+use futures::task::noop_waker_ref;
+use wasmer::*;
+
+/// A future that is pending the first time it's polled.
+#[derive(Default)]
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// A future that is pending until `ready` is set.
+struct WaitFor {
+    ready: Arc<AtomicBool>,
+}
+
+impl Future for WaitFor {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.ready.load(Ordering::SeqCst) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+fn instantiate(store: &mut Store, imports: &Imports) -> anyhow::Result<Instance> {
+    let module = Module::new(
+        store,
+        r#"
+(module
+  (import "host" "add" (func $add (param i32 i32) (result i32)))
+  (func (export "add_twice") (param i32 i32) (result i32)
+    (call $add (call $add (local.get 0) (local.get 1)) (local.get 1)))
+  (func (export "add_then_trap") (param i32 i32) (result i32)
+    (drop (call $add (local.get 0) (local.get 1)))
+    unreachable))
+"#,
+    )?;
+    Ok(Instance::new(store, &module, imports)?)
+}
+
+#[test]
+fn async_host_functions_suspend_async_calls() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let add = Function::new_typed_async(&mut store, |(a, b): (i32, i32)| async move {
+        YieldNow::default().await;
+        Ok::<_, RuntimeError>(a + b)
+    });
+    let instance = instantiate(&mut store, &imports! { "host" => { "add" => add } })?;
+
+    let add_twice = instance.exports.get_function("add_twice")?;
+    let result = block_on(add_twice.call_async(&mut store, &[Value::I32(1), Value::I32(2)]))?;
+    assert_eq!(result.into_vec(), vec![Value::I32(5)]);
+
+    let add_twice: TypedFunction<(i32, i32), i32> = add_twice.typed(&store)?;
+    assert_eq!(block_on(add_twice.call_async(&mut store, 3, 4))?, 11);
+
+    Ok(())
+}
+
+#[test]
+fn async_host_functions_can_use_their_env() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let env = FunctionEnv::new(&mut store, 0);
+    let add = Function::new_typed_with_env_async(
+        &mut store,
+        &env,
+        |mut env: FunctionEnvMut<i32>, (a, b): (i32, i32)| {
+            Box::pin(async move {
+                *env.data_mut() += 1;
+                YieldNow::default().await;
+                *env.data_mut() += 1;
+                Ok::<_, RuntimeError>(a + b)
+            })
+        },
+    );
+    let instance = instantiate(&mut store, &imports! { "host" => { "add" => add } })?;
+
+    let add_twice = instance.exports.get_function("add_twice")?;
+    let result = block_on(add_twice.call_async(&mut store, &[Value::I32(1), Value::I32(2)]))?;
+    assert_eq!(result.into_vec(), vec![Value::I32(5)]);
+    assert_eq!(*env.as_ref(&store), 4);
+
+    Ok(())
+}
+
+#[test]
+fn async_host_functions_can_write_memory_after_suspending() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (import "host" "read" (func $read (param i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "read_and_load") (result i32)
+    (drop (call $read (i32.const 16)))
+    (i32.load (i32.const 16))))
+"#,
+    )?;
+    let env = FunctionEnv::new(&mut store, None::<Memory>);
+    let read = Function::new_typed_with_env_async(
+        &mut store,
+        &env,
+        |env: FunctionEnvMut<Option<Memory>>, ptr: i32| {
+            Box::pin(async move {
+                YieldNow::default().await;
+                let memory = env.data().as_ref().unwrap();
+                memory
+                    .view(&env)
+                    .write(ptr as u64, &42i32.to_le_bytes())
+                    .map_err(|error| RuntimeError::new(error.to_string()))?;
+                Ok::<_, RuntimeError>(4)
+            })
+        },
+    );
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &imports! { "host" => { "read" => read } },
+    )?;
+    *env.as_mut(&mut store) = Some(instance.exports.get_memory("memory")?.clone());
+
+    let read_and_load: TypedFunction<(), i32> = instance
+        .exports
+        .get_function("read_and_load")?
+        .typed(&store)?;
+    assert_eq!(block_on(read_and_load.call_async(&mut store))?, 42);
+
+    Ok(())
+}
+
+#[test]
+fn async_host_functions_require_async_calls() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let signature = FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I32]);
+    let add = Function::new_async(&mut store, &signature, |args| {
+        let sum = args[0].unwrap_i32() + args[1].unwrap_i32();
+        async move { Ok(vec![Value::I32(sum)]) }
+    });
+    let instance = instantiate(&mut store, &imports! { "host" => { "add" => add } })?;
+
+    let add_twice = instance.exports.get_function("add_twice")?;
+    let error = add_twice
+        .call(&mut store, &[Value::I32(1), Value::I32(2)])
+        .unwrap_err();
+    assert!(error.message().contains("async call"), "{}", error);
+
+    Ok(())
+}
+
+#[test]
+fn async_calls_can_be_cancelled() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let ready = Arc::new(AtomicBool::new(false));
+    let add = Function::new_typed_async(&mut store, {
+        let ready = ready.clone();
+        move |(a, b): (i32, i32)| {
+            let ready = ready.clone();
+            async move {
+                WaitFor { ready }.await;
+                Ok::<_, RuntimeError>(a + b)
+            }
+        }
+    });
+    let instance = instantiate(&mut store, &imports! { "host" => { "add" => add } })?;
+    let add_twice: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_function("add_twice")?.typed(&store)?;
+
+    {
+        let mut call = Box::pin(add_twice.call_async(&mut store, 1, 2));
+        let mut cx = Context::from_waker(noop_waker_ref());
+        assert!(call.as_mut().poll(&mut cx).is_pending());
+        assert!(call.as_mut().poll(&mut cx).is_pending());
+    }
+
+    // The store is still usable after the call was cancelled.
+    ready.store(true, Ordering::SeqCst);
+    assert_eq!(block_on(add_twice.call_async(&mut store, 1, 2))?, 5);
+
+    Ok(())
+}
+
+#[test]
+fn async_calls_can_be_resumed_on_another_thread() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let ready = Arc::new(AtomicBool::new(false));
+    let add = Function::new_typed_async(&mut store, {
+        let ready = ready.clone();
+        move |(a, b): (i32, i32)| {
+            let ready = ready.clone();
+            async move {
+                WaitFor { ready }.await;
+                Ok::<_, RuntimeError>(a + b)
+            }
+        }
+    });
+    let instance = instantiate(&mut store, &imports! { "host" => { "add" => add } })?;
+    let add_then_trap: TypedFunction<(i32, i32), i32> = instance
+        .exports
+        .get_function("add_then_trap")?
+        .typed(&store)?;
+
+    let mut call = Box::pin(async move { add_then_trap.call_async(&mut store, 1, 2).await });
+    let mut cx = Context::from_waker(noop_waker_ref());
+    assert!(call.as_mut().poll(&mut cx).is_pending());
+
+    // The trap raised after the call is resumed is caught on the new thread.
+    ready.store(true, Ordering::SeqCst);
+    let error = std::thread::spawn(move || block_on(call))
+        .join()
+        .unwrap()
+        .unwrap_err();
+    assert!(error.message().contains("unreachable"), "{}", error);
+
+    Ok(())
+}
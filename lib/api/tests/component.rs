@@ -0,0 +1,499 @@
+#![cfg(feature = "component-model")]
+
+use wasmer::component::*;
+use wasmer::*;
+
+/// A component exporting functions that exercise the canonical ABI,
+/// with a bump allocator as `realloc`.
+fn component_wat() -> String {
+    let params = (1..=17)
+        .map(|i| format!("(param \"a{}\" u32)", i))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        r#"
+(component
+  (import "host" (instance $host
+    (export "log" (func (param string)))
+    (export "upper" (func (param string) (result string)))
+  ))
+  (core module $libc
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ret i32)
+      (local.set $ret
+        (i32.and
+          (i32.add (global.get $next) (i32.sub (local.get 2) (i32.const 1)))
+          (i32.sub (i32.const 0) (local.get 2))))
+      (global.set $next (i32.add (local.get $ret) (local.get 3)))
+      (local.get $ret))
+  )
+  (core instance $libc (instantiate $libc))
+  (core func $log (canon lower (func $host "log") (memory $libc "memory")))
+  (core func $upper (canon lower (func $host "upper")
+    (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (core module $m
+    (import "host" "log" (func $log (param i32 i32)))
+    (import "host" "upper" (func $upper (param i32 i32 i32)))
+    (import "libc" "memory" (memory 1))
+    (func (export "greet") (param i32 i32) (result i32)
+      (call $log (local.get 0) (local.get 1))
+      (i32.store (i32.const 0) (local.get 0))
+      (i32.store (i32.const 4) (local.get 1))
+      (i32.const 0))
+    (func (export "relay") (param i32 i32) (result i32)
+      (call $upper (local.get 0) (local.get 1) (i32.const 16))
+      (i32.const 16))
+    (func (export "add") (param i32 i32 i32 i32) (result i32)
+      (i32.store (i32.const 8) (i32.add (local.get 0) (local.get 2)))
+      (i32.store (i32.const 12) (i32.add (local.get 1) (local.get 3)))
+      (i32.const 8))
+    (func (export "sum") (param $ptr i32) (param $len i32) (result i32)
+      (local $sum i32)
+      (block $done
+        (loop $loop
+          (br_if $done (i32.eqz (local.get $len)))
+          (local.set $sum (i32.add (local.get $sum) (i32.load (local.get $ptr))))
+          (local.set $ptr (i32.add (local.get $ptr) (i32.const 4)))
+          (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+          (br $loop)))
+      (local.get $sum))
+    (func (export "area") (param $case i32) (param $payload i32) (result f32)
+      (if (result f32) (i32.eq (local.get $case) (i32.const 1))
+        (then
+          (f32.mul
+            (f32.const 3)
+            (f32.mul
+              (f32.reinterpret_i32 (local.get $payload))
+              (f32.reinterpret_i32 (local.get $payload)))))
+        (else
+          (if (result f32) (i32.eq (local.get $case) (i32.const 2))
+            (then (f32.convert_i32_u (i32.mul (local.get $payload) (local.get $payload))))
+            (else (f32.const 0))))))
+    (func (export "sum17") (param $ptr i32) (result i32)
+      (local $sum i32)
+      (local $i i32)
+      (block $done
+        (loop $loop
+          (br_if $done (i32.eq (local.get $i) (i32.const 17)))
+          (local.set $sum
+            (i32.add
+              (local.get $sum)
+              (i32.load (i32.add (local.get $ptr) (i32.mul (local.get $i) (i32.const 4))))))
+          (local.set $i (i32.add (local.get $i) (i32.const 1)))
+          (br $loop)))
+      (local.get $sum))
+    (func (export "oversized") (result i32)
+      (i32.store (i32.const 0) (i32.const 0))
+      (i32.store (i32.const 4) (i32.const -16))
+      (i32.const 0))
+    (func (export "hollow") (result i32)
+      (i32.store (i32.const 0) (i32.const 0))
+      (i32.store (i32.const 4) (i32.const -16))
+      (i32.const 0))
+    (func (export "flip") (param i32) (result i32)
+      (i32.xor (local.get 0) (i32.const 3)))
+    (func (export "next-color") (param i32) (result i32)
+      (i32.rem_u (i32.add (local.get 0) (i32.const 1)) (i32.const 3)))
+    (func (export "widen") (param i32 i64) (result f64)
+      (if (result f64) (i32.eqz (local.get 0))
+        (then (f64.convert_i64_u (local.get 1)))
+        (else (f64.reinterpret_i64 (local.get 1)))))
+  )
+  (core instance $i (instantiate $m
+    (with "host" (instance (export "log" (func $log)) (export "upper" (func $upper))))
+    (with "libc" (instance $libc))
+  ))
+  (type $point (record (field "x" s32) (field "y" s32)))
+  (type $shape (variant (case "none") (case "circle" float32) (case "square" u32)))
+  (type $perms (flags "read" "write"))
+  (type $color (enum "red" "green" "blue"))
+  (type $number (union u32 float64))
+  (func (export "greet") (param string) (result string)
+    (canon lift (core func $i "greet")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "relay") (param string) (result string)
+    (canon lift (core func $i "relay")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func $add (export "add") (param "a" $point) (param "b" $point) (result $point)
+    (canon lift (core func $i "add") (memory $libc "memory")))
+  (func (export "sum") (param (list u32)) (result u32)
+    (canon lift (core func $i "sum")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func $area (export "area") (param $shape) (result float32)
+    (canon lift (core func $i "area")))
+  (func (export "sum17") {} (result u32)
+    (canon lift (core func $i "sum17")
+      (memory $libc "memory") (realloc (func $libc "realloc"))))
+  (func (export "oversized") (result string)
+    (canon lift (core func $i "oversized") (memory $libc "memory")))
+  (func (export "hollow") (result (list (tuple)))
+    (canon lift (core func $i "hollow") (memory $libc "memory")))
+  (func $flip (param $perms) (result $perms)
+    (canon lift (core func $i "flip")))
+  (func $next-color (param $color) (result $color)
+    (canon lift (core func $i "next-color")))
+  (func $widen (param $number) (result float64)
+    (canon lift (core func $i "widen")))
+  (instance $geometry
+    (export "add" (func $add))
+    (export "area" (func $area))
+    (export "flip" (func $flip))
+    (export "next-color" (func $next-color))
+    (export "widen" (func $widen))
+  )
+  (export "geometry" (instance $geometry))
+)
+"#,
+        params
+    )
+}
+
+fn host_imports(store: &mut Store) -> (ComponentImports, FunctionEnv<Vec<String>>) {
+    let env = FunctionEnv::new(store, Vec::new());
+    let mut imports = ComponentImports::new();
+    imports
+        .instance("host")
+        .typed_func_with_env(&env, "log", |mut env, (message,): (String,)| {
+            env.data_mut().push(message);
+            Ok(())
+        })
+        .typed_func_with_env(&env, "upper", |_env, (message,): (String,)| {
+            Ok(message.to_uppercase())
+        });
+    (imports, env)
+}
+
+fn instantiate(store: &mut Store) -> anyhow::Result<(ComponentInstance, FunctionEnv<Vec<String>>)> {
+    let component = Component::new(store, component_wat())?;
+    let (imports, env) = host_imports(store);
+    let instance = ComponentInstance::new(store, &component, &imports)?;
+    Ok((instance, env))
+}
+
+#[test]
+fn component_types() -> anyhow::Result<()> {
+    let store = Store::default();
+    let component = Component::new(&store, component_wat())?;
+
+    let imports = component
+        .imports()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(imports, vec!["host"]);
+
+    let exports = component.exports().collect::<Vec<_>>();
+    let export = |name: &str| {
+        exports
+            .iter()
+            .find(|(export, _)| *export == name)
+            .map(|(_, ty)| (*ty).clone())
+            .unwrap()
+    };
+    match export("greet") {
+        ComponentExternType::Func(ty) => assert_eq!(ty.to_string(), "func(string) -> string"),
+        ty => panic!("unexpected type {:?}", ty),
+    }
+    match export("add") {
+        ComponentExternType::Func(ty) => assert_eq!(
+            ty.to_string(),
+            "func(a: record { x: s32, y: s32 }, b: record { x: s32, y: s32 }) -> record { x: s32, y: s32 }"
+        ),
+        ty => panic!("unexpected type {:?}", ty),
+    }
+    match export("geometry") {
+        ComponentExternType::Instance(ty) => assert_eq!(
+            ty.exports().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["add", "area", "flip", "next-color", "widen"]
+        ),
+        ty => panic!("unexpected type {:?}", ty),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn strings_cross_the_component_boundary() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, env) = instantiate(&mut store)?;
+
+    let greet = instance
+        .exports
+        .get_typed_func::<(String,), String>("greet")?;
+    assert_eq!(greet.call(&mut store, ("hello".to_string(),))?, "hello");
+    assert_eq!(env.as_ref(&store), &vec!["hello".to_string()]);
+
+    let relay = instance
+        .exports
+        .get_typed_func::<(String,), String>("relay")?;
+    assert_eq!(relay.call(&mut store, ("wasmer".to_string(),))?, "WASMER");
+
+    Ok(())
+}
+
+#[test]
+fn records_lists_and_variants() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, _) = instantiate(&mut store)?;
+
+    let point = |x: i32, y: i32| {
+        ComponentValue::Record(vec![
+            ("x".to_string(), ComponentValue::S32(x)),
+            ("y".to_string(), ComponentValue::S32(y)),
+        ])
+    };
+    let add = instance.exports.get_func("add")?;
+    assert_eq!(
+        add.call(&mut store, &[point(1, 2), point(10, 20)])?,
+        vec![point(11, 22)]
+    );
+
+    let sum = instance.exports.get_typed_func::<(Vec<u32>,), u32>("sum")?;
+    assert_eq!(sum.call(&mut store, (vec![1, 2, 3, 4],))?, 10);
+    assert_eq!(sum.call(&mut store, (vec![],))?, 0);
+
+    let area = instance.exports.get_func("area")?;
+    let shape = |case: &str, payload: Option<ComponentValue>| {
+        ComponentValue::Variant(case.to_string(), payload.map(Box::new))
+    };
+    assert_eq!(
+        area.call(&mut store, &[shape("circle", Some(2.0f32.into()))])?,
+        vec![ComponentValue::Float32(12.0)]
+    );
+    assert_eq!(
+        area.call(&mut store, &[shape("square", Some(3u32.into()))])?,
+        vec![ComponentValue::Float32(9.0)]
+    );
+    assert_eq!(
+        area.call(&mut store, &[shape("none", None)])?,
+        vec![ComponentValue::Float32(0.0)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn spilled_params() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, _) = instantiate(&mut store)?;
+
+    let sum17 = instance.exports.get_func("sum17")?;
+    let params = (1..=17u32).map(ComponentValue::U32).collect::<Vec<_>>();
+    assert_eq!(
+        sum17.call(&mut store, &params)?,
+        vec![ComponentValue::U32(153)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn mistyped_calls_are_rejected() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, _) = instantiate(&mut store)?;
+
+    assert!(instance
+        .exports
+        .get_typed_func::<(u32,), String>("greet")
+        .is_err());
+    let greet = instance.exports.get_func("greet")?;
+    assert!(greet.call(&mut store, &[ComponentValue::U32(1)]).is_err());
+    assert!(instance.exports.get_func("missing").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn out_of_bounds_strings_are_rejected() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, _) = instantiate(&mut store)?;
+
+    // The string is 4 GiB long, which the memory can't hold
+    let oversized = instance.exports.get_typed_func::<(), String>("oversized")?;
+    let error = oversized.call(&mut store, ()).unwrap_err();
+    assert!(error.message().contains("out of bounds"), "{}", error);
+
+    // The elements of the list take no memory, but there are 4 billion of
+    // them to allocate
+    let hollow = instance.exports.get_typed_func::<(), Vec<()>>("hollow")?;
+    let error = hollow.call(&mut store, ()).unwrap_err();
+    assert!(error.message().contains("out of bounds"), "{}", error);
+
+    Ok(())
+}
+
+#[test]
+fn missing_and_incompatible_imports() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let component = Component::new(&store, component_wat())?;
+
+    let error =
+        ComponentInstance::new(&mut store, &component, &ComponentImports::new()).unwrap_err();
+    assert!(
+        matches!(error, ComponentInstantiationError::MissingImport(ref name) if name == "host"),
+        "{}",
+        error
+    );
+
+    let env = FunctionEnv::new(&mut store, ());
+    let mut imports = ComponentImports::new();
+    imports
+        .instance("host")
+        .typed_func_with_env(&env, "log", |_env, (_,): (u32,)| Ok(()))
+        .typed_func_with_env(&env, "upper", |_env, (message,): (String,)| Ok(message));
+    let error = ComponentInstance::new(&mut store, &component, &imports).unwrap_err();
+    assert!(
+        matches!(
+            error,
+            ComponentInstantiationError::IncompatibleImport { .. }
+        ),
+        "{}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn dynamic_host_functions() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let component = Component::new(&store, component_wat())?;
+    let mut imports = ComponentImports::new();
+    imports
+        .instance("host")
+        .func("log", |_| Ok(vec![]))
+        .func("upper", |params| match params {
+            [ComponentValue::String(s)] => Ok(vec![ComponentValue::String(s.repeat(2))]),
+            _ => Err(RuntimeError::new("unexpected parameters")),
+        });
+    let instance = ComponentInstance::new(&mut store, &component, &imports)?;
+
+    let relay = instance.exports.get_func("relay")?;
+    assert_eq!(
+        relay.call(&mut store, &["ab".into()])?,
+        vec![ComponentValue::String("abab".to_string())]
+    );
+
+    Ok(())
+}
+
+mod bindings {
+    wasmer::component::bindgen!(
+        inline = r#"
+            interface host {
+                /// Logs a message.
+                log: func(message: string)
+                upper: func(message: string) -> string
+            }
+
+            interface geometry {
+                /// A point in the plane.
+                record point { x: s32, y: s32 }
+                variant shape { none, circle(float32), square(u32) }
+                flags perms { read, write }
+                enum color { red, green, blue }
+                union number { u32, float64 }
+
+                add: func(a: point, b: point) -> point
+                area: func(shape: shape) -> float32
+                flip: func(perms: perms) -> perms
+                next-color: func(color: color) -> color
+                widen: func(n: number) -> float64
+            }
+
+            world plugin {
+                import host: host
+                export greet: func(name: string) -> string
+                export relay: func(message: string) -> string
+                export sum: func(values: list<u32>) -> u32
+                export geometry: geometry
+            }
+        "#
+    );
+}
+
+#[derive(Default)]
+struct Host {
+    messages: Vec<String>,
+}
+
+impl bindings::host::Host for Host {
+    fn log(&mut self, message: String) -> Result<(), RuntimeError> {
+        self.messages.push(message);
+        Ok(())
+    }
+
+    fn upper(&mut self, message: String) -> Result<String, RuntimeError> {
+        Ok(message.to_uppercase())
+    }
+}
+
+#[test]
+fn bindgen_bindings() -> anyhow::Result<()> {
+    use bindings::geometry::{Color, Number, Perms, Point, Shape};
+    use bindings::Plugin;
+
+    let mut store = Store::default();
+    let component = Component::new(&store, component_wat())?;
+    let env = FunctionEnv::new(&mut store, Host::default());
+    let mut imports = ComponentImports::new();
+    Plugin::add_to_imports(&mut imports, &env);
+    let (plugin, _) = Plugin::instantiate(&mut store, &component, &imports)?;
+
+    assert_eq!(plugin.greet(&mut store, "hi".to_string())?, "hi");
+    assert_eq!(env.as_ref(&store).messages, vec!["hi".to_string()]);
+    assert_eq!(plugin.relay(&mut store, "hi".to_string())?, "HI");
+    assert_eq!(plugin.sum(&mut store, vec![1, 2, 3])?, 6);
+
+    let geometry = plugin.geometry();
+    assert_eq!(
+        geometry.add(&mut store, Point { x: 1, y: 2 }, Point { x: 3, y: 4 })?,
+        Point { x: 4, y: 6 }
+    );
+    assert_eq!(geometry.area(&mut store, Shape::Square(4))?, 16.0);
+    assert_eq!(geometry.area(&mut store, Shape::None)?, 0.0);
+    assert_eq!(
+        geometry.flip(
+            &mut store,
+            Perms {
+                read: true,
+                write: false
+            }
+        )?,
+        Perms {
+            read: false,
+            write: true
+        }
+    );
+    assert_eq!(geometry.next_color(&mut store, Color::Blue)?, Color::Red);
+    assert_eq!(geometry.widen(&mut store, Number::V0(7))?, 7.0);
+    assert_eq!(geometry.widen(&mut store, Number::V1(1.5))?, 1.5);
+
+    Ok(())
+}
+
+#[test]
+fn bindgen_bindings_check_exports() -> anyhow::Result<()> {
+    wasmer::component::bindgen!(
+        inline = r#"
+            world other {
+                export greet: func(name: u32) -> string
+            }
+        "#
+    );
+
+    let mut store = Store::default();
+    let component = Component::new(&store, component_wat())?;
+    let (imports, _) = host_imports(&mut store);
+    let error = Other::instantiate(&mut store, &component, &imports)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(error, ComponentInstantiationError::Export(_)),
+        "{}",
+        error
+    );
+
+    Ok(())
+}
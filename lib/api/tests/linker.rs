@@ -0,0 +1,347 @@
+#![cfg(feature = "compiler")]
+
+use wasmer::linker::*;
+use wasmer::*;
+
+const MAIN: &str = r#"
+(module
+  (@custom "dylink.0" "\01\04\10\00\00\00\02\0c\01\0alibside.so")
+  (import "env" "memory" (memory 1))
+  (import "env" "__indirect_function_table" (table 1 funcref))
+  (import "env" "__memory_base" (global $memory_base i32))
+  (import "env" "__table_base" (global $table_base i32))
+  (import "env" "__stack_pointer" (global $stack_pointer (mut i32)))
+  (import "GOT.mem" "counter" (global $counter (mut i32)))
+  (import "GOT.func" "double" (global $double (mut i32)))
+  (type $i32_to_i32 (func (param i32) (result i32)))
+  (data (global.get $memory_base) "\2a\00\00\00")
+  (global (export "value") i32 (i32.const 0))
+  (func (export "read_counter") (result i32)
+    (i32.load (global.get $counter)))
+  (func (export "call_double") (param i32) (result i32)
+    (call_indirect (type $i32_to_i32) (local.get 0) (global.get $double)))
+  (func (export "stack_pointer") (result i32)
+    (global.get $stack_pointer)))
+"#;
+
+const SIDE: &str = r#"
+(module
+  (@custom "dylink.0" "\01\04\08\02\01\00")
+  (import "env" "memory" (memory 1))
+  (import "env" "__indirect_function_table" (table 1 funcref))
+  (import "env" "__memory_base" (global $memory_base i32))
+  (import "env" "__table_base" (global $table_base i32))
+  (import "GOT.mem" "value" (global $value (mut i32)))
+  (elem (global.get $table_base) $double)
+  (data (global.get $memory_base) "\07\00\00\00")
+  (global (export "counter") i32 (i32.const 0))
+  (func $double (export "double") (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "__wasm_call_ctors")
+    (i32.store
+      (global.get $memory_base)
+      (i32.add
+        (i32.load (global.get $memory_base))
+        (i32.load (global.get $value))))))
+"#;
+
+#[test]
+fn relocatable_modules() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let linker = Linker::new(&imports! {});
+    linker.register_library("libside.so", Module::new(&store, SIDE)?);
+    let instance = linker.instantiate_main(&mut store, &Module::new(&store, MAIN)?)?;
+
+    // The constructor of the side module added the value of the main
+    // module to its own counter.
+    let read_counter = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "read_counter")?;
+    assert_eq!(read_counter.call(&mut store)?, 49);
+    let call_double = instance
+        .exports
+        .get_typed_function::<i32, i32>(&store, "call_double")?;
+    assert_eq!(call_double.call(&mut store, 21)?, 42);
+
+    // The stack of the main module is placed after its data.
+    let stack_pointer = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "stack_pointer")?;
+    assert_eq!(
+        stack_pointer.call(&mut store)? as u32,
+        1040 + DEFAULT_STACK_SIZE
+    );
+
+    let value = linker.dlsym(&mut store, None, "value")?;
+    assert_eq!(value, 1024);
+    let handle = linker.dlopen(&mut store, "libside.so")?;
+    let counter = linker.dlsym(&mut store, Some(handle), "counter")?;
+    let mut bytes = [0; 4];
+    linker
+        .memory()
+        .unwrap()
+        .view(&store)
+        .read(counter as u64, &mut bytes)?;
+    assert_eq!(i32::from_le_bytes(bytes), 49);
+
+    let double = linker.dlsym(&mut store, Some(handle), "double")?;
+    let table = linker.table().unwrap();
+    match table.get(&mut store, double) {
+        Some(Value::FuncRef(Some(function))) => {
+            assert_eq!(
+                function.call(&mut store, &[Value::I32(4)])?[0],
+                Value::I32(8)
+            );
+        }
+        _ => panic!("`double` is not in the table"),
+    }
+    linker.dlclose(handle)?;
+
+    Ok(())
+}
+
+#[test]
+fn undefined_symbols() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let main = Module::new(
+        &store,
+        r#"(module
+          (memory (export "memory") 1)
+          (table (export "__indirect_function_table") 1 funcref))"#,
+    )?;
+    let strong = Module::new(
+        &store,
+        r#"(module
+          (@custom "dylink.0" "\01\04\00\00\00\00")
+          (import "GOT.mem" "missing" (global (mut i32))))"#,
+    )?;
+    let weak = Module::new(
+        &store,
+        r#"(module
+          (@custom "dylink.0" "\01\04\00\00\00\00\04\12\01\07GOT.mem\07missing\01")
+          (import "GOT.mem" "missing" (global (mut i32))))"#,
+    )?;
+
+    let linker = Linker::new(&imports! {});
+    linker.register_library("libstrong.so", strong);
+    linker.register_library("libweak.so", weak);
+    assert!(matches!(
+        linker.dlopen(&mut store, "libstrong.so"),
+        Err(LinkerError::MainModuleNotLoaded)
+    ));
+    linker.instantiate_main(&mut store, &main)?;
+
+    assert!(matches!(
+        linker.dlopen(&mut store, "libstrong.so"),
+        Err(LinkerError::UndefinedSymbol(symbol)) if symbol == "missing"
+    ));
+    assert_eq!(
+        linker.dlerror().as_deref(),
+        Some("undefined symbol `missing`")
+    );
+    assert_eq!(linker.dlerror(), None);
+
+    linker.dlopen(&mut store, "/usr/lib/libweak.so")?;
+    assert!(matches!(
+        linker.dlopen(&mut store, "libmissing.so"),
+        Err(LinkerError::LibraryNotFound(_))
+    ));
+    assert!(matches!(
+        linker.dlsym(&mut store, None, "missing"),
+        Err(LinkerError::UndefinedSymbol(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn failed_dlopen_unresolves_symbols() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let main = Module::new(
+        &store,
+        r#"(module
+          (@custom "dylink.0" "\01\04\00\00\00\00\04\22\02\08GOT.func\05scale\01\07GOT.mem\07counter\01")
+          (import "env" "memory" (memory 1))
+          (import "env" "__indirect_function_table" (table 1 funcref))
+          (import "GOT.mem" "counter" (global $counter (mut i32)))
+          (import "GOT.func" "scale" (global $scale (mut i32)))
+          (type $i32_to_i32 (func (param i32) (result i32)))
+          (func (export "read_counter") (result i32)
+            (i32.load (global.get $counter)))
+          (func (export "call_scale") (param i32) (result i32)
+            (call_indirect (type $i32_to_i32) (local.get 0) (global.get $scale))))"#,
+    )?;
+    let library = |store: &Store, missing: &str, counter: u8, factor: i32| {
+        Module::new(
+            store,
+            format!(
+                r#"(module
+                  (@custom "dylink.0" "\01\04\04\02\00\00")
+                  (import "env" "memory" (memory 1))
+                  (import "env" "__memory_base" (global $memory_base i32))
+                  {}
+                  (data (global.get $memory_base) "\{:02x}\00\00\00")
+                  (global (export "counter") i32 (i32.const 0))
+                  (func (export "scale") (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const {}))))"#,
+                missing, counter, factor
+            ),
+        )
+    };
+
+    let linker = Linker::new(&imports! {});
+    let instance = linker.instantiate_main(&mut store, &main)?;
+    linker.register_library(
+        "libscale.so",
+        library(
+            &store,
+            r#"(import "GOT.mem" "missing" (global (mut i32)))"#,
+            7,
+            2,
+        )?,
+    );
+    assert!(matches!(
+        linker.dlopen(&mut store, "libscale.so"),
+        Err(LinkerError::UndefinedSymbol(symbol)) if symbol == "missing"
+    ));
+
+    // The global offset table of the main module must not keep pointing
+    // into the library that failed to load
+    linker.register_library("libscale.so", library(&store, "", 9, 3)?);
+    linker.dlopen(&mut store, "libscale.so")?;
+    let read_counter = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "read_counter")?;
+    assert_eq!(read_counter.call(&mut store)?, 9);
+    let call_scale = instance
+        .exports
+        .get_typed_function::<i32, i32>(&store, "call_scale")?;
+    assert_eq!(call_scale.call(&mut store, 2)?, 6);
+
+    Ok(())
+}
+
+#[test]
+fn lazy_binding() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let main = Module::new(
+        &store,
+        r#"(module
+          (import "env" "add" (func $add (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (table (export "__indirect_function_table") 1 funcref)
+          (func (export "run") (result i32)
+            (call $add (i32.const 1) (i32.const 2))))"#,
+    )?;
+    let library = Module::new(
+        &store,
+        r#"(module
+          (@custom "dylink.0" "\01\04\00\00\00\00")
+          (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))"#,
+    )?;
+
+    let linker = Linker::new(&imports! {});
+    linker.register_library("libadd.so", library);
+    let instance = linker.instantiate_main(&mut store, &main)?;
+    let run = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "run")?;
+    assert!(run.call(&mut store).is_err());
+
+    linker.dlopen(&mut store, "libadd.so")?;
+    assert_eq!(run.call(&mut store)?, 3);
+
+    Ok(())
+}
+
+#[test]
+fn guest_dlopen() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let main = Module::new(
+        &store,
+        r#"(module
+          (import "env" "dlopen" (func $dlopen (param i32 i32) (result i32)))
+          (import "env" "dlsym" (func $dlsym (param i32 i32) (result i32)))
+          (import "env" "dlerror" (func $dlerror (result i32)))
+          (memory (export "memory") 1)
+          (table (export "__indirect_function_table") 1 funcref)
+          (type $i32_i32_to_i32 (func (param i32 i32) (result i32)))
+          (data (i32.const 16) "libadd.so\00")
+          (data (i32.const 32) "add\00")
+          (data (i32.const 48) "libmissing.so\00")
+          (func (export "add") (param i32 i32) (result i32)
+            (call_indirect (type $i32_i32_to_i32)
+              (local.get 0)
+              (local.get 1)
+              (call $dlsym (call $dlopen (i32.const 16) (i32.const 0)) (i32.const 32))))
+          (func (export "open_missing") (result i32)
+            (call $dlopen (i32.const 48) (i32.const 0)))
+          (func (export "error") (result i32)
+            (call $dlerror)))"#,
+    )?;
+    let library = Module::new(
+        &store,
+        r#"(module
+          (@custom "dylink.0" "\01\04\00\00\00\00")
+          (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))"#,
+    )?;
+
+    let linker = Linker::new(&imports! {});
+    for ((ns, name), extern_) in &linker.dl_imports(&mut store) {
+        linker.define(&ns, &name, extern_);
+    }
+    linker.register_library("libadd.so", library);
+    let instance = linker.instantiate_main(&mut store, &main)?;
+
+    let add = instance
+        .exports
+        .get_typed_function::<(i32, i32), i32>(&store, "add")?;
+    assert_eq!(add.call(&mut store, 2, 3)?, 5);
+
+    let open_missing = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "open_missing")?;
+    assert_eq!(open_missing.call(&mut store)?, 0);
+    let error = instance
+        .exports
+        .get_typed_function::<(), i32>(&store, "error")?;
+    let message = error.call(&mut store)?;
+    assert_ne!(message, 0);
+    assert_eq!(
+        linker.read_c_string(&store, message as u32)?,
+        "library `libmissing.so` was not found"
+    );
+    assert_eq!(error.call(&mut store)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn oversized_side_module() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let main = Module::new(
+        &store,
+        r#"(module
+          (memory (export "memory") 1)
+          (table (export "__indirect_function_table") 1 funcref))"#,
+    )?;
+    // A side module with almost 4 GiB of data, that doesn't fit after the
+    // memory of the main module
+    let library = Module::new(
+        &store,
+        r#"(module
+          (@custom "dylink.0" "\01\08\f0\ff\ff\ff\0f\04\00\00"))"#,
+    )?;
+
+    let linker = Linker::new(&imports! {});
+    linker.register_library("libbig.so", library);
+    linker.instantiate_main(&mut store, &main)?;
+    assert!(matches!(
+        linker.dlopen(&mut store, "libbig.so"),
+        Err(LinkerError::Allocation(0xffff_fff0))
+    ));
+
+    Ok(())
+}
@@ -275,3 +275,201 @@ fn calling_host_functions_with_negative_values_works() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(feature = "sys")]
+#[test]
+fn deserialize_checked() -> Result<(), String> {
+    let mut store = Store::default();
+    let wat = r#"(module (func (export "answer") (result i32) (i32.const 42)))"#;
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let signing_key = ArtifactSigningKey::from_bytes(&[1; 32]);
+    let mut trusted_keys = TrustedKeys::new();
+    trusted_keys.add(signing_key.public_key());
+
+    let signed = module
+        .serialize_checked(Some(&signing_key))
+        .map_err(|e| format!("{e:?}"))?;
+    let module = Module::deserialize_checked(&store, signed.clone(), &trusted_keys)
+        .map_err(|e| format!("{e:?}"))?;
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    let answer: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "answer")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(answer.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+
+    // Tampered, unsigned and untrusted modules are rejected
+    let mut tampered = signed.to_vec();
+    tampered[100] ^= 1;
+    assert!(matches!(
+        Module::deserialize_checked(&store, tampered.clone(), &trusted_keys),
+        Err(DeserializeError::CorruptedBinary(_))
+    ));
+    assert!(matches!(
+        unsafe { Module::deserialize(&store, tampered) },
+        Err(DeserializeError::CorruptedBinary(_))
+    ));
+    let unsigned = module
+        .serialize_checked(None)
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(
+        Module::deserialize_checked(&store, unsigned, &trusted_keys),
+        Err(DeserializeError::Untrusted(_))
+    ));
+    assert!(matches!(
+        Module::deserialize_checked(&store, signed, &TrustedKeys::new()),
+        Err(DeserializeError::Untrusted(_))
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn deserialize_incompatible() -> Result<(), String> {
+    let store = Store::default();
+    let module = Module::new(&store, "(module)").map_err(|e| format!("{e:?}"))?;
+    let signing_key = ArtifactSigningKey::from_bytes(&[1; 32]);
+    let mut trusted_keys = TrustedKeys::new();
+    trusted_keys.add(signing_key.public_key());
+    let signed = module
+        .serialize_checked(Some(&signing_key))
+        .map_err(|e| format!("{e:?}"))?;
+    let headless_store =
+        |target| Store::new(EngineBuilder::headless().set_target(Some(target)).engine());
+
+    // Modules compiled for another target are rejected, even if they are
+    // signed by a trusted key
+    let triple: Triple = "riscv64gc-unknown-linux-gnu".parse().unwrap();
+    let other_store = headless_store(Target::new(triple, CpuFeature::set()));
+    assert!(matches!(
+        Module::deserialize_checked(&other_store, signed.clone(), &trusted_keys),
+        Err(DeserializeError::Incompatible(_))
+    ));
+    assert!(matches!(
+        unsafe { Module::deserialize(&other_store, signed.clone()) },
+        Err(DeserializeError::Incompatible(_))
+    ));
+
+    // So are modules that use CPU features the target doesn't have
+    if !store.engine().target().cpu_features().is_empty() {
+        let other_store = headless_store(Target::new(Triple::host(), CpuFeature::set()));
+        assert!(matches!(
+            Module::deserialize_checked(&other_store, signed.clone(), &trusted_keys),
+            Err(DeserializeError::Incompatible(_))
+        ));
+    }
+
+    // The engine the module was compiled with loads it
+    let same_store = headless_store(store.engine().target().clone());
+    Module::deserialize_checked(&same_store, signed, &trusted_keys)
+        .map_err(|e| format!("{e:?}"))?;
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn modules_share_artifacts() -> Result<(), String> {
+    let engine = Store::default().engine().clone();
+    let wat = r#"(module (func (export "answer") (result i32) (i32.const 42)))"#;
+    let mut store = Store::new(engine.clone());
+    let other_store = Store::new(engine.clone());
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let other_module = Module::new(&other_store, wat).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(engine.artifact_count(), 1);
+    let serialized = module.serialize().map_err(|e| format!("{e:?}"))?;
+    let deserialized = unsafe { Module::deserialize(&other_store, serialized.clone()) }
+        .map_err(|e| format!("{e:?}"))?;
+    unsafe { Module::deserialize(&store, serialized) }.map_err(|e| format!("{e:?}"))?;
+    assert_eq!(engine.artifact_count(), 2);
+    drop((other_module, deserialized));
+
+    // The store keeps the code of its instances alive
+    let instance =
+        Instance::new(&mut store, &module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+    drop(module);
+    assert_eq!(engine.artifact_count(), 1);
+    let answer: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&mut store, "answer")
+        .map_err(|e| format!("{e:?}"))?;
+    assert_eq!(answer.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+    drop((instance, answer, store));
+    assert_eq!(engine.artifact_count(), 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn streaming_compilation() -> Result<(), String> {
+    /// A reader that returns a few bytes at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let wasm = wat2wasm(
+        br#"(module
+              (memory 1)
+              (func $double (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+              (func (export "answer") (result i32)
+                (i32.add (call $double (i32.load (i32.const 0))) (i32.const 2)))
+              (data (i32.const 0) "\14"))"#,
+    )
+    .map_err(|e| format!("{e:?}"))?;
+    let mut store = Store::default();
+
+    let mut streaming = Module::streaming(&store);
+    for byte in wasm.chunks(1) {
+        streaming.push(byte).map_err(|e| format!("{e:?}"))?;
+    }
+    let module = streaming.finish().map_err(|e| format!("{e:?}"))?;
+    let read_module = Module::from_reader(&store, Trickle(&wasm)).map_err(|e| format!("{e:?}"))?;
+
+    for module in &[module, read_module] {
+        let instance =
+            Instance::new(&mut store, module, &imports! {}).map_err(|e| format!("{e:?}"))?;
+        let answer: TypedFunction<(), i32> = instance
+            .exports
+            .get_typed_function(&mut store, "answer")
+            .map_err(|e| format!("{e:?}"))?;
+        assert_eq!(answer.call(&mut store).map_err(|e| format!("{e:?}"))?, 42);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sys")]
+#[test]
+fn streaming_compilation_validates() -> Result<(), String> {
+    let store = Store::default();
+    let wasm = wat2wasm(br#"(module (func (export "answer") (result i32) (i32.const 42)))"#)
+        .map_err(|e| format!("{e:?}"))?;
+
+    // A truncated module
+    let mut streaming = Module::streaming(&store);
+    streaming
+        .push(&wasm[..wasm.len() - 1])
+        .map_err(|e| format!("{e:?}"))?;
+    assert!(matches!(streaming.finish(), Err(CompileError::Validate(_))));
+
+    // A function returning an i64
+    let mut invalid = wasm.to_vec();
+    let const_op = invalid.iter().rposition(|&byte| byte == 0x41).unwrap();
+    invalid[const_op] = 0x42;
+    let mut streaming = Module::streaming(&store);
+    let result = streaming.push(&invalid).and_then(|()| streaming.finish());
+    assert!(matches!(result, Err(CompileError::Validate(_))));
+
+    Ok(())
+}
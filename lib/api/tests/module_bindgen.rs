@@ -0,0 +1,300 @@
+#![cfg(feature = "sys")]
+
+use wasmer::*;
+
+mod witx {
+    wasmer::module_bindgen!(
+        inline = r#"
+            ;;; Error codes.
+            (typename $errno
+              (enum (@witx tag u16)
+                $success
+                ;;; Bad file descriptor.
+                $badf
+                $inval))
+
+            (typename $perms
+              (flags (@witx repr u8)
+                $read
+                $write))
+
+            ;;; A point in the plane.
+            (typename $point
+              (record
+                (field $x s32)
+                (field $y s32)))
+
+            (typename $fd u32)
+
+            (module $host
+              (import "memory" (memory))
+              ;;; Logs a message.
+              (@interface func (export "log")
+                (param $message string))
+              (@interface func (export "sum")
+                (param $values (list u32))
+                (result $sum u32))
+              (@interface func (export "add")
+                (param $a $point)
+                (param $b $point)
+                (result $point $point))
+              (@interface func (export "open")
+                (param $path string)
+                (param $perms $perms)
+                (result $error (expected $fd (error $errno))))
+              (@interface func (export "close")
+                (param $fd $fd)
+                (result $error (expected (error $errno))))
+            )
+        "#
+    );
+}
+
+use witx::host::{Errno, Perms, Point};
+
+#[derive(Default)]
+struct HostEnv {
+    memory: Option<Memory>,
+    messages: Vec<String>,
+    files: Vec<(String, Perms)>,
+}
+
+impl witx::HostEnv for HostEnv {
+    fn memory(&self) -> &Memory {
+        self.memory.as_ref().unwrap()
+    }
+}
+
+impl witx::host::Host for HostEnv {
+    fn log(mut env: FunctionEnvMut<'_, Self>, message: String) -> Result<(), RuntimeError> {
+        env.data_mut().messages.push(message);
+        Ok(())
+    }
+
+    fn sum(_env: FunctionEnvMut<'_, Self>, values: Vec<u32>) -> Result<u32, RuntimeError> {
+        Ok(values.iter().sum())
+    }
+
+    fn add(_env: FunctionEnvMut<'_, Self>, a: Point, b: Point) -> Result<Point, RuntimeError> {
+        Ok(Point {
+            x: a.x + b.x,
+            y: a.y + b.y,
+        })
+    }
+
+    fn open(
+        mut env: FunctionEnvMut<'_, Self>,
+        path: String,
+        perms: Perms,
+    ) -> Result<Result<u32, Errno>, RuntimeError> {
+        if path.is_empty() {
+            return Ok(Err(Errno::INVAL));
+        }
+        let files = &mut env.data_mut().files;
+        files.push((path, perms));
+        Ok(Ok(files.len() as u32 + 2))
+    }
+
+    fn close(env: FunctionEnvMut<'_, Self>, fd: u32) -> Result<Result<(), Errno>, RuntimeError> {
+        if (3..env.data().files.len() as u32 + 3).contains(&fd) {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(Errno::BADF))
+        }
+    }
+}
+
+fn instantiate_witx(store: &mut Store) -> anyhow::Result<(Instance, FunctionEnv<HostEnv>)> {
+    let module = Module::new(
+        store,
+        r#"
+(module
+  (import "host" "log" (func $log (param i32 i32)))
+  (import "host" "sum" (func $sum (param i32 i32) (result i32)))
+  (import "host" "add" (func $add (param i32 i32 i32)))
+  (import "host" "open" (func $open (param i32 i32 i32 i32) (result i32)))
+  (import "host" "close" (func $close (param i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "hello")
+  (data (i32.const 16) "\01\00\00\00\02\00\00\00\03\00\00\00")
+  (data (i32.const 32) "\01\00\00\00\02\00\00\00\0a\00\00\00\14\00\00\00")
+  (data (i32.const 80) "\ff\fe")
+  (func (export "greet")
+    (call $log (i32.const 0) (i32.const 5)))
+  (func (export "greet_invalid")
+    (call $log (i32.const 80) (i32.const 2)))
+  (func (export "total") (result i32)
+    (call $sum (i32.const 16) (i32.const 3)))
+  (func (export "total_out_of_bounds") (result i32)
+    (call $sum (i32.const 16) (i32.const 0x10000000)))
+  (func (export "add_points")
+    (call $add (i32.const 32) (i32.const 40) (i32.const 48)))
+  (func (export "open") (param i32 i32) (result i32)
+    (call $open (i32.const 0) (local.get 0) (local.get 1) (i32.const 64)))
+  (func (export "close") (param i32) (result i32)
+    (call $close (local.get 0)))
+)
+"#,
+    )?;
+    let env = FunctionEnv::new(store, HostEnv::default());
+    let mut imports = Imports::new();
+    witx::Host::add_to_imports(store, &mut imports, &env);
+    let instance = Instance::new(store, &module, &imports)?;
+    let memory = instance.exports.get_memory("memory")?.clone();
+    env.as_mut(store).memory = Some(memory);
+    Ok((instance, env))
+}
+
+#[test]
+fn witx_types() {
+    assert_eq!(Errno::BADF, Errno(1));
+    assert_eq!(Errno::INVAL.name(), Some("inval"));
+    assert_eq!(format!("{:?}", Errno::SUCCESS), "success");
+    assert_eq!(format!("{:?}", Errno(7)), "Errno(7)");
+
+    let perms = Perms::READ | Perms::WRITE;
+    assert_eq!(perms, Perms(3));
+    assert!(perms.contains(Perms::WRITE));
+    assert!(!Perms::READ.contains(perms));
+
+    assert_eq!(std::mem::size_of::<Point>(), 8);
+}
+
+#[test]
+fn witx_imports_decode_memory() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, env) = instantiate_witx(&mut store)?;
+    let memory = instance.exports.get_memory("memory")?.clone();
+
+    let greet: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "greet")?;
+    greet.call(&mut store)?;
+    assert_eq!(env.as_ref(&store).messages, ["hello"]);
+
+    let total: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "total")?;
+    assert_eq!(total.call(&mut store)?, 6);
+
+    let add_points: TypedFunction<(), ()> =
+        instance.exports.get_typed_function(&store, "add_points")?;
+    add_points.call(&mut store)?;
+    let point = WasmPtr::<Point>::new(48).read(&memory.view(&store))?;
+    assert_eq!(point, Point { x: 11, y: 22 });
+
+    Ok(())
+}
+
+#[test]
+fn witx_imports_return_errors() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, env) = instantiate_witx(&mut store)?;
+    let memory = instance.exports.get_memory("memory")?.clone();
+
+    let open: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&store, "open")?;
+    assert_eq!(open.call(&mut store, 5, 3)?, 0);
+    let fd = WasmPtr::<u32>::new(64).read(&memory.view(&store))?;
+    assert_eq!(fd, 3);
+    assert_eq!(
+        env.as_ref(&store).files,
+        [("hello".to_string(), Perms::READ | Perms::WRITE)]
+    );
+    assert_eq!(open.call(&mut store, 0, 1)?, Errno::INVAL.0 as i32);
+
+    let close: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "close")?;
+    assert_eq!(close.call(&mut store, 3)?, 0);
+    assert_eq!(close.call(&mut store, 4)?, Errno::BADF.0 as i32);
+
+    Ok(())
+}
+
+#[test]
+fn witx_imports_trap_on_invalid_arguments() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let (instance, _) = instantiate_witx(&mut store)?;
+
+    let greet_invalid: TypedFunction<(), ()> = instance
+        .exports
+        .get_typed_function(&store, "greet_invalid")?;
+    assert!(greet_invalid.call(&mut store).is_err());
+
+    let total_out_of_bounds: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&store, "total_out_of_bounds")?;
+    assert!(total_out_of_bounds.call(&mut store).is_err());
+
+    Ok(())
+}
+
+mod wit {
+    wasmer::module_bindgen!(
+        inline = r#"
+            world calc {
+                import double: func(x: u32) -> u32
+                /// Doubles and adds one.
+                export apply: func(x: u32) -> u32
+                export is-even: func(x: s64) -> bool
+                export halve: func(x: float64) -> float64
+                export first: func(c: char) -> char
+            }
+        "#
+    );
+}
+
+struct CalcEnv {
+    memory: Option<Memory>,
+}
+
+impl wit::CalcEnv for CalcEnv {
+    fn memory(&self) -> &Memory {
+        self.memory.as_ref().unwrap()
+    }
+}
+
+impl wit::CalcImports for CalcEnv {
+    fn double(_env: FunctionEnvMut<'_, Self>, x: u32) -> Result<u32, RuntimeError> {
+        Ok(x * 2)
+    }
+}
+
+#[test]
+fn wit_world_exports() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+(module
+  (import "env" "double" (func $double (param i32) (result i32)))
+  (func (export "apply") (param i32) (result i32)
+    (i32.add (call $double (local.get 0)) (i32.const 1)))
+  (func (export "is-even") (param i64) (result i32)
+    (i64.eqz (i64.rem_u (local.get 0) (i64.const 2))))
+  (func (export "halve") (param f64) (result f64)
+    (f64.div (local.get 0) (f64.const 2)))
+  (func (export "first") (param i32) (result i32)
+    (local.get 0))
+)
+"#,
+    )?;
+    let env = FunctionEnv::new(&mut store, CalcEnv { memory: None });
+    let mut imports = Imports::new();
+    wit::Calc::add_to_imports(&mut store, &mut imports, &env);
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let calc = wit::Calc::new(&store, &instance)?;
+
+    assert_eq!(calc.apply(&mut store, 20)?, 41);
+    assert!(calc.is_even(&mut store, 42)?);
+    assert!(!calc.is_even(&mut store, 7)?);
+    assert_eq!(calc.halve(&mut store, 5.0)?, 2.5);
+    assert_eq!(calc.first(&mut store, 'é')?, 'é');
+    assert!(env.as_ref(&store).memory.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn wit_world_checks_exports() -> anyhow::Result<()> {
+    let mut store = Store::default();
+    let module = Module::new(&store, r#"(module (func (export "apply") (param i64)))"#)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    assert!(wit::Calc::new(&store, &instance).is_err());
+    Ok(())
+}
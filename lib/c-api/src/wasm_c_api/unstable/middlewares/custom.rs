@@ -0,0 +1,658 @@
+//! Unstable non-standard Wasmer-specific API to write module
+//! middlewares in C.
+//!
+//! A custom middleware calls a C callback for each operator of each
+//! function of a module. The callback emits the operators that replace
+//! it, which can be the operator itself, nothing, or any sequence of
+//! operators. The middleware can also add globals to the module, for
+//! instance to count things at runtime, that are exported so that the
+//! host can read them.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use wasmer_inline_c::assert_c;
+//! # fn main() {
+//! #    (assert_c! {
+//! # #include "tests/wasmer.h"
+//! #
+//! // Count the calls, and turn the additions into multiplications.
+//! bool feed(void* env, uint32_t local_function_index, wasmer_parser_operator_t operator, wasmer_middleware_state_t* state) {
+//!     (void) env;
+//!     (void) local_function_index;
+//!
+//!     switch (operator) {
+//!         case Call: {
+//!             // Add 1 to the first global of the middleware.
+//!             uint32_t calls = wasmer_middleware_state_global_index(state, 0);
+//!             wasmer_middleware_state_push_with_index(state, GlobalGet, calls);
+//!             wasmer_middleware_state_push_i64_const(state, 1);
+//!             wasmer_middleware_state_push(state, I64Add);
+//!             wasmer_middleware_state_push_with_index(state, GlobalSet, calls);
+//!             wasmer_middleware_state_push_current(state);
+//!             return true;
+//!         }
+//!
+//!         case I32Add:
+//!             return wasmer_middleware_state_push(state, I32Mul);
+//!
+//!         default:
+//!             wasmer_middleware_state_push_current(state);
+//!             return true;
+//!     }
+//! }
+//!
+//! int main() {
+//!     // Create the middleware, with a global to count the calls.
+//!     wasmer_custom_middleware_t* custom = wasmer_custom_middleware_new("call-counter", feed, NULL, NULL);
+//!     wasm_val_t zero = WASM_I64_VAL(0);
+//!     assert(wasmer_custom_middleware_add_global(custom, &zero, "calls") == 0);
+//!
+//!     // Create the configuration, the engine and the store.
+//!     wasm_config_t* config = wasm_config_new();
+//!     wasm_config_push_middleware(config, wasmer_custom_middleware_as_middleware(custom));
+//!     wasm_engine_t* engine = wasm_engine_new_with_config(config);
+//!     wasm_store_t* store = wasm_store_new(engine);
+//!
+//!     // Create the module, and instantiate it.
+//!     wasm_byte_vec_t wat;
+//!     wasmer_byte_vec_new_from_string(
+//!         &wat,
+//!         "(module\n"
+//!         "  (func $add (param i32 i32) (result i32)\n"
+//!         "    (i32.add (local.get 0) (local.get 1)))\n"
+//!         "  (func (export \"add_twice\") (param i32 i32) (result i32)\n"
+//!         "    (call $add (call $add (local.get 0) (local.get 1)) (local.get 1))))"
+//!     );
+//!     wasm_byte_vec_t wasm;
+//!     wat2wasm(&wat, &wasm);
+//!
+//!     wasm_module_t* module = wasm_module_new(store, &wasm);
+//!     assert(module);
+//!
+//!     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+//!     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+//!     assert(instance);
+//!
+//!     // The exports of the middleware come after the ones of the module.
+//!     wasm_extern_vec_t exports;
+//!     wasm_instance_exports(instance, &exports);
+//!     assert(exports.size == 2);
+//!     const wasm_func_t* add_twice = wasm_extern_as_func(exports.data[0]);
+//!     const wasm_global_t* calls = wasm_extern_as_global(exports.data[1]);
+//!
+//!     wasm_val_t arguments[2] = { WASM_I32_VAL(2), WASM_I32_VAL(3) };
+//!     wasm_val_t results[1] = { WASM_INIT_VAL };
+//!     wasm_val_vec_t arguments_as_array = WASM_ARRAY_VEC(arguments);
+//!     wasm_val_vec_t results_as_array = WASM_ARRAY_VEC(results);
+//!     wasm_trap_t* trap = wasm_func_call(add_twice, &arguments_as_array, &results_as_array);
+//!     assert(trap == NULL);
+//!
+//!     // (2 * 3) * 3
+//!     assert(results[0].of.i32 == 18);
+//!
+//!     wasm_val_t value;
+//!     wasm_global_get(calls, &value);
+//!     assert(value.of.i64 == 2);
+//!
+//!     wasm_extern_vec_delete(&exports);
+//!     wasm_instance_delete(instance);
+//!     wasm_module_delete(module);
+//!     wasm_byte_vec_delete(&wasm);
+//!     wasm_byte_vec_delete(&wat);
+//!     wasm_store_delete(store);
+//!     wasm_engine_delete(engine);
+//!
+//!     return 0;
+//! }
+//! #    })
+//! #    .success();
+//! # }
+//! ```
+
+use super::super::super::types::wasm_valkind_enum;
+use super::super::super::value::wasm_val_t;
+use super::super::parser::operator::wasmer_parser_operator_t;
+use super::wasmer_middleware_t;
+use std::convert::TryFrom;
+use std::ffi::{c_void, CStr};
+use std::fmt;
+use std::os::raw::c_char;
+use std::sync::Arc;
+use wasmer_api::wasmparser::Operator;
+use wasmer_api::{
+    ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{entity::EntityRef, GlobalIndex, ModuleInfo};
+
+/// Function type to represent the callback of a custom middleware,
+/// see [`wasmer_custom_middleware_new`].
+///
+/// The callback is called for each operator of the function at
+/// `local_function_index`. It emits the operators that replace it
+/// with the `wasmer_middleware_state_push*` functions, and returns
+/// `false` to reject the module.
+#[allow(non_camel_case_types)]
+pub type wasmer_custom_middleware_feed_t = unsafe extern "C" fn(
+    env: *mut c_void,
+    local_function_index: u32,
+    operator: wasmer_parser_operator_t,
+    state: &mut wasmer_middleware_state_t,
+) -> bool;
+
+/// The callback of a custom middleware and its environment.
+struct Callback {
+    feed: wasmer_custom_middleware_feed_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+}
+
+// The host is responsible for the callback and its environment being
+// usable from any thread.
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+impl Drop for Callback {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer {
+            unsafe { finalizer(self.env) };
+        }
+    }
+}
+
+/// A global added to the modules by a custom middleware.
+#[derive(Debug)]
+struct CustomGlobal {
+    ty: Type,
+    init: GlobalInit,
+    export_name: String,
+}
+
+/// The module-level custom middleware.
+struct CustomMiddleware {
+    name: String,
+    callback: Arc<Callback>,
+    globals: Vec<CustomGlobal>,
+}
+
+impl fmt::Debug for CustomMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomMiddleware")
+            .field("name", &self.name)
+            .field("callback", &"<function>")
+            .field("globals", &self.globals)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for CustomMiddleware {
+    fn generate_function_middleware(
+        &self,
+        module_info: &ModuleInfo,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        // The globals are found by their exports, as the middleware can
+        // be applied to several modules
+        let global_indexes = self
+            .globals
+            .iter()
+            .map(|global| match module_info.exports.get(&global.export_name) {
+                Some(ExportIndex::Global(index)) => *index,
+                _ => panic!(
+                    "CustomMiddleware::generate_function_middleware: the `{}` middleware wasn't applied to the module.",
+                    self.name
+                ),
+            })
+            .collect();
+
+        Box::new(CustomFunctionMiddleware {
+            name: self.name.clone(),
+            callback: self.callback.clone(),
+            local_function_index: local_function_index.index() as u32,
+            global_indexes,
+        })
+    }
+
+    /// The name of the middleware identifies its callback.
+    fn deterministic_id(&self) -> Option<String> {
+        Some(format!("custom-{}-globals={:?}", self.name, self.globals))
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        for global in &self.globals {
+            let index = module_info
+                .globals
+                .push(GlobalType::new(global.ty, Mutability::Var));
+            module_info.global_initializers.push(global.init.clone());
+            module_info
+                .exports
+                .insert(global.export_name.clone(), ExportIndex::Global(index));
+        }
+    }
+}
+
+/// The function-level custom middleware.
+struct CustomFunctionMiddleware {
+    name: String,
+    callback: Arc<Callback>,
+    local_function_index: u32,
+    global_indexes: Vec<GlobalIndex>,
+}
+
+impl fmt::Debug for CustomFunctionMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomFunctionMiddleware")
+            .field("name", &self.name)
+            .field("local_function_index", &self.local_function_index)
+            .finish()
+    }
+}
+
+impl FunctionMiddleware for CustomFunctionMiddleware {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let kind = wasmer_parser_operator_t::from(&operator);
+        let mut state = wasmer_middleware_state_t {
+            operator,
+            output: state,
+            global_indexes: &self.global_indexes,
+        };
+        let callback = &self.callback;
+
+        if unsafe { (callback.feed)(callback.env, self.local_function_index, kind, &mut state) } {
+            Ok(())
+        } else {
+            Err(MiddlewareError::new(
+                &self.name,
+                format!("the `{:?}` operator was rejected", kind),
+            ))
+        }
+    }
+}
+
+/// Opaque type representing a custom middleware, i.e. a middleware
+/// implemented in C.
+///
+/// To transform this specific middleware into a generic one, please
+/// see [`wasmer_custom_middleware_as_middleware`].
+///
+/// # Example
+///
+/// See module's documentation.
+#[allow(non_camel_case_types)]
+pub struct wasmer_custom_middleware_t {
+    inner: CustomMiddleware,
+}
+
+/// Opaque type representing the operator that a custom middleware is
+/// fed with, and the operators it emits.
+///
+/// It is only valid during the call of the callback of the
+/// middleware.
+///
+/// # Example
+///
+/// See module's documentation.
+#[allow(non_camel_case_types)]
+pub struct wasmer_middleware_state_t<'a, 'b> {
+    operator: Operator<'a>,
+    output: &'b mut MiddlewareReaderState<'a>,
+    global_indexes: &'b [GlobalIndex],
+}
+
+/// Creates a new custom middleware that calls `feed` for each operator
+/// of the modules it is applied to.
+///
+/// `name` identifies the middleware in the errors and in the caches of
+/// compiled modules, so two middlewares with the same name must
+/// instrument the modules in the same way. `env` is passed to each call
+/// of `feed`, and to `finalizer`, if any, when the middleware is
+/// deleted.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_custom_middleware_new(
+    name: *const c_char,
+    feed: wasmer_custom_middleware_feed_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+) -> Option<Box<wasmer_custom_middleware_t>> {
+    let name = c_try!(CStr::from_ptr(name).to_str());
+
+    Some(Box::new(wasmer_custom_middleware_t {
+        inner: CustomMiddleware {
+            name: name.to_string(),
+            callback: Arc::new(Callback {
+                feed,
+                env,
+                finalizer,
+            }),
+            globals: vec![],
+        },
+    }))
+}
+
+/// Deletes a [`wasmer_custom_middleware_t`].
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_custom_middleware_delete(
+    _middleware: Option<Box<wasmer_custom_middleware_t>>,
+) {
+}
+
+/// Adds a mutable global to the modules the middleware is applied to,
+/// initialized with `initial_value` and exported as `export_name`.
+///
+/// The function returns the number of the global in the middleware,
+/// to be passed to [`wasmer_middleware_state_global_index`], or `-1`
+/// if the value isn't a number or the name isn't valid UTF-8.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_custom_middleware_add_global(
+    middleware: &mut wasmer_custom_middleware_t,
+    initial_value: &wasm_val_t,
+    export_name: *const c_char,
+) -> i32 {
+    let export_name = c_try!(CStr::from_ptr(export_name).to_str(); otherwise -1);
+    let kind = c_try!(wasm_valkind_enum::try_from(initial_value.kind); otherwise -1);
+    let (ty, init) = match kind {
+        wasm_valkind_enum::WASM_I32 => (Type::I32, GlobalInit::I32Const(initial_value.of.int32_t)),
+        wasm_valkind_enum::WASM_I64 => (Type::I64, GlobalInit::I64Const(initial_value.of.int64_t)),
+        wasm_valkind_enum::WASM_F32 => {
+            (Type::F32, GlobalInit::F32Const(initial_value.of.float32_t))
+        }
+        wasm_valkind_enum::WASM_F64 => {
+            (Type::F64, GlobalInit::F64Const(initial_value.of.float64_t))
+        }
+        _ => {
+            crate::error::update_last_error("globals of custom middlewares must be numbers");
+
+            return -1;
+        }
+    };
+
+    let globals = &mut middleware.inner.globals;
+    globals.push(CustomGlobal {
+        ty,
+        init,
+        export_name: export_name.to_string(),
+    });
+
+    (globals.len() - 1) as i32
+}
+
+/// Transforms a [`wasmer_custom_middleware_t`] into a generic
+/// [`wasmer_middleware_t`], to then be pushed in the configuration with
+/// [`wasm_config_push_middleware`][super::wasm_config_push_middleware].
+///
+/// This function takes ownership of `middleware`.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_custom_middleware_as_middleware(
+    middleware: Option<Box<wasmer_custom_middleware_t>>,
+) -> Option<Box<wasmer_middleware_t>> {
+    let middleware = middleware?;
+
+    Some(Box::new(wasmer_middleware_t {
+        inner: Arc::new(middleware.inner),
+    }))
+}
+
+/// Emits the operator the middleware is fed with, unchanged.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_push_current(state: &mut wasmer_middleware_state_t) {
+    state.output.push_operator(state.operator.clone());
+}
+
+/// Emits an operator without immediates, e.g. `I32Add` or `Drop`.
+/// `Block`, `Loop` and `If` are emitted without parameters and
+/// results.
+///
+/// The function returns `false` if the operator has immediates.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_push(
+    state: &mut wasmer_middleware_state_t,
+    operator: wasmer_parser_operator_t,
+) -> bool {
+    match operator.to_operator() {
+        Some(operator) => {
+            state.output.push_operator(operator);
+
+            true
+        }
+        None => false,
+    }
+}
+
+/// Emits an operator whose only immediate is an index: `LocalGet`,
+/// `LocalSet`, `LocalTee`, `GlobalGet`, `GlobalSet`, `Call`, `Br`,
+/// `BrIf` or `RefFunc`.
+///
+/// The function returns `false` for the other operators.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_push_with_index(
+    state: &mut wasmer_middleware_state_t,
+    operator: wasmer_parser_operator_t,
+    index: u32,
+) -> bool {
+    use wasmer_parser_operator_t as K;
+
+    let operator = match operator {
+        K::LocalGet => Operator::LocalGet { local_index: index },
+        K::LocalSet => Operator::LocalSet { local_index: index },
+        K::LocalTee => Operator::LocalTee { local_index: index },
+        K::GlobalGet => Operator::GlobalGet {
+            global_index: index,
+        },
+        K::GlobalSet => Operator::GlobalSet {
+            global_index: index,
+        },
+        K::Call => Operator::Call {
+            function_index: index,
+        },
+        K::Br => Operator::Br {
+            relative_depth: index,
+        },
+        K::BrIf => Operator::BrIf {
+            relative_depth: index,
+        },
+        K::RefFunc => Operator::RefFunc {
+            function_index: index,
+        },
+        _ => return false,
+    };
+    state.output.push_operator(operator);
+
+    true
+}
+
+/// Emits an `i32.const` operator.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_push_i32_const(
+    state: &mut wasmer_middleware_state_t,
+    value: i32,
+) {
+    state.output.push_operator(Operator::I32Const { value });
+}
+
+/// Emits an `i64.const` operator.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_push_i64_const(
+    state: &mut wasmer_middleware_state_t,
+    value: i64,
+) {
+    state.output.push_operator(Operator::I64Const { value });
+}
+
+/// Reads the index immediate of the operator the middleware is fed
+/// with, for the operators accepted by
+/// [`wasmer_middleware_state_push_with_index`], and the type index of
+/// `CallIndirect`.
+///
+/// The function returns `false` if the operator has no such index.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_index(
+    state: &wasmer_middleware_state_t,
+    out: &mut u32,
+) -> bool {
+    *out = match state.operator {
+        Operator::LocalGet { local_index }
+        | Operator::LocalSet { local_index }
+        | Operator::LocalTee { local_index } => local_index,
+        Operator::GlobalGet { global_index } | Operator::GlobalSet { global_index } => global_index,
+        Operator::Call { function_index } | Operator::RefFunc { function_index } => function_index,
+        Operator::CallIndirect { index, .. } => index,
+        Operator::Br { relative_depth } | Operator::BrIf { relative_depth } => relative_depth,
+        _ => return false,
+    };
+
+    true
+}
+
+/// Returns the index in the module of the global number `global` of
+/// the middleware, as returned by
+/// [`wasmer_custom_middleware_add_global`], or `-1` if there is no
+/// such global.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_middleware_state_global_index(
+    state: &wasmer_middleware_state_t,
+    global: u32,
+) -> i64 {
+    state
+        .global_indexes
+        .get(global as usize)
+        .map_or(-1, |index| index.index() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(target_os = "windows"))]
+    use inline_c::assert_c;
+    #[cfg(target_os = "windows")]
+    use wasmer_inline_c::assert_c;
+
+    #[test]
+    fn test_custom_middleware_can_reject_modules() {
+        (assert_c! {
+            #include "tests/wasmer.h"
+
+            typedef struct {
+                uint32_t called_functions[4];
+                int finalized;
+            } env_t;
+
+            // Reject the modules that read globals, and record the
+            // functions called.
+            static bool feed(void* env, uint32_t local_function_index, wasmer_parser_operator_t operator, wasmer_middleware_state_t* state) {
+                (void) local_function_index;
+                env_t* calls = (env_t*) env;
+                uint32_t index = 0;
+
+                switch (operator) {
+                    case GlobalGet:
+                        return false;
+
+                    case Call:
+                        assert(wasmer_middleware_state_index(state, &index));
+                        calls->called_functions[index] += 1;
+                        break;
+
+                    default:
+                        assert(!wasmer_middleware_state_index(state, &index));
+                        break;
+                }
+
+                assert(wasmer_middleware_state_global_index(state, 0) == -1);
+                wasmer_middleware_state_push_current(state);
+                return true;
+            }
+
+            static void finalize(void* env) {
+                ((env_t*) env)->finalized = 1;
+            }
+
+            int main() {
+                env_t env = {0};
+                wasmer_custom_middleware_t* custom = wasmer_custom_middleware_new("checker", feed, &env, finalize);
+                assert(custom);
+                wasm_config_t* config = wasm_config_new();
+                wasm_config_push_middleware(config, wasmer_custom_middleware_as_middleware(custom));
+                wasm_engine_t* engine = wasm_engine_new_with_config(config);
+                wasm_store_t* store = wasm_store_new(engine);
+
+                wasm_byte_vec_t wat;
+                wasmer_byte_vec_new_from_string(
+                    &wat,
+                    "(module\n"
+                    "  (func $f)\n"
+                    "  (func (call $f) (call $f)))"
+                );
+                wasm_byte_vec_t wasm;
+                wat2wasm(&wat, &wasm);
+                wasm_module_t* module = wasm_module_new(store, &wasm);
+                assert(module);
+                assert(env.called_functions[0] == 2);
+                wasm_module_delete(module);
+                wasm_byte_vec_delete(&wasm);
+                wasm_byte_vec_delete(&wat);
+
+                wasmer_byte_vec_new_from_string(
+                    &wat,
+                    "(module\n"
+                    "  (global $g i32 (i32.const 0))\n"
+                    "  (func (result i32) (global.get $g)))"
+                );
+                wat2wasm(&wat, &wasm);
+                assert(!wasm_module_new(store, &wasm));
+                wasm_byte_vec_delete(&wasm);
+                wasm_byte_vec_delete(&wat);
+
+                wasm_store_delete(store);
+                wasm_engine_delete(engine);
+                assert(env.finalized);
+
+                return 0;
+            }
+        })
+        .success();
+    }
+}
@@ -132,12 +132,18 @@
 //! ```
 
 use super::super::super::instance::wasm_instance_t;
+use super::super::super::store::StoreRef;
+use super::super::super::trap::wasm_trap_t;
 use super::super::parser::operator::wasmer_parser_operator_t;
+use super::super::trap::{wasmer_trap_code, wasmer_trap_code_t};
 use super::wasmer_middleware_t;
 use std::sync::Arc;
 use wasmer_api::wasmparser::Operator;
 use wasmer_middlewares::{
-    metering::{get_remaining_points, set_remaining_points, MeteringPoints},
+    metering::{
+        clear_interrupt, get_remaining_points, interrupt_handle, is_interrupted,
+        set_remaining_points, MeteringInterruptHandle, MeteringPoints,
+    },
     Metering,
 };
 
@@ -184,6 +190,24 @@ pub extern "C" fn wasmer_metering_new(
     })
 }
 
+/// Creates a new metering middleware like [`wasmer_metering_new`],
+/// whose execution can also be interrupted from another thread.
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[no_mangle]
+pub extern "C" fn wasmer_metering_new_with_interruption(
+    initial_limit: u64,
+    cost_function: wasmer_metering_cost_function_t,
+) -> Box<wasmer_metering_t> {
+    let cost_function = move |operator: &Operator| -> u64 { cost_function(operator.into()) };
+
+    Box::new(wasmer_metering_t {
+        inner: Arc::new(Metering::new(initial_limit, Box::new(cost_function)).with_interruption()),
+    })
+}
+
 /// Deletes a [`wasmer_metering_t`].
 ///
 /// # Example
@@ -305,6 +329,216 @@ pub unsafe extern "C" fn wasmer_metering_set_remaining_points(
     set_remaining_points(&mut instance.store.store_mut(), &instance.inner, new_limit);
 }
 
+/// Opaque type representing a handle to interrupt the execution of an
+/// instance from another thread.
+///
+/// The handle keeps the store of the instance alive.
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[allow(non_camel_case_types)]
+pub struct wasmer_metering_interrupt_handle_t {
+    _store: StoreRef,
+    inner: MeteringInterruptHandle,
+}
+
+/// Creates a handle to interrupt the execution of an instance from
+/// another thread, e.g. to enforce a timeout. The metering middleware
+/// must have been created with [`wasmer_metering_new_with_interruption`].
+///
+/// An interrupted call traps at its next branch or call, with the
+/// `INTERRUPTED` trap code (see [`wasmer_metering_trap_code`]), and so
+/// do the next calls until [`wasmer_metering_clear_interrupt`] is
+/// called.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// # #include <pthread.h>
+/// # #include <unistd.h>
+/// #
+/// uint64_t cost_function(wasmer_parser_operator_t wasm_operator) {
+///     switch(wasm_operator) {
+///         default:
+///             return 0;
+///     }
+/// }
+///
+/// // Interrupt the instance after a while.
+/// void* interrupt_later(void* handle) {
+///     usleep(50000);
+///     wasmer_metering_interrupt((wasmer_metering_interrupt_handle_t*) handle);
+///
+///     return NULL;
+/// }
+///
+/// int main() {
+///     wasmer_metering_t* metering = wasmer_metering_new_with_interruption(0, cost_function);
+///     wasmer_middleware_t* middleware = wasmer_metering_as_middleware(metering);
+///     wasm_config_t* config = wasm_config_new();
+///     wasm_config_push_middleware(config, middleware);
+///     wasm_engine_t* engine = wasm_engine_new_with_config(config);
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a module that loops forever, and instantiate it.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (func (export \"spin\")\n"
+///         "    (loop $again (br $again))))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(instance);
+///
+///     wasm_extern_vec_t exports;
+///     wasm_instance_exports(instance, &exports);
+///     const wasm_func_t* spin = wasm_extern_as_func(exports.data[0]);
+///
+///     // Interrupt the call from another thread.
+///     wasmer_metering_interrupt_handle_t* handle = wasmer_metering_interrupt_handle_new(instance);
+///     pthread_t thread;
+///     pthread_create(&thread, NULL, interrupt_later, handle);
+///
+///     wasm_val_vec_t arguments = WASM_EMPTY_VEC;
+///     wasm_val_vec_t results = WASM_EMPTY_VEC;
+///     wasm_trap_t* trap = wasm_func_call(spin, &arguments, &results);
+///     pthread_join(thread, NULL);
+///
+///     // The call was interrupted.
+///     assert(trap);
+///     assert(wasmer_metering_is_interrupted(instance));
+///     assert(!wasmer_metering_points_are_exhausted(instance));
+///
+///     wasmer_trap_code_t code;
+///     assert(wasmer_metering_trap_code(instance, trap, &code));
+///     assert(code == INTERRUPTED);
+///
+///     wasmer_metering_clear_interrupt(instance);
+///     assert(!wasmer_metering_is_interrupted(instance));
+///
+///     // Free everything.
+///     wasm_trap_delete(trap);
+///     wasmer_metering_interrupt_handle_delete(handle);
+///     wasm_extern_vec_delete(&exports);
+///     wasm_instance_delete(instance);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_metering_interrupt_handle_new(
+    instance: &mut wasm_instance_t,
+) -> Box<wasmer_metering_interrupt_handle_t> {
+    let inner = interrupt_handle(&mut instance.store.store_mut(), &instance.inner);
+
+    Box::new(wasmer_metering_interrupt_handle_t {
+        _store: instance.store.clone(),
+        inner,
+    })
+}
+
+/// Deletes a [`wasmer_metering_interrupt_handle_t`].
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[no_mangle]
+pub extern "C" fn wasmer_metering_interrupt_handle_delete(
+    _handle: Option<Box<wasmer_metering_interrupt_handle_t>>,
+) {
+}
+
+/// Interrupts the execution of the instance of `handle`. This function
+/// can be called from any thread.
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[no_mangle]
+pub extern "C" fn wasmer_metering_interrupt(handle: &wasmer_metering_interrupt_handle_t) {
+    // The handle keeps the store alive.
+    unsafe { handle.inner.interrupt() };
+}
+
+/// Returns true if the execution of the instance was interrupted,
+/// false otherwise.
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_metering_is_interrupted(instance: &mut wasm_instance_t) -> bool {
+    is_interrupted(&mut instance.store.store_mut(), &instance.inner)
+}
+
+/// Lets an instance whose execution was interrupted run again.
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_metering_clear_interrupt(instance: &mut wasm_instance_t) {
+    clear_interrupt(&mut instance.store.store_mut(), &instance.inner);
+}
+
+/// Like [`wasmer_trap_code`][super::super::trap::wasmer_trap_code],
+/// but the traps raised by the metering middleware of `instance` get
+/// the `METERING_EXHAUSTED` or `INTERRUPTED` trap code instead of
+/// `UNREACHABLE_CODE_REACHED`.
+///
+/// # Example
+///
+/// See [`wasmer_metering_interrupt_handle_new`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_metering_trap_code(
+    instance: &mut wasm_instance_t,
+    trap: &wasm_trap_t,
+    out: &mut wasmer_trap_code_t,
+) -> bool {
+    if !wasmer_trap_code(trap, out) {
+        return false;
+    }
+
+    if *out == wasmer_trap_code_t::UNREACHABLE_CODE_REACHED {
+        let mut store = instance.store.store_mut();
+        let interruptible = instance
+            .inner
+            .exports
+            .get_global("wasmer_metering_interrupted")
+            .is_ok();
+
+        if interruptible && is_interrupted(&mut store, &instance.inner) {
+            *out = wasmer_trap_code_t::INTERRUPTED;
+        } else if let MeteringPoints::Exhausted = get_remaining_points(&mut store, &instance.inner)
+        {
+            *out = wasmer_trap_code_t::METERING_EXHAUSTED;
+        }
+    }
+
+    true
+}
+
 /// Transforms a [`wasmer_metering_t`] into a generic
 /// [`wasmer_middleware_t`], to then be pushed in the configuration with
 /// [`wasm_config_push_middleware`][super::wasm_config_push_middleware].
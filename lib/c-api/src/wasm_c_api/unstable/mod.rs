@@ -6,5 +6,6 @@ pub mod module;
 #[cfg(feature = "compiler")]
 pub mod parser;
 pub mod target_lexicon;
+pub mod trap;
 #[cfg(feature = "wasi")]
 pub mod wasi;
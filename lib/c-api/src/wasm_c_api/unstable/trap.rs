@@ -0,0 +1,170 @@
+//! Unstable non-standard Wasmer-specific API to inspect the reason of
+//! a trap.
+
+use super::super::trap::wasm_trap_t;
+use wasmer_types::TrapCode;
+
+/// The reason of a trap raised by a WebAssembly instruction, see
+/// [`wasmer_trap_code`].
+///
+/// This is a Wasmer-specific type with Wasmer-specific functions for
+/// manipulating it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub enum wasmer_trap_code_t {
+    /// The call stack was exhausted.
+    STACK_OVERFLOW = 0,
+
+    /// A memory access was out of bounds.
+    HEAP_ACCESS_OUT_OF_BOUNDS = 1,
+
+    /// A memory access was misaligned.
+    HEAP_MISALIGNED = 2,
+
+    /// A table access was out of bounds.
+    TABLE_ACCESS_OUT_OF_BOUNDS = 3,
+
+    /// Another bounds check failed.
+    OUT_OF_BOUNDS = 4,
+
+    /// An indirect call reached a null table entry.
+    INDIRECT_CALL_TO_NULL = 5,
+
+    /// The signature of an indirect call didn't match.
+    BAD_SIGNATURE = 6,
+
+    /// An integer arithmetic operation overflowed.
+    INTEGER_OVERFLOW = 7,
+
+    /// An integer was divided by zero.
+    INTEGER_DIVISION_BY_ZERO = 8,
+
+    /// A float-to-int conversion failed.
+    BAD_CONVERSION_TO_INTEGER = 9,
+
+    /// An `unreachable` instruction was reached.
+    ///
+    /// This is also the code that `wasmer_trap_code` returns for the
+    /// traps raised by the metering middleware, which
+    /// `wasmer_metering_trap_code` tells apart.
+    UNREACHABLE_CODE_REACHED = 10,
+
+    /// An atomic memory access was unaligned.
+    UNALIGNED_ATOMIC = 11,
+
+    /// The metering points were exhausted.
+    METERING_EXHAUSTED = 12,
+
+    /// The execution was interrupted from another thread.
+    INTERRUPTED = 13,
+}
+
+impl From<TrapCode> for wasmer_trap_code_t {
+    fn from(other: TrapCode) -> Self {
+        match other {
+            TrapCode::StackOverflow => Self::STACK_OVERFLOW,
+            TrapCode::HeapAccessOutOfBounds => Self::HEAP_ACCESS_OUT_OF_BOUNDS,
+            TrapCode::HeapMisaligned => Self::HEAP_MISALIGNED,
+            TrapCode::TableAccessOutOfBounds => Self::TABLE_ACCESS_OUT_OF_BOUNDS,
+            TrapCode::OutOfBounds => Self::OUT_OF_BOUNDS,
+            TrapCode::IndirectCallToNull => Self::INDIRECT_CALL_TO_NULL,
+            TrapCode::BadSignature => Self::BAD_SIGNATURE,
+            TrapCode::IntegerOverflow => Self::INTEGER_OVERFLOW,
+            TrapCode::IntegerDivisionByZero => Self::INTEGER_DIVISION_BY_ZERO,
+            TrapCode::BadConversionToInteger => Self::BAD_CONVERSION_TO_INTEGER,
+            TrapCode::UnreachableCodeReached => Self::UNREACHABLE_CODE_REACHED,
+            TrapCode::UnalignedAtomic => Self::UNALIGNED_ATOMIC,
+        }
+    }
+}
+
+/// Unstable non-standard Wasmer-specific API to get the reason of a
+/// trap raised by a WebAssembly instruction.
+///
+/// The function returns `true` and writes the reason in `out` if the
+/// trap was raised by a WebAssembly instruction, `false` otherwise,
+/// e.g. if it was raised by a host function or created with
+/// `wasm_trap_new`.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a WebAssembly module that divides by zero.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (func (export \"divide\") (result i32)\n"
+///         "    (i32.div_u (i32.const 1) (i32.const 0))))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(instance);
+///
+///     wasm_extern_vec_t exports;
+///     wasm_instance_exports(instance, &exports);
+///     const wasm_func_t* divide = wasm_extern_as_func(exports.data[0]);
+///
+///     // Call the function, and read the trap code.
+///     wasm_val_vec_t arguments = WASM_EMPTY_VEC;
+///     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+///     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+///     wasm_trap_t* trap = wasm_func_call(divide, &arguments, &results);
+///     assert(trap);
+///
+///     wasmer_trap_code_t code;
+///     assert(wasmer_trap_code(trap, &code));
+///     assert(code == INTEGER_DIVISION_BY_ZERO);
+///
+///     // A trap created by the host has no code.
+///     wasm_message_t message;
+///     wasm_name_new_from_string_nt(&message, "host error");
+///     wasm_trap_t* host_trap = wasm_trap_new(store, &message);
+///     assert(!wasmer_trap_code(host_trap, &code));
+///
+///     // Free everything.
+///     wasm_trap_delete(host_trap);
+///     wasm_name_delete(&message);
+///     wasm_trap_delete(trap);
+///     wasm_extern_vec_delete(&exports);
+///     wasm_instance_delete(instance);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub extern "C" fn wasmer_trap_code(trap: &wasm_trap_t, out: &mut wasmer_trap_code_t) -> bool {
+    match trap.inner.clone().to_trap() {
+        Some(code) => {
+            *out = code.into();
+
+            true
+        }
+        None => false,
+    }
+}
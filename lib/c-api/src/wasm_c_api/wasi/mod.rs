@@ -4,27 +4,40 @@
 
 pub use super::unstable::wasi::wasi_get_unordered_imports;
 use super::{
-    externals::{wasm_extern_t, wasm_extern_vec_t, wasm_func_t, wasm_memory_t},
+    externals::{
+        wasm_env_finalizer_t, wasm_extern_t, wasm_extern_vec_t, wasm_func_t, wasm_memory_t,
+    },
     instance::wasm_instance_t,
     module::wasm_module_t,
     store::{wasm_store_t, StoreRef},
+    trap::wasm_trap_t,
+    types::wasm_byte_vec_t,
 };
 use crate::error::update_last_error;
 use std::convert::TryFrom;
-use std::ffi::CStr;
+use std::ffi::{c_void, CStr};
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
 use std::os::raw::c_char;
+use std::path::Path;
 use std::slice;
+use wasmer_vfs::{mem_fs, FileSystem, FsError, VirtualFile};
 use wasmer_wasi::{
-    get_wasi_version, Pipe, WasiFile, WasiFunctionEnv, WasiState, WasiStateBuilder, WasiVersion,
+    get_wasi_version, Pipe, WasiError, WasiFile, WasiFunctionEnv, WasiState, WasiStateBuilder,
+    WasiVersion,
 };
 
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
 pub struct wasi_config_t {
-    inherit_stdout: bool,
-    inherit_stderr: bool,
-    inherit_stdin: bool,
+    /// The files the standard streams of the guest are connected to,
+    /// `None` for the streams inherited from the host
+    stdin: Option<Box<dyn WasiFile + Send + Sync>>,
+    stdout: Option<Box<dyn WasiFile + Send + Sync>>,
+    stderr: Option<Box<dyn WasiFile + Send + Sync>>,
     state_builder: WasiStateBuilder,
+    /// The in-memory filesystem that replaces the host filesystem, if any
+    fs: Option<mem_fs::FileSystem>,
 }
 
 #[no_mangle]
@@ -37,10 +50,11 @@ pub unsafe extern "C" fn wasi_config_new(
     let prog_name = c_try!(name_c_str.to_str());
 
     Some(Box::new(wasi_config_t {
-        inherit_stdout: true,
-        inherit_stderr: true,
-        inherit_stdin: true,
+        stdin: None,
+        stdout: None,
+        stderr: None,
         state_builder: WasiState::new(prog_name),
+        fs: None,
     }))
 }
 
@@ -128,34 +142,370 @@ pub unsafe extern "C" fn wasi_config_mapdir(
     true
 }
 
+/// Unpacks the tar archive of `data_len` bytes at `data` into the
+/// directory `mount_point` of an in-memory filesystem, and preopens the
+/// directory read-only.
+///
+/// The in-memory filesystem replaces the host filesystem, so host
+/// directories can't be preopened or mapped along with archives.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_config_mount_tar(
+    config: &mut wasi_config_t,
+    mount_point: *const c_char,
+    data: *const u8,
+    data_len: usize,
+) -> bool {
+    debug_assert!(!mount_point.is_null());
+
+    let mount_point_cstr = CStr::from_ptr(mount_point);
+    let mount_point_str = match mount_point_cstr.to_str() {
+        Ok(mount_point_str) => mount_point_str,
+        Err(e) => {
+            update_last_error(e);
+            return false;
+        }
+    };
+    let data = if data_len == 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(data, data_len)
+    };
+
+    let fs = match &config.fs {
+        Some(fs) => fs.clone(),
+        None => {
+            let fs = mem_fs::FileSystem::default();
+            config.state_builder.set_fs(Box::new(fs.clone()));
+            config.fs = Some(fs.clone());
+            fs
+        }
+    };
+    if let Err(e) = fs.unpack_tar(Path::new(mount_point_str), data) {
+        update_last_error(e);
+        return false;
+    }
+    if let Err(e) = config
+        .state_builder
+        .preopen(|p| p.directory(mount_point_str).read(true))
+    {
+        update_last_error(e);
+        return false;
+    }
+
+    true
+}
+
+/// Sets the in-memory filesystem that the guest sees instead of the host
+/// filesystem.
+///
+/// The filesystem is shared with the configuration: files the guest writes
+/// can be read with `wasi_filesystem_read_file` after it ran. Its
+/// directories still have to be preopened or mapped for the guest to open
+/// them, and archives mounted with `wasi_config_mount_tar` after this call
+/// are unpacked into it.
+#[no_mangle]
+pub extern "C" fn wasi_config_set_filesystem(config: &mut wasi_config_t, fs: &wasi_filesystem_t) {
+    config.state_builder.set_fs(Box::new(fs.inner.clone()));
+    config.fs = Some(fs.inner.clone());
+}
+
 #[no_mangle]
 pub extern "C" fn wasi_config_capture_stdout(config: &mut wasi_config_t) {
-    config.inherit_stdout = false;
+    config.stdout = Some(Box::new(Pipe::new()));
 }
 
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stdout(config: &mut wasi_config_t) {
-    config.inherit_stdout = true;
+    config.stdout = None;
+}
+
+/// Sends what the guest writes to its standard output to `callback`.
+///
+/// `env` is passed to each call of `callback`, and to `finalizer`, if
+/// any, once the store of the WASI environment is deleted.
+#[no_mangle]
+pub extern "C" fn wasi_config_stdout_callback(
+    config: &mut wasi_config_t,
+    callback: wasi_write_callback_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+) {
+    config.stdout = Some(Box::new(CallbackFile::writer(callback, env, finalizer)));
 }
 
 #[no_mangle]
 pub extern "C" fn wasi_config_capture_stderr(config: &mut wasi_config_t) {
-    config.inherit_stderr = false;
+    config.stderr = Some(Box::new(Pipe::new()));
 }
 
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stderr(config: &mut wasi_config_t) {
-    config.inherit_stderr = true;
+    config.stderr = None;
 }
 
-//#[no_mangle]
-//pub extern "C" fn wasi_config_capture_stdin(config: &mut wasi_config_t) {
-//    config.inherit_stdin = false;
-//}
+/// Sends what the guest writes to its standard error to `callback`, see
+/// `wasi_config_stdout_callback`.
+#[no_mangle]
+pub extern "C" fn wasi_config_stderr_callback(
+    config: &mut wasi_config_t,
+    callback: wasi_write_callback_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+) {
+    config.stderr = Some(Box::new(CallbackFile::writer(callback, env, finalizer)));
+}
+
+/// Connects the standard input of the guest to a buffer, that the host
+/// writes to with `wasi_env_write_stdin`. The guest reads the end of its
+/// input when the buffer is empty.
+#[no_mangle]
+pub extern "C" fn wasi_config_capture_stdin(config: &mut wasi_config_t) {
+    config.stdin = Some(Box::new(Pipe::new()));
+}
+
+/// Connects the standard input of the guest to a buffer holding the
+/// `data_len` bytes at `data`, like `wasi_config_capture_stdin`.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_config_stdin_buffer(
+    config: &mut wasi_config_t,
+    data: *const u8,
+    data_len: usize,
+) {
+    let mut stdin = Pipe::new();
+    if data_len > 0 {
+        // Writing to a pipe can't fail
+        let _ = stdin.write_all(slice::from_raw_parts(data, data_len));
+    }
+    config.stdin = Some(Box::new(stdin));
+}
+
+/// Reads the standard input of the guest from `callback`.
+///
+/// `env` is passed to each call of `callback`, and to `finalizer`, if
+/// any, once the store of the WASI environment is deleted.
+#[no_mangle]
+pub extern "C" fn wasi_config_stdin_callback(
+    config: &mut wasi_config_t,
+    callback: wasi_read_callback_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(env: *mut c_void)>,
+) {
+    config.stdin = Some(Box::new(CallbackFile::reader(callback, env, finalizer)));
+}
 
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stdin(config: &mut wasi_config_t) {
-    config.inherit_stdin = true;
+    config.stdin = None;
+}
+
+/// A callback that reads up to `buffer_len` bytes into `buffer`, returning
+/// the number of bytes read, 0 at the end of the input, or -1 on error.
+#[allow(non_camel_case_types)]
+pub type wasi_read_callback_t =
+    unsafe extern "C" fn(env: *mut c_void, buffer: *mut c_char, buffer_len: usize) -> isize;
+
+/// A callback that writes up to `buffer_len` bytes from `buffer`, returning
+/// the number of bytes written, or -1 on error.
+#[allow(non_camel_case_types)]
+pub type wasi_write_callback_t =
+    unsafe extern "C" fn(env: *mut c_void, buffer: *const c_char, buffer_len: usize) -> isize;
+
+/// A standard stream of the guest that calls back into the host.
+struct CallbackFile {
+    read: Option<wasi_read_callback_t>,
+    write: Option<wasi_write_callback_t>,
+    env: *mut c_void,
+    finalizer: Option<wasm_env_finalizer_t>,
+}
+
+// The host is responsible for the callbacks and their environment being
+// usable from any thread.
+unsafe impl Send for CallbackFile {}
+unsafe impl Sync for CallbackFile {}
+
+impl CallbackFile {
+    fn reader(
+        callback: wasi_read_callback_t,
+        env: *mut c_void,
+        finalizer: Option<wasm_env_finalizer_t>,
+    ) -> Self {
+        Self {
+            read: Some(callback),
+            write: None,
+            env,
+            finalizer,
+        }
+    }
+
+    fn writer(
+        callback: wasi_write_callback_t,
+        env: *mut c_void,
+        finalizer: Option<wasm_env_finalizer_t>,
+    ) -> Self {
+        Self {
+            read: None,
+            write: Some(callback),
+            env,
+            finalizer,
+        }
+    }
+}
+
+impl Drop for CallbackFile {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer {
+            unsafe { finalizer(self.env) };
+        }
+    }
+}
+
+impl fmt::Debug for CallbackFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackFile")
+            .field("env", &self.env)
+            .finish()
+    }
+}
+
+/// The result of a callback, as the result of an I/O operation.
+fn callback_result(result: isize) -> io::Result<usize> {
+    usize::try_from(result)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "the host callback failed"))
+}
+
+impl Read for CallbackFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.read.ok_or(io::ErrorKind::Unsupported)?;
+        callback_result(unsafe { read(self.env, buf.as_mut_ptr().cast(), buf.len()) })
+    }
+}
+
+impl Write for CallbackFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let write = self.write.ok_or(io::ErrorKind::Unsupported)?;
+        callback_result(unsafe { write(self.env, buf.as_ptr().cast(), buf.len()) })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for CallbackFile {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek in a standard stream",
+        ))
+    }
+}
+
+impl VirtualFile for CallbackFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+
+    fn last_modified(&self) -> u64 {
+        0
+    }
+
+    fn created_time(&self) -> u64 {
+        0
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> Result<(), FsError> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
+
+/// An in-memory filesystem, that guests can be given instead of the host
+/// filesystem with `wasi_config_set_filesystem`.
+#[allow(non_camel_case_types)]
+pub struct wasi_filesystem_t {
+    inner: mem_fs::FileSystem,
+}
+
+/// Creates an empty in-memory filesystem.
+#[no_mangle]
+pub extern "C" fn wasi_filesystem_new() -> Box<wasi_filesystem_t> {
+    Box::new(wasi_filesystem_t {
+        inner: mem_fs::FileSystem::default(),
+    })
+}
+
+/// Deletes a [`wasi_filesystem_t`]. The configurations it was given to
+/// keep its files.
+#[no_mangle]
+pub extern "C" fn wasi_filesystem_delete(_fs: Option<Box<wasi_filesystem_t>>) {}
+
+/// Creates the directory `path` of a filesystem, along with its missing
+/// parents.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_filesystem_create_dir(
+    fs: &wasi_filesystem_t,
+    path: *const c_char,
+) -> bool {
+    let path = c_try!(CStr::from_ptr(path).to_str(); otherwise false);
+    let mut ancestors = Path::new(path).ancestors().collect::<Vec<_>>();
+    ancestors.reverse();
+    for dir in ancestors {
+        if dir.as_os_str().is_empty() || fs.inner.read_dir(dir).is_ok() {
+            continue;
+        }
+        c_try!(fs.inner.create_dir(dir); otherwise false);
+    }
+    true
+}
+
+/// Writes the `data_len` bytes at `data` to the file `path` of a
+/// filesystem, replacing its contents if it exists. The directory of the
+/// file must exist.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_filesystem_write_file(
+    fs: &wasi_filesystem_t,
+    path: *const c_char,
+    data: *const u8,
+    data_len: usize,
+) -> bool {
+    let path = c_try!(CStr::from_ptr(path).to_str(); otherwise false);
+    let data = if data_len == 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(data, data_len)
+    };
+    let mut file = c_try!(fs
+        .inner
+        .new_open_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path); otherwise false);
+    c_try!(file.write_all(data); otherwise false);
+    true
+}
+
+/// Reads the file `path` of a filesystem into `out`, which must be
+/// deleted with `wasm_byte_vec_delete`.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_filesystem_read_file(
+    fs: &wasi_filesystem_t,
+    path: *const c_char,
+    out: &mut wasm_byte_vec_t,
+) -> bool {
+    let path = c_try!(CStr::from_ptr(path).to_str(); otherwise false);
+    let mut file = c_try!(fs.inner.new_open_options().read(true).open(path); otherwise false);
+    let mut data = vec![];
+    c_try!(file.read_to_end(&mut data); otherwise false);
+    out.set_buffer(data);
+    true
 }
 
 #[allow(non_camel_case_types)]
@@ -175,15 +525,17 @@ pub unsafe extern "C" fn wasi_env_new(
 ) -> Option<Box<wasi_env_t>> {
     let store = &mut store?.inner;
     let mut store_mut = store.store_mut();
-    if !config.inherit_stdout {
-        config.state_builder.stdout(Box::new(Pipe::new()));
+    if let Some(stdin) = config.stdin.take() {
+        config.state_builder.stdin(stdin);
     }
 
-    if !config.inherit_stderr {
-        config.state_builder.stderr(Box::new(Pipe::new()));
+    if let Some(stdout) = config.stdout.take() {
+        config.state_builder.stdout(stdout);
     }
 
-    // TODO: impl capturer for stdin
+    if let Some(stderr) = config.stderr.take() {
+        config.state_builder.stderr(stderr);
+    }
 
     let wasi_state = c_try!(config.state_builder.finalize(&mut store_mut));
 
@@ -250,6 +602,39 @@ pub unsafe extern "C" fn wasi_env_read_stderr(
     }
 }
 
+/// Writes `buffer_len` bytes from `buffer` to the standard input of the
+/// guest, which must be captured with `wasi_config_capture_stdin` or
+/// `wasi_config_stdin_buffer`.
+///
+/// Returns the number of bytes written, or -1 on error.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_env_write_stdin(
+    env: &mut wasi_env_t,
+    buffer: *const c_char,
+    buffer_len: usize,
+) -> isize {
+    let inner_buffer = if buffer_len == 0 {
+        &[][..]
+    } else {
+        slice::from_raw_parts(buffer as *const u8, buffer_len)
+    };
+    let mut store_mut = env.store.store_mut();
+    let state = env.inner.data_mut(&mut store_mut).state();
+    match state.stdin() {
+        Ok(Some(mut stdin)) => match stdin.write(inner_buffer) {
+            Ok(written) => written as isize,
+            Err(err) => {
+                update_last_error(format!("failed to write to `stdin`: {}", err));
+                -1
+            }
+        },
+        _ => {
+            update_last_error("could not find a file handle for `stdin`");
+            -1
+        }
+    }
+}
+
 fn read_inner(
     wasi_file: &mut Box<dyn WasiFile + Send + Sync + 'static>,
     inner_buffer: &mut [u8],
@@ -401,6 +786,23 @@ pub unsafe extern "C" fn wasi_get_start_function(
     }))
 }
 
+/// Tells whether a trap was raised by the guest exiting with
+/// `proc_exit`, rather than by an error.
+///
+/// The function returns `true` and writes the exit code of the guest
+/// in `exit_code` if it exited, `false` otherwise.
+#[no_mangle]
+pub extern "C" fn wasi_trap_exit_code(trap: &wasm_trap_t, exit_code: &mut u32) -> bool {
+    match trap.inner.downcast_ref::<WasiError>() {
+        Some(WasiError::Exit(code)) => {
+            *exit_code = *code;
+
+            true
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(not(target_os = "windows"))]
@@ -506,4 +908,367 @@ mod tests {
         })
         .success();
     }
+
+    #[test]
+    fn test_wasi_config_mount_tar() {
+        (assert_c! {
+            #include "tests/wasmer.h"
+            #include <string.h>
+
+            // Writes a tar archive with a single file, `hello.txt`.
+            static void write_tar(char* tar, const char* contents) {
+                memset(tar, 0, 3 * 512);
+                strcpy(tar, "hello.txt");
+                strcpy(tar + 100, "0000644");
+                strcpy(tar + 108, "0000000");
+                strcpy(tar + 116, "0000000");
+                sprintf(tar + 124, "%011o", (unsigned) strlen(contents));
+                strcpy(tar + 136, "00000000000");
+                tar[156] = '0';
+                memcpy(tar + 257, "ustar\0" "00", 8);
+                memset(tar + 148, ' ', 8);
+                unsigned checksum = 0;
+                for (int i = 0; i < 512; i++) {
+                    checksum += (unsigned char) tar[i];
+                }
+                sprintf(tar + 148, "%06o", checksum);
+                memcpy(tar + 512, contents, strlen(contents));
+            }
+
+            int main() {
+                wasm_engine_t* engine = wasm_engine_new();
+                wasm_store_t* store = wasm_store_new(engine);
+
+                wasm_byte_vec_t wat;
+                wasmer_byte_vec_new_from_string(
+                    &wat,
+                    "(module\n"
+                    "  (import \"wasi_snapshot_preview1\" \"path_open\"\n"
+                    "    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))\n"
+                    "  (import \"wasi_snapshot_preview1\" \"fd_read\"\n"
+                    "    (func $fd_read (param i32 i32 i32 i32) (result i32)))\n"
+                    "  (import \"wasi_snapshot_preview1\" \"fd_write\"\n"
+                    "    (func $fd_write (param i32 i32 i32 i32) (result i32)))\n"
+                    "  (memory (export \"memory\") 1)\n"
+                    "  (data (i32.const 0) \"hello.txt\")\n"
+                    "  (func (export \"_start\")\n"
+                    "    (if (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 9)\n"
+                    "                         (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 16))\n"
+                    "      (then (unreachable)))\n"
+                    "    (i32.store (i32.const 32) (i32.const 64))\n"
+                    "    (i32.store (i32.const 36) (i32.const 32))\n"
+                    "    (drop (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 40)))\n"
+                    "    (i32.store (i32.const 36) (i32.load (i32.const 40)))\n"
+                    "    (drop (call $fd_write (i32.const 1) (i32.const 32) (i32.const 1) (i32.const 44)))))"
+                );
+                wasm_byte_vec_t wasm;
+                wat2wasm(&wat, &wasm);
+                wasm_module_t* module = wasm_module_new(store, &wasm);
+                assert(module);
+
+                char tar[3 * 512];
+                write_tar(tar, "Hello, archive!");
+                wasi_config_t* config = wasi_config_new("example_program");
+                wasi_config_capture_stdout(config);
+                assert(wasi_config_mount_tar(config, "/data", (const uint8_t*) tar, sizeof(tar)));
+                wasi_env_t* wasi_env = wasi_env_new(store, config);
+                assert(wasi_env);
+
+                wasm_extern_vec_t imports;
+                assert(wasi_get_imports(store, wasi_env, module, &imports));
+                wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+                assert(instance);
+                assert(wasi_env_initialize_instance(wasi_env, store, instance));
+
+                wasm_func_t* start = wasi_get_start_function(instance);
+                assert(start);
+                wasm_val_vec_t args = WASM_EMPTY_VEC;
+                wasm_val_vec_t results = WASM_EMPTY_VEC;
+                assert(!wasm_func_call(start, &args, &results));
+
+                char buffer[64] = {0};
+                wasi_env_read_stdout(wasi_env, buffer, sizeof(buffer) - 1);
+                assert(strcmp(buffer, "Hello, archive!") == 0);
+
+                wasm_func_delete(start);
+                wasm_instance_delete(instance);
+                wasm_extern_vec_delete(&imports);
+                wasi_env_delete(wasi_env);
+                wasm_module_delete(module);
+                wasm_byte_vec_delete(&wasm);
+                wasm_byte_vec_delete(&wat);
+                wasm_store_delete(store);
+                wasm_engine_delete(engine);
+
+                return 0;
+            }
+        })
+        .success();
+    }
+
+    #[test]
+    fn test_wasi_stdio_callbacks_and_buffers() {
+        (assert_c! {
+            #include "tests/wasmer.h"
+            #include <string.h>
+
+            typedef struct {
+                char data[64];
+                size_t len;
+                int finalized;
+            } output_t;
+
+            static intptr_t write_output(void* env, const char* buffer, uintptr_t buffer_len) {
+                output_t* output = (output_t*) env;
+                memcpy(output->data + output->len, buffer, buffer_len);
+                output->len += buffer_len;
+                return buffer_len;
+            }
+
+            static void finalize_output(void* env) {
+                ((output_t*) env)->finalized = 1;
+            }
+
+            // Runs a module that copies a chunk of its standard input to its
+            // standard output and its standard error.
+            static void run_echo(wasm_store_t* store, wasm_module_t* module, wasi_env_t* wasi_env) {
+                wasm_extern_vec_t imports;
+                assert(wasi_get_imports(store, wasi_env, module, &imports));
+                wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+                assert(instance);
+                assert(wasi_env_initialize_instance(wasi_env, store, instance));
+
+                wasm_func_t* start = wasi_get_start_function(instance);
+                assert(start);
+                wasm_val_vec_t args = WASM_EMPTY_VEC;
+                wasm_val_vec_t results = WASM_EMPTY_VEC;
+                assert(!wasm_func_call(start, &args, &results));
+
+                wasm_func_delete(start);
+                wasm_instance_delete(instance);
+                wasm_extern_vec_delete(&imports);
+            }
+
+            int main() {
+                wasm_engine_t* engine = wasm_engine_new();
+                wasm_store_t* store = wasm_store_new(engine);
+
+                wasm_byte_vec_t wat;
+                wasmer_byte_vec_new_from_string(
+                    &wat,
+                    "(module\n"
+                    "  (import \"wasi_snapshot_preview1\" \"fd_read\"\n"
+                    "    (func $fd_read (param i32 i32 i32 i32) (result i32)))\n"
+                    "  (import \"wasi_snapshot_preview1\" \"fd_write\"\n"
+                    "    (func $fd_write (param i32 i32 i32 i32) (result i32)))\n"
+                    "  (memory (export \"memory\") 1)\n"
+                    "  (func (export \"_start\")\n"
+                    "    (i32.store (i32.const 32) (i32.const 64))\n"
+                    "    (i32.store (i32.const 36) (i32.const 32))\n"
+                    "    (drop (call $fd_read (i32.const 0) (i32.const 32) (i32.const 1) (i32.const 40)))\n"
+                    "    (i32.store (i32.const 36) (i32.load (i32.const 40)))\n"
+                    "    (drop (call $fd_write (i32.const 1) (i32.const 32) (i32.const 1) (i32.const 44)))\n"
+                    "    (drop (call $fd_write (i32.const 2) (i32.const 32) (i32.const 1) (i32.const 44)))))"
+                );
+                wasm_byte_vec_t wasm;
+                wat2wasm(&wat, &wasm);
+                wasm_module_t* module = wasm_module_new(store, &wasm);
+                assert(module);
+
+                // Standard input from a buffer, standard output to a callback
+                output_t output = {0};
+                wasi_config_t* config = wasi_config_new("example_program");
+                wasi_config_stdin_buffer(config, (const uint8_t*) "Hello, stdin!", 13);
+                wasi_config_stdout_callback(config, write_output, &output, finalize_output);
+                wasi_config_capture_stderr(config);
+                wasi_env_t* wasi_env = wasi_env_new(store, config);
+                assert(wasi_env);
+                run_echo(store, module, wasi_env);
+                assert(output.len == 13);
+                assert(memcmp(output.data, "Hello, stdin!", 13) == 0);
+
+                char buffer[64] = {0};
+                wasi_env_read_stderr(wasi_env, buffer, sizeof(buffer) - 1);
+                assert(strcmp(buffer, "Hello, stdin!") == 0);
+                wasi_env_delete(wasi_env);
+
+                // Standard input written by the host
+                config = wasi_config_new("example_program");
+                wasi_config_capture_stdin(config);
+                wasi_config_capture_stdout(config);
+                wasi_env = wasi_env_new(store, config);
+                assert(wasi_env);
+                assert(wasi_env_write_stdin(wasi_env, "Hello, host!", 12) == 12);
+                run_echo(store, module, wasi_env);
+
+                memset(buffer, 0, sizeof(buffer));
+                wasi_env_read_stdout(wasi_env, buffer, sizeof(buffer) - 1);
+                assert(strcmp(buffer, "Hello, host!") == 0);
+                wasi_env_delete(wasi_env);
+
+                wasm_module_delete(module);
+                wasm_byte_vec_delete(&wasm);
+                wasm_byte_vec_delete(&wat);
+                wasm_store_delete(store);
+                wasm_engine_delete(engine);
+                assert(output.finalized);
+
+                return 0;
+            }
+        })
+        .success();
+    }
+
+    #[test]
+    fn test_wasi_config_set_filesystem() {
+        (assert_c! {
+            #include "tests/wasmer.h"
+            #include <string.h>
+
+            int main() {
+                wasm_engine_t* engine = wasm_engine_new();
+                wasm_store_t* store = wasm_store_new(engine);
+
+                // Copies `in.txt` to `out.txt`
+                wasm_byte_vec_t wat;
+                wasmer_byte_vec_new_from_string(
+                    &wat,
+                    "(module\n"
+                    "  (import \"wasi_snapshot_preview1\" \"path_open\"\n"
+                    "    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))\n"
+                    "  (import \"wasi_snapshot_preview1\" \"fd_read\"\n"
+                    "    (func $fd_read (param i32 i32 i32 i32) (result i32)))\n"
+                    "  (import \"wasi_snapshot_preview1\" \"fd_write\"\n"
+                    "    (func $fd_write (param i32 i32 i32 i32) (result i32)))\n"
+                    "  (memory (export \"memory\") 1)\n"
+                    "  (data (i32.const 0) \"in.txt\")\n"
+                    "  (data (i32.const 8) \"out.txt\")\n"
+                    "  (func (export \"_start\")\n"
+                    "    (if (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 6)\n"
+                    "                         (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 16))\n"
+                    "      (then (unreachable)))\n"
+                    "    (if (call $path_open (i32.const 4) (i32.const 0) (i32.const 8) (i32.const 7)\n"
+                    "                         (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 20))\n"
+                    "      (then (unreachable)))\n"
+                    "    (i32.store (i32.const 32) (i32.const 64))\n"
+                    "    (i32.store (i32.const 36) (i32.const 32))\n"
+                    "    (drop (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 40)))\n"
+                    "    (i32.store (i32.const 36) (i32.load (i32.const 40)))\n"
+                    "    (drop (call $fd_write (i32.load (i32.const 20)) (i32.const 32) (i32.const 1) (i32.const 44)))))"
+                );
+                wasm_byte_vec_t wasm;
+                wat2wasm(&wat, &wasm);
+                wasm_module_t* module = wasm_module_new(store, &wasm);
+                assert(module);
+
+                wasi_filesystem_t* fs = wasi_filesystem_new();
+                assert(wasi_filesystem_create_dir(fs, "/data/nested"));
+                assert(wasi_filesystem_write_file(fs, "/data/in.txt", (const uint8_t*) "Hello, fs!", 10));
+                assert(!wasi_filesystem_write_file(fs, "/missing/in.txt", (const uint8_t*) "", 0));
+
+                wasi_config_t* config = wasi_config_new("example_program");
+                wasi_config_set_filesystem(config, fs);
+                assert(wasi_config_preopen_dir(config, "/data"));
+                wasi_env_t* wasi_env = wasi_env_new(store, config);
+                assert(wasi_env);
+
+                wasm_extern_vec_t imports;
+                assert(wasi_get_imports(store, wasi_env, module, &imports));
+                wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+                assert(instance);
+                assert(wasi_env_initialize_instance(wasi_env, store, instance));
+
+                wasm_func_t* start = wasi_get_start_function(instance);
+                assert(start);
+                wasm_val_vec_t args = WASM_EMPTY_VEC;
+                wasm_val_vec_t results = WASM_EMPTY_VEC;
+                assert(!wasm_func_call(start, &args, &results));
+
+                wasm_byte_vec_t out;
+                assert(wasi_filesystem_read_file(fs, "/data/out.txt", &out));
+                assert(out.size == 10);
+                assert(memcmp(out.data, "Hello, fs!", 10) == 0);
+                wasm_byte_vec_delete(&out);
+
+                wasm_func_delete(start);
+                wasm_instance_delete(instance);
+                wasm_extern_vec_delete(&imports);
+                wasi_env_delete(wasi_env);
+                wasi_filesystem_delete(fs);
+                wasm_module_delete(module);
+                wasm_byte_vec_delete(&wasm);
+                wasm_byte_vec_delete(&wat);
+                wasm_store_delete(store);
+                wasm_engine_delete(engine);
+
+                return 0;
+            }
+        })
+        .success();
+    }
+
+    #[test]
+    fn test_wasi_trap_exit_code() {
+        (assert_c! {
+            #include "tests/wasmer.h"
+
+            int main() {
+                wasm_engine_t* engine = wasm_engine_new();
+                wasm_store_t* store = wasm_store_new(engine);
+
+                wasm_byte_vec_t wat;
+                wasmer_byte_vec_new_from_string(
+                    &wat,
+                    "(module\n"
+                    "  (import \"wasi_snapshot_preview1\" \"proc_exit\"\n"
+                    "    (func $proc_exit (param i32)))\n"
+                    "  (memory (export \"memory\") 1)\n"
+                    "  (func (export \"_start\")\n"
+                    "    (call $proc_exit (i32.const 3))))"
+                );
+                wasm_byte_vec_t wasm;
+                wat2wasm(&wat, &wasm);
+                wasm_module_t* module = wasm_module_new(store, &wasm);
+                assert(module);
+
+                wasi_config_t* config = wasi_config_new("example_program");
+                wasi_env_t* wasi_env = wasi_env_new(store, config);
+                assert(wasi_env);
+
+                wasm_extern_vec_t imports;
+                assert(wasi_get_imports(store, wasi_env, module, &imports));
+                wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+                assert(instance);
+                assert(wasi_env_initialize_instance(wasi_env, store, instance));
+
+                wasm_func_t* start = wasi_get_start_function(instance);
+                assert(start);
+                wasm_val_vec_t args = WASM_EMPTY_VEC;
+                wasm_val_vec_t results = WASM_EMPTY_VEC;
+                wasm_trap_t* trap = wasm_func_call(start, &args, &results);
+                assert(trap);
+
+                // The guest exited, it didn't trap.
+                uint32_t exit_code = 0;
+                assert(wasi_trap_exit_code(trap, &exit_code));
+                assert(exit_code == 3);
+                wasmer_trap_code_t code;
+                assert(!wasmer_trap_code(trap, &code));
+
+                wasm_trap_delete(trap);
+                wasm_func_delete(start);
+                wasm_instance_delete(instance);
+                wasm_extern_vec_delete(&imports);
+                wasi_env_delete(wasi_env);
+                wasm_module_delete(module);
+                wasm_byte_vec_delete(&wasm);
+                wasm_byte_vec_delete(&wat);
+                wasm_store_delete(store);
+                wasm_engine_delete(engine);
+
+                return 0;
+            }
+        })
+        .success();
+    }
 }
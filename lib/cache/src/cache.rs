@@ -4,7 +4,7 @@
 
 use crate::hash::Hash;
 use std::error::Error;
-use wasmer::{Module, Store};
+use wasmer::{CompileError, Module, Store};
 
 /// A generic cache for storing and loading compiled wasm modules.
 pub trait Cache {
@@ -21,4 +21,42 @@ pub trait Cache {
 
     /// Store a [`Module`] into the cache with the given [`Hash`].
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError>;
+
+    /// Loads the module compiled from `wasm` by the engine of the
+    /// [`Store`], or compiles `wasm` and stores the module if it is missing
+    /// from the cache or can not be loaded (for example because it got
+    /// corrupted).
+    ///
+    /// The key of the module is derived from `wasm` and the engine with
+    /// [`Hash::generate_for_engine`], so modules compiled for a different
+    /// target or features, or by a differently configured compiler, do not
+    /// replace each other. The module is also stored under the key of
+    /// [`Hash::generate_for_headless`], so that headless engines can load
+    /// it. Nothing is cached for an engine without a deterministic id.
+    ///
+    /// Failing to store the compiled module is not an error, the module is
+    /// returned anyway.
+    ///
+    /// # Safety
+    /// This function is unsafe as the cache store could be tampered with.
+    unsafe fn load_or_compile(
+        &mut self,
+        store: &Store,
+        wasm: &[u8],
+    ) -> Result<Module, CompileError> {
+        let key = match Hash::generate_for_engine(store.engine(), wasm) {
+            Some(key) => key,
+            None => return Module::new(store, wasm),
+        };
+        if let Ok(module) = self.load(store, key) {
+            return Ok(module);
+        }
+        let module = Module::new(store, wasm)?;
+        let _ = self.store(key, &module);
+        let headless_key = Hash::generate_for_headless(store.engine(), wasm);
+        if headless_key != key {
+            let _ = self.store(headless_key, &module);
+        }
+        Ok(module)
+    }
 }
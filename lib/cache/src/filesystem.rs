@@ -1,11 +1,35 @@
 #![cfg_attr(not(feature = "filesystem"), allow(unused))]
 use crate::cache::Cache;
 use crate::hash::Hash;
-use std::fs::{create_dir_all, File};
+use filetime::FileTime;
+use fs2::FileExt;
+use std::fs::{self, create_dir_all, File, OpenOptions};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use wasmer::{DeserializeError, Module, SerializeError, Store};
 
+/// Magic bytes at the start of every entry of the cache
+const ENTRY_MAGIC: &[u8; 8] = b"wasmerc\x03";
+/// Size of the header of an entry: the magic bytes, the size of the
+/// serialized module, its checksum and the hashes of the compatibility id
+/// and of the deterministic id of the engine that produced it, or twice
+/// the hash of [`FUNCTION_ENTRY_ID`]
+const ENTRY_HEADER_SIZE: usize = 8 + 8 + 32 + 32 + 32;
+/// The id recorded in place of the engine ids by the entries that hold a
+/// function of a [`wasmer::FunctionCache`] rather than a module
+const FUNCTION_ENTRY_ID: &str = "function";
+/// Name of the file that is locked while the cache is used
+const LOCK_FILE: &str = ".lock";
+/// Suffix of the files that are being written
+const TEMP_SUFFIX: &str = ".tmp";
+/// Age after which a temporary file is considered abandoned by a writer
+/// that crashed
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+/// Error of the engines whose modules can't be cached
+const UNIDENTIFIED_ENGINE: &str = "the engine has no deterministic id, as one of the \
+     middlewares of its compiler has none, so its modules can't be cached";
+
 /// Representation of a directory that contains compiled wasm artifacts.
 ///
 /// The `FileSystemCache` type implements the [`Cache`] trait, which allows it to be used
@@ -17,23 +41,79 @@ use wasmer::{DeserializeError, Module, SerializeError, Store};
 /// use wasmer::{DeserializeError, SerializeError};
 /// use wasmer_cache::{Cache, FileSystemCache, Hash};
 ///
-/// # use wasmer::{Module};
-/// fn store_module(module: &Module, bytes: &[u8]) -> Result<(), SerializeError> {
+/// # use wasmer::{Module, Store};
+/// fn store_module(store: &Store, module: &Module, bytes: &[u8]) -> Result<(), SerializeError> {
 ///     // Create a new file system cache.
 ///     let mut fs_cache = FileSystemCache::new("some/directory/goes/here")?;
 ///
-///     // Compute a key for a given WebAssembly binary
-///     let key = Hash::generate(bytes);
-///
-///     // Store a module into the cache given a key
-///     fs_cache.store(key, module)?;
+///     // Compute a key for a given WebAssembly binary and the engine
+///     // that compiled it, unless its modules can't be cached
+///     if let Some(key) = Hash::generate_for_engine(store.engine(), bytes) {
+///         // Store a module into the cache given a key
+///         fs_cache.store(key, module)?;
+///     }
 ///
 ///     Ok(())
 /// }
 /// ```
+///
+/// # Size limit and concurrent use
+///
+/// The total size of the cache can be limited with
+/// [`FileSystemCache::set_max_size`], in which case the least recently used
+/// modules are evicted when a new module is stored. Entries are written to
+/// a temporary file which is then atomically renamed, and the cache
+/// directory is locked while it is modified, so several processes can share
+/// a cache. Every entry is checksummed: an entry that got corrupted is
+/// removed by [`Cache::load`] and reported as
+/// [`DeserializeError::CorruptedBinary`], so that the module gets compiled
+/// and stored again (see [`Cache::load_or_compile`]).
+///
+/// # Engine compatibility
+///
+/// Every entry records the [compatibility id] and the [deterministic id] of
+/// the engine that compiled the module. Loading an entry with an engine
+/// that has a different compatibility id fails with
+/// [`DeserializeError::Incompatible`] instead of deserializing an artifact
+/// the engine can not run. A headless engine loads any compatible entry, so
+/// a cache filled by a compiling engine can be used where no compiler is
+/// available, while an engine that compiles also requires the same
+/// deterministic id. Keys created with [`Hash::generate_for_engine`]
+/// depend on the deterministic id, so differently configured compilers get
+/// their own entries, while headless engines look up the entries that
+/// [`Cache::load_or_compile`] also stores under the compatibility id.
+///
+/// Engines without a deterministic id, because one of the middlewares of
+/// their compiler has none, can't load or store any entry.
+///
+/// # Function caches
+///
+/// A `FileSystemCache` can also be the [`FunctionCacheStore`] of a
+/// [`FunctionCache`], so that the functions compiled by a process are reused
+/// by the processes that compile the same functions after it. The functions
+/// are stored next to the modules and count towards the size limit.
+///
+/// ```
+/// use wasmer::FunctionCache;
+/// use wasmer_cache::FileSystemCache;
+/// use wasmer_compiler_singlepass::Singlepass;
+///
+/// # fn configure() -> std::io::Result<()> {
+/// let functions = FunctionCache::with_store(FileSystemCache::new("some/directory/goes/here")?);
+/// let mut compiler = Singlepass::default();
+/// compiler.function_cache(functions);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [compatibility id]: wasmer::Engine::compatibility_id
+/// [deterministic id]: wasmer::Engine::deterministic_id
+/// [`FunctionCacheStore`]: wasmer::FunctionCacheStore
+/// [`FunctionCache`]: wasmer::FunctionCache
 pub struct FileSystemCache {
     path: PathBuf,
     ext: Option<String>,
+    max_size: Option<u64>,
 }
 
 #[cfg(feature = "filesystem")]
@@ -45,7 +125,11 @@ impl FileSystemCache {
             let metadata = path.metadata()?;
             if metadata.is_dir() {
                 if !metadata.permissions().readonly() {
-                    Ok(Self { path, ext: None })
+                    Ok(Self {
+                        path,
+                        ext: None,
+                        max_size: None,
+                    })
                 } else {
                     // This directory is readonly.
                     Err(io::Error::new(
@@ -72,7 +156,11 @@ impl FileSystemCache {
                     format!("failed to create cache directory: {}", path.display()),
                 ))
             } else {
-                Ok(Self { path, ext: None })
+                Ok(Self {
+                    path,
+                    ext: None,
+                    max_size: None,
+                })
             }
         }
     }
@@ -84,35 +172,553 @@ impl FileSystemCache {
     pub fn set_cache_extension(&mut self, ext: Option<impl ToString>) {
         self.ext = ext.map(|ext| ext.to_string());
     }
-}
 
-#[cfg(feature = "filesystem")]
-impl Cache for FileSystemCache {
-    type DeserializeError = DeserializeError;
-    type SerializeError = SerializeError;
+    /// Limit the total size of the cached modules, in bytes.
+    ///
+    /// When storing a module makes the cache grow past the limit, the least
+    /// recently used modules are evicted. The module that was just stored
+    /// is always kept, even if it is larger than the limit on its own.
+    pub fn set_max_size(&mut self, max_size: Option<u64>) {
+        self.max_size = max_size;
+    }
 
-    unsafe fn load(&self, store: &Store, key: Hash) -> Result<Module, Self::DeserializeError> {
+    /// Returns the total size of the cached modules, in bytes.
+    pub fn size(&self) -> io::Result<u64> {
+        let _lock = self.lock(true)?;
+        Ok(self.entries()?.iter().map(|entry| entry.size).sum())
+    }
+
+    fn entry_path(&self, key: Hash) -> PathBuf {
         let filename = if let Some(ref ext) = self.ext {
             format!("{}.{}", key.to_string(), ext)
         } else {
             key.to_string()
         };
-        let path = self.path.join(filename);
-        Module::deserialize_from_file(store, path)
+        self.path.join(filename)
     }
 
-    fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
-        let filename = if let Some(ref ext) = self.ext {
-            format!("{}.{}", key.to_string(), ext)
+    /// Locks the cache directory, the lock is released when the returned
+    /// file is dropped.
+    fn lock(&self, exclusive: bool) -> io::Result<File> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(self.path.join(LOCK_FILE))?;
+        if exclusive {
+            file.lock_exclusive()?;
         } else {
-            key.to_string()
+            file.lock_shared()?;
+        }
+        Ok(file)
+    }
+
+    /// Lists the entries of the cache. Abandoned temporary files are
+    /// removed, so the cache must be locked exclusively.
+    fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !metadata.is_file() {
+                continue;
+            }
+            if name.ends_with(TEMP_SUFFIX) {
+                let age = metadata.modified()?.elapsed().unwrap_or_default();
+                if age > STALE_TEMP_AGE {
+                    remove_file(&entry.path())?;
+                }
+                continue;
+            }
+            // Other files might live next to the cache, they are left alone
+            if !is_entry_name(&name, self.ext.as_deref()) {
+                continue;
+            }
+            entries.push(CacheEntry {
+                last_used: metadata.modified()?,
+                size: metadata.len(),
+                path: entry.path(),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Removes the least recently used entries until the cache fits in
+    /// `max_size`, except for the entry at `keep`.
+    fn evict(&self, max_size: u64, keep: &Path) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        entries.sort_by_key(|entry| entry.last_used);
+        for entry in entries {
+            if total <= max_size {
+                break;
+            }
+            if entry.path == keep {
+                continue;
+            }
+            remove_file(&entry.path)?;
+            total -= entry.size;
+        }
+        Ok(())
+    }
+
+    /// Reads the entry at `path`, returning the hashes of the ids it records
+    /// and its payload.
+    ///
+    /// A corrupted entry is removed, so that it gets stored again.
+    fn read_entry(&self, path: &Path) -> io::Result<(EntryIds, Vec<u8>)> {
+        let contents = {
+            let _lock = self.lock(false)?;
+            fs::read(path)?
         };
-        let path = self.path.join(filename);
-        let mut file = File::create(path)?;
+        match decode_entry(&contents) {
+            Some((ids, payload)) => {
+                // The modification time tracks when the entry was last used,
+                // as the access time is not updated on many file systems
+                let _ = filetime::set_file_mtime(path, FileTime::now());
+                Ok((ids, payload.to_vec()))
+            }
+            None => {
+                // Unless another process just stored the entry again
+                let _lock = self.lock(true)?;
+                if fs::read(path).map_or(true, |contents| decode_entry(&contents).is_none()) {
+                    remove_file(path)?;
+                }
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("the cache entry {} is corrupted", path.display()),
+                ))
+            }
+        }
+    }
 
-        let buffer = module.serialize()?;
-        file.write_all(&buffer)?;
+    /// Writes the entry at `path`, then evicts the least recently used
+    /// entries if the cache got too large.
+    fn write_entry(
+        &self,
+        path: &Path,
+        compatibility_id: &str,
+        id: &str,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let mut file = tempfile::Builder::new()
+            .prefix(".")
+            .suffix(TEMP_SUFFIX)
+            .tempfile_in(&self.path)?;
+        // Temporary files are only readable by their owner, but the cache
+        // might be shared between users
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.as_file()
+                .set_permissions(fs::Permissions::from_mode(0o644))?;
+        }
+        file.write_all(&encode_entry_header(compatibility_id, id, payload))?;
+        file.write_all(payload)?;
+        file.as_file().sync_all()?;
+
+        let _lock = self.lock(true)?;
+        file.persist(path).map_err(|e| e.error)?;
+        if let Some(max_size) = self.max_size {
+            self.evict(max_size, path)?;
+        }
+        Ok(())
+    }
+}
+
+/// The hashes of the ids recorded by an entry
+struct EntryIds {
+    /// The hash of the compatibility id of the engine
+    compatibility: [u8; 32],
+    /// The hash of the deterministic id of the engine
+    deterministic: [u8; 32],
+}
+
+/// A module stored in the cache
+struct CacheEntry {
+    last_used: SystemTime,
+    size: u64,
+    path: PathBuf,
+}
+
+/// Returns true if `name` is the name of an entry of the cache: the
+/// hexadecimal representation of a [`Hash`] followed by the extension.
+fn is_entry_name(name: &str, ext: Option<&str>) -> bool {
+    let hash = match ext {
+        Some(ext) => match name
+            .strip_suffix(ext)
+            .and_then(|name| name.strip_suffix('.'))
+        {
+            Some(hash) => hash,
+            None => return false,
+        },
+        None => name,
+    };
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Removes a file, which might have been removed by another process
+/// already.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Prepends the header of an entry to a serialized module
+fn encode_entry_header(compatibility_id: &str, engine_id: &str, payload: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(ENTRY_HEADER_SIZE);
+    header.extend_from_slice(ENTRY_MAGIC);
+    header.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    header.extend_from_slice(blake3::hash(payload).as_bytes());
+    header.extend_from_slice(blake3::hash(compatibility_id.as_bytes()).as_bytes());
+    header.extend_from_slice(blake3::hash(engine_id.as_bytes()).as_bytes());
+    header
+}
+
+/// Returns the hashes of the engine ids and the serialized module of an
+/// entry, or `None` if the entry is corrupted
+fn decode_entry(contents: &[u8]) -> Option<(EntryIds, &[u8])> {
+    if contents.len() < ENTRY_HEADER_SIZE || &contents[..8] != ENTRY_MAGIC {
+        return None;
+    }
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&contents[8..16]);
+    let payload = &contents[ENTRY_HEADER_SIZE..];
+    if u64::from_le_bytes(size) != payload.len() as u64
+        || blake3::hash(payload).as_bytes() != &contents[16..48]
+    {
+        return None;
+    }
+    let mut ids = EntryIds {
+        compatibility: [0; 32],
+        deterministic: [0; 32],
+    };
+    ids.compatibility.copy_from_slice(&contents[48..80]);
+    ids.deterministic
+        .copy_from_slice(&contents[80..ENTRY_HEADER_SIZE]);
+    Some((ids, payload))
+}
+
+#[cfg(feature = "filesystem")]
+impl Cache for FileSystemCache {
+    type DeserializeError = DeserializeError;
+    type SerializeError = SerializeError;
+
+    unsafe fn load(&self, store: &Store, key: Hash) -> Result<Module, Self::DeserializeError> {
+        let path = self.entry_path(key);
+        let (ids, payload) = self.read_entry(&path).map_err(|e| {
+            if e.kind() == io::ErrorKind::InvalidData {
+                DeserializeError::CorruptedBinary(e.to_string())
+            } else {
+                DeserializeError::Io(e)
+            }
+        })?;
+        let engine = store.engine();
+        let compatibility_id = engine.compatibility_id();
+        if &ids.compatibility != blake3::hash(compatibility_id.as_bytes()).as_bytes() {
+            return Err(DeserializeError::Incompatible(format!(
+                "the cache entry {} was compiled for a different Wasmer version, \
+                 target or features than `{}`",
+                path.display(),
+                compatibility_id
+            )));
+        }
+        // A headless engine runs whatever compiler produced the module, but
+        // an engine that compiles must not load the modules of a compiler
+        // configured differently, for example without its middlewares
+        if !engine.is_headless() {
+            let engine_id = engine
+                .deterministic_id()
+                .ok_or_else(|| DeserializeError::Incompatible(UNIDENTIFIED_ENGINE.to_string()))?;
+            if &ids.deterministic != blake3::hash(engine_id.as_bytes()).as_bytes() {
+                return Err(DeserializeError::Incompatible(format!(
+                    "the cache entry {} was compiled by an engine with a different \
+                     compiler configuration than `{}`",
+                    path.display(),
+                    engine_id
+                )));
+            }
+        }
+        Module::deserialize(store, payload)
+    }
 
+    fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
+        let engine_id = module
+            .engine_id()
+            .ok_or_else(|| SerializeError::Generic(UNIDENTIFIED_ENGINE.to_string()))?;
+        let buffer = module.serialize()?;
+        self.write_entry(
+            &self.entry_path(key),
+            module.engine_compatibility_id(),
+            engine_id,
+            &buffer,
+        )?;
         Ok(())
     }
 }
+
+/// The entries are checksummed, and only the entries stored as functions
+/// are loaded, so `load` returns the exact bytes given to `store`.
+#[cfg(feature = "function-cache")]
+unsafe impl wasmer::FunctionCacheStore for FileSystemCache {
+    fn load(&self, key: &[u8; 32]) -> io::Result<Vec<u8>> {
+        let path = self.entry_path(Hash::new(*key));
+        let (ids, payload) = self.read_entry(&path)?;
+        if &ids.deterministic != blake3::hash(FUNCTION_ENTRY_ID.as_bytes()).as_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the cache entry {} is not a function", path.display()),
+            ));
+        }
+        Ok(payload)
+    }
+
+    fn store(&self, key: &[u8; 32], entry: &[u8]) -> io::Result<()> {
+        self.write_entry(
+            &self.entry_path(Hash::new(*key)),
+            FUNCTION_ENTRY_ID,
+            FUNCTION_ENTRY_ID,
+            entry,
+        )
+    }
+}
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use wasmer::{imports, CompilerConfig, Engine, EngineBuilder, Features, Instance, Target};
+    use wasmer_compiler_singlepass::Singlepass;
+
+    const EMPTY_WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    fn set_last_used(cache: &FileSystemCache, key: Hash, secs: i64) {
+        filetime::set_file_mtime(cache.entry_path(key), FileTime::from_unix_time(secs, 0)).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::new(Singlepass::default());
+        let module = Module::new(&store, EMPTY_WASM).unwrap();
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        let (a, b, c) = (Hash::new([1; 32]), Hash::new([2; 32]), Hash::new([3; 32]));
+
+        cache.store(a, &module).unwrap();
+        let entry_size = cache.size().unwrap();
+        cache.store(b, &module).unwrap();
+        assert_eq!(cache.size().unwrap(), 2 * entry_size);
+        set_last_used(&cache, a, 1000);
+        set_last_used(&cache, b, 2000);
+
+        // Loading `a` makes `b` the least recently used entry
+        unsafe { cache.load(&store, a).unwrap() };
+        cache.set_max_size(Some(2 * entry_size + entry_size / 2));
+        cache.store(c, &module).unwrap();
+        assert_eq!(cache.size().unwrap(), 2 * entry_size);
+        assert!(cache.entry_path(a).exists());
+        assert!(!cache.entry_path(b).exists());
+        assert!(cache.entry_path(c).exists());
+
+        // The module that was just stored is kept
+        cache.set_max_size(Some(1));
+        cache.store(b, &module).unwrap();
+        assert_eq!(cache.size().unwrap(), entry_size);
+        assert!(cache.entry_path(b).exists());
+    }
+
+    #[test]
+    fn corrupted_entries_are_recompiled() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::new(Singlepass::default());
+        let module = Module::new(&store, EMPTY_WASM).unwrap();
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        cache.set_cache_extension(Some("wasmu"));
+        let key = Hash::generate_for_engine(store.engine(), EMPTY_WASM).unwrap();
+
+        cache.store(key, &module).unwrap();
+        let path = cache.entry_path(key);
+        let mut contents = fs::read(&path).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 0xff;
+        fs::write(&path, &contents).unwrap();
+
+        let err = unsafe { cache.load(&store, key).unwrap_err() };
+        assert!(matches!(err, DeserializeError::CorruptedBinary(_)));
+        assert!(!path.exists());
+
+        unsafe { cache.load_or_compile(&store, EMPTY_WASM).unwrap() };
+        unsafe { cache.load(&store, key).unwrap() };
+
+        // Files that are not entries are left alone
+        fs::write(dir.path().join("notes.txt"), b"hello").unwrap();
+        cache.set_max_size(Some(1));
+        cache.store(Hash::new([0; 32]), &module).unwrap();
+        assert!(dir.path().join("notes.txt").exists());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_modules_of_other_engines() {
+        let dir = TempDir::new().unwrap();
+        let store = Store::new(Singlepass::default());
+        let mut config = Singlepass::default();
+        config.canonicalize_nans(false);
+        let other_store = Store::new(config);
+        let mut features = Features::default();
+        features.threads(true);
+        let other_features = Store::new(
+            EngineBuilder::new(Singlepass::default())
+                .set_features(Some(features))
+                .engine(),
+        );
+        assert_ne!(
+            Hash::generate_for_engine(store.engine(), EMPTY_WASM),
+            Hash::generate_for_engine(other_store.engine(), EMPTY_WASM)
+        );
+        assert_ne!(
+            Hash::generate_for_engine(store.engine(), EMPTY_WASM),
+            Hash::generate_for_engine(other_features.engine(), EMPTY_WASM)
+        );
+
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        let key = Hash::generate(EMPTY_WASM);
+        cache
+            .store(key, &Module::new(&store, EMPTY_WASM).unwrap())
+            .unwrap();
+        let err = unsafe { cache.load(&other_store, key).unwrap_err() };
+        assert!(matches!(err, DeserializeError::Incompatible(_)), "{}", err);
+        let err = unsafe { cache.load(&other_features, key).unwrap_err() };
+        assert!(matches!(err, DeserializeError::Incompatible(_)), "{}", err);
+        unsafe { cache.load(&store, key).unwrap() };
+
+        // Engines with other compilers get their own entry, and share the
+        // one of headless engines with the same features
+        unsafe { cache.load_or_compile(&other_store, EMPTY_WASM).unwrap() };
+        unsafe { cache.load_or_compile(&store, EMPTY_WASM).unwrap() };
+        unsafe { cache.load_or_compile(&other_features, EMPTY_WASM).unwrap() };
+        assert_eq!(cache.entries().unwrap().len(), 6);
+        for store in &[&store, &other_store] {
+            let key = Hash::generate_for_engine(store.engine(), EMPTY_WASM).unwrap();
+            unsafe { cache.load(store, key).unwrap() };
+        }
+    }
+
+    #[test]
+    fn loads_compiled_modules_headless() {
+        // (module (func (export "answer") (result i32) i32.const 42))
+        let wasm = b"\0asm\x01\0\0\0\
+            \x01\x05\x01\x60\x00\x01\x7f\
+            \x03\x02\x01\x00\
+            \x07\x0a\x01\x06answer\x00\x00\
+            \x0a\x06\x01\x04\x00\x41\x2a\x0b";
+
+        let dir = TempDir::new().unwrap();
+        let compiler = Singlepass::default();
+        let features = compiler.default_features_for_target(&Target::default());
+        let store = Store::new(compiler);
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        unsafe { cache.load_or_compile(&store, wasm).unwrap() };
+
+        let mut headless = Store::new(
+            EngineBuilder::headless()
+                .set_features(Some(features))
+                .engine(),
+        );
+        assert!(headless.engine().is_headless());
+        assert_eq!(
+            headless.engine().compatibility_id(),
+            store.engine().compatibility_id()
+        );
+        let key = Hash::generate_for_engine(headless.engine(), wasm).unwrap();
+        assert_eq!(key, Hash::generate_for_headless(store.engine(), wasm));
+        assert_ne!(Some(key), Hash::generate_for_engine(store.engine(), wasm));
+        let module = unsafe { cache.load(&headless, key).unwrap() };
+        let instance = Instance::new(&mut headless, &module, &imports! {}).unwrap();
+        let answer = instance
+            .exports
+            .get_typed_function::<(), i32>(&headless, "answer")
+            .unwrap();
+        assert_eq!(answer.call(&mut headless).unwrap(), 42);
+
+        // With the default features, the modules of compilers that enable
+        // them too are loaded by `Engine::headless`
+        let compiler = Singlepass::default();
+        let store = Store::new(
+            EngineBuilder::new(compiler)
+                .set_features(Some(Features::default()))
+                .engine(),
+        );
+        unsafe { cache.load_or_compile(&store, wasm).unwrap() };
+        let headless = Store::new(Engine::headless());
+        let key = Hash::generate_for_engine(headless.engine(), wasm).unwrap();
+        unsafe { cache.load(&headless, key).unwrap() };
+    }
+
+    #[test]
+    fn refuses_engines_without_deterministic_id() {
+        use std::sync::Arc;
+        use wasmer::{FunctionMiddleware, LocalFunctionIndex, ModuleInfo, ModuleMiddleware};
+
+        #[derive(Debug)]
+        struct Passthrough;
+
+        impl FunctionMiddleware for Passthrough {}
+
+        impl ModuleMiddleware for Passthrough {
+            fn generate_function_middleware(
+                &self,
+                _: &ModuleInfo,
+                _: LocalFunctionIndex,
+            ) -> Box<dyn FunctionMiddleware> {
+                Box::new(Passthrough)
+            }
+        }
+
+        let dir = TempDir::new().unwrap();
+        let mut config = Singlepass::default();
+        config.push_middleware(Arc::new(Passthrough));
+        let store = Store::new(config);
+        assert_eq!(store.engine().deterministic_id(), None);
+        assert_eq!(Hash::generate_for_engine(store.engine(), EMPTY_WASM), None);
+
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        let module = Module::new(&store, EMPTY_WASM).unwrap();
+        assert!(cache.store(Hash::generate(EMPTY_WASM), &module).is_err());
+        unsafe { cache.load_or_compile(&store, EMPTY_WASM).unwrap() };
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "function-cache")]
+    #[test]
+    fn stores_functions() {
+        use wasmer::FunctionCacheStore;
+
+        let dir = TempDir::new().unwrap();
+        let store = Store::new(Singlepass::default());
+        let mut cache = FileSystemCache::new(dir.path()).unwrap();
+        let (function, module) = ([1; 32], [2; 32]);
+
+        FunctionCacheStore::store(&cache, &function, b"function").unwrap();
+        assert_eq!(
+            FunctionCacheStore::load(&cache, &function).unwrap(),
+            b"function"
+        );
+        assert!(FunctionCacheStore::load(&cache, &module).is_err());
+
+        // Functions and modules are never mistaken for each other
+        Cache::store(
+            &mut cache,
+            Hash::new(module),
+            &Module::new(&store, EMPTY_WASM).unwrap(),
+        )
+        .unwrap();
+        assert!(FunctionCacheStore::load(&cache, &module).is_err());
+        let err = unsafe { Cache::load(&cache, &store, Hash::new(function)).unwrap_err() };
+        assert!(matches!(err, DeserializeError::Incompatible(_)), "{}", err);
+    }
+}
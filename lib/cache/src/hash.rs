@@ -1,6 +1,7 @@
 use crate::DeserializeError;
 use std::str::FromStr;
 use std::string::ToString;
+use wasmer::Engine;
 
 /// A hash used as a key when loading and storing modules in a
 /// [`Cache`].
@@ -17,11 +18,56 @@ impl Hash {
     }
 
     /// Creates a new hash from a slice of bytes.
+    ///
+    /// The hash only depends on the bytes, so modules compiled from the
+    /// same bytes by differently configured engines get the same key. Use
+    /// [`Hash::generate_for_engine`] to tell them apart.
     pub fn generate(bytes: &[u8]) -> Self {
         let hash = blake3::hash(bytes);
         Self::new(hash.into())
     }
 
+    /// Creates a new hash from a WebAssembly binary and the engine that
+    /// compiles or loads it, or returns `None` if the modules of the engine
+    /// can't be cached.
+    ///
+    /// The key of an engine that compiles depends on its [deterministic
+    /// id], so modules compiled by different compilers, compiler settings
+    /// or middlewares get different keys. It is `None` when the engine has
+    /// no deterministic id, because one of its middlewares has none.
+    ///
+    /// A headless engine can't tell which compiler produced a module, so
+    /// its key is the one of [`Hash::generate_for_headless`], under which
+    /// [`Cache::load_or_compile`] also stores the modules it compiles.
+    ///
+    /// [deterministic id]: Engine::deterministic_id
+    /// [`Cache::load_or_compile`]: crate::Cache::load_or_compile
+    pub fn generate_for_engine(engine: &Engine, wasm: &[u8]) -> Option<Self> {
+        if engine.is_headless() {
+            return Some(Self::generate_for_headless(engine, wasm));
+        }
+        engine
+            .deterministic_id()
+            .map(|engine_id| Self::generate_for_id(engine_id, wasm))
+    }
+
+    /// Creates a new hash from a WebAssembly binary and the [compatibility
+    /// id] of an engine: the key that headless engines use to load the
+    /// modules compiled for the same target and features by any compiler.
+    ///
+    /// [compatibility id]: Engine::compatibility_id
+    pub fn generate_for_headless(engine: &Engine, wasm: &[u8]) -> Self {
+        Self::generate_for_id(engine.compatibility_id(), wasm)
+    }
+
+    fn generate_for_id(engine_id: &str, wasm: &[u8]) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(engine_id.len() as u64).to_le_bytes());
+        hasher.update(engine_id.as_bytes());
+        hasher.update(wasm);
+        Self::new(hasher.finalize().into())
+    }
+
     pub(crate) fn to_array(self) -> [u8; 32] {
         self.0
     }
@@ -29,6 +29,14 @@ pub struct WasmFeatures {
     #[clap(long = "enable-bulk-memory")]
     pub bulk_memory: bool,
 
+    /// Enable support for the relaxed SIMD proposal.
+    #[clap(long = "enable-relaxed-simd")]
+    pub relaxed_simd: bool,
+
+    /// Enable support for the extended constant expressions proposal.
+    #[clap(long = "enable-extended-const")]
+    pub extended_const: bool,
+
     /// Enable support for all pre-standard proposals.
     #[clap(long = "enable-all")]
     pub all: bool,
@@ -165,6 +165,12 @@ impl CompilerOptions {
         if self.features.reference_types || self.features.all {
             features.reference_types(true);
         }
+        if self.features.relaxed_simd || self.features.all {
+            features.relaxed_simd(true);
+        }
+        if self.features.extended_const || self.features.all {
+            features.extended_const(true);
+        }
         Ok(features)
     }
 
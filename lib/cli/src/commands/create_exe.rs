@@ -5,6 +5,7 @@ use crate::store::CompilerOptions;
 use anyhow::{Context, Result};
 use clap::Parser;
 use std::env;
+use std::ffi::OsString;
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
@@ -12,7 +13,7 @@ use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use wasmer::*;
-use wasmer_object::{emit_serialized, get_object_for_target};
+use wasmer_object::{emit_data, emit_serialized, get_object_for_target, Object};
 
 /// The `prefixer` returns the a String to prefix each of the
 /// functions in the static object generated by the
@@ -104,6 +105,51 @@ pub struct CreateExe {
     #[clap(short = 'l')]
     libraries: Vec<String>,
 
+    /// Link the executable with the linker and runtime objects shipped with Wasmer
+    ///
+    /// Instead of `zig` or a system C compiler, the executable is linked with the `lld` linker
+    /// in `$WASMER_DIR/bin` and the runtime objects of the target in
+    /// `$WASMER_DIR/lib/targets/<TARGET>`, for example `x86_64-unknown-linux-musl`. Nothing is
+    /// downloaded and no C toolchain is needed, for the host or any other target. Only Linux
+    /// targets are supported, Wasmer packages include the runtime objects of
+    /// `x86_64-unknown-linux-musl` and `aarch64-unknown-linux-musl`. The executables are
+    /// static, so other Linux targets, like `x86_64-unknown-linux-gnu`, are linked with the
+    /// runtime objects of the musl target of their architecture. The directory of a target
+    /// contains:
+    ///
+    /// - `wasmer_main.o`, the compiled `main` function of the executables
+    /// - `libwasmer-headless.a` or `libwasmer.a`, unless `--library-path` is given
+    /// - `crt1.o`, `crti.o` and `crtn.o`, and optionally `crtbegin.o` and `crtend.o`
+    /// - the static libraries that libwasmer depends on, like `libc.a`
+    ///
+    /// Only the `serialized` object format is supported.
+    #[clap(long = "self-contained", verbatim_doc_comment)]
+    self_contained: bool,
+
+    /// Additional command embedded in the executable, as `NAME=PATH`
+    ///
+    /// The executable runs the command named like the file it is run as, so that it can be
+    /// linked or copied under the name of each command, or the command given with
+    /// `--command NAME` as its first arguments. Otherwise it runs the module in `FILE`.
+    ///
+    /// Only the `serialized` object format is supported.
+    #[clap(long = "command", value_name = "NAME=PATH", verbatim_doc_comment)]
+    commands: Vec<String>,
+
+    /// Directory or tar archive embedded in the executable, and mounted read-only at `/`
+    ///
+    /// The filesystem replaces the host filesystem, so the `--dir` and `--mapdir` arguments
+    /// of the executable can't be used along with it.
+    ///
+    /// Only the `serialized` object format is supported.
+    #[clap(
+        long = "fs",
+        value_name = "PATH",
+        parse(from_os_str),
+        verbatim_doc_comment
+    )]
+    filesystem: Option<PathBuf>,
+
     #[clap(flatten)]
     compiler: CompilerOptions,
 }
@@ -111,7 +157,13 @@ pub struct CreateExe {
 impl CreateExe {
     /// Runs logic for the `compile` subcommand
     pub fn execute(&self) -> Result<()> {
-        let object_format = self.object_format.unwrap_or(ObjectFormat::Symbols);
+        let object_format =
+            self.object_format
+                .unwrap_or(if self.self_contained || self.is_bundle() {
+                    ObjectFormat::Serialized
+                } else {
+                    ObjectFormat::Symbols
+                });
         let working_dir = tempfile::tempdir()?;
         let starting_cd = env::current_dir()?;
         let output_path = starting_cd.join(&self.output);
@@ -120,6 +172,7 @@ impl CreateExe {
          * is set cannot be encoded with structopt, so we have to perform cli flag validation
          * manually here */
         let cross_compile: Option<CrossCompile> = if self.target_triple.is_none()
+            && !self.self_contained
             && (self.library_path.is_some()
                 || self.tarball.is_some()
                 || self.zig_binary_path.is_some())
@@ -155,6 +208,27 @@ impl CreateExe {
             })
             .unwrap_or_default();
 
+        if self.is_bundle() {
+            if self.header.is_some() {
+                bail!(
+                    "Objects created with `wasmer create-obj` can't embed commands or filesystems"
+                );
+            }
+            if let ObjectFormat::Symbols = object_format {
+                bail!("Executables that embed commands or filesystems only support the `serialized` object format");
+            }
+        }
+
+        if self.self_contained {
+            return self.link_self_contained(
+                &target,
+                object_format,
+                working_dir.path(),
+                &starting_cd,
+                &output_path,
+            );
+        }
+
         env::set_current_dir(&working_dir)?;
 
         let cross_compilation: Option<CrossCompileSetup> = if let Some(mut cross_subc) =
@@ -311,6 +385,9 @@ impl CreateExe {
                     let bytes = module.serialize()?;
                     let mut obj = get_object_for_target(target.triple())?;
                     emit_serialized(&mut obj, &bytes, target.triple())?;
+                    if self.is_bundle() {
+                        self.emit_bundle(&mut obj, &store, &starting_cd)?;
+                    }
                     let mut writer = BufWriter::new(File::create(&wasm_object_path)?);
                     obj.write_stream(&mut writer)
                         .map_err(|err| anyhow::anyhow!(err.to_string()))?;
@@ -319,6 +396,9 @@ impl CreateExe {
                     // Write down header file that includes deserialize function
                     {
                         let mut writer = BufWriter::new(File::create(&static_defs_header_path)?);
+                        if self.is_bundle() {
+                            writer.write_all(b"#define WASMER_BUNDLE\n")?;
+                        }
                         writer.write_all(WASMER_DESERIALIZE_HEADER.as_bytes())?;
                         writer.flush()?;
                     }
@@ -427,6 +507,130 @@ impl CreateExe {
         Ok(())
     }
 
+    /// Creates the executable with the linker and runtime objects shipped
+    /// with Wasmer, see `--self-contained`.
+    fn link_self_contained(
+        &self,
+        target: &Target,
+        object_format: ObjectFormat,
+        working_dir: &Path,
+        starting_cd: &Path,
+        output_path: &Path,
+    ) -> Result<()> {
+        if self.zig_binary_path.is_some() || self.tarball.is_some() {
+            bail!("The --zig-binary-path and --tarball flags can't be used with --self-contained");
+        }
+        if self.header.is_some() {
+            bail!("Objects created with `wasmer create-obj` can't be linked with --self-contained");
+        }
+        if let ObjectFormat::Symbols = object_format {
+            bail!("Executables linked with --self-contained only support the `serialized` object format");
+        }
+        let runtime = SelfContainedRuntime::find(
+            target.triple(),
+            self.library_path
+                .as_ref()
+                .map(|path| starting_cd.join(path)),
+        )?;
+
+        let (store, compiler_type) = self.compiler.get_store_for_target(target.clone())?;
+
+        println!("Compiler: {}", compiler_type.to_string());
+        println!("Target: {}", target.triple());
+        println!("Format: {:?}", object_format);
+        println!("Runtime: {}", runtime.dir.display());
+
+        // The module is serialized without being loaded, as it may be compiled
+        // for another target than the host
+        let data = fs::read(starting_cd.join(&self.path))?;
+        #[cfg(feature = "wat")]
+        let data = wat2wasm(&data)?;
+        let bytes = Artifact::generate_serialized(store.engine(), &data, store.tunables())
+            .context("failed to compile Wasm")?;
+        let mut obj = get_object_for_target(target.triple())?;
+        emit_serialized(&mut obj, &bytes, target.triple())?;
+        // The `main` function of the runtime reads the bundle, even if empty
+        self.emit_bundle(&mut obj, &store, starting_cd)?;
+        let wasm_object_path = working_dir.join("wasm.o");
+        let mut writer = BufWriter::new(File::create(&wasm_object_path)?);
+        obj.write_stream(&mut writer)
+            .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+        writer.flush()?;
+        drop(writer);
+
+        runtime
+            .link(&wasm_object_path, &self.libraries, output_path)
+            .context("Failed to link objects together")?;
+
+        eprintln!(
+            "✔ Executable for `{}` target linked successfully to `{}`.",
+            target.triple(),
+            self.output.display(),
+        );
+        Ok(())
+    }
+
+    /// Returns true if the executable embeds commands or a filesystem.
+    fn is_bundle(&self) -> bool {
+        !self.commands.is_empty() || self.filesystem.is_some()
+    }
+
+    /// Emits the commands and the filesystem embedded in the executable,
+    /// see `--command` and `--fs`, into the object of its module.
+    ///
+    /// The commands are stored one after the other, each as the length of
+    /// its name and the length of its serialized module as 64-bit little
+    /// endian integers, followed by its name and its module, both padded to
+    /// 16 bytes. The filesystem is stored as a tar archive.
+    fn emit_bundle(&self, obj: &mut Object, store: &Store, starting_cd: &Path) -> Result<()> {
+        let mut commands = vec![];
+        for command in self.commands.iter() {
+            let (name, path) = command
+                .split_once('=')
+                .filter(|(name, _)| !name.is_empty() && !name.contains('/'))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Expected a command of the form NAME=PATH, got `{}`",
+                        command
+                    )
+                })?;
+            let data = fs::read(starting_cd.join(path))
+                .with_context(|| format!("Could not read the `{}` command", name))?;
+            #[cfg(feature = "wat")]
+            let data = wat2wasm(&data)?;
+            let bytes = Artifact::generate_serialized(store.engine(), &data, store.tunables())
+                .with_context(|| format!("failed to compile the `{}` command", name))?;
+            for field in [name.len(), bytes.len()] {
+                commands.extend_from_slice(&(field as u64).to_le_bytes());
+            }
+            for field in [name.as_bytes(), &bytes] {
+                commands.extend_from_slice(field);
+                commands.resize((commands.len() + 15) & !15, 0);
+            }
+        }
+
+        let filesystem = match self.filesystem.as_ref() {
+            Some(path) => filesystem_archive(&starting_cd.join(path))?,
+            None => vec![],
+        };
+
+        emit_data(
+            obj,
+            b"WASMER_COMMANDS_LENGTH",
+            &commands.len().to_le_bytes(),
+            8,
+        )?;
+        emit_data(obj, b"WASMER_COMMANDS_DATA", &commands, 16)?;
+        emit_data(
+            obj,
+            b"WASMER_FILESYSTEM_LENGTH",
+            &filesystem.len().to_le_bytes(),
+            8,
+        )?;
+        emit_data(obj, b"WASMER_FILESYSTEM_DATA", &filesystem, 16)?;
+        Ok(())
+    }
+
     fn compile_zig(
         &self,
         output_path: PathBuf,
@@ -663,6 +867,230 @@ fn get_libwasmer_cache_path() -> anyhow::Result<PathBuf> {
     Ok(path)
 }
 
+/// The tar archive of the filesystem at `path`, which is either a directory
+/// or a tar archive.
+fn filesystem_archive(path: &Path) -> Result<Vec<u8>> {
+    if path.is_dir() {
+        let mut builder = tar::Builder::new(vec![]);
+        builder.follow_symlinks(true);
+        builder
+            .append_dir_all(".", path)
+            .with_context(|| format!("Could not archive `{}`", path.display()))?;
+        return Ok(builder.into_inner()?);
+    }
+    let archive = fs::read(path).with_context(|| format!("Could not read `{}`", path.display()))?;
+    // Check that the archive can be read
+    for entry in tar::Archive::new(&archive[..])
+        .entries()
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("`{}` isn't a tar archive", path.display()))?
+    {
+        drop(entry);
+    }
+    Ok(archive)
+}
+
+/// The linker and runtime objects shipped with Wasmer that executables are
+/// linked with when using `--self-contained`.
+#[derive(Debug)]
+struct SelfContainedRuntime {
+    /// Path to the `lld` linker.
+    linker_path: PathBuf,
+    /// The linker emulation for the target.
+    emulation: &'static str,
+    /// Directory of the runtime objects of the target.
+    dir: PathBuf,
+    /// Path to the static libwasmer library.
+    libwasmer_path: PathBuf,
+}
+
+impl SelfContainedRuntime {
+    /// Objects linked before the objects of the executable.
+    const START_OBJECTS: &'static [&'static str] = &["crt1.o", "crti.o"];
+    /// Objects linked after all the others.
+    const END_OBJECTS: &'static [&'static str] = &["crtn.o"];
+
+    /// Finds the linker and the runtime objects for `target`, and the
+    /// libwasmer at `library_path` if given.
+    fn find(target: &Triple, library_path: Option<PathBuf>) -> anyhow::Result<Self> {
+        if target.operating_system != wasmer_types::OperatingSystem::Linux {
+            bail!(
+                "The `{}` target isn't supported with --self-contained, only Linux targets are.",
+                target
+            );
+        }
+        let emulation = match target.architecture {
+            Architecture::X86_64 => "elf_x86_64",
+            Architecture::Aarch64(_) => "aarch64linux",
+            _ => bail!(
+                "The `{}` target isn't supported with --self-contained, only x86_64 and aarch64 are.",
+                target
+            ),
+        };
+
+        let wasmer_dir = get_wasmer_dir()?;
+        let linker_path =
+            wasmer_dir
+                .join("bin")
+                .join(if cfg!(windows) { "lld.exe" } else { "lld" });
+        if !linker_path.is_file() {
+            bail!(
+                "Could not find the linker shipped with Wasmer at `{}`.",
+                linker_path.display()
+            );
+        }
+        let targets_dir = wasmer_dir.join("lib").join("targets");
+        let dir = Some(targets_dir.join(target.to_string()))
+            .filter(|dir| dir.is_dir())
+            .or_else(|| {
+                // The executables are static, hence the runtime of the musl
+                // target of the architecture works for any Linux target
+                let mut musl_target = target.clone();
+                musl_target.environment = wasmer_types::Environment::Musl;
+                Some(targets_dir.join(musl_target.to_string())).filter(|dir| dir.is_dir())
+            });
+        let dir = match dir {
+            Some(dir) => dir,
+            None => {
+                let mut packaged = fs::read_dir(&targets_dir)
+                    .map(|entries| {
+                        entries
+                            .filter_map(|entry| entry.ok())
+                            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                packaged.sort();
+                bail!(
+                    "Could not find the runtime objects for the `{}` target in `{}`, --self-contained only supports the targets Wasmer ships runtime objects for: {}. The runtime objects of other targets can be built with `make package-create-exe-runtime CREATE_EXE_RUNTIME_TARGETS={}` in the Wasmer repository.",
+                    target,
+                    targets_dir.display(),
+                    if packaged.is_empty() {
+                        "none".to_string()
+                    } else {
+                        packaged.join(", ")
+                    },
+                    target
+                );
+            }
+        };
+        let libwasmer_path = match library_path {
+            Some(path) => path,
+            None => ["libwasmer-headless.a", "libwasmer.a"]
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Could not find libwasmer for the `{}` target in `{}`.",
+                        target,
+                        dir.display()
+                    )
+                })?,
+        };
+        let runtime = Self {
+            linker_path,
+            emulation,
+            dir,
+            libwasmer_path,
+        };
+        for name in Self::START_OBJECTS
+            .iter()
+            .chain(&["wasmer_main.o"])
+            .chain(Self::END_OBJECTS)
+        {
+            if !runtime.dir.join(name).is_file() {
+                bail!(
+                    "Could not find `{}` for the `{}` target in `{}`.",
+                    name,
+                    target,
+                    runtime.dir.display()
+                );
+            }
+        }
+        Ok(runtime)
+    }
+
+    /// The arguments to the linker, to link the object of the module at
+    /// `wasm_object_path` into a static executable at `output_path`.
+    fn args(
+        &self,
+        wasm_object_path: &Path,
+        libraries: &[String],
+        output_path: &Path,
+    ) -> anyhow::Result<Vec<OsString>> {
+        let optional = |name: &str| Some(self.dir.join(name)).filter(|path| path.is_file());
+        // The static libraries of the C library and its dependencies, in a
+        // group so that their order doesn't matter
+        let mut static_libraries = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str());
+            if path.extension().map_or(false, |extension| extension == "a")
+                && !matches!(name, Some("libwasmer.a") | Some("libwasmer-headless.a"))
+            {
+                static_libraries.push(path);
+            }
+        }
+        static_libraries.sort();
+
+        let mut args: Vec<OsString> = vec![
+            "-flavor".into(),
+            "gnu".into(),
+            "-m".into(),
+            self.emulation.into(),
+            "-static".into(),
+            "--gc-sections".into(),
+            "-o".into(),
+            output_path.into(),
+        ];
+        args.extend(
+            Self::START_OBJECTS
+                .iter()
+                .map(|name| self.dir.join(name).into()),
+        );
+        args.extend(optional("crtbegin.o").map(OsString::from));
+        args.push(self.dir.join("wasmer_main.o").into());
+        args.push(wasm_object_path.into());
+        args.push("--start-group".into());
+        args.push(self.libwasmer_path.clone().into());
+        args.extend(static_libraries.into_iter().map(OsString::from));
+        args.push(format!("-L{}", self.dir.display()).into());
+        args.extend(libraries.iter().map(|lib| format!("-l{}", lib).into()));
+        args.push("--end-group".into());
+        args.extend(optional("crtend.o").map(OsString::from));
+        args.extend(
+            Self::END_OBJECTS
+                .iter()
+                .map(|name| self.dir.join(name).into()),
+        );
+        Ok(args)
+    }
+
+    fn link(
+        &self,
+        wasm_object_path: &Path,
+        libraries: &[String],
+        output_path: &Path,
+    ) -> anyhow::Result<()> {
+        println!("Using linker: {}", self.linker_path.display());
+        println!("Using libwasmer file: {}", self.libwasmer_path.display());
+        let output = Command::new(&self.linker_path)
+            .args(self.args(wasm_object_path, libraries, output_path)?)
+            .output()
+            .context("Could not execute the linker")?;
+
+        if !output.status.success() {
+            bail!(
+                "linking failed with: stdout: {}\n\nstderr: {}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+}
+
 /// Compile the C code.
 fn run_c_compile(
     path_to_c_src: &Path,
@@ -2,8 +2,15 @@ use crate::store::StoreOptions;
 use anyhow::{Context, Result};
 use bytesize::ByteSize;
 use clap::Parser;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::str::FromStr;
 use wasmer::*;
+use wasmer_compiler::wasmparser::{
+    self, BinaryReader, DataKind, ImportSectionEntryType, Name, NameSectionReader, Payload,
+    ProducersSectionReader, SectionReader,
+};
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer validate` subcommand
@@ -12,10 +19,35 @@ pub struct Inspect {
     #[clap(name = "FILE", parse(from_os_str))]
     path: PathBuf,
 
+    /// Output format. One of `human` or `json`.
+    #[clap(long, name = "FORMAT", default_value = "human")]
+    format: InspectFormat,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// The output format of the `inspect` subcommand
+#[derive(Debug, Clone, Copy)]
+enum InspectFormat {
+    /// A summary meant to be read
+    Human,
+    /// The full report, meant to be processed by other tools
+    Json,
+}
+
+impl FromStr for InspectFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err("must be one of two options: `human` or `json`."),
+        }
+    }
+}
+
 impl Inspect {
     /// Runs logic for the `validate` subcommand
     pub fn execute(&self) -> Result<()> {
@@ -26,44 +58,463 @@ impl Inspect {
         let (store, _compiler_type) = self.store.get_store()?;
         let module_contents = std::fs::read(&self.path)?;
         let iswasm = is_wasm(&module_contents);
-        let module_len = module_contents.len();
-        let module = Module::new(&store, module_contents)?;
-        println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
-        println!("Size: {}", ByteSize(module_len as _));
-        println!("Imports:");
-        println!("  Functions:");
-        for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        let module = Module::new(&store, &module_contents)?;
+        #[cfg(feature = "wat")]
+        let wasm = wat2wasm(&module_contents)?;
+        #[cfg(not(feature = "wat"))]
+        let wasm = std::borrow::Cow::Borrowed(&module_contents[..]);
+        let mut report = ModuleReport::new(&wasm, &module)?;
+        report.kind = if !iswasm { "wat" } else { "wasm" };
+        report.size = module_contents.len() as _;
+        match self.format {
+            InspectFormat::Human => report.print(),
+            InspectFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
         }
-        println!("  Memories:");
-        for f in module.imports().memories() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        Ok(())
+    }
+}
+
+/// Everything `wasmer inspect` reports about a module.
+///
+/// Sizes are in bytes, and don't include the headers of the sections.
+#[derive(Debug, Default, Serialize)]
+struct ModuleReport {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: u64,
+    sections: Vec<SectionReport>,
+    functions: FunctionsReport,
+    data_segments: Vec<DataSegmentReport>,
+    memories: Vec<MemoryReport>,
+    tables: Vec<TableReport>,
+    start_function: Option<u32>,
+    wasi_version: Option<&'static str>,
+    producers: BTreeMap<String, Vec<ProducerReport>>,
+    module_name: Option<String>,
+    target_features: Vec<String>,
+    imports: Vec<ExternReport>,
+    exports: Vec<ExternReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct SectionReport {
+    id: u8,
+    name: String,
+    size: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct FunctionsReport {
+    imported: u32,
+    defined: u32,
+    code_size: u64,
+    bodies: Vec<FunctionReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionReport {
+    index: u32,
+    name: Option<String>,
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct DataSegmentReport {
+    /// The memory the segment is copied to, `None` for passive segments
+    memory: Option<u32>,
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct MemoryReport {
+    index: u32,
+    import: Option<ImportName>,
+    minimum_pages: u64,
+    maximum_pages: Option<u64>,
+    shared: bool,
+    memory64: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct TableReport {
+    index: u32,
+    import: Option<ImportName>,
+    element_type: String,
+    minimum: u32,
+    maximum: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportName {
+    module: String,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ProducerReport {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExternReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<String>,
+    name: String,
+    kind: &'static str,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+impl ModuleReport {
+    fn new(wasm: &[u8], module: &Module) -> Result<Self> {
+        let mut report = Self::default();
+        let mut function_names = BTreeMap::new();
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::TypeSection(s) => report.section(1, s.range()),
+                Payload::ImportSection(s) => {
+                    report.section(2, s.range());
+                    for import in s {
+                        let import = import?;
+                        let name = || {
+                            Some(ImportName {
+                                module: import.module.to_string(),
+                                name: import.field.unwrap_or_default().to_string(),
+                            })
+                        };
+                        match import.ty {
+                            ImportSectionEntryType::Function(_) => report.functions.imported += 1,
+                            ImportSectionEntryType::Memory(ty) => report.memory(name(), ty),
+                            ImportSectionEntryType::Table(ty) => report.table(name(), ty),
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::FunctionSection(s) => {
+                    report.section(3, s.range());
+                    report.functions.defined = s.get_count();
+                }
+                Payload::TableSection(s) => {
+                    report.section(4, s.range());
+                    for ty in s {
+                        report.table(None, ty?);
+                    }
+                }
+                Payload::MemorySection(s) => {
+                    report.section(5, s.range());
+                    for ty in s {
+                        report.memory(None, ty?);
+                    }
+                }
+                Payload::GlobalSection(s) => report.section(6, s.range()),
+                Payload::ExportSection(s) => report.section(7, s.range()),
+                Payload::StartSection { func, range } => {
+                    report.section(8, range);
+                    report.start_function = Some(func);
+                }
+                Payload::ElementSection(s) => report.section(9, s.range()),
+                Payload::CodeSectionStart { range, .. } => report.section(10, range),
+                Payload::CodeSectionEntry(body) => {
+                    let size = (body.range().end - body.range().start) as u64;
+                    report.functions.code_size += size;
+                    report.functions.bodies.push(FunctionReport {
+                        index: report.functions.imported + report.functions.bodies.len() as u32,
+                        name: None,
+                        size,
+                    });
+                }
+                Payload::DataSection(s) => {
+                    report.section(11, s.range());
+                    for data in s {
+                        let data = data?;
+                        report.data_segments.push(DataSegmentReport {
+                            memory: match data.kind {
+                                DataKind::Passive => None,
+                                DataKind::Active { memory_index, .. } => Some(memory_index),
+                            },
+                            size: data.data.len() as _,
+                        });
+                    }
+                }
+                Payload::DataCountSection { range, .. } => report.section(12, range),
+                Payload::TagSection(s) => report.section(13, s.range()),
+                Payload::CustomSection {
+                    name,
+                    data_offset,
+                    data,
+                    range,
+                } => {
+                    report.sections.push(SectionReport {
+                        id: 0,
+                        name: name.to_string(),
+                        size: (range.end - range.start) as _,
+                    });
+                    // Custom sections are informative, so the malformed ones are skipped
+                    let _ = match name {
+                        "name" => report.names(data, data_offset, &mut function_names),
+                        "producers" => report.producers(data, data_offset),
+                        "target_features" => report.target_features(data, data_offset),
+                        _ => Ok(()),
+                    };
+                }
+                Payload::UnknownSection { id, range, .. } => report.section(id, range),
+                _ => {}
+            }
         }
-        println!("  Tables:");
-        for f in module.imports().tables() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        for function in report.functions.bodies.iter_mut() {
+            function.name = function_names.remove(&function.index);
         }
-        println!("  Globals:");
-        for f in module.imports().globals() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+
+        #[cfg(feature = "wasi")]
+        {
+            report.wasi_version = wasmer_wasi::get_wasi_version(module, false)
+                .map(|version| version.get_namespace_str());
         }
-        println!("Exports:");
-        println!("  Functions:");
-        for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+
+        for import in module.imports() {
+            let (kind, ty) = extern_type(import.ty());
+            report.imports.push(ExternReport {
+                module: Some(import.module().to_string()),
+                name: import.name().to_string(),
+                kind,
+                ty,
+            });
         }
-        println!("  Memories:");
-        for f in module.exports().memories() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        for export in module.exports() {
+            let (kind, ty) = extern_type(export.ty());
+            report.exports.push(ExternReport {
+                module: None,
+                name: export.name().to_string(),
+                kind,
+                ty,
+            });
         }
-        println!("  Tables:");
-        for f in module.exports().tables() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        Ok(report)
+    }
+
+    fn section(&mut self, id: u8, range: wasmparser::Range) {
+        let name = match id {
+            1 => "type",
+            2 => "import",
+            3 => "function",
+            4 => "table",
+            5 => "memory",
+            6 => "global",
+            7 => "export",
+            8 => "start",
+            9 => "element",
+            10 => "code",
+            11 => "data",
+            12 => "datacount",
+            13 => "tag",
+            _ => "unknown",
+        };
+        self.sections.push(SectionReport {
+            id,
+            name: name.to_string(),
+            size: (range.end - range.start) as _,
+        });
+    }
+
+    fn memory(&mut self, import: Option<ImportName>, ty: wasmparser::MemoryType) {
+        self.memories.push(MemoryReport {
+            index: self.memories.len() as _,
+            import,
+            minimum_pages: ty.initial,
+            maximum_pages: ty.maximum,
+            shared: ty.shared,
+            memory64: ty.memory64,
+        });
+    }
+
+    fn table(&mut self, import: Option<ImportName>, ty: wasmparser::TableType) {
+        self.tables.push(TableReport {
+            index: self.tables.len() as _,
+            import,
+            element_type: match ty.element_type {
+                wasmparser::Type::FuncRef => "funcref".to_string(),
+                wasmparser::Type::ExternRef => "externref".to_string(),
+                other => format!("{:?}", other),
+            },
+            minimum: ty.initial,
+            maximum: ty.maximum,
+        });
+    }
+
+    /// Reads the `name` custom section.
+    fn names(
+        &mut self,
+        data: &[u8],
+        offset: usize,
+        function_names: &mut BTreeMap<u32, String>,
+    ) -> wasmparser::Result<()> {
+        let mut reader = NameSectionReader::new(data, offset)?;
+        while !reader.eof() {
+            match reader.read()? {
+                Name::Module(name) => self.module_name = Some(name.get_name()?.to_string()),
+                Name::Function(names) => {
+                    let mut names = names.get_map()?;
+                    for _ in 0..names.get_count() {
+                        let naming = names.read()?;
+                        function_names.insert(naming.index, naming.name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the `producers` custom section.
+    fn producers(&mut self, data: &[u8], offset: usize) -> wasmparser::Result<()> {
+        for field in ProducersSectionReader::new(data, offset)? {
+            let field = field?;
+            let mut values = vec![];
+            for value in field.get_producer_field_values_reader()? {
+                let value = value?;
+                values.push(ProducerReport {
+                    name: value.name.to_string(),
+                    version: value.version.to_string(),
+                });
+            }
+            self.producers.insert(field.name.to_string(), values);
         }
-        println!("  Globals:");
-        for f in module.exports().globals() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        Ok(())
+    }
+
+    /// Reads the `target_features` custom section, a vector of features
+    /// names each prefixed with `+` (used), `-` (not used) or `=` (required).
+    fn target_features(&mut self, data: &[u8], offset: usize) -> wasmparser::Result<()> {
+        let mut reader = BinaryReader::new_with_offset(data, offset);
+        for _ in 0..reader.read_var_u32()? {
+            let prefix = reader.read_u8()? as u8 as char;
+            let name = reader.read_string()?;
+            self.target_features.push(format!("{}{}", prefix, name));
         }
         Ok(())
     }
+
+    fn print(&self) {
+        println!("Type: {}", self.kind);
+        println!("Size: {}", ByteSize(self.size));
+        println!("Sections:");
+        for section in self.sections.iter() {
+            if section.id == 0 {
+                println!("  custom \"{}\": {}", section.name, ByteSize(section.size));
+            } else {
+                println!("  {}: {}", section.name, ByteSize(section.size));
+            }
+        }
+        println!(
+            "Functions: {} imported, {} defined, {} of code",
+            self.functions.imported,
+            self.functions.defined,
+            ByteSize(self.functions.code_size)
+        );
+        println!(
+            "Data segments: {}, {}",
+            self.data_segments.len(),
+            ByteSize(self.data_segments.iter().map(|data| data.size).sum())
+        );
+        println!("Memories:");
+        for memory in self.memories.iter() {
+            let maximum = match memory.maximum_pages {
+                Some(maximum) => format!("{} pages", maximum),
+                None => "unbounded".to_string(),
+            };
+            print!(
+                "  {}: {} pages minimum, {} maximum",
+                memory.index, memory.minimum_pages, maximum
+            );
+            if memory.shared {
+                print!(", shared");
+            }
+            if memory.memory64 {
+                print!(", 64-bit");
+            }
+            match memory.import.as_ref() {
+                Some(import) => {
+                    println!(" (imported from \"{}\".\"{}\")", import.module, import.name)
+                }
+                None => println!(),
+            }
+        }
+        println!("Tables:");
+        for table in self.tables.iter() {
+            let maximum = match table.maximum {
+                Some(maximum) => maximum.to_string(),
+                None => "unbounded".to_string(),
+            };
+            print!(
+                "  {}: {}, {} elements minimum, {} maximum",
+                table.index, table.element_type, table.minimum, maximum
+            );
+            match table.import.as_ref() {
+                Some(import) => {
+                    println!(" (imported from \"{}\".\"{}\")", import.module, import.name)
+                }
+                None => println!(),
+            }
+        }
+        if let Some(start_function) = self.start_function {
+            println!("Start function: {}", start_function);
+        }
+        if let Some(wasi_version) = self.wasi_version {
+            println!("WASI version: {}", wasi_version);
+        }
+        if let Some(module_name) = self.module_name.as_ref() {
+            println!("Module name: {}", module_name);
+        }
+        if !self.producers.is_empty() {
+            println!("Producers:");
+            for (field, values) in self.producers.iter() {
+                let values = values
+                    .iter()
+                    .map(|value| format!("{} {}", value.name, value.version))
+                    .collect::<Vec<_>>();
+                println!("  {}: {}", field, values.join(", "));
+            }
+        }
+        if !self.target_features.is_empty() {
+            println!("Target features: {}", self.target_features.join(", "));
+        }
+        println!("Imports:");
+        for kind in ["function", "memory", "table", "global"] {
+            println!("  {}:", extern_kind_title(kind));
+            for import in self.imports.iter().filter(|import| import.kind == kind) {
+                println!(
+                    "    \"{}\".\"{}\": {}",
+                    import.module.as_deref().unwrap_or_default(),
+                    import.name,
+                    import.ty
+                );
+            }
+        }
+        println!("Exports:");
+        for kind in ["function", "memory", "table", "global"] {
+            println!("  {}:", extern_kind_title(kind));
+            for export in self.exports.iter().filter(|export| export.kind == kind) {
+                println!("    \"{}\": {}", export.name, export.ty);
+            }
+        }
+    }
+}
+
+fn extern_type(ty: &ExternType) -> (&'static str, String) {
+    match ty {
+        ExternType::Function(ty) => ("function", ty.to_string()),
+        ExternType::Memory(ty) => ("memory", ty.to_string()),
+        ExternType::Table(ty) => ("table", ty.to_string()),
+        ExternType::Global(ty) => ("global", ty.to_string()),
+    }
+}
+
+fn extern_kind_title(kind: &str) -> &'static str {
+    match kind {
+        "function" => "Functions",
+        "memory" => "Memories",
+        "table" => "Tables",
+        _ => "Globals",
+    }
 }
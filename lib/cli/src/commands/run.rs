@@ -1,4 +1,4 @@
-use crate::common::get_cache_dir;
+use crate::common::{get_cache_dir, get_cache_max_size};
 #[cfg(feature = "debug")]
 use crate::logging;
 use crate::store::{CompilerType, StoreOptions};
@@ -267,10 +267,22 @@ impl Run {
             let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
             return Ok((store, module));
         }
-        let (store, compiler_type) = self.store.get_store()?;
+        #[cfg(feature = "wasi")]
+        let deterministic = self.wasi.is_deterministic();
+        #[cfg(not(feature = "wasi"))]
+        let deterministic = false;
+        let mut store_options = self.store.clone();
+        if deterministic {
+            store_options.canonicalize_nans();
+        }
+        #[cfg(feature = "wasi")]
+        if let Some(limit) = self.wasi.memory_limit() {
+            store_options.limit_memory(Pages(limit));
+        }
+        let (store, compiler_type) = store_options.get_store()?;
         #[cfg(feature = "cache")]
         let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
-            self.get_module_from_cache(&store, &contents, &compiler_type)
+            self.get_module_from_cache(&store, &contents)
         } else {
             Module::new(&store, contents).map_err(|e| e.into())
         };
@@ -290,24 +302,25 @@ impl Run {
     }
 
     #[cfg(feature = "cache")]
-    fn get_module_from_cache(
-        &self,
-        store: &Store,
-        contents: &[u8],
-        compiler_type: &CompilerType,
-    ) -> Result<Module> {
+    fn get_module_from_cache(&self, store: &Store, contents: &[u8]) -> Result<Module> {
         // We try to get it from cache, in case caching is enabled
         // and the file length is greater than 4KB.
         // For files smaller than 4KB caching is not worth,
         // as it takes space and the speedup is minimal.
-        let mut cache = self.get_cache(compiler_type)?;
+        let mut cache = self.get_cache()?;
         // Try to get the hash from the provided `--cache-key`, otherwise
-        // generate one from the provided file `.wasm` contents.
-        let hash = self
+        // generate one from the provided file `.wasm` contents and the
+        // configuration of the engine. The modules of engines without a
+        // deterministic id are not cached.
+        let hash = match self
             .cache_key
             .as_ref()
             .and_then(|key| Hash::from_str(key).ok())
-            .unwrap_or_else(|| Hash::generate(contents));
+            .or_else(|| Hash::generate_for_engine(store.engine(), contents))
+        {
+            Some(hash) => hash,
+            None => return Ok(Module::new(store, contents)?),
+        };
         match unsafe { cache.load(store, hash) } {
             Ok(module) => Ok(module),
             Err(e) => {
@@ -316,7 +329,7 @@ impl Run {
                         // Do not notify on IO errors
                     }
                     err => {
-                        warning!("cached module is corrupted: {}", err);
+                        warning!("cached module can not be loaded: {}", err);
                     }
                 }
                 let module = Module::new(store, contents)?;
@@ -328,14 +341,14 @@ impl Run {
     }
 
     #[cfg(feature = "cache")]
-    /// Get the Compiler Filesystem cache
-    fn get_cache(&self, compiler_type: &CompilerType) -> Result<FileSystemCache> {
-        let mut cache_dir_root = get_cache_dir();
-        cache_dir_root.push(compiler_type.to_string());
-        let mut cache = FileSystemCache::new(cache_dir_root)?;
+    /// Get the Filesystem cache, which is shared by all the compilers as
+    /// the keys depend on the configuration of the engine
+    fn get_cache(&self) -> Result<FileSystemCache> {
+        let mut cache = FileSystemCache::new(get_cache_dir())?;
 
         let extension = "wasmu";
         cache.set_cache_extension(Some(extension));
+        cache.set_max_size(get_cache_max_size());
         Ok(cache)
     }
 
@@ -1,15 +1,50 @@
 use crate::utils::{parse_envvar, parse_mapdir};
 use anyhow::Result;
 use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufWriter;
 use std::path::PathBuf;
+use std::str::FromStr;
 use wasmer::{AsStoreMut, FunctionEnv, Instance, Module, RuntimeError, Value};
 use wasmer_wasi::{
-    get_wasi_versions, import_object_for_all_wasi_versions, is_wasix_module, WasiEnv, WasiError,
-    WasiState, WasiVersion,
+    get_wasi_versions, import_object_for_all_wasi_versions, is_wasix_module, Deterministic,
+    ProfileError, SandboxProfile, StraceFormat, SyscallFamily, SyscallJournal, SyscallTracer,
+    WasiEnv, WasiError, WasiState, WasiVersion,
 };
 
 use clap::Parser;
 
+/// Loads a sandbox profile (boxed, as it is large compared to the other
+/// options)
+fn parse_profile(path: &str) -> Result<Box<SandboxProfile>, ProfileError> {
+    SandboxProfile::from_file(path).map(Box::new)
+}
+
+/// The networking backend of the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkingBackend {
+    /// The sockets of the host
+    Host,
+    /// A TCP/IP stack in userspace for the raw and ICMP sockets and the
+    /// management of addresses and routes, the host sockets for the rest
+    Userspace,
+}
+
+impl FromStr for NetworkingBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "host" => Ok(Self::Host),
+            "userspace" => Ok(Self::Userspace),
+            _ => Err(format!(
+                "unknown networking backend `{}` (expected `host` or `userspace`)",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Parser, Clone, Default)]
 /// WASI Options
 pub struct Wasi {
@@ -48,6 +83,57 @@ pub struct Wasi {
     /// Require WASI modules to only import 1 version of WASI.
     #[clap(long = "deny-multiple-wasi-versions")]
     pub deny_multiple_wasi_versions: bool,
+
+    /// Load the permissions of the program from a sandbox profile (a TOML
+    /// file). The directories, environment variables and arguments given
+    /// on the command line are added to the ones of the profile
+    #[clap(
+        long = "profile",
+        name = "PROFILE",
+        parse(try_from_str = parse_profile)
+    )]
+    profile: Option<Box<SandboxProfile>>,
+
+    /// Record all the WASI syscalls of the program into a trace file
+    #[clap(long = "record", name = "RECORD_FILE", conflicts_with = "REPLAY_FILE")]
+    record: Option<PathBuf>,
+
+    /// Replay the WASI syscalls from a trace file made with `--record`
+    #[clap(long = "replay", name = "REPLAY_FILE")]
+    replay: Option<PathBuf>,
+
+    /// Run deterministically: the clocks are frozen, random numbers are
+    /// derived from the given seed, threads are disabled and NaNs are
+    /// canonicalized
+    #[clap(long = "deterministic", name = "SEED")]
+    deterministic: Option<u64>,
+
+    /// Print the WASI syscalls made by the program, like `strace`
+    #[clap(long = "strace")]
+    strace: bool,
+
+    /// Only trace the given families of syscalls (fs, clock, random, poll,
+    /// process, thread, net, tty, bus)
+    #[clap(
+        long = "strace-filter",
+        name = "FAMILIES",
+        requires = "strace",
+        use_value_delimiter = true
+    )]
+    strace_filter: Vec<SyscallFamily>,
+
+    /// Print the traced syscalls as JSON lines
+    #[clap(long = "strace-json", requires = "strace")]
+    strace_json: bool,
+
+    /// Write the traced syscalls to a file instead of stderr
+    #[clap(long = "strace-file", name = "STRACE_FILE", requires = "strace")]
+    strace_file: Option<PathBuf>,
+
+    /// The networking backend: `host` (the default) or `userspace`, which
+    /// needs the `userspace-networking` feature
+    #[clap(long = "net", name = "BACKEND")]
+    net: Option<NetworkingBackend>,
 }
 
 #[allow(dead_code)]
@@ -59,6 +145,18 @@ impl Wasi {
         get_wasi_versions(module, true)
     }
 
+    /// Checks if the module has to run deterministically
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic.is_some()
+    }
+
+    /// Gets the memory limit of the sandbox profile, in pages
+    pub fn memory_limit(&self) -> Option<u32> {
+        self.profile
+            .as_ref()
+            .and_then(|profile| profile.limits.memory_pages)
+    }
+
     /// Checks if a given module has any WASI imports at all.
     pub fn has_wasi_imports(module: &Module) -> bool {
         // Get the wasi version in non-strict mode, so no other imports
@@ -77,12 +175,53 @@ impl Wasi {
         let args = args.iter().cloned().map(|arg| arg.into_bytes());
 
         let mut wasi_state_builder = WasiState::new(program_name);
+        if let Some(profile) = self.profile.as_ref() {
+            wasi_state_builder.profile(profile)?;
+        }
         wasi_state_builder
             .args(args)
             .envs(self.env_vars.clone())
             .preopen_dirs(self.pre_opened_directories.clone())?
             .map_dirs(self.mapped_dirs.clone())?;
 
+        if let Some(seed) = self.deterministic {
+            wasi_state_builder.deterministic(Deterministic::new(seed));
+        }
+
+        if let Some(path) = self.record.as_ref() {
+            wasi_state_builder.journal(SyscallJournal::record_to_file(path)?);
+        } else if let Some(path) = self.replay.as_ref() {
+            wasi_state_builder.journal(SyscallJournal::replay_from_file(path)?);
+        }
+
+        if self.strace {
+            let mut tracer = match self.strace_file.as_ref() {
+                Some(path) => SyscallTracer::new(BufWriter::new(File::create(path)?)),
+                None => SyscallTracer::stderr(),
+            };
+            if self.strace_json {
+                tracer = tracer.format(StraceFormat::JsonLines);
+            }
+            if !self.strace_filter.is_empty() {
+                tracer = tracer.families(self.strace_filter.iter().copied());
+            }
+            wasi_state_builder.strace(tracer);
+        }
+
+        match self.net {
+            None | Some(NetworkingBackend::Host) => {}
+            #[cfg(feature = "userspace-networking")]
+            Some(NetworkingBackend::Userspace) => {
+                wasi_state_builder.networking(wasmer_wasi::UserspaceNetworking::default());
+            }
+            #[cfg(not(feature = "userspace-networking"))]
+            Some(NetworkingBackend::Userspace) => {
+                anyhow::bail!(
+                    "the userspace networking backend requires the `userspace-networking` feature"
+                );
+            }
+        }
+
         #[cfg(feature = "experimental-io-devices")]
         {
             if self.enable_experimental_io_devices {
@@ -11,6 +11,69 @@
 
 extern wasm_module_t* wasmer_object_module_new(wasm_store_t* store, const char* module_name) asm("wasmer_object_module_new");
 
+#ifdef WASMER_BUNDLE
+// The commands and the filesystem embedded with `--command` and `--fs`
+extern size_t WASMER_COMMANDS_LENGTH asm("WASMER_COMMANDS_LENGTH");
+extern char WASMER_COMMANDS_DATA asm("WASMER_COMMANDS_DATA");
+extern size_t WASMER_FILESYSTEM_LENGTH asm("WASMER_FILESYSTEM_LENGTH");
+extern char WASMER_FILESYSTEM_DATA asm("WASMER_FILESYSTEM_DATA");
+
+static uint64_t read_u64(const char *data) {
+  unsigned char bytes[8];
+  memcpy(bytes, data, 8);
+  uint64_t value = 0;
+  for (int i = 7; i >= 0; --i) {
+    value = (value << 8) | bytes[i];
+  }
+  return value;
+}
+
+static size_t align16(size_t offset) { return (offset + 15) & ~(size_t)15; }
+
+// Looks up the command `name`, returns NULL if there's no such command.
+static const char *find_command(const char *name, size_t *module_len) {
+  const char *data = &WASMER_COMMANDS_DATA;
+  size_t offset = 0;
+  while (offset < WASMER_COMMANDS_LENGTH) {
+    size_t name_len = read_u64(data + offset);
+    size_t len = read_u64(data + offset + 8);
+    const char *command_name = data + offset + 16;
+    const char *module_data = command_name + align16(name_len);
+    if (name_len == strlen(name) && memcmp(command_name, name, name_len) == 0) {
+      *module_len = len;
+      return module_data;
+    }
+    offset = (module_data - data) + align16(len);
+  }
+  return NULL;
+}
+
+static wasm_module_t *command_module_new(wasm_store_t *store,
+                                         const char *module_data,
+                                         size_t module_len) {
+  wasm_byte_vec_t module_byte_vec = {
+      .size = module_len,
+      .data = (char *)module_data,
+  };
+  return wasm_module_deserialize(store, &module_byte_vec);
+}
+
+static const char *basename_of(const char *path) {
+  const char *name = path;
+  for (const char *c = path; *c; ++c) {
+#ifdef _WIN32
+    if (*c == '\\') {
+      name = c + 1;
+    }
+#endif
+    if (*c == '/') {
+      name = c + 1;
+    }
+  }
+  return name;
+}
+#endif
+
 
 static void print_wasmer_error() {
   int error_len = wasmer_last_error_length();
@@ -47,8 +110,8 @@ static void pass_mapdir_arg(wasi_config_t *wasi_config, char *mapdir) {
 // We try to parse out `--dir` and `--mapdir` ahead of time and process those
 // specially. All other arguments are passed to the guest program.
 static void handle_arguments(wasi_config_t *wasi_config, int argc,
-                             char *argv[]) {
-  for (int i = 1; i < argc; ++i) {
+                             char *argv[], int first_arg) {
+  for (int i = first_arg; i < argc; ++i) {
     // We probably want special args like `--dir` and `--mapdir` to not be
     // passed directly
     if (strcmp(argv[i], "--dir") == 0) {
@@ -93,7 +156,31 @@ int main(int argc, char *argv[]) {
   wasm_engine_t *engine = wasm_engine_new_with_config(config);
   wasm_store_t *store = wasm_store_new(engine);
 
-  wasm_module_t *module = wasmer_object_module_new(store, "module");
+  const char *program_name = argv[0];
+  int first_arg = 1;
+  wasm_module_t *module = NULL;
+
+#ifdef WASMER_BUNDLE
+  // Run the command named like the executable, or the one given with
+  // `--command NAME`, and the main module otherwise
+  size_t module_len = 0;
+  const char *module_data = NULL;
+  if (argc > 2 && strcmp(argv[1], "--command") == 0) {
+    module_data = find_command(argv[2], &module_len);
+    if (!module_data) {
+      fprintf(stderr, "Unknown command `%s`\n", argv[2]);
+      return -1;
+    }
+    program_name = argv[2];
+    first_arg = 3;
+  } else {
+    module_data = find_command(basename_of(argv[0]), &module_len);
+  }
+  if (module_data) {
+    module = command_module_new(store, module_data, module_len);
+  } else
+#endif
+  module = wasmer_object_module_new(store, "module");
 
   if (!module) {
     fprintf(stderr, "Failed to create module\n");
@@ -105,8 +192,18 @@ int main(int argc, char *argv[]) {
   // Module.
 
 #ifdef WASI
-  wasi_config_t *wasi_config = wasi_config_new(argv[0]);
-  handle_arguments(wasi_config, argc, argv);
+  wasi_config_t *wasi_config = wasi_config_new(program_name);
+#ifdef WASMER_BUNDLE
+  if (WASMER_FILESYSTEM_LENGTH > 0 &&
+      !wasi_config_mount_tar(wasi_config, "/",
+                             (const uint8_t *)&WASMER_FILESYSTEM_DATA,
+                             WASMER_FILESYSTEM_LENGTH)) {
+    fprintf(stderr, "Failed to mount the embedded filesystem\n");
+    print_wasmer_error();
+    return -1;
+  }
+#endif
+  handle_arguments(wasi_config, argc, argv, first_arg);
 
   wasi_env_t *wasi_env = wasi_env_new(store, wasi_config);
   if (!wasi_env) {
@@ -29,6 +29,14 @@ pub struct WasmFeatures {
     #[clap(long = "enable-bulk-memory")]
     pub bulk_memory: bool,
 
+    /// Enable support for the relaxed SIMD proposal.
+    #[clap(long = "enable-relaxed-simd")]
+    pub relaxed_simd: bool,
+
+    /// Enable support for the extended constant expressions proposal.
+    #[clap(long = "enable-extended-const")]
+    pub extended_const: bool,
+
     /// Enable support for all pre-standard proposals.
     #[clap(long = "enable-all")]
     pub all: bool,
@@ -51,3 +59,22 @@ pub fn get_cache_dir() -> PathBuf {
         }
     }
 }
+
+/// Get the maximum size of the cache, in bytes, from the
+/// `WASMER_CACHE_MAX_SIZE` environment variable (a number of bytes, with an
+/// optional `K`, `M` or `G` suffix)
+pub fn get_cache_max_size() -> Option<u64> {
+    let size = env::var("WASMER_CACHE_MAX_SIZE").ok()?;
+    let size = size.trim();
+    let (digits, multiplier) = match size.char_indices().last()? {
+        (i, 'k') | (i, 'K') => (&size[..i], 1 << 10),
+        (i, 'm') | (i, 'M') => (&size[..i], 1 << 20),
+        (i, 'g') | (i, 'G') => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(multiplier))
+}
@@ -22,6 +22,28 @@ pub struct StoreOptions {
     #[cfg(feature = "compiler")]
     #[clap(flatten)]
     compiler: CompilerOptions,
+
+    /// Maximum size of the memories (set by the commands, not from the
+    /// command line)
+    #[clap(skip)]
+    memory_limit: Option<Pages>,
+}
+
+impl StoreOptions {
+    /// Limits the size of the memories of the modules
+    pub fn limit_memory(&mut self, limit: Pages) {
+        self.memory_limit = Some(limit);
+    }
+
+    fn new_store(&self, engine: Engine, target: &Target) -> Store {
+        match self.memory_limit {
+            Some(limit) => Store::new_with_tunables(
+                engine,
+                LimitingTunables::new(BaseTunables::for_target(target), limit),
+            ),
+            None => Store::new(engine),
+        }
+    }
 }
 
 #[cfg(feature = "compiler")]
@@ -45,6 +67,11 @@ pub struct CompilerOptions {
     #[cfg(any(feature = "singlepass", feature = "cranelift", feature = "llvm"))]
     enable_verifier: bool,
 
+    /// Enable NaN canonicalization, which makes floating point results
+    /// identical across architectures.
+    #[clap(long)]
+    canonicalize_nans: bool,
+
     /// LLVM debug directory, where IR and object files will be written to.
     #[cfg(feature = "llvm")]
     #[clap(long, parse(from_os_str))]
@@ -98,6 +125,12 @@ impl CompilerOptions {
         if self.features.reference_types || self.features.all {
             features.reference_types(true);
         }
+        if self.features.relaxed_simd || self.features.all {
+            features.relaxed_simd(true);
+        }
+        if self.features.extended_const || self.features.all {
+            features.extended_const(true);
+        }
         Ok(features)
     }
 
@@ -128,7 +161,7 @@ impl CompilerOptions {
     #[allow(unused_variables)]
     pub(crate) fn get_compiler_config(&self) -> Result<(Box<dyn CompilerConfig>, CompilerType)> {
         let compiler = self.get_compiler()?;
-        let compiler_config: Box<dyn CompilerConfig> = match compiler {
+        let mut compiler_config: Box<dyn CompilerConfig> = match compiler {
             CompilerType::Headless => bail!("The headless engine can't be chosen"),
             #[cfg(feature = "singlepass")]
             CompilerType::Singlepass => {
@@ -258,6 +291,11 @@ impl CompilerOptions {
             }
         };
 
+        #[allow(unreachable_code)]
+        if self.canonicalize_nans {
+            compiler_config.canonicalize_nans(true);
+        }
+
         #[allow(unreachable_code)]
         Ok((compiler_config, compiler))
     }
@@ -303,6 +341,11 @@ impl ToString for CompilerType {
 
 #[cfg(all(feature = "compiler"))]
 impl StoreOptions {
+    /// Enables NaN canonicalization in the compiler
+    pub fn canonicalize_nans(&mut self) {
+        self.compiler.canonicalize_nans = true;
+    }
+
     /// Gets the store for the host target, with the compiler name selected
     pub fn get_store(&self) -> Result<(Store, CompilerType)> {
         let target = Target::default();
@@ -312,8 +355,8 @@ impl StoreOptions {
     /// Gets the store for a given target, with the compiler name selected.
     pub fn get_store_for_target(&self, target: Target) -> Result<(Store, CompilerType)> {
         let (compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
-        let engine = self.get_engine_with_compiler(target, compiler_config)?;
-        let store = Store::new(engine);
+        let engine = self.get_engine_with_compiler(target.clone(), compiler_config)?;
+        let store = self.new_store(engine, &target);
         Ok((store, compiler_type))
     }
 
@@ -331,6 +374,10 @@ impl StoreOptions {
 // If we don't have a compiler, but we have an engine
 #[cfg(not(feature = "compiler"))]
 impl StoreOptions {
+    /// Enables NaN canonicalization in the compiler (a headless engine
+    /// does not compile anything, so there is nothing to do)
+    pub fn canonicalize_nans(&mut self) {}
+
     fn get_engine_headless(&self) -> Result<Engine> {
         let engine: Engine = wasmer_compiler::EngineBuilder::headless().engine();
         Ok(engine)
@@ -339,7 +386,7 @@ impl StoreOptions {
     /// Get the store (headless engine)
     pub fn get_store(&self) -> Result<(Store, CompilerType)> {
         let engine = self.get_engine_headless()?;
-        let store = Store::new(engine);
+        let store = self.new_store(engine, &Target::default());
         Ok((store, CompilerType::Headless))
     }
 }
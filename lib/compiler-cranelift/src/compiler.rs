@@ -21,9 +21,12 @@ use gimli::write::{Address, EhFrame, FrameTable};
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
+#[cfg(feature = "unwind")]
+use wasmer_compiler::FrameInstructions;
 use wasmer_compiler::{
-    Compiler, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader, ModuleMiddleware,
-    ModuleMiddlewareChain, ModuleTranslationState,
+    CompilationThreadPool, Compiler, FunctionBinaryReader, FunctionBodyData, FunctionCache,
+    MiddlewareBinaryReader, ModuleFunctionCache, ModuleMiddleware, ModuleMiddlewareChain,
+    ModuleTranslationState,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
@@ -37,12 +40,14 @@ use wasmer_types::{
 /// optimizing it and then translating to assembly.
 pub struct CraneliftCompiler {
     config: Cranelift,
+    pool: CompilationThreadPool,
 }
 
 impl CraneliftCompiler {
     /// Creates a new Cranelift compiler
     pub fn new(config: Cranelift) -> Self {
-        Self { config }
+        let pool = CompilationThreadPool::new(config.num_threads);
+        Self { config, pool }
     }
 
     /// Gets the WebAssembly features for this Compiler
@@ -57,6 +62,17 @@ impl Compiler for CraneliftCompiler {
         &self.config.middlewares
     }
 
+    fn canonicalizes_nans(&self) -> bool {
+        self.config.enable_nan_canonicalization
+    }
+
+    fn deterministic_id(&self) -> String {
+        format!(
+            "cranelift-opt_level={:?}-nan_canonicalization={}-pic={}",
+            self.config.opt_level, self.config.enable_nan_canonicalization, self.config.enable_pic
+        )
+    }
+
     /// Compile the module using Cranelift, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -65,6 +81,98 @@ impl Compiler for CraneliftCompiler {
         compile_info: &CompileModuleInfo,
         module_translation_state: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<Compilation, CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation_state,
+                &function_body_inputs.iter().collect::<Vec<_>>(),
+                self.config.function_cache.as_ref(),
+                true,
+            )
+        })
+    }
+
+    fn compile_module_with_function_cache(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        function_cache: &FunctionCache,
+    ) -> Result<Compilation, CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation_state,
+                &function_body_inputs.iter().collect::<Vec<_>>(),
+                Some(function_cache),
+                true,
+            )
+        })
+    }
+
+    fn precompile_functions(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: &[(LocalFunctionIndex, FunctionBodyData<'_>)],
+        function_cache: &FunctionCache,
+    ) -> Result<(), CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation_state,
+                &function_body_inputs
+                    .iter()
+                    .map(|(i, input)| (*i, input))
+                    .collect::<Vec<_>>(),
+                Some(function_cache),
+                false,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn function_cache(&self) -> Option<&FunctionCache> {
+        self.config.function_cache.as_ref()
+    }
+
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        body: &FunctionBodyData<'_>,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_module_in_current_pool(
+            target,
+            compile_info,
+            module_translation_state,
+            &[(index, body)],
+            self.config.function_cache.as_ref(),
+            false,
+        )
+    }
+}
+
+impl CraneliftCompiler {
+    /// Compiles the given functions of the module with the threads of the
+    /// current `rayon` pool, looking them up in `function_cache` first,
+    /// along with the trampolines of the module if `with_trampolines` is set.
+    fn compile_module_in_current_pool(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: &[(LocalFunctionIndex, &FunctionBodyData<'_>)],
+        function_cache: Option<&FunctionCache>,
+        with_trampolines: bool,
     ) -> Result<Compilation, CompileError> {
         let isa = self
             .config()
@@ -106,22 +214,20 @@ impl Compiler for CraneliftCompiler {
 
         let mut custom_sections = PrimaryMap::new();
 
-        #[cfg(not(feature = "rayon"))]
-        let mut func_translator = FuncTranslator::new();
-        #[cfg(not(feature = "rayon"))]
-        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
-            .into_iter()
-            .map(|(i, input)| {
+        let function_cache = ModuleFunctionCache::new(function_cache, self, target, compile_info);
+        let compile_function = |func_translator: &mut FuncTranslator,
+                                i: LocalFunctionIndex,
+                                input: &FunctionBodyData<'_>|
+         -> Result<_, CompileError> {
+            let (function, fde) = function_cache.get_or_compile(i, input, || {
                 let func_index = module.func_index(i);
                 let mut context = Context::new();
                 let mut func_env = FuncEnvironment::new(
                     isa.frontend_config(),
                     module,
                     &signatures,
-                    &memory_styles,
-                    &table_styles,
+                    memory_styles,
+                    table_styles,
                 );
                 context.func.name = get_function_name(func_index);
                 context.func.signature = signatures[module.functions[func_index]].clone();
@@ -133,7 +239,7 @@ impl Compiler for CraneliftCompiler {
                 reader.set_middleware_chain(
                     self.config
                         .middlewares
-                        .generate_function_middleware_chain(i),
+                        .generate_function_middleware_chain(module, i),
                 );
 
                 func_translator.translate(
@@ -153,14 +259,14 @@ impl Compiler for CraneliftCompiler {
                 let func_relocs = result
                     .buffer
                     .relocs()
-                    .into_iter()
+                    .iter()
                     .map(|r| mach_reloc_to_reloc(module, r))
                     .collect::<Vec<_>>();
 
                 let traps = result
                     .buffer
                     .traps()
-                    .into_iter()
+                    .iter()
                     .map(mach_trap_to_trap)
                     .collect::<Vec<_>>();
 
@@ -168,16 +274,16 @@ impl Compiler for CraneliftCompiler {
                     #[cfg(feature = "unwind")]
                     CraneliftUnwindInfo::Fde(fde) => {
                         if dwarf_frametable.is_some() {
-                            let fde = fde.to_fde(Address::Symbol {
-                                // The symbol is the kind of relocation.
-                                // "0" is used for functions
-                                symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                // We use the addend as a way to specify the
-                                // function index
-                                addend: i.index() as _,
-                            });
-                            // The unwind information is inserted into the dwarf section
-                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
+                            // The unwind information is inserted into the dwarf section,
+                            // the instructions of the FDE are cached with the function
+                            let frame =
+                                FrameInstructions::from_fde(fde.to_fde(Address::Constant(0)))
+                                    .ok_or_else(|| {
+                                        CompileError::Codegen(
+                                            "the unwind information can't be encoded".to_string(),
+                                        )
+                                    })?;
+                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(frame))
                         } else {
                             (None, None)
                         }
@@ -205,107 +311,38 @@ impl Compiler for CraneliftCompiler {
                     },
                     fde,
                 ))
-            })
+            })?;
+
+            // The FDE refers to the function by its index, so it is created
+            // after the lookup in the cache.
+            #[cfg(feature = "unwind")]
+            let fde = fde.map(|fde| {
+                fde.to_fde(Address::Symbol {
+                    // The symbol is the kind of relocation.
+                    // "0" is used for functions
+                    symbol: WriterRelocate::FUNCTION_SYMBOL,
+                    // We use the addend as a way to specify the
+                    // function index
+                    addend: i.index() as _,
+                })
+            });
+            Ok((function, fde))
+        };
+
+        #[cfg(not(feature = "rayon"))]
+        let mut func_translator = FuncTranslator::new();
+        #[cfg(not(feature = "rayon"))]
+        let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
+            .iter()
+            .map(|(i, input)| compile_function(&mut func_translator, *i, input))
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .unzip();
         #[cfg(feature = "rayon")]
         let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map_init(FuncTranslator::new, |func_translator, (i, input)| {
-                let func_index = module.func_index(*i);
-                let mut context = Context::new();
-                let mut func_env = FuncEnvironment::new(
-                    isa.frontend_config(),
-                    module,
-                    &signatures,
-                    memory_styles,
-                    table_styles,
-                );
-                context.func.name = get_function_name(func_index);
-                context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(
-                    self.config
-                        .middlewares
-                        .generate_function_middleware_chain(*i),
-                );
-
-                func_translator.translate(
-                    module_translation_state,
-                    &mut reader,
-                    &mut context.func,
-                    &mut func_env,
-                    *i,
-                )?;
-
-                let mut code_buf: Vec<u8> = Vec::new();
-                context
-                    .compile_and_emit(&*isa, &mut code_buf)
-                    .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
-
-                let result = context.mach_compile_result.as_ref().unwrap();
-                let func_relocs = result
-                    .buffer
-                    .relocs()
-                    .iter()
-                    .map(|r| mach_reloc_to_reloc(module, r))
-                    .collect::<Vec<_>>();
-
-                let traps = result
-                    .buffer
-                    .traps()
-                    .iter()
-                    .map(mach_trap_to_trap)
-                    .collect::<Vec<_>>();
-
-                let (unwind_info, fde) = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::Fde(fde) => {
-                        if dwarf_frametable.is_some() {
-                            let fde = fde.to_fde(Address::Symbol {
-                                // The symbol is the kind of relocation.
-                                // "0" is used for functions
-                                symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                // We use the addend as a way to specify the
-                                // function index
-                                addend: i.index() as _,
-                            });
-                            // The unwind information is inserted into the dwarf section
-                            (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
-                        } else {
-                            (None, None)
-                        }
-                    }
-                    #[cfg(feature = "unwind")]
-                    other => (other.maybe_into_to_windows_unwind(), None),
-
-                    // This is a bit hacky, but necessary since gimli is not
-                    // available when the "unwind" feature is disabled.
-                    #[cfg(not(feature = "unwind"))]
-                    other => (other.maybe_into_to_windows_unwind(), None::<()>),
-                };
-
-                let range = reader.range();
-                let address_map = get_function_address_map(&context, range, code_buf.len());
-
-                Ok((
-                    CompiledFunction {
-                        body: FunctionBody {
-                            body: code_buf,
-                            unwind_info,
-                        },
-                        relocations: func_relocs,
-                        frame_info: CompiledFunctionFrameInfo { address_map, traps },
-                    },
-                    fde,
-                ))
+                compile_function(func_translator, *i, input)
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
@@ -328,6 +365,16 @@ impl Compiler for CraneliftCompiler {
         #[cfg(not(feature = "unwind"))]
         let dwarf = None;
 
+        if !with_trampolines {
+            return Ok(Compilation::new(
+                functions.into_iter().collect(),
+                custom_sections,
+                PrimaryMap::new(),
+                PrimaryMap::new(),
+                dwarf,
+            ));
+        }
+
         // function call trampolines (only for local functions, by signature)
         #[cfg(not(feature = "rayon"))]
         let mut cx = FunctionBuilderContext::new();
@@ -3,7 +3,9 @@ use cranelift_codegen::isa::{lookup, TargetIsa};
 use cranelift_codegen::settings::{self, Configurable};
 use cranelift_codegen::CodegenResult;
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
+use wasmer_compiler::{
+    Compiler, CompilerConfig, Engine, EngineBuilder, FunctionCache, ModuleMiddleware,
+};
 use wasmer_types::{Architecture, CpuFeature, Target};
 
 // Runtime Environment
@@ -29,12 +31,15 @@ pub enum CraneliftOptLevel {
 /// consumed by `wasmer_engine::Engine::new`.
 #[derive(Debug, Clone)]
 pub struct Cranelift {
-    enable_nan_canonicalization: bool,
-    enable_verifier: bool,
-    enable_pic: bool,
-    opt_level: CraneliftOptLevel,
+    pub(crate) enable_nan_canonicalization: bool,
+    pub(crate) enable_verifier: bool,
+    pub(crate) enable_pic: bool,
+    pub(crate) opt_level: CraneliftOptLevel,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+    #[cfg_attr(not(feature = "rayon"), allow(dead_code))]
+    pub(crate) num_threads: Option<usize>,
+    pub(crate) function_cache: Option<FunctionCache>,
 }
 
 impl Cranelift {
@@ -47,6 +52,8 @@ impl Cranelift {
             opt_level: CraneliftOptLevel::Speed,
             enable_pic: false,
             middlewares: vec![],
+            num_threads: None,
+            function_cache: None,
         }
     }
 
@@ -65,6 +72,25 @@ impl Cranelift {
         self
     }
 
+    /// The number of threads used to compile the functions of a module.
+    /// The compiler creates its thread pool once and reuses it for every
+    /// module it compiles.
+    ///
+    /// By default the global thread pool of `rayon` is used, which has a
+    /// thread per CPU. Functions are compiled one after the other when the
+    /// `rayon` feature is disabled.
+    pub fn num_threads(&mut self, num_threads: usize) -> &mut Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Caches the code generated for each function in `cache`, so that
+    /// recompiling a module only compiles the functions that changed.
+    pub fn function_cache(&mut self, cache: FunctionCache) -> &mut Self {
+        self.function_cache = Some(cache);
+        self
+    }
+
     /// Generates the ISA for the provided target
     pub fn isa(&self, target: &Target) -> CodegenResult<Box<dyn TargetIsa>> {
         let mut builder =
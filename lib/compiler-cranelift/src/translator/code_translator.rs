@@ -1531,7 +1531,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             // to WASM using the less specific v128 type for certain operations and more specific
             // types (e.g. i8x16) for others.
         }
-        Operator::I8x16Swizzle => {
+        Operator::I8x16Swizzle | Operator::I8x16RelaxedSwizzle => {
             let (a, b) = pop2_with_bitcast(state, I8X16, builder);
             state.push1(builder.ins().swizzle(I8X16, a, b))
         }
@@ -1638,7 +1638,14 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let b_mod_bitwidth = builder.ins().band_imm(b, bitwidth - 1);
             state.push1(builder.ins().sshr(bitcast_a, b_mod_bitwidth))
         }
-        Operator::V128Bitselect => {
+        Operator::V128Bitselect
+        | Operator::I8x16LaneSelect
+        | Operator::I16x8LaneSelect
+        | Operator::I32x4LaneSelect
+        | Operator::I64x2LaneSelect => {
+            // The lane selects are allowed to only look at the top bit of each
+            // lane of the mask: selecting bit by bit is one of their valid
+            // implementations.
             let (a, b, c) = state.pop3();
             let bitcast_a = optionally_bitcast_vector(a, I8X16, builder);
             let bitcast_b = optionally_bitcast_vector(b, I8X16, builder);
@@ -1734,11 +1741,17 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
             state.push1(builder.ins().fdiv(a, b))
         }
-        Operator::F32x4Max | Operator::F64x2Max => {
+        Operator::F32x4Max
+        | Operator::F64x2Max
+        | Operator::F32x4RelaxedMax
+        | Operator::F64x2RelaxedMax => {
             let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
             state.push1(builder.ins().fmax(a, b))
         }
-        Operator::F32x4Min | Operator::F64x2Min => {
+        Operator::F32x4Min
+        | Operator::F64x2Min
+        | Operator::F32x4RelaxedMin
+        | Operator::F64x2RelaxedMin => {
             let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
             state.push1(builder.ins().fmin(a, b))
         }
@@ -1750,6 +1763,23 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
             state.push1(builder.ins().fmin_pseudo(a, b))
         }
+        Operator::F32x4Fma | Operator::F64x2Fma => {
+            // Relaxed SIMD allows not fusing the multiplication and the addition.
+            let (a, b, c) = state.pop3();
+            let a = optionally_bitcast_vector(a, type_of(op), builder);
+            let b = optionally_bitcast_vector(b, type_of(op), builder);
+            let c = optionally_bitcast_vector(c, type_of(op), builder);
+            let product = builder.ins().fmul(a, b);
+            state.push1(builder.ins().fadd(product, c))
+        }
+        Operator::F32x4Fms | Operator::F64x2Fms => {
+            let (a, b, c) = state.pop3();
+            let a = optionally_bitcast_vector(a, type_of(op), builder);
+            let b = optionally_bitcast_vector(b, type_of(op), builder);
+            let c = optionally_bitcast_vector(c, type_of(op), builder);
+            let product = builder.ins().fmul(a, b);
+            state.push1(builder.ins().fsub(c, product))
+        }
         Operator::F32x4Sqrt | Operator::F64x2Sqrt => {
             let a = pop1_with_bitcast(state, type_of(op), builder);
             state.push1(builder.ins().sqrt(a))
@@ -1787,11 +1817,11 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let a = pop1_with_bitcast(state, F64X2, builder);
             state.push1(builder.ins().fvdemote(a));
         }
-        Operator::I32x4TruncSatF32x4S => {
+        Operator::I32x4TruncSatF32x4S | Operator::I32x4RelaxedTruncSatF32x4S => {
             let a = pop1_with_bitcast(state, F32X4, builder);
             state.push1(builder.ins().fcvt_to_sint_sat(I32X4, a))
         }
-        Operator::I32x4TruncSatF64x2SZero => {
+        Operator::I32x4TruncSatF64x2SZero | Operator::I32x4RelaxedTruncSatF64x2SZero => {
             let a = pop1_with_bitcast(state, F64X2, builder);
             let converted_a = builder.ins().fcvt_to_sint_sat(I64X2, a);
             let handle = builder.func.dfg.constants.insert(vec![0u8; 16].into());
@@ -1799,11 +1829,11 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
 
             state.push1(builder.ins().snarrow(converted_a, zero));
         }
-        Operator::I32x4TruncSatF32x4U => {
+        Operator::I32x4TruncSatF32x4U | Operator::I32x4RelaxedTruncSatF32x4U => {
             let a = pop1_with_bitcast(state, F32X4, builder);
             state.push1(builder.ins().fcvt_to_uint_sat(I32X4, a))
         }
-        Operator::I32x4TruncSatF64x2UZero => {
+        Operator::I32x4TruncSatF64x2UZero | Operator::I32x4RelaxedTruncSatF64x2UZero => {
             let a = pop1_with_bitcast(state, F64X2, builder);
             let converted_a = builder.ins().fcvt_to_uint_sat(I64X2, a);
             let handle = builder.func.dfg.constants.insert(vec![0u8; 16].into());
@@ -2006,25 +2036,6 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => {
             return Err(wasm_unsupported!("proposed tail-call operator {:?}", op));
         }
-        Operator::I8x16RelaxedSwizzle
-        | Operator::I32x4RelaxedTruncSatF32x4S
-        | Operator::I32x4RelaxedTruncSatF32x4U
-        | Operator::I32x4RelaxedTruncSatF64x2SZero
-        | Operator::I32x4RelaxedTruncSatF64x2UZero
-        | Operator::F32x4Fma
-        | Operator::F32x4Fms
-        | Operator::F64x2Fma
-        | Operator::F64x2Fms
-        | Operator::I8x16LaneSelect
-        | Operator::I16x8LaneSelect
-        | Operator::I32x4LaneSelect
-        | Operator::I64x2LaneSelect
-        | Operator::F32x4RelaxedMin
-        | Operator::F32x4RelaxedMax
-        | Operator::F64x2RelaxedMin
-        | Operator::F64x2RelaxedMax => {
-            return Err(wasm_unsupported!("proposed relaxed-simd operator {:?}", op));
-        }
     };
     Ok(())
 }
@@ -2830,6 +2841,10 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::F32x4Max
         | Operator::F32x4PMin
         | Operator::F32x4PMax
+        | Operator::F32x4RelaxedMin
+        | Operator::F32x4RelaxedMax
+        | Operator::F32x4Fma
+        | Operator::F32x4Fms
         | Operator::F32x4ConvertI32x4S
         | Operator::F32x4ConvertI32x4U
         | Operator::F32x4Ceil
@@ -2857,6 +2872,10 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::F64x2Max
         | Operator::F64x2PMin
         | Operator::F64x2PMax
+        | Operator::F64x2RelaxedMin
+        | Operator::F64x2RelaxedMax
+        | Operator::F64x2Fma
+        | Operator::F64x2Fms
         | Operator::F64x2Ceil
         | Operator::F64x2Floor
         | Operator::F64x2Trunc
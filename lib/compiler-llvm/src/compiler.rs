@@ -1,4 +1,5 @@
 use crate::config::LLVM;
+use crate::object_file::CompiledFunction;
 use crate::trampoline::FuncTrampoline;
 use crate::translator::FuncTranslator;
 use crate::CompiledKind;
@@ -9,8 +10,12 @@ use inkwell::targets::FileType;
 use inkwell::DLLStorageClass;
 use rayon::iter::ParallelBridge;
 use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rkyv::{AlignedVec, Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, FunctionBodyData, ModuleMiddleware, ModuleTranslationState};
+use wasmer_compiler::{
+    CompilationThreadPool, Compiler, FunctionBodyData, FunctionCache, FunctionCacheData,
+    ModuleFunctionCache, ModuleMiddleware, ModuleTranslationState,
+};
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     Compilation, CompileError, CompileModuleInfo, CustomSection, CustomSectionProtection, Dwarf,
@@ -24,12 +29,14 @@ use wasmer_types::{
 /// optimizing it and then translating to assembly.
 pub struct LLVMCompiler {
     config: LLVM,
+    pool: CompilationThreadPool,
 }
 
 impl LLVMCompiler {
     /// Creates a new LLVM compiler
     pub fn new(config: LLVM) -> LLVMCompiler {
-        LLVMCompiler { config }
+        let pool = CompilationThreadPool::new(config.num_threads);
+        LLVMCompiler { config, pool }
     }
 
     /// Gets the config for this Compiler
@@ -38,6 +45,28 @@ impl LLVMCompiler {
     }
 }
 
+/// The custom sections of a function, which are cached along with it.
+#[derive(Clone, Default, Archive, RkyvDeserialize, RkyvSerialize)]
+struct FunctionSections {
+    custom_sections: PrimaryMap<SectionIndex, CustomSection>,
+    eh_frame_section_indices: Vec<SectionIndex>,
+}
+
+impl FunctionCacheData for FunctionSections {
+    fn encode(&self) -> Option<Vec<u8>> {
+        let bytes = rkyv::to_bytes::<_, 4096>(self).ok()?;
+        Some(bytes.to_vec())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        // The bytes might not be aligned for the archived types
+        let mut aligned = AlignedVec::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+        // Safe as the function cache only decodes bytes returned by `encode`
+        unsafe { rkyv::from_bytes_unchecked(aligned.as_slice()) }.ok()
+    }
+}
+
 struct ShortNames {}
 
 impl SymbolRegistry for ShortNames {
@@ -192,6 +221,17 @@ impl Compiler for LLVMCompiler {
         &self.config.middlewares
     }
 
+    fn canonicalizes_nans(&self) -> bool {
+        self.config.enable_nan_canonicalization
+    }
+
+    fn deterministic_id(&self) -> String {
+        format!(
+            "llvm-opt_level={:?}-nan_canonicalization={}-pic={}",
+            self.config.opt_level, self.config.enable_nan_canonicalization, self.config.is_pic
+        )
+    }
+
     fn experimental_native_compile_module<'data, 'module>(
         &self,
         target: &Target,
@@ -221,6 +261,98 @@ impl Compiler for LLVMCompiler {
         compile_info: &'module CompileModuleInfo,
         module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+    ) -> Result<Compilation, CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation,
+                &function_body_inputs.iter().collect::<Vec<_>>(),
+                self.config.function_cache.as_ref(),
+                true,
+            )
+        })
+    }
+
+    fn compile_module_with_function_cache<'data, 'module>(
+        &self,
+        target: &Target,
+        compile_info: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        function_cache: &FunctionCache,
+    ) -> Result<Compilation, CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation,
+                &function_body_inputs.iter().collect::<Vec<_>>(),
+                Some(function_cache),
+                true,
+            )
+        })
+    }
+
+    fn precompile_functions<'data, 'module>(
+        &self,
+        target: &Target,
+        compile_info: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: &[(LocalFunctionIndex, FunctionBodyData<'data>)],
+        function_cache: &FunctionCache,
+    ) -> Result<(), CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation,
+                &function_body_inputs
+                    .iter()
+                    .map(|(i, input)| (*i, input))
+                    .collect::<Vec<_>>(),
+                Some(function_cache),
+                false,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn function_cache(&self) -> Option<&FunctionCache> {
+        self.config.function_cache.as_ref()
+    }
+
+    fn compile_function<'data, 'module>(
+        &self,
+        target: &Target,
+        compile_info: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        body: &FunctionBodyData<'data>,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_module_in_current_pool(
+            target,
+            compile_info,
+            module_translation,
+            &[(index, body)],
+            self.config.function_cache.as_ref(),
+            false,
+        )
+    }
+}
+
+impl LLVMCompiler {
+    /// Compiles the given functions of the module with the threads of the
+    /// current `rayon` pool, looking them up in `function_cache` first,
+    /// along with the trampolines of the module if `with_trampolines` is set.
+    fn compile_module_in_current_pool(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: &[(LocalFunctionIndex, &FunctionBodyData<'_>)],
+        function_cache: Option<&FunctionCache>,
+        with_trampolines: bool,
     ) -> Result<Compilation, CompileError> {
         //let data = Arc::new(Mutex::new(0));
         let memory_styles = &compile_info.memory_styles;
@@ -233,9 +365,15 @@ impl Compiler for LLVMCompiler {
         let mut module_custom_sections = PrimaryMap::new();
         let mut frame_section_bytes = vec![];
         let mut frame_section_relocations = vec![];
+        // The callbacks must see every function, so nothing is cached when
+        // they are set.
+        let function_cache = ModuleFunctionCache::new(
+            function_cache.filter(|_| self.config.callbacks.is_none()),
+            self,
+            target,
+            compile_info,
+        );
         let functions = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map_init(
                 || {
@@ -245,16 +383,33 @@ impl Compiler for LLVMCompiler {
                 |func_translator, (i, input)| {
                     // TODO: remove (to serialize)
                     //let _data = data.lock().unwrap();
-                    func_translator.translate(
-                        module,
-                        module_translation,
-                        i,
-                        input,
-                        self.config(),
-                        memory_styles,
-                        table_styles,
-                        &ShortNames {},
-                    )
+                    let (compiled_function, sections) =
+                        function_cache.get_or_compile(*i, input, || {
+                            let compiled = func_translator.translate(
+                                module,
+                                module_translation,
+                                i,
+                                input,
+                                self.config(),
+                                memory_styles,
+                                table_styles,
+                                &ShortNames {},
+                            )?;
+                            let sections = FunctionSections {
+                                custom_sections: compiled.custom_sections,
+                                eh_frame_section_indices: compiled.eh_frame_section_indices,
+                            };
+                            Ok((compiled.compiled_function, Some(sections)))
+                        })?;
+                    let FunctionSections {
+                        custom_sections,
+                        eh_frame_section_indices,
+                    } = sections.unwrap_or_default();
+                    Ok(CompiledFunction {
+                        compiled_function,
+                        custom_sections,
+                        eh_frame_section_indices,
+                    })
                 },
             )
             .collect::<Result<Vec<_>, CompileError>>()?
@@ -327,6 +482,16 @@ impl Compiler for LLVMCompiler {
             None
         };
 
+        if !with_trampolines {
+            return Ok(Compilation::new(
+                functions,
+                module_custom_sections,
+                PrimaryMap::new(),
+                PrimaryMap::new(),
+                dwarf,
+            ));
+        }
+
         let function_call_trampolines = module
             .signatures
             .values()
@@ -8,7 +8,9 @@ use itertools::Itertools;
 use std::fmt::Debug;
 use std::sync::Arc;
 use target_lexicon::Architecture;
-use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
+use wasmer_compiler::{
+    Compiler, CompilerConfig, Engine, EngineBuilder, FunctionCache, ModuleMiddleware,
+};
 use wasmer_types::{FunctionType, LocalFunctionIndex, Target, Triple};
 
 /// The InkWell ModuleInfo type
@@ -42,10 +44,12 @@ pub struct LLVM {
     pub(crate) enable_nan_canonicalization: bool,
     pub(crate) enable_verifier: bool,
     pub(crate) opt_level: LLVMOptLevel,
-    is_pic: bool,
+    pub(crate) is_pic: bool,
     pub(crate) callbacks: Option<Arc<dyn LLVMCallbacks>>,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+    pub(crate) num_threads: Option<usize>,
+    pub(crate) function_cache: Option<FunctionCache>,
 }
 
 impl LLVM {
@@ -59,6 +63,8 @@ impl LLVM {
             is_pic: false,
             callbacks: None,
             middlewares: vec![],
+            num_threads: None,
+            function_cache: None,
         }
     }
 
@@ -75,6 +81,27 @@ impl LLVM {
         self
     }
 
+    /// The number of threads used to compile the functions of a module.
+    /// The compiler creates its thread pool once and reuses it for every
+    /// module it compiles.
+    ///
+    /// By default the global thread pool of `rayon` is used, which has a
+    /// thread per CPU.
+    pub fn num_threads(&mut self, num_threads: usize) -> &mut Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Caches the code generated for each function in `cache`, so that
+    /// recompiling a module only compiles the functions that changed.
+    ///
+    /// The cache is not used when callbacks are set, since they would not
+    /// be called for the functions found in the cache.
+    pub fn function_cache(&mut self, cache: FunctionCache) -> &mut Self {
+        self.function_cache = Some(cache);
+        self
+    }
+
     fn reloc_mode(&self) -> RelocMode {
         if self.is_pic {
             RelocMode::PIC
@@ -140,7 +140,7 @@ impl FuncTranslator {
         reader.set_middleware_chain(
             config
                 .middlewares
-                .generate_function_middleware_chain(*local_func_index),
+                .generate_function_middleware_chain(wasm_module, *local_func_index),
         );
 
         let mut params = vec![];
@@ -2071,6 +2071,16 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             format!("global {}", global_index.as_u32()),
                             value.as_instruction_value().unwrap(),
                         );
+                        // The host changes volatile globals behind the back
+                        // of the function, their loads must not be hoisted
+                        // out of the loops that check them.
+                        if self.wasm_module.volatile_globals.contains(&global_index) {
+                            value
+                                .as_instruction_value()
+                                .unwrap()
+                                .set_volatile(true)
+                                .unwrap();
+                        }
                         self.state.push1(value);
                     }
                 }
@@ -2098,6 +2108,9 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             format!("global {}", global_index.as_u32()),
                             store,
                         );
+                        if self.wasm_module.volatile_globals.contains(&global_index) {
+                            store.set_volatile(true).unwrap();
+                        }
                     }
                 }
             }
@@ -3244,7 +3257,13 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_and(v1, v2, "");
                 self.state.push1(res);
             }
-            Operator::V128Bitselect => {
+            Operator::V128Bitselect
+            | Operator::I8x16LaneSelect
+            | Operator::I16x8LaneSelect
+            | Operator::I32x4LaneSelect
+            | Operator::I64x2LaneSelect => {
+                // Selecting bit by bit is a valid implementation of the lane
+                // selects of relaxed SIMD.
                 let ((v1, i1), (v2, i2), (cond, cond_info)) = self.state.pop3_extra()?;
                 let v1 = self.apply_pending_canonicalization(v1, i1);
                 let v2 = self.apply_pending_canonicalization(v2, i2);
@@ -4298,6 +4317,74 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     (i1.strip_pending() & i2.strip_pending()) | ExtraInfo::pending_f64_nan(),
                 );
             }
+            Operator::F32x4Fma | Operator::F32x4Fms | Operator::F64x2Fma | Operator::F64x2Fms => {
+                // Relaxed SIMD allows not fusing the multiplication and the
+                // addition.
+                let ((v1, i1), (v2, i2), (v3, i3)) = self.state.pop3_extra()?;
+                let is_fms = matches!(op, Operator::F32x4Fms | Operator::F64x2Fms);
+                let ((v1, i1), (v2, i2), (v3, i3), mul, add, sub, pending_nan) =
+                    if matches!(op, Operator::F32x4Fma | Operator::F32x4Fms) {
+                        (
+                            self.v128_into_f32x4(v1, i1),
+                            self.v128_into_f32x4(v2, i2),
+                            self.v128_into_f32x4(v3, i3),
+                            self.intrinsics.mul_f32x4,
+                            self.intrinsics.add_f32x4,
+                            self.intrinsics.sub_f32x4,
+                            ExtraInfo::pending_f32_nan(),
+                        )
+                    } else {
+                        (
+                            self.v128_into_f64x2(v1, i1),
+                            self.v128_into_f64x2(v2, i2),
+                            self.v128_into_f64x2(v3, i3),
+                            self.intrinsics.mul_f64x2,
+                            self.intrinsics.add_f64x2,
+                            self.intrinsics.sub_f64x2,
+                            ExtraInfo::pending_f64_nan(),
+                        )
+                    };
+                let product = self
+                    .builder
+                    .build_call(
+                        mul,
+                        &[
+                            v1.into(),
+                            v2.into(),
+                            self.intrinsics.fp_rounding_md,
+                            self.intrinsics.fp_exception_md,
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                let (f, lhs, rhs): (_, BasicMetadataValueEnum, BasicMetadataValueEnum) = if is_fms {
+                    (sub, v3.into(), product.into())
+                } else {
+                    (add, product.into(), v3.into())
+                };
+                let res = self
+                    .builder
+                    .build_call(
+                        f,
+                        &[
+                            lhs,
+                            rhs,
+                            self.intrinsics.fp_rounding_md,
+                            self.intrinsics.fp_exception_md,
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap();
+                let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
+                self.state.push1_extra(
+                    res,
+                    (i1.strip_pending() & i2.strip_pending() & i3.strip_pending()) | pending_nan,
+                );
+            }
             Operator::F32Div => {
                 let (v1, v2) = self.state.pop2()?;
                 let (v1, v2) = (v1.into_float_value(), v2.into_float_value());
@@ -4648,7 +4735,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
                 self.state.push1(res);
             }
-            Operator::F32x4Min => {
+            Operator::F32x4Min | Operator::F32x4RelaxedMin => {
                 // This implements the same logic as LLVM's @llvm.minimum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -4774,7 +4861,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
-            Operator::F64x2Min => {
+            Operator::F64x2Min | Operator::F64x2RelaxedMin => {
                 // This implements the same logic as LLVM's @llvm.minimum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -5118,7 +5205,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
                 self.state.push1(res);
             }
-            Operator::F32x4Max => {
+            Operator::F32x4Max | Operator::F32x4RelaxedMax => {
                 // This implements the same logic as LLVM's @llvm.maximum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -5245,7 +5332,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
-            Operator::F64x2Max => {
+            Operator::F64x2Max | Operator::F64x2RelaxedMax => {
                 // This implements the same logic as LLVM's @llvm.maximum
                 // intrinsic would, but x86 lowering of that intrinsic
                 // encounters a fatal error in LLVM 11.
@@ -7035,7 +7122,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
-            Operator::I32x4TruncSatF32x4S => {
+            Operator::I32x4TruncSatF32x4S | Operator::I32x4RelaxedTruncSatF32x4S => {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let v = v.into_int_value();
@@ -7050,7 +7137,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
                 self.state.push1(res);
             }
-            Operator::I32x4TruncSatF32x4U => {
+            Operator::I32x4TruncSatF32x4U | Operator::I32x4RelaxedTruncSatF32x4U => {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let v = v.into_int_value();
@@ -7065,13 +7152,18 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
                 self.state.push1(res);
             }
-            Operator::I32x4TruncSatF64x2SZero | Operator::I32x4TruncSatF64x2UZero => {
+            Operator::I32x4TruncSatF64x2SZero
+            | Operator::I32x4TruncSatF64x2UZero
+            | Operator::I32x4RelaxedTruncSatF64x2SZero
+            | Operator::I32x4RelaxedTruncSatF64x2UZero => {
                 let ((min, max), (cmp_min, cmp_max)) = match op {
-                    Operator::I32x4TruncSatF64x2SZero => (
+                    Operator::I32x4TruncSatF64x2SZero
+                    | Operator::I32x4RelaxedTruncSatF64x2SZero => (
                         (std::i32::MIN as u64, std::i32::MAX as u64),
                         (LEF64_GEQ_I32_MIN, GEF64_LEQ_I32_MAX),
                     ),
-                    Operator::I32x4TruncSatF64x2UZero => (
+                    Operator::I32x4TruncSatF64x2UZero
+                    | Operator::I32x4RelaxedTruncSatF64x2UZero => (
                         (std::u32::MIN as u64, std::u32::MAX as u64),
                         (LEF64_GEQ_U32_MIN, GEF64_LEQ_U32_MAX),
                     ),
@@ -8594,7 +8686,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 };
                 self.state.push1_extra(res, info);
             }
-            Operator::I8x16Swizzle => {
+            Operator::I8x16Swizzle | Operator::I8x16RelaxedSwizzle => {
                 let ((v1, i1), (v2, i2)) = self.state.pop2_extra()?;
                 let v1 = self.apply_pending_canonicalization(v1, i1);
                 let v1 = self
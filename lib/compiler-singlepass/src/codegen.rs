@@ -1,13 +1,9 @@
 use crate::address_map::get_function_address_map;
 use crate::codegen_error;
-#[cfg(feature = "unwind")]
-use crate::dwarf::WriterRelocate;
 use crate::location::{Location, Reg};
 use crate::machine::{CodegenError, Label, Machine, MachineStackOffset, NATIVE_PAGE_SIZE};
 use crate::unwind::UnwindFrame;
 use crate::{common_decl::*, config::Singlepass};
-#[cfg(feature = "unwind")]
-use gimli::write::Address;
 use smallvec::{smallvec, SmallVec};
 use std::cmp;
 use std::iter;
@@ -5949,10 +5945,7 @@ impl<'a, M: Machine> FuncGen<'a, M> {
             CallingConvention::SystemV | CallingConvention::AppleAarch64 => {
                 let unwind = self.machine.gen_dwarf_unwind_info(body_len);
                 if let Some(unwind) = unwind {
-                    fde = Some(unwind.to_fde(Address::Symbol {
-                        symbol: WriterRelocate::FUNCTION_SYMBOL,
-                        addend: self.fsm.local_function_id as _,
-                    }));
+                    fde = Some(unwind.into_frame());
                     unwind_info = Some(CompiledFunctionUnwindInfo::Dwarf);
                 }
             }
@@ -13,15 +13,16 @@ use crate::machine::{
 use crate::machine_arm64::MachineARM64;
 use crate::machine_x64::MachineX86_64;
 #[cfg(feature = "unwind")]
-use crate::unwind::{create_systemv_cie, UnwindFrame};
+use crate::unwind::create_systemv_cie;
 #[cfg(feature = "unwind")]
-use gimli::write::{EhFrame, FrameTable};
+use gimli::write::{Address, EhFrame, FrameTable};
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::sync::Arc;
 use wasmer_compiler::{
-    Compiler, CompilerConfig, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader,
-    ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+    CompilationThreadPool, Compiler, CompilerConfig, FunctionBinaryReader, FunctionBodyData,
+    FunctionCache, MiddlewareBinaryReader, ModuleFunctionCache, ModuleMiddleware,
+    ModuleMiddlewareChain, ModuleTranslationState,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
@@ -41,12 +42,14 @@ impl From<CodegenError> for CompileError {
 /// It does the compilation in one pass
 pub struct SinglepassCompiler {
     config: Singlepass,
+    pool: CompilationThreadPool,
 }
 
 impl SinglepassCompiler {
     /// Creates a new Singlepass compiler
     pub fn new(config: Singlepass) -> Self {
-        Self { config }
+        let pool = CompilationThreadPool::new(config.num_threads);
+        Self { config, pool }
     }
 
     /// Gets the config for this Compiler
@@ -61,14 +64,120 @@ impl Compiler for SinglepassCompiler {
         &self.config.middlewares
     }
 
+    fn canonicalizes_nans(&self) -> bool {
+        self.config.enable_nan_canonicalization
+    }
+
+    fn deterministic_id(&self) -> String {
+        format!(
+            "singlepass-nan_canonicalization={}",
+            self.config.enable_nan_canonicalization
+        )
+    }
+
     /// Compile the module using Singlepass, producing a compilation result with
     /// associated relocations.
     fn compile_module(
         &self,
         target: &Target,
         compile_info: &CompileModuleInfo,
-        _module_translation: &ModuleTranslationState,
+        module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<Compilation, CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation,
+                function_body_inputs.iter().collect(),
+                self.config.function_cache.as_ref(),
+                true,
+            )
+        })
+    }
+
+    fn compile_module_with_function_cache(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        function_cache: &FunctionCache,
+    ) -> Result<Compilation, CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation,
+                function_body_inputs.iter().collect(),
+                Some(function_cache),
+                true,
+            )
+        })
+    }
+
+    fn precompile_functions(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: &[(LocalFunctionIndex, FunctionBodyData<'_>)],
+        function_cache: &FunctionCache,
+    ) -> Result<(), CompileError> {
+        self.pool.install(|| {
+            self.compile_module_in_current_pool(
+                target,
+                compile_info,
+                module_translation,
+                function_body_inputs
+                    .iter()
+                    .map(|(i, input)| (*i, input))
+                    .collect(),
+                Some(function_cache),
+                false,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn function_cache(&self) -> Option<&FunctionCache> {
+        self.config.function_cache.as_ref()
+    }
+
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        body: &FunctionBodyData<'_>,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_module_in_current_pool(
+            target,
+            compile_info,
+            module_translation,
+            vec![(index, body)],
+            self.config.function_cache.as_ref(),
+            false,
+        )
+    }
+}
+
+impl SinglepassCompiler {
+    /// Compiles the given functions of the module with the threads of the
+    /// current `rayon` pool, looking them up in `function_cache` first,
+    /// along with the trampolines of the module if `with_trampolines` is set.
+    ///
+    /// The trampolines to call imported functions are always generated, as
+    /// the functions refer to them.
+    fn compile_module_in_current_pool(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        function_body_inputs: Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>,
+        function_cache: Option<&FunctionCache>,
+        with_trampolines: bool,
     ) -> Result<Compilation, CompileError> {
         match target.triple().architecture {
             Architecture::X86_64 => {}
@@ -148,81 +257,119 @@ impl Compiler for SinglepassCompiler {
             .collect::<Vec<_>>()
             .into_iter()
             .collect();
+        let function_cache = ModuleFunctionCache::new(function_cache, self, target, compile_info);
         let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .into_par_iter_if_rayon()
-            .map(|(i, input)| {
-                let middleware_chain = self
-                    .config
-                    .middlewares
-                    .generate_function_middleware_chain(i);
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(middleware_chain);
-
-                // This local list excludes arguments.
-                let mut locals = vec![];
-                let num_locals = reader.read_local_count()?;
-                for _ in 0..num_locals {
-                    let (count, ty) = reader.read_local_decl()?;
-                    for _ in 0..count {
-                        locals.push(ty);
-                    }
-                }
+            .map(|(i, input)| -> Result<_, CompileError> {
+                let (function, frame) = function_cache.get_or_compile(i, input, || {
+                    let middleware_chain = self
+                        .config
+                        .middlewares
+                        .generate_function_middleware_chain(module, i);
+                    let mut reader =
+                        MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+                    reader.set_middleware_chain(middleware_chain);
 
-                match target.triple().architecture {
-                    Architecture::X86_64 => {
-                        let machine = MachineX86_64::new(simd_arch);
-                        let mut generator = FuncGen::new(
-                            module,
-                            &self.config,
-                            &vmoffsets,
-                            memory_styles,
-                            table_styles,
-                            i,
-                            &locals,
-                            machine,
-                            calling_convention,
-                        )
-                        .map_err(to_compile_error)?;
-                        while generator.has_control_frames() {
-                            generator.set_srcloc(reader.original_position() as u32);
-                            let op = reader.read_operator()?;
-                            generator.feed_operator(op).map_err(to_compile_error)?;
+                    // This local list excludes arguments.
+                    let mut locals = vec![];
+                    let num_locals = reader.read_local_count()?;
+                    for _ in 0..num_locals {
+                        let (count, ty) = reader.read_local_decl()?;
+                        for _ in 0..count {
+                            locals.push(ty);
                         }
-
-                        generator.finalize(input).map_err(to_compile_error)
                     }
-                    Architecture::Aarch64(_) => {
-                        let machine = MachineARM64::new();
-                        let mut generator = FuncGen::new(
-                            module,
-                            &self.config,
-                            &vmoffsets,
-                            memory_styles,
-                            table_styles,
-                            i,
-                            &locals,
-                            machine,
-                            calling_convention,
-                        )
-                        .map_err(to_compile_error)?;
-                        while generator.has_control_frames() {
-                            generator.set_srcloc(reader.original_position() as u32);
-                            let op = reader.read_operator()?;
-                            generator.feed_operator(op).map_err(to_compile_error)?;
+
+                    match target.triple().architecture {
+                        Architecture::X86_64 => {
+                            let machine = MachineX86_64::new(simd_arch);
+                            let mut generator = FuncGen::new(
+                                module,
+                                &self.config,
+                                &vmoffsets,
+                                memory_styles,
+                                table_styles,
+                                i,
+                                &locals,
+                                machine,
+                                calling_convention,
+                            )
+                            .map_err(to_compile_error)?;
+                            while generator.has_control_frames() {
+                                generator.set_srcloc(reader.original_position() as u32);
+                                let op = reader.read_operator()?;
+                                generator.feed_operator(op).map_err(to_compile_error)?;
+                            }
+
+                            generator.finalize(input).map_err(to_compile_error)
                         }
+                        Architecture::Aarch64(_) => {
+                            let machine = MachineARM64::new();
+                            let mut generator = FuncGen::new(
+                                module,
+                                &self.config,
+                                &vmoffsets,
+                                memory_styles,
+                                table_styles,
+                                i,
+                                &locals,
+                                machine,
+                                calling_convention,
+                            )
+                            .map_err(to_compile_error)?;
+                            while generator.has_control_frames() {
+                                generator.set_srcloc(reader.original_position() as u32);
+                                let op = reader.read_operator()?;
+                                generator.feed_operator(op).map_err(to_compile_error)?;
+                            }
 
-                        generator.finalize(input).map_err(to_compile_error)
+                            generator.finalize(input).map_err(to_compile_error)
+                        }
+                        _ => unimplemented!(),
                     }
-                    _ => unimplemented!(),
-                }
+                })?;
+                #[cfg(feature = "unwind")]
+                let fde = frame.map(|frame| {
+                    frame.to_fde(Address::Symbol {
+                        symbol: WriterRelocate::FUNCTION_SYMBOL,
+                        addend: i.index() as _,
+                    })
+                });
+                #[cfg(not(feature = "unwind"))]
+                let fde = frame;
+                Ok((function, fde))
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .unzip();
 
+        #[cfg(feature = "unwind")]
+        let dwarf = if let Some((mut dwarf_frametable, cie_id)) = dwarf_frametable {
+            for fde in fdes.into_iter().flatten() {
+                dwarf_frametable.add_fde(cie_id, fde);
+            }
+            let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
+            dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
+
+            let eh_frame_section = eh_frame.0.into_section();
+            custom_sections.push(eh_frame_section);
+            Some(Dwarf::new(SectionIndex::new(custom_sections.len() - 1)))
+        } else {
+            None
+        };
+        #[cfg(not(feature = "unwind"))]
+        let dwarf = None;
+
+        if !with_trampolines {
+            return Ok(Compilation::new(
+                functions.into_iter().collect(),
+                custom_sections,
+                PrimaryMap::new(),
+                PrimaryMap::new(),
+                dwarf,
+            ));
+        }
+
         let function_call_trampolines = module
             .signatures
             .values()
@@ -249,25 +396,6 @@ impl Compiler for SinglepassCompiler {
             .into_iter()
             .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
 
-        #[cfg(feature = "unwind")]
-        let dwarf = if let Some((mut dwarf_frametable, cie_id)) = dwarf_frametable {
-            for fde in fdes.into_iter().flatten() {
-                match fde {
-                    UnwindFrame::SystemV(fde) => dwarf_frametable.add_fde(cie_id, fde),
-                }
-            }
-            let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
-            dwarf_frametable.write_eh_frame(&mut eh_frame).unwrap();
-
-            let eh_frame_section = eh_frame.0.into_section();
-            custom_sections.push(eh_frame_section);
-            Some(Dwarf::new(SectionIndex::new(custom_sections.len() - 1)))
-        } else {
-            None
-        };
-        #[cfg(not(feature = "unwind"))]
-        let dwarf = None;
-
         Ok(Compilation::new(
             functions.into_iter().collect(),
             custom_sections,
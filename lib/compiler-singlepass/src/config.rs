@@ -3,7 +3,9 @@
 
 use crate::compiler::SinglepassCompiler;
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, CompilerConfig, Engine, EngineBuilder, ModuleMiddleware};
+use wasmer_compiler::{
+    Compiler, CompilerConfig, Engine, EngineBuilder, FunctionCache, ModuleMiddleware,
+};
 use wasmer_types::{CpuFeature, Features, Target};
 
 #[derive(Debug, Clone)]
@@ -11,6 +13,8 @@ pub struct Singlepass {
     pub(crate) enable_nan_canonicalization: bool,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
+    pub(crate) num_threads: Option<usize>,
+    pub(crate) function_cache: Option<FunctionCache>,
 }
 
 impl Singlepass {
@@ -20,6 +24,8 @@ impl Singlepass {
         Self {
             enable_nan_canonicalization: true,
             middlewares: vec![],
+            num_threads: None,
+            function_cache: None,
         }
     }
 
@@ -31,6 +37,25 @@ impl Singlepass {
         self.enable_nan_canonicalization = enable;
         self
     }
+
+    /// The number of threads used to compile the functions of a module.
+    /// The compiler creates its thread pool once and reuses it for every
+    /// module it compiles.
+    ///
+    /// By default the global thread pool of `rayon` is used, which has a
+    /// thread per CPU. Functions are compiled one after the other when the
+    /// `rayon` feature is disabled.
+    pub fn num_threads(&mut self, num_threads: usize) -> &mut Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Caches the code generated for each function in `cache`, so that
+    /// recompiling a module only compiles the functions that changed.
+    pub fn function_cache(&mut self, cache: FunctionCache) -> &mut Self {
+        self.function_cache = Some(cache);
+        self
+    }
 }
 
 impl CompilerConfig for Singlepass {
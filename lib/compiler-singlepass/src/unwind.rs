@@ -1,9 +1,11 @@
 #[cfg(feature = "unwind")]
-use gimli::write::{Address, CallFrameInstruction, CommonInformationEntry, FrameDescriptionEntry};
+use gimli::write::{CallFrameInstruction, CommonInformationEntry};
 #[cfg(feature = "unwind")]
 use gimli::{AArch64, Encoding, Format, X86_64};
 use std::fmt::Debug;
 #[cfg(feature = "unwind")]
+use wasmer_compiler::FrameInstructions;
+#[cfg(feature = "unwind")]
 use wasmer_types::Architecture;
 
 #[derive(Clone, Debug)]
@@ -23,23 +25,23 @@ pub struct UnwindInstructions {
     pub len: u32,
 }
 
+/// The unwind information that is cached with a function, which is turned
+/// into a `FrameDescriptionEntry` once the function has an address.
 #[cfg(feature = "unwind")]
-pub enum UnwindFrame {
-    SystemV(gimli::write::FrameDescriptionEntry),
-}
+pub type UnwindFrame = FrameInstructions;
 
 #[cfg(not(feature = "unwind"))]
-pub type UnwindFrame = u32;
+pub type UnwindFrame = ();
 
 #[cfg(feature = "unwind")]
 impl UnwindInstructions {
-    /// Converts the unwind information into a `FrameDescriptionEntry`.
-    pub fn to_fde(&self, address: Address) -> UnwindFrame {
-        let mut fde = FrameDescriptionEntry::new(address, self.len);
-        for (offset, inst) in &self.instructions {
-            fde.add_instruction(*offset, inst.clone());
+    /// Converts the unwind information into the instructions of a
+    /// `FrameDescriptionEntry`.
+    pub fn into_frame(self) -> UnwindFrame {
+        FrameInstructions {
+            instructions: self.instructions,
+            len: self.len,
         }
-        UnwindFrame::SystemV(fde)
     }
 }
 
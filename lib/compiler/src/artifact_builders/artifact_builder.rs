@@ -6,14 +6,19 @@ use super::trampoline::{libcall_trampoline_len, make_libcall_trampolines};
 use crate::ArtifactCreate;
 use crate::EngineInner;
 use crate::Features;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use crate::FunctionCache;
+#[cfg(feature = "compiler")]
+use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
 use crate::{ModuleEnvironment, ModuleMiddlewareChain};
 use enumset::EnumSet;
 use std::mem;
 use wasmer_types::entity::PrimaryMap;
-#[cfg(feature = "compiler")]
-use wasmer_types::CompileModuleInfo;
 use wasmer_types::MetadataHeader;
 use wasmer_types::SerializeError;
+#[cfg(feature = "compiler")]
+use wasmer_types::{Compilation, CompileModuleInfo};
 use wasmer_types::{
     CompileError, CpuFeature, CustomSection, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex,
     MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex,
@@ -45,6 +50,81 @@ impl ArtifactBuild {
         target: &Target,
         memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<Self, CompileError> {
+        Self::translate_and_compile(
+            inner_engine,
+            data,
+            target,
+            memory_styles,
+            table_styles,
+            None,
+            |compiler, compile_info, module_translation, function_body_inputs| {
+                compiler.compile_module(
+                    target,
+                    compile_info,
+                    module_translation,
+                    function_body_inputs,
+                )
+            },
+        )
+    }
+
+    /// Compile a data buffer into a `ArtifactBuild`, looking up its
+    /// functions in `function_cache` before compiling them.
+    ///
+    /// If the middlewares of the compiler were already applied to the
+    /// sections of the module before its code section, that `module` is
+    /// compiled instead of applying them again, so that the functions
+    /// compiled into `function_cache` with it match.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_with_function_cache(
+        inner_engine: &mut EngineInner,
+        data: &[u8],
+        target: &Target,
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+        function_cache: &FunctionCache,
+        module: Option<ModuleInfo>,
+    ) -> Result<Self, CompileError> {
+        Self::translate_and_compile(
+            inner_engine,
+            data,
+            target,
+            memory_styles,
+            table_styles,
+            module,
+            |compiler, compile_info, module_translation, function_body_inputs| {
+                compiler.compile_module_with_function_cache(
+                    target,
+                    compile_info,
+                    module_translation,
+                    function_body_inputs,
+                    function_cache,
+                )
+            },
+        )
+    }
+
+    /// Translates a data buffer and compiles the functions of the module
+    /// with `compile`.
+    ///
+    /// `prepared` is the module translated up to its code section, with the
+    /// middlewares already applied, if any.
+    #[cfg(feature = "compiler")]
+    fn translate_and_compile(
+        inner_engine: &mut EngineInner,
+        data: &[u8],
+        target: &Target,
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+        prepared: Option<ModuleInfo>,
+        compile: impl FnOnce(
+            &dyn Compiler,
+            &CompileModuleInfo,
+            &ModuleTranslationState,
+            PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        ) -> Result<Compilation, CompileError>,
     ) -> Result<Self, CompileError> {
         let environ = ModuleEnvironment::new();
         let features = inner_engine.features().clone();
@@ -53,10 +133,24 @@ impl ArtifactBuild {
 
         let compiler = inner_engine.compiler()?;
 
-        // We try to apply the middleware first
         let mut module = translation.module;
-        let middlewares = compiler.get_middlewares();
-        middlewares.apply_on_module_info(&mut module);
+        match prepared {
+            // The middlewares can only be applied once, so only the
+            // sections after the code section are taken from the translation.
+            Some(mut prepared) => {
+                prepared.name = module.name.take();
+                prepared.passive_data = mem::take(&mut module.passive_data);
+                prepared.function_names = mem::take(&mut module.function_names);
+                prepared.custom_sections = mem::take(&mut module.custom_sections);
+                prepared.custom_sections_data = mem::take(&mut module.custom_sections_data);
+                module = prepared;
+            }
+            // We try to apply the middleware first
+            None => {
+                let middlewares = compiler.get_middlewares();
+                middlewares.apply_on_module_info(&mut module);
+            }
+        }
 
         let compile_info = CompileModuleInfo {
             module,
@@ -66,8 +160,8 @@ impl ArtifactBuild {
         };
 
         // Compile the Module
-        let compilation = compiler.compile_module(
-            target,
+        let compilation = compile(
+            compiler,
             &compile_info,
             // SAFETY: Calling `unwrap` is correct since
             // `environ.translate()` above will write some data into
@@ -75,9 +169,6 @@ impl ArtifactBuild {
             translation.module_translation_state.as_ref().unwrap(),
             translation.function_body_inputs,
         )?;
-        let function_call_trampolines = compilation.get_function_call_trampolines();
-        let dynamic_function_trampolines = compilation.get_dynamic_function_trampolines();
-
         let data_initializers = translation
             .data_initializers
             .iter()
@@ -85,6 +176,24 @@ impl ArtifactBuild {
             .collect::<Vec<_>>()
             .into_boxed_slice();
 
+        Ok(Self::from_compilation(
+            compilation,
+            compile_info,
+            data_initializers,
+            target,
+        ))
+    }
+
+    /// Create a new ArtifactBuild from the compilation of a module
+    #[cfg(feature = "compiler")]
+    pub(crate) fn from_compilation(
+        compilation: Compilation,
+        compile_info: CompileModuleInfo,
+        data_initializers: Box<[OwnedDataInitializer]>,
+        target: &Target,
+    ) -> Self {
+        let function_call_trampolines = compilation.get_function_call_trampolines();
+        let dynamic_function_trampolines = compilation.get_dynamic_function_trampolines();
         let frame_infos = compilation.get_frame_info();
 
         // Synthesize a custom section to hold the libcall trampolines.
@@ -112,8 +221,9 @@ impl ArtifactBuild {
             compile_info,
             data_initializers,
             cpu_features: target.cpu_features().as_u64(),
+            target_triple: target.triple().to_string(),
         };
-        Ok(Self { serializable })
+        Self { serializable }
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -185,6 +295,11 @@ impl ArtifactBuild {
     pub fn get_frame_info_ref(&self) -> &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo> {
         &self.serializable.compilation.function_frame_info
     }
+
+    /// Get ModuleInfo ref
+    pub fn get_module_info_ref(&self) -> &ModuleInfo {
+        &self.serializable.compile_info.module
+    }
 }
 
 impl ArtifactCreate for ArtifactBuild {
@@ -5,6 +5,8 @@ use crate::lib::std::boxed::Box;
 use crate::lib::std::sync::Arc;
 use crate::translator::ModuleMiddleware;
 use crate::FunctionBodyData;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::FunctionCache;
 use crate::ModuleTranslationState;
 use wasmer_types::compilation::function::Compilation;
 use wasmer_types::compilation::module::CompileModuleInfo;
@@ -15,6 +17,28 @@ use wasmer_types::error::CompileError;
 use wasmer_types::{Features, LocalFunctionIndex};
 use wasmparser::{Validator, WasmFeatures};
 
+/// The `wasmparser` features that validate the modules using `features`.
+pub(crate) fn wasm_features(features: &Features) -> WasmFeatures {
+    WasmFeatures {
+        bulk_memory: features.bulk_memory,
+        threads: features.threads,
+        reference_types: features.reference_types,
+        multi_value: features.multi_value,
+        simd: features.simd,
+        tail_call: features.tail_call,
+        module_linking: features.module_linking,
+        multi_memory: features.multi_memory,
+        memory64: features.memory64,
+        exceptions: features.exceptions,
+        deterministic_only: false,
+        extended_const: features.extended_const,
+        relaxed_simd: features.relaxed_simd,
+        mutable_global: true,
+        saturating_float_to_int: true,
+        sign_extension: true,
+    }
+}
+
 /// The compiler configuration options.
 pub trait CompilerConfig {
     /// Enable Position Independent Code (PIC).
@@ -77,7 +101,10 @@ where
 }
 
 /// An implementation of a Compiler from parsed WebAssembly module to Compiled native code.
-pub trait Compiler: Send {
+///
+/// Compilers are shared by the threads that compile the functions of
+/// lazily compiled modules, hence `Sync`.
+pub trait Compiler: Send + Sync {
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
@@ -87,25 +114,7 @@ pub trait Compiler: Send {
         data: &'data [u8],
     ) -> Result<(), CompileError> {
         let mut validator = Validator::new();
-        let wasm_features = WasmFeatures {
-            bulk_memory: features.bulk_memory,
-            threads: features.threads,
-            reference_types: features.reference_types,
-            multi_value: features.multi_value,
-            simd: features.simd,
-            tail_call: features.tail_call,
-            module_linking: features.module_linking,
-            multi_memory: features.multi_memory,
-            memory64: features.memory64,
-            exceptions: features.exceptions,
-            deterministic_only: false,
-            extended_const: features.extended_const,
-            relaxed_simd: features.relaxed_simd,
-            mutable_global: true,
-            saturating_float_to_int: true,
-            sign_extension: true,
-        };
-        validator.wasm_features(wasm_features);
+        validator.wasm_features(wasm_features(features));
         validator
             .validate_all(data)
             .map_err(|e| CompileError::Validate(format!("{}", e)))?;
@@ -124,6 +133,70 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Compiles a parsed module like [`Compiler::compile_module`], looking
+    /// up its functions in `function_cache` before compiling them.
+    ///
+    /// Compilers that don't cache the functions they compile compile the
+    /// whole module.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn compile_module_with_function_cache<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        _function_cache: &FunctionCache,
+    ) -> Result<Compilation, CompileError> {
+        self.compile_module(target, module, module_translation, function_body_inputs)
+    }
+
+    /// Compiles some functions of a parsed module into `function_cache`, so
+    /// that compiling the module later with
+    /// [`Compiler::compile_module_with_function_cache`] and the same cache
+    /// doesn't compile them again.
+    ///
+    /// This lets the functions of a module be compiled while the rest of
+    /// the module is still being read: `module` only needs to be translated
+    /// up to its code section. Compilers that don't cache the functions they
+    /// compile don't do anything.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn precompile_functions<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _function_body_inputs: &[(LocalFunctionIndex, FunctionBodyData<'data>)],
+        _function_cache: &FunctionCache,
+    ) -> Result<(), CompileError> {
+        Ok(())
+    }
+
+    /// Returns the cache the compiler is configured to look up the
+    /// functions it compiles in, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn function_cache(&self) -> Option<&FunctionCache> {
+        None
+    }
+
+    /// Compiles a single function of a parsed module, for engines that
+    /// compile the functions of a module the first time they are called.
+    ///
+    /// The returned [`Compilation`] holds the compiled function, at index 0,
+    /// and the custom sections it refers to, but no trampolines. Relocations
+    /// to local functions still use their index in the module.
+    fn compile_function<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _index: LocalFunctionIndex,
+        _body: &FunctionBodyData<'data>,
+    ) -> Result<Compilation, CompileError> {
+        Err(CompileError::UnsupportedFeature(
+            "compiling a single function".to_string(),
+        ))
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
@@ -143,4 +216,18 @@ pub trait Compiler: Send {
 
     /// Get the middlewares for this compiler
     fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>];
+
+    /// Returns true if the compiler canonicalizes the NaNs that floating
+    /// point operations produce (see [`CompilerConfig::canonicalize_nans`]).
+    fn canonicalizes_nans(&self) -> bool {
+        false
+    }
+
+    /// Returns an id that identifies the compiler and every option that
+    /// changes the code it generates, except for the middlewares.
+    ///
+    /// Two compilers with the same id must generate compatible artifacts.
+    fn deterministic_id(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
 }
@@ -1,18 +1,24 @@
 //! Define `Artifact`, based on `ArtifactBuild`
 //! to allow compiling and instantiating to be done as separate steps.
 
+use crate::engine::integrity;
+#[cfg(feature = "compiler")]
+use crate::engine::lazy::LazyFunctions;
 use crate::engine::link::link_module;
 use crate::ArtifactBuild;
 use crate::ArtifactCreate;
 use crate::Features;
+#[cfg(feature = "compiler")]
+use crate::FunctionCache;
 use crate::ModuleEnvironment;
 use crate::{
     register_frame_info, resolve_imports, FunctionExtent, GlobalFrameInfoRegistration,
     InstantiationError, RuntimeError, Tunables,
 };
+use crate::{ArtifactSigningKey, TrustedKeys};
+use crate::{CodeMemory, Engine, EngineInner};
 #[cfg(feature = "static-artifact-create")]
 use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
-use crate::{Engine, EngineInner};
 use enumset::EnumSet;
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use std::mem;
@@ -29,7 +35,7 @@ use wasmer_types::SerializableCompilation;
 use wasmer_types::{
     CompileError, CpuFeature, DataInitializer, DeserializeError, FunctionIndex, LocalFunctionIndex,
     MemoryIndex, ModuleInfo, OwnedDataInitializer, SerializableModule, SerializeError,
-    SignatureIndex, TableIndex,
+    SignatureIndex, TableIndex, Triple,
 };
 #[cfg(feature = "static-artifact-create")]
 use wasmer_types::{CompileModuleInfo, Target};
@@ -46,6 +52,16 @@ pub struct Artifact {
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// The functions compiled on their first call, for artifacts compiled
+    /// lazily
+    #[cfg(feature = "compiler")]
+    lazy: Option<Arc<LazyFunctions>>,
+    /// The memory holding the compiled code, `None` for deserialized static
+    /// artifacts.
+    ///
+    /// This field must stay the last one: the code is freed when it is
+    /// dropped, which must happen after the frame info is unregistered.
+    _code_memory: Option<CodeMemory>,
 }
 
 #[cfg(feature = "static-artifact-create")]
@@ -62,21 +78,78 @@ impl Artifact {
         data: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Self, CompileError> {
-        let environ = ModuleEnvironment::new();
+        Self::new_with_function_cache(engine, data, tunables, None, None)
+    }
+
+    /// Compile a data buffer into a `ArtifactBuild`, looking up its
+    /// functions in `function_cache`, if any, before compiling them.
+    ///
+    /// `module` is the module translated up to its code section with the
+    /// middlewares already applied, when its functions were compiled into
+    /// `function_cache` ahead of the rest of the module.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn new_with_function_cache(
+        engine: &Engine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+        function_cache: Option<&FunctionCache>,
+        module: Option<ModuleInfo>,
+    ) -> Result<Self, CompileError> {
         let mut inner_engine = engine.inner_mut();
-        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
-        let module = translation.module;
-        let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
-            .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
-            .collect();
-        let table_styles: PrimaryMap<TableIndex, TableStyle> = module
-            .tables
-            .values()
-            .map(|table_type| tunables.table_style(table_type))
-            .collect();
+        let (memory_styles, table_styles) = Self::styles(data, tunables)?;
+
+        if engine.lazy_compilation() && LazyFunctions::is_supported(engine.target()) {
+            let (artifact, mut lazy) =
+                LazyFunctions::new(engine, &mut inner_engine, data, memory_styles, table_styles)?;
+            let mut artifact =
+                Self::from_parts_in(&mut inner_engine, artifact, lazy.code_memory())?;
+            LazyFunctions::set_stubs(
+                &mut lazy,
+                artifact
+                    .finished_functions
+                    .values()
+                    .map(|ptr| **ptr as usize),
+            );
+            artifact.lazy = Some(lazy);
+            return Ok(artifact);
+        }
+
+        let artifact = match function_cache {
+            Some(function_cache) => ArtifactBuild::new_with_function_cache(
+                &mut inner_engine,
+                data,
+                engine.target(),
+                memory_styles,
+                table_styles,
+                function_cache,
+                module,
+            )?,
+            None => ArtifactBuild::new(
+                &mut inner_engine,
+                data,
+                engine.target(),
+                memory_styles,
+                table_styles,
+            )?,
+        };
 
+        Self::from_parts(&mut inner_engine, artifact)
+    }
+
+    /// Compile a data buffer into a serialized `ArtifactBuild`, without
+    /// loading its code, so that the module can be compiled for a target
+    /// other than the host.
+    ///
+    /// The bytes can be deserialized with [`Artifact::deserialize`] by an
+    /// engine for the target of `engine`.
+    #[cfg(feature = "compiler")]
+    pub fn generate_serialized(
+        engine: &Engine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<Vec<u8>, CompileError> {
+        let mut inner_engine = engine.inner_mut();
+        let (memory_styles, table_styles) = Self::styles(data, tunables)?;
         let artifact = ArtifactBuild::new(
             &mut inner_engine,
             data,
@@ -84,8 +157,49 @@ impl Artifact {
             memory_styles,
             table_styles,
         )?;
+        artifact
+            .serialize()
+            .map_err(|err| CompileError::Codegen(format!("{}", err)))
+    }
 
-        Self::from_parts(&mut inner_engine, artifact)
+    /// The styles of the memories and tables of the module in `data`.
+    #[cfg(feature = "compiler")]
+    #[allow(clippy::type_complexity)]
+    fn styles(
+        data: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<
+        (
+            PrimaryMap<MemoryIndex, MemoryStyle>,
+            PrimaryMap<TableIndex, TableStyle>,
+        ),
+        CompileError,
+    > {
+        let environ = ModuleEnvironment::new();
+        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        Ok(Self::module_styles(&translation.module, tunables))
+    }
+
+    /// The styles of the memories and tables of `module`.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn module_styles(
+        module: &ModuleInfo,
+        tunables: &dyn Tunables,
+    ) -> (
+        PrimaryMap<MemoryIndex, MemoryStyle>,
+        PrimaryMap<TableIndex, TableStyle>,
+    ) {
+        let memory_styles = module
+            .memories
+            .values()
+            .map(|memory_type| tunables.memory_style(memory_type))
+            .collect();
+        let table_styles = module
+            .tables
+            .values()
+            .map(|table_type| tunables.table_style(table_type))
+            .collect();
+        (memory_styles, table_styles)
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -117,6 +231,59 @@ impl Artifact {
             ));
         }
 
+        let bytes = integrity::check_trailer(bytes, None)?;
+        Self::deserialize_universal(engine, bytes)
+    }
+
+    /// Deserialize a ArtifactBuild that is signed by one of the
+    /// `trusted_keys`, as produced by [`Artifact::serialize_checked`].
+    ///
+    /// Unlike [`Artifact::deserialize`] this function is safe: the checksum
+    /// and the signature of the artifact are verified before anything is
+    /// read from it, so the artifact is exactly the one produced by a
+    /// trusted signer. Like [`Artifact::deserialize`], it rejects the
+    /// artifacts compiled for another target than the one of `engine`
+    /// before their code is loaded.
+    pub fn deserialize_checked(
+        engine: &Engine,
+        bytes: &[u8],
+        trusted_keys: &TrustedKeys,
+    ) -> Result<Self, DeserializeError> {
+        let bytes = integrity::check_trailer(bytes, Some(trusted_keys))?;
+        if !ArtifactBuild::is_deserializable(bytes) {
+            return Err(DeserializeError::Incompatible(
+                "The provided bytes are not wasmer-universal".to_string(),
+            ));
+        }
+        // Safety: the artifact was serialized by a trusted signer and is
+        // intact
+        unsafe { Self::deserialize_universal(engine, bytes) }
+    }
+
+    /// Serializes the artifact followed by its checksum, and by its
+    /// signature if a `signing_key` is provided.
+    ///
+    /// Signed artifacts can be safely loaded with
+    /// [`Artifact::deserialize_checked`], and the checksum of any
+    /// artifact serialized this way is verified by
+    /// [`Artifact::deserialize`].
+    pub fn serialize_checked(
+        &self,
+        signing_key: Option<&ArtifactSigningKey>,
+    ) -> Result<Vec<u8>, SerializeError> {
+        let mut bytes = self.serialize()?;
+        integrity::append_trailer(&mut bytes, signing_key);
+        Ok(bytes)
+    }
+
+    /// Deserialize a `wasmer-universal` ArtifactBuild
+    ///
+    /// # Safety
+    /// See [`Artifact::deserialize`].
+    unsafe fn deserialize_universal(
+        engine: &Engine,
+        bytes: &[u8],
+    ) -> Result<Self, DeserializeError> {
         let bytes = Self::get_byte_slice(bytes, ArtifactBuild::MAGIC_HEADER.len(), bytes.len())?;
 
         let metadata_len = MetadataHeader::parse(bytes)?;
@@ -124,15 +291,60 @@ impl Artifact {
         let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
 
         let serializable = SerializableModule::deserialize(metadata_slice)?;
+        Self::check_compatible(engine, &serializable)?;
         let artifact = ArtifactBuild::from_serializable(serializable);
         let mut inner_engine = engine.inner_mut();
         Self::from_parts(&mut inner_engine, artifact).map_err(DeserializeError::Compiler)
     }
 
+    /// Checks that a deserialized module was compiled for the target of
+    /// `engine`: the same architecture, operating system and binary format
+    /// (the environment, like `gnu` or `musl`, doesn't change the code),
+    /// and CPU features that the target has.
+    fn check_compatible(
+        engine: &Engine,
+        serializable: &SerializableModule,
+    ) -> Result<(), DeserializeError> {
+        let target = engine.target();
+        let compatible = match serializable.target_triple().parse::<Triple>() {
+            Ok(triple) => {
+                triple.architecture == target.triple().architecture
+                    && triple.operating_system == target.triple().operating_system
+                    && triple.binary_format == target.triple().binary_format
+            }
+            Err(_) => false,
+        };
+        if !compatible {
+            return Err(DeserializeError::Incompatible(format!(
+                "the module was compiled for {}, but the engine targets {}",
+                serializable.target_triple(),
+                target.triple()
+            )));
+        }
+        let cpu_features = serializable.cpu_features();
+        if !target.cpu_features().is_superset(cpu_features) {
+            return Err(DeserializeError::Incompatible(format!(
+                "the module uses CPU features that the target of the engine doesn't have: {:?}",
+                cpu_features.difference(*target.cpu_features())
+            )));
+        }
+        Ok(())
+    }
+
     /// Construct a `ArtifactBuild` from component parts.
     pub fn from_parts(
         engine_inner: &mut EngineInner,
         artifact: ArtifactBuild,
+    ) -> Result<Self, CompileError> {
+        Self::from_parts_in(engine_inner, artifact, CodeMemory::new())
+    }
+
+    /// Construct a `ArtifactBuild` from component parts, allocating its
+    /// code in `code_memory`.
+    fn from_parts_in(
+        engine_inner: &mut EngineInner,
+        artifact: ArtifactBuild,
+        mut code_memory: CodeMemory,
     ) -> Result<Self, CompileError> {
         let module_info = artifact.create_module_info();
         let (
@@ -141,6 +353,7 @@ impl Artifact {
             finished_dynamic_function_trampolines,
             custom_sections,
         ) = engine_inner.allocate(
+            &mut code_memory,
             &module_info,
             artifact.get_function_bodies_ref(),
             artifact.get_function_call_trampolines_ref(),
@@ -182,9 +395,15 @@ impl Artifact {
         };
 
         // Make all code compiled thus far executable.
-        engine_inner.publish_compiled_code();
+        code_memory.publish();
 
-        engine_inner.publish_eh_frame(eh_frame)?;
+        // Register DWARF-type exception handling information associated with the code.
+        code_memory
+            .unwind_registry_mut()
+            .publish(eh_frame)
+            .map_err(|e| {
+                CompileError::Resource(format!("Error while publishing the unwind code: {}", e))
+            })?;
 
         let finished_function_lengths = finished_functions
             .values()
@@ -210,6 +429,9 @@ impl Artifact {
             signatures,
             frame_info_registration: Some(Mutex::new(None)),
             finished_function_lengths,
+            #[cfg(feature = "compiler")]
+            lazy: None,
+            _code_memory: Some(code_memory),
         })
     }
 
@@ -217,6 +439,11 @@ impl Artifact {
     pub fn is_deserializable(bytes: &[u8]) -> bool {
         ArtifactBuild::is_deserializable(bytes)
     }
+
+    /// The information about the module of this artifact
+    pub(crate) fn module_info(&self) -> &ModuleInfo {
+        self.artifact.get_module_info_ref()
+    }
 }
 
 impl ArtifactCreate for Artifact {
@@ -245,6 +472,12 @@ impl ArtifactCreate for Artifact {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        #[cfg(feature = "compiler")]
+        if self.lazy.is_some() {
+            return Err(SerializeError::Generic(
+                "Modules compiled lazily can't be serialized".to_string(),
+            ));
+        }
         self.artifact.serialize()
     }
 }
@@ -684,6 +917,7 @@ impl Artifact {
             compile_info: metadata.compile_info,
             data_initializers: metadata.data_initializers,
             cpu_features: metadata.cpu_features,
+            target_triple: engine.target().triple().to_string(),
         });
 
         let finished_function_lengths = finished_functions
@@ -702,6 +936,9 @@ impl Artifact {
             signatures: signatures.into_boxed_slice(),
             finished_function_lengths,
             frame_info_registration: None,
+            #[cfg(feature = "compiler")]
+            lazy: None,
+            _code_memory: None,
         })
     }
 }
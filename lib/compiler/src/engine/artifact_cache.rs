@@ -0,0 +1,89 @@
+//! Deduplication of the artifacts of an engine.
+
+use crate::{Artifact, ArtifactCreate, Tunables};
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+/// The artifacts created by an engine, indexed by the hash of the bytes
+/// they were created from.
+///
+/// Only weak references are kept: an artifact, and the code memory it
+/// owns, is freed as soon as the last module using it is dropped.
+#[derive(Default)]
+pub(crate) struct ArtifactCache {
+    /// Artifacts compiled from WebAssembly. The same bytes might be
+    /// compiled with different tunables, which change the generated code.
+    compiled: HashMap<[u8; 32], Vec<Weak<Artifact>>>,
+    /// Artifacts deserialized from a serialized module.
+    deserialized: HashMap<[u8; 32], Weak<Artifact>>,
+}
+
+impl ArtifactCache {
+    /// Returns the hash used to index the artifacts created from `bytes`.
+    pub(crate) fn key(bytes: &[u8]) -> [u8; 32] {
+        blake3::hash(bytes).into()
+    }
+
+    /// Returns the artifact compiled from the bytes with the given key, if
+    /// it is still alive and was compiled for the same tunables.
+    pub(crate) fn get_compiled(
+        &self,
+        key: &[u8; 32],
+        tunables: &dyn Tunables,
+    ) -> Option<Arc<Artifact>> {
+        self.compiled
+            .get(key)?
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|artifact| same_styles(artifact, tunables))
+    }
+
+    /// Records an artifact compiled from the bytes with the given key.
+    pub(crate) fn insert_compiled(&mut self, key: [u8; 32], artifact: &Arc<Artifact>) {
+        self.prune();
+        self.compiled
+            .entry(key)
+            .or_default()
+            .push(Arc::downgrade(artifact));
+    }
+
+    /// Returns the artifact deserialized from the bytes with the given key,
+    /// if it is still alive.
+    pub(crate) fn get_deserialized(&self, key: &[u8; 32]) -> Option<Arc<Artifact>> {
+        self.deserialized.get(key)?.upgrade()
+    }
+
+    /// Records an artifact deserialized from the bytes with the given key.
+    pub(crate) fn insert_deserialized(&mut self, key: [u8; 32], artifact: &Arc<Artifact>) {
+        self.prune();
+        self.deserialized.insert(key, Arc::downgrade(artifact));
+    }
+
+    /// Returns the number of artifacts that are alive.
+    pub(crate) fn len(&self) -> usize {
+        let alive = |artifact: &&Weak<Artifact>| artifact.strong_count() > 0;
+        self.compiled.values().flatten().filter(alive).count()
+            + self.deserialized.values().filter(alive).count()
+    }
+
+    /// Forgets the artifacts that were freed.
+    fn prune(&mut self) {
+        self.compiled.retain(|_, artifacts| {
+            artifacts.retain(|artifact| artifact.strong_count() > 0);
+            !artifacts.is_empty()
+        });
+        self.deserialized
+            .retain(|_, artifact| artifact.strong_count() > 0);
+    }
+}
+
+/// Returns true if compiling the module of `artifact` with `tunables` would
+/// use the same memory and table styles as `artifact`.
+fn same_styles(artifact: &Artifact, tunables: &dyn Tunables) -> bool {
+    let module = artifact.module_info();
+    module.memories.iter().all(|(index, memory)| {
+        Some(&tunables.memory_style(memory)) == artifact.memory_styles().get(index)
+    }) && module.tables.iter().all(|(index, table)| {
+        Some(&tunables.table_style(table)) == artifact.table_styles().get(index)
+    })
+}
@@ -1,5 +1,11 @@
 use super::Engine;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::lazy::TierUp;
 use crate::CompilerConfig;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
 use wasmer_types::{Features, Target};
 
 /// The Builder contents of `Engine`
@@ -10,6 +16,11 @@ pub struct EngineBuilder {
     target: Option<Target>,
     /// The features to compile the Wasm module with
     features: Option<Features>,
+    /// Whether functions are compiled on their first call
+    lazy_compilation: bool,
+    /// The compiler that recompiles hot functions, and the number of calls
+    /// that makes a function hot
+    tier_up: Option<(Box<dyn CompilerConfig>, u32)>,
 }
 
 impl EngineBuilder {
@@ -22,15 +33,22 @@ impl EngineBuilder {
             compiler_config: Some(compiler_config.into()),
             target: None,
             features: None,
+            lazy_compilation: false,
+            tier_up: None,
         }
     }
 
     /// Create a new headless Backend
+    ///
+    /// The target and the features of a headless engine are the ones the
+    /// modules it loads were compiled for, see [`Engine::compatibility_id`].
     pub fn headless() -> Self {
         Self {
             compiler_config: None,
             target: None,
             features: None,
+            lazy_compilation: false,
+            tier_up: None,
         }
     }
 
@@ -46,6 +64,44 @@ impl EngineBuilder {
         self
     }
 
+    /// Compile the functions of a module the first time they are called,
+    /// instead of when the module is created.
+    ///
+    /// This makes creating a module faster when most of its functions are
+    /// never called, at the cost of a pause on the first call to each
+    /// function. Lazy compilation is only supported on x86_64, except on
+    /// Windows, and when compiling for the host: the functions of other
+    /// modules are compiled when the module is created. Modules compiled
+    /// lazily can't be serialized.
+    pub fn set_lazy_compilation(mut self, lazy_compilation: bool) -> Self {
+        self.lazy_compilation = lazy_compilation;
+        self
+    }
+
+    /// Recompile the functions called `call_threshold` times with the
+    /// compiler of `compiler_config`, usually an optimizing compiler like
+    /// Cranelift or LLVM when the engine compiles with Singlepass.
+    ///
+    /// This enables lazy compilation, see [`Self::set_lazy_compilation`]:
+    /// the functions are first compiled with the compiler of the engine, and
+    /// the calls to each function are counted. The hot functions are
+    /// recompiled on a background thread, and the next calls to them run the
+    /// recompiled code. The errors of the recompilations are returned by
+    /// [`Engine::take_tier_up_errors`].
+    ///
+    /// Only calls are counted: the loops of a function don't make it hot,
+    /// and a running call keeps running the code it entered. When either
+    /// compiler uses middlewares, the functions are not recompiled, as the
+    /// middlewares of the optimizing compiler don't have the state that the
+    /// ones of the engine's compiler derived from the module.
+    pub fn set_tier_up<T>(mut self, compiler_config: T, call_threshold: u32) -> Self
+    where
+        T: Into<Box<dyn CompilerConfig>>,
+    {
+        self.tier_up = Some((compiler_config.into(), call_threshold));
+        self
+    }
+
     /// Build the `Engine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> Engine {
@@ -54,16 +110,28 @@ impl EngineBuilder {
             let features = self
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
-            Engine::new(compiler_config, target, features)
+            let mut engine = Engine::new(compiler_config, target, features);
+            engine.set_lazy_compilation(self.lazy_compilation || self.tier_up.is_some());
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some((tier_up_config, call_threshold)) = self.tier_up {
+                engine.set_tier_up(Some(Arc::new(TierUp::new(
+                    tier_up_config.compiler(),
+                    call_threshold,
+                ))));
+            }
+            engine
         } else {
-            Engine::headless()
+            Engine::headless_for(target, self.features.unwrap_or_default())
         }
     }
 
     /// Build the `Engine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> Engine {
-        Engine::headless()
+        Engine::headless_for(
+            self.target.unwrap_or_default(),
+            self.features.unwrap_or_default(),
+        )
     }
 
     /// The Wasm features
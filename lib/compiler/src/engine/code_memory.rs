@@ -3,6 +3,7 @@
 
 //! Memory management for executable code.
 use super::unwind::UnwindRegistry;
+use std::sync::{Arc, Mutex};
 use wasmer_types::{CompiledFunctionUnwindInfo, CustomSection, FunctionBody};
 use wasmer_vm::{Mmap, VMFunctionBody};
 
@@ -17,10 +18,79 @@ const ARCH_FUNCTION_ALIGNMENT: usize = 16;
 ///
 const DATA_SECTION_ALIGNMENT: usize = 64;
 
+/// An address range reserved for code, that code memories can take their
+/// pages from.
+///
+/// The code of the code memories taken from a region is close enough to
+/// call each other with 32-bit relative calls, as long as the region is
+/// smaller than 2 GiB. The pages are only given back when the region is
+/// dropped, after all its code memories.
+#[cfg_attr(not(feature = "compiler"), allow(dead_code))]
+pub(crate) struct CodeRegion {
+    /// The reserved mapping, and the offset of its first page that wasn't
+    /// taken yet
+    mapping: Mutex<(Mmap, usize)>,
+}
+
+#[cfg_attr(not(feature = "compiler"), allow(dead_code))]
+impl CodeRegion {
+    /// Reserves a region of at least `size` bytes, without making any of
+    /// its pages accessible.
+    pub(crate) fn reserve(size: usize) -> Result<Self, String> {
+        let page_size = region::page::size();
+        // The last page is never made accessible, it guards the end of the
+        // region
+        let size = round_up(size, page_size) + page_size;
+        Ok(Self {
+            mapping: Mutex::new((Mmap::accessible_reserved(0, size)?, 0)),
+        })
+    }
+
+    /// Makes the next `len` bytes of the region accessible, rounded up to
+    /// whole pages, and returns their address.
+    fn take(&self, len: usize) -> Result<*mut u8, String> {
+        let page_size = region::page::size();
+        let len = round_up(len, page_size);
+        let mut mapping = self.mapping.lock().unwrap();
+        let (mmap, next) = &mut *mapping;
+        let start = *next;
+        if mmap.len() - page_size - start < len {
+            return Err(format!(
+                "the code region of {} bytes is full",
+                mmap.len() - page_size
+            ));
+        }
+        mmap.make_accessible(start, len)?;
+        *next += len;
+        Ok(unsafe { mmap.as_mut_ptr().add(start) })
+    }
+}
+
+/// The memory of a `CodeMemory`.
+#[cfg_attr(not(feature = "compiler"), allow(dead_code))]
+enum Memory {
+    /// A mapping of its own
+    Mmap(Mmap),
+    /// Pages taken from a region, given as an address and a length
+    Region(Arc<CodeRegion>, usize, usize),
+}
+
+impl Memory {
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            Self::Mmap(mmap) => mmap.as_mut_slice(),
+            Self::Region(_, _, 0) => &mut [],
+            Self::Region(_, ptr, len) => unsafe {
+                std::slice::from_raw_parts_mut(*ptr as *mut u8, *len)
+            },
+        }
+    }
+}
+
 /// Memory manager for executable code.
 pub struct CodeMemory {
     unwind_registry: UnwindRegistry,
-    mmap: Mmap,
+    memory: Memory,
     start_of_nonexecutable_pages: usize,
 }
 
@@ -29,7 +99,18 @@ impl CodeMemory {
     pub fn new() -> Self {
         Self {
             unwind_registry: UnwindRegistry::new(),
-            mmap: Mmap::new(),
+            memory: Memory::Mmap(Mmap::new()),
+            start_of_nonexecutable_pages: 0,
+        }
+    }
+
+    /// Create a new `CodeMemory` instance that takes its pages from
+    /// `region`.
+    #[cfg_attr(not(feature = "compiler"), allow(dead_code))]
+    pub(crate) fn new_in(region: &Arc<CodeRegion>) -> Self {
+        Self {
+            unwind_registry: UnwindRegistry::new(),
+            memory: Memory::Region(region.clone(), 0, 0),
             start_of_nonexecutable_pages: 0,
         }
     }
@@ -79,13 +160,20 @@ impl CodeMemory {
 
         // 2. Allocate the pages. Mark them all read-write.
 
-        self.mmap = Mmap::with_at_least(total_len)?;
+        match &mut self.memory {
+            Memory::Mmap(mmap) => *mmap = Mmap::with_at_least(total_len)?,
+            Memory::Region(region, ptr, len) if total_len > 0 => {
+                *ptr = region.take(total_len)? as usize;
+                *len = total_len;
+            }
+            Memory::Region(..) => {}
+        }
 
         // 3. Determine where the pointers to each function, executable section
         // or data section are. Copy the functions. Collect the addresses of each and return them.
 
         let mut bytes = 0;
-        let mut buf = self.mmap.as_mut_slice();
+        let mut buf = self.memory.as_mut_slice();
         for func in functions {
             let len = round_up(
                 Self::function_allocation_size(func),
@@ -138,13 +226,14 @@ impl CodeMemory {
 
     /// Apply the page permissions.
     pub fn publish(&mut self) {
-        if self.mmap.is_empty() || self.start_of_nonexecutable_pages == 0 {
+        let memory = self.memory.as_mut_slice();
+        if memory.is_empty() || self.start_of_nonexecutable_pages == 0 {
             return;
         }
-        assert!(self.mmap.len() >= self.start_of_nonexecutable_pages);
+        assert!(memory.len() >= self.start_of_nonexecutable_pages);
         unsafe {
             region::protect(
-                self.mmap.as_mut_ptr(),
+                memory.as_mut_ptr(),
                 self.start_of_nonexecutable_pages,
                 region::Protection::READ_EXECUTE,
             )
@@ -216,9 +305,32 @@ fn round_up(size: usize, multiple: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::CodeMemory;
+    use super::{CodeMemory, CodeRegion};
+    use std::sync::Arc;
+    use wasmer_types::FunctionBody;
+
     fn _assert() {
         fn _assert_send_sync<T: Send + Sync>() {}
         _assert_send_sync::<CodeMemory>();
     }
+
+    #[test]
+    fn allocates_in_region() {
+        let page_size = region::page::size();
+        let region = Arc::new(CodeRegion::reserve(2 * page_size).unwrap());
+        let body = FunctionBody {
+            body: vec![0xc3],
+            unwind_info: None,
+        };
+        let mut first = CodeMemory::new_in(&region);
+        let first_address = first.allocate(&[&body], &[], &[]).unwrap().0[0].as_ptr() as usize;
+        first.publish();
+        let mut second = CodeMemory::new_in(&region);
+        let second_address = second.allocate(&[&body], &[], &[]).unwrap().0[0].as_ptr() as usize;
+        second.publish();
+        assert_eq!(second_address - first_address, page_size);
+
+        let mut third = CodeMemory::new_in(&region);
+        assert!(third.allocate(&[&body], &[], &[]).is_err());
+    }
 }
@@ -1,14 +1,24 @@
 //! Universal compilation.
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::artifact_cache::ArtifactCache;
 use crate::engine::builder::EngineBuilder;
 #[cfg(not(target_arch = "wasm32"))]
+use crate::engine::integrity;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engine::lazy::TierUp;
+#[cfg(not(target_arch = "wasm32"))]
 use crate::Artifact;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::CodeMemory;
 #[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+use crate::FunctionCache;
+#[cfg(feature = "compiler")]
 use crate::{Compiler, CompilerConfig};
 #[cfg(not(target_arch = "wasm32"))]
-use crate::{FunctionExtent, Tunables};
+use crate::{FunctionExtent, TrustedKeys, Tunables};
 #[cfg(not(target_arch = "wasm32"))]
 use memmap2::Mmap;
 #[cfg(not(target_arch = "wasm32"))]
@@ -36,6 +46,17 @@ pub struct Engine {
     /// The target for the compiler
     target: Arc<Target>,
     engine_id: EngineId,
+    compatibility_id: Arc<str>,
+    deterministic_id: Option<Arc<str>>,
+    /// Whether functions are compiled on their first call
+    lazy_compilation: bool,
+    /// The recompilation of the hot functions of lazily compiled modules
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    tier_up: Option<Arc<TierUp>>,
+    /// The artifacts created by this engine, so that they can be shared
+    #[cfg(not(target_arch = "wasm32"))]
+    artifacts: Arc<Mutex<ArtifactCache>>,
 }
 
 impl Engine {
@@ -46,20 +67,54 @@ impl Engine {
         target: Target,
         features: Features,
     ) -> Self {
+        let compiler = compiler_config.compiler();
+        let compatibility_id = Self::compatibility_id_for(&target, &features);
+        let mut deterministic_id = Some(format!(
+            "{}-{}",
+            compatibility_id,
+            compiler.deterministic_id()
+        ));
+        for middleware in compiler.get_middlewares() {
+            deterministic_id = match (deterministic_id, middleware.deterministic_id()) {
+                (Some(mut id), Some(middleware_id)) => {
+                    id.push('-');
+                    id.push_str(&middleware_id);
+                    Some(id)
+                }
+                _ => None,
+            };
+        }
         Self {
             inner: Arc::new(Mutex::new(EngineInner {
-                compiler: Some(compiler_config.compiler()),
+                compiler: Some(Arc::from(compiler)),
                 features,
                 #[cfg(not(target_arch = "wasm32"))]
-                code_memory: vec![],
-                #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
+            compatibility_id: compatibility_id.into(),
+            deterministic_id: deterministic_id.map(Into::into),
+            lazy_compilation: false,
+            #[cfg(feature = "compiler")]
+            #[cfg(not(target_arch = "wasm32"))]
+            tier_up: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            artifacts: Default::default(),
         }
     }
 
+    /// Returns true if the compiler of the engine canonicalizes NaNs, or
+    /// `None` for an engine without a compiler.
+    #[cfg(feature = "compiler")]
+    pub fn canonicalizes_nans(&self) -> Option<bool> {
+        let inner = self.inner();
+        inner
+            .compiler()
+            .ok()
+            .map(|compiler| compiler.canonicalizes_nans())
+    }
+
     /// Create a headless `Engine`
     ///
     /// A headless engine is an engine without any compiler attached.
@@ -74,19 +129,32 @@ impl Engine {
     /// Headless engines can't compile or validate any modules,
     /// they just take already processed Modules (via `Module::serialize`).
     pub fn headless() -> Self {
+        Self::headless_for(Target::default(), Features::default())
+    }
+
+    /// Create a headless `Engine` that loads the modules compiled for
+    /// `target` with `features`, see [`Engine::compatibility_id`].
+    pub(crate) fn headless_for(target: Target, features: Features) -> Self {
+        let compatibility_id: Arc<str> = Self::compatibility_id_for(&target, &features).into();
         Self {
             inner: Arc::new(Mutex::new(EngineInner {
                 #[cfg(feature = "compiler")]
                 compiler: None,
                 #[cfg(feature = "compiler")]
-                features: Features::default(),
-                #[cfg(not(target_arch = "wasm32"))]
-                code_memory: vec![],
+                features,
                 #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
             })),
-            target: Arc::new(Target::default()),
+            deterministic_id: Some(compatibility_id.clone()),
+            compatibility_id,
+            target: Arc::new(target),
             engine_id: EngineId::default(),
+            lazy_compilation: false,
+            #[cfg(feature = "compiler")]
+            #[cfg(not(target_arch = "wasm32"))]
+            tier_up: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            artifacts: Default::default(),
         }
     }
 
@@ -105,6 +173,42 @@ impl Engine {
         &self.target
     }
 
+    /// Returns true if the functions of the modules compiled by this engine
+    /// are compiled on their first call, see
+    /// [`EngineBuilder::set_lazy_compilation`].
+    pub fn lazy_compilation(&self) -> bool {
+        self.lazy_compilation
+    }
+
+    #[cfg(feature = "compiler")]
+    pub(crate) fn set_lazy_compilation(&mut self, lazy_compilation: bool) {
+        self.lazy_compilation = lazy_compilation;
+    }
+
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn tier_up(&self) -> Option<&Arc<TierUp>> {
+        self.tier_up.as_ref()
+    }
+
+    /// Returns the errors of the recompilations of hot functions that failed
+    /// since the last call, see [`EngineBuilder::set_tier_up`]. The
+    /// functions that couldn't be recompiled keep running the code of the
+    /// engine's compiler.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn take_tier_up_errors(&self) -> Vec<CompileError> {
+        self.tier_up
+            .as_ref()
+            .map_or_else(Vec::new, |tier_up| tier_up.take_errors())
+    }
+
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn set_tier_up(&mut self, tier_up: Option<Arc<TierUp>>) {
+        self.tier_up = tier_up;
+    }
+
     /// Register a signature
     #[cfg(not(target_arch = "wasm32"))]
     pub fn register_signature(&self, func_type: &FunctionType) -> VMSharedSignatureIndex {
@@ -133,7 +237,35 @@ impl Engine {
         binary: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Arc<Artifact>, CompileError> {
-        Ok(Arc::new(Artifact::new(self, binary, tunables)?))
+        self.compile_with_function_cache(binary, tunables, None, None)
+    }
+
+    /// Compile a WebAssembly binary, looking up its functions in
+    /// `function_cache`, if any, before compiling them
+    ///
+    /// See [`Artifact::new_with_function_cache`] for `module`.
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn compile_with_function_cache(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        function_cache: Option<&FunctionCache>,
+        module: Option<ModuleInfo>,
+    ) -> Result<Arc<Artifact>, CompileError> {
+        let key = ArtifactCache::key(binary);
+        if let Some(artifact) = self.artifacts().get_compiled(&key, tunables) {
+            return Ok(artifact);
+        }
+        let artifact = Arc::new(Artifact::new_with_function_cache(
+            self,
+            binary,
+            tunables,
+            function_cache,
+            module,
+        )?);
+        self.artifacts().insert_compiled(key, &artifact);
+        Ok(artifact)
     }
 
     /// Compile a WebAssembly binary
@@ -156,7 +288,48 @@ impl Engine {
     ///
     /// The serialized content must represent a serialized WebAssembly module.
     pub unsafe fn deserialize(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
-        Ok(Arc::new(Artifact::deserialize(self, bytes)?))
+        let key = ArtifactCache::key(bytes);
+        if let Some(artifact) = self.artifacts().get_deserialized(&key) {
+            return Ok(artifact);
+        }
+        let artifact = Arc::new(Artifact::deserialize(self, bytes)?);
+        self.artifacts().insert_deserialized(key, &artifact);
+        Ok(artifact)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Deserializes a WebAssembly module that is signed by one of the
+    /// `trusted_keys`, see [`Artifact::deserialize_checked`].
+    pub fn deserialize_checked(
+        &self,
+        bytes: &[u8],
+        trusted_keys: &TrustedKeys,
+    ) -> Result<Arc<Artifact>, DeserializeError> {
+        let key = ArtifactCache::key(bytes);
+        if let Some(artifact) = self.artifacts().get_deserialized(&key) {
+            // The same bytes might have been trusted by another set of keys
+            integrity::check_trailer(bytes, Some(trusted_keys))?;
+            return Ok(artifact);
+        }
+        let artifact = Arc::new(Artifact::deserialize_checked(self, bytes, trusted_keys)?);
+        self.artifacts().insert_deserialized(key, &artifact);
+        Ok(artifact)
+    }
+
+    /// Returns the number of artifacts created by this engine, or its
+    /// clones, that are still in use.
+    ///
+    /// Compiling or deserializing the same bytes again while a module
+    /// created from them is alive returns the same artifact, which shares
+    /// the compiled code instead of creating a copy of it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn artifact_count(&self) -> usize {
+        self.artifacts().len()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn artifacts(&self) -> std::sync::MutexGuard<'_, ArtifactCache> {
+        self.artifacts.lock().unwrap()
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -183,6 +356,98 @@ impl Engine {
         &self.engine_id
     }
 
+    /// Returns an id that identifies everything that changes the artifacts
+    /// produced by this engine: its [compatibility id], the compiler
+    /// configuration and its middlewares.
+    ///
+    /// Unlike [`Engine::id`], two engines configured the same way have the
+    /// same deterministic id, even across processes. The deterministic id
+    /// of a headless engine is its compatibility id.
+    ///
+    /// Returns `None` when one of the middlewares of the compiler has no
+    /// [deterministic id], as the artifacts of such an engine can't be
+    /// told apart from the ones of a differently configured engine.
+    ///
+    /// [compatibility id]: Engine::compatibility_id
+    /// [deterministic id]: crate::ModuleMiddleware::deterministic_id
+    pub fn deterministic_id(&self) -> Option<&str> {
+        self.deterministic_id.as_deref()
+    }
+
+    /// Returns an id that identifies what an artifact must have been
+    /// compiled for to be loaded by this engine: the Wasmer version, the
+    /// target triple, the CPU features and the enabled features.
+    ///
+    /// Unlike the [deterministic id], it doesn't depend on the compiler,
+    /// so a headless engine has the same compatibility id as the engines
+    /// that compile for its target and features, and can load the artifacts
+    /// they produce.
+    ///
+    /// [deterministic id]: Engine::deterministic_id
+    pub fn compatibility_id(&self) -> &str {
+        &self.compatibility_id
+    }
+
+    /// Returns true if the engine has no compiler, see [`Engine::headless`].
+    pub fn is_headless(&self) -> bool {
+        #[cfg(feature = "compiler")]
+        {
+            self.inner().compiler().is_err()
+        }
+        #[cfg(not(feature = "compiler"))]
+        {
+            true
+        }
+    }
+
+    /// The id is persisted by caches, so it is written from the bits of the
+    /// features rather than from their `Debug` output.
+    fn compatibility_id_for(target: &Target, features: &Features) -> String {
+        format!(
+            "wasmer-{}-{}-{:x}-{:x}",
+            env!("CARGO_PKG_VERSION"),
+            target.triple(),
+            target.cpu_features().as_u64(),
+            Self::features_bits(features)
+        )
+    }
+
+    /// Returns a bit per feature. The bits of the existing features must
+    /// not change, new features take the next bit.
+    fn features_bits(features: &Features) -> u64 {
+        let Features {
+            threads,
+            reference_types,
+            simd,
+            bulk_memory,
+            multi_value,
+            tail_call,
+            module_linking,
+            multi_memory,
+            memory64,
+            exceptions,
+            relaxed_simd,
+            extended_const,
+        } = *features;
+        [
+            threads,
+            reference_types,
+            simd,
+            bulk_memory,
+            multi_value,
+            tail_call,
+            module_linking,
+            multi_memory,
+            memory64,
+            exceptions,
+            relaxed_simd,
+            extended_const,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &enabled)| bits | ((enabled as u64) << bit))
+    }
+
     /// Clone the engine
     pub fn cloned(&self) -> Self {
         self.clone()
@@ -193,14 +458,10 @@ impl Engine {
 pub struct EngineInner {
     #[cfg(feature = "compiler")]
     /// The compiler and cpu features
-    compiler: Option<Box<dyn Compiler>>,
+    compiler: Option<Arc<dyn Compiler>>,
     #[cfg(feature = "compiler")]
     /// The compiler and cpu features
     features: Features,
-    /// The code memory is responsible of publishing the compiled
-    /// functions to memory.
-    #[cfg(not(target_arch = "wasm32"))]
-    code_memory: Vec<CodeMemory>,
     /// The signature registry is used mainly to operate with trampolines
     /// performantly.
     #[cfg(not(target_arch = "wasm32"))]
@@ -219,6 +480,18 @@ impl EngineInner {
         }
     }
 
+    /// Gets a handle to the compiler associated to this engine, to compile
+    /// without holding the lock of the engine.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn shared_compiler(&self) -> Result<Arc<dyn Compiler>, CompileError> {
+        match self.compiler.as_ref() {
+            None => Err(CompileError::Codegen(
+                "The Engine is not compiled in.".to_string(),
+            )),
+            Some(compiler) => Ok(compiler.clone()),
+        }
+    }
+
     /// Validate the module
     #[cfg(feature = "compiler")]
     pub fn validate(&self, data: &[u8]) -> Result<(), CompileError> {
@@ -232,11 +505,12 @@ impl EngineInner {
         &self.features
     }
 
-    /// Allocate compiled functions into memory
+    /// Allocate compiled functions into `code_memory`
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
     pub(crate) fn allocate(
         &mut self,
+        code_memory: &mut CodeMemory,
         _module: &ModuleInfo,
         functions: &PrimaryMap<LocalFunctionIndex, FunctionBody>,
         function_call_trampolines: &PrimaryMap<SignatureIndex, FunctionBody>,
@@ -259,12 +533,8 @@ impl EngineInner {
         let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
             .values()
             .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
-        self.code_memory.push(CodeMemory::new());
-
         let (mut allocated_functions, allocated_executable_sections, allocated_data_sections) =
-            self.code_memory
-                .last_mut()
-                .unwrap()
+            code_memory
                 .allocate(
                     function_bodies.as_slice(),
                     executable_sections.as_slice(),
@@ -326,26 +596,6 @@ impl EngineInner {
         ))
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
-    /// Make memory containing compiled code executable.
-    pub(crate) fn publish_compiled_code(&mut self) {
-        self.code_memory.last_mut().unwrap().publish();
-    }
-
-    #[cfg(not(target_arch = "wasm32"))]
-    /// Register DWARF-type exception handling information associated with the code.
-    pub(crate) fn publish_eh_frame(&mut self, eh_frame: Option<&[u8]>) -> Result<(), CompileError> {
-        self.code_memory
-            .last_mut()
-            .unwrap()
-            .unwind_registry_mut()
-            .publish(eh_frame)
-            .map_err(|e| {
-                CompileError::Resource(format!("Error while publishing the unwind code: {}", e))
-            })?;
-        Ok(())
-    }
-
     /// Shared signature registry.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn signatures(&self) -> &SignatureRegistry {
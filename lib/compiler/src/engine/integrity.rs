@@ -0,0 +1,246 @@
+//! Checksums and signatures of serialized artifacts.
+//!
+//! An integrity trailer is appended after the serialized artifact, so
+//! artifacts with a trailer can still be loaded by readers that don't know
+//! about it. The trailer is made of the BLAKE3 checksum of the artifact, and
+//! optionally the Ed25519 public key of the signer and the signature of the
+//! checksum, followed by a magic value that tells the two layouts apart.
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use std::convert::TryFrom;
+use std::fmt;
+use wasmer_types::DeserializeError;
+
+/// Magic value at the end of a checksummed artifact
+const CHECKSUM_MAGIC: &[u8; 16] = b"wasmer-checksum\0";
+/// Magic value at the end of a signed artifact
+const SIGNATURE_MAGIC: &[u8; 16] = b"wasmer-signature";
+
+const CHECKSUM_LEN: usize = 32;
+const PUBLIC_KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// A key used to sign serialized artifacts.
+pub struct ArtifactSigningKey(Keypair);
+
+impl ArtifactSigningKey {
+    /// Creates a signing key from the 32 bytes of an Ed25519 secret key.
+    pub fn from_bytes(secret: &[u8; 32]) -> Self {
+        let secret = SecretKey::from_bytes(secret).expect("secret keys are 32 bytes long");
+        let public = PublicKey::from(&secret);
+        Self(Keypair { secret, public })
+    }
+
+    /// Returns the public key that verifies the signatures of this key.
+    pub fn public_key(&self) -> ArtifactPublicKey {
+        ArtifactPublicKey(self.0.public)
+    }
+}
+
+impl fmt::Debug for ArtifactSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ArtifactSigningKey")
+            .field(&self.public_key())
+            .finish()
+    }
+}
+
+/// The public key of an [`ArtifactSigningKey`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ArtifactPublicKey(PublicKey);
+
+impl ArtifactPublicKey {
+    /// Creates a public key from the 32 bytes of an Ed25519 public key.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, DeserializeError> {
+        PublicKey::from_bytes(bytes)
+            .map(Self)
+            .map_err(|e| DeserializeError::Generic(format!("invalid public key: {}", e)))
+    }
+
+    /// Returns the bytes of the public key.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+}
+
+impl fmt::Debug for ArtifactPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.to_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// The set of keys whose signatures are trusted when deserializing
+/// artifacts with [`Engine::deserialize_checked`].
+///
+/// [`Engine::deserialize_checked`]: crate::Engine::deserialize_checked
+#[derive(Debug, Clone, Default)]
+pub struct TrustedKeys {
+    keys: Vec<ArtifactPublicKey>,
+}
+
+impl TrustedKeys {
+    /// Creates an empty set, which trusts no artifact.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts the artifacts signed by the given key.
+    pub fn add(&mut self, key: ArtifactPublicKey) -> &mut Self {
+        if !self.contains(&key) {
+            self.keys.push(key);
+        }
+        self
+    }
+
+    /// Returns true if the artifacts signed by the given key are trusted.
+    pub fn contains(&self, key: &ArtifactPublicKey) -> bool {
+        self.keys.contains(key)
+    }
+}
+
+impl std::iter::FromIterator<ArtifactPublicKey> for TrustedKeys {
+    fn from_iter<I: IntoIterator<Item = ArtifactPublicKey>>(iter: I) -> Self {
+        let mut keys = Self::new();
+        for key in iter {
+            keys.add(key);
+        }
+        keys
+    }
+}
+
+/// Appends the integrity trailer to a serialized artifact, signing it if a
+/// key is provided.
+pub(crate) fn append_trailer(bytes: &mut Vec<u8>, signing_key: Option<&ArtifactSigningKey>) {
+    let checksum = blake3::hash(bytes);
+    bytes.extend_from_slice(checksum.as_bytes());
+    match signing_key {
+        Some(key) => {
+            let signature = key.0.sign(checksum.as_bytes());
+            bytes.extend_from_slice(key.0.public.as_bytes());
+            bytes.extend_from_slice(&signature.to_bytes());
+            bytes.extend_from_slice(SIGNATURE_MAGIC);
+        }
+        None => bytes.extend_from_slice(CHECKSUM_MAGIC),
+    }
+}
+
+/// Verifies the integrity trailer of a serialized artifact, if it has one,
+/// and returns the artifact without it.
+///
+/// When `trusted_keys` is provided the artifact must be signed by one of
+/// them, otherwise only the checksum is verified.
+pub(crate) fn check_trailer<'a>(
+    bytes: &'a [u8],
+    trusted_keys: Option<&TrustedKeys>,
+) -> Result<&'a [u8], DeserializeError> {
+    let (signed, trailer_len) = if bytes.ends_with(SIGNATURE_MAGIC) {
+        (true, CHECKSUM_LEN + PUBLIC_KEY_LEN + SIGNATURE_LEN + 16)
+    } else if bytes.ends_with(CHECKSUM_MAGIC) {
+        (false, CHECKSUM_LEN + 16)
+    } else if trusted_keys.is_some() {
+        return Err(DeserializeError::Untrusted(
+            "the artifact is not signed".to_string(),
+        ));
+    } else {
+        return Ok(bytes);
+    };
+    if bytes.len() < trailer_len {
+        return Err(DeserializeError::CorruptedBinary(
+            "truncated integrity trailer".to_string(),
+        ));
+    }
+    let (artifact, trailer) = bytes.split_at(bytes.len() - trailer_len);
+    let checksum = &trailer[..CHECKSUM_LEN];
+    if blake3::hash(artifact).as_bytes() != checksum {
+        return Err(DeserializeError::CorruptedBinary(
+            "the checksum of the artifact does not match".to_string(),
+        ));
+    }
+
+    if let Some(trusted_keys) = trusted_keys {
+        if !signed {
+            return Err(DeserializeError::Untrusted(
+                "the artifact is not signed".to_string(),
+            ));
+        }
+        let public_key = &trailer[CHECKSUM_LEN..CHECKSUM_LEN + PUBLIC_KEY_LEN];
+        let public_key = PublicKey::from_bytes(public_key)
+            .map(ArtifactPublicKey)
+            .map_err(|_| DeserializeError::Untrusted("invalid public key".to_string()))?;
+        if !trusted_keys.contains(&public_key) {
+            return Err(DeserializeError::Untrusted(format!(
+                "the artifact is signed by an untrusted key: {:?}",
+                public_key
+            )));
+        }
+        let signature = &trailer[CHECKSUM_LEN + PUBLIC_KEY_LEN..trailer_len - 16];
+        let signature = Signature::try_from(signature)
+            .map_err(|_| DeserializeError::Untrusted("invalid signature".to_string()))?;
+        public_key.0.verify(checksum, &signature).map_err(|_| {
+            DeserializeError::Untrusted("the signature of the artifact is invalid".to_string())
+        })?;
+    }
+    Ok(artifact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTIFACT: &[u8] = b"wasmer-universal and some bytes";
+
+    fn trusted(keys: &[&ArtifactSigningKey]) -> TrustedKeys {
+        keys.iter().map(|key| key.public_key()).collect()
+    }
+
+    #[test]
+    fn checksum() {
+        let mut bytes = ARTIFACT.to_vec();
+        append_trailer(&mut bytes, None);
+        assert_eq!(check_trailer(&bytes, None).unwrap(), ARTIFACT);
+        assert!(matches!(
+            check_trailer(&bytes, Some(&TrustedKeys::new())),
+            Err(DeserializeError::Untrusted(_))
+        ));
+
+        bytes[3] ^= 1;
+        assert!(matches!(
+            check_trailer(&bytes, None),
+            Err(DeserializeError::CorruptedBinary(_))
+        ));
+
+        // Artifacts without a trailer are accepted unless a signature is
+        // required
+        assert_eq!(check_trailer(ARTIFACT, None).unwrap(), ARTIFACT);
+        assert!(check_trailer(ARTIFACT, Some(&TrustedKeys::new())).is_err());
+    }
+
+    #[test]
+    fn signature() {
+        let key = ArtifactSigningKey::from_bytes(&[7; 32]);
+        let other_key = ArtifactSigningKey::from_bytes(&[8; 32]);
+        let mut bytes = ARTIFACT.to_vec();
+        append_trailer(&mut bytes, Some(&key));
+
+        assert_eq!(check_trailer(&bytes, None).unwrap(), ARTIFACT);
+        assert_eq!(
+            check_trailer(&bytes, Some(&trusted(&[&other_key, &key]))).unwrap(),
+            ARTIFACT
+        );
+        let err = check_trailer(&bytes, Some(&trusted(&[&other_key]))).unwrap_err();
+        assert!(err.to_string().contains("untrusted key"), "{}", err);
+
+        // Re-signing the checksum with another key while claiming to be the
+        // trusted key is detected
+        let len = bytes.len();
+        let forged = other_key
+            .0
+            .sign(&bytes[ARTIFACT.len()..ARTIFACT.len() + 32]);
+        bytes[len - 80..len - 16].copy_from_slice(&forged.to_bytes());
+        let err = check_trailer(&bytes, Some(&trusted(&[&key]))).unwrap_err();
+        assert!(err.to_string().contains("signature"), "{}", err);
+    }
+}
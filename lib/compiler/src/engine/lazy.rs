@@ -0,0 +1,703 @@
+//! Lazy compilation of the functions of a module.
+//!
+//! In lazy mode the functions of a module are compiled the first time they
+//! are called, instead of when the module is created. Each function starts
+//! as a stub that jumps to the address stored in a slot of its own. The
+//! slots initially point to a thunk that compiles the function, stores the
+//! address of its code in the slot and jumps to it, so only the first call
+//! to a function goes through the compiler.
+//!
+//! With tiered compilation, the stubs also count down the calls to their
+//! function. The call that brings the count to zero goes through the thunk,
+//! which queues the function for recompilation with the optimizing compiler
+//! of the engine on a background thread. The optimized code then replaces
+//! the code in the slot of the function, which is shared by all the
+//! instances of the module: calls that already entered the previous code
+//! finish in it, later calls go to the optimized code.
+//!
+//! Only calls are counted, not the iterations of loops: a function that
+//! is called a few times but loops for long is not recompiled, and a
+//! running call never switches to the optimized code. Modules compiled
+//! with middlewares are not recompiled either, as the middlewares of the
+//! optimizing compiler don't have the state (like the indexes of the
+//! globals they add) that the middlewares of the engine's compiler
+//! derived from the module.
+//!
+//! The stubs, the thunk and the code of the compiled functions are all
+//! allocated in one region reserved when the module is created, as the
+//! compiled functions call each other through the stubs with 32-bit
+//! relative calls.
+//!
+//! The stubs and the thunk are written for x86_64 with the System V calling
+//! convention, other targets compile the functions of a module eagerly.
+
+use crate::engine::code_memory::CodeRegion;
+use crate::engine::link::link_function;
+use crate::{
+    libcall_trampoline_len, make_libcall_trampolines, register_function_frame_info, ArtifactBuild,
+    CodeMemory, Compiler, Engine, EngineInner, FunctionBodyData, GlobalFrameInfoRegistration,
+    ModuleEnvironment, ModuleMiddlewareChain, ModuleTranslationState,
+};
+use std::fmt;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    Compilation, CompileError, CompileModuleInfo, CompiledFunction, FunctionBody,
+    LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, TableIndex,
+    TableStyle, Target, Triple,
+};
+use wasmer_vm::{on_host_stack, raise_user_trap, resume_panic};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The size of the address range reserved for the code of a module compiled
+/// lazily, half the reach of a 32-bit relative call
+const CODE_REGION_SIZE: usize = 1 << 30;
+
+/// The recompilation of the hot functions of the modules that an engine
+/// compiles lazily, with an optimizing compiler.
+pub(crate) struct TierUp {
+    compiler: Mutex<Box<dyn Compiler>>,
+    call_threshold: u32,
+    /// The jobs of the background thread that recompiles the functions,
+    /// started with the first job
+    jobs: Mutex<Option<Sender<Job>>>,
+    /// The errors of the recompilations that failed, not yet taken
+    errors: Mutex<Vec<CompileError>>,
+}
+
+impl TierUp {
+    /// Recompiles with `compiler` the functions called `call_threshold`
+    /// times.
+    pub(crate) fn new(compiler: Box<dyn Compiler>, call_threshold: u32) -> Self {
+        Self {
+            compiler: Mutex::new(compiler),
+            call_threshold: call_threshold.max(1),
+            jobs: Mutex::new(None),
+            errors: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns true if the functions compiled by `compiler` can be replaced
+    /// by functions compiled by the optimizing compiler.
+    ///
+    /// Middlewares keep the state they derive from the module they
+    /// transform, which the middlewares of the other compiler don't have,
+    /// so neither compiler can use middlewares.
+    fn is_compatible_with(&self, compiler: &dyn Compiler) -> bool {
+        compiler.get_middlewares().is_empty()
+            && self.compiler.lock().unwrap().get_middlewares().is_empty()
+    }
+
+    /// Returns the errors of the recompilations that failed since the last
+    /// call.
+    pub(crate) fn take_errors(&self) -> Vec<CompileError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    /// Runs `job` on the background thread.
+    fn spawn(&self, job: Job) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.is_none() {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let worker = thread::Builder::new()
+                .name("wasmer-tier-up".to_string())
+                .spawn(move || {
+                    for job in receiver {
+                        // A function that can't be recompiled keeps its code
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                });
+            if worker.is_err() {
+                // Functions aren't recompiled without a thread to do it
+                return;
+            }
+            *jobs = Some(sender);
+        }
+        let _ = jobs.as_ref().unwrap().send(job);
+    }
+}
+
+impl fmt::Debug for TierUp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TierUp")
+            .field("call_threshold", &self.call_threshold)
+            .finish()
+    }
+}
+
+/// The body of a function, in the WebAssembly module it comes from.
+struct LazyBody {
+    range: Range<usize>,
+    module_offset: usize,
+}
+
+/// The code of a function compiled on its first call.
+struct CompiledCode {
+    _registration: Option<GlobalFrameInfoRegistration>,
+    /// This field must stay after the registration, for the code to be
+    /// freed after the frame info is unregistered.
+    _code_memory: CodeMemory,
+}
+
+/// The functions of a module that are compiled on their first call.
+pub(crate) struct LazyFunctions {
+    engine: Engine,
+    compile_info: CompileModuleInfo,
+    module: Arc<ModuleInfo>,
+    module_translation: ModuleTranslationState,
+    wasm: Box<[u8]>,
+    bodies: PrimaryMap<LocalFunctionIndex, LazyBody>,
+    /// The address that the stub of each function jumps to
+    slots: Box<[AtomicUsize]>,
+    /// Held while each function is compiled on its first call, for other
+    /// threads calling it to wait for its code instead of compiling it too
+    compiling: Box<[Mutex<()>]>,
+    /// The address of the stub of each function, that the compiled functions
+    /// call each other through
+    stubs: PrimaryMap<LocalFunctionIndex, usize>,
+    /// The code of the compiled functions, including the code replaced by
+    /// optimized code which might still be running
+    compiled: Mutex<Vec<CompiledCode>>,
+    /// The region that all the code of the module is allocated in
+    code_region: Arc<CodeRegion>,
+    thunk: usize,
+    _thunk_memory: CodeMemory,
+    tier_up: Option<Arc<TierUp>>,
+    /// The number of calls left before each function is recompiled, that
+    /// the stubs count down
+    counters: Box<[AtomicU32]>,
+    /// Whether each function was queued for recompilation
+    tier_up_requested: Box<[AtomicBool]>,
+}
+
+impl LazyFunctions {
+    /// Returns true if the functions of modules compiled for `target` can
+    /// be compiled lazily.
+    pub(crate) fn is_supported(target: &Target) -> bool {
+        cfg!(all(target_arch = "x86_64", not(target_os = "windows")))
+            && *target.triple() == Triple::host()
+    }
+
+    /// Translates a module and compiles its trampolines, returning an
+    /// `ArtifactBuild` where the body of each function is a stub that
+    /// compiles the function on its first call.
+    ///
+    /// The stubs refer to the returned `LazyFunctions`, which must outlive
+    /// the code of the artifact. The artifact must be allocated in its
+    /// `code_memory`, and its `set_stubs` called once it is.
+    pub(crate) fn new(
+        engine: &Engine,
+        engine_inner: &mut EngineInner,
+        data: &[u8],
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<(ArtifactBuild, Arc<Self>), CompileError> {
+        let target = engine.target();
+        let translation = ModuleEnvironment::new()
+            .translate(data)
+            .map_err(CompileError::Wasm)?;
+        let compiler = engine_inner.compiler()?;
+
+        let mut module = translation.module;
+        compiler.get_middlewares().apply_on_module_info(&mut module);
+        let compile_info = CompileModuleInfo {
+            module,
+            features: engine_inner.features().clone(),
+            memory_styles,
+            table_styles,
+        };
+        // SAFETY: Calling `unwrap` is correct since `environ.translate()`
+        // above will write some data into `module_translation_state`.
+        let module_translation = translation.module_translation_state.unwrap();
+
+        // Only the trampolines are compiled now
+        let compilation = compiler.compile_module(
+            target,
+            &compile_info,
+            &module_translation,
+            PrimaryMap::new(),
+        )?;
+        let tier_up = engine
+            .tier_up()
+            .filter(|tier_up| tier_up.is_compatible_with(compiler))
+            .cloned();
+
+        let bodies = translation
+            .function_body_inputs
+            .values()
+            .map(|body| {
+                let start = body.data.as_ptr() as usize - data.as_ptr() as usize;
+                LazyBody {
+                    range: start..start + body.data.len(),
+                    module_offset: body.module_offset,
+                }
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+        let data_initializers = translation
+            .data_initializers
+            .iter()
+            .map(OwnedDataInitializer::new)
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+
+        let artifact_compile_info = CompileModuleInfo {
+            module: compile_info.module.clone(),
+            features: compile_info.features.clone(),
+            memory_styles: compile_info.memory_styles.clone(),
+            table_styles: compile_info.table_styles.clone(),
+        };
+        let call_threshold = tier_up.as_ref().map_or(0, |tier_up| tier_up.call_threshold);
+        let code_region = Arc::new(CodeRegion::reserve(CODE_REGION_SIZE).map_err(|message| {
+            CompileError::Resource(format!(
+                "failed to reserve memory for functions: {}",
+                message
+            ))
+        })?);
+        let mut lazy = Arc::new(Self {
+            engine: engine.clone(),
+            module: Arc::new(compile_info.module.clone()),
+            compile_info,
+            module_translation,
+            wasm: data.into(),
+            slots: bodies.values().map(|_| AtomicUsize::new(0)).collect(),
+            compiling: bodies.values().map(|_| Mutex::new(())).collect(),
+            counters: bodies
+                .values()
+                .map(|_| AtomicU32::new(call_threshold))
+                .collect(),
+            tier_up_requested: bodies.values().map(|_| AtomicBool::new(false)).collect(),
+            bodies,
+            stubs: PrimaryMap::new(),
+            compiled: Mutex::new(vec![]),
+            thunk: 0,
+            _thunk_memory: CodeMemory::new_in(&code_region),
+            code_region,
+            tier_up,
+        });
+
+        // The thunk refers to the `LazyFunctions`, which doesn't move from
+        // now on.
+        let thunk = FunctionBody {
+            body: thunk_code(Arc::as_ptr(&lazy) as usize, compile_function as usize),
+            unwind_info: None,
+        };
+        let this = Arc::get_mut(&mut lazy).unwrap();
+        let (functions, _, _) =
+            this._thunk_memory
+                .allocate(&[&thunk], &[], &[])
+                .map_err(|message| {
+                    CompileError::Resource(format!(
+                        "failed to allocate memory for functions: {}",
+                        message
+                    ))
+                })?;
+        this.thunk = functions[0].as_ptr() as usize;
+        this._thunk_memory.publish();
+        for slot in this.slots.iter() {
+            slot.store(this.thunk, Ordering::Relaxed);
+        }
+
+        let stubs = (0..lazy.slots.len())
+            .map(|index| {
+                let slot = &lazy.slots[index] as *const AtomicUsize as usize;
+                let body = match lazy.tier_up {
+                    Some(_) => {
+                        let counter = &lazy.counters[index] as *const AtomicU32 as usize;
+                        counting_stub_code(index as u32, slot, counter, lazy.thunk)
+                    }
+                    None => stub_code(index as u32, slot),
+                };
+                CompiledFunction {
+                    body: FunctionBody {
+                        body,
+                        unwind_info: None,
+                    },
+                    relocations: vec![],
+                    frame_info: Default::default(),
+                }
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+        let compilation = Compilation::new(
+            stubs,
+            compilation.get_custom_sections(),
+            compilation.get_function_call_trampolines(),
+            compilation.get_dynamic_function_trampolines(),
+            compilation.get_debug(),
+        );
+        let artifact = ArtifactBuild::from_compilation(
+            compilation,
+            artifact_compile_info,
+            data_initializers,
+            target,
+        );
+        Ok((artifact, lazy))
+    }
+
+    /// Returns a `CodeMemory` in the region of the module, that the code
+    /// of the artifact must be allocated in.
+    pub(crate) fn code_memory(&self) -> CodeMemory {
+        CodeMemory::new_in(&self.code_region)
+    }
+
+    /// Sets the address of the stubs of the functions, once they are
+    /// allocated.
+    pub(crate) fn set_stubs(lazy: &mut Arc<Self>, stubs: impl Iterator<Item = usize>) {
+        Arc::get_mut(lazy)
+            .expect("the stubs are set before the functions are called")
+            .stubs = stubs.collect();
+    }
+
+    /// Called by the thunk for the function at `index`: compiles it unless
+    /// it was compiled already, queues it for recompilation if it is hot,
+    /// and returns the address of its code.
+    fn enter(self: &Arc<Self>, index: LocalFunctionIndex) -> Result<usize, CompileError> {
+        let address = self.compile(index)?;
+        if let Some(tier_up) = &self.tier_up {
+            // The count is zero, or below if other threads called the
+            // function since
+            let count = self.counters[index.index()].load(Ordering::Relaxed);
+            if (count == 0 || count > tier_up.call_threshold)
+                && !self.tier_up_requested[index.index()].swap(true, Ordering::Relaxed)
+            {
+                let lazy = Arc::downgrade(self);
+                tier_up.spawn(Box::new(move || Self::tier_up(lazy, index)));
+            }
+        }
+        Ok(address)
+    }
+
+    /// Compiles the function at `index`, unless it was compiled already, and
+    /// returns the address of its code.
+    ///
+    /// Only the function is locked while it is compiled, other functions
+    /// of the module are compiled concurrently. The engine is locked only
+    /// while the code is loaded.
+    fn compile(&self, index: LocalFunctionIndex) -> Result<usize, CompileError> {
+        let _compiling = self.compiling[index.index()].lock().unwrap();
+        let slot = &self.slots[index.index()];
+        let address = slot.load(Ordering::Acquire);
+        if address != self.thunk {
+            // Compiled by another thread while this one was waiting
+            return Ok(address);
+        }
+
+        let compiler = self.engine.inner().shared_compiler()?;
+        let compilation = compiler.compile_function(
+            self.engine.target(),
+            &self.compile_info,
+            &self.module_translation,
+            index,
+            &self.body(index),
+        )?;
+        let (code, address) = self.load(&mut self.engine.inner_mut(), index, compilation)?;
+
+        self.compiled.lock().unwrap().push(code);
+        slot.store(address, Ordering::Release);
+        Ok(address)
+    }
+
+    /// Recompiles the function at `index` with the optimizing compiler and
+    /// replaces its code, unless the module was dropped since.
+    fn tier_up(lazy: Weak<Self>, index: LocalFunctionIndex) {
+        let lazy = match lazy.upgrade() {
+            Some(lazy) => lazy,
+            None => return,
+        };
+        let tier_up = lazy.tier_up.as_ref().unwrap();
+        let loaded = panic::catch_unwind(AssertUnwindSafe(|| {
+            let compilation = tier_up.compiler.lock().unwrap().compile_function(
+                lazy.engine.target(),
+                &lazy.compile_info,
+                &lazy.module_translation,
+                index,
+                &lazy.body(index),
+            )?;
+            lazy.load(&mut lazy.engine.inner_mut(), index, compilation)
+        }))
+        .unwrap_or_else(|_| {
+            Err(CompileError::Codegen(
+                "the optimizing compiler panicked".to_string(),
+            ))
+        });
+        match loaded {
+            Ok((code, address)) => {
+                lazy.compiled.lock().unwrap().push(code);
+                lazy.slots[index.index()].store(address, Ordering::Release);
+            }
+            // A function that can't be recompiled keeps its code
+            Err(error) => tier_up.errors.lock().unwrap().push(error),
+        }
+    }
+
+    fn body(&self, index: LocalFunctionIndex) -> FunctionBodyData<'_> {
+        let body = &self.bodies[index];
+        FunctionBodyData {
+            data: &self.wasm[body.range.clone()],
+            module_offset: body.module_offset,
+        }
+    }
+
+    /// Allocates, links and registers the code of the function at `index`,
+    /// and returns it with its address.
+    fn load(
+        &self,
+        engine_inner: &mut EngineInner,
+        index: LocalFunctionIndex,
+        compilation: Compilation,
+    ) -> Result<(CompiledCode, usize), CompileError> {
+        let target = self.engine.target();
+        let mut custom_sections = compilation.get_custom_sections();
+        let libcall_trampolines = custom_sections.push(make_libcall_trampolines(target));
+        let section_relocations = custom_sections
+            .values()
+            .map(|section| section.relocations.clone())
+            .collect::<PrimaryMap<_, _>>();
+        let mut code_memory = self.code_memory();
+        let (functions, _, _, sections) = engine_inner.allocate(
+            &mut code_memory,
+            &self.module,
+            &compilation.get_function_bodies(),
+            &PrimaryMap::new(),
+            &PrimaryMap::new(),
+            &custom_sections,
+        )?;
+
+        let function = LocalFunctionIndex::new(0);
+        let extent = &functions[function];
+        let code = *extent.ptr as usize;
+        let function_address = |i| if i == index { code } else { self.stubs[i] };
+        link_function(
+            code,
+            &compilation.get(function).relocations,
+            &function_address,
+            &sections,
+            &section_relocations,
+            libcall_trampolines,
+            libcall_trampoline_len(target),
+        )?;
+
+        let eh_frame = compilation.get_debug().map(|debug| unsafe {
+            std::slice::from_raw_parts(
+                *sections[debug.eh_frame],
+                custom_sections[debug.eh_frame].bytes.len(),
+            )
+        });
+        code_memory.publish();
+        code_memory
+            .unwind_registry_mut()
+            .publish(eh_frame)
+            .map_err(|e| {
+                CompileError::Resource(format!("Error while publishing the unwind code: {}", e))
+            })?;
+
+        let registration = register_function_frame_info(
+            self.module.clone(),
+            index,
+            extent,
+            compilation.get(function).frame_info.clone(),
+        );
+        let code_memory = CompiledCode {
+            _registration: registration,
+            _code_memory: code_memory,
+        };
+        Ok((code_memory, code))
+    }
+}
+
+/// Compiles the function at `index` and returns the address of its code,
+/// called by the thunk on the first call to a function, and on the call
+/// that makes it hot.
+///
+/// The function is compiled on the host stack. A compilation error is
+/// raised as a trap in the caller of the function.
+extern "C" fn compile_function(lazy: *const LazyFunctions, index: u32) -> usize {
+    // The artifact that owns the `LazyFunctions` outlives the calls to its
+    // functions.
+    let lazy = unsafe {
+        Arc::increment_strong_count(lazy);
+        Arc::from_raw(lazy)
+    };
+    let result = on_host_stack(|| {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            lazy.enter(LocalFunctionIndex::from_u32(index))
+        }))
+    });
+    drop(lazy);
+    match result {
+        Ok(Ok(address)) => address,
+        Ok(Err(error)) => unsafe { raise_user_trap(Box::new(error)) },
+        Err(panic) => unsafe { resume_panic(panic) },
+    }
+}
+
+/// The stub of the function at `index`, which jumps to the address stored
+/// at `slot` with the index of the function in `r11`.
+fn stub_code(index: u32, slot: usize) -> Vec<u8> {
+    let mut code = vec![];
+    // mov r11d, index
+    code.extend_from_slice(&[0x41, 0xbb]);
+    code.extend_from_slice(&index.to_le_bytes());
+    // movabs rax, slot
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&(slot as u64).to_le_bytes());
+    // jmp qword ptr [rax]
+    code.extend_from_slice(&[0xff, 0x20]);
+    code
+}
+
+/// The stub of the function at `index` with tiered compilation, which
+/// decrements the counter at `counter` and jumps to the address stored at
+/// `slot`, or to the `thunk` when the counter reaches zero.
+fn counting_stub_code(index: u32, slot: usize, counter: usize, thunk: usize) -> Vec<u8> {
+    let mut code = vec![];
+    // mov r11d, index
+    code.extend_from_slice(&[0x41, 0xbb]);
+    code.extend_from_slice(&index.to_le_bytes());
+    // movabs rax, counter
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&(counter as u64).to_le_bytes());
+    // sub dword ptr [rax], 1
+    // je thunk
+    code.extend_from_slice(&[0x83, 0x28, 0x01, 0x74, 0x0c]);
+    // movabs rax, slot
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&(slot as u64).to_le_bytes());
+    // jmp qword ptr [rax]
+    code.extend_from_slice(&[0xff, 0x20]);
+    // thunk: movabs rax, thunk
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&(thunk as u64).to_le_bytes());
+    // jmp rax
+    code.extend_from_slice(&[0xff, 0xe0]);
+    code
+}
+
+/// The thunk that the stubs of the functions jump to until they are
+/// compiled. It calls `callback(lazy, r11d)` and jumps to the address it
+/// returns, keeping the arguments of the function in their registers and
+/// on the stack.
+fn thunk_code(lazy: usize, callback: usize) -> Vec<u8> {
+    let mut code = vec![];
+    // push rbp
+    // mov rbp, rsp
+    // sub rsp, 0xb0
+    code.extend_from_slice(&[
+        0x55, 0x48, 0x89, 0xe5, 0x48, 0x81, 0xec, 0xb0, 0x00, 0x00, 0x00,
+    ]);
+    // Save the argument registers: rdi, rsi, rdx, rcx, r8 and r9 at
+    // [rsp + 0x00..0x30], xmm0 to xmm7 at [rsp + 0x30..0xb0]
+    code.extend_from_slice(&[
+        0x48, 0x89, 0x3c, 0x24, // mov [rsp], rdi
+        0x48, 0x89, 0x74, 0x24, 0x08, // mov [rsp + 0x08], rsi
+        0x48, 0x89, 0x54, 0x24, 0x10, // mov [rsp + 0x10], rdx
+        0x48, 0x89, 0x4c, 0x24, 0x18, // mov [rsp + 0x18], rcx
+        0x4c, 0x89, 0x44, 0x24, 0x20, // mov [rsp + 0x20], r8
+        0x4c, 0x89, 0x4c, 0x24, 0x28, // mov [rsp + 0x28], r9
+    ]);
+    for (xmm, offset) in (0..8u8).zip((0x30..).step_by(0x10)) {
+        // movdqu [rsp + offset], xmm
+        code.extend_from_slice(&movdqu(0x7f, xmm, offset));
+    }
+    // movabs rdi, lazy
+    code.extend_from_slice(&[0x48, 0xbf]);
+    code.extend_from_slice(&(lazy as u64).to_le_bytes());
+    // mov esi, r11d
+    code.extend_from_slice(&[0x44, 0x89, 0xde]);
+    // movabs rax, callback
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&(callback as u64).to_le_bytes());
+    // call rax
+    code.extend_from_slice(&[0xff, 0xd0]);
+    // Restore the argument registers
+    code.extend_from_slice(&[
+        0x48, 0x8b, 0x3c, 0x24, // mov rdi, [rsp]
+        0x48, 0x8b, 0x74, 0x24, 0x08, // mov rsi, [rsp + 0x08]
+        0x48, 0x8b, 0x54, 0x24, 0x10, // mov rdx, [rsp + 0x10]
+        0x48, 0x8b, 0x4c, 0x24, 0x18, // mov rcx, [rsp + 0x18]
+        0x4c, 0x8b, 0x44, 0x24, 0x20, // mov r8, [rsp + 0x20]
+        0x4c, 0x8b, 0x4c, 0x24, 0x28, // mov r9, [rsp + 0x28]
+    ]);
+    for (xmm, offset) in (0..8u8).zip((0x30..).step_by(0x10)) {
+        // movdqu xmm, [rsp + offset]
+        code.extend_from_slice(&movdqu(0x6f, xmm, offset));
+    }
+    // mov rsp, rbp
+    // pop rbp
+    // jmp rax
+    code.extend_from_slice(&[0x48, 0x89, 0xec, 0x5d, 0xff, 0xe0]);
+    code
+}
+
+/// Encodes `movdqu` between `xmm` and `[rsp + offset]`, with `opcode` 0x6f
+/// for a load and 0x7f for a store.
+fn movdqu(opcode: u8, xmm: u8, offset: u32) -> Vec<u8> {
+    let mut code = vec![0xf3, 0x0f, opcode];
+    if offset < 0x80 {
+        // ModRM with a 8-bit displacement, SIB for rsp
+        code.extend_from_slice(&[0x44 | (xmm << 3), 0x24, offset as u8]);
+    } else {
+        // ModRM with a 32-bit displacement, SIB for rsp
+        code.extend_from_slice(&[0x84 | (xmm << 3), 0x24]);
+        code.extend_from_slice(&offset.to_le_bytes());
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_stub() {
+        assert_eq!(
+            stub_code(0x1122_3344, 0x1122_3344_5566_7788),
+            [
+                0x41, 0xbb, 0x44, 0x33, 0x22, 0x11, 0x48, 0xb8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33,
+                0x22, 0x11, 0xff, 0x20
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_the_counting_stub() {
+        let code = counting_stub_code(1, 0x2222, 0x3333, 0x4444);
+        assert_eq!(code.len(), 0x2d);
+        assert_eq!(code[0x10..0x17], [0x83, 0x28, 0x01, 0x74, 0x0c, 0x48, 0xb8]);
+        assert_eq!(code[0x21..0x25], [0x48, 0xb8, 0x44, 0x44]);
+        assert_eq!(code[0x2b..], [0xff, 0xe0]);
+    }
+
+    #[test]
+    fn encodes_the_register_saves() {
+        assert_eq!(movdqu(0x7f, 0, 0x30), [0xf3, 0x0f, 0x7f, 0x44, 0x24, 0x30]);
+        assert_eq!(movdqu(0x7f, 4, 0x70), [0xf3, 0x0f, 0x7f, 0x64, 0x24, 0x70]);
+        assert_eq!(
+            movdqu(0x7f, 5, 0x80),
+            [0xf3, 0x0f, 0x7f, 0xac, 0x24, 0x80, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            movdqu(0x6f, 7, 0xa0),
+            [0xf3, 0x0f, 0x6f, 0xbc, 0x24, 0xa0, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn encodes_the_thunk() {
+        let code = thunk_code(0x1122_3344_5566_7788, 0x8877_6655_4433_2211);
+        assert_eq!(code.len(), 214);
+        assert_eq!(
+            code[0x61..0x7a],
+            [
+                0x48, 0xbf, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x44, 0x89, 0xde, 0x48,
+                0xb8, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0xff, 0xd0
+            ]
+        );
+        assert_eq!(code[208..], [0x48, 0x89, 0xec, 0x5d, 0xff, 0xe0]);
+    }
+}
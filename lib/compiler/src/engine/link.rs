@@ -2,23 +2,27 @@
 
 use crate::get_libcall_trampoline;
 use crate::FunctionExtent;
+#[cfg(feature = "compiler")]
+use std::convert::TryFrom;
 use std::ptr::{read_unaligned, write_unaligned};
 use wasmer_types::entity::PrimaryMap;
+#[cfg(feature = "compiler")]
+use wasmer_types::CompileError;
 use wasmer_types::{LocalFunctionIndex, ModuleInfo};
 use wasmer_types::{Relocation, RelocationKind, RelocationTarget, Relocations, SectionIndex};
 use wasmer_vm::libcalls::function_pointer;
 use wasmer_vm::SectionBodyPtr;
 
-fn apply_relocation(
-    body: usize,
+/// Returns the address that the relocation `r` refers to.
+fn relocation_target(
     r: &Relocation,
-    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    function_address: &dyn Fn(LocalFunctionIndex) -> usize,
     allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
     libcall_trampolines: SectionIndex,
     libcall_trampoline_len: usize,
-) {
-    let target_func_address: usize = match r.reloc_target {
-        RelocationTarget::LocalFunc(index) => *allocated_functions[index].ptr as usize,
+) -> usize {
+    match r.reloc_target {
+        RelocationTarget::LocalFunc(index) => function_address(index),
         RelocationTarget::LibCall(libcall) => {
             // Use the direct target of the libcall if the relocation supports
             // a full 64-bit address. Otherwise use a trampoline.
@@ -35,8 +39,10 @@ fn apply_relocation(
         RelocationTarget::CustomSection(custom_section) => {
             *allocated_sections[custom_section] as usize
         }
-    };
+    }
+}
 
+fn apply_relocation(body: usize, r: &Relocation, target_func_address: usize) {
     match r.kind {
         RelocationKind::Abs8 => unsafe {
             let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
@@ -111,30 +117,77 @@ pub fn link_module(
     libcall_trampolines: SectionIndex,
     trampoline_len: usize,
 ) {
-    for (i, section_relocs) in section_relocations.iter() {
-        let body = *allocated_sections[i] as usize;
-        for r in section_relocs {
-            apply_relocation(
-                body,
+    let function_address = |index| *allocated_functions[index].ptr as usize;
+    let relocations = section_relocations
+        .iter()
+        .map(|(i, relocs)| (*allocated_sections[i] as usize, relocs))
+        .chain(
+            function_relocations
+                .iter()
+                .map(|(i, relocs)| (*allocated_functions[i].ptr as usize, relocs)),
+        );
+    for (body, relocs) in relocations {
+        for r in relocs {
+            let target = relocation_target(
                 r,
-                allocated_functions,
+                &function_address,
                 allocated_sections,
                 libcall_trampolines,
                 trampoline_len,
             );
+            apply_relocation(body, r, target);
         }
     }
-    for (i, function_relocs) in function_relocations.iter() {
-        let body = *allocated_functions[i].ptr as usize;
-        for r in function_relocs {
-            apply_relocation(
-                body,
+}
+
+/// Links a function that was compiled apart from the other functions of
+/// its module, along with its custom sections.
+///
+/// Relocations to local functions are resolved with `function_address`.
+/// Unlike [`link_module`], this function fails instead of truncating the
+/// 32-bit relative relocations that cannot reach their target, as the
+/// function is not allocated next to the code it refers to.
+#[cfg(feature = "compiler")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn link_function(
+    body: usize,
+    relocations: &[Relocation],
+    function_address: &dyn Fn(LocalFunctionIndex) -> usize,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
+    libcall_trampolines: SectionIndex,
+    trampoline_len: usize,
+) -> Result<(), CompileError> {
+    let relocations = section_relocations
+        .iter()
+        .map(|(i, relocs)| (*allocated_sections[i] as usize, relocs.as_slice()))
+        .chain(std::iter::once((body, relocations)));
+    // Check every relocation before patching anything
+    let mut patches = vec![];
+    for (body, relocs) in relocations {
+        for r in relocs {
+            let target = relocation_target(
                 r,
-                allocated_functions,
+                function_address,
                 allocated_sections,
                 libcall_trampolines,
                 trampoline_len,
             );
+            if let RelocationKind::X86PCRel4 | RelocationKind::X86CallPCRel4 = r.kind {
+                let address = (body + r.offset as usize) as i64;
+                let delta = target as i64 + r.addend - address;
+                if i32::try_from(delta).is_err() {
+                    return Err(CompileError::Resource(format!(
+                        "the target of a relocation to {:?} is out of reach",
+                        r.reloc_target
+                    )));
+                }
+            }
+            patches.push((body, r, target));
         }
     }
+    for (body, r, target) in patches {
+        apply_relocation(body, r, target);
+    }
+    Ok(())
 }
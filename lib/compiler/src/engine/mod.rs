@@ -12,6 +12,9 @@ mod tunables;
 #[cfg(not(target_arch = "wasm32"))]
 mod artifact;
 #[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod artifact_cache;
+#[cfg(feature = "translator")]
 mod builder;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
@@ -20,7 +23,16 @@ mod code_memory;
 mod inner;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
+mod integrity;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+mod lazy;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
 mod link;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+mod streaming;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod unwind;
@@ -45,4 +57,10 @@ pub use self::code_memory::CodeMemory;
 pub use self::inner::{Engine, EngineInner};
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
+pub use self::integrity::{ArtifactPublicKey, ArtifactSigningKey, TrustedKeys};
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::link::link_module;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::streaming::StreamingCompilation;
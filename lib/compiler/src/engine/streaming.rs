@@ -0,0 +1,301 @@
+//! Compilation of a module while its bytes are read.
+//!
+//! The sections of a module are validated as they arrive. Once the sections
+//! before the code section are known, each function body is sent to a
+//! background thread that validates it and compiles it into a
+//! [`FunctionCache`] while the next bodies are read. When the whole module
+//! has arrived, it is compiled as usual with that cache, so only the
+//! functions that weren't compiled yet and the trampolines of the module are
+//! compiled then.
+//!
+//! The middlewares of the compiler are applied once, to the sections before
+//! the code section, and the module is compiled with the result, since the
+//! code of the functions compiled ahead depends on it. With a middleware
+//! that has no deterministic id, the functions can't be cached, so they are
+//! only validated while the module is read.
+
+use crate::compiler::wasm_features;
+use crate::{Artifact, Engine, FunctionBodyData, FunctionCache, ModuleEnvironment, Tunables};
+use crate::{ModuleFunctionCache, ModuleMiddlewareChain, ModuleTranslationState};
+use std::panic;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{CompileError, CompileModuleInfo, LocalFunctionIndex, ModuleInfo};
+use wasmparser::{
+    BinaryReaderError, Chunk, FuncValidator, FunctionBody, Parser, Payload, ValidPayload,
+    Validator, ValidatorResources,
+};
+
+/// The number of functions compiled at most at once by the worker, so that
+/// the engine isn't locked for long.
+const MAX_BATCH_SIZE: usize = 64;
+
+/// A function body received by a [`StreamingCompilation`].
+struct Body {
+    index: LocalFunctionIndex,
+    validator: FuncValidator<ValidatorResources>,
+    data: Vec<u8>,
+    module_offset: usize,
+}
+
+/// The thread that validates and compiles the function bodies of a module.
+struct Worker {
+    bodies: Sender<Body>,
+    thread: JoinHandle<Result<(), CompileError>>,
+}
+
+impl Worker {
+    /// Waits for the bodies sent so far to be validated, and returns the
+    /// first validation error.
+    fn join(self) -> Result<(), CompileError> {
+        drop(self.bodies);
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// The compilation of a module whose bytes arrive in chunks, for instance
+/// from a file or from the network.
+///
+/// The module is validated and its functions are compiled as the chunks
+/// are pushed, so that compiling a module overlaps with reading it.
+pub struct StreamingCompilation {
+    engine: Engine,
+    /// The bytes of the module received so far
+    data: Vec<u8>,
+    parser: Parser,
+    /// The number of bytes of `data` already parsed
+    parsed: usize,
+    validator: Validator,
+    function_cache: FunctionCache,
+    /// The sections before the code section with the middlewares applied,
+    /// if the functions are compiled while the module is read
+    module: Option<ModuleInfo>,
+    worker: Option<Worker>,
+    /// The number of function bodies received so far
+    num_bodies: usize,
+    /// Whether the end of the module was parsed
+    finished: bool,
+}
+
+impl StreamingCompilation {
+    /// Starts the compilation of a module with `engine`.
+    ///
+    /// The functions are compiled into the cache of the compiler of the
+    /// engine, if it has one.
+    pub fn new(engine: &Engine) -> Self {
+        let mut validator = Validator::new();
+        let function_cache = {
+            let inner = engine.inner();
+            validator.wasm_features(wasm_features(inner.features()));
+            inner
+                .compiler()
+                .ok()
+                .and_then(|compiler| compiler.function_cache())
+                .cloned()
+                .unwrap_or_default()
+        };
+        Self {
+            engine: engine.clone(),
+            data: vec![],
+            parser: Parser::new(0),
+            parsed: 0,
+            validator,
+            function_cache,
+            module: None,
+            worker: None,
+            num_bodies: 0,
+            finished: false,
+        }
+    }
+
+    /// Validates and compiles the next bytes of the module.
+    ///
+    /// The sections of the module are processed as soon as they are
+    /// complete, and the functions as soon as their body is complete.
+    pub fn push(&mut self, bytes: &[u8], tunables: &dyn Tunables) -> Result<(), CompileError> {
+        self.data.extend_from_slice(bytes);
+        self.parse(false, tunables)
+    }
+
+    /// Compiles the module, once all its bytes were pushed.
+    pub fn finish(mut self, tunables: &dyn Tunables) -> Result<Arc<Artifact>, CompileError> {
+        self.parse(true, tunables)?;
+        if let Some(worker) = self.worker.take() {
+            worker.join()?;
+        }
+        self.engine.compile_with_function_cache(
+            &self.data,
+            tunables,
+            Some(&self.function_cache),
+            self.module.take(),
+        )
+    }
+
+    /// Parses the sections and the function bodies that are complete in
+    /// the bytes received so far, or all the remaining bytes if `eof` is
+    /// set.
+    fn parse(&mut self, eof: bool, tunables: &dyn Tunables) -> Result<(), CompileError> {
+        loop {
+            if self.finished {
+                return Ok(());
+            }
+            let (payload, consumed) = match self
+                .parser
+                .parse(&self.data[self.parsed..], eof)
+                .map_err(validate_error)?
+            {
+                Chunk::NeedMoreData(_) => return Ok(()),
+                Chunk::Parsed { payload, consumed } => (payload, consumed),
+            };
+            match self.validator.payload(&payload).map_err(validate_error)? {
+                ValidPayload::Ok => {}
+                ValidPayload::Submodule(_) => {
+                    return Err(CompileError::UnsupportedFeature(
+                        "module linking".to_string(),
+                    ))
+                }
+                ValidPayload::Func(validator, body) => {
+                    let range = body.range();
+                    let body = Body {
+                        index: LocalFunctionIndex::new(self.num_bodies),
+                        validator,
+                        data: self.data[range.start..range.end].to_vec(),
+                        module_offset: range.start,
+                    };
+                    self.num_bodies += 1;
+                    let worker = self.worker.as_ref().unwrap();
+                    if worker.bodies.send(body).is_err() {
+                        // The worker stops at the first invalid function
+                        return self.worker.take().unwrap().join();
+                    }
+                }
+            }
+            match payload {
+                Payload::CodeSectionStart { .. } => {
+                    // The code section starts at the current position
+                    self.start_worker(tunables)?;
+                }
+                Payload::End => self.finished = true,
+                _ => {}
+            }
+            self.parsed += consumed;
+        }
+    }
+
+    /// Starts the worker that validates and compiles the function bodies of
+    /// the module, once the sections before the code section were parsed.
+    fn start_worker(&mut self, tunables: &dyn Tunables) -> Result<(), CompileError> {
+        let translation = ModuleEnvironment::new()
+            .translate(&self.data[..self.parsed])
+            .map_err(CompileError::Wasm)?;
+        let mut module = translation.module;
+        let (memory_styles, table_styles) = Artifact::module_styles(&module, tunables);
+        let (precompile, features) = {
+            let inner = self.engine.inner();
+            // The functions of modules compiled lazily are only compiled
+            // when they are called, and the functions compiled ahead are
+            // lost if the function cache can't keep them.
+            let precompile = !self.engine.lazy_compilation()
+                && inner
+                    .compiler()
+                    .map_or(false, ModuleFunctionCache::is_supported_by);
+            if precompile {
+                inner
+                    .compiler()?
+                    .get_middlewares()
+                    .apply_on_module_info(&mut module);
+                self.module = Some(module.clone());
+            }
+            (precompile, inner.features().clone())
+        };
+        let compile_info = CompileModuleInfo {
+            module,
+            features,
+            memory_styles,
+            table_styles,
+        };
+        // SAFETY: Calling `unwrap` is correct since `environ.translate()`
+        // above will write some data into `module_translation_state`.
+        let module_translation = translation.module_translation_state.unwrap();
+
+        let engine = self.engine.clone();
+        let function_cache = self.function_cache.clone();
+        let (bodies, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("wasmer-streaming-compilation".to_string())
+            .spawn(move || {
+                run_worker(
+                    &engine,
+                    &compile_info,
+                    &module_translation,
+                    &function_cache,
+                    receiver,
+                    precompile,
+                )
+            })
+            .map_err(|e| CompileError::Resource(e.to_string()))?;
+        self.worker = Some(Worker { bodies, thread });
+        Ok(())
+    }
+}
+
+/// Validates the function bodies received by `bodies`, and compiles them
+/// into `function_cache` if `precompile` is set.
+///
+/// The bodies that arrive while a batch of functions is compiled are
+/// compiled together, in parallel if the compiler supports it, up to
+/// [`MAX_BATCH_SIZE`] at once. The engine is only locked while a batch is
+/// compiled.
+fn run_worker(
+    engine: &Engine,
+    compile_info: &CompileModuleInfo,
+    module_translation: &ModuleTranslationState,
+    function_cache: &FunctionCache,
+    bodies: Receiver<Body>,
+    precompile: bool,
+) -> Result<(), CompileError> {
+    while let Ok(body) = bodies.recv() {
+        let mut batch = vec![body];
+        batch.extend(bodies.try_iter().take(MAX_BATCH_SIZE - 1));
+        for body in &mut batch {
+            body.validator
+                .validate(&FunctionBody::new(body.module_offset, &body.data))
+                .map_err(validate_error)?;
+        }
+        if !precompile {
+            continue;
+        }
+        let function_body_inputs = batch
+            .iter()
+            .map(|body| {
+                let input = FunctionBodyData {
+                    data: &body.data,
+                    module_offset: body.module_offset,
+                };
+                (body.index, input)
+            })
+            .collect::<Vec<_>>();
+        let inner = engine.inner();
+        if let Ok(compiler) = inner.compiler() {
+            // The functions that fail to compile now are compiled again
+            // with the module, which reports the error.
+            let _ = compiler.precompile_functions(
+                engine.target(),
+                compile_info,
+                module_translation,
+                &function_body_inputs,
+                function_cache,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_error(error: BinaryReaderError) -> CompileError {
+    CompileError::Validate(format!("{}", error))
+}
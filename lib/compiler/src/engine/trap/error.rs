@@ -222,6 +222,15 @@ impl RuntimeError {
         }
     }
 
+    /// Returns a reference to the error of the `RuntimeError`, if it is
+    /// a user error of type `T`.
+    pub fn downcast_ref<T: Error + 'static>(&self) -> Option<&T> {
+        match &self.inner.source {
+            RuntimeErrorSource::User(err) => err.downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
     /// Returns trap code, if it's a Trap
     pub fn to_trap(self) -> Option<TrapCode> {
         if let RuntimeErrorSource::Trap(trap_code) = self.inner.source {
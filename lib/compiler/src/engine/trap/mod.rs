@@ -1,6 +1,8 @@
 mod error;
 mod frame_info;
 pub use error::RuntimeError;
+#[cfg(feature = "compiler")]
+pub(crate) use frame_info::register_function as register_function_frame_info;
 pub use frame_info::{
     register as register_frame_info, FrameInfo, FunctionExtent, GlobalFrameInfoRegistration,
     FRAME_INFO,
@@ -3,7 +3,7 @@ use std::ptr::NonNull;
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType,
-    ModuleInfo, TableIndex, TableType,
+    ModuleInfo, Pages, TableIndex, TableType,
 };
 use wasmer_vm::{InternalStoreHandle, MemoryError, StoreObjects};
 use wasmer_vm::{MemoryStyle, TableStyle};
@@ -19,6 +19,12 @@ pub trait Tunables {
     /// Construct a `TableStyle` for the provided `TableType`
     fn table_style(&self, table: &TableType) -> TableStyle;
 
+    /// Returns the size that no memory created by these tunables can grow
+    /// past, or `None` if the memories are only limited by their type.
+    fn memory_limit(&self) -> Option<Pages> {
+        None
+    }
+
     /// Create a memory owned by the host given a [`MemoryType`] and a [`MemoryStyle`].
     fn create_host_memory(
         &self,
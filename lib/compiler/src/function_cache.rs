@@ -0,0 +1,991 @@
+//! Caching of the code generated for the functions of a module.
+//!
+//! Compilers look up each function body in a [`FunctionCache`] before
+//! compiling it, so that compiling a module that only differs in a few
+//! function bodies from a module that was compiled before only compiles the
+//! functions that changed.
+
+use crate::wasmparser::{Operator, TypeOrFuncType};
+use crate::{Compiler, FunctionBinaryReader, FunctionBodyData, MiddlewareBinaryReader};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    CompileError, CompileModuleInfo, CompiledFunction, FunctionIndex, LocalFunctionIndex,
+    ModuleInfo, SerializableFunction, SignatureIndex, SourceLoc, Target,
+};
+
+/// The number of functions a [`FunctionCache`] keeps in memory by default.
+const DEFAULT_MAX_LEN: usize = 1 << 16;
+
+/// Data that a compiler caches along with a compiled function, such as its
+/// unwind information.
+///
+/// Functions are only written to the [`FunctionCacheStore`] of a cache when
+/// their data can be encoded, the others are only cached in memory.
+pub trait FunctionCacheData: Clone + Send + Sync + 'static {
+    /// Encodes the data, or returns `None` if it can't be stored.
+    fn encode(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Decodes data encoded by [`FunctionCacheData::encode`].
+    fn decode(_bytes: &[u8]) -> Option<Self> {
+        None
+    }
+}
+
+impl FunctionCacheData for () {
+    fn encode(&self) -> Option<Vec<u8>> {
+        Some(vec![])
+    }
+
+    fn decode(_bytes: &[u8]) -> Option<Self> {
+        Some(())
+    }
+}
+
+/// A persistent store for the functions of a [`FunctionCache`], which lets
+/// processes reuse the functions compiled by the processes before them.
+///
+/// The cache ignores the errors of the store: a function that can't be
+/// loaded is compiled again.
+///
+/// # Safety
+///
+/// The entries are deserialized without being validated, so `load` must
+/// return the exact bytes given to `store` for the same key, or fail.
+pub unsafe trait FunctionCacheStore: Send + Sync {
+    /// Loads the entry stored with `key`.
+    fn load(&self, key: &[u8; 32]) -> io::Result<Vec<u8>>;
+
+    /// Stores `entry` with `key`, replacing the entry that was stored with
+    /// it before, if any.
+    fn store(&self, key: &[u8; 32], entry: &[u8]) -> io::Result<()>;
+}
+
+/// A function compiled before.
+struct CachedFunction {
+    function: CompiledFunction,
+    /// The offset of the function body in the module it was compiled from
+    module_offset: usize,
+    /// Data that the compiler keeps outside of the compiled function, such
+    /// as its unwind information
+    extra: Option<Arc<dyn Any + Send + Sync>>,
+    /// The value of the clock of the cache when the function was last used
+    last_used: u64,
+}
+
+/// The functions that a [`FunctionCache`] keeps in memory.
+struct Functions {
+    entries: HashMap<[u8; 32], CachedFunction>,
+    /// Incremented every time a function is used, to find the least
+    /// recently used ones
+    clock: u64,
+    max_len: usize,
+}
+
+impl Functions {
+    fn get(&mut self, key: &[u8; 32]) -> Option<&CachedFunction> {
+        self.clock += 1;
+        let clock = self.clock;
+        let cached = self.entries.get_mut(key)?;
+        cached.last_used = clock;
+        Some(cached)
+    }
+
+    fn insert(&mut self, key: [u8; 32], mut cached: CachedFunction) {
+        self.clock += 1;
+        cached.last_used = self.clock;
+        self.entries.insert(key, cached);
+        if self.entries.len() > self.max_len {
+            self.evict();
+        }
+    }
+
+    /// Evicts the least recently used functions, so that only three
+    /// quarters of `max_len` are left and the next insertions don't evict
+    /// again right away.
+    fn evict(&mut self) {
+        let keep = self.max_len - self.max_len / 4;
+        if keep == 0 {
+            self.entries.clear();
+            return;
+        }
+        if self.entries.len() <= keep {
+            return;
+        }
+        let mut last_used = self
+            .entries
+            .values()
+            .map(|cached| cached.last_used)
+            .collect::<Vec<_>>();
+        let first_kept = last_used.len() - keep;
+        let (_, &mut oldest_kept, _) = last_used.select_nth_unstable(first_kept);
+        self.entries
+            .retain(|_, cached| cached.last_used >= oldest_kept);
+    }
+}
+
+/// A cache of compiled functions, shared by the compilers it is given to.
+///
+/// Functions are indexed by their body and everything in the module and in
+/// the compiler configuration that changes the code generated for them, so a
+/// cache can be shared by compilers with different configurations.
+///
+/// The cache keeps up to [`FunctionCache::set_max_len`] functions in memory,
+/// evicting the least recently used ones. A cache created with
+/// [`FunctionCache::with_store`] also writes the functions to a
+/// [`FunctionCacheStore`], and looks up the functions it doesn't have in
+/// memory there, so that they are compiled once for all the processes using
+/// the store.
+///
+/// The cache is cheap to clone: the clones share the same functions.
+#[derive(Clone)]
+pub struct FunctionCache {
+    functions: Arc<Mutex<Functions>>,
+    store: Option<Arc<dyn FunctionCacheStore>>,
+}
+
+impl Default for FunctionCache {
+    fn default() -> Self {
+        Self {
+            functions: Arc::new(Mutex::new(Functions {
+                entries: HashMap::new(),
+                clock: 0,
+                max_len: DEFAULT_MAX_LEN,
+            })),
+            store: None,
+        }
+    }
+}
+
+impl FunctionCache {
+    /// Creates an empty cache, that lives in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty cache that persists the functions in `store`.
+    pub fn with_store(store: impl FunctionCacheStore + 'static) -> Self {
+        Self {
+            store: Some(Arc::new(store)),
+            ..Self::default()
+        }
+    }
+
+    /// Limits the number of functions kept in memory, 65536 by default.
+    ///
+    /// When the cache grows past the limit, the least recently used
+    /// functions are evicted from memory, but not from the store.
+    pub fn set_max_len(&self, max_len: usize) {
+        let mut functions = self.functions.lock().unwrap();
+        functions.max_len = max_len;
+        if functions.entries.len() > max_len {
+            functions.evict();
+        }
+    }
+
+    /// Returns the number of functions in memory.
+    pub fn len(&self) -> usize {
+        self.functions.lock().unwrap().entries.len()
+    }
+
+    /// Returns true if the cache holds no function in memory.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the functions from memory.
+    pub fn clear(&self) {
+        self.functions.lock().unwrap().entries.clear();
+    }
+
+    /// Returns the function with `key` and the offset of its body, from
+    /// memory or from the store.
+    fn get<T: FunctionCacheData>(
+        &self,
+        key: &[u8; 32],
+    ) -> Option<(CompiledFunction, usize, Option<T>)> {
+        if let Some(cached) = self.functions.lock().unwrap().get(key) {
+            let extra = match &cached.extra {
+                Some(extra) => extra.downcast_ref::<T>().cloned(),
+                None => None,
+            };
+            if extra.is_some() == cached.extra.is_some() {
+                return Some((cached.function.clone(), cached.module_offset, extra));
+            }
+        }
+
+        let entry = self.store.as_ref()?.load(key).ok()?;
+        // Safe as the store returns the entries it was given
+        let stored = unsafe { SerializableFunction::deserialize(&entry) }.ok()?;
+        let extra = match &stored.extra {
+            Some(extra) => Some(T::decode(extra)?),
+            None => None,
+        };
+        let module_offset = stored.module_offset as usize;
+        self.insert_in_memory(*key, &stored.function, module_offset, &extra);
+        Some((stored.function, module_offset, extra))
+    }
+
+    /// Caches a function in memory and in the store.
+    fn insert<T: FunctionCacheData>(
+        &self,
+        key: [u8; 32],
+        function: &CompiledFunction,
+        module_offset: usize,
+        extra: &Option<T>,
+    ) {
+        self.insert_in_memory(key, function, module_offset, extra);
+        let store = match &self.store {
+            Some(store) => store,
+            None => return,
+        };
+        let encoded_extra = match extra {
+            Some(extra) => match extra.encode() {
+                Some(encoded) => Some(encoded),
+                None => return,
+            },
+            None => None,
+        };
+        let stored = SerializableFunction {
+            function: function.clone(),
+            module_offset: module_offset as u64,
+            extra: encoded_extra,
+        };
+        if let Ok(entry) = stored.serialize() {
+            let _ = store.store(&key, &entry);
+        }
+    }
+
+    fn insert_in_memory<T: FunctionCacheData>(
+        &self,
+        key: [u8; 32],
+        function: &CompiledFunction,
+        module_offset: usize,
+        extra: &Option<T>,
+    ) {
+        let cached = CachedFunction {
+            function: function.clone(),
+            module_offset,
+            extra: extra
+                .clone()
+                .map(|extra| Arc::new(extra) as Arc<dyn Any + Send + Sync>),
+            last_used: 0,
+        };
+        self.functions.lock().unwrap().insert(key, cached);
+    }
+}
+
+impl fmt::Debug for FunctionCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionCache")
+            .field("len", &self.len())
+            .field("persistent", &self.store.is_some())
+            .finish()
+    }
+}
+
+/// The view of a [`FunctionCache`] used to compile the functions of one
+/// module.
+pub struct ModuleFunctionCache<'a> {
+    cache: Option<&'a FunctionCache>,
+    module: &'a ModuleInfo,
+    /// Hash of everything that the code of a function depends on, other
+    /// than its own body and the types it refers to
+    module_hash: [u8; 32],
+}
+
+impl<'a> ModuleFunctionCache<'a> {
+    /// Creates the view of `cache` for compiling the functions of a module
+    /// with `compiler`. No function is cached when `cache` is `None`, or
+    /// when a middleware of `compiler` has no deterministic id.
+    pub fn new(
+        cache: Option<&'a FunctionCache>,
+        compiler: &dyn Compiler,
+        target: &Target,
+        compile_info: &'a CompileModuleInfo,
+    ) -> Self {
+        let cache = cache.filter(|_| Self::is_supported_by(compiler));
+        let module_hash = match cache {
+            Some(_) => Self::module_hash(compiler, target, compile_info),
+            None => [0; 32],
+        };
+        Self {
+            cache,
+            module: &compile_info.module,
+            module_hash,
+        }
+    }
+
+    /// Returns true if the functions compiled by `compiler` can be cached,
+    /// that is if all its middlewares have a deterministic id.
+    pub(crate) fn is_supported_by(compiler: &dyn Compiler) -> bool {
+        compiler
+            .get_middlewares()
+            .iter()
+            .all(|middleware| middleware.deterministic_id().is_some())
+    }
+
+    fn module_hash(
+        compiler: &dyn Compiler,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+    ) -> [u8; 32] {
+        let module = &compile_info.module;
+        let mut hasher = blake3::Hasher::new();
+        let mut update = |part: &str| {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        };
+        // The layout of the stored functions changes between versions
+        update(crate::VERSION);
+        update(&format!("{}-{:?}", target.triple(), target.cpu_features()));
+        update(&compiler.deterministic_id());
+        for middleware in compiler.get_middlewares() {
+            update(&middleware.deterministic_id().unwrap_or_default());
+        }
+        update(&format!("{:?}", compile_info.features));
+        update(&format!(
+            "{:?}{:?}",
+            compile_info.memory_styles, compile_info.table_styles
+        ));
+        // The layout of the `VMContext`, through which the code of a
+        // function reaches the imports, tables, memories and globals. The
+        // types of the functions are only hashed by the functions that refer
+        // to them, so that adding a function leaves the others cached.
+        update(&format!(
+            "{:?}{:?}{:?}{:?}",
+            module.globals, module.volatile_globals, module.memories, module.tables
+        ));
+        update(&format!(
+            "{}-{}-{}-{}-{}",
+            module.signatures.len(),
+            module.num_imported_functions,
+            module.num_imported_tables,
+            module.num_imported_memories,
+            module.num_imported_globals
+        ));
+        hasher.finalize().into()
+    }
+
+    fn key(&self, index: LocalFunctionIndex, input: &FunctionBodyData<'_>) -> [u8; 32] {
+        let module = self.module;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.module_hash);
+        hasher.update(&(index.index() as u64).to_le_bytes());
+        let mut update_signature = |signature: Option<SignatureIndex>| {
+            let part = match signature.and_then(|signature| module.signatures.get(signature)) {
+                Some(signature) => format!("{:?}", signature),
+                None => String::new(),
+            };
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        };
+        let function_signature = |function| module.functions.get(function).copied();
+        update_signature(function_signature(module.func_index(index)));
+        // The types of the called functions and blocks are only referred to
+        // by index in the body. An invalid body is never cached, so reading
+        // it stops at the first error.
+        let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+        let num_locals = reader.read_local_count().unwrap_or(0);
+        let locals_read = (0..num_locals).all(|_| reader.read_local_decl().is_ok());
+        while locals_read && !reader.eof() {
+            let signature = match reader.read_operator() {
+                Ok(Operator::Call { function_index })
+                | Ok(Operator::ReturnCall { function_index })
+                | Ok(Operator::RefFunc { function_index }) => {
+                    function_signature(FunctionIndex::from_u32(function_index))
+                }
+                Ok(Operator::CallIndirect { index, .. })
+                | Ok(Operator::ReturnCallIndirect { index, .. })
+                | Ok(Operator::Block {
+                    ty: TypeOrFuncType::FuncType(index),
+                })
+                | Ok(Operator::Loop {
+                    ty: TypeOrFuncType::FuncType(index),
+                })
+                | Ok(Operator::If {
+                    ty: TypeOrFuncType::FuncType(index),
+                })
+                | Ok(Operator::Try {
+                    ty: TypeOrFuncType::FuncType(index),
+                }) => Some(SignatureIndex::from_u32(index)),
+                Ok(_) => continue,
+                Err(_) => break,
+            };
+            update_signature(signature);
+        }
+        hasher.update(input.data);
+        hasher.finalize().into()
+    }
+
+    /// Returns the cached code of the function at `index`, or compiles it
+    /// with `compile` and caches the result.
+    ///
+    /// Besides the compiled function, `compile` can return data of its own
+    /// that is cached along with it.
+    pub fn get_or_compile<T, F>(
+        &self,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+        compile: F,
+    ) -> Result<(CompiledFunction, Option<T>), CompileError>
+    where
+        T: FunctionCacheData,
+        F: FnOnce() -> Result<(CompiledFunction, Option<T>), CompileError>,
+    {
+        let cache = match self.cache {
+            Some(cache) => cache,
+            None => return compile(),
+        };
+        let key = self.key(index, input);
+        if let Some((mut function, module_offset, extra)) = cache.get(&key) {
+            relocate_source_locations(&mut function, module_offset, input.module_offset);
+            return Ok((function, extra));
+        }
+
+        let (function, extra) = compile()?;
+        cache.insert(key, &function, input.module_offset, &extra);
+        Ok((function, extra))
+    }
+}
+
+/// The call frame instructions of a function, which compilers cache instead
+/// of its `FrameDescriptionEntry` as the address of the function is only
+/// known when the module is linked.
+#[cfg(feature = "gimli")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameInstructions {
+    /// The instructions, with the offset in the function from which they
+    /// apply
+    pub instructions: Vec<(u32, gimli::write::CallFrameInstruction)>,
+    /// The length of the function
+    pub len: u32,
+}
+
+#[cfg(feature = "gimli")]
+impl FrameInstructions {
+    /// Returns the instructions of `fde`, or `None` if they can't be
+    /// encoded.
+    pub fn from_fde(fde: gimli::write::FrameDescriptionEntry) -> Option<Self> {
+        // The entry doesn't give access to its instructions, so they are
+        // read back from the section it is written to
+        Self::decode(&Self::eh_frame(fde)?)
+    }
+
+    /// Builds the `FrameDescriptionEntry` of the function at `address`.
+    pub fn to_fde(&self, address: gimli::write::Address) -> gimli::write::FrameDescriptionEntry {
+        let mut fde = gimli::write::FrameDescriptionEntry::new(address, self.len);
+        for (offset, instruction) in &self.instructions {
+            fde.add_instruction(*offset, instruction.clone());
+        }
+        fde
+    }
+
+    /// Writes an `.eh_frame` section with the sole entry `fde`.
+    fn eh_frame(fde: gimli::write::FrameDescriptionEntry) -> Option<Vec<u8>> {
+        use gimli::write::{CommonInformationEntry, EhFrame, EndianVec, FrameTable};
+        use gimli::{Encoding, Format, LittleEndian, Register};
+
+        let encoding = Encoding {
+            address_size: 8,
+            format: Format::Dwarf32,
+            version: 1,
+        };
+        // Alignment factors of 1 store the offsets unchanged
+        let cie = CommonInformationEntry::new(encoding, 1, 1, Register(0));
+        let mut table = FrameTable::default();
+        let cie_id = table.add_cie(cie);
+        table.add_fde(cie_id, fde);
+        let mut eh_frame = EhFrame(EndianVec::new(LittleEndian));
+        table.write_eh_frame(&mut eh_frame).ok()?;
+        Some(eh_frame.0.into_vec())
+    }
+}
+
+#[cfg(feature = "gimli")]
+impl FunctionCacheData for FrameInstructions {
+    fn encode(&self) -> Option<Vec<u8>> {
+        Self::eh_frame(self.to_fde(gimli::write::Address::Constant(0)))
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        use gimli::read::{BaseAddresses, CallFrameInstruction as Read, CieOrFde, UnwindSection};
+        use gimli::write::CallFrameInstruction as Write;
+        use gimli::{EndianSlice, LittleEndian};
+        use std::convert::TryFrom;
+
+        fn raw(
+            expression: gimli::read::Expression<EndianSlice<'_, LittleEndian>>,
+        ) -> gimli::write::Expression {
+            gimli::write::Expression::raw(expression.0.slice().to_vec())
+        }
+
+        let mut eh_frame = gimli::read::EhFrame::new(bytes, LittleEndian);
+        eh_frame.set_address_size(8);
+        let bases = BaseAddresses::default();
+        let mut entries = eh_frame.entries(&bases);
+        let fde = loop {
+            if let CieOrFde::Fde(partial) = entries.next().ok()?? {
+                break partial
+                    .parse(|eh_frame, bases, offset| eh_frame.cie_from_offset(bases, offset))
+                    .ok()?;
+            }
+        };
+
+        let offset_i32 = |offset: u64| i32::try_from(offset).ok();
+        let factored_i32 = |offset: i64| i32::try_from(offset).ok();
+        let mut instructions = Vec::new();
+        let mut offset = 0u32;
+        let mut iter = fde.instructions(&eh_frame, &bases);
+        while let Some(instruction) = iter.next().ok()? {
+            let instruction = match instruction {
+                Read::SetLoc { address } => {
+                    offset = u32::try_from(address).ok()?;
+                    continue;
+                }
+                Read::AdvanceLoc { delta } => {
+                    offset = offset.checked_add(delta)?;
+                    continue;
+                }
+                Read::Nop => continue,
+                Read::DefCfa {
+                    register,
+                    offset: cfa_offset,
+                } => Write::Cfa(register, offset_i32(cfa_offset)?),
+                Read::DefCfaSf {
+                    register,
+                    factored_offset,
+                } => Write::Cfa(register, factored_i32(factored_offset)?),
+                Read::DefCfaRegister { register } => Write::CfaRegister(register),
+                Read::DefCfaOffset { offset: cfa_offset } => {
+                    Write::CfaOffset(offset_i32(cfa_offset)?)
+                }
+                Read::DefCfaOffsetSf { factored_offset } => {
+                    Write::CfaOffset(factored_i32(factored_offset)?)
+                }
+                Read::Undefined { register } => Write::Undefined(register),
+                Read::SameValue { register } => Write::SameValue(register),
+                Read::Offset {
+                    register,
+                    factored_offset,
+                } => Write::Offset(register, offset_i32(factored_offset)?),
+                Read::OffsetExtendedSf {
+                    register,
+                    factored_offset,
+                } => Write::Offset(register, factored_i32(factored_offset)?),
+                Read::ValOffset {
+                    register,
+                    factored_offset,
+                } => Write::ValOffset(register, offset_i32(factored_offset)?),
+                Read::ValOffsetSf {
+                    register,
+                    factored_offset,
+                } => Write::ValOffset(register, factored_i32(factored_offset)?),
+                Read::Register {
+                    dest_register,
+                    src_register,
+                } => Write::Register(dest_register, src_register),
+                Read::Restore { register } => Write::Restore(register),
+                Read::RememberState => Write::RememberState,
+                Read::RestoreState => Write::RestoreState,
+                Read::ArgsSize { size } => Write::ArgsSize(u32::try_from(size).ok()?),
+                Read::DefCfaExpression { expression } => Write::CfaExpression(raw(expression)),
+                Read::Expression {
+                    register,
+                    expression,
+                } => Write::Expression(register, raw(expression)),
+                Read::ValExpression {
+                    register,
+                    expression,
+                } => Write::ValExpression(register, raw(expression)),
+            };
+            instructions.push((offset, instruction));
+        }
+        Some(Self {
+            instructions,
+            len: u32::try_from(fde.len()).ok()?,
+        })
+    }
+}
+
+/// Moves the source locations of a function that was compiled from a body
+/// at `old_offset` in its module to a body at `new_offset`.
+fn relocate_source_locations(
+    function: &mut CompiledFunction,
+    old_offset: usize,
+    new_offset: usize,
+) {
+    if old_offset == new_offset {
+        return;
+    }
+    // Source locations are 32 bits and wrap around, like the offsets they
+    // are created from.
+    let delta = (new_offset as u32).wrapping_sub(old_offset as u32);
+    let relocate = |srcloc: &mut SourceLoc| {
+        if !srcloc.is_default() {
+            *srcloc = SourceLoc::new(srcloc.bits().wrapping_add(delta));
+        }
+    };
+    let address_map = &mut function.frame_info.address_map;
+    relocate(&mut address_map.start_srcloc);
+    relocate(&mut address_map.end_srcloc);
+    for instruction in &mut address_map.instructions {
+        relocate(&mut instruction.srcloc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FunctionMiddleware, ModuleMiddleware};
+    use std::cell::Cell;
+    use wasmer_types::{
+        CompiledFunctionFrameInfo, FunctionAddressMap, FunctionBody, FunctionType,
+        InstructionAddressMap, ModuleInfo, Target, Type,
+    };
+
+    struct DummyCompiler(&'static str, Vec<Arc<dyn ModuleMiddleware>>);
+
+    impl Compiler for DummyCompiler {
+        fn compile_module(
+            &self,
+            _target: &Target,
+            _module: &CompileModuleInfo,
+            _module_translation: &crate::ModuleTranslationState,
+            _function_body_inputs: wasmer_types::entity::PrimaryMap<
+                LocalFunctionIndex,
+                FunctionBodyData<'_>,
+            >,
+        ) -> Result<wasmer_types::Compilation, CompileError> {
+            unimplemented!()
+        }
+
+        fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
+            &self.1
+        }
+
+        fn deterministic_id(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    #[derive(Debug)]
+    struct DummyMiddleware(Option<&'static str>);
+
+    impl FunctionCacheData for usize {
+        fn encode(&self) -> Option<Vec<u8>> {
+            Some((*self as u64).to_le_bytes().to_vec())
+        }
+
+        fn decode(bytes: &[u8]) -> Option<Self> {
+            let mut encoded = [0; 8];
+            encoded.copy_from_slice(bytes);
+            Some(u64::from_le_bytes(encoded) as usize)
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(Arc<Mutex<HashMap<[u8; 32], Vec<u8>>>>);
+
+    unsafe impl FunctionCacheStore for MemoryStore {
+        fn load(&self, key: &[u8; 32]) -> io::Result<Vec<u8>> {
+            let entries = self.0.lock().unwrap();
+            let entry = entries.get(key).ok_or(io::ErrorKind::NotFound)?;
+            Ok(entry.clone())
+        }
+
+        fn store(&self, key: &[u8; 32], entry: &[u8]) -> io::Result<()> {
+            self.0.lock().unwrap().insert(*key, entry.to_vec());
+            Ok(())
+        }
+    }
+
+    impl ModuleMiddleware for DummyMiddleware {
+        fn generate_function_middleware(
+            &self,
+            _: &ModuleInfo,
+            _: LocalFunctionIndex,
+        ) -> Box<dyn FunctionMiddleware> {
+            unimplemented!()
+        }
+
+        fn deterministic_id(&self) -> Option<String> {
+            self.0.map(str::to_string)
+        }
+    }
+
+    fn compile_info() -> CompileModuleInfo {
+        CompileModuleInfo {
+            features: Default::default(),
+            module: ModuleInfo::new(),
+            memory_styles: Default::default(),
+            table_styles: Default::default(),
+        }
+    }
+
+    fn compiled(input: &FunctionBodyData<'_>) -> CompiledFunction {
+        let srcloc = |offset: usize| SourceLoc::new(offset as u32);
+        CompiledFunction {
+            body: FunctionBody {
+                body: input.data.to_vec(),
+                unwind_info: None,
+            },
+            relocations: vec![],
+            frame_info: CompiledFunctionFrameInfo {
+                traps: vec![],
+                address_map: FunctionAddressMap {
+                    instructions: vec![InstructionAddressMap {
+                        srcloc: srcloc(input.module_offset + 1),
+                        code_offset: 0,
+                        code_len: input.data.len(),
+                    }],
+                    start_srcloc: srcloc(input.module_offset),
+                    end_srcloc: srcloc(input.module_offset + input.data.len()),
+                    body_offset: 0,
+                    body_len: input.data.len(),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn compiles_changed_functions_only() {
+        let cache = FunctionCache::new();
+        let compiler = DummyCompiler("dummy", vec![]);
+        let target = Target::default();
+        let info = compile_info();
+        let module_cache = ModuleFunctionCache::new(Some(&cache), &compiler, &target, &info);
+
+        let compilations = Cell::new(0);
+        let compile = |index: usize, data: &[u8], module_offset: usize| {
+            let input = FunctionBodyData {
+                data,
+                module_offset,
+            };
+            module_cache
+                .get_or_compile(LocalFunctionIndex::new(index), &input, || {
+                    compilations.set(compilations.get() + 1);
+                    Ok((compiled(&input), Some(data.len())))
+                })
+                .unwrap()
+        };
+
+        compile(0, b"first", 10);
+        compile(1, b"second", 20);
+        assert_eq!(compilations.get(), 2);
+
+        // The same bodies, moved in the module
+        let (function, extra) = compile(1, b"second", 25);
+        assert_eq!(compilations.get(), 2);
+        assert_eq!(extra, Some(6));
+        let address_map = &function.frame_info.address_map;
+        assert_eq!(address_map.start_srcloc, SourceLoc::new(25));
+        assert_eq!(address_map.instructions[0].srcloc, SourceLoc::new(26));
+        assert_eq!(address_map.end_srcloc, SourceLoc::new(31));
+
+        // A changed body, and the same body at another index
+        compile(1, b"changed", 20);
+        compile(2, b"first", 30);
+        assert_eq!(compilations.get(), 4);
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn depends_on_the_referenced_types() {
+        let cache = FunctionCache::new();
+        let compiler = DummyCompiler("dummy", vec![]);
+        let target = Target::default();
+        let compilations = Cell::new(0);
+        let compile = |info: &CompileModuleInfo, index: usize, data: &[u8]| {
+            let module_cache = ModuleFunctionCache::new(Some(&cache), &compiler, &target, info);
+            let input = FunctionBodyData {
+                data,
+                module_offset: 0,
+            };
+            module_cache
+                .get_or_compile::<usize, _>(LocalFunctionIndex::new(index), &input, || {
+                    compilations.set(compilations.get() + 1);
+                    Ok((compiled(&input), None))
+                })
+                .unwrap();
+        };
+        let module = |params: &[Vec<Type>]| {
+            let mut info = compile_info();
+            let signatures = &mut info.module.signatures;
+            for params in params {
+                let signature = FunctionType::new(params.clone(), vec![]);
+                let signature = match signatures.iter().find(|(_, s)| **s == signature) {
+                    Some((index, _)) => index,
+                    None => signatures.push(signature),
+                };
+                info.module.functions.push(signature);
+            }
+            info
+        };
+        // An empty function, and a function calling it
+        let (callee, caller): (&[u8], &[u8]) = (&[0x00, 0x0b], &[0x00, 0x10, 0x00, 0x0b]);
+
+        let info = module(&[vec![], vec![Type::I32]]);
+        compile(&info, 0, callee);
+        compile(&info, 1, caller);
+        assert_eq!(compilations.get(), 2);
+
+        // An unrelated function
+        let info = module(&[vec![], vec![Type::I32], vec![Type::I32]]);
+        compile(&info, 0, callee);
+        compile(&info, 1, caller);
+        assert_eq!(compilations.get(), 2);
+        compile(&info, 2, callee);
+        assert_eq!(compilations.get(), 3);
+
+        // The called function changed its type
+        let info = module(&[vec![Type::I32], vec![Type::I32], vec![]]);
+        compile(&info, 1, caller);
+        assert_eq!(compilations.get(), 4);
+    }
+
+    #[test]
+    fn depends_on_the_compiler() {
+        let cache = FunctionCache::new();
+        let target = Target::default();
+        let info = compile_info();
+        let input = FunctionBodyData {
+            data: b"body",
+            module_offset: 0,
+        };
+        let mut compilations = 0;
+        for id in &["a", "b", "a"] {
+            let compiler = DummyCompiler(id, vec![]);
+            ModuleFunctionCache::new(Some(&cache), &compiler, &target, &info)
+                .get_or_compile::<(), _>(LocalFunctionIndex::new(0), &input, || {
+                    compilations += 1;
+                    Ok((compiled(&input), None))
+                })
+                .unwrap();
+        }
+        assert_eq!(compilations, 2);
+
+        // Nothing is cached without a cache
+        let compiler = DummyCompiler("a", vec![]);
+        ModuleFunctionCache::new(None, &compiler, &target, &info)
+            .get_or_compile::<(), _>(LocalFunctionIndex::new(0), &input, || {
+                compilations += 1;
+                Ok((compiled(&input), None))
+            })
+            .unwrap();
+        assert_eq!(compilations, 3);
+    }
+
+    #[test]
+    fn depends_on_the_middlewares() {
+        let cache = FunctionCache::new();
+        let target = Target::default();
+        let info = compile_info();
+        let input = FunctionBodyData {
+            data: b"body",
+            module_offset: 0,
+        };
+        let mut compilations = 0;
+        for id in &[Some("a"), Some("b"), Some("a"), None, None] {
+            let middleware = Arc::new(DummyMiddleware(*id));
+            let compiler = DummyCompiler("dummy", vec![middleware]);
+            ModuleFunctionCache::new(Some(&cache), &compiler, &target, &info)
+                .get_or_compile::<(), _>(LocalFunctionIndex::new(0), &input, || {
+                    compilations += 1;
+                    Ok((compiled(&input), None))
+                })
+                .unwrap();
+        }
+        // Middlewares without a deterministic id are never cached
+        assert_eq!(compilations, 4);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_functions() {
+        let cache = FunctionCache::new();
+        let compiler = DummyCompiler("dummy", vec![]);
+        let target = Target::default();
+        let info = compile_info();
+        let module_cache = ModuleFunctionCache::new(Some(&cache), &compiler, &target, &info);
+
+        let compilations = Cell::new(0);
+        let compile = |index: usize| {
+            let input = FunctionBodyData {
+                data: b"body",
+                module_offset: 0,
+            };
+            module_cache
+                .get_or_compile::<(), _>(LocalFunctionIndex::new(index), &input, || {
+                    compilations.set(compilations.get() + 1);
+                    Ok((compiled(&input), None))
+                })
+                .unwrap();
+        };
+
+        cache.set_max_len(4);
+        for index in 0..4 {
+            compile(index);
+        }
+        compile(0);
+        assert_eq!(compilations.get(), 4);
+
+        // Going past the limit evicts the least recently used functions,
+        // down to three quarters of the limit
+        compile(4);
+        assert_eq!(compilations.get(), 5);
+        assert_eq!(cache.len(), 3);
+        compile(0);
+        compile(4);
+        assert_eq!(compilations.get(), 5);
+        compile(1);
+        assert_eq!(compilations.get(), 6);
+    }
+
+    #[test]
+    fn loads_functions_from_the_store() {
+        let store = MemoryStore::default();
+        let entries = store.0.clone();
+        let first = FunctionCache::with_store(store);
+        let second = FunctionCache::with_store(MemoryStore(entries.clone()));
+        let compiler = DummyCompiler("dummy", vec![]);
+        let target = Target::default();
+        let info = compile_info();
+
+        let compilations = Cell::new(0);
+        let compile = |cache: &FunctionCache, module_offset: usize| {
+            let input = FunctionBodyData {
+                data: b"body",
+                module_offset,
+            };
+            ModuleFunctionCache::new(Some(cache), &compiler, &target, &info)
+                .get_or_compile(LocalFunctionIndex::new(0), &input, || {
+                    compilations.set(compilations.get() + 1);
+                    Ok((compiled(&input), Some(42usize)))
+                })
+                .unwrap()
+        };
+
+        compile(&first, 10);
+        assert_eq!(entries.lock().unwrap().len(), 1);
+        let (function, extra) = compile(&second, 20);
+        assert_eq!(compilations.get(), 1);
+        assert_eq!(extra, Some(42));
+        assert_eq!(
+            function.frame_info.address_map.start_srcloc,
+            SourceLoc::new(20)
+        );
+        assert_eq!(second.len(), 1);
+
+        // Functions missing from the store are compiled again
+        entries.lock().unwrap().clear();
+        compile(&FunctionCache::with_store(MemoryStore(entries)), 10);
+        assert_eq!(compilations.get(), 2);
+    }
+}
@@ -66,12 +66,29 @@ pub use self::artifact_builders::*;
 #[cfg(feature = "translator")]
 mod compiler;
 
+#[cfg(feature = "translator")]
+mod thread_pool;
 #[cfg(feature = "translator")]
 #[macro_use]
 mod translator;
 #[cfg(feature = "translator")]
 pub use crate::compiler::{Compiler, CompilerConfig};
 #[cfg(feature = "translator")]
+pub use crate::thread_pool::CompilationThreadPool;
+
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+mod function_cache;
+#[cfg(feature = "translator")]
+#[cfg(feature = "gimli")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::function_cache::FrameInstructions;
+#[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::function_cache::{
+    FunctionCache, FunctionCacheData, FunctionCacheStore, ModuleFunctionCache,
+};
+#[cfg(feature = "translator")]
 pub use crate::translator::{
     from_binaryreadererror_wasmerror, translate_module, wptype_to_type, FunctionBinaryReader,
     FunctionBodyData, FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState,
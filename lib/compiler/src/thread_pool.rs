@@ -0,0 +1,41 @@
+//! The thread pool that compilers compile the functions of a module in.
+
+use wasmer_types::CompileError;
+
+/// The thread pool of a compiler, as set by the `num_threads` option of its
+/// configuration.
+///
+/// The pool is built once, when the compiler is created, and reused for
+/// every module it compiles.
+pub struct CompilationThreadPool {
+    /// The configured pool, or the error that building it failed with,
+    /// which is reported when compiling
+    pool: Option<Result<rayon::ThreadPool, String>>,
+}
+
+impl CompilationThreadPool {
+    /// Builds a pool with `num_threads` threads, or uses the current
+    /// `rayon` pool if `num_threads` is `None`.
+    pub fn new(num_threads: Option<usize>) -> Self {
+        let pool = num_threads.map(|num_threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .map_err(|e| e.to_string())
+        });
+        Self { pool }
+    }
+
+    /// Runs `compile` in the pool, so that the parallel iterators it uses
+    /// run on the threads of the pool.
+    pub fn install<T: Send>(
+        &self,
+        compile: impl FnOnce() -> Result<T, CompileError> + Send,
+    ) -> Result<T, CompileError> {
+        match &self.pool {
+            Some(Ok(pool)) => pool.install(compile),
+            Some(Err(e)) => Err(CompileError::Resource(e.clone())),
+            None => compile(),
+        }
+    }
+}
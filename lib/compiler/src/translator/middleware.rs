@@ -18,13 +18,31 @@ pub trait ModuleMiddleware: Debug + Send + Sync {
     /// Here we generate a separate object for each function instead of executing directly on per-function operators,
     /// in order to enable concurrent middleware application. Takes immutable `&self` because this function can be called
     /// concurrently from multiple compilation threads.
+    ///
+    /// `module_info` is the module of the function, as transformed by the middlewares. The items that
+    /// `transform_module_info` adds must be looked up in it, as a middleware can be applied to several
+    /// modules and, with lazy compilation, functions are compiled after other modules were transformed.
     fn generate_function_middleware(
         &self,
+        module_info: &ModuleInfo,
         local_function_index: LocalFunctionIndex,
     ) -> Box<dyn FunctionMiddleware>;
 
     /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
     fn transform_module_info(&self, _: &mut ModuleInfo) {}
+
+    /// Returns an id that identifies the middleware and its configuration.
+    ///
+    /// Modules compiled with middlewares that have the same ids must be
+    /// interchangeable. The type of a middleware doesn't identify the
+    /// options or callbacks it was created with, so there is no default:
+    /// modules compiled with a middleware that returns `None` are never
+    /// cached, neither by the [`FunctionCache`] nor as serialized modules.
+    ///
+    /// [`FunctionCache`]: crate::FunctionCache
+    fn deterministic_id(&self) -> Option<String> {
+        None
+    }
 }
 
 /// A function middleware specialized for a single function.
@@ -65,6 +83,7 @@ pub trait ModuleMiddlewareChain {
     /// Generates a function middleware chain.
     fn generate_function_middleware_chain(
         &self,
+        module_info: &ModuleInfo,
         local_function_index: LocalFunctionIndex,
     ) -> Vec<Box<dyn FunctionMiddleware>>;
 
@@ -76,10 +95,11 @@ impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
     /// Generates a function middleware chain.
     fn generate_function_middleware_chain(
         &self,
+        module_info: &ModuleInfo,
         local_function_index: LocalFunctionIndex,
     ) -> Vec<Box<dyn FunctionMiddleware>> {
         self.iter()
-            .map(|x| x.generate_function_middleware(local_function_index))
+            .map(|x| x.generate_function_middleware(module_info, local_function_index))
             .collect()
     }
 
@@ -22,7 +22,8 @@ use wasmer_types::entity::packed_option::ReservedValue;
 use wasmer_types::entity::EntityRef;
 use wasmer_types::{
     DataIndex, ElemIndex, FunctionIndex, FunctionType, GlobalIndex, GlobalInit, GlobalType,
-    MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType, Type, V128,
+    InitExpr, InitExprOp, MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType,
+    Type, V128,
 };
 use wasmer_types::{WasmError, WasmResult};
 use wasmparser::{
@@ -226,6 +227,41 @@ pub fn parse_memory_section(
     Ok(())
 }
 
+/// Reads a constant expression computing an integer, which may use the
+/// operators of the extended constant expressions proposal.
+fn read_init_expr(init_expr: &wasmparser::InitExpr, section: &str) -> WasmResult<InitExpr> {
+    let mut init_expr_reader = init_expr.get_binary_reader();
+    let mut ops = Vec::new();
+    loop {
+        let op = match init_expr_reader
+            .read_operator()
+            .map_err(from_binaryreadererror_wasmerror)?
+        {
+            Operator::I32Const { value } => InitExprOp::I32Const(value),
+            Operator::I64Const { value } => InitExprOp::I64Const(value),
+            Operator::GlobalGet { global_index } => {
+                InitExprOp::GlobalGet(GlobalIndex::from_u32(global_index))
+            }
+            Operator::I32Add => InitExprOp::I32Add,
+            Operator::I32Sub => InitExprOp::I32Sub,
+            Operator::I32Mul => InitExprOp::I32Mul,
+            Operator::I64Add => InitExprOp::I64Add,
+            Operator::I64Sub => InitExprOp::I64Sub,
+            Operator::I64Mul => InitExprOp::I64Mul,
+            Operator::End => break,
+            ref s => {
+                return Err(wasm_unsupported!(
+                    "unsupported init expr in {} section: {:?}",
+                    section,
+                    s
+                ));
+            }
+        };
+        ops.push(op);
+    }
+    Ok(InitExpr::new(ops))
+}
+
 /// Parses the Global section of the wasm module.
 pub fn parse_global_section(
     globals: GlobalSectionReader,
@@ -242,10 +278,17 @@ pub fn parse_global_section(
             init_expr,
         } = entry.map_err(from_binaryreadererror_wasmerror)?;
         let mut init_expr_reader = init_expr.get_binary_reader();
-        let initializer = match init_expr_reader
+        let first = init_expr_reader
             .read_operator()
-            .map_err(from_binaryreadererror_wasmerror)?
-        {
+            .map_err(from_binaryreadererror_wasmerror)?;
+        let is_single_operator = matches!(
+            init_expr_reader
+                .read_operator()
+                .map_err(from_binaryreadererror_wasmerror)?,
+            Operator::End
+        );
+        let initializer = match first {
+            _ if !is_single_operator => GlobalInit::Expr(read_init_expr(&init_expr, "global")?),
             Operator::I32Const { value } => GlobalInit::I32Const(value),
             Operator::I64Const { value } => GlobalInit::I64Const(value),
             Operator::F32Const { value } => GlobalInit::F32Const(f32::from_bits(value.bits())),
@@ -377,26 +420,10 @@ pub fn parse_element_section<'data>(
                 table_index,
                 init_expr,
             } => {
-                let mut init_expr_reader = init_expr.get_binary_reader();
-                let (base, offset) = match init_expr_reader
-                    .read_operator()
-                    .map_err(from_binaryreadererror_wasmerror)?
-                {
-                    Operator::I32Const { value } => (None, value as u32 as usize),
-                    Operator::GlobalGet { global_index } => {
-                        (Some(GlobalIndex::from_u32(global_index)), 0)
-                    }
-                    ref s => {
-                        return Err(wasm_unsupported!(
-                            "unsupported init expr in element section: {:?}",
-                            s
-                        ));
-                    }
-                };
+                let offset_expr = read_init_expr(&init_expr, "element")?;
                 environ.declare_table_initializers(
                     TableIndex::from_u32(table_index),
-                    base,
-                    offset,
+                    offset_expr,
                     segments,
                 )?
             }
@@ -428,26 +455,10 @@ pub fn parse_data_section<'data>(
                 memory_index,
                 init_expr,
             } => {
-                let mut init_expr_reader = init_expr.get_binary_reader();
-                let (base, offset) = match init_expr_reader
-                    .read_operator()
-                    .map_err(from_binaryreadererror_wasmerror)?
-                {
-                    Operator::I32Const { value } => (None, value as u32 as usize),
-                    Operator::GlobalGet { global_index } => {
-                        (Some(GlobalIndex::from_u32(global_index)), 0)
-                    }
-                    ref s => {
-                        return Err(wasm_unsupported!(
-                            "unsupported init expr in data section: {:?}",
-                            s
-                        ))
-                    }
-                };
+                let offset_expr = read_init_expr(&init_expr, "data")?;
                 environ.declare_data_initialization(
                     MemoryIndex::from_u32(memory_index),
-                    base,
-                    offset,
+                    offset_expr,
                     data,
                 )?;
             }
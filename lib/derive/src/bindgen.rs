@@ -0,0 +1,845 @@
+use crate::wit::{self, Document, Func, Interface, Type, TypeDefKind, World, WorldItem};
+use crate::witx;
+use proc_macro2::{Span, TokenStream};
+use proc_macro_error::{abort, abort_call_site};
+use quote::{format_ident, quote};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Ident, Lit, LitStr, MetaNameValue, Token};
+
+/// Where the WIT document comes from.
+enum Source {
+    Path(LitStr),
+    Inline(LitStr),
+}
+
+/// The arguments of `bindgen!`.
+pub struct Input {
+    source: Source,
+    world: Option<LitStr>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let args = Punctuated::<MetaNameValue, Token![,]>::parse_terminated(input)?;
+        let mut source = None;
+        let mut world = None;
+        for arg in args {
+            let value = match &arg.lit {
+                Lit::Str(value) => value.clone(),
+                lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+            };
+            if arg.path.is_ident("path") && source.is_none() {
+                source = Some(Source::Path(value));
+            } else if arg.path.is_ident("inline") && source.is_none() {
+                source = Some(Source::Inline(value));
+            } else if arg.path.is_ident("world") && world.is_none() {
+                world = Some(value);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &arg.path,
+                    "expected one of `path` or `inline`, and optionally `world`",
+                ));
+            }
+        }
+        match source {
+            Some(source) => Ok(Self { source, world }),
+            None => Err(input.error("expected a `path` or `inline` argument")),
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof",
+    "unsized", "virtual", "yield",
+];
+
+pub(crate) fn snake(name: &str) -> Ident {
+    let name = name.replace('-', "_").to_lowercase();
+    match name.as_str() {
+        "self" | "super" | "crate" => format_ident!("{}_", name),
+        _ if KEYWORDS.contains(&name.as_str()) => Ident::new_raw(&name, Span::call_site()),
+        _ if name.starts_with(|c: char| c.is_ascii_digit()) => format_ident!("_{}", name),
+        _ => format_ident!("{}", name),
+    }
+}
+
+/// The name of a parameter of a function, which mustn't shadow the
+/// other parameters of the generated functions.
+pub(crate) fn param(name: &str) -> Ident {
+    match snake(name) {
+        ident if ident == "store" || ident == "env" => format_ident!("{}_", ident),
+        ident => ident,
+    }
+}
+
+pub(crate) fn camel(name: &str) -> Ident {
+    let name: String = name
+        .split(|c| c == '-' || c == '_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    match name.as_str() {
+        "Self" => format_ident!("Self_"),
+        _ if name.starts_with(|c: char| c.is_ascii_digit()) => format_ident!("_{}", name),
+        _ => format_ident!("{}", name),
+    }
+}
+
+pub(crate) fn docs(docs: &[String]) -> TokenStream {
+    quote! { #(#[doc = #docs])* }
+}
+
+/// Generates the bindings for an interface, or the functions of a
+/// world, whose named types are declared in `types`.
+struct Generator<'a> {
+    types: HashSet<&'a str>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(interface: Option<&'a Interface>) -> Self {
+        let mut types = HashSet::new();
+        for def in interface.into_iter().flat_map(|i| &i.types) {
+            if !types.insert(def.name.as_str()) {
+                abort_call_site!("type `{}` is defined twice", def.name);
+            }
+        }
+        Self { types }
+    }
+
+    fn ty(&self, ty: &Type) -> TokenStream {
+        match ty {
+            Type::Bool => quote!(bool),
+            Type::S8 => quote!(i8),
+            Type::U8 => quote!(u8),
+            Type::S16 => quote!(i16),
+            Type::U16 => quote!(u16),
+            Type::S32 => quote!(i32),
+            Type::U32 => quote!(u32),
+            Type::S64 => quote!(i64),
+            Type::U64 => quote!(u64),
+            Type::Float32 => quote!(f32),
+            Type::Float64 => quote!(f64),
+            Type::Char => quote!(char),
+            Type::String => quote!(::std::string::String),
+            Type::List(ty) => {
+                let ty = self.ty(ty);
+                quote!(::std::vec::Vec<#ty>)
+            }
+            Type::Tuple(types) => {
+                if types.is_empty() {
+                    abort_call_site!("empty tuples are not supported");
+                }
+                let types = types.iter().map(|ty| self.ty(ty));
+                quote!((#(#types,)*))
+            }
+            Type::Option(ty) => {
+                let ty = self.ty(ty);
+                quote!(::std::option::Option<#ty>)
+            }
+            Type::Result { ok, err } => {
+                let ok = self.payload(ok.as_deref());
+                let err = self.payload(err.as_deref());
+                quote!(::std::result::Result<#ok, #err>)
+            }
+            Type::Pointer(_) => abort_call_site!("pointers are not supported in components"),
+            Type::Named(name) => {
+                if !self.types.contains(name.as_str()) {
+                    abort_call_site!("unknown type `{}`", name);
+                }
+                let name = camel(name);
+                quote!(#name)
+            }
+        }
+    }
+
+    fn payload(&self, ty: Option<&Type>) -> TokenStream {
+        match ty {
+            Some(ty) => self.ty(ty),
+            None => quote!(()),
+        }
+    }
+
+    fn type_def(&self, def: &wit::TypeDef) -> TokenStream {
+        let name = camel(&def.name);
+        let wit_name = &def.name;
+        let doc = docs(&def.docs);
+        let unexpected = quote! {
+            value => Err(::wasmer::RuntimeError::new(format!(
+                "expected a value of type `{}`, found a value of kind `{}`",
+                #wit_name,
+                value.kind()
+            ))),
+        };
+        let (def, ty, into_value, from_value) = match &def.kind {
+            TypeDefKind::Alias(ty) => {
+                let ty = self.ty(ty);
+                return quote! {
+                    #doc
+                    pub type #name = #ty;
+                };
+            }
+            TypeDefKind::Record(fields) => {
+                let idents = fields.iter().map(|f| snake(&f.name)).collect::<Vec<_>>();
+                let names = fields.iter().map(|f| &f.name).collect::<Vec<_>>();
+                let types = fields.iter().map(|f| self.ty(&f.ty)).collect::<Vec<_>>();
+                let field_docs = fields.iter().map(|f| docs(&f.docs));
+                let count = fields.len();
+                (
+                    quote! {
+                        #[derive(Debug, Clone, PartialEq)]
+                        pub struct #name {
+                            #(#field_docs pub #idents: #types,)*
+                        }
+                    },
+                    quote! {
+                        ::wasmer::component::InterfaceType::Record(vec![
+                            #((#names.to_string(), <#types as ::wasmer::component::ComponentType>::ty()),)*
+                        ])
+                    },
+                    quote! {
+                        ::wasmer::component::ComponentValue::Record(vec![
+                            #((#names.to_string(), ::wasmer::component::ComponentType::into_value(self.#idents)),)*
+                        ])
+                    },
+                    quote! {
+                        match value {
+                            ::wasmer::component::ComponentValue::Record(fields) if fields.len() == #count => {
+                                #[allow(unused_mut, unused_variables)]
+                                let mut fields = fields.into_iter().map(|(_, value)| value);
+                                Ok(Self {
+                                    #(#idents: ::wasmer::component::ComponentType::from_value(fields.next().unwrap())?,)*
+                                })
+                            }
+                            #unexpected
+                        }
+                    },
+                )
+            }
+            TypeDefKind::Variant(cases) => {
+                let idents = cases.iter().map(|c| camel(&c.name)).collect::<Vec<_>>();
+                let names = cases.iter().map(|c| &c.name).collect::<Vec<_>>();
+                let payloads = cases
+                    .iter()
+                    .map(|c| self.payload(c.ty.as_ref()))
+                    .collect::<Vec<_>>();
+                let variants = cases.iter().zip(&idents).map(|(case, ident)| {
+                    let doc = docs(&case.docs);
+                    match &case.ty {
+                        Some(ty) => {
+                            let ty = self.ty(ty);
+                            quote!(#doc #ident(#ty))
+                        }
+                        None => quote!(#doc #ident),
+                    }
+                });
+                let into_cases = cases.iter().zip(&idents).zip(&names).map(
+                    |((case, ident), name)| match &case.ty {
+                        Some(_) => quote! {
+                            Self::#ident(payload) => (#name, Some(::std::boxed::Box::new(
+                                ::wasmer::component::ComponentType::into_value(payload),
+                            ))),
+                        },
+                        None => quote!(Self::#ident => (#name, None),),
+                    },
+                );
+                let from_cases = cases.iter().zip(&idents).zip(&names).zip(&payloads).map(
+                    |(((case, ident), name), payload)| {
+                        let payload = quote! {
+                            <#payload as ::wasmer::component::ComponentPayload>::from_payload(
+                                payload.map(|payload| *payload),
+                            )?
+                        };
+                        match &case.ty {
+                            Some(_) => quote!(#name => Ok(Self::#ident(#payload)),),
+                            None => quote! {
+                                #name => {
+                                    #payload;
+                                    Ok(Self::#ident)
+                                }
+                            },
+                        }
+                    },
+                );
+                (
+                    quote! {
+                        #[derive(Debug, Clone, PartialEq)]
+                        pub enum #name {
+                            #(#variants,)*
+                        }
+                    },
+                    quote! {
+                        ::wasmer::component::InterfaceType::Variant(vec![
+                            #((
+                                #names.to_string(),
+                                <#payloads as ::wasmer::component::ComponentPayload>::payload_ty(),
+                            ),)*
+                        ])
+                    },
+                    quote! {
+                        let (case, payload) = match self {
+                            #(#into_cases)*
+                        };
+                        ::wasmer::component::ComponentValue::Variant(case.to_string(), payload)
+                    },
+                    quote! {
+                        match value {
+                            ::wasmer::component::ComponentValue::Variant(case, payload) => {
+                                match case.as_str() {
+                                    #(#from_cases)*
+                                    case => Err(::wasmer::RuntimeError::new(format!(
+                                        "unknown case `{}` of `{}`",
+                                        case,
+                                        #wit_name
+                                    ))),
+                                }
+                            }
+                            #unexpected
+                        }
+                    },
+                )
+            }
+            TypeDefKind::Enum(cases) => {
+                let idents = cases.iter().map(|c| camel(&c.name)).collect::<Vec<_>>();
+                let names = cases.iter().map(|c| &c.name).collect::<Vec<_>>();
+                let case_docs = cases.iter().map(|c| docs(&c.docs));
+                (
+                    quote! {
+                        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                        pub enum #name {
+                            #(#case_docs #idents,)*
+                        }
+                    },
+                    quote! {
+                        ::wasmer::component::InterfaceType::Enum(vec![
+                            #(#names.to_string(),)*
+                        ])
+                    },
+                    quote! {
+                        ::wasmer::component::ComponentValue::Enum(match self {
+                            #(Self::#idents => #names,)*
+                        }.to_string())
+                    },
+                    quote! {
+                        match value {
+                            ::wasmer::component::ComponentValue::Enum(case) => match case.as_str() {
+                                #(#names => Ok(Self::#idents),)*
+                                case => Err(::wasmer::RuntimeError::new(format!(
+                                    "unknown case `{}` of `{}`",
+                                    case,
+                                    #wit_name
+                                ))),
+                            },
+                            #unexpected
+                        }
+                    },
+                )
+            }
+            TypeDefKind::Flags(flags) => {
+                let idents = flags.iter().map(|f| snake(&f.name)).collect::<Vec<_>>();
+                let names = flags.iter().map(|f| &f.name).collect::<Vec<_>>();
+                let flag_docs = flags.iter().map(|f| docs(&f.docs));
+                (
+                    quote! {
+                        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+                        pub struct #name {
+                            #(#flag_docs pub #idents: bool,)*
+                        }
+                    },
+                    quote! {
+                        ::wasmer::component::InterfaceType::Flags(vec![
+                            #(#names.to_string(),)*
+                        ])
+                    },
+                    quote! {
+                        #[allow(unused_mut)]
+                        let mut flags = ::std::vec::Vec::new();
+                        #(
+                            if self.#idents {
+                                flags.push(#names.to_string());
+                            }
+                        )*
+                        ::wasmer::component::ComponentValue::Flags(flags)
+                    },
+                    quote! {
+                        match value {
+                            ::wasmer::component::ComponentValue::Flags(flags) => {
+                                #[allow(unused_mut)]
+                                let mut value = Self::default();
+                                for flag in flags {
+                                    match flag.as_str() {
+                                        #(#names => value.#idents = true,)*
+                                        flag => return Err(::wasmer::RuntimeError::new(format!(
+                                            "unknown flag `{}` of `{}`",
+                                            flag,
+                                            #wit_name
+                                        ))),
+                                    }
+                                }
+                                Ok(value)
+                            }
+                            #unexpected
+                        }
+                    },
+                )
+            }
+            TypeDefKind::Union(types) => {
+                let idents = (0..types.len())
+                    .map(|i| format_ident!("V{}", i))
+                    .collect::<Vec<_>>();
+                let indices = (0..types.len() as u32).collect::<Vec<_>>();
+                let types = types.iter().map(|ty| self.ty(ty)).collect::<Vec<_>>();
+                (
+                    quote! {
+                        #[derive(Debug, Clone, PartialEq)]
+                        pub enum #name {
+                            #(#idents(#types),)*
+                        }
+                    },
+                    quote! {
+                        ::wasmer::component::InterfaceType::Union(vec![
+                            #(<#types as ::wasmer::component::ComponentType>::ty(),)*
+                        ])
+                    },
+                    quote! {
+                        match self {
+                            #(Self::#idents(value) => ::wasmer::component::ComponentValue::Union(
+                                #indices,
+                                ::std::boxed::Box::new(::wasmer::component::ComponentType::into_value(value)),
+                            ),)*
+                        }
+                    },
+                    quote! {
+                        match value {
+                            ::wasmer::component::ComponentValue::Union(index, value) => match index {
+                                #(#indices => Ok(Self::#idents(
+                                    ::wasmer::component::ComponentType::from_value(*value)?,
+                                )),)*
+                                index => Err(::wasmer::RuntimeError::new(format!(
+                                    "invalid case {} of `{}`",
+                                    index,
+                                    #wit_name
+                                ))),
+                            },
+                            #unexpected
+                        }
+                    },
+                )
+            }
+        };
+        quote! {
+            #doc
+            #def
+
+            impl ::wasmer::component::ComponentType for #name {
+                fn ty() -> ::wasmer::component::InterfaceType {
+                    #ty
+                }
+
+                fn into_value(self) -> ::wasmer::component::ComponentValue {
+                    #into_value
+                }
+
+                fn from_value(
+                    value: ::wasmer::component::ComponentValue,
+                ) -> ::std::result::Result<Self, ::wasmer::RuntimeError> {
+                    #from_value
+                }
+            }
+        }
+    }
+
+    /// Returns the parameter names and types, and the result type, of
+    /// a function.
+    fn signature(&self, func: &Func) -> (Vec<Ident>, Vec<TokenStream>, TokenStream) {
+        if func.params.len() > 12 {
+            abort_call_site!(
+                "function `{}` has more than 12 parameters, which is not supported",
+                func.name
+            );
+        }
+        let names = func.params.iter().map(|(name, _)| param(name)).collect();
+        let types = func.params.iter().map(|(_, ty)| self.ty(ty)).collect();
+        let result = self.payload(func.result.as_ref());
+        (names, types, result)
+    }
+
+    /// Generates the method of a host trait implementing an imported
+    /// function.
+    fn trait_method(&self, func: &Func) -> TokenStream {
+        let (names, types, result) = self.signature(func);
+        let doc = docs(&func.docs);
+        let method = snake(&func.name);
+        quote! {
+            #doc
+            fn #method(&mut self, #(#names: #types),*) -> ::std::result::Result<#result, ::wasmer::RuntimeError>;
+        }
+    }
+
+    /// Generates the definition of an imported function in
+    /// `imports`, forwarding calls to the method of the host trait.
+    fn define_import(&self, func: &Func, imports: &TokenStream) -> TokenStream {
+        let (names, types, _) = self.signature(func);
+        let method = snake(&func.name);
+        let name = &func.name;
+        quote! {
+            #imports.typed_func_with_env(
+                env,
+                #name,
+                |mut env: ::wasmer::FunctionEnvMut<T>, (#(#names,)*): (#(#types,)*)| {
+                    env.data_mut().#method(#(#names),*)
+                },
+            );
+        }
+    }
+
+    /// Generates the field, its initialization, and the method calling
+    /// an exported function.
+    fn export(&self, func: &Func) -> (TokenStream, TokenStream, TokenStream) {
+        let (names, types, result) = self.signature(func);
+        let doc = docs(&func.docs);
+        let ident = snake(&func.name);
+        let name = &func.name;
+        (
+            quote! {
+                #ident: ::wasmer::component::TypedComponentFunction<(#(#types,)*), #result>
+            },
+            quote! {
+                #ident: exports.get_typed_func(#name)?
+            },
+            quote! {
+                #doc
+                pub fn #ident(
+                    &self,
+                    store: &mut impl ::wasmer::AsStoreMut,
+                    #(#names: #types),*
+                ) -> ::std::result::Result<#result, ::wasmer::RuntimeError> {
+                    self.#ident.call(store, (#(#names,)*))
+                }
+            },
+        )
+    }
+}
+
+/// Generates the module of an instance imported or exported by a
+/// world.
+fn interface_module(name: &str, interface: &Interface, import: bool) -> TokenStream {
+    let generator = Generator::new(Some(interface));
+    let module = snake(name);
+    let ident = camel(name);
+    let doc = docs(&interface.docs);
+    let types = interface.types.iter().map(|def| generator.type_def(def));
+    let items = if import {
+        let methods = interface.funcs.iter().map(|f| generator.trait_method(f));
+        let defines = interface
+            .funcs
+            .iter()
+            .map(|f| generator.define_import(f, &quote!(imports)));
+        let trait_doc = format!(
+            "The host implementation of the `{}` instance imported by the component.",
+            name
+        );
+        quote! {
+            #[doc = #trait_doc]
+            pub trait #ident: Send + 'static {
+                #(#methods)*
+            }
+
+            /// Defines the functions of the instance in `imports`, the
+            /// imports of the instance, calling the methods of the
+            /// environment.
+            pub fn add_to_imports<T: #ident>(
+                imports: &mut ::wasmer::component::ComponentImports,
+                env: &::wasmer::FunctionEnv<T>,
+            ) {
+                #(#defines)*
+            }
+        }
+    } else {
+        let (fields, inits, methods): (Vec<_>, Vec<_>, Vec<_>) =
+            unzip3(interface.funcs.iter().map(|f| generator.export(f)));
+        let struct_doc = format!(
+            "The functions of the `{}` instance exported by the component.",
+            name
+        );
+        quote! {
+            #[doc = #struct_doc]
+            #[derive(Clone)]
+            pub struct #ident {
+                #(#fields,)*
+            }
+
+            impl #ident {
+                /// Gets the functions from the exports of the instance.
+                pub fn new(
+                    exports: &::wasmer::component::ComponentExports,
+                ) -> ::std::result::Result<Self, ::wasmer::ExportError> {
+                    Ok(Self {
+                        #(#inits,)*
+                    })
+                }
+
+                #(#methods)*
+            }
+        }
+    };
+    quote! {
+        #doc
+        #[allow(clippy::all)]
+        pub mod #module {
+            #(#types)*
+
+            #items
+        }
+    }
+}
+
+pub(crate) fn unzip3<A, B, C>(iter: impl Iterator<Item = (A, B, C)>) -> (Vec<A>, Vec<B>, Vec<C>) {
+    let (mut a, mut b, mut c) = (Vec::new(), Vec::new(), Vec::new());
+    for (x, y, z) in iter {
+        a.push(x);
+        b.push(y);
+        c.push(z);
+    }
+    (a, b, c)
+}
+
+fn world(world: &World) -> TokenStream {
+    let mut names = HashSet::new();
+    for item in world.imports.iter().chain(&world.exports) {
+        if !names.insert(snake(item.name()).to_string()) {
+            abort_call_site!(
+                "`{}` is imported or exported twice by world `{}`",
+                item.name(),
+                world.name
+            );
+        }
+    }
+
+    let generator = Generator::new(None);
+    let ident = camel(&world.name);
+    let doc = docs(&world.docs);
+    let mut modules = Vec::new();
+
+    // Imports.
+    let mut bounds = Vec::new();
+    let mut defines = Vec::new();
+    let mut import_methods = Vec::new();
+    for item in &world.imports {
+        match item {
+            WorldItem::Interface { name, interface } => {
+                modules.push(interface_module(name, interface, true));
+                let module = snake(name);
+                let ident = camel(name);
+                bounds.push(quote!(#module::#ident));
+                defines.push(quote! {
+                    #module::add_to_imports(imports.instance(#name), env);
+                });
+            }
+            WorldItem::Func(func) => {
+                import_methods.push(generator.trait_method(func));
+                defines.push(generator.define_import(func, &quote!(imports)));
+            }
+        }
+    }
+    let imports_trait = format_ident!("{}Imports", ident);
+    let imports_trait_def = if import_methods.is_empty() {
+        quote!()
+    } else {
+        bounds.push(quote!(#imports_trait));
+        let trait_doc = format!(
+            "The host implementation of the functions imported by the `{}` world.",
+            world.name
+        );
+        quote! {
+            #[doc = #trait_doc]
+            pub trait #imports_trait: Send + 'static {
+                #(#import_methods)*
+            }
+        }
+    };
+    let add_to_imports = if bounds.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            /// Defines the imports of the world in `imports`, calling
+            /// the methods of the environment.
+            pub fn add_to_imports<T>(
+                imports: &mut ::wasmer::component::ComponentImports,
+                env: &::wasmer::FunctionEnv<T>,
+            ) where
+                T: #(#bounds)+*,
+            {
+                #(#defines)*
+            }
+        }
+    };
+
+    // Exports.
+    let mut fields = Vec::new();
+    let mut inits = Vec::new();
+    let mut methods = Vec::new();
+    for item in &world.exports {
+        match item {
+            WorldItem::Interface { name, interface } => {
+                modules.push(interface_module(name, interface, false));
+                let module = snake(name);
+                let ty = camel(name);
+                let field = snake(name);
+                let method_doc = format!("The functions of the exported `{}` instance.", name);
+                fields.push(quote!(#field: #module::#ty));
+                inits.push(quote!(#field: #module::#ty::new(exports.get_instance(#name)?)?));
+                methods.push(quote! {
+                    #[doc = #method_doc]
+                    pub fn #field(&self) -> &#module::#ty {
+                        &self.#field
+                    }
+                });
+            }
+            WorldItem::Func(func) => {
+                let (field, init, method) = generator.export(func);
+                fields.push(field);
+                inits.push(init);
+                methods.push(method);
+            }
+        }
+    }
+
+    quote! {
+        #(#modules)*
+
+        #imports_trait_def
+
+        #doc
+        #[derive(Clone)]
+        pub struct #ident {
+            #(#fields,)*
+        }
+
+        impl #ident {
+            #add_to_imports
+
+            /// Instantiates the component, and gets the exports of the
+            /// world from the instance.
+            pub fn instantiate(
+                store: &mut impl ::wasmer::AsStoreMut,
+                component: &::wasmer::component::Component,
+                imports: &::wasmer::component::ComponentImports,
+            ) -> ::std::result::Result<
+                (Self, ::wasmer::component::ComponentInstance),
+                ::wasmer::component::ComponentInstantiationError,
+            > {
+                let instance = ::wasmer::component::ComponentInstance::new(store, component, imports)?;
+                Ok((Self::new(&instance.exports)?, instance))
+            }
+
+            /// Gets the exports of the world from the exports of a
+            /// component instance.
+            pub fn new(
+                exports: &::wasmer::component::ComponentExports,
+            ) -> ::std::result::Result<Self, ::wasmer::ExportError> {
+                Ok(Self {
+                    #(#inits,)*
+                })
+            }
+
+            #(#methods)*
+        }
+    }
+}
+
+/// Loads the WIT document of the input, or its WITX document if
+/// `allow_witx` is set, and selects its world. Returns the world and
+/// the items tracking the files it was read from.
+pub(crate) fn load(input: &Input, allow_witx: bool) -> (World, TokenStream) {
+    let root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into()));
+    let span = match &input.source {
+        Source::Path(lit) | Source::Inline(lit) => lit,
+    };
+    let mut files = Vec::new();
+    let mut read = |path: PathBuf| match std::fs::read_to_string(&path) {
+        Ok(source) => {
+            files.push(path.display().to_string());
+            Ok(source)
+        }
+        Err(e) => Err(wit::Error::new(format!(
+            "failed to read `{}`: {}",
+            path.display(),
+            e
+        ))),
+    };
+    let (source, dir, witx) = match &input.source {
+        Source::Path(path) => {
+            let full_path = root.join(path.value());
+            let source = match read(full_path.clone()) {
+                Ok(source) => source,
+                Err(e) => abort!(path, "{}", e),
+            };
+            let dir = full_path.parent().map_or(root, |dir| dir.to_path_buf());
+            (source, dir, path.value().ends_with(".witx"))
+        }
+        Source::Inline(source) => {
+            let source = source.value();
+            let witx = source.trim_start().starts_with(|c| c == '(' || c == ';');
+            (source, root, witx)
+        }
+    };
+    let document: Document = if witx {
+        if !allow_witx {
+            abort!(
+                span,
+                "WITX documents are only supported by `module_bindgen!`"
+            );
+        }
+        match witx::parse(&source, &mut |path| read(dir.join(path))) {
+            Ok(document) => document,
+            Err(e) => abort!(span, "invalid WITX document: {}", e),
+        }
+    } else {
+        match wit::parse(&source) {
+            Ok(document) => document,
+            Err(e) => abort!(span, "invalid WIT document: {}", e),
+        }
+    };
+    let world = match &input.world {
+        Some(name) => match document.worlds.into_iter().find(|w| w.name == name.value()) {
+            Some(world) => world,
+            None => abort!(name, "unknown world `{}`", name.value()),
+        },
+        None => {
+            let mut worlds = document.worlds;
+            match worlds.len() {
+                1 => worlds.remove(0),
+                0 => abort!(span, "the document doesn't define any world"),
+                _ => abort!(
+                    span,
+                    "the document defines several worlds, select one with `world = \"...\"`"
+                ),
+            }
+        }
+    };
+    // Rebuild the bindings when the documents change.
+    let tracking = quote! {
+        #(const _: &str = include_str!(#files);)*
+    };
+    (world, tracking)
+}
+
+pub fn bindgen(input: Input) -> TokenStream {
+    let (world, tracking) = load(&input, false);
+    let world = self::world(&world);
+    quote! {
+        #tracking
+        #world
+    }
+}
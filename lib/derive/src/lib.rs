@@ -3,7 +3,11 @@ extern crate proc_macro;
 use proc_macro_error::proc_macro_error;
 use syn::{parse_macro_input, DeriveInput};
 
+mod bindgen;
+mod module_bindgen;
 mod value_type;
+mod wit;
+mod witx;
 
 #[proc_macro_error]
 #[proc_macro_derive(ValueType)]
@@ -12,3 +16,103 @@ pub fn derive_value_type(input: proc_macro::TokenStream) -> proc_macro::TokenStr
     let gen = value_type::impl_value_type(&input);
     gen.into()
 }
+
+/// Generates typed bindings for a world of a [WIT] document.
+///
+/// The document is either read from a file, relative to the
+/// directory of the crate's manifest, or given inline:
+///
+/// ```ignore
+/// wasmer::component::bindgen!(path = "wit/plugin.wit", world = "plugin");
+/// wasmer::component::bindgen!(inline = "world plugin { export run: func() }");
+/// ```
+///
+/// `world` can be omitted if the document defines a single world.
+///
+/// For a world `plugin`, the macro generates:
+///
+/// * a `pub mod` per instance imported or exported by the world,
+///   containing the types of its interface, which implement
+///   `ComponentType`; an imported instance `host` gets a `Host` trait
+///   implemented by the host and an `add_to_imports` function, and an
+///   exported instance `api` gets an `Api` struct whose methods call
+///   the functions of the instance;
+/// * a `PluginImports` trait for the functions imported by the world;
+/// * a `Plugin` struct with an `add_to_imports` function, which
+///   defines the imports of the world with the methods of the
+///   environment, an `instantiate` function, and a method per function
+///   or instance exported by the world.
+///
+/// Resources, `use` declarations, default exports and functions with
+/// multiple results are not supported. Resources can't be used by
+/// components loaded with `wasmer::component` either, see the
+/// limitations of that module.
+///
+/// [WIT]: https://github.com/WebAssembly/component-model/blob/main/design/mvp/WIT.md
+#[proc_macro_error]
+#[proc_macro]
+pub fn bindgen(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as bindgen::Input);
+    bindgen::bindgen(input).into()
+}
+
+/// Generates typed bindings for the imports and exports of a core
+/// module, described by a world of a [WIT] document or by the modules
+/// of a [WITX] document.
+///
+/// The document is given as for [`bindgen!`](macro@bindgen); a
+/// document whose path ends with `.witx`, or whose inline source starts
+/// with `(` or `;`, is parsed as WITX:
+///
+/// ```ignore
+/// wasmer::module_bindgen!(path = "witx/host.witx");
+/// ```
+///
+/// For a world `host`, the macro generates:
+///
+/// * a `HostEnv` trait, giving the memory from which the arguments of
+///   host functions are decoded;
+/// * a `pub mod` per imported interface `fs`, containing the types of
+///   the interface, a `Fs` trait implemented by the host, and an
+///   `add_to_imports` function defining the functions of the `fs`
+///   namespace;
+/// * a `HostImports` trait for the functions imported by the world from
+///   the `env` namespace;
+/// * a `Host` struct with an `add_to_imports` function, a `new`
+///   function getting the exported functions from an instance, and a
+///   method per exported function.
+///
+/// Records, enums and flags are `ValueType`s, so they can be read from
+/// and written to memory with `WasmPtr`. Strings and lists are passed
+/// as a pointer and a length, records as a pointer, and records and
+/// the success value of a `result` whose error is an enum are returned
+/// through a pointer given as the last parameter. Arguments that can't
+/// be decoded make the host function trap.
+///
+/// # Limitations
+///
+/// Only the types with an obvious representation in a core module are
+/// supported, other types are rejected at compile time:
+///
+/// * parameters can be numbers, bools, chars, enums, flags, pointers,
+///   strings, lists and records, but not tuples, options, results or
+///   variants;
+/// * results can be numbers, bools, chars, enums, flags, pointers,
+///   records and `result`s whose error is an enum, but not strings,
+///   lists, tuples, options or variants;
+/// * record fields, list elements, the targets of pointers and the
+///   success values of `result`s are stored in memory, so they can't be
+///   bools, chars, strings or lists;
+/// * variants and unions can't be defined;
+/// * the parameters of exported functions must be numbers, bools, chars,
+///   enums, flags or pointers, and their results can't be returned
+///   through memory.
+///
+/// [WIT]: https://github.com/WebAssembly/component-model/blob/main/design/mvp/WIT.md
+/// [WITX]: https://github.com/WebAssembly/WASI/blob/main/legacy/tools/witx-docs.md
+#[proc_macro_error]
+#[proc_macro]
+pub fn module_bindgen(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as bindgen::Input);
+    module_bindgen::module_bindgen(input).into()
+}
@@ -0,0 +1,825 @@
+use crate::bindgen::{self, camel, docs, param, snake, unzip3, Input};
+use crate::wit::{Func, Interface, Type, TypeDef, TypeDefKind, World, WorldItem};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort_call_site;
+use quote::{format_ident, quote};
+use std::collections::HashMap;
+use syn::Ident;
+
+/// The name of the namespace of the functions imported directly by a
+/// world.
+const DEFAULT_NAMESPACE: &str = "env";
+
+/// A core value type.
+#[derive(Clone, Copy)]
+enum Core {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl Core {
+    fn ty(self) -> TokenStream {
+        match self {
+            Self::I32 => quote!(i32),
+            Self::I64 => quote!(i64),
+            Self::F32 => quote!(f32),
+            Self::F64 => quote!(f64),
+        }
+    }
+}
+
+/// How a value passed as a single core value is converted.
+#[derive(Clone)]
+enum Scalar {
+    /// An integer or a float, converted with `as`.
+    Number(TokenStream),
+    Bool,
+    Char,
+    /// An enum or flags, i.e. a newtype of an integer.
+    Newtype(TokenStream, TokenStream),
+    Pointer(TokenStream),
+}
+
+impl Scalar {
+    /// Converts the core value `value` to the Rust value.
+    fn lift(&self, value: &Ident) -> TokenStream {
+        match self {
+            Self::Number(ty) => quote!(#value as #ty),
+            Self::Bool => quote!(#value != 0),
+            Self::Char => quote! {
+                ::std::char::from_u32(#value as u32)
+                    .ok_or_else(|| ::wasmer::RuntimeError::new("invalid char"))?
+            },
+            Self::Newtype(ty, repr) => quote!(#ty(#value as #repr)),
+            Self::Pointer(_) => quote!(::wasmer::WasmPtr::new(#value as u32)),
+        }
+    }
+
+    /// Converts the Rust value `value` to a core value of type `core`.
+    fn lower(&self, value: TokenStream, core: Core) -> TokenStream {
+        let core = core.ty();
+        match self {
+            Self::Number(_) | Self::Bool | Self::Char => quote!(#value as #core),
+            Self::Newtype(..) => quote!(#value.0 as #core),
+            Self::Pointer(_) => quote!(#value.offset() as #core),
+        }
+    }
+}
+
+/// How a parameter is passed to a core function.
+enum ParamAbi {
+    Scalar(Core, Scalar),
+    /// A string, passed as a pointer and a length.
+    String,
+    /// A list, passed as a pointer and a length.
+    List(TokenStream),
+    /// A record, passed as a pointer.
+    Record(TokenStream),
+}
+
+/// How the result of a function is returned by a core function.
+enum ResultAbi {
+    None,
+    Scalar(Core, Scalar),
+    /// A value stored at a pointer passed as the last parameter.
+    Out(TokenStream),
+    /// A result whose error is an enum: the error is returned, `0`
+    /// meaning success, and the success value, if any, is stored at a
+    /// pointer passed as the last parameter.
+    Expected {
+        ok: Option<TokenStream>,
+        err: (Core, Scalar),
+    },
+}
+
+fn upper(name: &str) -> Ident {
+    let name = name.replace('-', "_").to_uppercase();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format_ident!("_{}", name)
+    } else {
+        format_ident!("{}", name)
+    }
+}
+
+/// Generates the bindings of the types and functions of an interface
+/// for core modules.
+struct Generator<'a> {
+    types: HashMap<&'a str, &'a TypeDef>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(interface: Option<&'a Interface>) -> Self {
+        let mut types = HashMap::new();
+        for def in interface.into_iter().flat_map(|i| &i.types) {
+            if types.insert(def.name.as_str(), def).is_some() {
+                abort_call_site!("type `{}` is defined twice", def.name);
+            }
+        }
+        Self { types }
+    }
+
+    fn def(&self, name: &str) -> &'a TypeDef {
+        match self.types.get(name) {
+            Some(def) => def,
+            None => abort_call_site!("unknown type `{}`", name),
+        }
+    }
+
+    /// Resolves the type aliases of `ty`.
+    fn resolve<'b>(&self, ty: &'b Type) -> &'b Type
+    where
+        'a: 'b,
+    {
+        match ty {
+            Type::Named(name) => match &self.def(name).kind {
+                TypeDefKind::Alias(ty) => self.resolve(ty),
+                _ => ty,
+            },
+            _ => ty,
+        }
+    }
+
+    /// The integer type representing an enum or flags.
+    fn repr(&self, def: &TypeDef) -> (TokenStream, Core) {
+        let (count, bits_per_case) = match &def.kind {
+            TypeDefKind::Enum(cases) => (cases.len(), false),
+            TypeDefKind::Flags(flags) => (flags.len(), true),
+            _ => unreachable!(),
+        };
+        let repr = match def.repr.as_ref().map(|ty| self.resolve(ty)) {
+            Some(Type::U8) => 8,
+            Some(Type::U16) => 16,
+            Some(Type::U32) => 32,
+            Some(Type::U64) => 64,
+            Some(_) => {
+                abort_call_site!("`{}` must be represented by an unsigned integer", def.name)
+            }
+            None if bits_per_case => match count {
+                0..=8 => 8,
+                9..=16 => 16,
+                17..=32 => 32,
+                33..=64 => 64,
+                _ => abort_call_site!("flags `{}` has more than 64 flags", def.name),
+            },
+            None if count <= 1 << 8 => 8,
+            None if count <= 1 << 16 => 16,
+            None => 32,
+        };
+        match repr {
+            8 => (quote!(u8), Core::I32),
+            16 => (quote!(u16), Core::I32),
+            32 => (quote!(u32), Core::I32),
+            _ => (quote!(u64), Core::I64),
+        }
+    }
+
+    /// The Rust type of a WIT type.
+    fn ty(&self, ty: &Type) -> TokenStream {
+        match ty {
+            Type::Bool => quote!(bool),
+            Type::S8 => quote!(i8),
+            Type::U8 => quote!(u8),
+            Type::S16 => quote!(i16),
+            Type::U16 => quote!(u16),
+            Type::S32 => quote!(i32),
+            Type::U32 => quote!(u32),
+            Type::S64 => quote!(i64),
+            Type::U64 => quote!(u64),
+            Type::Float32 => quote!(f32),
+            Type::Float64 => quote!(f64),
+            Type::Char => quote!(char),
+            Type::String => quote!(::std::string::String),
+            Type::List(ty) => {
+                let ty = self.memory_ty(ty);
+                quote!(::std::vec::Vec<#ty>)
+            }
+            Type::Pointer(ty) => {
+                let ty = self.memory_ty(ty);
+                quote!(::wasmer::WasmPtr<#ty>)
+            }
+            Type::Named(name) => {
+                self.def(name);
+                let name = camel(name);
+                quote!(#name)
+            }
+            Type::Tuple(_) | Type::Option(_) | Type::Result { .. } => {
+                abort_call_site!("tuples, options and results are not supported by module bindings")
+            }
+        }
+    }
+
+    /// The Rust type of a WIT type that can be stored in linear
+    /// memory.
+    fn memory_ty(&self, ty: &Type) -> TokenStream {
+        match self.resolve(ty) {
+            Type::Bool | Type::Char | Type::String | Type::List(_) => abort_call_site!(
+                "bools, chars, strings and lists can't be stored in linear memory, \
+                 use integers and pointers instead"
+            ),
+            _ => self.ty(ty),
+        }
+    }
+
+    fn scalar(&self, ty: &Type) -> Option<(Core, Scalar)> {
+        let number = |core, ty| Some((core, Scalar::Number(ty)));
+        match self.resolve(ty) {
+            Type::Bool => Some((Core::I32, Scalar::Bool)),
+            Type::Char => Some((Core::I32, Scalar::Char)),
+            Type::S8 => number(Core::I32, quote!(i8)),
+            Type::U8 => number(Core::I32, quote!(u8)),
+            Type::S16 => number(Core::I32, quote!(i16)),
+            Type::U16 => number(Core::I32, quote!(u16)),
+            Type::S32 => number(Core::I32, quote!(i32)),
+            Type::U32 => number(Core::I32, quote!(u32)),
+            Type::S64 => number(Core::I64, quote!(i64)),
+            Type::U64 => number(Core::I64, quote!(u64)),
+            Type::Float32 => number(Core::F32, quote!(f32)),
+            Type::Float64 => number(Core::F64, quote!(f64)),
+            Type::Pointer(_) => Some((Core::I32, Scalar::Pointer(self.ty(ty)))),
+            Type::Named(name) => {
+                let def = self.def(name);
+                match &def.kind {
+                    TypeDefKind::Enum(_) | TypeDefKind::Flags(_) => {
+                        let (repr, core) = self.repr(def);
+                        let ident = camel(name);
+                        Some((core, Scalar::Newtype(quote!(#ident), repr)))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn param_abi(&self, ty: &Type) -> ParamAbi {
+        if let Some((core, scalar)) = self.scalar(ty) {
+            return ParamAbi::Scalar(core, scalar);
+        }
+        match self.resolve(ty) {
+            Type::String => ParamAbi::String,
+            Type::List(elem) => ParamAbi::List(self.memory_ty(elem)),
+            Type::Named(_) => ParamAbi::Record(self.ty(ty)),
+            _ => abort_call_site!(
+                "tuples, options and results are not supported as parameters by module bindings"
+            ),
+        }
+    }
+
+    fn result_abi(&self, ty: Option<&Type>) -> ResultAbi {
+        let ty = match ty {
+            Some(ty) => ty,
+            None => return ResultAbi::None,
+        };
+        if let Some((core, scalar)) = self.scalar(ty) {
+            return ResultAbi::Scalar(core, scalar);
+        }
+        match self.resolve(ty) {
+            Type::Named(_) => ResultAbi::Out(self.memory_ty(ty)),
+            Type::Result { ok, err } => {
+                let err = match err.as_deref().map(|err| (err, self.resolve(err))) {
+                    Some((err, Type::Named(name)))
+                        if matches!(self.def(name).kind, TypeDefKind::Enum(_)) =>
+                    {
+                        self.scalar(err).unwrap()
+                    }
+                    _ => {
+                        abort_call_site!("the error of a result must be an enum in module bindings")
+                    }
+                };
+                ResultAbi::Expected {
+                    ok: ok.as_deref().map(|ok| self.memory_ty(ok)),
+                    err,
+                }
+            }
+            _ => abort_call_site!(
+                "strings, lists, tuples and options are not supported as results by module bindings"
+            ),
+        }
+    }
+
+    /// The Rust type returned by a function.
+    fn result_ty(&self, ty: Option<&Type>) -> TokenStream {
+        match ty.map(|ty| (ty, self.resolve(ty))) {
+            None => quote!(()),
+            Some((_, Type::Result { ok, err })) => {
+                let ok = match ok {
+                    Some(ok) => self.ty(ok),
+                    None => quote!(()),
+                };
+                let err = self.ty(err.as_ref().unwrap());
+                quote!(::std::result::Result<#ok, #err>)
+            }
+            Some((ty, _)) => self.ty(ty),
+        }
+    }
+
+    fn type_def(&self, def: &TypeDef) -> TokenStream {
+        let name = camel(&def.name);
+        let doc = docs(&def.docs);
+        match &def.kind {
+            TypeDefKind::Alias(ty) => {
+                let ty = self.ty(ty);
+                quote! {
+                    #doc
+                    pub type #name = #ty;
+                }
+            }
+            TypeDefKind::Record(fields) => {
+                let idents = fields.iter().map(|f| snake(&f.name));
+                let types = fields.iter().map(|f| self.memory_ty(&f.ty));
+                let field_docs = fields.iter().map(|f| docs(&f.docs));
+                quote! {
+                    #doc
+                    #[derive(Debug, Clone, Copy, PartialEq, ::wasmer::ValueType)]
+                    #[repr(C)]
+                    pub struct #name {
+                        #(#field_docs pub #idents: #types,)*
+                    }
+                }
+            }
+            TypeDefKind::Enum(cases) => {
+                let (repr, _) = self.repr(def);
+                let consts = cases.iter().map(|c| upper(&c.name)).collect::<Vec<_>>();
+                let names = cases.iter().map(|c| &c.name).collect::<Vec<_>>();
+                let case_docs = cases.iter().map(|c| docs(&c.docs));
+                let values = (0..cases.len()).map(|i| {
+                    let i = proc_macro2::Literal::u64_unsuffixed(i as u64);
+                    quote!(#i)
+                });
+                quote! {
+                    #doc
+                    #[derive(Clone, Copy, PartialEq, Eq, Hash, ::wasmer::ValueType)]
+                    #[repr(transparent)]
+                    pub struct #name(pub #repr);
+
+                    impl #name {
+                        #(#case_docs pub const #consts: Self = Self(#values);)*
+
+                        /// The name of the case, if the value is a valid case.
+                        pub fn name(self) -> ::std::option::Option<&'static str> {
+                            match self {
+                                #(Self::#consts => Some(#names),)*
+                                _ => None,
+                            }
+                        }
+                    }
+
+                    impl ::std::fmt::Debug for #name {
+                        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                            match self.name() {
+                                Some(name) => f.write_str(name),
+                                None => write!(f, "{}({})", stringify!(#name), self.0),
+                            }
+                        }
+                    }
+                }
+            }
+            TypeDefKind::Flags(flags) => {
+                let (repr, _) = self.repr(def);
+                let consts = flags.iter().map(|f| upper(&f.name));
+                let flag_docs = flags.iter().map(|f| docs(&f.docs));
+                let bits = (0..flags.len()).map(|i| {
+                    let i = proc_macro2::Literal::u64_unsuffixed(i as u64);
+                    quote!(#i)
+                });
+                quote! {
+                    #doc
+                    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ::wasmer::ValueType)]
+                    #[repr(transparent)]
+                    pub struct #name(pub #repr);
+
+                    impl #name {
+                        #(#flag_docs pub const #consts: Self = Self(1 << #bits);)*
+
+                        /// Returns whether all the flags of `other` are set.
+                        pub fn contains(self, other: Self) -> bool {
+                            self.0 & other.0 == other.0
+                        }
+                    }
+
+                    impl ::std::ops::BitOr for #name {
+                        type Output = Self;
+
+                        fn bitor(self, other: Self) -> Self {
+                            Self(self.0 | other.0)
+                        }
+                    }
+                }
+            }
+            TypeDefKind::Variant(_) | TypeDefKind::Union(_) => {
+                abort_call_site!(
+                    "`{}`: variants and unions are not supported by module bindings",
+                    def.name
+                )
+            }
+        }
+    }
+
+    /// Generates the method of a host trait implementing an imported
+    /// function.
+    fn trait_method(&self, func: &Func) -> TokenStream {
+        let names = func.params.iter().map(|(name, _)| param(name));
+        let types = func.params.iter().map(|(_, ty)| self.ty(ty));
+        let result = self.result_ty(func.result.as_ref());
+        let doc = docs(&func.docs);
+        let method = snake(&func.name);
+        quote! {
+            #doc
+            fn #method(
+                env: ::wasmer::FunctionEnvMut<'_, Self>,
+                #(#names: #types),*
+            ) -> ::std::result::Result<#result, ::wasmer::RuntimeError>;
+        }
+    }
+
+    /// Generates the definition of an imported function in `imports`,
+    /// decoding its arguments from the core values and the memory of
+    /// the instance.
+    fn define_import(
+        &self,
+        func: &Func,
+        namespace: &str,
+        host_trait: &TokenStream,
+        env_trait: &TokenStream,
+    ) -> TokenStream {
+        let mut core_params = Vec::new();
+        let mut args = Vec::new();
+        let mut lifts = Vec::new();
+        let mut uses_memory = false;
+        for (name, ty) in &func.params {
+            let arg = param(name);
+            let ptr = format_ident!("{}_ptr", arg);
+            let len = format_ident!("{}_len", arg);
+            match self.param_abi(ty) {
+                ParamAbi::Scalar(core, scalar) => {
+                    let core = core.ty();
+                    core_params.push(quote!(#arg: #core));
+                    let lift = scalar.lift(&arg);
+                    lifts.push(quote!(let #arg = #lift;));
+                }
+                ParamAbi::String => {
+                    uses_memory = true;
+                    core_params.push(quote!(#ptr: i32));
+                    core_params.push(quote!(#len: i32));
+                    lifts.push(quote! {
+                        let #arg = ::wasmer::WasmPtr::<u8>::new(#ptr as u32)
+                            .read_utf8_string(&memory.view(&env), #len as u32)?;
+                    });
+                }
+                ParamAbi::List(elem) => {
+                    uses_memory = true;
+                    core_params.push(quote!(#ptr: i32));
+                    core_params.push(quote!(#len: i32));
+                    lifts.push(quote! {
+                        let #arg = ::wasmer::WasmPtr::<#elem>::new(#ptr as u32)
+                            .slice(&memory.view(&env), #len as u32)?
+                            .read_to_vec()?;
+                    });
+                }
+                ParamAbi::Record(ty) => {
+                    uses_memory = true;
+                    core_params.push(quote!(#ptr: i32));
+                    lifts.push(quote! {
+                        let #arg = ::wasmer::WasmPtr::<#ty>::new(#ptr as u32)
+                            .read(&memory.view(&env))?;
+                    });
+                }
+            }
+            args.push(arg);
+        }
+
+        let store_result = |value: TokenStream, ty: &TokenStream| {
+            quote! {
+                ::wasmer::WasmPtr::<#ty>::new(result_ptr as u32)
+                    .write(&memory.view(&env), #value)?;
+            }
+        };
+        let (core_result, lower) = match self.result_abi(func.result.as_ref()) {
+            ResultAbi::None => (quote!(()), quote!(Ok(result))),
+            ResultAbi::Scalar(core, scalar) => {
+                let lower = scalar.lower(quote!(result), core);
+                (core.ty(), quote!(Ok(#lower)))
+            }
+            ResultAbi::Out(ty) => {
+                uses_memory = true;
+                core_params.push(quote!(result_ptr: i32));
+                let store = store_result(quote!(result), &ty);
+                (quote!(()), quote! { #store Ok(()) })
+            }
+            ResultAbi::Expected {
+                ok,
+                err: (core, scalar),
+            } => {
+                let store = match ok {
+                    Some(ty) => {
+                        uses_memory = true;
+                        core_params.push(quote!(result_ptr: i32));
+                        store_result(quote!(value), &ty)
+                    }
+                    None => quote!(let () = value;),
+                };
+                let lower = scalar.lower(quote!(error), core);
+                (
+                    core.ty(),
+                    quote! {
+                        match result {
+                            Ok(value) => {
+                                #store
+                                Ok(0)
+                            }
+                            Err(error) => Ok(#lower),
+                        }
+                    },
+                )
+            }
+        };
+
+        let memory = if uses_memory {
+            quote!(let memory = <T as #env_trait>::memory(env.data()).clone();)
+        } else {
+            quote!()
+        };
+        let method = snake(&func.name);
+        let name = &func.name;
+        quote! {
+            imports.define(
+                #namespace,
+                #name,
+                ::wasmer::Function::new_typed_with_env(
+                    store,
+                    env,
+                    |mut env: ::wasmer::FunctionEnvMut<T>, #(#core_params),*|
+                     -> ::std::result::Result<#core_result, ::wasmer::RuntimeError> {
+                        #memory
+                        #(#lifts)*
+                        let result = <T as #host_trait>::#method(env.as_mut(), #(#args),*)?;
+                        #lower
+                    },
+                ),
+            );
+        }
+    }
+
+    /// Generates the field, its initialization, and the method calling
+    /// an exported function.
+    fn export(&self, func: &Func) -> (TokenStream, TokenStream, TokenStream) {
+        let mut names = Vec::new();
+        let mut types = Vec::new();
+        let mut core_params = Vec::new();
+        let mut lowers = Vec::new();
+        for (name, ty) in &func.params {
+            let (core, scalar) = match self.scalar(ty) {
+                Some(scalar) => scalar,
+                None => abort_call_site!(
+                    "`{}`: the parameters of exported functions must be numbers, bools, \
+                     chars, enums, flags or pointers in module bindings",
+                    func.name
+                ),
+            };
+            let name = param(name);
+            lowers.push(scalar.lower(quote!(#name), core));
+            core_params.push(core.ty());
+            types.push(self.ty(ty));
+            names.push(name);
+        }
+        let result_ty = self.result_ty(func.result.as_ref());
+        let (core_result, lift) = match self.result_abi(func.result.as_ref()) {
+            ResultAbi::None => (quote!(()), quote!(Ok(result))),
+            ResultAbi::Scalar(core, scalar) => {
+                let lift = scalar.lift(&format_ident!("result"));
+                (
+                    core.ty(),
+                    quote! {
+                        let result = #lift;
+                        Ok(result)
+                    },
+                )
+            }
+            ResultAbi::Expected {
+                ok: None,
+                err: (core, scalar),
+            } => {
+                let lift = scalar.lift(&format_ident!("result"));
+                (
+                    core.ty(),
+                    quote! {
+                        Ok(if result == 0 { Ok(()) } else { Err(#lift) })
+                    },
+                )
+            }
+            ResultAbi::Out(_) | ResultAbi::Expected { .. } => abort_call_site!(
+                "`{}`: exported functions can't return values through memory in module bindings",
+                func.name
+            ),
+        };
+        let doc = docs(&func.docs);
+        let ident = snake(&func.name);
+        let name = &func.name;
+        (
+            quote! {
+                #ident: ::wasmer::TypedFunction<(#(#core_params),*), #core_result>
+            },
+            quote! {
+                #ident: instance.exports.get_typed_function(store, #name)?
+            },
+            quote! {
+                #doc
+                pub fn #ident(
+                    &self,
+                    store: &mut impl ::wasmer::AsStoreMut,
+                    #(#names: #types),*
+                ) -> ::std::result::Result<#result_ty, ::wasmer::RuntimeError> {
+                    #[allow(clippy::let_unit_value)]
+                    let result = self.#ident.call(store, #(#lowers),*)?;
+                    #lift
+                }
+            },
+        )
+    }
+}
+
+/// Generates the module of an interface imported by a world.
+fn interface_module(name: &str, interface: &Interface, env_trait: &Ident) -> TokenStream {
+    let generator = Generator::new(Some(interface));
+    let module = snake(name);
+    let ident = camel(name);
+    let doc = docs(&interface.docs);
+    let types = interface.types.iter().map(|def| generator.type_def(def));
+    let methods = interface.funcs.iter().map(|f| generator.trait_method(f));
+    let env_trait = quote!(super::#env_trait);
+    let defines = interface
+        .funcs
+        .iter()
+        .map(|f| generator.define_import(f, name, &quote!(#ident), &env_trait));
+    let trait_doc = format!(
+        "The host implementation of the functions imported from `{}`.",
+        name
+    );
+    quote! {
+        #doc
+        #[allow(clippy::all)]
+        pub mod #module {
+            #(#types)*
+
+            #[doc = #trait_doc]
+            pub trait #ident: #env_trait + Sized {
+                #(#methods)*
+            }
+
+            /// Defines the functions of the namespace in `imports`,
+            /// calling the functions of the environment.
+            pub fn add_to_imports<T: #ident>(
+                store: &mut impl ::wasmer::AsStoreMut,
+                imports: &mut ::wasmer::Imports,
+                env: &::wasmer::FunctionEnv<T>,
+            ) {
+                #(#defines)*
+            }
+        }
+    }
+}
+
+fn world(world: &World) -> TokenStream {
+    let generator = Generator::new(None);
+    let ident = camel(&world.name);
+    let doc = docs(&world.docs);
+    let env_trait = format_ident!("{}Env", ident);
+    let imports_trait = format_ident!("{}Imports", ident);
+    let mut modules = Vec::new();
+
+    // Imports.
+    let mut bounds = Vec::new();
+    let mut defines = Vec::new();
+    let mut import_methods = Vec::new();
+    for item in &world.imports {
+        match item {
+            WorldItem::Interface { name, interface } => {
+                if name == DEFAULT_NAMESPACE
+                    && world
+                        .imports
+                        .iter()
+                        .any(|i| matches!(i, WorldItem::Func(_)))
+                {
+                    abort_call_site!(
+                        "`{}` is the namespace of the functions imported by the world",
+                        name
+                    );
+                }
+                modules.push(interface_module(name, interface, &env_trait));
+                let module = snake(name);
+                let ident = camel(name);
+                bounds.push(quote!(#module::#ident));
+                defines.push(quote!(#module::add_to_imports(store, imports, env);));
+            }
+            WorldItem::Func(func) => {
+                import_methods.push(generator.trait_method(func));
+                defines.push(generator.define_import(
+                    func,
+                    DEFAULT_NAMESPACE,
+                    &quote!(#imports_trait),
+                    &quote!(#env_trait),
+                ));
+            }
+        }
+    }
+    let imports_trait_def = if import_methods.is_empty() {
+        quote!()
+    } else {
+        bounds.push(quote!(#imports_trait));
+        let trait_doc = format!(
+            "The host implementation of the functions imported from `{}` by the `{}` world.",
+            DEFAULT_NAMESPACE, world.name
+        );
+        quote! {
+            #[doc = #trait_doc]
+            pub trait #imports_trait: #env_trait + Sized {
+                #(#import_methods)*
+            }
+        }
+    };
+    let (env_trait_def, add_to_imports) = if bounds.is_empty() {
+        (quote!(), quote!())
+    } else {
+        let env_doc = format!(
+            "The environment of the host functions imported by the `{}` world.",
+            world.name
+        );
+        (
+            quote! {
+                #[doc = #env_doc]
+                pub trait #env_trait: Send + 'static {
+                    /// The memory of the instance, from which the
+                    /// arguments of the host functions are decoded. It
+                    /// is usually the `memory` exported by the instance,
+                    /// set in the environment once it is instantiated.
+                    fn memory(&self) -> &::wasmer::Memory;
+                }
+            },
+            quote! {
+                /// Defines the imports of the world in `imports`,
+                /// calling the functions of the environment.
+                pub fn add_to_imports<T>(
+                    store: &mut impl ::wasmer::AsStoreMut,
+                    imports: &mut ::wasmer::Imports,
+                    env: &::wasmer::FunctionEnv<T>,
+                ) where
+                    T: #(#bounds)+*,
+                {
+                    #(#defines)*
+                }
+            },
+        )
+    };
+
+    // Exports.
+    let (fields, inits, methods): (Vec<_>, Vec<_>, Vec<_>) =
+        unzip3(world.exports.iter().map(|item| match item {
+            WorldItem::Func(func) => generator.export(func),
+            WorldItem::Interface { name, .. } => {
+                abort_call_site!("`{}`: module bindings can only export functions", name)
+            }
+        }));
+
+    quote! {
+        #(#modules)*
+
+        #env_trait_def
+
+        #imports_trait_def
+
+        #doc
+        #[derive(Clone)]
+        pub struct #ident {
+            #(#fields,)*
+        }
+
+        impl #ident {
+            #add_to_imports
+
+            /// Gets the functions exported by the world from an
+            /// instance.
+            pub fn new(
+                store: &impl ::wasmer::AsStoreRef,
+                instance: &::wasmer::Instance,
+            ) -> ::std::result::Result<Self, ::wasmer::ExportError> {
+                let _ = (store, instance);
+                Ok(Self {
+                    #(#inits,)*
+                })
+            }
+
+            #(#methods)*
+        }
+    }
+}
+
+pub fn module_bindgen(input: Input) -> TokenStream {
+    let (world, tracking) = bindgen::load(&input, true);
+    let world = self::world(&world);
+    quote! {
+        #tracking
+        #world
+    }
+}
@@ -0,0 +1,681 @@
+//! A parser for the subset of [WIT] supported by `bindgen!`.
+//!
+//! WIT documents contain interfaces, which define types and
+//! functions, and worlds, which import and export interfaces and
+//! functions. Resources and `use` declarations are not supported.
+//!
+//! WITX documents are parsed to the same representation, see
+//! [`crate::witx`].
+//!
+//! [WIT]: https://github.com/WebAssembly/component-model/blob/main/design/mvp/WIT.md
+
+use std::fmt;
+
+/// A parsed WIT document.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub interfaces: Vec<Interface>,
+    pub worlds: Vec<World>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub types: Vec<TypeDef>,
+    pub funcs: Vec<Func>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeDef {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub kind: TypeDefKind,
+    /// The integer representation of an enum or flags, if it is
+    /// explicitly given (WITX only).
+    pub repr: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeDefKind {
+    Record(Vec<Field>),
+    Variant(Vec<Case>),
+    Enum(Vec<Name>),
+    Flags(Vec<Name>),
+    Union(Vec<Type>),
+    Alias(Type),
+}
+
+/// A documented name, e.g. a case of an enum.
+#[derive(Debug, Clone)]
+pub struct Name {
+    pub name: String,
+    pub docs: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub ty: Option<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    Float32,
+    Float64,
+    Char,
+    String,
+    List(Box<Type>),
+    Tuple(Vec<Type>),
+    Option(Box<Type>),
+    Result {
+        ok: Option<Box<Type>>,
+        err: Option<Box<Type>>,
+    },
+    /// A pointer into the linear memory of a core module (WITX only).
+    Pointer(Box<Type>),
+    /// A type defined in the enclosing interface.
+    Named(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Func {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub params: Vec<(String, Type)>,
+    pub result: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct World {
+    pub name: String,
+    pub docs: Vec<String>,
+    pub imports: Vec<WorldItem>,
+    pub exports: Vec<WorldItem>,
+}
+
+#[derive(Debug, Clone)]
+pub enum WorldItem {
+    /// An instance implementing an interface.
+    Interface { name: String, interface: Interface },
+    /// A function.
+    Func(Func),
+}
+
+impl WorldItem {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Interface { name, .. } => name,
+            Self::Func(func) => &func.name,
+        }
+    }
+}
+
+/// An error while parsing a WIT document.
+#[derive(Debug)]
+pub struct Error {
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl Error {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::at(0, message)
+    }
+
+    pub fn at(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// An identifier or a keyword.
+    Id(String),
+    /// An identifier escaped with `%`, which is never a keyword.
+    ExplicitId(String),
+    /// A `///` doc comment.
+    Doc(String),
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Lt,
+    Gt,
+    Comma,
+    Colon,
+    Equals,
+    Arrow,
+    Underscore,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "`{}`", id),
+            Self::ExplicitId(id) => write!(f, "`%{}`", id),
+            Self::Doc(_) => write!(f, "a doc comment"),
+            Self::LBrace => write!(f, "`{{`"),
+            Self::RBrace => write!(f, "`}}`"),
+            Self::LParen => write!(f, "`(`"),
+            Self::RParen => write!(f, "`)`"),
+            Self::Lt => write!(f, "`<`"),
+            Self::Gt => write!(f, "`>`"),
+            Self::Comma => write!(f, "`,`"),
+            Self::Colon => write!(f, "`:`"),
+            Self::Equals => write!(f, "`=`"),
+            Self::Arrow => write!(f, "`->`"),
+            Self::Underscore => write!(f, "`_`"),
+        }
+    }
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                let comment: String =
+                    std::iter::from_fn(|| chars.next_if(|&c| c != '\n')).collect();
+                match comment.strip_prefix("//") {
+                    Some(doc) if !doc.starts_with('/') => {
+                        Token::Doc(doc.strip_prefix(' ').unwrap_or(doc).to_string())
+                    }
+                    _ => continue,
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        }
+                        None => {
+                            return Err(Error {
+                                line,
+                                message: "unterminated block comment".to_string(),
+                            })
+                        }
+                    }
+                }
+                continue;
+            }
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '<' => Token::Lt,
+            '>' => Token::Gt,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '=' => Token::Equals,
+            '_' => Token::Underscore,
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                Token::Arrow
+            }
+            '%' => {
+                let id: String = std::iter::from_fn(|| chars.next_if(|&c| is_id_char(c))).collect();
+                if id.is_empty() {
+                    return Err(Error {
+                        line,
+                        message: "expected an identifier after `%`".to_string(),
+                    });
+                }
+                Token::ExplicitId(id)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let rest: String =
+                    std::iter::from_fn(|| chars.next_if(|&c| is_id_char(c))).collect();
+                Token::Id(format!("{}{}", c, rest))
+            }
+            c => {
+                return Err(Error {
+                    line,
+                    message: format!("unexpected character `{}`", c),
+                })
+            }
+        };
+        tokens.push((line, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// The references of worlds to interfaces of the document, as the
+    /// index of the world, whether the item is imported, the index of
+    /// the item, and the name of the interface.
+    references: Vec<(usize, bool, usize, String)>,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(0, |(line, _)| *line)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Error> {
+        Err(Error {
+            line: self.line(),
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        match self.tokens.get(self.pos) {
+            Some((_, token)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.eat(&Token::Id(keyword.to_string()))
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), Error> {
+        match self.next()? {
+            token if &token == expected => Ok(()),
+            token => {
+                self.pos -= 1;
+                self.error(format!("expected {}, found {}", expected, token))
+            }
+        }
+    }
+
+    fn id(&mut self) -> Result<String, Error> {
+        match self.next()? {
+            Token::Id(id) | Token::ExplicitId(id) => Ok(id),
+            token => {
+                self.pos -= 1;
+                self.error(format!("expected an identifier, found {}", token))
+            }
+        }
+    }
+
+    fn docs(&mut self) -> Vec<String> {
+        let mut docs = Vec::new();
+        while let Some(Token::Doc(doc)) = self.peek() {
+            docs.push(doc.clone());
+            self.pos += 1;
+        }
+        docs
+    }
+
+    /// Parses a comma-separated list of items between `open` and
+    /// `close`, with an optional trailing comma.
+    fn list<T>(
+        &mut self,
+        open: Token,
+        close: Token,
+        mut item: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        self.expect(&open)?;
+        let mut items = Vec::new();
+        loop {
+            self.docs();
+            if self.eat(&close) {
+                return Ok(items);
+            }
+            items.push(item(self)?);
+            self.docs();
+            if !self.eat(&Token::Comma) {
+                self.expect(&close)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn document(&mut self) -> Result<Document, Error> {
+        let mut document = Document::default();
+        while self.pos < self.tokens.len() {
+            let docs = self.docs();
+            if self.pos == self.tokens.len() {
+                break;
+            }
+            if self.eat_keyword("interface") {
+                let name = self.id()?;
+                document.interfaces.push(self.interface(name, docs)?);
+            } else if self.eat_keyword("world") {
+                let index = document.worlds.len();
+                document.worlds.push(self.world(index, docs)?);
+            } else {
+                let token = self.next()?;
+                self.pos -= 1;
+                return self.error(format!("expected `interface` or `world`, found {}", token));
+            }
+        }
+        Ok(document)
+    }
+
+    fn interface(&mut self, name: String, docs: Vec<String>) -> Result<Interface, Error> {
+        let mut interface = Interface {
+            name,
+            docs,
+            types: Vec::new(),
+            funcs: Vec::new(),
+        };
+        self.expect(&Token::LBrace)?;
+        loop {
+            let docs = self.docs();
+            if self.eat(&Token::RBrace) {
+                return Ok(interface);
+            }
+            match self.peek() {
+                Some(Token::Id(keyword)) if keyword == "resource" => {
+                    return self.error("resources are not supported")
+                }
+                Some(Token::Id(keyword)) if keyword == "use" => {
+                    return self.error("`use` declarations are not supported")
+                }
+                _ => {}
+            }
+            let kind = if self.eat_keyword("record") {
+                let name = self.id()?;
+                let fields = self.list(Token::LBrace, Token::RBrace, |p| {
+                    let docs = p.docs();
+                    let name = p.id()?;
+                    p.expect(&Token::Colon)?;
+                    let ty = p.ty()?;
+                    Ok(Field { name, docs, ty })
+                })?;
+                Some((name, TypeDefKind::Record(fields)))
+            } else if self.eat_keyword("variant") {
+                let name = self.id()?;
+                let cases = self.list(Token::LBrace, Token::RBrace, |p| {
+                    let docs = p.docs();
+                    let name = p.id()?;
+                    let ty = if p.eat(&Token::LParen) {
+                        let ty = p.ty()?;
+                        p.expect(&Token::RParen)?;
+                        Some(ty)
+                    } else {
+                        None
+                    };
+                    Ok(Case { name, docs, ty })
+                })?;
+                Some((name, TypeDefKind::Variant(cases)))
+            } else if self.eat_keyword("enum") {
+                let name = self.id()?;
+                Some((name, TypeDefKind::Enum(self.names()?)))
+            } else if self.eat_keyword("flags") {
+                let name = self.id()?;
+                Some((name, TypeDefKind::Flags(self.names()?)))
+            } else if self.eat_keyword("union") {
+                let name = self.id()?;
+                let types = self.list(Token::LBrace, Token::RBrace, Self::ty)?;
+                Some((name, TypeDefKind::Union(types)))
+            } else if self.eat_keyword("type") {
+                let name = self.id()?;
+                self.expect(&Token::Equals)?;
+                Some((name, TypeDefKind::Alias(self.ty()?)))
+            } else {
+                None
+            };
+            match kind {
+                Some((name, kind)) => interface.types.push(TypeDef {
+                    name,
+                    docs,
+                    kind,
+                    repr: None,
+                }),
+                None => {
+                    let name = self.id()?;
+                    self.expect(&Token::Colon)?;
+                    interface.funcs.push(self.func(name, docs)?);
+                }
+            }
+        }
+    }
+
+    fn names(&mut self) -> Result<Vec<Name>, Error> {
+        self.list(Token::LBrace, Token::RBrace, |p| {
+            let docs = p.docs();
+            let name = p.id()?;
+            Ok(Name { name, docs })
+        })
+    }
+
+    fn func(&mut self, name: String, docs: Vec<String>) -> Result<Func, Error> {
+        if !self.eat_keyword("func") {
+            return self.error("expected `func`");
+        }
+        let params = self.list(Token::LParen, Token::RParen, |p| {
+            let name = p.id()?;
+            p.expect(&Token::Colon)?;
+            Ok((name, p.ty()?))
+        })?;
+        let result = if self.eat(&Token::Arrow) {
+            if self.peek() == Some(&Token::LParen) {
+                return self.error("functions with multiple results are not supported");
+            }
+            Some(self.ty()?)
+        } else {
+            None
+        };
+        Ok(Func {
+            name,
+            docs,
+            params,
+            result,
+        })
+    }
+
+    fn world(&mut self, index: usize, docs: Vec<String>) -> Result<World, Error> {
+        let mut world = World {
+            name: self.id()?,
+            docs,
+            imports: Vec::new(),
+            exports: Vec::new(),
+        };
+        self.expect(&Token::LBrace)?;
+        loop {
+            let docs = self.docs();
+            if self.eat(&Token::RBrace) {
+                return Ok(world);
+            }
+            let import = if self.eat_keyword("import") {
+                true
+            } else if self.eat_keyword("export") {
+                false
+            } else if self.eat_keyword("default") {
+                return self.error("default exports are not supported");
+            } else {
+                return self.error("expected `import` or `export`");
+            };
+            let name = self.id()?;
+            self.expect(&Token::Colon)?;
+            let item = if self.peek() == Some(&Token::Id("func".to_string())) {
+                WorldItem::Func(self.func(name, docs)?)
+            } else if self.eat_keyword("interface") {
+                let interface = self.interface(name.clone(), docs)?;
+                WorldItem::Interface { name, interface }
+            } else {
+                // A reference to an interface of the document, resolved
+                // once the whole document is parsed.
+                let items = if import {
+                    &world.imports
+                } else {
+                    &world.exports
+                };
+                let reference = self.id()?;
+                self.references
+                    .push((index, import, items.len(), reference.clone()));
+                let interface = Interface {
+                    name: reference,
+                    docs,
+                    types: Vec::new(),
+                    funcs: Vec::new(),
+                };
+                WorldItem::Interface { name, interface }
+            };
+            if import {
+                world.imports.push(item);
+            } else {
+                world.exports.push(item);
+            }
+        }
+    }
+
+    fn ty(&mut self) -> Result<Type, Error> {
+        let id = match self.next()? {
+            Token::Id(id) => id,
+            Token::ExplicitId(id) => return Ok(Type::Named(id)),
+            token => {
+                self.pos -= 1;
+                return self.error(format!("expected a type, found {}", token));
+            }
+        };
+        Ok(match id.as_str() {
+            "bool" => Type::Bool,
+            "s8" => Type::S8,
+            "u8" => Type::U8,
+            "s16" => Type::S16,
+            "u16" => Type::U16,
+            "s32" => Type::S32,
+            "u32" => Type::U32,
+            "s64" => Type::S64,
+            "u64" => Type::U64,
+            "float32" | "f32" => Type::Float32,
+            "float64" | "f64" => Type::Float64,
+            "char" => Type::Char,
+            "string" => Type::String,
+            "list" => {
+                self.expect(&Token::Lt)?;
+                let ty = self.ty()?;
+                self.expect(&Token::Gt)?;
+                Type::List(Box::new(ty))
+            }
+            "option" => {
+                self.expect(&Token::Lt)?;
+                let ty = self.ty()?;
+                self.expect(&Token::Gt)?;
+                Type::Option(Box::new(ty))
+            }
+            "tuple" => Type::Tuple(self.list(Token::Lt, Token::Gt, Self::ty)?),
+            "result" => {
+                if !self.eat(&Token::Lt) {
+                    return Ok(Type::Result {
+                        ok: None,
+                        err: None,
+                    });
+                }
+                let ok = if self.eat(&Token::Underscore) {
+                    None
+                } else {
+                    Some(Box::new(self.ty()?))
+                };
+                let err = if self.eat(&Token::Comma) {
+                    Some(Box::new(self.ty()?))
+                } else {
+                    None
+                };
+                self.expect(&Token::Gt)?;
+                Type::Result { ok, err }
+            }
+            _ => Type::Named(id),
+        })
+    }
+}
+
+/// Parses a WIT document, and resolves the interfaces referenced by
+/// its worlds.
+pub fn parse(source: &str) -> Result<Document, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        references: Vec::new(),
+    };
+    let mut document = parser.document()?;
+    for (world, import, index, reference) in parser.references {
+        let resolved = match document.interfaces.iter().find(|i| i.name == reference) {
+            Some(interface) => interface.clone(),
+            None => {
+                return Err(Error::new(format!(
+                    "unknown interface `{}` in world `{}`",
+                    reference, document.worlds[world].name
+                )))
+            }
+        };
+        let world = &mut document.worlds[world];
+        let items = if import {
+            &mut world.imports
+        } else {
+            &mut world.exports
+        };
+        if let WorldItem::Interface { interface, .. } = &mut items[index] {
+            interface.types = resolved.types;
+            interface.funcs = resolved.funcs;
+            if interface.docs.is_empty() {
+                interface.docs = resolved.docs;
+            }
+        }
+    }
+    Ok(document)
+}
@@ -0,0 +1,397 @@
+//! A parser for the subset of [WITX] supported by `module_bindgen!`.
+//!
+//! WITX documents define type names and modules of functions imported
+//! by core modules, e.g. WASI. They are parsed to the representation
+//! of WIT documents: each module becomes an interface, with all the
+//! type names of the document, and a world of the same name importing
+//! it.
+//!
+//! [WITX]: https://github.com/WebAssembly/WASI/blob/main/legacy/tools/witx-docs.md
+
+use crate::wit::{Case, Document, Error, Field, Func, Interface, Name, Type, TypeDef};
+use crate::wit::{TypeDefKind, World, WorldItem};
+
+/// An S-expression.
+#[derive(Debug, Clone)]
+enum SExpr {
+    Atom(usize, String),
+    Str(usize, String),
+    /// A list, with the `;;;` doc comments preceding it.
+    List(usize, Vec<SExpr>, Vec<String>),
+}
+
+impl SExpr {
+    fn line(&self) -> usize {
+        match self {
+            Self::Atom(line, _) | Self::Str(line, _) | Self::List(line, ..) => *line,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Error> {
+        Err(Error::at(self.line(), message))
+    }
+
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Self::Atom(_, atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of an `$id` atom.
+    fn id(&self) -> Result<String, Error> {
+        match self.atom().and_then(|atom| atom.strip_prefix('$')) {
+            Some(id) => Ok(id.to_string()),
+            None => self.error("expected an `$identifier`"),
+        }
+    }
+
+    fn string(&self) -> Result<String, Error> {
+        match self {
+            Self::Str(_, s) => Ok(s.clone()),
+            _ => self.error("expected a string"),
+        }
+    }
+
+    /// Returns the items and docs of a list starting with `keyword`.
+    fn form(&self, keyword: &str) -> Option<(&[SExpr], &[String])> {
+        match self {
+            Self::List(_, items, docs) if items.first()?.atom() == Some(keyword) => {
+                Some((&items[1..], docs))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the items of a `(@witx <keyword> ...)` annotation.
+    fn annotation(&self, keyword: &str) -> Option<&[SExpr]> {
+        match self.form("@witx")? {
+            (items, _) if items.first()?.atom() == Some(keyword) => Some(&items[1..]),
+            _ => None,
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<SExpr>, Error> {
+    let mut stack: Vec<(usize, Vec<SExpr>, Vec<String>)> = vec![(1, Vec::new(), Vec::new())];
+    let mut docs = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            ';' if chars.peek() == Some(&';') => {
+                let comment: String =
+                    std::iter::from_fn(|| chars.next_if(|&c| c != '\n')).collect();
+                if let Some(doc) = comment.strip_prefix(";;") {
+                    docs.push(doc.strip_prefix(' ').unwrap_or(doc).to_string());
+                }
+            }
+            '(' if chars.peek() == Some(&';') => {
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some(')') if last == ';' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        }
+                        None => return Err(Error::at(line, "unterminated block comment")),
+                    }
+                }
+            }
+            '(' => stack.push((line, Vec::new(), std::mem::take(&mut docs))),
+            ')' => {
+                let (start, items, list_docs) = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some((_, parent, _)) => parent.push(SExpr::List(start, items, list_docs)),
+                    None => return Err(Error::at(line, "unexpected `)`")),
+                }
+            }
+            '"' => {
+                let s: String = std::iter::from_fn(|| chars.next_if(|&c| c != '"')).collect();
+                if chars.next().is_none() {
+                    return Err(Error::at(line, "unterminated string"));
+                }
+                stack.last_mut().unwrap().1.push(SExpr::Str(line, s));
+            }
+            c => {
+                let rest: String = std::iter::from_fn(|| {
+                    chars.next_if(|&c| !c.is_whitespace() && c != '(' && c != ')' && c != ';')
+                })
+                .collect();
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .1
+                    .push(SExpr::Atom(line, format!("{}{}", c, rest)));
+            }
+        }
+    }
+    match stack.pop() {
+        Some((_, items, _)) if stack.is_empty() => Ok(items),
+        _ => Err(Error::at(line, "unclosed `(`")),
+    }
+}
+
+fn ty(expr: &SExpr) -> Result<Type, Error> {
+    if let Some(atom) = expr.atom() {
+        return Ok(match atom {
+            "bool" => Type::Bool,
+            "s8" => Type::S8,
+            "u8" | "char8" => Type::U8,
+            "s16" => Type::S16,
+            "u16" => Type::U16,
+            "s32" => Type::S32,
+            "u32" | "usize" => Type::U32,
+            "s64" => Type::S64,
+            "u64" => Type::U64,
+            "f32" => Type::Float32,
+            "f64" => Type::Float64,
+            "char" => Type::Char,
+            "string" => Type::String,
+            _ => Type::Named(expr.id()?),
+        });
+    }
+    if let Some(items) = expr
+        .annotation("pointer")
+        .or_else(|| expr.annotation("const_pointer"))
+    {
+        return match items {
+            [pointee] => Ok(Type::Pointer(Box::new(ty(pointee)?))),
+            _ => expr.error("expected the type of the pointee"),
+        };
+    }
+    if let Some((items, _)) = expr.form("list") {
+        return match items {
+            [elem] => Ok(Type::List(Box::new(ty(elem)?))),
+            _ => expr.error("expected the type of the elements"),
+        };
+    }
+    if let Some((items, _)) = expr.form("expected") {
+        let mut ok = None;
+        let mut err = None;
+        for item in items {
+            match item.form("error") {
+                Some(([e], _)) => err = Some(Box::new(ty(e)?)),
+                Some(_) => return item.error("expected the type of the error"),
+                None => ok = Some(Box::new(ty(item)?)),
+            }
+        }
+        return Ok(Type::Result { ok, err });
+    }
+    if let Some((items, _)) = expr.form("tuple") {
+        return Ok(Type::Tuple(items.iter().map(ty).collect::<Result<_, _>>()?));
+    }
+    expr.error("expected a type")
+}
+
+/// Splits the explicit representation of an enum or flags, given as
+/// `(@witx tag <type>)`, `(@witx repr <type>)` or a leading type, from
+/// the names of its cases.
+fn repr_and_names(items: &[SExpr], keyword: &str) -> Result<(Option<Type>, Vec<Name>), Error> {
+    let (repr, names) = match items.split_first() {
+        Some((first, rest)) => match first.annotation(keyword) {
+            Some([repr]) => (Some(ty(repr)?), rest),
+            Some(_) => return first.error("expected a representation type"),
+            None if first.atom().map_or(false, |atom| !atom.starts_with('$')) => {
+                (Some(ty(first)?), rest)
+            }
+            None => (None, items),
+        },
+        None => (None, items),
+    };
+    let names = names
+        .iter()
+        .map(|name| match name {
+            SExpr::List(_, items, docs) if items.len() == 1 => Ok(Name {
+                name: items[0].id()?,
+                docs: docs.clone(),
+            }),
+            _ => Ok(Name {
+                name: name.id()?,
+                docs: Vec::new(),
+            }),
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok((repr, names))
+}
+
+fn type_def(expr: &SExpr, name: String, docs: Vec<String>) -> Result<TypeDef, Error> {
+    let mut repr = None;
+    let kind = if let Some((fields, _)) = expr.form("record") {
+        TypeDefKind::Record(
+            fields
+                .iter()
+                .map(|field| match field.form("field") {
+                    Some(([name, field_ty], docs)) => Ok(Field {
+                        name: name.id()?,
+                        docs: docs.to_vec(),
+                        ty: ty(field_ty)?,
+                    }),
+                    _ => field.error("expected `(field $name <type>)`"),
+                })
+                .collect::<Result<_, _>>()?,
+        )
+    } else if let Some((items, _)) = expr.form("enum") {
+        let (enum_repr, cases) = repr_and_names(items, "tag")?;
+        repr = enum_repr;
+        TypeDefKind::Enum(cases)
+    } else if let Some((items, _)) = expr.form("flags") {
+        let (flags_repr, flags) = repr_and_names(items, "repr")?;
+        repr = flags_repr;
+        TypeDefKind::Flags(flags)
+    } else if let Some((items, _)) = expr.form("variant") {
+        let cases = match items.split_first() {
+            Some((first, rest)) => match first.annotation("tag") {
+                Some([tag]) => {
+                    repr = Some(ty(tag)?);
+                    rest
+                }
+                Some(_) => return first.error("expected a representation type"),
+                None => items,
+            },
+            None => items,
+        };
+        TypeDefKind::Variant(
+            cases
+                .iter()
+                .map(|case| match case.form("case") {
+                    Some(([name], docs)) => Ok(Case {
+                        name: name.id()?,
+                        docs: docs.to_vec(),
+                        ty: None,
+                    }),
+                    Some(([name, case_ty], docs)) => Ok(Case {
+                        name: name.id()?,
+                        docs: docs.to_vec(),
+                        ty: Some(ty(case_ty)?),
+                    }),
+                    _ => case.error("expected `(case $name <type>?)`"),
+                })
+                .collect::<Result<_, _>>()?,
+        )
+    } else if let Some((items, _)) = expr.form("union") {
+        TypeDefKind::Union(items.iter().map(ty).collect::<Result<_, _>>()?)
+    } else if expr.form("handle").is_some() {
+        TypeDefKind::Alias(Type::U32)
+    } else {
+        TypeDefKind::Alias(ty(expr)?)
+    };
+    Ok(TypeDef {
+        name,
+        docs,
+        kind,
+        repr,
+    })
+}
+
+fn func(items: &[SExpr], docs: &[String]) -> Result<Func, Error> {
+    let (name, rest) = match items {
+        [export, rest @ ..] => match export.form("export") {
+            Some(([name], _)) => (name.string()?, rest),
+            _ => return export.error("expected `(export \"name\")`"),
+        },
+        _ => return Err(Error::new("expected `(export \"name\")`")),
+    };
+    let mut params = Vec::new();
+    let mut result = None;
+    for item in rest {
+        if let Some(([name, param_ty], _)) = item.form("param") {
+            params.push((name.id()?, ty(param_ty)?));
+        } else if let Some(([_, result_ty], _)) = item.form("result") {
+            if result.is_some() {
+                return item.error("functions with multiple results are not supported");
+            }
+            result = Some(ty(result_ty)?);
+        } else if item.annotation("noreturn").is_none() {
+            return item.error("expected `(param ...)` or `(result ...)`");
+        }
+    }
+    Ok(Func {
+        name,
+        docs: docs.to_vec(),
+        params,
+        result,
+    })
+}
+
+fn items(
+    exprs: &[SExpr],
+    read: &mut dyn FnMut(&str) -> Result<String, Error>,
+    types: &mut Vec<TypeDef>,
+    modules: &mut Vec<Interface>,
+) -> Result<(), Error> {
+    for expr in exprs {
+        if let Some((items, docs)) = expr.form("typename") {
+            match items {
+                [name, def] => types.push(type_def(def, name.id()?, docs.to_vec())?),
+                _ => return expr.error("expected `(typename $name <type>)`"),
+            }
+        } else if let Some((items, _)) = expr.form("use") {
+            for path in items {
+                let source = read(&path.string()?)?;
+                self::items(&tokenize(&source)?, read, types, modules)?;
+            }
+        } else if let Some((items, docs)) = expr.form("module") {
+            let (name, items) = match items.split_first() {
+                Some((name, items)) => (name.id()?, items),
+                None => return expr.error("expected a module name"),
+            };
+            let mut funcs = Vec::new();
+            for item in items {
+                if let Some((items, docs)) = item.form("@interface") {
+                    match items.split_first() {
+                        Some((keyword, items)) if keyword.atom() == Some("func") => {
+                            funcs.push(func(items, docs)?)
+                        }
+                        _ => return item.error("expected `(@interface func ...)`"),
+                    }
+                } else if item.form("import").is_none() {
+                    return item.error("expected `(import ...)` or `(@interface func ...)`");
+                }
+            }
+            modules.push(Interface {
+                name,
+                docs: docs.to_vec(),
+                types: Vec::new(),
+                funcs,
+            });
+        } else {
+            return expr.error("expected `(typename ...)`, `(use ...)` or `(module ...)`");
+        }
+    }
+    Ok(())
+}
+
+/// Parses a WITX document; `read` reads the documents it uses.
+pub fn parse(
+    source: &str,
+    read: &mut dyn FnMut(&str) -> Result<String, Error>,
+) -> Result<Document, Error> {
+    let mut types = Vec::new();
+    let mut modules = Vec::new();
+    items(&tokenize(source)?, read, &mut types, &mut modules)?;
+    let worlds = modules
+        .iter_mut()
+        .map(|module| {
+            module.types = types.clone();
+            World {
+                name: module.name.clone(),
+                docs: module.docs.clone(),
+                imports: vec![WorldItem::Interface {
+                    name: module.name.clone(),
+                    interface: module.clone(),
+                }],
+                exports: Vec::new(),
+            }
+        })
+        .collect();
+    Ok(Document {
+        interfaces: modules,
+        worlds,
+    })
+}
@@ -0,0 +1,7 @@
+extern crate wasmer;
+
+wasmer::component::bindgen!(
+    inline = "interface files { resource file }", //~ invalid WIT document: line 1: resources are not supported
+);
+
+fn main() {}
@@ -22,6 +22,7 @@ use std::collections::HashMap;
 use std::f64;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
+use wasmer::linker::Linker;
 use wasmer::{
     imports, namespace, AsStoreMut, ExportError, Exports, Function, FunctionEnv, FunctionEnvMut,
     FunctionType, Global, Imports, Instance, Memory, MemoryType, Module, Pages, RuntimeError,
@@ -80,6 +81,7 @@ pub struct EmEnv {
     memory: Arc<RwLock<Option<Memory>>>,
     data: Arc<Mutex<Option<EmscriptenData>>>,
     funcs: Arc<Mutex<EmscriptenFunctions>>,
+    linker: Option<Linker>,
 }
 
 impl Default for EmEnv {
@@ -95,6 +97,7 @@ impl EmEnv {
             memory: Arc::new(RwLock::new(None)),
             data: Arc::new(Mutex::new(None)),
             funcs: Arc::new(Mutex::new(EmscriptenFunctions::new())),
+            linker: None,
         }
     }
 
@@ -108,6 +111,11 @@ impl EmEnv {
         (*self.memory.read().unwrap()).as_ref().cloned().unwrap()
     }
 
+    /// Set the linker used by `dlopen` and `dlsym` to load side modules.
+    pub fn set_linker(&mut self, linker: Linker) {
+        self.linker = Some(linker);
+    }
+
     pub fn set_functions(&mut self, funcs: EmscriptenFunctions) {
         self.funcs = Arc::new(Mutex::new(funcs));
     }
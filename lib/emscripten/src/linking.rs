@@ -1,28 +1,144 @@
 use crate::EmEnv;
+use wasmer::linker::{LibraryHandle, Linker};
 use wasmer::FunctionEnvMut;
 
-// TODO: Need to implement.
+/// Returns the linker set with `EmEnv::set_linker`, if any.
+fn get_linker(ctx: &FunctionEnvMut<EmEnv>) -> Option<Linker> {
+    ctx.data().linker.clone()
+}
 
 /// emscripten: dlopen(filename: *const c_char, flag: c_int) -> *mut c_void
-pub fn _dlopen(mut _ctx: FunctionEnvMut<EmEnv>, _filename: u32, _flag: u32) -> i32 {
+pub fn _dlopen(mut ctx: FunctionEnvMut<EmEnv>, filename: u32, _flag: u32) -> i32 {
     debug!("emscripten::_dlopen");
-    -1
+    let linker = match get_linker(&ctx) {
+        Some(linker) => linker,
+        None => return 0,
+    };
+    if filename == 0 {
+        return LibraryHandle::MAIN.to_raw() as i32;
+    }
+    linker
+        .read_c_string(&ctx, filename)
+        .and_then(|filename| linker.dlopen(&mut ctx, &filename))
+        .map_or(0, |handle| handle.to_raw() as i32)
 }
 
 /// emscripten: dlclose(handle: *mut c_void) -> c_int
-pub fn _dlclose(mut _ctx: FunctionEnvMut<EmEnv>, _filename: u32) -> i32 {
+pub fn _dlclose(ctx: FunctionEnvMut<EmEnv>, handle: u32) -> i32 {
     debug!("emscripten::_dlclose");
-    -1
+    let linker = match get_linker(&ctx) {
+        Some(linker) => linker,
+        None => return -1,
+    };
+    match LibraryHandle::from_raw(handle).map(|handle| linker.dlclose(handle)) {
+        Some(Ok(())) => 0,
+        _ => -1,
+    }
 }
 
 /// emscripten: dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void
-pub fn _dlsym(mut _ctx: FunctionEnvMut<EmEnv>, _filepath: u32, _symbol: u32) -> i32 {
+pub fn _dlsym(mut ctx: FunctionEnvMut<EmEnv>, handle: u32, symbol: u32) -> i32 {
     debug!("emscripten::_dlsym");
-    -1
+    let linker = match get_linker(&ctx) {
+        Some(linker) => linker,
+        None => return 0,
+    };
+    let symbol = match linker.read_c_string(&ctx, symbol) {
+        Ok(symbol) => symbol,
+        Err(_) => return 0,
+    };
+    let handle = LibraryHandle::from_raw(handle);
+    // Emscripten exports C symbols with a leading underscore.
+    let prefixed = format!("_{}", symbol);
+    let symbol = if linker.lookup(handle, &prefixed).is_ok() {
+        prefixed
+    } else {
+        symbol
+    };
+    linker
+        .dlsym(&mut ctx, handle, &symbol)
+        .map_or(0, |address| address as i32)
 }
 
 /// emscripten: dlerror() -> *mut c_char
-pub fn _dlerror(mut _ctx: FunctionEnvMut<EmEnv>) -> i32 {
+pub fn _dlerror(mut ctx: FunctionEnvMut<EmEnv>) -> i32 {
     debug!("emscripten::_dlerror");
-    -1
+    match get_linker(&ctx) {
+        Some(linker) => linker.dlerror_ptr(&mut ctx).unwrap_or(0) as i32,
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer::{imports, Function, FunctionEnv, Module, Store};
+
+    #[test]
+    fn dlopen_and_dlsym() -> Result<(), Box<dyn std::error::Error>> {
+        let mut store = Store::default();
+        let main = Module::new(
+            &store,
+            r#"(module
+              (import "env" "_dlopen" (func $dlopen (param i32 i32) (result i32)))
+              (import "env" "_dlsym" (func $dlsym (param i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (table (export "__indirect_function_table") 1 funcref)
+              (type $i32_i32_to_i32 (func (param i32 i32) (result i32)))
+              (data (i32.const 16) "libadd.so\00")
+              (data (i32.const 32) "add\00")
+              (data (i32.const 48) "libmissing.so\00")
+              (func (export "add") (param i32 i32) (result i32)
+                (call_indirect (type $i32_i32_to_i32)
+                  (local.get 0)
+                  (local.get 1)
+                  (call $dlsym (call $dlopen (i32.const 16) (i32.const 0)) (i32.const 32))))
+              (func (export "open_main") (result i32)
+                (call $dlopen (i32.const 0) (i32.const 0)))
+              (func (export "open_missing") (result i32)
+                (call $dlopen (i32.const 48) (i32.const 0))))"#,
+        )?;
+        // Emscripten prefixes the C symbols with an underscore
+        let library = Module::new(
+            &store,
+            r#"(module
+              (@custom "dylink.0" "\01\04\00\00\00\00")
+              (func (export "_add") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1))))"#,
+        )?;
+
+        let env = FunctionEnv::new(&mut store, EmEnv::new());
+        let linker = Linker::new(&imports! {});
+        linker.define(
+            "env",
+            "_dlopen",
+            Function::new_typed_with_env(&mut store, &env, _dlopen),
+        );
+        linker.define(
+            "env",
+            "_dlsym",
+            Function::new_typed_with_env(&mut store, &env, _dlsym),
+        );
+        linker.register_library("libadd.so", library);
+        env.as_mut(&mut store).set_linker(linker.clone());
+        let instance = linker.instantiate_main(&mut store, &main)?;
+
+        let add = instance
+            .exports
+            .get_typed_function::<(i32, i32), i32>(&store, "add")?;
+        assert_eq!(add.call(&mut store, 2, 3)?, 5);
+        let open_main = instance
+            .exports
+            .get_typed_function::<(), i32>(&store, "open_main")?;
+        assert_eq!(
+            open_main.call(&mut store)?,
+            LibraryHandle::MAIN.to_raw() as i32
+        );
+        let open_missing = instance
+            .exports
+            .get_typed_function::<(), i32>(&store, "open_missing")?;
+        assert_eq!(open_missing.call(&mut store)?, 0);
+
+        Ok(())
+    }
 }
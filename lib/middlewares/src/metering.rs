@@ -3,6 +3,10 @@
 //! operators executed. The WebAssemblt instance execution is stopped
 //! when the limit is reached.
 //!
+//! The execution of a metered instance can also be interrupted from
+//! another thread when the middleware is created with
+//! [`Metering::with_interruption`], see [`interrupt_handle`].
+//!
 //! # Example
 //!
 //! [See the `metering` detailed and complete
@@ -10,16 +14,20 @@
 
 use std::convert::TryInto;
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use wasmer::vm::VMExtern;
 use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 use wasmer::{
     AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
     LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
 };
 use wasmer_types::{GlobalIndex, ModuleInfo};
+use wasmer_vm::VMGlobalDefinition;
 
 #[derive(Clone)]
-struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex);
+struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex, Option<GlobalIndex>);
 
 impl MeteringGlobalIndexes {
     /// The global index in the current module for remaining points.
@@ -35,6 +43,15 @@ impl MeteringGlobalIndexes {
     fn points_exhausted(&self) -> GlobalIndex {
         self.1
     }
+
+    /// The global index in the current module for a boolean indicating whether the execution
+    /// was interrupted or not, if the execution can be interrupted.
+    /// This boolean is represented as a i32 global:
+    ///   * 0: the execution can continue
+    ///   * 1: the execution was interrupted
+    fn interrupted(&self) -> Option<GlobalIndex> {
+        self.2
+    }
 }
 
 impl fmt::Debug for MeteringGlobalIndexes {
@@ -42,18 +59,16 @@ impl fmt::Debug for MeteringGlobalIndexes {
         f.debug_struct("MeteringGlobalIndexes")
             .field("remaining_points", &self.remaining_points())
             .field("points_exhausted", &self.points_exhausted())
+            .field("interrupted", &self.interrupted())
             .finish()
     }
 }
 
 /// The module-level metering middleware.
 ///
-/// # Panic
-///
-/// An instance of `Metering` should _not_ be shared among different
-/// modules, since it tracks module-specific information like the
-/// global index to store metering state. Attempts to use a `Metering`
-/// instance from multiple modules will result in a panic.
+/// An instance of `Metering` can be shared among different modules:
+/// the globals that hold the metering state of a module are found by
+/// their exports when its functions are compiled.
 ///
 /// # Example
 ///
@@ -87,8 +102,11 @@ pub struct Metering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// Function that maps each operator to a cost in "points".
     cost_function: Arc<F>,
 
-    /// The global indexes for metering points.
-    global_indexes: Mutex<Option<MeteringGlobalIndexes>>,
+    /// Whether the execution can be interrupted from another thread.
+    interruptible: bool,
+
+    /// The id that identifies the cost function, if any.
+    cost_function_id: Option<String>,
 }
 
 /// The function-level metering middleware.
@@ -128,9 +146,32 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> Metering<F> {
         Self {
             initial_limit,
             cost_function: Arc::new(cost_function),
-            global_indexes: Mutex::new(None),
+            interruptible: false,
+            cost_function_id: None,
         }
     }
+
+    /// Makes the execution interruptible from another thread, see
+    /// [`interrupt_handle`].
+    ///
+    /// Every branch and call then checks a `wasmer_metering_interrupted`
+    /// global, which the module exports, before it executes.
+    pub fn with_interruption(mut self) -> Self {
+        self.interruptible = true;
+        self
+    }
+
+    /// Names the cost function, so that the modules compiled with this
+    /// middleware can be cached.
+    ///
+    /// Modules compiled with a `Metering` middleware are only cached when
+    /// the middleware has an id, as two cost functions can't be told apart
+    /// otherwise. Two cost functions with the same id must give the same
+    /// cost to every operator.
+    pub fn with_cost_function_id(mut self, id: impl Into<String>) -> Self {
+        self.cost_function_id = Some(id.into());
+        self
+    }
 }
 
 impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
@@ -138,29 +179,57 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
         f.debug_struct("Metering")
             .field("initial_limit", &self.initial_limit)
             .field("cost_function", &"<function>")
-            .field("global_indexes", &self.global_indexes)
+            .field("interruptible", &self.interruptible)
+            .field("cost_function_id", &self.cost_function_id)
             .finish()
     }
 }
 
 impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Metering<F> {
     /// Generates a `FunctionMiddleware` for a given function.
-    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+    fn generate_function_middleware(
+        &self,
+        module_info: &ModuleInfo,
+        _: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        // The globals are found by their exports, as the middleware can
+        // be applied to several modules
+        let global = |name: &str| match module_info.exports.get(name) {
+            Some(ExportIndex::Global(index)) => *index,
+            _ => panic!(
+                "Metering::generate_function_middleware: the `Metering` middleware wasn't applied to the module, `{}` is missing.",
+                name
+            ),
+        };
+        let global_indexes = MeteringGlobalIndexes(
+            global("wasmer_metering_remaining_points"),
+            global("wasmer_metering_points_exhausted"),
+            if self.interruptible {
+                Some(global("wasmer_metering_interrupted"))
+            } else {
+                None
+            },
+        );
+
         Box::new(FunctionMetering {
             cost_function: self.cost_function.clone(),
-            global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+            global_indexes,
             accumulated_cost: 0,
         })
     }
 
+    /// The initial limit is part of the module, and the cost function is
+    /// only identified by the id given to [`Metering::with_cost_function_id`].
+    fn deterministic_id(&self) -> Option<String> {
+        let cost_function_id = self.cost_function_id.as_ref()?;
+        Some(format!(
+            "metering-{}-initial_limit={}-interruptible={}",
+            cost_function_id, self.initial_limit, self.interruptible
+        ))
+    }
+
     /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
     fn transform_module_info(&self, module_info: &mut ModuleInfo) {
-        let mut global_indexes = self.global_indexes.lock().unwrap();
-
-        if global_indexes.is_some() {
-            panic!("Metering::transform_module_info: Attempting to use a `Metering` middleware from multiple modules.");
-        }
-
         // Append a global for remaining points and initialize it.
         let remaining_points_global_index = module_info
             .globals
@@ -189,10 +258,26 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
             ExportIndex::Global(points_exhausted_global_index),
         );
 
-        *global_indexes = Some(MeteringGlobalIndexes(
-            remaining_points_global_index,
-            points_exhausted_global_index,
-        ))
+        // Append a global for the interruption boolean and initialize it.
+        if self.interruptible {
+            let interrupted_global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I32, Mutability::Var));
+
+            module_info
+                .global_initializers
+                .push(GlobalInit::I32Const(0));
+
+            module_info.exports.insert(
+                "wasmer_metering_interrupted".to_string(),
+                ExportIndex::Global(interrupted_global_index),
+            );
+
+            // Other threads set the global while the module runs.
+            module_info
+                .volatile_globals
+                .insert(interrupted_global_index);
+        }
     }
 }
 
@@ -216,6 +301,30 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
         // corner cases.
         self.accumulated_cost += (self.cost_function)(&operator);
 
+        // Branches and calls are where a loop or a recursion can start over, so check there
+        // whether the execution was interrupted.
+        if let (
+            Some(interrupted),
+            Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. },
+        ) = (self.global_indexes.interrupted(), &operator)
+        {
+            state.extend(&[
+                // if globals[interrupted_index] { throw(); }
+                Operator::GlobalGet {
+                    global_index: interrupted.as_u32(),
+                },
+                Operator::If {
+                    ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                },
+                Operator::Unreachable,
+                Operator::End,
+            ]);
+        }
+
         // Possible sources and targets of a branch. Finalize the cost of the previous basic block and perform necessary checks.
         match operator {
             Operator::Loop { .. } // loop headers are branch targets
@@ -348,6 +457,117 @@ pub fn set_remaining_points(ctx: &mut impl AsStoreMut, instance: &Instance, poin
         .expect("Can't set `wasmer_metering_points_exhausted` in Instance");
 }
 
+/// A handle to interrupt the execution of an
+/// [`Instance`][wasmer::Instance] from another thread, see
+/// [`interrupt_handle`].
+#[derive(Clone, Debug)]
+pub struct MeteringInterruptHandle {
+    interrupted: NonNull<VMGlobalDefinition>,
+}
+
+// The handle only updates the global atomically.
+unsafe impl Send for MeteringInterruptHandle {}
+unsafe impl Sync for MeteringInterruptHandle {}
+
+impl MeteringInterruptHandle {
+    /// Interrupts the execution of the instance. The running call, if
+    /// any, and the next calls trap at their next branch or call until
+    /// [`clear_interrupt`] is called.
+    ///
+    /// # Safety
+    ///
+    /// The store of the instance must still be alive.
+    pub unsafe fn interrupt(&self) {
+        // The value of an `i32` global is stored at the start of its
+        // definition, which is aligned for atomic accesses.
+        let interrupted = &*(self.interrupted.as_ptr() as *const AtomicU32);
+        interrupted.store(1, Ordering::SeqCst);
+    }
+}
+
+/// Get a handle to interrupt the execution of an
+/// [`Instance`][wasmer::Instance] from another thread, for instance
+/// to enforce a timeout.
+///
+/// Note: a call that is interrupted traps as if it reached an
+/// `unreachable` instruction. [`is_interrupted`] tells it apart from
+/// other traps.
+///
+/// # Panic
+///
+/// The given [`Instance`][wasmer::Instance] must have been processed
+/// with a [`Metering`] middleware created with
+/// [`Metering::with_interruption`] at compile time, otherwise this
+/// will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::metering::interrupt_handle;
+///
+/// fn interrupt_after(store: &mut impl AsStoreMut, instance: &Instance, timeout: Duration) {
+///     let handle = interrupt_handle(store, instance);
+///     std::thread::spawn(move || {
+///         std::thread::sleep(timeout);
+///         // The store must outlive the thread.
+///         unsafe { handle.interrupt() };
+///     });
+/// }
+/// ```
+pub fn interrupt_handle(ctx: &mut impl AsStoreMut, instance: &Instance) -> MeteringInterruptHandle {
+    let global = instance
+        .exports
+        .get_extern("wasmer_metering_interrupted")
+        .expect("Can't get `wasmer_metering_interrupted` from Instance");
+    let interrupted = match global.to_vm_extern() {
+        VMExtern::Global(handle) => handle.get(ctx.objects_mut()).vmglobal(),
+        _ => panic!("`wasmer_metering_interrupted` from Instance is not a global"),
+    };
+
+    MeteringInterruptHandle { interrupted }
+}
+
+/// Returns true if the execution of an [`Instance`][wasmer::Instance]
+/// was interrupted with [`MeteringInterruptHandle::interrupt`].
+///
+/// # Panic
+///
+/// The given [`Instance`][wasmer::Instance] must have been processed
+/// with a [`Metering`] middleware created with
+/// [`Metering::with_interruption`] at compile time, otherwise this
+/// will panic.
+pub fn is_interrupted(ctx: &mut impl AsStoreMut, instance: &Instance) -> bool {
+    let interrupted: i32 = instance
+        .exports
+        .get_global("wasmer_metering_interrupted")
+        .expect("Can't get `wasmer_metering_interrupted` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_metering_interrupted` from Instance has wrong type");
+
+    interrupted > 0
+}
+
+/// Lets an [`Instance`][wasmer::Instance] whose execution was
+/// interrupted run again.
+///
+/// # Panic
+///
+/// The given [`Instance`][wasmer::Instance] must have been processed
+/// with a [`Metering`] middleware created with
+/// [`Metering::with_interruption`] at compile time, otherwise this
+/// will panic.
+pub fn clear_interrupt(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    instance
+        .exports
+        .get_global("wasmer_metering_interrupted")
+        .expect("Can't get `wasmer_metering_interrupted` from Instance")
+        .set(ctx, 0i32.into())
+        .expect("Can't set `wasmer_metering_interrupted` in Instance");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -396,6 +616,12 @@ mod tests {
             MeteringPoints::Remaining(10)
         );
 
+        // The execution can only be interrupted when asked for
+        assert!(instance
+            .exports
+            .get_global("wasmer_metering_interrupted")
+            .is_err());
+
         // First call
         //
         // Calling add_one costs 4 points. Here are the details of how it has been computed:
@@ -485,4 +711,69 @@ mod tests {
             MeteringPoints::Remaining(4)
         );
     }
+
+    #[test]
+    fn interrupt_works() {
+        let metering = Arc::new(Metering::new(u64::MAX, |_: &Operator| 0).with_interruption());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let wasm = wat2wasm(
+            br#"
+            (module
+            (func (export "spin")
+                (loop $again (br $again))))
+            "#,
+        )
+        .unwrap();
+        let module = Module::new(&store, wasm).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let spin: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("spin")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert!(!is_interrupted(&mut store, &instance));
+
+        let handle = interrupt_handle(&mut store, &instance);
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            unsafe { handle.interrupt() };
+        });
+        assert!(spin.call(&mut store).is_err());
+        interrupter.join().unwrap();
+        assert!(is_interrupted(&mut store, &instance));
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(u64::MAX)
+        );
+
+        // The next calls trap until the interruption is cleared
+        assert!(spin.call(&mut store).is_err());
+        clear_interrupt(&mut store, &instance);
+        assert!(!is_interrupted(&mut store, &instance));
+    }
+
+    #[test]
+    fn deterministic_id() {
+        // The cost function can't be identified without an id
+        assert_eq!(Metering::new(10, cost_function).deterministic_id(), None);
+
+        let metering = Metering::new(10, cost_function).with_cost_function_id("cost");
+        let interruptible = Metering::new(10, cost_function)
+            .with_cost_function_id("cost")
+            .with_interruption();
+        assert!(metering.deterministic_id().is_some());
+        assert_ne!(
+            metering.deterministic_id(),
+            interruptible.deterministic_id()
+        );
+        assert_ne!(
+            metering.deterministic_id(),
+            Metering::new(20, cost_function)
+                .with_cost_function_id("cost")
+                .deterministic_id()
+        );
+    }
 }
@@ -31,6 +31,9 @@ pub enum DeserializeError {
     /// The provided binary is corrupted
     #[error("corrupted binary: {0}")]
     CorruptedBinary(String),
+    /// The binary is not signed by a trusted key
+    #[error("untrusted binary: {0}")]
+    Untrusted(String),
     /// The binary was valid, but we got an error when
     /// trying to allocate the required resources.
     #[error(transparent)]
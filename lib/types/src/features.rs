@@ -115,6 +115,10 @@ impl Features {
     /// [proposal]: https://github.com/webassembly/simd
     pub fn simd(&mut self, enable: bool) -> &mut Self {
         self.simd = enable;
+        // Relaxed SIMD depends on SIMD
+        if !enable {
+            self.relaxed_simd(false);
+        }
         self
     }
 
@@ -232,6 +236,50 @@ impl Features {
         self.memory64 = enable;
         self
     }
+
+    /// Configures whether the WebAssembly relaxed SIMD proposal will
+    /// be enabled.
+    ///
+    /// The [WebAssembly relaxed SIMD proposal][proposal] is not
+    /// currently fully standardized and is undergoing development.
+    /// Support for this feature can be enabled through this method for
+    /// appropriate WebAssembly modules.
+    ///
+    /// This feature gates SIMD instructions whose results may depend on
+    /// the host, such as fused multiply-add. Note that enabling the relaxed
+    /// SIMD feature will also enable the SIMD feature.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/relaxed-simd
+    pub fn relaxed_simd(&mut self, enable: bool) -> &mut Self {
+        self.relaxed_simd = enable;
+        // The relaxed SIMD proposal depends on the SIMD proposal
+        if enable {
+            self.simd(true);
+        }
+        self
+    }
+
+    /// Configures whether the WebAssembly extended constant expressions
+    /// proposal will be enabled.
+    ///
+    /// The [WebAssembly extended constant expressions proposal][proposal]
+    /// is not currently fully standardized and is undergoing development.
+    /// Support for this feature can be enabled through this method for
+    /// appropriate WebAssembly modules.
+    ///
+    /// This feature allows `add`, `sub` and `mul` integer instructions in
+    /// the initializers of globals and in the offsets of element and data
+    /// segments.
+    ///
+    /// This is `false` by default.
+    ///
+    /// [proposal]: https://github.com/WebAssembly/extended-const
+    pub fn extended_const(&mut self, enable: bool) -> &mut Self {
+        self.extended_const = enable;
+        self
+    }
 }
 
 impl Default for Features {
@@ -340,4 +388,22 @@ mod test_features {
         features.memory64(true);
         assert!(features.memory64);
     }
+
+    #[test]
+    fn enable_relaxed_simd() {
+        let mut features = Features::new();
+        features.simd(false).relaxed_simd(true);
+        assert!(features.relaxed_simd);
+        assert!(features.simd);
+
+        features.simd(false);
+        assert!(!features.relaxed_simd);
+    }
+
+    #[test]
+    fn enable_extended_const() {
+        let mut features = Features::new();
+        features.extended_const(true);
+        assert!(features.extended_const);
+    }
 }
@@ -1,20 +1,110 @@
 use crate::indexes::{FunctionIndex, GlobalIndex, MemoryIndex, TableIndex};
 use crate::lib::std::boxed::Box;
+use crate::lib::std::vec::Vec;
 
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 
+/// An operator of a constant expression computing an integer.
+///
+/// Besides constants and `global.get`, the [extended constant
+/// expressions proposal] allows additions, subtractions and
+/// multiplications.
+///
+/// [extended constant expressions proposal]: https://github.com/WebAssembly/extended-const
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[archive(as = "Self")]
+pub enum InitExprOp {
+    /// An `i32.const`.
+    I32Const(i32),
+    /// An `i64.const`.
+    I64Const(i64),
+    /// A `global.get` of an `i32` or `i64` global.
+    GlobalGet(GlobalIndex),
+    /// An `i32.add`.
+    I32Add,
+    /// An `i32.sub`.
+    I32Sub,
+    /// An `i32.mul`.
+    I32Mul,
+    /// An `i64.add`.
+    I64Add,
+    /// An `i64.sub`.
+    I64Sub,
+    /// An `i64.mul`.
+    I64Mul,
+}
+
+/// A constant expression computing an integer, such as the offset of an
+/// element or data segment.
+///
+/// The operators are evaluated in order on a stack, as in the body of a
+/// function.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct InitExpr {
+    ops: Box<[InitExprOp]>,
+}
+
+impl InitExpr {
+    /// Creates a new constant expression from its operators.
+    pub fn new(ops: impl Into<Box<[InitExprOp]>>) -> Self {
+        Self { ops: ops.into() }
+    }
+
+    /// The operators of the expression.
+    pub fn ops(&self) -> &[InitExprOp] {
+        &self.ops
+    }
+
+    /// Evaluates the expression, calling `global` to get the value of a
+    /// global.
+    ///
+    /// Values are computed as `i64`s: the result of an expression of type
+    /// `i32` is in the low 32 bits and `global` only needs to set the low
+    /// 32 bits of the value of an `i32` global.
+    ///
+    /// Panics if the expression is not well typed, which can't happen for
+    /// an expression of a validated module.
+    pub fn eval(&self, mut global: impl FnMut(GlobalIndex) -> i64) -> i64 {
+        let mut stack: Vec<i64> = Vec::with_capacity(self.ops.len());
+        for op in self.ops.iter() {
+            let value = match *op {
+                InitExprOp::I32Const(value) => value as i64,
+                InitExprOp::I64Const(value) => value,
+                InitExprOp::GlobalGet(index) => global(index),
+                _ => {
+                    let b = stack.pop().expect("invalid constant expression");
+                    let a = stack.pop().expect("invalid constant expression");
+                    let (a32, b32) = (a as i32, b as i32);
+                    match *op {
+                        InitExprOp::I32Add => a32.wrapping_add(b32) as i64,
+                        InitExprOp::I32Sub => a32.wrapping_sub(b32) as i64,
+                        InitExprOp::I32Mul => a32.wrapping_mul(b32) as i64,
+                        InitExprOp::I64Add => a.wrapping_add(b),
+                        InitExprOp::I64Sub => a.wrapping_sub(b),
+                        InitExprOp::I64Mul => a.wrapping_mul(b),
+                        _ => unreachable!(),
+                    }
+                }
+            };
+            stack.push(value);
+        }
+        assert_eq!(stack.len(), 1, "invalid constant expression");
+        stack[0]
+    }
+}
+
 /// A WebAssembly table initializer.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct TableInitializer {
     /// The index of a table to initialize.
     pub table_index: TableIndex,
-    /// Optionally, a global variable giving a base index.
-    pub base: Option<GlobalIndex>,
-    /// The offset to add to the base.
-    pub offset: usize,
+    /// The constant expression giving the first element to initialize.
+    pub offset_expr: InitExpr,
     /// The values to write into the table elements.
     pub elements: Box<[FunctionIndex]>,
 }
@@ -27,11 +117,8 @@ pub struct DataInitializerLocation {
     /// The index of the memory to initialize.
     pub memory_index: MemoryIndex,
 
-    /// Optionally a Global variable base to initialize at.
-    pub base: Option<GlobalIndex>,
-
-    /// A constant offset to initialize at.
-    pub offset: usize,
+    /// The constant expression giving the offset to initialize at.
+    pub offset_expr: InitExpr,
 }
 
 /// A data initializer for linear memory.
@@ -66,3 +153,35 @@ impl OwnedDataInitializer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_init_expr() {
+        let expr = InitExpr::new([InitExprOp::I32Const(-3)]);
+        assert_eq!(expr.eval(|_| unreachable!()) as i32, -3);
+
+        // `global.get 1 + 16 * 4`, with a global whose high bits are garbage.
+        let expr = InitExpr::new([
+            InitExprOp::GlobalGet(GlobalIndex::from_u32(1)),
+            InitExprOp::I32Const(16),
+            InitExprOp::I32Const(4),
+            InitExprOp::I32Mul,
+            InitExprOp::I32Add,
+        ]);
+        let value = expr.eval(|index| {
+            assert_eq!(index, GlobalIndex::from_u32(1));
+            0x7fff_0000_0000_0400
+        });
+        assert_eq!(value as i32, 0x440);
+
+        let expr = InitExpr::new([
+            InitExprOp::I64Const(i64::MIN),
+            InitExprOp::I64Const(1),
+            InitExprOp::I64Sub,
+        ]);
+        assert_eq!(expr.eval(|_| unreachable!()), i64::MAX);
+    }
+}
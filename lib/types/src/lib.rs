@@ -74,7 +74,9 @@ pub use crate::compilation::target::{
     Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, CpuFeature, Endianness,
     Environment, OperatingSystem, PointerWidth, Target, Triple, Vendor,
 };
-pub use crate::serialize::{MetadataHeader, SerializableCompilation, SerializableModule};
+pub use crate::serialize::{
+    MetadataHeader, SerializableCompilation, SerializableFunction, SerializableModule,
+};
 pub use error::{
     CompileError, DeserializeError, ImportError, MemoryError, MiddlewareError,
     ParseCpuFeatureError, PreInstantiationError, SerializeError, WasmError, WasmResult,
@@ -89,7 +91,8 @@ pub use crate::indexes::{
     SignatureIndex, TableIndex,
 };
 pub use crate::initializers::{
-    DataInitializer, DataInitializerLocation, OwnedDataInitializer, TableInitializer,
+    DataInitializer, DataInitializerLocation, InitExpr, InitExprOp, OwnedDataInitializer,
+    TableInitializer,
 };
 pub use crate::memory::{Memory32, Memory64, MemorySize};
 pub use crate::module::{ExportsIterator, ImportKey, ImportsIterator, ModuleInfo};
@@ -1,14 +1,14 @@
 use crate::entity::PrimaryMap;
 use crate::{
-    compilation::target::CpuFeature, CompileModuleInfo, CompiledFunctionFrameInfo, CustomSection,
-    DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex,
-    SerializeError, SignatureIndex, TableIndex, TableStyle,
+    compilation::target::CpuFeature, CompileModuleInfo, CompiledFunction,
+    CompiledFunctionFrameInfo, CustomSection, DeserializeError, Dwarf, Features, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, MemoryIndex, MemoryStyle, ModuleInfo, OwnedDataInitializer,
+    Relocation, SectionIndex, SerializeError, SignatureIndex, TableIndex, TableStyle,
 };
 use enumset::EnumSet;
 use rkyv::{
     archived_value, de::deserializers::SharedDeserializeMap, ser::serializers::AllocSerializer,
-    ser::Serializer as RkyvSerializer, Archive, Deserialize as RkyvDeserialize,
+    ser::Serializer as RkyvSerializer, AlignedVec, Archive, Deserialize as RkyvDeserialize,
     Serialize as RkyvSerialize,
 };
 use std::convert::TryInto;
@@ -61,6 +61,8 @@ pub struct SerializableModule {
     pub data_initializers: Box<[OwnedDataInitializer]>,
     /// CPU Feature flags for this compilation
     pub cpu_features: u64,
+    /// Triple of the target the module was compiled for
+    pub target_triple: String,
 }
 
 fn to_serialize_error(err: impl std::error::Error) -> SerializeError {
@@ -142,6 +144,11 @@ impl SerializableModule {
         EnumSet::from_u64(self.cpu_features)
     }
 
+    /// Returns the triple of the target this Artifact was compiled for
+    pub fn target_triple(&self) -> &str {
+        &self.target_triple
+    }
+
     /// Returns data initializers to pass to `InstanceHandle::initialize`
     pub fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.data_initializers
@@ -165,6 +172,65 @@ impl SerializableModule {
     }
 }
 
+/// A function compiled on its own, serialized to keep it in a function cache
+/// across processes.
+#[derive(Archive, RkyvDeserialize, RkyvSerialize)]
+#[allow(missing_docs)]
+pub struct SerializableFunction {
+    pub function: CompiledFunction,
+    /// The offset of the function body in the module it was compiled from
+    pub module_offset: u64,
+    /// The data that the compiler keeps along with the function, in its
+    /// own encoding
+    pub extra: Option<Vec<u8>>,
+}
+
+impl SerializableFunction {
+    /// Serialize a function into bytes
+    /// The bytes will have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    pub fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        let mut serializer = AllocSerializer::<4096>::default();
+        let pos = serializer
+            .serialize_value(self)
+            .map_err(to_serialize_error)? as u64;
+        let mut serialized_data = serializer.into_serializer().into_inner();
+        serialized_data.extend_from_slice(&pos.to_le_bytes());
+        Ok(serialized_data.to_vec())
+    }
+
+    /// Deserialize a function from a slice, in the format produced by
+    /// [`SerializableFunction::serialize`].
+    ///
+    /// # Safety
+    ///
+    /// Like [`SerializableModule::deserialize`], the serialized data is not
+    /// validated, so it must have been produced by `serialize`.
+    pub unsafe fn deserialize(slice: &[u8]) -> Result<Self, DeserializeError> {
+        if slice.len() < 8 {
+            return Err(DeserializeError::Incompatible(
+                "invalid serialized data".into(),
+            ));
+        }
+        let (data, pos) = slice.split_at(slice.len() - 8);
+        let mut pos_bytes: [u8; 8] = Default::default();
+        pos_bytes.copy_from_slice(pos);
+        let pos = u64::from_le_bytes(pos_bytes) as usize;
+        if pos.saturating_add(mem::size_of::<ArchivedSerializableFunction>()) > data.len() {
+            return Err(DeserializeError::Incompatible(
+                "invalid serialized data".into(),
+            ));
+        }
+        // The slice might not be aligned for the archived types
+        let mut aligned = AlignedVec::with_capacity(data.len());
+        aligned.extend_from_slice(data);
+        let archived = archived_value::<Self>(aligned.as_slice(), pos);
+        let mut deserializer = SharedDeserializeMap::new();
+        RkyvDeserialize::deserialize(archived, &mut deserializer)
+            .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))
+    }
+}
+
 /// Metadata header which holds an ABI version and the length of the remaining
 /// metadata.
 #[repr(C)]
@@ -178,7 +244,7 @@ pub struct MetadataHeader {
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    const CURRENT_VERSION: u32 = 1;
+    const CURRENT_VERSION: u32 = 3;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
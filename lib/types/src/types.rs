@@ -1,4 +1,5 @@
 use crate::indexes::{FunctionIndex, GlobalIndex};
+use crate::initializers::InitExpr;
 use crate::lib::std::borrow::ToOwned;
 use crate::lib::std::fmt;
 use crate::lib::std::format;
@@ -398,10 +399,9 @@ impl fmt::Display for GlobalType {
 }
 
 /// Globals are initialized via the `const` operators or by referring to another import.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive)]
-#[archive(as = "Self")]
 pub enum GlobalInit {
     /// An `i32.const`.
     I32Const(i32),
@@ -422,6 +422,8 @@ pub enum GlobalInit {
     RefNullConst,
     /// A `ref.func <index>`.
     RefFunc(FunctionIndex),
+    /// An extended constant expression computing an `i32` or an `i64`.
+    Expr(InitExpr),
 }
 
 // Table Types
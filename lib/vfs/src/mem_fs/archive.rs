@@ -0,0 +1,148 @@
+//! Unpacking of tar archives into the in-memory file system.
+
+use super::FileSystem;
+use crate::{FileSystem as _, FsError, Result};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+impl FileSystem {
+    /// Unpacks the directories and regular files of the tar archive read
+    /// from `archive` into the directory at `mount_point`, which is created
+    /// if it doesn't exist.
+    ///
+    /// Other entries, like symbolic links, are skipped since the in-memory
+    /// file system doesn't support them. Entries whose path leaves the
+    /// archive are rejected.
+    pub fn unpack_tar<R: Read>(&self, mount_point: &Path, archive: R) -> Result<()> {
+        self.create_dir_all(mount_point)?;
+        let mut archive = tar::Archive::new(archive);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = match entry_path(mount_point, &entry.path()?)? {
+                Some(path) => path,
+                // The root of the archive
+                None => continue,
+            };
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_dir() {
+                self.create_dir_all(&path)?;
+            } else if entry_type.is_file() {
+                if let Some(parent) = path.parent() {
+                    self.create_dir_all(parent)?;
+                }
+                let mut file = self
+                    .new_open_options()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&path)?;
+                io::copy(&mut entry, &mut file)?;
+            } else {
+                tracing::debug!("skipping unsupported tar entry at {}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let mut current = PathBuf::from("/");
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                current.push(name);
+                match self.create_dir(&current) {
+                    Ok(()) | Err(FsError::AlreadyExists) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The path of an archive entry once unpacked at `mount_point`, or `None`
+/// for the root of the archive.
+fn entry_path(mount_point: &Path, entry_path: &Path) -> Result<Option<PathBuf>> {
+    let mut path = mount_point.to_path_buf();
+    let mut is_root = true;
+    for component in entry_path.components() {
+        match component {
+            Component::Normal(name) => {
+                path.push(name);
+                is_root = false;
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(FsError::InvalidInput)
+            }
+        }
+    }
+    Ok(if is_root { None } else { Some(path) })
+}
+
+#[cfg(test)]
+mod test_archive {
+    use crate::{mem_fs::*, FileSystem as FS, FsError};
+    use std::io::Read;
+    use std::path::Path;
+
+    fn archive(entries: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            match contents {
+                Some(contents) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_size(contents.len() as u64);
+                    header.set_mode(0o644);
+                    builder.append_data(&mut header, path, *contents).unwrap();
+                }
+                None => {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_size(0);
+                    header.set_mode(0o755);
+                    builder.append_data(&mut header, path, &[][..]).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn read(fs: &FileSystem, path: &str) -> String {
+        let mut file = fs.new_open_options().read(true).open(path).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_unpack_tar() {
+        let fs = FileSystem::default();
+        let archive = archive(&[
+            ("./", None),
+            ("./etc/", None),
+            ("./etc/hosts", Some(b"127.0.0.1 localhost\n")),
+            ("bin/tool", Some(b"tool")),
+        ]);
+        fs.unpack_tar(Path::new("/app"), &archive[..]).unwrap();
+
+        assert_eq!(read(&fs, "/app/etc/hosts"), "127.0.0.1 localhost\n");
+        assert_eq!(read(&fs, "/app/bin/tool"), "tool");
+        assert!(fs.metadata(Path::new("/app/bin")).unwrap().is_dir());
+    }
+
+    #[test]
+    fn test_unpack_tar_rejects_paths_leaving_the_archive() {
+        let fs = FileSystem::default();
+        let mut archive = archive(&[("file", Some(b"contents"))]);
+        // `append_data` refuses such paths, so the name is patched in place
+        archive[..7].copy_from_slice(b"../file");
+        let mut header = tar::Header::from_byte_slice(&archive[..512]).clone();
+        header.set_cksum();
+        archive[..512].copy_from_slice(header.as_bytes());
+
+        assert_eq!(
+            fs.unpack_tar(Path::new("/"), &archive[..]),
+            Err(FsError::InvalidInput)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "tar")]
+mod archive;
 mod file;
 mod file_opener;
 mod filesystem;
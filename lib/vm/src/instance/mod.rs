@@ -36,7 +36,7 @@ use std::sync::Arc;
 use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
     DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
-    LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError,
+    InitExpr, LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError,
     MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer, VMOffsets,
 };
 
@@ -1130,20 +1130,18 @@ impl InstanceHandle {
 
 /// Compute the offset for a memory data initializer.
 fn get_memory_init_start(init: &DataInitializer<'_>, instance: &Instance) -> usize {
-    let mut start = init.location.offset;
-
-    if let Some(base) = init.location.base {
-        let val = unsafe {
-            if let Some(def_index) = instance.module.local_global_index(base) {
-                instance.global(def_index).val.u32
-            } else {
-                instance.imported_global(base).definition.as_ref().val.u32
-            }
-        };
-        start += usize::try_from(val).unwrap();
-    }
+    eval_init_expr(&init.location.offset_expr, instance) as u32 as usize
+}
 
-    start
+/// Evaluate a constant expression, reading the globals of `instance`.
+fn eval_init_expr(expr: &InitExpr, instance: &Instance) -> i64 {
+    expr.eval(|index| unsafe {
+        if let Some(def_index) = instance.module.local_global_index(index) {
+            instance.global(def_index).val.i64
+        } else {
+            instance.imported_global(index).definition.as_ref().val.i64
+        }
+    })
 }
 
 #[allow(clippy::mut_from_ref)]
@@ -1166,20 +1164,7 @@ unsafe fn get_memory_slice<'instance>(
 
 /// Compute the offset for a table element initializer.
 fn get_table_init_start(init: &TableInitializer, instance: &Instance) -> usize {
-    let mut start = init.offset;
-
-    if let Some(base) = init.base {
-        let val = unsafe {
-            if let Some(def_index) = instance.module.local_global_index(base) {
-                instance.global(def_index).val.u32
-            } else {
-                instance.imported_global(base).definition.as_ref().val.u32
-            }
-        };
-        start += usize::try_from(val).unwrap();
-    }
-
-    start
+    eval_init_expr(&init.offset_expr, instance) as u32 as usize
 }
 
 /// Initialize the table memory from the provided initializers.
@@ -1288,6 +1273,10 @@ fn initialize_globals(instance: &Instance) {
                     let funcref = instance.func_ref(*func_idx).unwrap();
                     (*to).val = funcref.into_raw();
                 }
+                GlobalInit::Expr(expr) => {
+                    // An `i32` global only reads the low bits.
+                    (*to).val.i64 = eval_init_expr(expr, instance);
+                }
             }
         }
     }
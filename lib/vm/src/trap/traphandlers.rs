@@ -15,13 +15,17 @@ use scopeguard::defer;
 use std::any::Any;
 use std::cell::Cell;
 use std::error::Error;
+use std::future::Future;
 use std::io;
 use std::mem;
 #[cfg(unix)]
 use std::mem::MaybeUninit;
+use std::pin::Pin;
 use std::ptr::{self, NonNull};
 use std::sync::atomic::{compiler_fence, AtomicPtr, Ordering};
 use std::sync::{Mutex, Once};
+use std::task::{Context, Poll};
+use thiserror::Error;
 use wasmer_types::TrapCode;
 
 // TrapInformation can be stored in the "Undefined Instruction" itself.
@@ -934,6 +938,162 @@ pub fn on_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
     yielder.on_parent_stack(move || (wrapped.0)())
 }
 
+thread_local! {
+    static ASYNC_CALL: Cell<Option<NonNull<AsyncCallContext>>> = Cell::new(None);
+}
+
+/// The value an [`AsyncCall`] is resumed with: the context of the task
+/// polling it, or null if the call is cancelled.
+type AsyncResume = *mut Context<'static>;
+
+/// Information about the [`AsyncCall`] running on the current thread, which
+/// is used by [`block_on`] to poll futures and suspend the call.
+struct AsyncCallContext {
+    yielder: NonNull<Yielder<AsyncResume, ()>>,
+    cx: Cell<AsyncResume>,
+}
+
+/// The thread-local state of the trap handlers.
+///
+/// An [`AsyncCall`] swaps it when it is suspended or resumed, since other
+/// calls can run on the thread in the meantime, and since it can be resumed
+/// on another thread.
+#[derive(Clone, Copy)]
+struct ThreadState {
+    yielder: Option<NonNull<Yielder<(), UnwindReason>>>,
+    trap_handler: *mut TrapHandlerContext,
+    async_call: Option<NonNull<AsyncCallContext>>,
+}
+
+impl ThreadState {
+    fn current() -> Self {
+        Self {
+            yielder: YIELDER.with(|cell| cell.get()),
+            trap_handler: TRAP_HANDLER.with(|ptr| ptr.load(Ordering::Relaxed)),
+            async_call: ASYNC_CALL.with(|cell| cell.get()),
+        }
+    }
+
+    fn restore(self) {
+        YIELDER.with(|cell| cell.set(self.yielder));
+        TRAP_HANDLER.with(|ptr| ptr.store(self.trap_handler, Ordering::Relaxed));
+        ASYNC_CALL.with(|cell| cell.set(self.async_call));
+    }
+}
+
+/// A function call running on its own stack, which is suspended while the
+/// futures passed to [`block_on`] are pending.
+///
+/// The call completes when the `AsyncCall` is polled to completion. If it is
+/// dropped before, the pending and future calls to [`block_on`] return
+/// [`BlockOnError::Cancelled`] until the call returns.
+pub struct AsyncCall<'a, T> {
+    coro: ScopedCoroutine<'a, AsyncResume, (), T, DefaultStack>,
+}
+
+// The call only borrows `Send` values and is only suspended by `block_on`,
+// whose futures are `Send`. Its thread-local state is swapped by `resume`.
+unsafe impl<T: Send> Send for AsyncCall<'_, T> {}
+
+impl<'a, T> AsyncCall<'a, T> {
+    /// Creates a call of `f`, which runs when the `AsyncCall` is polled.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'a,
+    {
+        let coro = ScopedCoroutine::with_stack(DefaultStack::default(), move |yielder, cx| {
+            let context = AsyncCallContext {
+                yielder: yielder.into(),
+                cx: Cell::new(cx),
+            };
+            ThreadState {
+                yielder: None,
+                trap_handler: ptr::null_mut(),
+                async_call: Some(NonNull::from(&context)),
+            }
+            .restore();
+            f()
+        });
+        Self { coro }
+    }
+
+    fn resume(&mut self, cx: AsyncResume) -> Poll<T> {
+        let state = ThreadState::current();
+        defer! {
+            state.restore();
+        }
+        match self.coro.resume(cx) {
+            CoroutineResult::Yield(()) => Poll::Pending,
+            CoroutineResult::Return(result) => Poll::Ready(result),
+        }
+    }
+}
+
+impl<T> Future for AsyncCall<'_, T> {
+    type Output = Result<T, Trap>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The call may be resumed on a thread that hasn't run Wasm code yet.
+        lazy_per_thread_init()?;
+
+        // The lifetime of the context is erased, it's only used during this poll.
+        let cx: AsyncResume = (cx as *mut Context<'_>).cast();
+        self.get_mut().resume(cx).map(Ok)
+    }
+}
+
+impl<T> Drop for AsyncCall<'_, T> {
+    fn drop(&mut self) {
+        // Cancel the call instead of unwinding its stack, which may contain
+        // Wasm frames.
+        if self.coro.started() {
+            while !self.coro.done() {
+                let _ = self.resume(ptr::null_mut());
+            }
+        }
+    }
+}
+
+/// Error returned by [`block_on`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum BlockOnError {
+    /// `block_on` was called outside of an [`AsyncCall`].
+    #[error("async host functions can only be called from an async call")]
+    NotInAsyncCall,
+    /// The [`AsyncCall`] was dropped before it completed.
+    #[error("the async call was cancelled")]
+    Cancelled,
+}
+
+/// Polls `future` to completion from the [`AsyncCall`] running on the current
+/// thread, suspending the call while it is pending.
+pub fn block_on<F: Future>(future: F) -> Result<F::Output, BlockOnError> {
+    let context = ASYNC_CALL
+        .with(|cell| cell.get())
+        .ok_or(BlockOnError::NotInAsyncCall)?;
+    // The context lives on the stack of the call, which is running.
+    let context = unsafe { context.as_ref() };
+
+    let mut future = future;
+    // The future is shadowed, so it's never moved again.
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+    loop {
+        let cx = context.cx.get();
+        if cx.is_null() {
+            return Err(BlockOnError::Cancelled);
+        }
+        // `cx` is the context of the poll of the call, which is running.
+        if let Poll::Ready(output) = future.as_mut().poll(unsafe { &mut *cx }) {
+            return Ok(output);
+        }
+
+        let state = ThreadState::current();
+        let cx = unsafe { context.yielder.as_ref() }.suspend(());
+        state.restore();
+        context.cx.set(cx);
+    }
+}
+
 #[cfg(windows)]
 pub fn lazy_per_thread_init() -> Result<(), Trap> {
     // We need additional space on the stack to handle stack overflow
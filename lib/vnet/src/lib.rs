@@ -231,6 +231,26 @@ pub trait VirtualSocket: fmt::Debug + Send + Sync + 'static {
 
     /// Returns the status/state of the socket
     fn status(&self) -> Result<SocketStatus>;
+
+    /// Sets how long a receive waits for data before it fails with
+    /// `TimedOut` (`None` waits forever)
+    fn set_recv_timeout(&mut self, _timeout: Option<Duration>) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Returns how long a receive waits for data
+    fn recv_timeout(&self) -> Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// Makes the receives fail with `WouldBlock` instead of waiting when
+    /// no data is available
+    ///
+    /// Only the sockets whose receives would otherwise wait forever need
+    /// it, by default the socket is left as it is.
+    fn set_nonblocking(&mut self, _nonblocking: bool) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
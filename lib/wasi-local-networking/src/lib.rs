@@ -12,6 +12,9 @@ use wasmer_vnet::{
     VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
 };
 
+#[cfg(feature = "userspace")]
+pub mod userspace;
+
 #[derive(Debug, Default)]
 pub struct LocalNetworking {}
 
@@ -408,6 +411,14 @@ impl VirtualSocket for LocalTcpStream {
     fn status(&self) -> Result<SocketStatus> {
         Ok(SocketStatus::Opened)
     }
+
+    fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.set_opt_time(TimeType::ReadTimeout, timeout)
+    }
+
+    fn recv_timeout(&self) -> Result<Option<Duration>> {
+        self.opt_time(TimeType::ReadTimeout)
+    }
 }
 
 #[derive(Debug)]
@@ -581,4 +592,14 @@ impl VirtualSocket for LocalUdpSocket {
     fn status(&self) -> Result<SocketStatus> {
         Ok(SocketStatus::Opened)
     }
+
+    fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.0
+            .set_read_timeout(timeout)
+            .map_err(io_err_into_net_error)
+    }
+
+    fn recv_timeout(&self) -> Result<Option<Duration>> {
+        self.0.read_timeout().map_err(io_err_into_net_error)
+    }
 }
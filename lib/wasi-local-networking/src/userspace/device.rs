@@ -0,0 +1,340 @@
+use bytes::Bytes;
+use smoltcp::phy::{self, DeviceCapabilities, Medium};
+use smoltcp::time::Instant;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+use wasmer_vnet::{NetworkError, Result};
+
+/// Default size of an Ethernet frame (1500 byte IP MTU plus the 14 byte header)
+pub const DEFAULT_FRAME_MTU: usize = 1514;
+
+/// Maximum number of frames that are queued on a raw socket before the
+/// oldest ones are dropped
+const RAW_QUEUE_LIMIT: usize = 1024;
+
+/// A network device that carries Ethernet frames in and out of the
+/// userspace TCP/IP stack
+pub trait PacketDevice: fmt::Debug + Send + 'static {
+    /// Returns the next frame that arrived on this device (non-blocking)
+    fn recv(&mut self) -> Option<Vec<u8>>;
+
+    /// Sends a frame out on this device
+    fn send(&mut self, frame: &[u8]) -> Result<()>;
+
+    /// Maximum size of a frame (including the Ethernet header)
+    fn mtu(&self) -> usize {
+        DEFAULT_FRAME_MTU
+    }
+}
+
+/// A device that exchanges frames over in-process channels, which is useful
+/// to wire several stacks together or to let the host inject and capture
+/// packets
+#[derive(Debug)]
+pub struct ChannelDevice {
+    tx: mpsc::Sender<Vec<u8>>,
+    rx: mpsc::Receiver<Vec<u8>>,
+}
+
+impl ChannelDevice {
+    /// Creates a device that sends its frames to `tx` and receives them from `rx`
+    pub fn new(tx: mpsc::Sender<Vec<u8>>, rx: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self { tx, rx }
+    }
+
+    /// Creates two devices that are connected to each other like the two
+    /// ends of a network cable
+    pub fn pair() -> (Self, Self) {
+        let (tx1, rx1) = mpsc::channel();
+        let (tx2, rx2) = mpsc::channel();
+        (Self::new(tx1, rx2), Self::new(tx2, rx1))
+    }
+}
+
+impl PacketDevice for ChannelDevice {
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.rx.try_recv().ok()
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<()> {
+        self.tx
+            .send(frame.to_vec())
+            .map_err(|_| NetworkError::BrokenPipe)
+    }
+}
+
+/// A device that is backed by a TAP interface of the host operating system
+///
+/// The interface is only reached through the file descriptor of
+/// `/dev/net/tun`, which is owned by a `File` and hence can move between
+/// threads like any other file.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug)]
+pub struct TapDevice {
+    file: std::fs::File,
+    mtu: usize,
+}
+
+/// `_IOW('T', 202, int)`, which libc doesn't define
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[cfg(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+))]
+const TUNSETIFF: libc::c_ulong = 0x8004_54ca;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+)))]
+const TUNSETIFF: libc::c_ulong = 0x4004_54ca;
+
+/// The `struct ifreq` of the interface ioctls, with the flags or the MTU
+/// at the start of its union
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+struct IfReq {
+    name: [libc::c_char; libc::IFNAMSIZ],
+    data: libc::c_int,
+    _union: [u8; 20],
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl IfReq {
+    fn new(name: &str) -> Result<Self> {
+        let mut ifreq = Self {
+            name: [0; libc::IFNAMSIZ],
+            data: 0,
+            _union: [0; 20],
+        };
+        // The name is NUL terminated
+        if name.len() >= libc::IFNAMSIZ || name.bytes().any(|b| b == 0) {
+            return Err(NetworkError::InvalidInput);
+        }
+        for (dst, src) in ifreq.name.iter_mut().zip(name.bytes()) {
+            *dst = src as libc::c_char;
+        }
+        Ok(ifreq)
+    }
+
+    /// Runs the `request` ioctl on `fd` with this request
+    fn ioctl(&mut self, fd: libc::c_int, request: libc::c_ulong) -> Result<()> {
+        // Safety: `self` has the layout of the `struct ifreq` that the
+        // interface ioctls read and write
+        if unsafe { libc::ioctl(fd, request as _, self as *mut Self) } == -1 {
+            return Err(wasmer_vnet::io_err_into_net_error(
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl TapDevice {
+    /// Attaches to the TAP interface called `name` (or creates it if it
+    /// does not exist, which requires the CAP_NET_ADMIN capability)
+    pub fn new(name: &str) -> Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/net/tun")
+            .map_err(wasmer_vnet::io_err_into_net_error)?;
+        let mut ifreq = IfReq::new(name)?;
+        ifreq.data = libc::IFF_TAP | libc::IFF_NO_PI;
+        ifreq.ioctl(file.as_raw_fd(), TUNSETIFF)?;
+
+        // The MTU of the interface excludes the Ethernet header
+        let mut ifreq = IfReq::new(name)?;
+        // Safety: the socket is only used for the ioctl, and closed here
+        let socket = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+        if socket == -1 {
+            return Err(wasmer_vnet::io_err_into_net_error(
+                std::io::Error::last_os_error(),
+            ));
+        }
+        let mtu = ifreq.ioctl(socket, libc::SIOCGIFMTU as libc::c_ulong);
+        unsafe { libc::close(socket) };
+        mtu?;
+        Ok(Self {
+            file,
+            mtu: ifreq.data as usize + 14,
+        })
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl PacketDevice for TapDevice {
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        use std::io::Read;
+        let mut frame = vec![0; self.mtu];
+        match self.file.read(&mut frame[..]) {
+            Ok(len) => {
+                frame.truncate(len);
+                Some(frame)
+            }
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::WouldBlock {
+                    trace!("failed to read a frame from the TAP interface - {}", err);
+                }
+                None
+            }
+        }
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<()> {
+        use std::io::Write;
+        self.file
+            .write(frame)
+            .map(|_| ())
+            .map_err(wasmer_vnet::io_err_into_net_error)
+    }
+
+    fn mtu(&self) -> usize {
+        self.mtu
+    }
+}
+
+/// Receive queue of a raw socket that is fed with a copy of every frame
+/// that arrives on the device
+#[derive(Debug, Default)]
+pub(crate) struct RawTap {
+    pub(crate) queue: Mutex<VecDeque<Bytes>>,
+    pub(crate) promiscuous: AtomicBool,
+}
+
+impl RawTap {
+    fn push(&self, frame: &[u8], mac: &[u8; 6]) {
+        // Unless the socket is promiscuous it only sees frames that are
+        // destined for this interface, broadcasts or multicasts
+        let dst = match frame.get(..6) {
+            Some(dst) => dst,
+            None => return,
+        };
+        if !self.promiscuous.load(Ordering::Relaxed) && dst != &mac[..] && dst[0] & 0x01 == 0 {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= RAW_QUEUE_LIMIT {
+            queue.pop_front();
+        }
+        queue.push_back(Bytes::copy_from_slice(frame));
+    }
+}
+
+/// Adapts a `PacketDevice` (or the lack of one) to the device interface
+/// of the userspace stack and mirrors the received frames to raw sockets
+#[derive(Debug)]
+pub(crate) struct StackDevice {
+    device: Option<Box<dyn PacketDevice>>,
+    mac: [u8; 6],
+    taps: Vec<Weak<RawTap>>,
+}
+
+impl StackDevice {
+    pub(crate) fn new(mac: [u8; 6]) -> Self {
+        Self {
+            device: None,
+            mac,
+            taps: Vec::new(),
+        }
+    }
+
+    pub(crate) fn attach(&mut self, device: Box<dyn PacketDevice>) {
+        self.device.replace(device);
+    }
+
+    pub(crate) fn detach(&mut self) -> Option<Box<dyn PacketDevice>> {
+        self.device.take()
+    }
+
+    pub(crate) fn is_attached(&self) -> bool {
+        self.device.is_some()
+    }
+
+    pub(crate) fn add_tap(&mut self, tap: &Arc<RawTap>) {
+        self.taps.retain(|tap| tap.strong_count() > 0);
+        self.taps.push(Arc::downgrade(tap));
+    }
+
+    /// Sends a frame directly on the device, bypassing the stack
+    pub(crate) fn send_raw(&mut self, frame: &[u8]) -> Result<()> {
+        match self.device.as_mut() {
+            Some(device) => device.send(frame),
+            None => Err(NetworkError::NotConnected),
+        }
+    }
+}
+
+impl<'a> phy::Device<'a> for StackDevice {
+    type RxToken = StackRxToken;
+    type TxToken = StackTxToken<'a>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        let frame = self.device.as_mut()?.recv()?;
+        for tap in self.taps.iter().filter_map(Weak::upgrade) {
+            tap.push(&frame[..], &self.mac);
+        }
+        Some((StackRxToken(frame), StackTxToken(&mut self.device)))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        self.device.as_ref()?;
+        Some(StackTxToken(&mut self.device))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = self
+            .device
+            .as_ref()
+            .map(|device| device.mtu())
+            .unwrap_or(DEFAULT_FRAME_MTU);
+        caps
+    }
+}
+
+pub(crate) struct StackRxToken(Vec<u8>);
+
+impl phy::RxToken for StackRxToken {
+    fn consume<R, F>(mut self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(&mut self.0[..])
+    }
+}
+
+pub(crate) struct StackTxToken<'a>(&'a mut Option<Box<dyn PacketDevice>>);
+
+impl<'a> phy::TxToken for StackTxToken<'a> {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        let mut frame = vec![0u8; len];
+        let ret = f(&mut frame[..])?;
+        if let Some(device) = self.0.as_mut() {
+            if let Err(err) = device.send(&frame[..]) {
+                trace!("userspace stack failed to transmit a frame - {}", err);
+                return Err(smoltcp::Error::Exhausted);
+            }
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,679 @@
+//! Userspace TCP/IP stack for the local networking backend
+//!
+//! The stack runs over a `PacketDevice` (a TAP interface or an in-process
+//! channel) and provides the low level functionality that the host sockets
+//! cannot offer, namely raw sockets, ICMP sockets, address and routing
+//! management and DHCP. TCP, UDP, DNS and HTTP are delegated to the host.
+
+mod device;
+mod socket;
+
+pub use device::*;
+pub use socket::*;
+
+use managed::ManagedSlice;
+use smoltcp::iface::{Interface, InterfaceBuilder, NeighborCache, Route, Routes, SocketHandle};
+use smoltcp::socket::{
+    Dhcpv4Config, Dhcpv4Event, Dhcpv4Socket, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer,
+};
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr as StackCidr, Ipv4Cidr};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+use wasmer_vnet::{
+    IpCidr, IpRoute, NetworkError, Result, SocketHttpRequest, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    VirtualWebSocket,
+};
+
+use crate::LocalNetworking;
+
+/// Locally administered MAC address that is used when none is supplied
+pub const DEFAULT_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+/// Longest time the stack sleeps before it polls the device again
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How long `dhcp_acquire` waits for a lease before giving up
+const DHCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of packets (and bytes) buffered by each ICMP socket
+const ICMP_PACKETS: usize = 16;
+const ICMP_BUFFER: usize = 16 * 1024;
+
+/// State that is shared between the networking implementation, the
+/// sockets it creates and the thread that drives the stack
+pub(crate) struct Shared {
+    iface: Mutex<Interface<'static, StackDevice>>,
+    /// Lease acquired by `dhcp_acquire`, renewed by the thread that drives
+    /// the stack (locked after `iface`)
+    dhcp: Mutex<Option<DhcpLease>>,
+    changed: Condvar,
+    shutdown: AtomicBool,
+}
+
+/// The DHCP client of the stack and the address it has configured
+struct DhcpLease {
+    handle: SocketHandle,
+    address: Option<Ipv4Cidr>,
+}
+
+impl DhcpLease {
+    /// Assigns the address and the default route of a lease, or removes
+    /// them when the lease is lost (`config` is `None`)
+    fn configure(
+        &mut self,
+        iface: &mut Interface<'static, StackDevice>,
+        config: Option<&Dhcpv4Config>,
+    ) -> Result<()> {
+        let previous = self.address.take();
+        let address = config.map(|config| config.address);
+        let stale = [previous, address];
+        iface.update_ip_addrs(|addrs| {
+            if let ManagedSlice::Owned(addrs) = addrs {
+                addrs.retain(|a| {
+                    !stale
+                        .iter()
+                        .flatten()
+                        .any(|c| a.address() == c.address().into())
+                });
+                addrs.extend(address.map(StackCidr::Ipv4));
+            }
+        });
+        self.address = address;
+
+        match config.and_then(|config| config.router) {
+            Some(router) => iface
+                .routes_mut()
+                .add_default_ipv4_route(router)
+                .map(|_| ())
+                .map_err(|_| NetworkError::InvalidInput),
+            // The route of a previous lease goes away with it
+            None if previous.is_some() => {
+                iface.routes_mut().remove_default_ipv4_route();
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the renewals, changes and losses of the lease
+    fn poll(&mut self, iface: &mut Interface<'static, StackDevice>) {
+        let config = match iface.get_socket::<Dhcpv4Socket>(self.handle).poll() {
+            Some(Dhcpv4Event::Configured(config)) => Some(config),
+            Some(Dhcpv4Event::Deconfigured) => None,
+            None => return,
+        };
+        debug!("userspace stack dhcp lease - {:?}", config);
+        if let Err(err) = self.configure(iface, config.as_ref()) {
+            warn!("userspace stack dhcp lease - {}", err);
+        }
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("shutdown", &self.shutdown)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    pub(crate) fn lock(&self) -> Result<MutexGuard<'_, Interface<'static, StackDevice>>> {
+        if self.shutdown.load(Ordering::Acquire) {
+            return Err(NetworkError::ConnectionAborted);
+        }
+        self.iface.lock().map_err(|_| NetworkError::Lock)
+    }
+
+    /// Waits for the stack to be polled (or for a socket to queue data)
+    pub(crate) fn wait<'a>(
+        &self,
+        guard: MutexGuard<'a, Interface<'static, StackDevice>>,
+        timeout: Duration,
+    ) -> Result<MutexGuard<'a, Interface<'static, StackDevice>>> {
+        let (guard, _) = self
+            .changed
+            .wait_timeout(guard, timeout)
+            .map_err(|_| NetworkError::Lock)?;
+        if self.shutdown.load(Ordering::Acquire) {
+            return Err(NetworkError::ConnectionAborted);
+        }
+        Ok(guard)
+    }
+
+    /// Wakes up the stack so that queued packets are transmitted immediately
+    pub(crate) fn notify(&self) {
+        self.changed.notify_all();
+    }
+
+    fn run(&self) {
+        let mut iface = match self.iface.lock() {
+            Ok(iface) => iface,
+            Err(_) => return,
+        };
+        while !self.shutdown.load(Ordering::Acquire) {
+            let now = smoltcp::time::Instant::now();
+            if let Err(err) = iface.poll(now) {
+                trace!("userspace stack poll - {}", err);
+            }
+            if let Ok(mut dhcp) = self.dhcp.lock() {
+                if let Some(lease) = dhcp.as_mut() {
+                    lease.poll(&mut iface);
+                }
+            }
+            let delay = iface
+                .poll_delay(now)
+                .map(Duration::from)
+                .unwrap_or(POLL_INTERVAL)
+                .min(POLL_INTERVAL);
+            self.changed.notify_all();
+            iface = match self.changed.wait_timeout(iface, delay) {
+                Ok((iface, _)) => iface,
+                Err(_) => return,
+            };
+        }
+    }
+}
+
+/// Networking implementation that runs a userspace TCP/IP stack for the
+/// raw, ICMP and interface management operations while TCP, UDP, DNS
+/// and HTTP use the sockets of the host
+#[derive(Debug)]
+pub struct UserspaceNetworking {
+    shared: Arc<Shared>,
+    mac: [u8; 6],
+    host: LocalNetworking,
+}
+
+impl UserspaceNetworking {
+    /// Creates a stack with the given MAC address that is not yet attached
+    /// to a device (use `attach` or `bridge` to connect it)
+    pub fn new(mac: [u8; 6]) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let iface = InterfaceBuilder::new(StackDevice::new(mac), vec![])
+            .hardware_addr(EthernetAddress(mac).into())
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(Vec::new())
+            .routes(Routes::new(BTreeMap::new()))
+            .random_seed(seed)
+            .finalize();
+        let shared = Arc::new(Shared {
+            iface: Mutex::new(iface),
+            dhcp: Mutex::new(None),
+            changed: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        {
+            let shared = shared.clone();
+            std::thread::spawn(move || shared.run());
+        }
+        Self {
+            shared,
+            mac,
+            host: LocalNetworking::default(),
+        }
+    }
+
+    /// Creates a stack with the given MAC address that sends and receives
+    /// its frames on the supplied device
+    pub fn with_device<D>(mac: [u8; 6], device: D) -> Self
+    where
+        D: PacketDevice,
+    {
+        let ret = Self::new(mac);
+        ret.attach(device);
+        ret
+    }
+
+    /// Attaches the stack to a device, replacing the previous one
+    pub fn attach<D>(&self, device: D)
+    where
+        D: PacketDevice,
+    {
+        if let Ok(mut iface) = self.shared.lock() {
+            iface.device_mut().attach(Box::new(device));
+        }
+        self.shared.notify();
+    }
+
+    /// Detaches the stack from its device and returns it
+    pub fn detach(&self) -> Option<Box<dyn PacketDevice>> {
+        self.shared
+            .lock()
+            .ok()
+            .and_then(|mut iface| iface.device_mut().detach())
+    }
+}
+
+impl Default for UserspaceNetworking {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAC)
+    }
+}
+
+impl Drop for UserspaceNetworking {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.shared.notify();
+    }
+}
+
+fn into_stack_cidr(ip: IpAddr, prefix: u8) -> Result<StackCidr> {
+    let max = match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    if prefix > max {
+        return Err(NetworkError::InvalidInput);
+    }
+    Ok(StackCidr::new(ip.into(), prefix))
+}
+
+fn from_stack_cidr(cidr: &StackCidr) -> IpCidr {
+    IpCidr {
+        ip: cidr.address().into(),
+        prefix: cidr.prefix_len(),
+    }
+}
+
+/// Route lifetimes are expressed as the time since the UNIX epoch
+fn into_stack_time(time: Duration) -> smoltcp::time::Instant {
+    smoltcp::time::Instant::from_micros(time.as_micros() as i64)
+}
+
+fn from_stack_time(time: smoltcp::time::Instant) -> Duration {
+    Duration::from_micros(time.total_micros().max(0) as u64)
+}
+
+impl VirtualNetworking for UserspaceNetworking {
+    fn ws_connect(&self, url: &str) -> Result<Box<dyn VirtualWebSocket + Sync>> {
+        self.host.ws_connect(url)
+    }
+
+    fn http_request(
+        &self,
+        url: &str,
+        method: &str,
+        headers: &str,
+        gzip: bool,
+    ) -> Result<SocketHttpRequest> {
+        self.host.http_request(url, method, headers, gzip)
+    }
+
+    /// Bridges the stack onto the TAP interface named `network`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn bridge(&self, network: &str, _access_token: &str, security: StreamSecurity) -> Result<()> {
+        match security {
+            StreamSecurity::Unencrypted | StreamSecurity::AnyEncyption => {}
+            _ => return Err(NetworkError::Unsupported),
+        }
+        self.attach(TapDevice::new(network)?);
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn bridge(&self, _network: &str, _access_token: &str, _security: StreamSecurity) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn unbridge(&self) -> Result<()> {
+        self.detach();
+        Ok(())
+    }
+
+    /// Acquires an IPv4 lease, assigns the address and installs the default
+    /// route. The stack keeps renewing the lease, and updates the address
+    /// and the route when the lease changes or is lost.
+    fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        let mut iface = self.shared.lock()?;
+        if !iface.device().is_attached() {
+            return Err(NetworkError::NotConnected);
+        }
+        // A new lease replaces the previous one
+        let previous = self
+            .shared
+            .dhcp
+            .lock()
+            .map_err(|_| NetworkError::Lock)?
+            .take();
+        if let Some(mut lease) = previous {
+            lease.configure(&mut iface, None)?;
+            iface.remove_socket(lease.handle);
+        }
+        let handle = iface.add_socket(Dhcpv4Socket::new());
+        self.shared.notify();
+
+        let deadline = std::time::Instant::now() + DHCP_TIMEOUT;
+        let config = loop {
+            if let Some(Dhcpv4Event::Configured(config)) =
+                iface.get_socket::<Dhcpv4Socket>(handle).poll()
+            {
+                break Ok(config);
+            }
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                break Err(NetworkError::TimedOut);
+            }
+            iface = self.shared.wait(iface, deadline - now)?;
+        };
+        let config = match config {
+            Ok(config) => config,
+            Err(err) => {
+                iface.remove_socket(handle);
+                return Err(err);
+            }
+        };
+
+        let mut lease = DhcpLease {
+            handle,
+            address: None,
+        };
+        lease.configure(&mut iface, Some(&config))?;
+        *self.shared.dhcp.lock().map_err(|_| NetworkError::Lock)? = Some(lease);
+        Ok(vec![IpAddr::V4(config.address.address().into())])
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        let cidr = into_stack_cidr(ip, prefix)?;
+        let mut iface = self.shared.lock()?;
+        if iface.has_ip_addr(cidr.address()) {
+            return Err(NetworkError::AlreadyExists);
+        }
+        iface.update_ip_addrs(|addrs| {
+            if let ManagedSlice::Owned(addrs) = addrs {
+                addrs.push(cidr);
+            }
+        });
+        Ok(())
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        let ip: IpAddress = ip.into();
+        let mut iface = self.shared.lock()?;
+        if !iface.has_ip_addr(ip) {
+            return Err(NetworkError::AddressNotAvailable);
+        }
+        iface.update_ip_addrs(|addrs| {
+            if let ManagedSlice::Owned(addrs) = addrs {
+                addrs.retain(|a| a.address() != ip);
+            }
+        });
+        Ok(())
+    }
+
+    fn ip_clear(&self) -> Result<()> {
+        let mut iface = self.shared.lock()?;
+        iface.update_ip_addrs(|addrs| {
+            if let ManagedSlice::Owned(addrs) = addrs {
+                addrs.clear();
+            }
+        });
+        Ok(())
+    }
+
+    fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        let iface = self.shared.lock()?;
+        Ok(iface.ip_addrs().iter().map(from_stack_cidr).collect())
+    }
+
+    fn mac(&self) -> Result<[u8; 6]> {
+        Ok(self.mac)
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        let mut iface = self.shared.lock()?;
+        let routes = iface.routes_mut();
+        match ip {
+            IpAddr::V4(ip) => routes.add_default_ipv4_route(ip.into()),
+            IpAddr::V6(ip) => routes.add_default_ipv6_route(ip.into()),
+        }
+        .map(|_| ())
+        .map_err(|_| NetworkError::InvalidInput)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        let cidr = into_stack_cidr(cidr.ip, cidr.prefix)?;
+        let route = Route {
+            via_router: via_router.into(),
+            preferred_until: preferred_until.map(into_stack_time),
+            expires_at: expires_at.map(into_stack_time),
+        };
+        let mut iface = self.shared.lock()?;
+        let mut ret = Ok(());
+        iface.routes_mut().update(|map| {
+            if map.insert(cidr, route).is_err() {
+                ret = Err(NetworkError::InvalidInput);
+            }
+        });
+        ret
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        let ip: IpAddress = cidr.into();
+        let mut iface = self.shared.lock()?;
+        iface.routes_mut().update(|map| {
+            let keys = map
+                .iter()
+                .map(|(k, _)| *k)
+                .filter(|k| k.address() == ip)
+                .collect::<Vec<_>>();
+            for key in keys {
+                map.remove(&key);
+            }
+        });
+        Ok(())
+    }
+
+    fn route_clear(&self) -> Result<()> {
+        let mut iface = self.shared.lock()?;
+        iface.routes_mut().update(|map| {
+            let keys = map.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+            for key in keys {
+                map.remove(&key);
+            }
+        });
+        Ok(())
+    }
+
+    fn route_list(&self) -> Result<Vec<IpRoute>> {
+        let mut iface = self.shared.lock()?;
+        let mut ret = Vec::new();
+        iface.routes_mut().update(|map| {
+            ret.extend(map.iter().map(|(cidr, route)| IpRoute {
+                cidr: from_stack_cidr(cidr),
+                via_router: route.via_router.into(),
+                preferred_until: route.preferred_until.map(from_stack_time),
+                expires_at: route.expires_at.map(from_stack_time),
+            }));
+        });
+        Ok(ret)
+    }
+
+    fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        let tap = Arc::new(RawTap::default());
+        let mut iface = self.shared.lock()?;
+        iface.device_mut().add_tap(&tap);
+        Ok(Box::new(UserspaceRawSocket::new(self.shared.clone(), tap)))
+    }
+
+    fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.host.listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+    }
+
+    fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.host.bind_udp(addr, reuse_port, reuse_addr)
+    }
+
+    fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let mut iface = self.shared.lock()?;
+        if !addr.is_unspecified() && !iface.has_ip_addr(addr) {
+            return Err(NetworkError::AddressNotAvailable);
+        }
+        let socket = IcmpSocket::new(
+            IcmpSocketBuffer::new(
+                vec![IcmpPacketMetadata::EMPTY; ICMP_PACKETS],
+                vec![0; ICMP_BUFFER],
+            ),
+            IcmpSocketBuffer::new(
+                vec![IcmpPacketMetadata::EMPTY; ICMP_PACKETS],
+                vec![0; ICMP_BUFFER],
+            ),
+        );
+        let handle = iface.add_socket(socket);
+        Ok(Box::new(UserspaceIcmpSocket::new(
+            self.shared.clone(),
+            handle,
+            addr,
+        )))
+    }
+
+    fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.host.connect_tcp(addr, peer, timeout)
+    }
+
+    fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.host.resolve(host, port, dns_server)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::net::Ipv4Addr;
+
+    const MAC_A: [u8; 6] = [0x02, 0, 0, 0, 0, 0x0a];
+    const MAC_B: [u8; 6] = [0x02, 0, 0, 0, 0, 0x0b];
+
+    fn echo_request(ident: u16, seq: u16) -> Vec<u8> {
+        let mut packet = vec![8, 0, 0, 0];
+        packet.extend_from_slice(&ident.to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(b"wasmer");
+        let mut sum = 0u32;
+        for chunk in packet.chunks(2) {
+            let word = (chunk[0] as u32) << 8 | *chunk.get(1).unwrap_or(&0) as u32;
+            sum += word;
+        }
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        packet[2..4].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_ip_and_route_management() {
+        let net = UserspaceNetworking::new(MAC_A);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        net.ip_add(ip, 24).unwrap();
+        assert_eq!(net.ip_add(ip, 24), Err(NetworkError::AlreadyExists));
+        assert_eq!(
+            net.ip_add(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 33),
+            Err(NetworkError::InvalidInput)
+        );
+        assert_eq!(net.ip_list().unwrap(), vec![IpCidr { ip, prefix: 24 }]);
+
+        let cidr = IpCidr {
+            ip: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)),
+            prefix: 16,
+        };
+        let router = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 254));
+        net.route_add(cidr, router, None, None).unwrap();
+        net.gateway_set(router).unwrap();
+        let routes = net.route_list().unwrap();
+        assert_eq!(routes.len(), 2);
+        assert!(routes
+            .iter()
+            .any(|r| r.cidr == cidr && r.via_router == router));
+
+        net.route_remove(cidr.ip).unwrap();
+        assert_eq!(net.route_list().unwrap().len(), 1);
+        net.route_clear().unwrap();
+        assert!(net.route_list().unwrap().is_empty());
+
+        net.ip_remove(ip).unwrap();
+        assert!(net.ip_list().unwrap().is_empty());
+        assert_eq!(net.mac().unwrap(), MAC_A);
+    }
+
+    #[test]
+    fn test_ping_over_channel() {
+        let (dev_a, dev_b) = ChannelDevice::pair();
+        let net_a = UserspaceNetworking::with_device(MAC_A, dev_a);
+        let net_b = UserspaceNetworking::with_device(MAC_B, dev_b);
+        let ip_a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let ip_b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        net_a.ip_add(ip_a, 24).unwrap();
+        net_b.ip_add(ip_b, 24).unwrap();
+
+        // The raw socket on B sees the frames that A sends to it
+        let mut raw = net_b.bind_raw().unwrap();
+
+        let mut icmp = net_a.bind_icmp(ip_a).unwrap();
+        let request = echo_request(0x1234, 1);
+        icmp.send_to(Bytes::from(request.clone()), SocketAddr::new(ip_b, 0))
+            .unwrap();
+
+        let reply = icmp.recv_from().unwrap();
+        assert_eq!(reply.addr.ip(), ip_b);
+        assert_eq!(reply.data[0], 0, "expected an echo reply");
+        assert_eq!(&reply.data[4..], &request[4..]);
+
+        let frame = raw.recv().unwrap();
+        assert!(frame.data.len() >= 14);
+    }
+
+    #[test]
+    fn test_recv_without_packets() {
+        let net = UserspaceNetworking::new(MAC_A);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        net.ip_add(ip, 24).unwrap();
+
+        let mut raw = net.bind_raw().unwrap();
+        raw.set_nonblocking(true).unwrap();
+        assert!(matches!(raw.recv(), Err(NetworkError::WouldBlock)));
+
+        let mut icmp = net.bind_icmp(ip).unwrap();
+        icmp.set_recv_timeout(Some(Duration::from_millis(20)))
+            .unwrap();
+        assert!(matches!(icmp.recv_from(), Err(NetworkError::TimedOut)));
+    }
+}
@@ -0,0 +1,284 @@
+use bytes::Bytes;
+use smoltcp::iface::{Interface, SocketHandle};
+use smoltcp::socket::{IcmpEndpoint, IcmpSocket};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[allow(unused_imports, dead_code)]
+use tracing::{debug, error, info, trace, warn};
+use wasmer_vnet::{
+    NetworkError, Result, SocketReceive, SocketReceiveFrom, SocketStatus,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualRawSocket, VirtualSocket,
+};
+
+use super::{RawTap, Shared, StackDevice};
+
+/// How long a blocked receive waits before it checks the socket again
+const RECV_POLL: Duration = Duration::from_millis(50);
+
+fn stack_err_into_net_error(err: smoltcp::Error) -> NetworkError {
+    match err {
+        smoltcp::Error::Exhausted => NetworkError::WouldBlock,
+        smoltcp::Error::Illegal => NetworkError::InvalidInput,
+        smoltcp::Error::Unaddressable => NetworkError::AddressNotAvailable,
+        smoltcp::Error::Truncated => NetworkError::InvalidData,
+        smoltcp::Error::Finished => NetworkError::ConnectionAborted,
+        _ => NetworkError::IOError,
+    }
+}
+
+/// Receives with `recv` once the stack queued something for the socket.
+///
+/// `recv` returns `None` while nothing is queued. The receive then fails
+/// with `WouldBlock` if the socket is `nonblocking`, or with `TimedOut`
+/// once `timeout` elapsed.
+fn recv_queued<T>(
+    shared: &Shared,
+    nonblocking: bool,
+    timeout: Option<Duration>,
+    mut recv: impl FnMut(&mut Interface<'static, StackDevice>) -> Result<Option<T>>,
+) -> Result<T> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut iface = shared.lock()?;
+    loop {
+        if let Some(ret) = recv(&mut *iface)? {
+            return Ok(ret);
+        }
+        if nonblocking {
+            return Err(NetworkError::WouldBlock);
+        }
+        let wait = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(NetworkError::TimedOut);
+                }
+                (deadline - now).min(RECV_POLL)
+            }
+            None => RECV_POLL,
+        };
+        iface = shared.wait(iface, wait)?;
+    }
+}
+
+/// ICMP socket that sends and receives whole ICMP messages (header and
+/// payload) through the userspace stack
+#[derive(Debug)]
+pub struct UserspaceIcmpSocket {
+    shared: Arc<Shared>,
+    handle: SocketHandle,
+    addr: IpAddr,
+    peeked: Option<SocketReceiveFrom>,
+    recv_timeout: Option<Duration>,
+    nonblocking: bool,
+}
+
+impl UserspaceIcmpSocket {
+    pub(crate) fn new(shared: Arc<Shared>, handle: SocketHandle, addr: IpAddr) -> Self {
+        Self {
+            shared,
+            handle,
+            addr,
+            peeked: None,
+            recv_timeout: None,
+            nonblocking: false,
+        }
+    }
+}
+
+impl Drop for UserspaceIcmpSocket {
+    fn drop(&mut self) {
+        if let Ok(mut iface) = self.shared.lock() {
+            iface.remove_socket(self.handle);
+        }
+    }
+}
+
+impl VirtualIcmpSocket for UserspaceIcmpSocket {}
+
+impl VirtualConnectionlessSocket for UserspaceIcmpSocket {
+    fn send_to(&mut self, data: Bytes, addr: SocketAddr) -> Result<usize> {
+        if data.len() < 8 {
+            return Err(NetworkError::InvalidInput);
+        }
+        let mut iface = self.shared.lock()?;
+        let socket = iface.get_socket::<IcmpSocket>(self.handle);
+        if !socket.is_open() {
+            // Replies are matched on the identifier of the echo request
+            // (much like the ping sockets of Linux do)
+            let ident = u16::from_be_bytes([data[4], data[5]]);
+            socket
+                .bind(IcmpEndpoint::Ident(ident))
+                .map_err(stack_err_into_net_error)?;
+        }
+        socket
+            .send_slice(&data[..], addr.ip().into())
+            .map_err(stack_err_into_net_error)?;
+        self.shared.notify();
+        Ok(data.len())
+    }
+
+    fn recv_from(&mut self) -> Result<SocketReceiveFrom> {
+        if let Some(peeked) = self.peeked.take() {
+            return Ok(peeked);
+        }
+        let handle = self.handle;
+        recv_queued(
+            &self.shared,
+            self.nonblocking,
+            self.recv_timeout,
+            |iface| match iface.get_socket::<IcmpSocket>(handle).recv() {
+                Ok((data, addr)) => Ok(Some(SocketReceiveFrom {
+                    data: Bytes::copy_from_slice(data),
+                    truncated: false,
+                    addr: SocketAddr::new(addr.into(), 0),
+                })),
+                Err(smoltcp::Error::Exhausted) => Ok(None),
+                Err(err) => Err(stack_err_into_net_error(err)),
+            },
+        )
+    }
+
+    fn peek_from(&mut self) -> Result<SocketReceiveFrom> {
+        let ret = self.recv_from()?;
+        self.peeked.replace(SocketReceiveFrom {
+            data: ret.data.clone(),
+            truncated: ret.truncated,
+            addr: ret.addr,
+        });
+        Ok(ret)
+    }
+}
+
+impl VirtualSocket for UserspaceIcmpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let mut iface = self.shared.lock()?;
+        iface
+            .get_socket::<IcmpSocket>(self.handle)
+            .set_hop_limit(Some(ttl.min(u8::MAX as u32) as u8));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        let mut iface = self.shared.lock()?;
+        Ok(iface
+            .get_socket::<IcmpSocket>(self.handle)
+            .hop_limit()
+            .unwrap_or(64) as u32)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(SocketAddr::new(self.addr, 0))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.recv_timeout = timeout;
+        Ok(())
+    }
+
+    fn recv_timeout(&self) -> Result<Option<Duration>> {
+        Ok(self.recv_timeout)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+}
+
+/// Raw socket that reads and writes Ethernet frames directly on the
+/// device of the userspace stack
+#[derive(Debug)]
+pub struct UserspaceRawSocket {
+    shared: Arc<Shared>,
+    tap: Arc<RawTap>,
+    recv_timeout: Option<Duration>,
+    nonblocking: bool,
+}
+
+impl UserspaceRawSocket {
+    pub(crate) fn new(shared: Arc<Shared>, tap: Arc<RawTap>) -> Self {
+        Self {
+            shared,
+            tap,
+            recv_timeout: None,
+            nonblocking: false,
+        }
+    }
+}
+
+impl VirtualRawSocket for UserspaceRawSocket {
+    fn send(&mut self, data: Bytes) -> Result<usize> {
+        let mut iface = self.shared.lock()?;
+        iface.device_mut().send_raw(&data[..])?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<SocketReceive> {
+        let tap = &self.tap;
+        recv_queued(&self.shared, self.nonblocking, self.recv_timeout, |_| {
+            let data = tap.queue.lock().unwrap().pop_front();
+            Ok(data.map(|data| SocketReceive {
+                data,
+                truncated: false,
+            }))
+        })
+    }
+
+    fn set_promiscuous(&mut self, promiscuous: bool) -> Result<()> {
+        self.tap.promiscuous.store(promiscuous, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn promiscuous(&self) -> Result<bool> {
+        Ok(self.tap.promiscuous.load(Ordering::Relaxed))
+    }
+}
+
+impl VirtualSocket for UserspaceRawSocket {
+    // The frames carry their own IP header, and hence their own TTL
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        let iface = self.shared.lock()?;
+        let ip = iface
+            .ip_addrs()
+            .first()
+            .map(|cidr| cidr.address().into())
+            .unwrap_or(IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED));
+        Ok(SocketAddr::new(ip, 0))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.recv_timeout = timeout;
+        Ok(())
+    }
+
+    fn recv_timeout(&self) -> Result<Option<Duration>> {
+        Ok(self.recv_timeout)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+}
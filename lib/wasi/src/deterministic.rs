@@ -0,0 +1,237 @@
+//! Deterministic execution of WASI programs.
+//!
+//! A program that runs with [`Deterministic`] settings produces the same
+//! results on every machine: the clocks only move when the host advances
+//! the [`VirtualClock`] or when the program waits on a clock with
+//! `poll_oneoff` or `thread_sleep` (the clock then moves forward by the
+//! timeout, while a poll that only waits on files blocks until one of them
+//! is ready), the timestamps set with `*_NOW` flags come from the same
+//! clock, `random_get` returns a stream derived from a fixed seed and
+//! threads can not be spawned. As floating point operations can
+//! produce NaNs with architecture specific bits, the module must also be
+//! compiled with NaN canonicalization, by an engine created with
+//! [`Deterministic::engine`].
+//!
+//! The file system and the network of the host remain visible to the
+//! program, they should be replaced with in-memory implementations when
+//! they are part of what needs to be reproduced.
+
+use crate::syscalls::types::{
+    __wasi_clockid_t, __wasi_errno_t, __wasi_timestamp_t, __WASI_CLOCK_MONOTONIC,
+    __WASI_CLOCK_PROCESS_CPUTIME_ID, __WASI_CLOCK_REALTIME, __WASI_CLOCK_THREAD_CPUTIME_ID,
+    __WASI_EINVAL,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "compiler")]
+use wasmer::{CompilerConfig, Engine, EngineBuilder};
+
+/// A clock that only moves when the host advances it.
+///
+/// All the WASI clocks (realtime, monotonic and the CPU time clocks) read
+/// the same virtual time, which starts at zero.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    /// Creates a clock that starts at `now`
+    pub fn new(now: Duration) -> Self {
+        let clock = Self::default();
+        clock.set(now);
+        clock
+    }
+
+    /// Returns the current time of the clock
+    pub fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Acquire))
+    }
+
+    /// Sets the current time of the clock
+    pub fn set(&self, now: Duration) {
+        self.nanos.store(now.as_nanos() as u64, Ordering::Release);
+    }
+
+    /// Moves the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::AcqRel);
+    }
+
+    pub(crate) fn time_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        validate_clock_id(clock_id)?;
+        Ok(self.nanos.load(Ordering::Acquire))
+    }
+
+    pub(crate) fn res_get(
+        &self,
+        clock_id: __wasi_clockid_t,
+    ) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+        validate_clock_id(clock_id)?;
+        Ok(1)
+    }
+}
+
+fn validate_clock_id(clock_id: __wasi_clockid_t) -> Result<(), __wasi_errno_t> {
+    match clock_id {
+        __WASI_CLOCK_REALTIME
+        | __WASI_CLOCK_MONOTONIC
+        | __WASI_CLOCK_PROCESS_CPUTIME_ID
+        | __WASI_CLOCK_THREAD_CPUTIME_ID => Ok(()),
+        _ => Err(__WASI_EINVAL),
+    }
+}
+
+/// Settings that make the execution of a WASI program deterministic.
+///
+/// Usage:
+/// ```no_run
+/// # use std::time::Duration;
+/// # use wasmer::{Cranelift, Store};
+/// # use wasmer_wasi::{Deterministic, WasiState};
+/// let deterministic = Deterministic::new(42);
+/// let clock = deterministic.clock().clone();
+///
+/// // The module has to be compiled by an engine that canonicalizes NaNs
+/// let store = Store::new(deterministic.engine(Cranelift::default()));
+///
+/// let mut state_builder = WasiState::new("wasi-prog-name");
+/// state_builder.deterministic(deterministic);
+///
+/// // ...and later on, between two calls into the program
+/// clock.advance(Duration::from_millis(10));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deterministic {
+    seed: u64,
+    clock: VirtualClock,
+}
+
+impl Deterministic {
+    /// Creates deterministic settings whose random numbers are derived
+    /// from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            clock: VirtualClock::default(),
+        }
+    }
+
+    /// Uses `clock` as the clock of the program
+    pub fn with_clock(mut self, clock: VirtualClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the seed of the random numbers
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the clock of the program
+    pub fn clock(&self) -> &VirtualClock {
+        &self.clock
+    }
+
+    /// Turns on the compiler settings that deterministic execution
+    /// depends on (NaN canonicalization)
+    #[cfg(feature = "compiler")]
+    pub fn configure_compiler(&self, config: &mut dyn CompilerConfig) {
+        config.canonicalize_nans(true);
+    }
+
+    /// Creates an engine that compiles modules with `compiler_config` and
+    /// the settings that deterministic execution depends on, see
+    /// [`Deterministic::configure_compiler`]
+    #[cfg(feature = "compiler")]
+    pub fn engine<C>(&self, compiler_config: C) -> Engine
+    where
+        C: Into<Box<dyn CompilerConfig>>,
+    {
+        let mut compiler_config = compiler_config.into();
+        self.configure_compiler(compiler_config.as_mut());
+        EngineBuilder::new(compiler_config).engine()
+    }
+
+    pub(crate) fn build(&self) -> DeterministicState {
+        DeterministicState {
+            clock: self.clock.clone(),
+            rng: Mutex::new(SeededRng::new(self.seed)),
+        }
+    }
+}
+
+/// Deterministic state of a running program
+#[derive(Debug)]
+pub(crate) struct DeterministicState {
+    pub(crate) clock: VirtualClock,
+    rng: Mutex<SeededRng>,
+}
+
+impl DeterministicState {
+    pub(crate) fn fill_random(&self, buf: &mut [u8]) {
+        self.rng.lock().unwrap().fill(buf);
+    }
+}
+
+/// SplitMix64 generator, whose output is fully specified and therefore
+/// identical on every platform
+#[derive(Debug)]
+struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_rng_is_stable() {
+        // Reference values of SplitMix64 for the seed 1234567
+        let mut rng = SeededRng::new(1234567);
+        assert_eq!(rng.next_u64(), 6457827717110365317);
+        assert_eq!(rng.next_u64(), 3203168211198807973);
+
+        let mut a = [0u8; 13];
+        let mut b = [0u8; 13];
+        Deterministic::new(7).build().fill_random(&mut a);
+        Deterministic::new(7).build().fill_random(&mut b);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn virtual_clock() {
+        let clock = VirtualClock::new(Duration::from_secs(1));
+        clock.advance(Duration::from_nanos(5));
+        assert_eq!(clock.time_get(__WASI_CLOCK_MONOTONIC), Ok(1_000_000_005));
+        assert_eq!(clock.time_get(__WASI_CLOCK_REALTIME), Ok(1_000_000_005));
+        assert_eq!(clock.time_get(42), Err(__WASI_EINVAL));
+        assert_eq!(clock.res_get(__WASI_CLOCK_MONOTONIC), Ok(1));
+    }
+}
@@ -0,0 +1,1093 @@
+//! Recording and replaying of the syscalls made by a WASI program.
+//!
+//! When a [`SyscallJournal`] is attached to a [`WasiStateBuilder`] every
+//! WASI import is wrapped so that, in record mode, the arguments of the
+//! syscall, its return value, the guest memory it wrote to and the file
+//! descriptors it opened or closed are appended to a trace. In replay mode
+//! the syscalls are not executed against the host at all; instead the
+//! recorded memory writes and results are fed back to the program, which
+//! reproduces the original run exactly (clock reads, `random_get`, file and
+//! socket data included). The file descriptor table is rebuilt from the
+//! trace as the replay goes, so descriptors opened while recording exist
+//! again (as placeholders) at the same numbers.
+//!
+//! The memory written by a syscall is found by comparing the parts of the
+//! linear memory it can write to (its out-pointers and the buffers of its
+//! iovecs) before and after the call. Syscalls whose outputs are not known
+//! to the journal (`args_get`, `environ_get`, `process_spawn` and the bus,
+//! HTTP, web socket and port syscalls) have the whole linear memory
+//! compared instead.
+//!
+//! Writes to stdout and stderr are still performed while replaying so that
+//! the output of the program remains visible.
+//!
+//! Programs that spawn threads are recorded in the order in which their
+//! syscalls complete and may diverge on replay.
+//!
+//! [`WasiStateBuilder`]: crate::WasiStateBuilder
+
+use crate::state::{Fd, Kind};
+use crate::syscalls::types::{
+    self, __wasi_addr_port_t, __wasi_addr_t, __wasi_event_t, __wasi_fd_t, __wasi_fdstat_t,
+    __wasi_filestat_t, __wasi_option_timestamp_t, __wasi_prestat_t, __wasi_roflags_t,
+    __wasi_sockstatus_t, __wasi_tty_t,
+};
+use crate::{WasiEnv, WasiError};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use thiserror::Error;
+use tracing::trace;
+use wasmer::{
+    AsStoreMut, Extern, Function, FunctionEnv, FunctionEnvMut, FunctionType, Imports, MemoryView,
+    RuntimeError, Type, Value,
+};
+
+/// Magic bytes at the start of every journal file
+const JOURNAL_MAGIC: &[u8; 8] = b"WASIJRNL";
+
+/// Version of the journal format
+const JOURNAL_VERSION: u32 = 2;
+
+/// Granularity at which the memory is compared to find what a syscall wrote
+const DIFF_CHUNK: usize = 256;
+
+/// Error type returned when a journal can not be read, written or replayed.
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("journal I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("journal is malformed: {0}")]
+    InvalidFormat(String),
+    #[error("replay diverged at syscall #{index}: expected `{expected}` but the program made `{actual}`")]
+    Diverged {
+        index: u64,
+        expected: String,
+        actual: String,
+    },
+    #[error("replay ran past the end of the journal")]
+    Exhausted,
+}
+
+/// Records the syscalls of a WASI program or replays a previous recording.
+///
+/// While recording, the trace is buffered and only written out when the
+/// program exits or traps, when [`SyscallJournal::flush`] is called and
+/// when the journal is dropped.
+///
+/// Usage:
+/// ```no_run
+/// # use wasmer_wasi::{JournalError, SyscallJournal, WasiState};
+/// # fn main() -> Result<(), JournalError> {
+/// let mut state_builder = WasiState::new("wasi-prog-name");
+/// state_builder.journal(SyscallJournal::record_to_file("trace.wasijrnl")?);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SyscallJournal {
+    mode: Mutex<JournalMode>,
+}
+
+enum JournalMode {
+    Record {
+        writer: BufWriter<Box<dyn Write + Send + 'static>>,
+        index: u64,
+    },
+    Replay {
+        entries: VecDeque<JournalEntry>,
+        index: u64,
+    },
+}
+
+impl std::fmt::Debug for SyscallJournal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyscallJournal")
+            .field("replaying", &self.is_replaying())
+            .finish()
+    }
+}
+
+impl Drop for SyscallJournal {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl SyscallJournal {
+    /// Creates a journal that records every syscall into `writer`
+    pub fn record<W>(writer: W) -> Result<Self, JournalError>
+    where
+        W: Write + Send + 'static,
+    {
+        let writer: Box<dyn Write + Send + 'static> = Box::new(writer);
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(&JOURNAL_MAGIC[..])?;
+        writer.write_all(&JOURNAL_VERSION.to_le_bytes())?;
+        Ok(Self {
+            mode: Mutex::new(JournalMode::Record { writer, index: 0 }),
+        })
+    }
+
+    /// Creates a journal that records every syscall into the file at `path`
+    /// (the file is truncated if it already exists)
+    pub fn record_to_file(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        Self::record(File::create(path)?)
+    }
+
+    /// Creates a journal that replays the recording read from `reader`
+    pub fn replay<R: Read>(mut reader: R) -> Result<Self, JournalError> {
+        let mut magic = [0u8; 8];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != JOURNAL_MAGIC {
+            return Err(JournalError::InvalidFormat(
+                "missing journal header".to_string(),
+            ));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != JOURNAL_VERSION {
+            return Err(JournalError::InvalidFormat(format!(
+                "unsupported journal version {}",
+                version
+            )));
+        }
+
+        let mut entries = VecDeque::new();
+        while let Some(entry) = JournalEntry::read(&mut reader)? {
+            entries.push_back(entry);
+        }
+        Ok(Self {
+            mode: Mutex::new(JournalMode::Replay { entries, index: 0 }),
+        })
+    }
+
+    /// Creates a journal that replays the recording stored in the file at `path`
+    pub fn replay_from_file(path: impl AsRef<Path>) -> Result<Self, JournalError> {
+        let file = File::open(path)?;
+        Self::replay(BufReader::new(file))
+    }
+
+    /// Returns true if this journal replays a previous recording
+    pub fn is_replaying(&self) -> bool {
+        matches!(*self.mode.lock().unwrap(), JournalMode::Replay { .. })
+    }
+
+    /// Writes out the syscalls recorded so far, for instance to checkpoint a
+    /// long running program (this does nothing while replaying)
+    pub fn flush(&self) -> Result<(), JournalError> {
+        if let JournalMode::Record { writer, .. } = &mut *self.mode.lock().unwrap() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Executes (or replays) a single syscall of the program
+    fn syscall(
+        &self,
+        ctx: &mut FunctionEnvMut<WasiEnv>,
+        name: &str,
+        func: &Function,
+        params: &[Value],
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let args = params.iter().map(JournalValue::from).collect::<Vec<_>>();
+        if self.is_replaying() {
+            self.replay_syscall(ctx, name, func, args, params)
+        } else {
+            self.record_syscall(ctx, name, func, args, params)
+        }
+    }
+
+    fn record_syscall(
+        &self,
+        ctx: &mut FunctionEnvMut<WasiEnv>,
+        name: &str,
+        func: &Function,
+        args: Vec<JournalValue>,
+        params: &[Value],
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let memory = ctx.data().memory_clone();
+        let ranges = memory
+            .as_ref()
+            .and_then(|memory| output_ranges(&memory.view(&*ctx), name, &args));
+        let memory_before = memory
+            .as_ref()
+            .map(|memory| snapshot_memory(ctx, memory, ranges.as_deref()))
+            .unwrap_or_default();
+        let fds_before = snapshot_fds(ctx);
+
+        // The journal is not locked while the syscall runs as it might block
+        // (for instance while joining a thread that makes syscalls itself)
+        let (outcome, ret) = outcome_of(func.call(ctx, params));
+        let writes = match memory.as_ref() {
+            Some(memory) => {
+                let memory_after = snapshot_memory(ctx, memory, ranges.as_deref());
+                memory_before
+                    .iter()
+                    .zip(memory_after.iter())
+                    .flat_map(|((base, before), (_, after))| {
+                        diff_memory(before, after)
+                            .into_iter()
+                            .map(move |(offset, data)| (base + offset, data))
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let (opened, closed) = diff_fds(&fds_before, &snapshot_fds(ctx));
+        let checkpoint = !matches!(outcome, JournalOutcome::Returned(_));
+        let entry = JournalEntry {
+            name: name.to_string(),
+            args,
+            outcome,
+            writes,
+            opened,
+            closed,
+        };
+
+        let mut guard = self.mode.lock().unwrap();
+        if let JournalMode::Record { writer, index } = &mut *guard {
+            trace!("wasi::journal::record #{} {}", index, entry.describe());
+            entry
+                .write(writer)
+                .and_then(|_| match checkpoint {
+                    // The program is about to stop, make sure the trace
+                    // is complete even if the journal is never dropped
+                    true => writer.flush(),
+                    false => Ok(()),
+                })
+                .map_err(|err| RuntimeError::user(Box::new(JournalError::Io(err))))?;
+            *index += 1;
+        }
+        ret
+    }
+
+    fn replay_syscall(
+        &self,
+        ctx: &mut FunctionEnvMut<WasiEnv>,
+        name: &str,
+        func: &Function,
+        args: Vec<JournalValue>,
+        params: &[Value],
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let (entry, index) = {
+            let mut guard = self.mode.lock().unwrap();
+            match &mut *guard {
+                JournalMode::Replay { entries, index } => {
+                    let entry = entries
+                        .pop_front()
+                        .ok_or_else(|| RuntimeError::user(Box::new(JournalError::Exhausted)))?;
+                    *index += 1;
+                    (entry, *index - 1)
+                }
+                JournalMode::Record { .. } => unreachable!(),
+            }
+        };
+        trace!("wasi::journal::replay #{} {}", index, entry.describe());
+
+        let diverged = |actual: String| {
+            RuntimeError::user(Box::new(JournalError::Diverged {
+                index,
+                expected: entry.describe(),
+                actual,
+            }))
+        };
+        if entry.name != name || entry.args != args {
+            let actual = JournalEntry {
+                name: name.to_string(),
+                args,
+                outcome: JournalOutcome::Returned(Vec::new()),
+                writes: Vec::new(),
+                opened: Vec::new(),
+                closed: Vec::new(),
+            };
+            return Err(diverged(actual.describe()));
+        }
+
+        if echoes_output(ctx, name, &args) {
+            let _ = func.call(ctx, params);
+        }
+        if !entry.writes.is_empty() {
+            let memory = match ctx.data().memory_clone() {
+                Some(memory) => memory,
+                None => return Err(diverged("a syscall without memory".to_string())),
+            };
+            let view = memory.view(&*ctx);
+            for (offset, data) in entry.writes.iter() {
+                view.write(*offset, &data[..])
+                    .map_err(|_| diverged(format!("a write outside of memory at {}", offset)))?;
+            }
+        }
+        replay_fds(ctx, &entry.opened, &entry.closed);
+
+        match entry.outcome {
+            JournalOutcome::Returned(rets) => Ok(rets.into_iter().map(Value::from).collect()),
+            JournalOutcome::Exit(code) => Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+            JournalOutcome::Trap(message) => Err(RuntimeError::new(message)),
+        }
+    }
+}
+
+/// Converts the result of a syscall into its journal representation
+fn outcome_of(
+    ret: Result<Box<[Value]>, RuntimeError>,
+) -> (JournalOutcome, Result<Vec<Value>, RuntimeError>) {
+    match ret {
+        Ok(rets) => (
+            JournalOutcome::Returned(rets.iter().map(JournalValue::from).collect()),
+            Ok(rets.into_vec()),
+        ),
+        Err(err) => match err.downcast::<WasiError>() {
+            Ok(WasiError::Exit(code)) => (
+                JournalOutcome::Exit(code),
+                Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+            ),
+            Ok(err) => (
+                JournalOutcome::Trap(err.to_string()),
+                Err(RuntimeError::user(Box::new(err))),
+            ),
+            Err(err) => (JournalOutcome::Trap(err.message()), Err(err)),
+        },
+    }
+}
+
+/// Wraps all the functions of `imports` so that they go through the journal
+/// of the environment (if it has one)
+pub(crate) fn instrument_imports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    imports: Imports,
+) -> Imports {
+    let journal = match env.as_ref(store).journal.clone() {
+        Some(journal) => journal,
+        None => return imports,
+    };
+
+    let mut ret = Imports::new();
+    for ((namespace, name), ext) in &imports {
+        let ext = match ext {
+            Extern::Function(func) if is_journalable(&func.ty(store)) => {
+                let journal = journal.clone();
+                let ty = func.ty(store);
+                let call_name = name.clone();
+                Extern::Function(Function::new_with_env(
+                    store,
+                    env,
+                    ty,
+                    move |mut ctx, params| journal.syscall(&mut ctx, &call_name, &func, params),
+                ))
+            }
+            ext => ext,
+        };
+        ret.define(&namespace, &name, ext);
+    }
+    ret
+}
+
+/// Only functions that exclusively deal with numbers can be journaled
+fn is_journalable(ty: &FunctionType) -> bool {
+    ty.params()
+        .iter()
+        .chain(ty.results().iter())
+        .all(|ty| matches!(ty, Type::I32 | Type::I64 | Type::F32 | Type::F64))
+}
+
+/// Writes to stdout and stderr (or to a descriptor duplicated from them)
+/// are also performed on replay
+fn echoes_output(ctx: &FunctionEnvMut<WasiEnv>, name: &str, args: &[JournalValue]) -> bool {
+    if !matches!(name, "fd_write" | "fd_pwrite") {
+        return false;
+    }
+    let fd = match args.first() {
+        Some(JournalValue::I32(fd)) => *fd as u32,
+        _ => return false,
+    };
+    let fd_map = ctx.data().state.fs.fd_map.read().unwrap();
+    let inode = match fd_map.get(&fd) {
+        Some(fd) => fd.inode,
+        None => return false,
+    };
+    [types::__WASI_STDOUT_FILENO, types::__WASI_STDERR_FILENO]
+        .iter()
+        .any(|stdio| fd_map.get(stdio).map(|fd| fd.inode) == Some(inode))
+}
+
+/// Part of the memory that a syscall can write to, in terms of the indices
+/// of its arguments
+#[derive(Debug, Clone, Copy)]
+enum Output {
+    /// A value of the given size at the pointer in an argument
+    Fixed(usize, u64),
+    /// A buffer at the pointer in an argument, whose length is in another one
+    Buffer(usize, usize),
+    /// A buffer at the pointer in an argument, whose length is stored in
+    /// memory at the pointer in another one
+    BufferAt(usize, usize),
+    /// Values of the given size at the pointer in an argument, whose count
+    /// is in another one
+    Array(usize, usize, u64),
+    /// The buffers of the iovecs at the pointer in an argument, whose count
+    /// is in another one
+    Iovecs(usize, usize),
+}
+
+/// Returns the parts of memory that a syscall can write to, or `None` if
+/// they are not known
+fn syscall_outputs(name: &str) -> Option<&'static [Output]> {
+    use Output::*;
+    // Sizes are those of the 64-bit syscalls, larger ones are harmless as
+    // only the bytes that changed are recorded
+    const SIZE: u64 = size_of::<u64>() as u64;
+    const FD: u64 = size_of::<__wasi_fd_t>() as u64;
+    const FDSTAT: u64 = size_of::<__wasi_fdstat_t>() as u64;
+    const FILESTAT: u64 = size_of::<__wasi_filestat_t>() as u64;
+    const PRESTAT: u64 = size_of::<__wasi_prestat_t>() as u64;
+    const EVENT: u64 = size_of::<__wasi_event_t>() as u64;
+    const TTY: u64 = size_of::<__wasi_tty_t>() as u64;
+    const ADDR: u64 = size_of::<__wasi_addr_t>() as u64;
+    const ADDR_PORT: u64 = size_of::<__wasi_addr_port_t>() as u64;
+    const SOCK_STATUS: u64 = size_of::<__wasi_sockstatus_t>() as u64;
+    const TIMESTAMP: u64 = size_of::<__wasi_option_timestamp_t>() as u64;
+    const RO_FLAGS: u64 = size_of::<__wasi_roflags_t>() as u64;
+    Some(match name {
+        "args_sizes_get" | "environ_sizes_get" => &[Fixed(0, SIZE), Fixed(1, SIZE)],
+        "clock_res_get" => &[Fixed(1, SIZE)],
+        "clock_time_get" => &[Fixed(2, SIZE)],
+        "fd_advise" | "fd_allocate" | "fd_close" | "fd_datasync" | "fd_sync" => &[],
+        "fd_fdstat_set_flags" | "fd_fdstat_set_rights" => &[],
+        "fd_filestat_set_size" | "fd_filestat_set_times" | "fd_renumber" => &[],
+        "fd_fdstat_get" => &[Fixed(1, FDSTAT)],
+        "fd_filestat_get" => &[Fixed(1, FILESTAT)],
+        "fd_prestat_get" => &[Fixed(1, PRESTAT)],
+        "fd_prestat_dir_name" => &[Buffer(1, 2)],
+        "fd_pread" => &[Iovecs(1, 2), Fixed(4, SIZE)],
+        "fd_read" => &[Iovecs(1, 2), Fixed(3, SIZE)],
+        "fd_pwrite" => &[Fixed(4, SIZE)],
+        "fd_write" => &[Fixed(3, SIZE)],
+        "fd_readdir" => &[Buffer(1, 2), Fixed(4, SIZE)],
+        "fd_dup" => &[Fixed(1, FD)],
+        "fd_event" => &[Fixed(2, FD)],
+        "fd_seek" => &[Fixed(3, SIZE)],
+        "fd_tell" => &[Fixed(1, SIZE)],
+        "fd_pipe" => &[Fixed(0, FD), Fixed(1, FD)],
+        "path_create_directory" | "path_remove_directory" | "path_unlink_file" => &[],
+        "path_filestat_set_times" | "path_link" | "path_rename" | "path_symlink" => &[],
+        "path_filestat_get" => &[Fixed(4, FILESTAT)],
+        "path_open" => &[Fixed(8, FD)],
+        "path_readlink" => &[Buffer(3, 4), Fixed(5, SIZE)],
+        "poll_oneoff" => &[Array(1, 2, EVENT), Fixed(3, SIZE)],
+        "proc_exit" | "proc_raise" | "sched_yield" | "thread_exit" => &[],
+        "random_get" => &[Buffer(0, 1)],
+        "tty_get" => &[Fixed(0, TTY)],
+        "tty_set" | "chdir" => &[],
+        "getcwd" => &[BufferAt(0, 1), Fixed(1, SIZE)],
+        "getpid" | "thread_id" => &[Fixed(0, SIZE)],
+        "thread_spawn" => &[Fixed(4, SIZE)],
+        "thread_sleep" | "thread_join" => &[],
+        "thread_parallelism" => &[Fixed(0, SIZE)],
+        "sock_open" => &[Fixed(3, FD)],
+        "sock_status" => &[Fixed(1, SOCK_STATUS)],
+        "sock_addr_local" | "sock_addr_peer" => &[Fixed(1, ADDR_PORT)],
+        "sock_bind" | "sock_connect" | "sock_listen" | "sock_shutdown" => &[],
+        "sock_set_opt_flag" | "sock_set_opt_time" | "sock_set_opt_size" => &[],
+        "sock_join_multicast_v4" | "sock_leave_multicast_v4" => &[],
+        "sock_join_multicast_v6" | "sock_leave_multicast_v6" => &[],
+        "sock_get_opt_flag" | "sock_get_opt_size" => &[Fixed(2, SIZE)],
+        "sock_get_opt_time" => &[Fixed(2, TIMESTAMP)],
+        "sock_accept" => &[Fixed(2, FD), Fixed(3, ADDR_PORT)],
+        "sock_recv" => &[Iovecs(1, 2), Fixed(4, SIZE), Fixed(5, RO_FLAGS)],
+        "sock_recv_from" => &[
+            Iovecs(1, 2),
+            Fixed(4, SIZE),
+            Fixed(5, RO_FLAGS),
+            Fixed(6, ADDR_PORT),
+        ],
+        "sock_send" => &[Fixed(4, SIZE)],
+        "sock_send_to" => &[Fixed(5, SIZE)],
+        "sock_send_file" => &[Fixed(4, SIZE)],
+        "resolve" => &[Array(3, 4, ADDR), Fixed(5, SIZE)],
+        _ => return None,
+    })
+}
+
+/// Returns the ranges of memory that a syscall can write to given its
+/// arguments, or `None` if the whole memory has to be compared
+fn output_ranges(view: &MemoryView, name: &str, args: &[JournalValue]) -> Option<Vec<Range<u64>>> {
+    let outputs = syscall_outputs(name)?;
+    let arg = |idx: usize| args.get(idx).map(JournalValue::as_u64).unwrap_or(0);
+    // Pointers are 64-bit in the syscalls of the 64-bit memories
+    let wide = |idx: usize| matches!(args.get(idx), Some(JournalValue::I64(_)));
+    let read_offset = |offset: u64, is_wide: bool| {
+        let mut buf = [0u8; 8];
+        let len = if is_wide { 8 } else { 4 };
+        view.read(offset, &mut buf[..len])
+            .ok()
+            .map(|_| u64::from_le_bytes(buf))
+    };
+    let range = |start: u64, len: u64| start..start.saturating_add(len);
+
+    let mut ranges = Vec::new();
+    for output in outputs.iter() {
+        match *output {
+            Output::Fixed(ptr, size) => ranges.push(range(arg(ptr), size)),
+            Output::Buffer(ptr, len) => ranges.push(range(arg(ptr), arg(len))),
+            Output::BufferAt(ptr, len) => {
+                if let Some(len) = read_offset(arg(len), wide(len)) {
+                    ranges.push(range(arg(ptr), len));
+                }
+            }
+            Output::Array(ptr, count, size) => {
+                ranges.push(range(arg(ptr), arg(count).saturating_mul(size)))
+            }
+            Output::Iovecs(ptr, count) => {
+                let (is_wide, iovs) = (wide(ptr), arg(ptr));
+                let iov_size = if is_wide { 16 } else { 8 };
+                for n in 0..arg(count) {
+                    let iov = iovs.saturating_add(n.saturating_mul(iov_size));
+                    match (
+                        read_offset(iov, is_wide),
+                        read_offset(iov.saturating_add(iov_size / 2), is_wide),
+                    ) {
+                        (Some(buf), Some(buf_len)) => ranges.push(range(buf, buf_len)),
+                        // The syscall fails on the same iovec
+                        _ => break,
+                    }
+                }
+            }
+        }
+    }
+    Some(ranges)
+}
+
+/// Copies the given ranges of the linear memory of the program, or all of
+/// it if there are none (the parts past the end of memory are left out)
+fn snapshot_memory(
+    ctx: &FunctionEnvMut<WasiEnv>,
+    memory: &wasmer::Memory,
+    ranges: Option<&[Range<u64>]>,
+) -> Vec<(u64, Vec<u8>)> {
+    let view = memory.view(ctx);
+    let size = view.data_size();
+    let whole = [0..size];
+    ranges
+        .unwrap_or(&whole[..])
+        .iter()
+        .map(|range| {
+            let end = range.end.min(size).max(range.start);
+            let mut data = vec![0u8; (end - range.start) as usize];
+            let _ = view.read(range.start, &mut data[..]);
+            (range.start, data)
+        })
+        .collect()
+}
+
+/// Returns the blocks of memory that differ between two snapshots (memory
+/// that was grown in between is compared against zeroes)
+fn diff_memory(before: &[u8], after: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    let mut writes: Vec<(u64, Vec<u8>)> = Vec::new();
+    for (n, chunk) in after.chunks(DIFF_CHUNK).enumerate() {
+        let base = n * DIFF_CHUNK;
+        let prev = before.get(base..).unwrap_or(&[]);
+        let old = |idx: usize| prev.get(idx).copied().unwrap_or(0);
+        let first = match (0..chunk.len()).find(|&idx| chunk[idx] != old(idx)) {
+            Some(first) => first,
+            None => continue,
+        };
+        let last = (first..chunk.len())
+            .rev()
+            .find(|&idx| chunk[idx] != old(idx))
+            .unwrap_or(first);
+
+        let offset = (base + first) as u64;
+        match writes.last_mut() {
+            // Merge with the previous block if the two touch each other
+            Some((prev, data)) if *prev + data.len() as u64 == offset => {
+                data.extend_from_slice(&chunk[first..=last])
+            }
+            _ => writes.push((offset, chunk[first..=last].to_vec())),
+        }
+    }
+    writes
+}
+
+/// Copies the file descriptor table of the program
+fn snapshot_fds(ctx: &FunctionEnvMut<WasiEnv>) -> HashMap<u32, Fd> {
+    ctx.data().state.fs.fd_map.read().unwrap().clone()
+}
+
+/// Returns the descriptors that were opened and those that were closed
+/// between two snapshots of the descriptor table
+fn diff_fds(before: &HashMap<u32, Fd>, after: &HashMap<u32, Fd>) -> (Vec<JournalFd>, Vec<u32>) {
+    let changed = |fd: &u32, entry: &Fd, other: &HashMap<u32, Fd>| {
+        other.get(fd).map(|other| other.inode) != Some(entry.inode)
+    };
+    let mut opened = after
+        .iter()
+        .filter(|(fd, entry)| changed(fd, entry, before))
+        .map(|(fd, entry)| JournalFd {
+            fd: *fd,
+            rights: entry.rights,
+            rights_inheriting: entry.rights_inheriting,
+            flags: entry.flags,
+            open_flags: entry.open_flags,
+            alias: before
+                .iter()
+                .filter(|(_, other)| other.inode == entry.inode)
+                .map(|(fd, _)| *fd)
+                .min(),
+        })
+        .collect::<Vec<_>>();
+    opened.sort_by_key(|opened| opened.fd);
+    let mut closed = before
+        .iter()
+        .filter(|(fd, entry)| changed(fd, entry, after))
+        .map(|(fd, _)| *fd)
+        .collect::<Vec<_>>();
+    closed.sort_unstable();
+    (opened, closed)
+}
+
+/// Applies the descriptor changes of a replayed syscall to the descriptor
+/// table of the program
+fn replay_fds(ctx: &FunctionEnvMut<WasiEnv>, opened: &[JournalFd], closed: &[u32]) {
+    if opened.is_empty() && closed.is_empty() {
+        return;
+    }
+    let state = &ctx.data().state;
+    let mut inodes = state.inodes.write().unwrap();
+    let mut fd_map = state.fs.fd_map.write().unwrap();
+
+    // Aliases refer to the table as it was before the syscall
+    let aliases = opened
+        .iter()
+        .map(|opened| {
+            opened
+                .alias
+                .and_then(|alias| fd_map.get(&alias))
+                .map(|fd| fd.inode)
+        })
+        .collect::<Vec<_>>();
+    for fd in closed {
+        fd_map.remove(fd);
+    }
+    for (opened, alias) in opened.iter().zip(aliases.into_iter()) {
+        // Nothing is ever done with a descriptor that was opened during the
+        // recording, it only needs to exist
+        let inode = alias.unwrap_or_else(|| {
+            state.fs.create_inode_with_default_stat(
+                &mut inodes,
+                Kind::Buffer { buffer: Vec::new() },
+                false,
+                "journal".to_string(),
+            )
+        });
+        fd_map.insert(
+            opened.fd,
+            Fd {
+                rights: opened.rights,
+                rights_inheriting: opened.rights_inheriting,
+                flags: opened.flags,
+                offset: 0,
+                open_flags: opened.open_flags,
+                inode,
+            },
+        );
+        state
+            .fs
+            .next_fd
+            .fetch_max(opened.fd.saturating_add(1), Ordering::AcqRel);
+    }
+}
+
+/// A numeric value passed to or returned from a syscall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JournalValue {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+}
+
+impl JournalValue {
+    fn as_u64(&self) -> u64 {
+        match *self {
+            Self::I32(v) => v as u32 as u64,
+            Self::I64(v) => v as u64,
+            Self::F32(v) => v as u64,
+            Self::F64(v) => v,
+        }
+    }
+}
+
+impl From<&Value> for JournalValue {
+    fn from(value: &Value) -> Self {
+        match *value {
+            Value::I32(v) => Self::I32(v),
+            Value::I64(v) => Self::I64(v),
+            Value::F32(v) => Self::F32(v.to_bits()),
+            Value::F64(v) => Self::F64(v.to_bits()),
+            // Only numeric functions are journaled (see `is_journalable`)
+            _ => unreachable!("non-numeric value in a journaled syscall"),
+        }
+    }
+}
+
+impl From<JournalValue> for Value {
+    fn from(value: JournalValue) -> Self {
+        match value {
+            JournalValue::I32(v) => Self::I32(v),
+            JournalValue::I64(v) => Self::I64(v),
+            JournalValue::F32(v) => Self::F32(f32::from_bits(v)),
+            JournalValue::F64(v) => Self::F64(f64::from_bits(v)),
+        }
+    }
+}
+
+/// How a syscall finished
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum JournalOutcome {
+    Returned(Vec<JournalValue>),
+    Exit(types::__wasi_exitcode_t),
+    Trap(String),
+}
+
+/// A file descriptor that a syscall opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JournalFd {
+    fd: types::__wasi_fd_t,
+    rights: types::__wasi_rights_t,
+    rights_inheriting: types::__wasi_rights_t,
+    flags: types::__wasi_fdflags_t,
+    open_flags: u16,
+    /// A descriptor that existed before the syscall and refers to the same
+    /// file (for instance the original of a `fd_dup`)
+    alias: Option<types::__wasi_fd_t>,
+}
+
+impl JournalFd {
+    fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.fd.to_le_bytes())?;
+        writer.write_all(&self.rights.to_le_bytes())?;
+        writer.write_all(&self.rights_inheriting.to_le_bytes())?;
+        writer.write_all(&self.flags.to_le_bytes())?;
+        writer.write_all(&self.open_flags.to_le_bytes())?;
+        match self.alias {
+            Some(alias) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&alias.to_le_bytes())
+            }
+            None => writer.write_all(&[0]),
+        }
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Self, JournalError> {
+        Ok(Self {
+            fd: read_u32(reader)?,
+            rights: read_u64(reader)?,
+            rights_inheriting: read_u64(reader)?,
+            flags: read_u16(reader)?,
+            open_flags: read_u16(reader)?,
+            alias: match read_u8(reader)? {
+                0 => None,
+                _ => Some(read_u32(reader)?),
+            },
+        })
+    }
+}
+
+/// A single syscall in the journal
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JournalEntry {
+    name: String,
+    args: Vec<JournalValue>,
+    outcome: JournalOutcome,
+    writes: Vec<(u64, Vec<u8>)>,
+    opened: Vec<JournalFd>,
+    closed: Vec<types::__wasi_fd_t>,
+}
+
+impl JournalEntry {
+    fn describe(&self) -> String {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.as_u64().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let outcome = match &self.outcome {
+            JournalOutcome::Returned(rets) => format!(
+                "{:?}",
+                rets.iter().map(JournalValue::as_u64).collect::<Vec<_>>()
+            ),
+            JournalOutcome::Exit(code) => format!("exit({})", code),
+            JournalOutcome::Trap(message) => format!("trap({})", message),
+        };
+        format!("{}({}) -> {}", self.name, args, outcome)
+    }
+
+    fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write_bytes(writer, self.name.as_bytes())?;
+        write_values(writer, &self.args)?;
+        match &self.outcome {
+            JournalOutcome::Returned(rets) => {
+                writer.write_all(&[0])?;
+                write_values(writer, rets)?;
+            }
+            JournalOutcome::Exit(code) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&code.to_le_bytes())?;
+            }
+            JournalOutcome::Trap(message) => {
+                writer.write_all(&[2])?;
+                write_bytes(writer, message.as_bytes())?;
+            }
+        }
+        writer.write_all(&(self.writes.len() as u32).to_le_bytes())?;
+        for (offset, data) in self.writes.iter() {
+            writer.write_all(&offset.to_le_bytes())?;
+            write_bytes(writer, &data[..])?;
+        }
+        writer.write_all(&(self.opened.len() as u32).to_le_bytes())?;
+        for opened in self.opened.iter() {
+            opened.write(writer)?;
+        }
+        writer.write_all(&(self.closed.len() as u32).to_le_bytes())?;
+        for fd in self.closed.iter() {
+            writer.write_all(&fd.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next entry, or `None` at the end of the journal
+    fn read(reader: &mut impl Read) -> Result<Option<Self>, JournalError> {
+        let mut len = [0u8; 4];
+        match reader.read(&mut len[..1])? {
+            0 => return Ok(None),
+            _ => read_exact(reader, &mut len[1..])?,
+        }
+        let name = read_string(reader, u32::from_le_bytes(len))?;
+        let args = read_values(reader)?;
+        let outcome = match read_u8(reader)? {
+            0 => JournalOutcome::Returned(read_values(reader)?),
+            1 => JournalOutcome::Exit(read_u32(reader)?),
+            2 => {
+                let len = read_u32(reader)?;
+                JournalOutcome::Trap(read_string(reader, len)?)
+            }
+            tag => {
+                return Err(JournalError::InvalidFormat(format!(
+                    "unknown outcome tag {}",
+                    tag
+                )))
+            }
+        };
+        let count = read_u32(reader)?;
+        let mut writes = Vec::new();
+        for _ in 0..count {
+            let offset = read_u64(reader)?;
+            let len = read_u32(reader)?;
+            writes.push((offset, read_vec(reader, len)?));
+        }
+        let count = read_u32(reader)?;
+        let mut opened = Vec::new();
+        for _ in 0..count {
+            opened.push(JournalFd::read(reader)?);
+        }
+        let count = read_u32(reader)?;
+        let mut closed = Vec::new();
+        for _ in 0..count {
+            closed.push(read_u32(reader)?);
+        }
+        Ok(Some(Self {
+            name,
+            args,
+            outcome,
+            writes,
+            opened,
+            closed,
+        }))
+    }
+}
+
+fn write_bytes(writer: &mut dyn Write, data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(data)
+}
+
+fn write_values(writer: &mut dyn Write, values: &[JournalValue]) -> io::Result<()> {
+    writer.write_all(&[values.len() as u8])?;
+    for value in values {
+        let tag = match value {
+            JournalValue::I32(_) => 0u8,
+            JournalValue::I64(_) => 1,
+            JournalValue::F32(_) => 2,
+            JournalValue::F64(_) => 3,
+        };
+        writer.write_all(&[tag])?;
+        writer.write_all(&value.as_u64().to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), JournalError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            JournalError::InvalidFormat("journal is truncated".to_string())
+        }
+        _ => JournalError::Io(err),
+    })
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, JournalError> {
+    let mut buf = [0u8; 1];
+    read_exact(reader, &mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> Result<u16, JournalError> {
+    let mut buf = [0u8; 2];
+    read_exact(reader, &mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, JournalError> {
+    let mut buf = [0u8; 4];
+    read_exact(reader, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, JournalError> {
+    let mut buf = [0u8; 8];
+    read_exact(reader, &mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_vec(reader: &mut impl Read, len: u32) -> Result<Vec<u8>, JournalError> {
+    let mut buf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len as usize {
+        return Err(JournalError::InvalidFormat(
+            "journal is truncated".to_string(),
+        ));
+    }
+    Ok(buf)
+}
+
+fn read_string(reader: &mut impl Read, len: u32) -> Result<String, JournalError> {
+    String::from_utf8(read_vec(reader, len)?)
+        .map_err(|_| JournalError::InvalidFormat("invalid syscall name".to_string()))
+}
+
+fn read_values(reader: &mut impl Read) -> Result<Vec<JournalValue>, JournalError> {
+    let count = read_u8(reader)?;
+    let mut values = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let tag = read_u8(reader)?;
+        let bits = read_u64(reader)?;
+        values.push(match tag {
+            0 => JournalValue::I32(bits as u32 as i32),
+            1 => JournalValue::I64(bits as i64),
+            2 => JournalValue::F32(bits as u32),
+            3 => JournalValue::F64(bits),
+            tag => {
+                return Err(JournalError::InvalidFormat(format!(
+                    "unknown value tag {}",
+                    tag
+                )))
+            }
+        });
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_roundtrip() {
+        let entries = vec![
+            JournalEntry {
+                name: "clock_time_get".to_string(),
+                args: vec![
+                    JournalValue::I32(1),
+                    JournalValue::I64(-1),
+                    JournalValue::I32(64),
+                ],
+                outcome: JournalOutcome::Returned(vec![JournalValue::I32(0)]),
+                writes: vec![(64, 1234u64.to_le_bytes().to_vec())],
+                opened: Vec::new(),
+                closed: Vec::new(),
+            },
+            JournalEntry {
+                name: "fd_renumber".to_string(),
+                args: vec![JournalValue::I32(1), JournalValue::I32(7)],
+                outcome: JournalOutcome::Returned(vec![JournalValue::I32(0)]),
+                writes: Vec::new(),
+                opened: vec![JournalFd {
+                    fd: 7,
+                    rights: u64::MAX,
+                    rights_inheriting: 3,
+                    flags: 1,
+                    open_flags: 2,
+                    alias: Some(1),
+                }],
+                closed: vec![1],
+            },
+            JournalEntry {
+                name: "proc_exit".to_string(),
+                args: vec![JournalValue::I32(3)],
+                outcome: JournalOutcome::Exit(3),
+                writes: Vec::new(),
+                opened: Vec::new(),
+                closed: Vec::new(),
+            },
+        ];
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&JOURNAL_MAGIC[..]);
+        data.extend_from_slice(&JOURNAL_VERSION.to_le_bytes());
+        for entry in entries.iter() {
+            entry.write(&mut data).unwrap();
+        }
+
+        let journal = SyscallJournal::replay(&data[..]).unwrap();
+        assert!(journal.is_replaying());
+        match journal.mode.into_inner().unwrap() {
+            JournalMode::Replay { entries: read, .. } => {
+                assert_eq!(read.into_iter().collect::<Vec<_>>(), entries)
+            }
+            JournalMode::Record { .. } => unreachable!(),
+        }
+
+        data.truncate(data.len() - 3);
+        assert!(matches!(
+            SyscallJournal::replay(&data[..]),
+            Err(JournalError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn memory_diff() {
+        let before = vec![0u8; 1024];
+        let mut after = before.clone();
+        after[10] = 1;
+        after[255] = 3;
+        after[256] = 4;
+        after[700] = 5;
+        after.extend_from_slice(&[0, 0, 6]);
+        assert_eq!(
+            diff_memory(&before, &after),
+            vec![
+                // Blocks that touch across chunks are merged
+                (10, after[10..257].to_vec()),
+                (700, vec![5]),
+                (1026, vec![6]),
+            ]
+        );
+        assert!(diff_memory(&after, &after).is_empty());
+    }
+}
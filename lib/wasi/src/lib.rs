@@ -35,17 +35,29 @@ compile_error!(
 
 #[macro_use]
 mod macros;
+mod deterministic;
+mod journal;
+mod profile;
 mod runtime;
 mod state;
+mod strace;
 mod syscalls;
 mod utils;
 
 use crate::syscalls::*;
 
+pub use crate::deterministic::{Deterministic, VirtualClock};
+pub use crate::journal::{JournalError, SyscallJournal};
+#[cfg(feature = "sandbox-profile")]
+pub use crate::profile::ProfileError;
+pub use crate::profile::{
+    AddressRule, NetworkPolicy, PreopenProfile, ResourceLimits, SandboxProfile, SyscallPolicy,
+};
 pub use crate::state::{
     Fd, Pipe, Stderr, Stdin, Stdout, WasiFs, WasiInodes, WasiState, WasiStateBuilder,
     WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
 };
+pub use crate::strace::{StraceFormat, SyscallFamily, SyscallTracer};
 pub use crate::syscalls::types;
 #[cfg(feature = "wasix")]
 pub use crate::utils::is_wasix_module;
@@ -57,6 +69,8 @@ pub use wasmer_vfs::FsError as WasiFsError;
 pub use wasmer_vfs::VirtualFile as WasiFile;
 pub use wasmer_vfs::{FsError, VirtualFile};
 pub use wasmer_vnet::{UnsupportedVirtualNetworking, VirtualNetworking};
+#[cfg(feature = "userspace-vnet")]
+pub use wasmer_wasi_local_networking::userspace::UserspaceNetworking;
 use wasmer_wasi_types::__WASI_CLOCK_MONOTONIC;
 
 use derivative::*;
@@ -195,6 +209,23 @@ impl WasiFunctionEnv {
         Ok(())
     }
 
+    /// Initializes the WasiEnv for a program loaded with a dynamic
+    /// linker, using the memory shared by its modules.
+    #[cfg(all(feature = "sys", feature = "compiler"))]
+    pub fn initialize_with_linker(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        linker: &wasmer::linker::Linker,
+    ) -> Result<(), ExportError> {
+        let memory = linker
+            .memory()
+            .ok_or_else(|| ExportError::Missing("memory".to_string()))?;
+        let env = self.data_mut(store);
+        env.set_memory(memory);
+
+        Ok(())
+    }
+
     /// Like `import_object` but containing all the WASI versions detected in
     /// the module.
     pub fn import_object_for_all_wasi_versions(
@@ -251,6 +282,18 @@ pub struct WasiEnv {
     pub state: Arc<WasiState>,
     /// Implementation of the WASI runtime.
     pub(crate) runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    /// Journal that records or replays the syscalls of this environment
+    #[derivative(Debug = "ignore")]
+    pub(crate) journal: Option<Arc<SyscallJournal>>,
+    /// Clock and random numbers of a deterministic execution
+    pub(crate) deterministic: Option<Arc<deterministic::DeterministicState>>,
+    /// Restrictions of the sandbox profile of this environment
+    pub(crate) sandbox: Option<Arc<profile::SandboxPolicy>>,
+    /// Networking of the runtime restricted by the sandbox profile
+    sandbox_net: Option<Arc<profile::SandboxNetworking>>,
+    /// Tracer that prints the syscalls of this environment
+    #[derivative(Debug = "ignore")]
+    pub(crate) tracer: Option<Arc<SyscallTracer>>,
 }
 
 impl WasiEnv {
@@ -266,6 +309,11 @@ impl WasiEnv {
             malloc: None,
             free: None,
             runtime: Arc::new(PluggableRuntimeImplementation::default()),
+            journal: None,
+            deterministic: None,
+            sandbox: None,
+            sandbox_net: None,
+            tracer: None,
         }
     }
 
@@ -280,6 +328,25 @@ impl WasiEnv {
         R: WasiRuntimeImplementation + Send + Sync + 'static,
     {
         self.runtime = Arc::new(runtime);
+        let sandbox = self.sandbox.take();
+        self.set_sandbox(sandbox);
+    }
+
+    /// Restricts the syscalls of this environment to what the sandbox
+    /// profile allows, if any
+    pub(crate) fn set_sandbox(&mut self, sandbox: Option<Arc<profile::SandboxPolicy>>) {
+        self.sandbox_net = sandbox.as_ref().map(|policy| {
+            Arc::new(profile::SandboxNetworking::new(
+                policy.clone(),
+                self.runtime.clone(),
+            ))
+        });
+        self.sandbox = sandbox;
+    }
+
+    /// Returns the virtual clock of the program if it runs deterministically
+    pub fn virtual_clock(&self) -> Option<&VirtualClock> {
+        self.deterministic.as_ref().map(|state| &state.clock)
     }
 
     /// Returns the current thread ID
@@ -289,9 +356,20 @@ impl WasiEnv {
 
     /// Creates a new thread only this wasi environment
     pub fn new_thread(&self) -> WasiThread {
-        let (tx, rx) = mpsc::channel();
+        self.new_thread_within(None).unwrap()
+    }
 
+    /// Creates a new thread unless the environment already has
+    /// `max_threads` threads, checked under the same lock as the thread is
+    /// added so that concurrent spawns can't exceed the limit
+    pub(crate) fn new_thread_within(&self, max_threads: Option<u32>) -> Option<WasiThread> {
         let mut guard = self.state.threading.lock().unwrap();
+        if let Some(max_threads) = max_threads {
+            if guard.threads.len() >= max_threads as usize {
+                return None;
+            }
+        }
+        let (tx, rx) = mpsc::channel();
 
         guard.thread_seed += 1;
         let next_id: WasiThreadId = guard.thread_seed.into();
@@ -303,7 +381,7 @@ impl WasiEnv {
         };
 
         guard.threads.insert(thread.id, thread.clone());
-        thread
+        Some(thread)
     }
 
     /// Copy the lazy reference so that when it's initialized during the
@@ -348,7 +426,10 @@ impl WasiEnv {
 
     /// Accesses the virtual networking implementation
     pub fn net(&self) -> &(dyn VirtualNetworking) {
-        self.runtime.networking()
+        match self.sandbox_net.as_ref() {
+            Some(net) => net.as_ref(),
+            None => self.runtime.networking(),
+        }
     }
 
     /// Accesses the virtual bus implementation
@@ -419,7 +500,7 @@ pub fn generate_import_object_from_env(
     env: &FunctionEnv<WasiEnv>,
     version: WasiVersion,
 ) -> Imports {
-    match version {
+    let imports = match version {
         WasiVersion::Snapshot0 => generate_import_object_snapshot0(store, env),
         WasiVersion::Snapshot1 | WasiVersion::Latest => {
             generate_import_object_snapshot1(store, env)
@@ -430,7 +511,10 @@ pub fn generate_import_object_from_env(
         WasiVersion::Wasix64v1 => generate_import_object_wasix64_v1(store, env),
         #[cfg(not(feature = "wasix"))]
         _ => unimplemented!(),
-    }
+    };
+    let imports = profile::instrument_imports(store, env, imports);
+    let imports = journal::instrument_imports(store, env, imports);
+    strace::instrument_imports(store, env, imports)
 }
 
 fn wasi_unstable_exports(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>) -> Exports {
@@ -543,10 +627,13 @@ pub fn import_object_for_all_wasi_versions(
 ) -> Imports {
     let wasi_unstable_exports = wasi_unstable_exports(store, env);
     let wasi_snapshot_preview1_exports = wasi_snapshot_preview1_exports(store, env);
-    imports! {
+    let imports = imports! {
         "wasi_unstable" => wasi_unstable_exports,
         "wasi_snapshot_preview1" => wasi_snapshot_preview1_exports,
-    }
+    };
+    let imports = profile::instrument_imports(store, env, imports);
+    let imports = journal::instrument_imports(store, env, imports);
+    strace::instrument_imports(store, env, imports)
 }
 
 /// Combines a state generating function with the import list for legacy WASI
@@ -0,0 +1,1002 @@
+//! Sandbox profiles: the full permission set of a WASI program, described
+//! declaratively.
+//!
+//! A [`SandboxProfile`] lists the directories the program can see (and
+//! with which rights), its environment variables and arguments, whether it
+//! can use the network, which [`SyscallFamily`]s it can call and its
+//! resource limits. With the `sandbox-profile` feature profiles can be
+//! loaded from TOML files, so that they can be reviewed and versioned:
+//!
+//! ```toml
+//! args = ["--verbose"]
+//!
+//! [env]
+//! RUST_LOG = "info"
+//!
+//! [[preopen]]
+//! host = "data"        # relative to the directory of the profile
+//! guest = "/data"
+//! read = true
+//! write = true
+//! create = false
+//!
+//! [network]
+//! enabled = true
+//! connect = ["10.0.0.1:443", "*:53"]
+//! bind = []
+//!
+//! [syscalls]
+//! allow = ["fs", "clock", "random", "poll", "process", "net"]
+//!
+//! [limits]
+//! memory_pages = 256
+//! open_files = 64
+//! threads = 4
+//! ```
+//!
+//! Everything that is not granted by a profile is denied: without a
+//! `[network]` section the program can not use sockets at all. Syscalls
+//! that are not allowed fail with `ENOTCAPABLE` (or trap, for the few
+//! syscalls that can not return an error, like `proc_exit`). Note that
+//! writing to stdout and stderr is part of the `fs` family.
+//!
+//! The `connect` and `bind` allowlists apply to every syscall that reaches
+//! the network, which fails with `EPERM` for other addresses. Name
+//! resolution needs a `connect` rule for port 53. The URLs given to
+//! `http_request` and `ws_connect` are checked without resolving their
+//! host, which could resolve to another address when the request is made:
+//! a host name needs a rule that allows its port for any address, such as
+//! `*:443`, otherwise the URL has to name an allowed IP address. Raw
+//! and ICMP sockets, and the `port_*` syscalls that reconfigure the
+//! network interface, are only available when neither list is set.
+//!
+//! The memory limit can not be enforced by WASI itself, as memories grow
+//! without calling into WASI: it is applied by the tunables of the store,
+//! which [`SandboxProfile::store`] sets up. Finalizing a
+//! [`WasiStateBuilder`](crate::WasiStateBuilder) configured from a profile
+//! with a memory limit fails if the store does not enforce it.
+
+use crate::syscalls::types::*;
+use crate::{SyscallFamily, WasiEnv, WasiRuntimeImplementation};
+#[cfg(feature = "sandbox-profile")]
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+#[cfg(feature = "sandbox-profile")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "sandbox-profile")]
+use thiserror::Error;
+use wasmer::{
+    AsStoreMut, Extern, Function, FunctionEnv, FunctionEnvMut, Imports, RuntimeError, Type, Value,
+};
+#[cfg(feature = "compiler")]
+use wasmer::{BaseTunables, Engine, LimitingTunables, Pages, Store};
+use wasmer_vnet::{
+    Bytes, IpCidr, IpRoute, NetworkError, SocketHttpRequest, SocketReceive, SocketReceiveFrom,
+    SocketStatus, StreamSecurity, VirtualConnectedSocket, VirtualConnectionlessSocket,
+    VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
+};
+
+/// Error that can occur while loading a [`SandboxProfile`]
+#[cfg(feature = "sandbox-profile")]
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    /// The profile could not be read
+    #[error("failed to read the profile: {0}")]
+    Io(#[from] std::io::Error),
+    /// The profile is not valid TOML or has unknown or invalid fields
+    #[error("invalid profile: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// The permissions and the limits of a WASI program.
+///
+/// Usage:
+/// ```no_run
+/// # use wasmer::Cranelift;
+/// # use wasmer_wasi::{SandboxProfile, WasiState};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = SandboxProfile::from_file("guest.toml")?;
+/// let mut store = profile.store(Cranelift::default());
+///
+/// let mut state_builder = WasiState::new("wasi-prog-name");
+/// let wasi_env = state_builder.profile(&profile)?.finalize(&mut store)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct SandboxProfile {
+    /// Arguments passed to the program
+    pub args: Vec<String>,
+    /// Environment variables of the program
+    pub env: BTreeMap<String, String>,
+    /// Directories of the host that the program can access
+    #[cfg_attr(feature = "sandbox-profile", serde(rename = "preopen"))]
+    pub preopens: Vec<PreopenProfile>,
+    /// Network access of the program
+    pub network: NetworkPolicy,
+    /// Syscalls that the program can make
+    pub syscalls: SyscallPolicy,
+    /// Resource limits of the program
+    pub limits: ResourceLimits,
+}
+
+impl SandboxProfile {
+    /// Loads a profile from a TOML file.
+    ///
+    /// Relative host paths of the preopened directories are resolved from
+    /// the directory of the file.
+    #[cfg(feature = "sandbox-profile")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ProfileError> {
+        let path = path.as_ref();
+        let mut profile: Self = toml::from_str(&std::fs::read_to_string(path)?)?;
+        if let Some(base) = path.parent() {
+            for preopen in profile.preopens.iter_mut() {
+                if preopen.host.is_relative() {
+                    preopen.host = base.join(&preopen.host);
+                }
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Returns true if the program can call the given syscall
+    pub fn allows(&self, syscall: &str) -> bool {
+        match SyscallFamily::of(syscall) {
+            SyscallFamily::Net if !self.network.enabled => false,
+            family => match self.syscalls.allow.as_ref() {
+                Some(allow) => allow.contains(&family),
+                None => true,
+            },
+        }
+    }
+
+    /// Creates a store for `engine` whose tunables cap the memories to the
+    /// `memory_pages` limit of the profile, which
+    /// [`WasiStateBuilder::finalize`](crate::WasiStateBuilder::finalize)
+    /// requires
+    #[cfg(feature = "compiler")]
+    pub fn store(&self, engine: impl Into<Engine>) -> Store {
+        let engine = engine.into();
+        match self.limits.memory_pages {
+            Some(limit) => {
+                let base = BaseTunables::for_target(engine.target());
+                Store::new_with_tunables(engine, LimitingTunables::new(base, Pages(limit)))
+            }
+            None => Store::new(engine),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> SandboxPolicy {
+        SandboxPolicy {
+            profile: self.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "sandbox-profile")]
+impl FromStr for SandboxProfile {
+    type Err = ProfileError;
+
+    /// Parses a profile in the TOML format
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+/// A directory of the host that is visible to the program
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct PreopenProfile {
+    /// Path of the directory on the host
+    pub host: PathBuf,
+    /// Path of the directory as seen by the program (defaults to the host
+    /// path)
+    #[cfg_attr(feature = "sandbox-profile", serde(default))]
+    pub guest: Option<String>,
+    /// Whether the program can read the directory
+    #[cfg_attr(feature = "sandbox-profile", serde(default = "default_true"))]
+    pub read: bool,
+    /// Whether the program can write to the directory
+    #[cfg_attr(feature = "sandbox-profile", serde(default))]
+    pub write: bool,
+    /// Whether the program can create files in the directory
+    #[cfg_attr(feature = "sandbox-profile", serde(default))]
+    pub create: bool,
+}
+
+#[cfg(feature = "sandbox-profile")]
+fn default_true() -> bool {
+    true
+}
+
+/// Network access of a program
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct NetworkPolicy {
+    /// Whether the program can use the network at all
+    pub enabled: bool,
+    /// Addresses the program can connect and send datagrams to (any
+    /// address when `None`)
+    pub connect: Option<Vec<AddressRule>>,
+    /// Addresses the program can bind sockets to (any address when `None`)
+    pub bind: Option<Vec<AddressRule>>,
+}
+
+impl NetworkPolicy {
+    fn check(rules: Option<&Vec<AddressRule>>, addr: SocketAddr) -> wasmer_vnet::Result<()> {
+        match rules {
+            Some(rules) if !rules.iter().any(|rule| rule.matches(addr)) => {
+                Err(NetworkError::PermissionDenied)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the interface can only reach some addresses
+    fn is_restricted(&self) -> bool {
+        self.connect.is_some() || self.bind.is_some()
+    }
+}
+
+/// A set of socket addresses, written as `ip:port`, `ip`, `*:port` or `*`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(Deserialize),
+    serde(try_from = "String")
+)]
+pub struct AddressRule {
+    /// IP address that matches the rule (any address when `None`)
+    pub ip: Option<IpAddr>,
+    /// Port that matches the rule (any port when `None`)
+    pub port: Option<u16>,
+}
+
+impl AddressRule {
+    /// Returns true if the address matches the rule
+    pub fn matches(&self, addr: SocketAddr) -> bool {
+        self.ip.map_or(true, |ip| ip == addr.ip()) && self.port.map_or(true, |p| p == addr.port())
+    }
+}
+
+impl FromStr for AddressRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "*" {
+            return Ok(Self {
+                ip: None,
+                port: None,
+            });
+        }
+        if let Ok(addr) = s.parse::<SocketAddr>() {
+            return Ok(Self {
+                ip: Some(addr.ip()),
+                port: Some(addr.port()),
+            });
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(Self {
+                ip: Some(ip),
+                port: None,
+            });
+        }
+        match s.strip_prefix("*:").map(u16::from_str) {
+            Some(Ok(port)) => Ok(Self {
+                ip: None,
+                port: Some(port),
+            }),
+            _ => Err(format!(
+                "invalid address `{}` (expected `ip:port`, `ip`, `*:port` or `*`)",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for AddressRule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for AddressRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.ip, self.port) {
+            (Some(ip), Some(port)) => write!(f, "{}", SocketAddr::new(ip, port)),
+            (Some(ip), None) => write!(f, "{}", ip),
+            (None, Some(port)) => write!(f, "*:{}", port),
+            (None, None) => write!(f, "*"),
+        }
+    }
+}
+
+/// Syscalls that a program can make
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct SyscallPolicy {
+    /// Families of syscalls the program can call (all of them when `None`)
+    pub allow: Option<Vec<SyscallFamily>>,
+}
+
+/// Resource limits of a program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ResourceLimits {
+    /// Maximum size of the memories, in pages of 64 KiB (enforced by the
+    /// tunables of the store, see [`SandboxProfile::store`])
+    pub memory_pages: Option<u32>,
+    /// Maximum number of open file descriptors, including stdio, the
+    /// virtual root and the preopened directories
+    pub open_files: Option<u32>,
+    /// Maximum number of threads running at the same time, besides the
+    /// main thread
+    pub threads: Option<u32>,
+}
+
+/// Restrictions of a profile that are enforced while the program runs
+#[derive(Debug)]
+pub(crate) struct SandboxPolicy {
+    profile: SandboxProfile,
+}
+
+impl SandboxPolicy {
+    fn network(&self) -> wasmer_vnet::Result<&NetworkPolicy> {
+        let network = &self.profile.network;
+        if network.enabled {
+            Ok(network)
+        } else {
+            Err(NetworkError::PermissionDenied)
+        }
+    }
+
+    fn check_connect(&self, addr: SocketAddr) -> wasmer_vnet::Result<()> {
+        NetworkPolicy::check(self.network()?.connect.as_ref(), addr)
+    }
+
+    fn check_bind(&self, addr: SocketAddr) -> wasmer_vnet::Result<()> {
+        NetworkPolicy::check(self.network()?.bind.as_ref(), addr)
+    }
+
+    /// Checks that names can be resolved with `dns_server`, or with the
+    /// resolver of the host, whose address is unknown, when it is `None`
+    fn check_resolver(&self, dns_server: Option<IpAddr>) -> wasmer_vnet::Result<()> {
+        match dns_server {
+            Some(dns_server) => self.check_connect(SocketAddr::new(dns_server, 53)),
+            None => match self.network()?.connect.as_ref() {
+                Some(rules) if !rules.iter().any(|rule| rule.port.map_or(true, |p| p == 53)) => {
+                    Err(NetworkError::PermissionDenied)
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Checks that the host of `url` can be connected to
+    ///
+    /// The networking resolves the host itself when it connects, and could
+    /// get another address than one resolved here, so the check can't
+    /// depend on what the host resolves to: a host name is only allowed
+    /// when the rules let its port through for any address, otherwise the
+    /// URL has to name an allowed IP address.
+    fn check_url(&self, url: &str) -> wasmer_vnet::Result<()> {
+        let rules = match self.network()?.connect.as_ref() {
+            Some(rules) => rules,
+            None => return Ok(()),
+        };
+        let (host, port) = url_host_port(url).ok_or(NetworkError::InvalidInput)?;
+        let allowed = match host.parse::<IpAddr>() {
+            Ok(ip) => rules
+                .iter()
+                .any(|rule| rule.matches(SocketAddr::new(ip, port))),
+            Err(_) => {
+                self.check_resolver(None)?;
+                rules
+                    .iter()
+                    .any(|rule| rule.ip.is_none() && rule.port.map_or(true, |p| p == port))
+            }
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(NetworkError::PermissionDenied)
+        }
+    }
+
+    pub(crate) fn max_threads(&self) -> Option<u32> {
+        self.profile.limits.threads
+    }
+}
+
+/// The networking of the runtime of an environment, restricted to what its
+/// sandbox profile allows
+///
+/// The syscalls reach the network through it, and only through it, so it
+/// is where the allowlists are enforced. The UDP sockets it binds check the
+/// peers they connect and send datagrams to.
+#[derive(Debug)]
+pub(crate) struct SandboxNetworking {
+    policy: Arc<SandboxPolicy>,
+    runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+}
+
+impl SandboxNetworking {
+    pub(crate) fn new(
+        policy: Arc<SandboxPolicy>,
+        runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    ) -> Self {
+        Self { policy, runtime }
+    }
+
+    fn inner(&self) -> &dyn VirtualNetworking {
+        self.runtime.networking()
+    }
+
+    fn network(&self) -> wasmer_vnet::Result<&NetworkPolicy> {
+        self.policy.network()
+    }
+
+    fn check_connect(&self, addr: SocketAddr) -> wasmer_vnet::Result<()> {
+        self.policy.check_connect(addr)
+    }
+
+    fn check_bind(&self, addr: SocketAddr) -> wasmer_vnet::Result<()> {
+        self.policy.check_bind(addr)
+    }
+
+    /// Checks that the interface can reach any address, for the operations
+    /// that could get around the allowlists
+    fn check_unrestricted(&self) -> wasmer_vnet::Result<()> {
+        if self.network()?.is_restricted() {
+            Err(NetworkError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl VirtualNetworking for SandboxNetworking {
+    fn ws_connect(&self, url: &str) -> wasmer_vnet::Result<Box<dyn VirtualWebSocket + Sync>> {
+        self.policy.check_url(url)?;
+        self.inner().ws_connect(url)
+    }
+
+    fn http_request(
+        &self,
+        url: &str,
+        method: &str,
+        headers: &str,
+        gzip: bool,
+    ) -> wasmer_vnet::Result<SocketHttpRequest> {
+        self.policy.check_url(url)?;
+        self.inner().http_request(url, method, headers, gzip)
+    }
+
+    fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().bridge(network, access_token, security)
+    }
+
+    fn unbridge(&self) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().unbridge()
+    }
+
+    fn dhcp_acquire(&self) -> wasmer_vnet::Result<Vec<IpAddr>> {
+        self.check_unrestricted()?;
+        self.inner().dhcp_acquire()
+    }
+
+    fn ip_add(&self, ip: IpAddr, prefix: u8) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().ip_add(ip, prefix)
+    }
+
+    fn ip_remove(&self, ip: IpAddr) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().ip_remove(ip)
+    }
+
+    fn ip_clear(&self) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().ip_clear()
+    }
+
+    fn ip_list(&self) -> wasmer_vnet::Result<Vec<IpCidr>> {
+        self.network()?;
+        self.inner().ip_list()
+    }
+
+    fn mac(&self) -> wasmer_vnet::Result<[u8; 6]> {
+        self.network()?;
+        self.inner().mac()
+    }
+
+    fn gateway_set(&self, ip: IpAddr) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().gateway_set(ip)
+    }
+
+    fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner()
+            .route_add(cidr, via_router, preferred_until, expires_at)
+    }
+
+    fn route_remove(&self, cidr: IpAddr) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().route_remove(cidr)
+    }
+
+    fn route_clear(&self) -> wasmer_vnet::Result<()> {
+        self.check_unrestricted()?;
+        self.inner().route_clear()
+    }
+
+    fn route_list(&self) -> wasmer_vnet::Result<Vec<IpRoute>> {
+        self.network()?;
+        self.inner().route_list()
+    }
+
+    fn bind_raw(&self) -> wasmer_vnet::Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.check_unrestricted()?;
+        self.inner().bind_raw()
+    }
+
+    fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> wasmer_vnet::Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.check_bind(addr)?;
+        self.inner()
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+    }
+
+    fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> wasmer_vnet::Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.check_bind(addr)?;
+        let socket = self.inner().bind_udp(addr, reuse_port, reuse_addr)?;
+        Ok(Box::new(SandboxUdpSocket {
+            policy: self.policy.clone(),
+            inner: socket,
+        }))
+    }
+
+    fn bind_icmp(&self, addr: IpAddr) -> wasmer_vnet::Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        // ICMP sockets can send to any host
+        self.check_unrestricted()?;
+        self.inner().bind_icmp(addr)
+    }
+
+    fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> wasmer_vnet::Result<Box<dyn VirtualTcpSocket + Sync>> {
+        // Sockets that were not bound explicitly get any local address
+        if addr.port() != 0 || !addr.ip().is_unspecified() {
+            self.check_bind(addr)?;
+        }
+        self.check_connect(peer)?;
+        self.inner().connect_tcp(addr, peer, timeout)
+    }
+
+    fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> wasmer_vnet::Result<Vec<IpAddr>> {
+        self.policy.check_resolver(dns_server)?;
+        self.inner().resolve(host, port, dns_server)
+    }
+}
+
+/// A UDP socket bound by [`SandboxNetworking`], which checks the peers it
+/// connects and sends datagrams to
+#[derive(Debug)]
+struct SandboxUdpSocket {
+    policy: Arc<SandboxPolicy>,
+    inner: Box<dyn VirtualUdpSocket + Sync>,
+}
+
+impl VirtualSocket for SandboxUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> wasmer_vnet::Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> wasmer_vnet::Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> wasmer_vnet::Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> wasmer_vnet::Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> wasmer_vnet::Result<()> {
+        self.inner.set_recv_timeout(timeout)
+    }
+
+    fn recv_timeout(&self) -> wasmer_vnet::Result<Option<Duration>> {
+        self.inner.recv_timeout()
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> wasmer_vnet::Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+}
+
+impl VirtualConnectedSocket for SandboxUdpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> wasmer_vnet::Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> wasmer_vnet::Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn send(&mut self, data: Bytes) -> wasmer_vnet::Result<usize> {
+        self.inner.send(data)
+    }
+
+    fn flush(&mut self) -> wasmer_vnet::Result<()> {
+        self.inner.flush()
+    }
+
+    fn recv(&mut self) -> wasmer_vnet::Result<SocketReceive> {
+        self.inner.recv()
+    }
+
+    fn peek(&mut self) -> wasmer_vnet::Result<SocketReceive> {
+        self.inner.peek()
+    }
+}
+
+impl VirtualConnectionlessSocket for SandboxUdpSocket {
+    fn send_to(&mut self, data: Bytes, addr: SocketAddr) -> wasmer_vnet::Result<usize> {
+        self.policy.check_connect(addr)?;
+        self.inner.send_to(data, addr)
+    }
+
+    fn recv_from(&mut self) -> wasmer_vnet::Result<SocketReceiveFrom> {
+        self.inner.recv_from()
+    }
+
+    fn peek_from(&mut self) -> wasmer_vnet::Result<SocketReceiveFrom> {
+        self.inner.peek_from()
+    }
+}
+
+impl VirtualUdpSocket for SandboxUdpSocket {
+    fn connect(&mut self, addr: SocketAddr) -> wasmer_vnet::Result<()> {
+        self.policy.check_connect(addr)?;
+        self.inner.connect(addr)
+    }
+
+    fn set_broadcast(&mut self, broadcast: bool) -> wasmer_vnet::Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> wasmer_vnet::Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> wasmer_vnet::Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> wasmer_vnet::Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> wasmer_vnet::Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> wasmer_vnet::Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> wasmer_vnet::Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> wasmer_vnet::Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(
+        &mut self,
+        multiaddr: Ipv4Addr,
+        iface: Ipv4Addr,
+    ) -> wasmer_vnet::Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(
+        &mut self,
+        multiaddr: Ipv4Addr,
+        iface: Ipv4Addr,
+    ) -> wasmer_vnet::Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> wasmer_vnet::Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> wasmer_vnet::Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> wasmer_vnet::Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+/// Returns the host and the port of an absolute URL, or `None` if the URL
+/// has no port and its scheme has no default one
+fn url_host_port(url: &str) -> Option<(&str, u16)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next()?;
+    let authority = authority.rsplit('@').next()?;
+    let (host, port) = match authority.rfind(':') {
+        // The colons of an IPv6 address are between brackets
+        Some(i) if !authority[i..].contains(']') => {
+            (&authority[..i], Some(authority[i + 1..].parse().ok()?))
+        }
+        _ => (authority, None),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = match (port, scheme.to_ascii_lowercase().as_str()) {
+        (Some(port), _) => port,
+        (None, "http") | (None, "ws") => 80,
+        (None, "https") | (None, "wss") => 443,
+        _ => return None,
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some((host, port))
+    }
+}
+
+/// Replaces the functions of `imports` that are denied by the sandbox of
+/// the environment (if it has one)
+pub(crate) fn instrument_imports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    imports: Imports,
+) -> Imports {
+    let sandbox = match env.as_ref(store).sandbox.clone() {
+        Some(sandbox) => sandbox,
+        None => return imports,
+    };
+
+    let mut ret = Imports::new();
+    for ((namespace, name), ext) in &imports {
+        let ext = match ext {
+            Extern::Function(func) if !sandbox.profile.allows(name.as_str()) => {
+                let ty = func.ty(store);
+                let syscall = name.clone();
+                let denied = match ty.results() {
+                    [Type::I32] if SyscallFamily::of(&name) == SyscallFamily::Bus => {
+                        Some(__BUS_EDENIED as i32)
+                    }
+                    [Type::I32] => Some(__WASI_ENOTCAPABLE as i32),
+                    _ => None,
+                };
+                Extern::Function(Function::new_with_env(
+                    store,
+                    env,
+                    ty,
+                    move |_ctx: FunctionEnvMut<WasiEnv>, _params: &[Value]| match denied {
+                        Some(errno) => Ok(vec![Value::I32(errno)]),
+                        None => Err(RuntimeError::new(format!(
+                            "the syscall `{}` is not allowed by the sandbox profile",
+                            syscall
+                        ))),
+                    },
+                ))
+            }
+            ext => ext,
+        };
+        ret.define(&namespace, &name, ext);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_rules() {
+        let addr: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        for (rule, matches) in [
+            ("*", true),
+            ("10.0.0.1", true),
+            ("10.0.0.1:443", true),
+            ("10.0.0.1:80", false),
+            ("*:443", true),
+            ("*:53", false),
+            ("[::1]:443", false),
+        ] {
+            let rule = rule.parse::<AddressRule>().unwrap();
+            assert_eq!(rule.matches(addr), matches, "{}", rule);
+        }
+        assert_eq!(
+            "[::1]:443".parse::<AddressRule>().unwrap().to_string(),
+            "[::1]:443"
+        );
+        assert!("10.0.0.1:".parse::<AddressRule>().is_err());
+        assert!("*:http".parse::<AddressRule>().is_err());
+    }
+
+    #[test]
+    fn url_hosts() {
+        assert_eq!(
+            url_host_port("http://10.0.0.1/index.html"),
+            Some(("10.0.0.1", 80))
+        );
+        assert_eq!(
+            url_host_port("wss://user@example.com:8443?q"),
+            Some(("example.com", 8443))
+        );
+        assert_eq!(url_host_port("https://[::1]"), Some(("::1", 443)));
+        assert_eq!(url_host_port("http://[::1]:8080/"), Some(("::1", 8080)));
+        assert_eq!(url_host_port("ftp://example.com/"), None);
+        assert_eq!(url_host_port("example.com"), None);
+    }
+
+    #[test]
+    fn url_rules() {
+        let mut profile = SandboxProfile::default();
+        profile.network.enabled = true;
+        let rules = |rules: &[&str]| -> Option<Vec<AddressRule>> {
+            Some(rules.iter().map(|r| r.parse().unwrap()).collect())
+        };
+
+        // Host names don't resolve to a fixed address
+        profile.network.connect = rules(&["10.0.0.1:443", "*:53"]);
+        let policy = profile.policy();
+        assert!(policy.check_url("https://10.0.0.1/").is_ok());
+        assert!(policy.check_url("https://10.0.0.2/").is_err());
+        assert!(policy.check_url("https://example.com/").is_err());
+
+        profile.network.connect = rules(&["*:443", "*:53"]);
+        let policy = profile.policy();
+        assert!(policy.check_url("https://example.com/").is_ok());
+        assert!(policy.check_url("http://example.com/").is_err());
+
+        // Resolving the name needs the resolver
+        profile.network.connect = rules(&["*:443"]);
+        let policy = profile.policy();
+        assert!(policy.check_url("https://example.com/").is_err());
+        assert!(policy.check_url("https://10.0.0.1/").is_ok());
+    }
+
+    #[test]
+    fn allowed_syscalls() {
+        let mut profile = SandboxProfile::default();
+        assert!(profile.allows("fd_write"));
+        assert!(!profile.allows("sock_open"));
+
+        profile.network.enabled = true;
+        profile.syscalls.allow = Some(vec![SyscallFamily::Fs, SyscallFamily::Process]);
+        assert!(profile.allows("fd_write"));
+        assert!(profile.allows("proc_exit"));
+        assert!(!profile.allows("sock_open"));
+        assert!(!profile.allows("clock_time_get"));
+    }
+
+    #[cfg(feature = "sandbox-profile")]
+    #[test]
+    fn parse_profile() {
+        let profile: SandboxProfile = r#"
+            args = ["-v"]
+
+            [env]
+            KEY = "value"
+
+            [[preopen]]
+            host = "data"
+            guest = "/data"
+            write = true
+
+            [network]
+            enabled = true
+            connect = ["10.0.0.1:443"]
+
+            [syscalls]
+            allow = ["fs", "net"]
+
+            [limits]
+            open_files = 16
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(profile.args, vec!["-v".to_string()]);
+        assert_eq!(profile.env.get("KEY").map(String::as_str), Some("value"));
+        assert_eq!(
+            profile.preopens,
+            vec![PreopenProfile {
+                host: "data".into(),
+                guest: Some("/data".to_string()),
+                read: true,
+                write: true,
+                create: false,
+            }]
+        );
+        assert!(profile.network.enabled);
+        assert_eq!(profile.network.connect.as_ref().unwrap().len(), 1);
+        assert_eq!(profile.network.bind, None);
+        assert_eq!(
+            profile.syscalls.allow,
+            Some(vec![SyscallFamily::Fs, SyscallFamily::Net])
+        );
+        assert_eq!(profile.limits.open_files, Some(16));
+        assert_eq!(profile.limits.threads, None);
+
+        assert!("[network]\nenabled = 1".parse::<SandboxProfile>().is_err());
+        assert!("unknown = true".parse::<SandboxProfile>().is_err());
+        assert!("[syscalls]\nallow = [\"files\"]"
+            .parse::<SandboxProfile>()
+            .is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use thiserror::Error;
 use wasmer_vbus::{UnsupportedVirtualBus, VirtualBus};
 use wasmer_vnet::VirtualNetworking;
@@ -149,3 +150,52 @@ impl WasiRuntimeImplementation for PluggableRuntimeImplementation {
         self.thread_id_seed.fetch_add(1, Ordering::Relaxed).into()
     }
 }
+
+/// A runtime whose networking is replaced, while everything else is
+/// delegated to another runtime
+#[derive(Debug)]
+pub(crate) struct NetworkingOverride {
+    pub(crate) runtime: Arc<dyn WasiRuntimeImplementation + Send + Sync + 'static>,
+    pub(crate) networking: Arc<dyn VirtualNetworking + Sync>,
+}
+
+impl WasiRuntimeImplementation for NetworkingOverride {
+    fn bus(&self) -> &(dyn VirtualBus) {
+        self.runtime.bus()
+    }
+
+    fn networking(&self) -> &(dyn VirtualNetworking) {
+        self.networking.deref()
+    }
+
+    fn thread_generate_id(&self) -> WasiThreadId {
+        self.runtime.thread_generate_id()
+    }
+
+    fn tty_get(&self) -> WasiTtyState {
+        self.runtime.tty_get()
+    }
+
+    fn tty_set(&self, tty_state: WasiTtyState) {
+        self.runtime.tty_set(tty_state)
+    }
+
+    fn thread_spawn(
+        &self,
+        callback: Box<dyn FnOnce() + Send + 'static>,
+    ) -> Result<(), WasiThreadError> {
+        self.runtime.thread_spawn(callback)
+    }
+
+    fn thread_parallelism(&self) -> Result<usize, WasiThreadError> {
+        self.runtime.thread_parallelism()
+    }
+
+    fn yield_now(&self, id: WasiThreadId) -> Result<(), WasiError> {
+        self.runtime.yield_now(id)
+    }
+
+    fn getpid(&self) -> Option<u32> {
+        self.runtime.getpid()
+    }
+}
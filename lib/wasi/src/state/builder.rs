@@ -1,8 +1,13 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
+use crate::profile::SandboxPolicy;
+use crate::runtime::NetworkingOverride;
 use crate::state::{default_fs_backing, WasiFs, WasiState};
 use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
-use crate::{WasiEnv, WasiFunctionEnv, WasiInodes};
+use crate::{
+    Deterministic, SandboxProfile, SyscallJournal, SyscallTracer, VirtualNetworking, WasiEnv,
+    WasiFunctionEnv, WasiInodes,
+};
 use generational_arena::Arena;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
@@ -11,6 +16,8 @@ use std::sync::Arc;
 use std::sync::RwLock;
 use thiserror::Error;
 use wasmer::AsStoreMut;
+#[cfg(feature = "sys")]
+use wasmer::Pages;
 use wasmer_vfs::{FsError, VirtualFile};
 
 /// Creates an empty [`WasiStateBuilder`].
@@ -52,6 +59,13 @@ pub struct WasiStateBuilder {
     stdin_override: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     fs_override: Option<Box<dyn wasmer_vfs::FileSystem>>,
     runtime_override: Option<Arc<dyn crate::WasiRuntimeImplementation + Send + Sync + 'static>>,
+    networking_override: Option<Arc<dyn VirtualNetworking + Sync>>,
+    journal: Option<Arc<SyscallJournal>>,
+    deterministic: Option<Deterministic>,
+    tracer: Option<Arc<SyscallTracer>>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+    fd_limit: Option<u32>,
+    memory_limit: Option<u32>,
 }
 
 impl std::fmt::Debug for WasiStateBuilder {
@@ -66,6 +80,13 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("stderr_override exists", &self.stderr_override.is_some())
             .field("stdin_override exists", &self.stdin_override.is_some())
             .field("runtime_override_exists", &self.runtime_override.is_some())
+            .field("networking_override", &self.networking_override)
+            .field("journal", &self.journal)
+            .field("deterministic", &self.deterministic)
+            .field("tracer", &self.tracer)
+            .field("sandbox", &self.sandbox)
+            .field("fd_limit", &self.fd_limit)
+            .field("memory_limit", &self.memory_limit)
             .finish()
     }
 }
@@ -89,6 +110,10 @@ pub enum WasiStateCreationError {
     WasiFsSetupError(String),
     #[error(transparent)]
     FileSystemError(FsError),
+    #[error("deterministic execution requires a compiler that canonicalizes NaNs")]
+    NanCanonicalizationRequired,
+    #[error("the sandbox profile limits the memories to {0} pages, which the tunables of the store do not enforce")]
+    MemoryLimitRequired(u32),
 }
 
 fn validate_mapped_dir_alias(alias: &str) -> Result<(), WasiStateCreationError> {
@@ -328,6 +353,9 @@ impl WasiStateBuilder {
 
     /// Sets the WASI runtime implementation and overrides the default
     /// implementation
+    ///
+    /// The networking set with [`Self::networking`] takes precedence over
+    /// the one of the runtime.
     pub fn runtime<R>(&mut self, runtime: R) -> &mut Self
     where
         R: crate::WasiRuntimeImplementation + Send + Sync + 'static,
@@ -336,6 +364,81 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Sets the networking implementation of the program, for instance a
+    /// `UserspaceNetworking` stack, instead of the one of the runtime
+    ///
+    /// The rest of the runtime (set with [`Self::runtime`], or the default
+    /// one) is kept.
+    pub fn networking<N>(&mut self, networking: N) -> &mut Self
+    where
+        N: VirtualNetworking + Sync,
+    {
+        self.networking_override = Some(Arc::new(networking));
+        self
+    }
+
+    /// Records the syscalls of the program into a journal, or replays them
+    /// from a previous recording (see [`SyscallJournal`])
+    pub fn journal(&mut self, journal: SyscallJournal) -> &mut Self {
+        self.journal = Some(Arc::new(journal));
+        self
+    }
+
+    /// Makes the execution of the program deterministic: the clocks are
+    /// replaced with a virtual clock, `random_get` is seeded and threads
+    /// are disabled (see [`Deterministic`]).
+    ///
+    /// Floating point results are only deterministic when the module is
+    /// compiled with NaN canonicalization, so the store must use an engine
+    /// created with [`Deterministic::engine`], which turns it on. `finalize`
+    /// fails with [`WasiStateCreationError::NanCanonicalizationRequired`]
+    /// when the compiler of the store doesn't canonicalize NaNs. The modules
+    /// of a store without a compiler are assumed to have been compiled with
+    /// it.
+    pub fn deterministic(&mut self, deterministic: Deterministic) -> &mut Self {
+        self.deterministic = Some(deterministic);
+        self
+    }
+
+    /// Prints the syscalls made by the program, like `strace` does (see
+    /// [`SyscallTracer`])
+    pub fn strace(&mut self, tracer: SyscallTracer) -> &mut Self {
+        self.tracer = Some(Arc::new(tracer));
+        self
+    }
+
+    /// Configures the program from a sandbox profile: its arguments,
+    /// environment variables and preopened directories are added to the
+    /// ones of the builder, and the syscalls, network access and resource
+    /// limits of the program are restricted (see [`SandboxProfile`]).
+    ///
+    /// The memory limit of the profile is enforced by the tunables of the
+    /// store, see [`SandboxProfile::store`]: `finalize` fails with
+    /// [`WasiStateCreationError::MemoryLimitRequired`] if the store does not
+    /// cap the memories to it.
+    pub fn profile(
+        &mut self,
+        profile: &SandboxProfile,
+    ) -> Result<&mut Self, WasiStateCreationError> {
+        self.args(&profile.args).envs(&profile.env);
+        for preopen in profile.preopens.iter() {
+            self.preopen(|p| {
+                p.directory(&preopen.host)
+                    .read(preopen.read)
+                    .write(preopen.write)
+                    .create(preopen.create);
+                if let Some(guest) = preopen.guest.as_ref() {
+                    p.alias(guest);
+                }
+                p
+            })?;
+        }
+        self.fd_limit = profile.limits.open_files;
+        self.memory_limit = profile.limits.memory_pages;
+        self.sandbox = Some(Arc::new(profile.policy()));
+        Ok(self)
+    }
+
     /// Consumes the [`WasiStateBuilder`] and produces a [`WasiState`]
     ///
     /// Returns the error from `WasiFs::new` if there's an error
@@ -460,6 +563,7 @@ impl WasiStateBuilder {
                 f(inodes.deref_mut(), &mut wasi_fs)
                     .map_err(WasiStateCreationError::WasiFsSetupError)?;
             }
+            wasi_fs.fd_limit = self.fd_limit;
             wasi_fs
         };
 
@@ -497,12 +601,45 @@ impl WasiStateBuilder {
         &mut self,
         store: &mut impl AsStoreMut,
     ) -> Result<WasiFunctionEnv, WasiStateCreationError> {
+        #[cfg(feature = "compiler")]
+        if self.deterministic.is_some()
+            && store.as_store_ref().engine().canonicalizes_nans() == Some(false)
+        {
+            return Err(WasiStateCreationError::NanCanonicalizationRequired);
+        }
+
+        // Memories grow without calling into WASI, so the limit of the
+        // profile has to be enforced by the tunables of the store
+        #[cfg(feature = "sys")]
+        if let Some(limit) = self.memory_limit {
+            #[cfg(feature = "compiler")]
+            let enforced = store.as_store_ref().tunables().memory_limit();
+            #[cfg(not(feature = "compiler"))]
+            let enforced = None;
+            if !matches!(enforced, Some(maximum) if maximum <= Pages(limit)) {
+                return Err(WasiStateCreationError::MemoryLimitRequired(limit));
+            }
+        }
+
         let state = self.build()?;
 
         let mut env = WasiEnv::new(state);
         if let Some(runtime) = self.runtime_override.as_ref() {
             env.runtime = runtime.clone();
         }
+        if let Some(networking) = self.networking_override.as_ref() {
+            env.runtime = Arc::new(NetworkingOverride {
+                runtime: env.runtime.clone(),
+                networking: networking.clone(),
+            });
+        }
+        env.journal = self.journal.clone();
+        env.deterministic = self
+            .deterministic
+            .as_ref()
+            .map(|deterministic| Arc::new(deterministic.build()));
+        env.tracer = self.tracer.clone();
+        env.set_sandbox(self.sandbox.clone());
         Ok(WasiFunctionEnv::new(store, env))
     }
 }
@@ -337,6 +337,8 @@ pub struct WasiFs {
     pub is_wasix: AtomicBool,
     #[cfg_attr(feature = "enable-serde", serde(skip, default = "default_fs_backing"))]
     pub fs_backing: Box<dyn FileSystem>,
+    /// Maximum number of file descriptors that can be open at once
+    pub(crate) fd_limit: Option<u32>,
 }
 
 /// Returns the default filesystem backing
@@ -585,6 +587,7 @@ impl WasiFs {
             current_dir: Mutex::new("/".to_string()),
             is_wasix: AtomicBool::new(false),
             fs_backing,
+            fd_limit: None,
         };
         wasi_fs.create_stdin(inodes);
         wasi_fs.create_stdout(inodes);
@@ -1486,8 +1489,10 @@ impl WasiFs {
         open_flags: u16,
         inode: Inode,
     ) -> Result<__wasi_fd_t, __wasi_errno_t> {
+        let mut fd_map = self.fd_map.write().unwrap();
+        self.check_fd_limit(&fd_map)?;
         let idx = self.next_fd.fetch_add(1, Ordering::AcqRel);
-        self.fd_map.write().unwrap().insert(
+        fd_map.insert(
             idx,
             Fd {
                 rights,
@@ -1501,10 +1506,19 @@ impl WasiFs {
         Ok(idx)
     }
 
+    fn check_fd_limit(&self, fd_map: &HashMap<u32, Fd>) -> Result<(), __wasi_errno_t> {
+        match self.fd_limit {
+            Some(limit) if fd_map.len() >= limit as usize => Err(__WASI_EMFILE),
+            _ => Ok(()),
+        }
+    }
+
     pub fn clone_fd(&self, fd: __wasi_fd_t) -> Result<__wasi_fd_t, __wasi_errno_t> {
         let fd = self.get_fd(fd)?;
+        let mut fd_map = self.fd_map.write().unwrap();
+        self.check_fd_limit(&fd_map)?;
         let idx = self.next_fd.fetch_add(1, Ordering::AcqRel);
-        self.fd_map.write().unwrap().insert(
+        fd_map.insert(
             idx,
             Fd {
                 rights: fd.rights,
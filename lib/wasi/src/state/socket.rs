@@ -14,7 +14,7 @@ use wasmer::{MemorySize, MemoryView, WasmPtr, WasmSlice};
 use wasmer_vnet::{net_error_into_io_err, TimeType};
 use wasmer_vnet::{
     IpCidr, IpRoute, SocketHttpRequest, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
-    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
+    VirtualSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket, VirtualWebSocket,
 };
 
 #[cfg(feature = "enable-serde")]
@@ -48,6 +48,7 @@ pub enum InodeSocketKind {
         recv_timeout: Option<Duration>,
         connect_timeout: Option<Duration>,
         accept_timeout: Option<Duration>,
+        nonblocking: bool,
     },
     HttpRequest(Mutex<SocketHttpRequest>, InodeHttpSocketType),
     WebSocket(Box<dyn VirtualWebSocket + Sync>),
@@ -125,6 +126,26 @@ impl From<__wasi_sockoption_t> for WasiSocketOption {
     }
 }
 
+/// Applies the receive options that were set on a socket before it was
+/// bound
+fn set_recv_options<S: VirtualSocket + ?Sized>(
+    socket: &mut S,
+    recv_timeout: Option<Duration>,
+    nonblocking: bool,
+) -> Result<(), __wasi_errno_t> {
+    if let Some(timeout) = recv_timeout {
+        socket
+            .set_recv_timeout(Some(timeout))
+            .map_err(net_error_into_wasi_err)?;
+    }
+    if nonblocking {
+        socket
+            .set_nonblocking(true)
+            .map_err(net_error_into_wasi_err)?;
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub enum WasiSocketStatus {
     Opening,
@@ -167,9 +188,12 @@ impl InodeSocket {
             InodeSocketKind::PreSocket {
                 family,
                 ty,
+                pt,
                 addr,
                 reuse_port,
                 reuse_addr,
+                recv_timeout,
+                nonblocking,
                 ..
             } => {
                 match *family {
@@ -197,12 +221,30 @@ impl InodeSocket {
                         // more to do at this time
                         None
                     }
+                    __WASI_SOCK_TYPE_DGRAM | __WASI_SOCK_TYPE_RAW
+                        if *pt == __WASI_SOCK_PROTO_ICMP =>
+                    {
+                        let mut socket =
+                            net.bind_icmp(addr.ip()).map_err(net_error_into_wasi_err)?;
+                        set_recv_options(&mut *socket, *recv_timeout, *nonblocking)?;
+                        Some(InodeSocket::new(InodeSocketKind::Icmp(socket)))
+                    }
                     __WASI_SOCK_TYPE_DGRAM => {
-                        let socket = net
+                        let mut socket = net
                             .bind_udp(addr, *reuse_port, *reuse_addr)
                             .map_err(net_error_into_wasi_err)?;
+                        if let Some(timeout) = *recv_timeout {
+                            socket
+                                .set_recv_timeout(Some(timeout))
+                                .map_err(net_error_into_wasi_err)?;
+                        }
                         Some(InodeSocket::new(InodeSocketKind::UdpSocket(socket)))
                     }
+                    __WASI_SOCK_TYPE_RAW => {
+                        let mut socket = net.bind_raw().map_err(net_error_into_wasi_err)?;
+                        set_recv_options(&mut *socket, *recv_timeout, *nonblocking)?;
+                        Some(InodeSocket::new(InodeSocketKind::Raw(socket)))
+                    }
                     _ => return Err(__WASI_EINVAL),
                 })
             }
@@ -609,6 +651,24 @@ impl InodeSocket {
                 }
                 _ => Err(__WASI_EINVAL),
             },
+            InodeSocketKind::Icmp(sock) => match ty {
+                TimeType::ReadTimeout => sock
+                    .set_recv_timeout(timeout)
+                    .map_err(net_error_into_wasi_err),
+                _ => Err(__WASI_EINVAL),
+            },
+            InodeSocketKind::Raw(sock) => match ty {
+                TimeType::ReadTimeout => sock
+                    .set_recv_timeout(timeout)
+                    .map_err(net_error_into_wasi_err),
+                _ => Err(__WASI_EINVAL),
+            },
+            InodeSocketKind::UdpSocket(sock) => match ty {
+                TimeType::ReadTimeout => sock
+                    .set_recv_timeout(timeout)
+                    .map_err(net_error_into_wasi_err),
+                _ => Err(__WASI_EINVAL),
+            },
             InodeSocketKind::PreSocket {
                 recv_timeout,
                 send_timeout,
@@ -646,6 +706,18 @@ impl InodeSocket {
                 TimeType::AcceptTimeout => sock.timeout().map_err(net_error_into_wasi_err),
                 _ => Err(__WASI_EINVAL),
             },
+            InodeSocketKind::Icmp(sock) => match ty {
+                TimeType::ReadTimeout => sock.recv_timeout().map_err(net_error_into_wasi_err),
+                _ => Err(__WASI_EINVAL),
+            },
+            InodeSocketKind::Raw(sock) => match ty {
+                TimeType::ReadTimeout => sock.recv_timeout().map_err(net_error_into_wasi_err),
+                _ => Err(__WASI_EINVAL),
+            },
+            InodeSocketKind::UdpSocket(sock) => match ty {
+                TimeType::ReadTimeout => sock.recv_timeout().map_err(net_error_into_wasi_err),
+                _ => Err(__WASI_EINVAL),
+            },
             InodeSocketKind::PreSocket {
                 recv_timeout,
                 send_timeout,
@@ -664,6 +736,29 @@ impl InodeSocket {
         }
     }
 
+    /// Makes the receives of the socket fail with `EAGAIN` instead of
+    /// waiting for data, when its file descriptor is nonblocking
+    ///
+    /// Only the ICMP and raw sockets need it, as their receives can wait
+    /// forever in the backend.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<(), __wasi_errno_t> {
+        match &mut self.kind {
+            InodeSocketKind::Icmp(sock) => sock.set_nonblocking(nonblocking),
+            InodeSocketKind::Raw(sock) => sock.set_nonblocking(nonblocking),
+            InodeSocketKind::PreSocket {
+                nonblocking: pre_nonblocking,
+                ..
+            } => {
+                *pre_nonblocking = nonblocking;
+                Ok(())
+            }
+            InodeSocketKind::Closed => return Err(__WASI_EIO),
+            // The other sockets only keep the flag on their file descriptor
+            _ => Ok(()),
+        }
+        .map_err(net_error_into_wasi_err)
+    }
+
     pub fn set_ttl(&mut self, ttl: u32) -> Result<(), __wasi_errno_t> {
         match &mut self.kind {
             InodeSocketKind::TcpStream(sock) => sock.set_ttl(ttl).map_err(net_error_into_wasi_err),
@@ -0,0 +1,1107 @@
+//! strace-style tracing of the syscalls made by a WASI program.
+//!
+//! When a [`SyscallTracer`] is attached to a [`WasiStateBuilder`] every
+//! syscall is printed once it returns, together with its decoded arguments
+//! (paths, file descriptors and flags by name), its errno and how long it
+//! took. Syscalls can be filtered by [`SyscallFamily`] and the output can
+//! either be human readable or JSON lines.
+//!
+//! [`WasiStateBuilder`]: crate::WasiStateBuilder
+
+use crate::syscalls::types::*;
+use crate::{WasiEnv, WasiError, WasiVersion};
+use std::convert::TryInto;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wasmer::{
+    AsStoreMut, Extern, Function, FunctionEnv, FunctionEnvMut, Imports, MemoryView, RuntimeError,
+    Value,
+};
+
+/// Maximum number of bytes of a string or buffer that is printed
+const MAX_PRINTED_BYTES: usize = 64;
+
+/// Group of related syscalls that can be traced together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "sandbox-profile",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum SyscallFamily {
+    /// File and directory operations (`fd_*`, `path_*`, `getcwd`, `chdir`)
+    Fs,
+    /// Clocks (`clock_*`)
+    Clock,
+    /// Random numbers (`random_get`)
+    Random,
+    /// Polling (`poll_oneoff`)
+    Poll,
+    /// Process state (`proc_*`, `args_*`, `environ_*`, `getpid`, ...)
+    Process,
+    /// Threads (`thread_*`)
+    Thread,
+    /// Networking (`sock_*`, `port_*`, `resolve`, `http_*`, `ws_*`)
+    Net,
+    /// Terminal state (`tty_*`)
+    Tty,
+    /// Inter-process bus (`bus_*`, `call_*`)
+    Bus,
+}
+
+impl SyscallFamily {
+    /// All the families of syscalls
+    pub const ALL: [SyscallFamily; 9] = [
+        Self::Fs,
+        Self::Clock,
+        Self::Random,
+        Self::Poll,
+        Self::Process,
+        Self::Thread,
+        Self::Net,
+        Self::Tty,
+        Self::Bus,
+    ];
+
+    /// Returns the family that a syscall belongs to
+    pub fn of(syscall: &str) -> Self {
+        let prefix = syscall.split('_').next().unwrap_or_default();
+        match prefix {
+            "fd" | "path" | "getcwd" | "chdir" => Self::Fs,
+            "clock" => Self::Clock,
+            "random" => Self::Random,
+            "poll" => Self::Poll,
+            "thread" => Self::Thread,
+            "sock" | "port" | "resolve" | "http" | "ws" => Self::Net,
+            "tty" => Self::Tty,
+            "bus" | "call" => Self::Bus,
+            _ => Self::Process,
+        }
+    }
+
+    /// Returns the name of the family
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Fs => "fs",
+            Self::Clock => "clock",
+            Self::Random => "random",
+            Self::Poll => "poll",
+            Self::Process => "process",
+            Self::Thread => "thread",
+            Self::Net => "net",
+            Self::Tty => "tty",
+            Self::Bus => "bus",
+        }
+    }
+}
+
+impl FromStr for SyscallFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|family| family.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                let names = Self::ALL.iter().map(|f| f.name()).collect::<Vec<_>>();
+                format!(
+                    "unknown syscall family `{}` (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// How the traced syscalls are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StraceFormat {
+    /// One human readable line per syscall, similar to `strace`
+    Text,
+    /// One JSON object per line
+    JsonLines,
+}
+
+/// Prints the syscalls made by a WASI program.
+///
+/// Usage:
+/// ```no_run
+/// # use wasmer_wasi::{StraceFormat, SyscallFamily, SyscallTracer, WasiState};
+/// let mut state_builder = WasiState::new("wasi-prog-name");
+/// state_builder.strace(
+///     SyscallTracer::stderr()
+///         .format(StraceFormat::JsonLines)
+///         .families([SyscallFamily::Fs, SyscallFamily::Net]),
+/// );
+/// ```
+pub struct SyscallTracer {
+    output: Mutex<Box<dyn Write + Send + 'static>>,
+    format: StraceFormat,
+    families: Option<Vec<SyscallFamily>>,
+    start: Instant,
+}
+
+impl std::fmt::Debug for SyscallTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyscallTracer")
+            .field("format", &self.format)
+            .field("families", &self.families)
+            .finish()
+    }
+}
+
+impl SyscallTracer {
+    /// Creates a tracer that prints all the syscalls as text into `output`
+    pub fn new<W>(output: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            output: Mutex::new(Box::new(output)),
+            format: StraceFormat::Text,
+            families: None,
+            start: Instant::now(),
+        }
+    }
+
+    /// Creates a tracer that prints all the syscalls as text to stderr
+    pub fn stderr() -> Self {
+        Self::new(io::stderr())
+    }
+
+    /// Sets how the syscalls are printed
+    pub fn format(mut self, format: StraceFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Only traces the syscalls of the given families
+    pub fn families<I>(mut self, families: I) -> Self
+    where
+        I: IntoIterator<Item = SyscallFamily>,
+    {
+        self.families = Some(families.into_iter().collect());
+        self
+    }
+
+    /// Returns true if the syscall is traced
+    pub fn is_traced(&self, syscall: &str) -> bool {
+        match self.families.as_ref() {
+            Some(families) => families.contains(&SyscallFamily::of(syscall)),
+            None => true,
+        }
+    }
+
+    fn syscall(
+        &self,
+        ctx: &mut FunctionEnvMut<WasiEnv>,
+        call: &TracedCall,
+        params: &[Value],
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let tid: u32 = ctx.data().current_thread_id().into();
+        let timestamp = self.start.elapsed();
+        let mut args = call.decode_inputs(ctx, params);
+
+        let started = Instant::now();
+        let ret = call.func.call(ctx, params);
+        let duration = started.elapsed();
+
+        let (outcome, ret) = match ret {
+            Ok(rets) => {
+                let errno = match rets.first() {
+                    Some(Value::I32(ret)) if rets.len() == 1 => Some(*ret),
+                    _ => None,
+                };
+                if errno == Some(__WASI_ESUCCESS as i32) {
+                    call.decode_outputs(ctx, params, &mut args);
+                }
+                let outcome = match errno {
+                    Some(errno) if call.returns_errno() => Outcome::Errno(errno),
+                    _ => Outcome::Returned(rets.iter().map(value_to_i128).collect()),
+                };
+                (outcome, Ok(rets.into_vec()))
+            }
+            Err(err) => match err.downcast::<WasiError>() {
+                Ok(WasiError::Exit(code)) => (
+                    Outcome::Exit(code),
+                    Err(RuntimeError::user(Box::new(WasiError::Exit(code)))),
+                ),
+                Ok(err) => (
+                    Outcome::Trap(err.to_string()),
+                    Err(RuntimeError::user(Box::new(err))),
+                ),
+                Err(err) => (Outcome::Trap(err.message()), Err(err)),
+            },
+        };
+
+        let event = TraceEvent {
+            tid,
+            timestamp,
+            name: call.name.as_str(),
+            args,
+            outcome,
+            duration,
+        };
+        let line = match self.format {
+            StraceFormat::Text => event.to_text(),
+            StraceFormat::JsonLines => event.to_json(),
+        };
+        let mut output = self.output.lock().unwrap();
+        // Tracing is best effort, it must never make a syscall fail
+        let _ = writeln!(output, "{}", line).and_then(|_| output.flush());
+        drop(output);
+
+        ret
+    }
+}
+
+/// Wraps the functions of `imports` that are traced by the tracer of the
+/// environment (if it has one)
+pub(crate) fn instrument_imports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    imports: Imports,
+) -> Imports {
+    let tracer = match env.as_ref(store).tracer.clone() {
+        Some(tracer) => tracer,
+        None => return imports,
+    };
+
+    let mut ret = Imports::new();
+    for ((namespace, name), ext) in &imports {
+        let ext = match ext {
+            Extern::Function(func) if tracer.is_traced(name.as_str()) => {
+                let call = TracedCall {
+                    args: syscall_args(name.as_str()),
+                    memory64: namespace == WasiVersion::Wasix64v1.get_namespace_str(),
+                    name: name.clone(),
+                    func,
+                };
+                let tracer = tracer.clone();
+                let ty = call.func.ty(store);
+                Extern::Function(Function::new_with_env(
+                    store,
+                    env,
+                    ty,
+                    move |mut ctx, params| tracer.syscall(&mut ctx, &call, params),
+                ))
+            }
+            ext => ext,
+        };
+        ret.define(&namespace, &name, ext);
+    }
+    ret
+}
+
+/// A syscall that goes through the tracer
+struct TracedCall {
+    name: String,
+    func: Function,
+    args: Option<&'static [(&'static str, Arg)]>,
+    memory64: bool,
+}
+
+impl TracedCall {
+    /// The bus functions return their own error codes
+    fn returns_errno(&self) -> bool {
+        SyscallFamily::of(self.name.as_str()) != SyscallFamily::Bus
+    }
+
+    fn decode_inputs(&self, ctx: &FunctionEnvMut<WasiEnv>, params: &[Value]) -> Vec<TraceArg> {
+        let args = match self.args {
+            Some(args) if args.len() == params.len() => args,
+            // Unknown syscalls are printed as raw numbers
+            _ => {
+                return params
+                    .iter()
+                    .enumerate()
+                    .map(|(n, value)| TraceArg {
+                        name: format!("arg{}", n),
+                        value: Rendered::Num(value_to_i128(value)),
+                    })
+                    .collect()
+            }
+        };
+
+        let memory = ctx.data().memory_clone();
+        let view = memory.as_ref().map(|memory| memory.view(ctx));
+        let raw = |idx: usize| params.get(idx).map(value_to_u64).unwrap_or_default();
+        args.iter()
+            .zip(params.iter())
+            .map(|((name, arg), value)| {
+                let bits = value_to_u64(value);
+                let value = match *arg {
+                    Arg::Int => Rendered::Num(value_to_i128(value)),
+                    Arg::Fd => Rendered::Num(bits as i128),
+                    Arg::Ptr | Arg::Out(_) => Rendered::Name(format!("{:#x}", bits)),
+                    Arg::Hex => Rendered::Name(format!("{:#x}", bits)),
+                    Arg::Str(len) => match view.as_ref() {
+                        Some(view) => read_str(view, bits, raw(len)),
+                        None => Rendered::Name(format!("{:#x}", bits)),
+                    },
+                    Arg::Iovs(len) => match view.as_ref() {
+                        Some(view) => read_iovs(view, bits, raw(len), self.memory64),
+                        None => Rendered::Name(format!("{:#x}", bits)),
+                    },
+                    Arg::Flags(names) => Rendered::Name(flags_to_string(bits, names)),
+                    Arg::Enum(names) => Rendered::Name(enum_to_string(bits, names)),
+                    Arg::Rights => Rendered::Name(rights_to_string(bits)),
+                };
+                TraceArg {
+                    name: name.to_string(),
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    fn decode_outputs(
+        &self,
+        ctx: &FunctionEnvMut<WasiEnv>,
+        params: &[Value],
+        args: &mut [TraceArg],
+    ) {
+        let decl = match self.args {
+            Some(decl) if decl.len() == params.len() => decl,
+            _ => return,
+        };
+        let memory = match ctx.data().memory_clone() {
+            Some(memory) => memory,
+            None => return,
+        };
+        let view = memory.view(ctx);
+        for (((_, arg), value), trace) in decl.iter().zip(params.iter()).zip(args.iter_mut()) {
+            let size = match *arg {
+                Arg::Out(OutSize::U32) => 4,
+                Arg::Out(OutSize::U64) => 8,
+                Arg::Out(OutSize::Offset) if self.memory64 => 8,
+                Arg::Out(OutSize::Offset) => 4,
+                _ => continue,
+            };
+            let mut buf = [0u8; 8];
+            if view.read(value_to_u64(value), &mut buf[..size]).is_ok() {
+                trace.value = Rendered::Out(u64::from_le_bytes(buf));
+            }
+        }
+    }
+}
+
+/// How an argument of a syscall is decoded
+#[derive(Debug, Clone, Copy)]
+enum Arg {
+    /// A number
+    Int,
+    /// A file descriptor
+    Fd,
+    /// A pointer
+    Ptr,
+    /// A number that is best read in hexadecimal
+    Hex,
+    /// A string, whose length is the argument at the given index
+    Str(usize),
+    /// An array of `ciovec`s, whose length is the argument at the given index
+    Iovs(usize),
+    /// A set of flags
+    Flags(&'static [(u64, &'static str)]),
+    /// One of a set of values
+    Enum(&'static [(u64, &'static str)]),
+    /// A set of rights
+    Rights,
+    /// A pointer to a value the syscall returns
+    Out(OutSize),
+}
+
+/// Size of a value that is returned by a syscall through a pointer
+#[derive(Debug, Clone, Copy)]
+enum OutSize {
+    U32,
+    U64,
+    Offset,
+}
+
+const FD_FLAGS: &[(u64, &str)] = &[
+    (__WASI_FDFLAG_APPEND as u64, "APPEND"),
+    (__WASI_FDFLAG_DSYNC as u64, "DSYNC"),
+    (__WASI_FDFLAG_NONBLOCK as u64, "NONBLOCK"),
+    (__WASI_FDFLAG_RSYNC as u64, "RSYNC"),
+    (__WASI_FDFLAG_SYNC as u64, "SYNC"),
+];
+const O_FLAGS: &[(u64, &str)] = &[
+    (__WASI_O_CREAT as u64, "CREAT"),
+    (__WASI_O_DIRECTORY as u64, "DIRECTORY"),
+    (__WASI_O_EXCL as u64, "EXCL"),
+    (__WASI_O_TRUNC as u64, "TRUNC"),
+];
+const LOOKUP_FLAGS: &[(u64, &str)] = &[(__WASI_LOOKUP_SYMLINK_FOLLOW as u64, "SYMLINK_FOLLOW")];
+const FST_FLAGS: &[(u64, &str)] = &[
+    (__WASI_FILESTAT_SET_ATIM as u64, "ATIM"),
+    (__WASI_FILESTAT_SET_ATIM_NOW as u64, "ATIM_NOW"),
+    (__WASI_FILESTAT_SET_MTIM as u64, "MTIM"),
+    (__WASI_FILESTAT_SET_MTIM_NOW as u64, "MTIM_NOW"),
+];
+const SHUT_FLAGS: &[(u64, &str)] = &[(__WASI_SHUT_RD as u64, "RD"), (__WASI_SHUT_WR as u64, "WR")];
+const WHENCE: &[(u64, &str)] = &[
+    (__WASI_WHENCE_SET as u64, "SET"),
+    (__WASI_WHENCE_CUR as u64, "CUR"),
+    (__WASI_WHENCE_END as u64, "END"),
+];
+const CLOCKS: &[(u64, &str)] = &[
+    (__WASI_CLOCK_REALTIME as u64, "REALTIME"),
+    (__WASI_CLOCK_MONOTONIC as u64, "MONOTONIC"),
+    (__WASI_CLOCK_PROCESS_CPUTIME_ID as u64, "PROCESS_CPUTIME_ID"),
+    (__WASI_CLOCK_THREAD_CPUTIME_ID as u64, "THREAD_CPUTIME_ID"),
+];
+const ADVICE: &[(u64, &str)] = &[
+    (__WASI_ADVICE_NORMAL as u64, "NORMAL"),
+    (__WASI_ADVICE_SEQUENTIAL as u64, "SEQUENTIAL"),
+    (__WASI_ADVICE_RANDOM as u64, "RANDOM"),
+    (__WASI_ADVICE_WILLNEED as u64, "WILLNEED"),
+    (__WASI_ADVICE_DONTNEED as u64, "DONTNEED"),
+    (__WASI_ADVICE_NOREUSE as u64, "NOREUSE"),
+];
+const ADDRESS_FAMILIES: &[(u64, &str)] = &[
+    (__WASI_ADDRESS_FAMILY_UNSPEC as u64, "UNSPEC"),
+    (__WASI_ADDRESS_FAMILY_INET4 as u64, "INET4"),
+    (__WASI_ADDRESS_FAMILY_INET6 as u64, "INET6"),
+    (__WASI_ADDRESS_FAMILY_UNIX as u64, "UNIX"),
+];
+const SOCK_TYPES: &[(u64, &str)] = &[
+    (__WASI_SOCK_TYPE_DGRAM as u64, "DGRAM"),
+    (__WASI_SOCK_TYPE_STREAM as u64, "STREAM"),
+    (__WASI_SOCK_TYPE_RAW as u64, "RAW"),
+    (__WASI_SOCK_TYPE_SEQPACKET as u64, "SEQPACKET"),
+];
+
+/// Returns the names and the decoding of the arguments of a syscall, or
+/// `None` if the syscall is not known
+fn syscall_args(name: &str) -> Option<&'static [(&'static str, Arg)]> {
+    use Arg::*;
+    const FD: (&str, Arg) = ("fd", Fd);
+    const OUT_U32: Arg = Out(OutSize::U32);
+    const OUT_U64: Arg = Out(OutSize::U64);
+    const OUT_SIZE: Arg = Out(OutSize::Offset);
+    Some(match name {
+        "args_get" => &[("argv", Ptr), ("argv_buf", Ptr)],
+        "args_sizes_get" => &[("argc", OUT_SIZE), ("argv_buf_size", OUT_SIZE)],
+        "environ_get" => &[("environ", Ptr), ("environ_buf", Ptr)],
+        "environ_sizes_get" => &[("environ_count", OUT_SIZE), ("environ_buf_size", OUT_SIZE)],
+        "clock_res_get" => &[("clock_id", Enum(CLOCKS)), ("resolution", OUT_U64)],
+        "clock_time_get" => &[
+            ("clock_id", Enum(CLOCKS)),
+            ("precision", Int),
+            ("time", OUT_U64),
+        ],
+        "fd_advise" => &[FD, ("offset", Int), ("len", Int), ("advice", Enum(ADVICE))],
+        "fd_allocate" => &[FD, ("offset", Int), ("len", Int)],
+        "fd_close" | "fd_datasync" | "fd_sync" => &[FD],
+        "fd_fdstat_get" | "fd_filestat_get" | "fd_prestat_get" => &[FD, ("buf", Ptr)],
+        "fd_fdstat_set_flags" => &[FD, ("flags", Flags(FD_FLAGS))],
+        "fd_fdstat_set_rights" => &[
+            FD,
+            ("fs_rights_base", Rights),
+            ("fs_rights_inheriting", Rights),
+        ],
+        "fd_filestat_set_size" => &[FD, ("st_size", Int)],
+        "fd_filestat_set_times" => &[
+            FD,
+            ("st_atim", Int),
+            ("st_mtim", Int),
+            ("fst_flags", Flags(FST_FLAGS)),
+        ],
+        "fd_pread" => &[
+            FD,
+            ("iovs", Ptr),
+            ("iovs_len", Int),
+            ("offset", Int),
+            ("nread", OUT_SIZE),
+        ],
+        "fd_prestat_dir_name" => &[FD, ("path", Ptr), ("path_len", Int)],
+        "fd_pwrite" => &[
+            FD,
+            ("iovs", Iovs(2)),
+            ("iovs_len", Int),
+            ("offset", Int),
+            ("nwritten", OUT_SIZE),
+        ],
+        "fd_read" => &[FD, ("iovs", Ptr), ("iovs_len", Int), ("nread", OUT_SIZE)],
+        "fd_readdir" => &[
+            FD,
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("cookie", Int),
+            ("bufused", OUT_SIZE),
+        ],
+        "fd_renumber" => &[("from", Fd), ("to", Fd)],
+        "fd_dup" => &[FD, ("ret_fd", OUT_U32)],
+        "fd_event" => &[("initial_val", Int), ("flags", Hex), ("ret_fd", OUT_U32)],
+        "fd_seek" => &[
+            FD,
+            ("offset", Int),
+            ("whence", Enum(WHENCE)),
+            ("newoffset", OUT_U64),
+        ],
+        "fd_tell" => &[FD, ("offset", OUT_U64)],
+        "fd_write" => &[
+            FD,
+            ("iovs", Iovs(2)),
+            ("iovs_len", Int),
+            ("nwritten", OUT_SIZE),
+        ],
+        "fd_pipe" => &[("ro_fd1", OUT_U32), ("ro_fd2", OUT_U32)],
+        "path_create_directory" | "path_remove_directory" | "path_unlink_file" => {
+            &[FD, ("path", Str(2)), ("path_len", Int)]
+        }
+        "path_filestat_get" => &[
+            FD,
+            ("flags", Flags(LOOKUP_FLAGS)),
+            ("path", Str(3)),
+            ("path_len", Int),
+            ("buf", Ptr),
+        ],
+        "path_filestat_set_times" => &[
+            FD,
+            ("flags", Flags(LOOKUP_FLAGS)),
+            ("path", Str(3)),
+            ("path_len", Int),
+            ("st_atim", Int),
+            ("st_mtim", Int),
+            ("fst_flags", Flags(FST_FLAGS)),
+        ],
+        "path_link" => &[
+            ("old_fd", Fd),
+            ("old_flags", Flags(LOOKUP_FLAGS)),
+            ("old_path", Str(3)),
+            ("old_path_len", Int),
+            ("new_fd", Fd),
+            ("new_path", Str(6)),
+            ("new_path_len", Int),
+        ],
+        "path_open" => &[
+            ("dirfd", Fd),
+            ("dirflags", Flags(LOOKUP_FLAGS)),
+            ("path", Str(3)),
+            ("path_len", Int),
+            ("o_flags", Flags(O_FLAGS)),
+            ("fs_rights_base", Rights),
+            ("fs_rights_inheriting", Rights),
+            ("fs_flags", Flags(FD_FLAGS)),
+            ("fd", OUT_U32),
+        ],
+        "path_readlink" => &[
+            ("dir_fd", Fd),
+            ("path", Str(2)),
+            ("path_len", Int),
+            ("buf", Ptr),
+            ("buf_len", Int),
+            ("buf_used", OUT_SIZE),
+        ],
+        "path_rename" => &[
+            ("old_fd", Fd),
+            ("old_path", Str(2)),
+            ("old_path_len", Int),
+            ("new_fd", Fd),
+            ("new_path", Str(5)),
+            ("new_path_len", Int),
+        ],
+        "path_symlink" => &[
+            ("old_path", Str(1)),
+            ("old_path_len", Int),
+            FD,
+            ("new_path", Str(4)),
+            ("new_path_len", Int),
+        ],
+        "poll_oneoff" => &[
+            ("in", Ptr),
+            ("out", Ptr),
+            ("nsubscriptions", Int),
+            ("nevents", OUT_SIZE),
+        ],
+        "proc_exit" | "thread_exit" => &[("code", Int)],
+        "proc_raise" => &[("sig", Int)],
+        "sched_yield" => &[],
+        "random_get" => &[("buf", Ptr), ("buf_len", Int)],
+        "tty_get" | "tty_set" => &[("tty_state", Ptr)],
+        "getcwd" => &[("path", Ptr), ("path_len", OUT_SIZE)],
+        "chdir" => &[("path", Str(1)), ("path_len", Int)],
+        "getpid" => &[("ret_pid", OUT_U32)],
+        "thread_spawn" => &[
+            ("method", Str(1)),
+            ("method_len", Int),
+            ("user_data", Int),
+            ("reactor", Int),
+            ("ret_tid", OUT_U32),
+        ],
+        "thread_sleep" => &[("duration", Int)],
+        "thread_id" => &[("ret_tid", OUT_U32)],
+        "thread_join" => &[("tid", Int)],
+        "thread_parallelism" => &[("ret_parallelism", OUT_SIZE)],
+        "sock_open" => &[
+            ("af", Enum(ADDRESS_FAMILIES)),
+            ("ty", Enum(SOCK_TYPES)),
+            ("pt", Int),
+            ("ro_sock", OUT_U32),
+        ],
+        "sock_status" | "sock_addr_local" | "sock_addr_peer" | "sock_bind" | "sock_connect" => {
+            &[("sock", Fd), ("addr", Ptr)]
+        }
+        "sock_set_opt_flag" | "sock_set_opt_size" => &[("sock", Fd), ("opt", Int), ("value", Int)],
+        "sock_get_opt_flag" | "sock_get_opt_time" | "sock_set_opt_time" | "sock_get_opt_size" => {
+            &[("sock", Fd), ("opt", Int), ("value", Ptr)]
+        }
+        "sock_listen" => &[("sock", Fd), ("backlog", Int)],
+        "sock_accept" => &[
+            ("sock", Fd),
+            ("fd_flags", Flags(FD_FLAGS)),
+            ("ro_fd", OUT_U32),
+            ("ro_addr", Ptr),
+        ],
+        "sock_recv" => &[
+            ("sock", Fd),
+            ("ri_data", Ptr),
+            ("ri_data_len", Int),
+            ("ri_flags", Hex),
+            ("ro_data_len", OUT_SIZE),
+            ("ro_flags", Ptr),
+        ],
+        "sock_recv_from" => &[
+            ("sock", Fd),
+            ("ri_data", Ptr),
+            ("ri_data_len", Int),
+            ("ri_flags", Hex),
+            ("ro_data_len", OUT_SIZE),
+            ("ro_flags", Ptr),
+            ("ro_addr", Ptr),
+        ],
+        "sock_send" => &[
+            ("sock", Fd),
+            ("si_data", Iovs(2)),
+            ("si_data_len", Int),
+            ("si_flags", Hex),
+            ("ret_data_len", OUT_SIZE),
+        ],
+        "sock_send_to" => &[
+            ("sock", Fd),
+            ("si_data", Iovs(2)),
+            ("si_data_len", Int),
+            ("si_flags", Hex),
+            ("addr", Ptr),
+            ("ret_data_len", OUT_SIZE),
+        ],
+        "sock_send_file" => &[
+            ("sock", Fd),
+            ("in_fd", Fd),
+            ("offset", Int),
+            ("count", Int),
+            ("ret_sent", OUT_U64),
+        ],
+        "sock_shutdown" => &[("sock", Fd), ("how", Flags(SHUT_FLAGS))],
+        "resolve" => &[
+            ("host", Str(1)),
+            ("host_len", Int),
+            ("port", Int),
+            ("addrs", Ptr),
+            ("naddrs", Int),
+            ("ret_naddrs", OUT_SIZE),
+        ],
+        _ => return None,
+    })
+}
+
+fn value_to_u64(value: &Value) -> u64 {
+    match *value {
+        Value::I32(v) => v as u32 as u64,
+        Value::I64(v) => v as u64,
+        Value::F32(v) => v.to_bits() as u64,
+        Value::F64(v) => v.to_bits(),
+        _ => 0,
+    }
+}
+
+fn value_to_i128(value: &Value) -> i128 {
+    match *value {
+        Value::I32(v) => v as i128,
+        Value::I64(v) => v as i128,
+        _ => value_to_u64(value) as i128,
+    }
+}
+
+fn read_str(view: &MemoryView, ptr: u64, len: u64) -> Rendered {
+    let shown = len.min(MAX_PRINTED_BYTES as u64) as usize;
+    let mut buf = vec![0u8; shown];
+    match view.read(ptr, &mut buf[..]) {
+        Ok(()) => Rendered::Str(
+            String::from_utf8_lossy(&buf[..]).into_owned(),
+            shown as u64 != len,
+        ),
+        Err(_) => Rendered::Name(format!("{:#x}", ptr)),
+    }
+}
+
+fn read_iovs(view: &MemoryView, iovs: u64, count: u64, memory64: bool) -> Rendered {
+    let ptr_size = if memory64 { 8 } else { 4 };
+    let read_ptr = |offset: u64| -> Option<u64> {
+        let mut buf = [0u8; 8];
+        view.read(offset, &mut buf[..ptr_size as usize]).ok()?;
+        Some(u64::from_le_bytes(buf))
+    };
+
+    let mut data = Vec::new();
+    let mut truncated = false;
+    for n in 0..count {
+        let iov = iovs + n * 2 * ptr_size;
+        let (buf, len) = match (read_ptr(iov), read_ptr(iov + ptr_size)) {
+            (Some(buf), Some(len)) => (buf, len),
+            _ => return Rendered::Name(format!("{:#x}", iovs)),
+        };
+        let room = (MAX_PRINTED_BYTES - data.len()) as u64;
+        if len > room {
+            truncated = true;
+        }
+        let mut chunk = vec![0u8; len.min(room) as usize];
+        if view.read(buf, &mut chunk[..]).is_err() {
+            return Rendered::Name(format!("{:#x}", iovs));
+        }
+        data.extend_from_slice(&chunk[..]);
+        if truncated {
+            break;
+        }
+    }
+    Rendered::Str(String::from_utf8_lossy(&data[..]).into_owned(), truncated)
+}
+
+fn flags_to_string(bits: u64, names: &[(u64, &str)]) -> String {
+    if bits == 0 {
+        return "0".to_string();
+    }
+    let mut rest = bits;
+    let mut parts = Vec::new();
+    for (flag, name) in names {
+        if bits & flag != 0 {
+            parts.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        parts.push(format!("{:#x}", rest));
+    }
+    parts.join("|")
+}
+
+fn enum_to_string(value: u64, names: &[(u64, &str)]) -> String {
+    names
+        .iter()
+        .find(|(v, _)| *v == value)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| value.to_string())
+}
+
+fn rights_to_string(bits: u64) -> String {
+    if bits == crate::ALL_RIGHTS {
+        return "ALL".to_string();
+    }
+    let names = (0..64)
+        .map(|n| 1u64 << n)
+        .filter(|right| bits & right != 0)
+        .map(|right| match right_to_string(right) {
+            Some(name) => name.trim_start_matches("__WASI_RIGHT_").to_string(),
+            None => format!("{:#x}", right),
+        })
+        .collect::<Vec<_>>();
+    match names.is_empty() {
+        true => "0".to_string(),
+        false => names.join("|"),
+    }
+}
+
+/// Returns the name of a WASI error code
+fn errno_name(errno: i32) -> Option<&'static str> {
+    let errno: __wasi_errno_t = errno.try_into().ok()?;
+    Some(match errno {
+        __WASI_ESUCCESS => "ESUCCESS",
+        __WASI_E2BIG => "E2BIG",
+        __WASI_EACCES => "EACCES",
+        __WASI_EADDRINUSE => "EADDRINUSE",
+        __WASI_EADDRNOTAVAIL => "EADDRNOTAVAIL",
+        __WASI_EAFNOSUPPORT => "EAFNOSUPPORT",
+        __WASI_EAGAIN => "EAGAIN",
+        __WASI_EALREADY => "EALREADY",
+        __WASI_EBADF => "EBADF",
+        __WASI_EBADMSG => "EBADMSG",
+        __WASI_EBUSY => "EBUSY",
+        __WASI_ECANCELED => "ECANCELED",
+        __WASI_ECHILD => "ECHILD",
+        __WASI_ECONNABORTED => "ECONNABORTED",
+        __WASI_ECONNREFUSED => "ECONNREFUSED",
+        __WASI_ECONNRESET => "ECONNRESET",
+        __WASI_EDEADLK => "EDEADLK",
+        __WASI_EDESTADDRREQ => "EDESTADDRREQ",
+        __WASI_EDOM => "EDOM",
+        __WASI_EDQUOT => "EDQUOT",
+        __WASI_EEXIST => "EEXIST",
+        __WASI_EFAULT => "EFAULT",
+        __WASI_EFBIG => "EFBIG",
+        __WASI_EHOSTUNREACH => "EHOSTUNREACH",
+        __WASI_EIDRM => "EIDRM",
+        __WASI_EILSEQ => "EILSEQ",
+        __WASI_EINPROGRESS => "EINPROGRESS",
+        __WASI_EINTR => "EINTR",
+        __WASI_EINVAL => "EINVAL",
+        __WASI_EIO => "EIO",
+        __WASI_EISCONN => "EISCONN",
+        __WASI_EISDIR => "EISDIR",
+        __WASI_ELOOP => "ELOOP",
+        __WASI_EMFILE => "EMFILE",
+        __WASI_EMLINK => "EMLINK",
+        __WASI_EMSGSIZE => "EMSGSIZE",
+        __WASI_EMULTIHOP => "EMULTIHOP",
+        __WASI_ENAMETOOLONG => "ENAMETOOLONG",
+        __WASI_ENETDOWN => "ENETDOWN",
+        __WASI_ENETRESET => "ENETRESET",
+        __WASI_ENETUNREACH => "ENETUNREACH",
+        __WASI_ENFILE => "ENFILE",
+        __WASI_ENOBUFS => "ENOBUFS",
+        __WASI_ENODEV => "ENODEV",
+        __WASI_ENOENT => "ENOENT",
+        __WASI_ENOEXEC => "ENOEXEC",
+        __WASI_ENOLCK => "ENOLCK",
+        __WASI_ENOLINK => "ENOLINK",
+        __WASI_ENOMEM => "ENOMEM",
+        __WASI_ENOMSG => "ENOMSG",
+        __WASI_ENOPROTOOPT => "ENOPROTOOPT",
+        __WASI_ENOSPC => "ENOSPC",
+        __WASI_ENOSYS => "ENOSYS",
+        __WASI_ENOTCONN => "ENOTCONN",
+        __WASI_ENOTDIR => "ENOTDIR",
+        __WASI_ENOTEMPTY => "ENOTEMPTY",
+        __WASI_ENOTRECOVERABLE => "ENOTRECOVERABLE",
+        __WASI_ENOTSOCK => "ENOTSOCK",
+        __WASI_ENOTSUP => "ENOTSUP",
+        __WASI_ENOTTY => "ENOTTY",
+        __WASI_ENXIO => "ENXIO",
+        __WASI_EOVERFLOW => "EOVERFLOW",
+        __WASI_EOWNERDEAD => "EOWNERDEAD",
+        __WASI_EPERM => "EPERM",
+        __WASI_EPIPE => "EPIPE",
+        __WASI_EPROTO => "EPROTO",
+        __WASI_EPROTONOSUPPORT => "EPROTONOSUPPORT",
+        __WASI_EPROTOTYPE => "EPROTOTYPE",
+        __WASI_ERANGE => "ERANGE",
+        __WASI_EROFS => "EROFS",
+        __WASI_ESPIPE => "ESPIPE",
+        __WASI_ESRCH => "ESRCH",
+        __WASI_ESTALE => "ESTALE",
+        __WASI_ETIMEDOUT => "ETIMEDOUT",
+        __WASI_ETXTBSY => "ETXTBSY",
+        __WASI_EXDEV => "EXDEV",
+        __WASI_ENOTCAPABLE => "ENOTCAPABLE",
+        _ => return None,
+    })
+}
+
+/// A decoded argument
+#[derive(Debug, Clone, PartialEq)]
+enum Rendered {
+    /// A number
+    Num(i128),
+    /// A symbolic value (flags, pointers, ...)
+    Name(String),
+    /// A string and whether it was truncated
+    Str(String, bool),
+    /// A value that the syscall returned through a pointer
+    Out(u64),
+}
+
+#[derive(Debug)]
+struct TraceArg {
+    name: String,
+    value: Rendered,
+}
+
+/// How a syscall finished
+#[derive(Debug)]
+enum Outcome {
+    Errno(i32),
+    Returned(Vec<i128>),
+    Exit(__wasi_exitcode_t),
+    Trap(String),
+}
+
+/// A syscall that has been made by the program
+struct TraceEvent<'a> {
+    tid: u32,
+    timestamp: Duration,
+    name: &'a str,
+    args: Vec<TraceArg>,
+    outcome: Outcome,
+    duration: Duration,
+}
+
+impl TraceEvent<'_> {
+    fn to_text(&self) -> String {
+        let mut line = String::new();
+        if self.tid != 0 {
+            let _ = write!(line, "[tid {}] ", self.tid);
+        }
+        let args = self
+            .args
+            .iter()
+            .map(|arg| match &arg.value {
+                Rendered::Num(v) => v.to_string(),
+                Rendered::Name(name) => name.clone(),
+                Rendered::Str(s, false) => format!("{:?}", s),
+                Rendered::Str(s, true) => format!("{:?}...", s),
+                Rendered::Out(v) => format!("[{}]", v),
+            })
+            .collect::<Vec<_>>();
+        let _ = write!(line, "{}({}) = ", self.name, args.join(", "));
+        let _ = match &self.outcome {
+            Outcome::Errno(0) => write!(line, "0"),
+            Outcome::Errno(errno) => match errno_name(*errno) {
+                Some(name) => write!(line, "{} {}", errno, name),
+                None => write!(line, "{}", errno),
+            },
+            Outcome::Returned(rets) if rets.len() == 1 => write!(line, "{}", rets[0]),
+            Outcome::Returned(rets) if rets.is_empty() => write!(line, "?"),
+            Outcome::Returned(rets) => write!(line, "{:?}", rets),
+            Outcome::Exit(code) => write!(line, "? <exit {}>", code),
+            Outcome::Trap(message) => write!(line, "? <trap: {}>", message),
+        };
+        let _ = write!(line, " <{:.6}>", self.duration.as_secs_f64());
+        line
+    }
+
+    fn to_json(&self) -> String {
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "{{\"tid\":{},\"time_ns\":{},\"syscall\":{},\"family\":{},\"args\":{{",
+            self.tid,
+            self.timestamp.as_nanos(),
+            json_string(self.name),
+            json_string(SyscallFamily::of(self.name).name()),
+        );
+        for (n, arg) in self.args.iter().enumerate() {
+            if n > 0 {
+                line.push(',');
+            }
+            let value = match &arg.value {
+                Rendered::Num(v) => v.to_string(),
+                Rendered::Out(v) => v.to_string(),
+                Rendered::Name(name) => json_string(name),
+                Rendered::Str(s, _) => json_string(s),
+            };
+            let _ = write!(line, "{}:{}", json_string(&arg.name), value);
+        }
+        line.push('}');
+        let _ = match &self.outcome {
+            Outcome::Errno(errno) => match errno_name(*errno) {
+                Some(name) => write!(
+                    line,
+                    ",\"result\":{},\"errno\":{}",
+                    errno,
+                    json_string(name)
+                ),
+                None => write!(line, ",\"result\":{}", errno),
+            },
+            Outcome::Returned(rets) => write!(line, ",\"result\":{:?}", rets),
+            Outcome::Exit(code) => write!(line, ",\"exit\":{}", code),
+            Outcome::Trap(message) => write!(line, ",\"trap\":{}", json_string(message)),
+        };
+        let _ = write!(line, ",\"duration_ns\":{}}}", self.duration.as_nanos());
+        line
+    }
+}
+
+/// Quotes and escapes a string for JSON
+fn json_string(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(ret, "\\u{:04x}", c as u32);
+            }
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syscall_families() {
+        assert_eq!(SyscallFamily::of("path_open"), SyscallFamily::Fs);
+        assert_eq!(SyscallFamily::of("fd_write"), SyscallFamily::Fs);
+        assert_eq!(SyscallFamily::of("sock_recv_from"), SyscallFamily::Net);
+        assert_eq!(SyscallFamily::of("args_sizes_get"), SyscallFamily::Process);
+        assert_eq!(SyscallFamily::of("sched_yield"), SyscallFamily::Process);
+        for family in SyscallFamily::ALL {
+            assert_eq!(family.name().parse::<SyscallFamily>(), Ok(family));
+        }
+        assert!("files".parse::<SyscallFamily>().is_err());
+    }
+
+    #[test]
+    fn render_values() {
+        assert_eq!(
+            flags_to_string((__WASI_O_CREAT | __WASI_O_TRUNC) as u64 | 0x40, O_FLAGS),
+            "CREAT|TRUNC|0x40"
+        );
+        assert_eq!(flags_to_string(0, O_FLAGS), "0");
+        assert_eq!(enum_to_string(2, WHENCE), "END");
+        assert_eq!(
+            rights_to_string(__WASI_RIGHT_FD_READ | __WASI_RIGHT_FD_WRITE),
+            "FD_READ|FD_WRITE"
+        );
+        assert_eq!(errno_name(__WASI_ENOENT as i32), Some("ENOENT"));
+        assert_eq!(json_string("a\"b\n\u{1}"), "\"a\\\"b\\n\\u0001\"");
+    }
+
+    #[test]
+    fn event_formats() {
+        let event = TraceEvent {
+            tid: 0,
+            timestamp: Duration::from_nanos(10),
+            name: "path_open",
+            args: vec![
+                TraceArg {
+                    name: "dirfd".to_string(),
+                    value: Rendered::Num(3),
+                },
+                TraceArg {
+                    name: "path".to_string(),
+                    value: Rendered::Str("hello.txt".to_string(), false),
+                },
+                TraceArg {
+                    name: "fd".to_string(),
+                    value: Rendered::Name("0x10".to_string()),
+                },
+            ],
+            outcome: Outcome::Errno(__WASI_ENOENT as i32),
+            duration: Duration::from_micros(12),
+        };
+        assert_eq!(
+            event.to_text(),
+            "path_open(3, \"hello.txt\", 0x10) = 44 ENOENT <0.000012>"
+        );
+        assert_eq!(
+            event.to_json(),
+            "{\"tid\":0,\"time_ns\":10,\"syscall\":\"path_open\",\"family\":\"fs\",\
+             \"args\":{\"dirfd\":3,\"path\":\"hello.txt\",\"fd\":\"0x10\"},\
+             \"result\":44,\"errno\":\"ENOENT\",\"duration_ns\":12000}"
+        );
+    }
+}
@@ -283,7 +283,12 @@ fn write_buffer_array<M: MemorySize>(
     __WASI_ESUCCESS
 }
 
-fn get_current_time_in_nanos() -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+/// Returns the current time, read from the virtual clock when the program
+/// runs deterministically
+fn get_current_time_in_nanos(env: &WasiEnv) -> Result<__wasi_timestamp_t, __wasi_errno_t> {
+    if let Some(deterministic) = env.deterministic.as_ref() {
+        return deterministic.clock.time_get(__WASI_CLOCK_REALTIME);
+    }
     let now = std::time::SystemTime::now();
     let duration = now
         .duration_since(std::time::SystemTime::UNIX_EPOCH)
@@ -374,8 +379,11 @@ pub fn clock_res_get<M: MemorySize>(
     let memory = env.memory_view(&ctx);
 
     let out_addr = resolution.deref(&memory);
-    let t_out = wasi_try!(platform_clock_res_get(clock_id, out_addr));
-    wasi_try_mem!(resolution.write(&memory, t_out as __wasi_timestamp_t));
+    let t_out = match env.deterministic.as_ref() {
+        Some(deterministic) => wasi_try!(deterministic.clock.res_get(clock_id)),
+        None => wasi_try!(platform_clock_res_get(clock_id, out_addr)) as __wasi_timestamp_t,
+    };
+    wasi_try_mem!(resolution.write(&memory, t_out));
     __WASI_ESUCCESS
 }
 
@@ -402,8 +410,11 @@ pub fn clock_time_get<M: MemorySize>(
     let env = ctx.data();
     let memory = env.memory_view(&ctx);
 
-    let t_out = wasi_try!(platform_clock_time_get(clock_id, precision));
-    wasi_try_mem!(time.write(&memory, t_out as __wasi_timestamp_t));
+    let t_out = match env.deterministic.as_ref() {
+        Some(deterministic) => wasi_try!(deterministic.clock.time_get(clock_id)),
+        None => wasi_try!(platform_clock_time_get(clock_id, precision)) as __wasi_timestamp_t,
+    };
+    wasi_try_mem!(time.write(&memory, t_out));
 
     let result = __WASI_ESUCCESS;
     trace!(
@@ -636,7 +647,7 @@ pub fn fd_fdstat_set_flags(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_fdstat_set_flags");
     let env = ctx.data();
-    let (_, mut state) = env.get_memory_and_wasi_state(&ctx, 0);
+    let (_, state, inodes) = env.get_memory_and_wasi_state_and_inodes(&ctx, 0);
     let mut fd_map = state.fs.fd_map.write().unwrap();
     let fd_entry = wasi_try!(fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
 
@@ -644,6 +655,11 @@ pub fn fd_fdstat_set_flags(
         return __WASI_EACCES;
     }
 
+    // ICMP and raw sockets wait for data in their backend, which needs to know the flag
+    if let Kind::Socket { socket } = inodes.arena[fd_entry.inode].write().deref_mut() {
+        wasi_try!(socket.set_nonblocking(flags & __WASI_FDFLAG_NONBLOCK != 0));
+    }
+
     fd_entry.flags = flags;
     __WASI_ESUCCESS
 }
@@ -798,7 +814,7 @@ pub fn fd_filestat_set_times(
         let time_to_set = if fst_flags & __WASI_FILESTAT_SET_ATIM != 0 {
             st_atim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_atim = time_to_set;
     }
@@ -807,7 +823,7 @@ pub fn fd_filestat_set_times(
         let time_to_set = if fst_flags & __WASI_FILESTAT_SET_MTIM != 0 {
             st_mtim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_mtim = time_to_set;
     }
@@ -2107,7 +2123,7 @@ pub fn path_filestat_set_times<M: MemorySize>(
         let time_to_set = if fst_flags & __WASI_FILESTAT_SET_ATIM != 0 {
             st_atim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_atim = time_to_set;
     }
@@ -2115,7 +2131,7 @@ pub fn path_filestat_set_times<M: MemorySize>(
         let time_to_set = if fst_flags & __WASI_FILESTAT_SET_MTIM != 0 {
             st_mtim
         } else {
-            wasi_try!(get_current_time_in_nanos())
+            wasi_try!(get_current_time_in_nanos(env))
         };
         inode.stat.write().unwrap().st_mtim = time_to_set;
     }
@@ -3078,6 +3094,14 @@ pub fn poll_oneoff<M: MemorySize>(
                     // this is a hack
                     // TODO: do this properly
                     time_to_sleep = Duration::from_nanos(clock_info.timeout);
+                    if let Some(deterministic) = env.deterministic.as_ref() {
+                        if clock_info.flags & __WASI_SUBSCRIPTION_CLOCK_ABSTIME != 0 {
+                            let now =
+                                wasi_try_ok!(deterministic.clock.time_get(clock_info.clock_id));
+                            time_to_sleep =
+                                Duration::from_nanos(clock_info.timeout.saturating_sub(now));
+                        }
+                    }
                     clock_subs.push((clock_info, s.user_data));
                     None
                 } else {
@@ -3160,9 +3184,34 @@ pub fn poll_oneoff<M: MemorySize>(
 
     let mut seen_events = vec![Default::default(); in_events.len()];
 
-    let start = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
     let mut triggered = 0;
-    while triggered == 0 {
+    // In deterministic mode the clocks are virtual: when the program also
+    // waits on a clock, the files are polled once and, when none of them is
+    // ready, the clock moves forward by the timeout of the clock
+    // subscriptions. Without a clock subscription, the program waits for
+    // the files however long it takes, as it can't observe the real time
+    let virtual_timeout = env.deterministic.is_some() && !clock_subs.is_empty();
+    let wait_for_fds = env.deterministic.is_some() && clock_subs.is_empty() && !fds.is_empty();
+    if let Some(deterministic) = env.deterministic.as_ref().filter(|_| virtual_timeout) {
+        triggered = match poll(
+            fds.as_slice(),
+            in_events.as_slice(),
+            seen_events.as_mut_slice(),
+            Duration::ZERO,
+        ) {
+            Ok(a) => a,
+            Err(FsError::WouldBlock) => 0,
+            Err(err) => {
+                return Ok(fs_error_into_wasi_err(err));
+            }
+        };
+        if triggered == 0 {
+            deterministic.clock.advance(time_to_sleep);
+        }
+    }
+
+    let start = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
+    while triggered == 0 && !virtual_timeout {
         let now = platform_clock_time_get(__WASI_CLOCK_MONOTONIC, 1_000_000).unwrap() as u128;
         let delta = match now.checked_sub(start) {
             Some(a) => Duration::from_nanos(a as u64),
@@ -3187,7 +3236,7 @@ pub fn poll_oneoff<M: MemorySize>(
                 return Ok(fs_error_into_wasi_err(err));
             }
         };
-        if delta > time_to_sleep {
+        if delta > time_to_sleep && !wait_for_fds {
             break;
         }
     }
@@ -3316,7 +3365,13 @@ pub fn random_get<M: MemorySize>(
     let memory = env.memory_view(&ctx);
     let buf_len64: u64 = buf_len.into();
     let mut u8_buffer = vec![0; buf_len64 as usize];
-    let res = getrandom::getrandom(&mut u8_buffer);
+    let res = match env.deterministic.as_ref() {
+        Some(deterministic) => {
+            deterministic.fill_random(&mut u8_buffer);
+            Ok(())
+        }
+        None => getrandom::getrandom(&mut u8_buffer),
+    };
     match res {
         Ok(()) => {
             let buf = wasi_try_mem!(buf.slice(&memory, buf_len));
@@ -3505,6 +3560,10 @@ pub fn thread_spawn<M: MemorySize>(
 ) -> __wasi_errno_t {
     debug!("wasi::thread_spawn");
     let env = ctx.data();
+    if env.deterministic.is_some() {
+        // Threads are scheduled by the host and hence not deterministic
+        return __WASI_ENOTSUP;
+    }
     let memory = env.memory_view(&ctx);
     let method = unsafe { get_input_str!(&memory, method, method_len) };
 
@@ -3528,13 +3587,17 @@ pub fn thread_spawn<M: MemorySize>(
     };
 
     // Create the sub-thread
+    let max_threads = env.sandbox.as_ref().and_then(|s| s.max_threads());
     let mut sub_env = env.clone();
-    let mut sub_thread = env.new_thread();
+    let mut sub_thread = match env.new_thread_within(max_threads) {
+        Some(thread) => thread,
+        None => return __WASI_EAGAIN,
+    };
     sub_env.id = sub_thread.id;
 
     let child = {
         let id = sub_thread.id;
-        wasi_try!(env
+        let spawned = env
             .runtime
             .thread_spawn(Box::new(move || {
                 /*
@@ -3567,7 +3630,12 @@ pub fn thread_spawn<M: MemorySize>(
             .map_err(|err| {
                 let err: __wasi_errno_t = err.into();
                 err
-            }));
+            });
+        if let Err(err) = spawned {
+            // The thread never ran, it doesn't count against the limit
+            env.state.threading.lock().unwrap().threads.remove(&id);
+            return err;
+        }
         id
     };
     let child: __wasi_tid_t = child.into();
@@ -3590,6 +3658,12 @@ pub fn thread_sleep(
 
     let env = ctx.data();
     let duration = Duration::from_nanos(duration as u64);
+    // Like the clock subscriptions of `poll_oneoff`, the sleep only moves
+    // the virtual clock in deterministic mode
+    if let Some(deterministic) = env.deterministic.as_ref() {
+        deterministic.clock.advance(duration);
+        return Ok(__WASI_ESUCCESS);
+    }
     env.sleep(duration)?;
     Ok(__WASI_ESUCCESS)
 }
@@ -3652,10 +3726,13 @@ pub fn thread_parallelism<M: MemorySize>(
     debug!("wasi::thread_parallelism");
 
     let env = ctx.data();
-    let parallelism = wasi_try!(env.runtime().thread_parallelism().map_err(|err| {
-        let err: __wasi_errno_t = err.into();
-        err
-    }));
+    let parallelism = match env.deterministic {
+        Some(_) => 1,
+        None => wasi_try!(env.runtime().thread_parallelism().map_err(|err| {
+            let err: __wasi_errno_t = err.into();
+            err
+        })),
+    };
     let parallelism: M::Offset = wasi_try!(parallelism.try_into().map_err(|_| __WASI_EOVERFLOW));
     let memory = env.memory_view(&ctx);
     wasi_try_mem!(ret_parallelism.write(&memory, parallelism));
@@ -4746,7 +4823,7 @@ pub fn sock_open<M: MemorySize>(
     let (memory, state, mut inodes) = env.get_memory_and_wasi_state_and_inodes_mut(&ctx, 0);
 
     let kind = match ty {
-        __WASI_SOCK_TYPE_STREAM | __WASI_SOCK_TYPE_DGRAM => Kind::Socket {
+        __WASI_SOCK_TYPE_STREAM | __WASI_SOCK_TYPE_DGRAM | __WASI_SOCK_TYPE_RAW => Kind::Socket {
             socket: InodeSocket::new(InodeSocketKind::PreSocket {
                 family: af,
                 ty,
@@ -4761,6 +4838,7 @@ pub fn sock_open<M: MemorySize>(
                 recv_timeout: None,
                 connect_timeout: None,
                 accept_timeout: None,
+                nonblocking: false,
             }),
         },
         _ => return __WASI_ENOTSUP,
@@ -0,0 +1,143 @@
+use std::io::Read;
+use std::time::Duration;
+
+use wasmer::{Cranelift, EngineBuilder, Instance, Module, Store};
+use wasmer_wasi::{Deterministic, Pipe, VirtualClock, WasiState, WasiStateCreationError};
+
+const CLOCK_AND_RANDOM_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        (drop (call $random_get (i32.const 32) (i32.const 16)))
+        (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 48)))
+
+        ;; Write the 24 bytes of random data and time to stdout
+        (i32.store (i32.const 0) (i32.const 32))
+        (i32.store (i32.const 4) (i32.const 24))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+    )
+)
+"#;
+
+fn run(deterministic: Deterministic) -> Vec<u8> {
+    run_wat(CLOCK_AND_RANDOM_WAT, deterministic)
+}
+
+fn run_wat(wat: &str, deterministic: Deterministic) -> Vec<u8> {
+    let mut store = Store::new(deterministic.engine(Cranelift::default()));
+    let module = Module::new(&store, wat).unwrap();
+
+    let mut stdout = Pipe::new();
+    let wasi_env = WasiState::new("command-name")
+        .stdout(Box::new(stdout.clone()))
+        .deterministic(deterministic)
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut output = Vec::new();
+    stdout.read_to_end(&mut output).unwrap();
+    output
+}
+
+#[test]
+fn test_deterministic_runs() {
+    let clock = VirtualClock::new(Duration::from_secs(3));
+    let first = run(Deterministic::new(42).with_clock(clock.clone()));
+    let second = run(Deterministic::new(42).with_clock(clock.clone()));
+    assert_eq!(first, second);
+    assert_eq!(&first[16..], &3_000_000_000u64.to_le_bytes()[..]);
+
+    clock.advance(Duration::from_nanos(7));
+    let third = run(Deterministic::new(43).with_clock(clock));
+    assert_ne!(&first[..16], &third[..16]);
+    assert_eq!(&third[16..], &3_000_000_007u64.to_le_bytes()[..]);
+}
+
+const POLL_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        ;; Wait for two seconds on the monotonic clock
+        (i32.store8 (i32.const 108) (i32.const 0))
+        (i32.store (i32.const 116) (i32.const 1))
+        (i64.store (i32.const 124) (i64.const 2000000000))
+        (drop (call $poll_oneoff (i32.const 100) (i32.const 200) (i32.const 1) (i32.const 240)))
+        (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 48)))
+
+        ;; Write the time after the wait to stdout
+        (i32.store (i32.const 0) (i32.const 48))
+        (i32.store (i32.const 4) (i32.const 8))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+    )
+)
+"#;
+
+#[test]
+fn test_deterministic_poll() {
+    let clock = VirtualClock::new(Duration::from_secs(3));
+    let output = run_wat(POLL_WAT, Deterministic::new(42).with_clock(clock.clone()));
+    assert_eq!(&output[..], &5_000_000_000u64.to_le_bytes()[..]);
+    assert_eq!(clock.now(), Duration::from_secs(5));
+}
+
+const SLEEP_WAT: &str = r#"
+(module
+    (import "wasix_32v1" "thread_sleep" (func $thread_sleep (param i64) (result i32)))
+    (import "wasix_32v1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasix_32v1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        ;; Sleep for an hour
+        (drop (call $thread_sleep (i64.const 3600000000000)))
+        (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 48)))
+
+        ;; Write the time after the sleep to stdout
+        (i32.store (i32.const 0) (i32.const 48))
+        (i32.store (i32.const 4) (i32.const 8))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+    )
+)
+"#;
+
+#[test]
+fn test_deterministic_sleep() {
+    let clock = VirtualClock::new(Duration::from_secs(3));
+    let output = run_wat(SLEEP_WAT, Deterministic::new(42).with_clock(clock.clone()));
+    assert_eq!(&output[..], &3_603_000_000_000u64.to_le_bytes()[..]);
+    assert_eq!(clock.now(), Duration::from_secs(3603));
+}
+
+#[test]
+fn test_deterministic_requires_nan_canonicalization() {
+    let mut store = Store::new(EngineBuilder::new(Cranelift::default()).engine());
+    let result = WasiState::new("command-name")
+        .deterministic(Deterministic::new(42))
+        .finalize(&mut store);
+    assert_eq!(
+        result.err(),
+        Some(WasiStateCreationError::NanCanonicalizationRequired)
+    );
+}
@@ -0,0 +1,268 @@
+use std::io::{Read, Write};
+
+use wasmer::{Instance, Module, Store};
+use wasmer_wasi::{JournalError, Pipe, SyscallJournal, WasiError, WasiState};
+
+const NONDETERMINISTIC_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        (drop (call $random_get (i32.const 32) (i32.const 16)))
+        (drop (call $clock_time_get (i32.const 0) (i64.const 1) (i32.const 48)))
+
+        ;; Write the 24 bytes of random data and time to stdout
+        (i32.store (i32.const 0) (i32.const 32))
+        (i32.store (i32.const 4) (i32.const 24))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+
+        ;; Exit with the low bits of the random data
+        (call $proc_exit (i32.and (i32.load8_u (i32.const 32)) (i32.const 7)))
+    )
+)
+"#;
+
+const SOCKET_WAT: &str = r#"
+(module
+    (import "wasix_32v1" "sock_open" (func $sock_open (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_set_opt_flag" (func $sock_set_opt_flag (param i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_bind" (func $sock_bind (param i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_listen" (func $sock_listen (param i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_connect" (func $sock_connect (param i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_addr_local" (func $sock_addr_local (param i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    ;; 127.0.0.1:0 and 127.0.0.1:9
+    (data (i32.const 200) "\01\00\00\7f\00\00\01")
+    (data (i32.const 240) "\01\09\00\7f\00\00\01")
+
+    (func $main (export "_start")
+        (local $tcp i32)
+        (local $udp i32)
+
+        ;; A listening TCP socket
+        (i32.store (i32.const 300) (call $sock_open (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 100)))
+        (local.set $tcp (i32.load (i32.const 100)))
+        (i32.store (i32.const 304) (call $sock_bind (local.get $tcp) (i32.const 200)))
+        (i32.store (i32.const 308) (call $sock_listen (local.get $tcp) (i32.const 8)))
+
+        ;; A connected UDP socket
+        (i32.store (i32.const 312) (call $sock_open (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 104)))
+        (local.set $udp (i32.load (i32.const 104)))
+        (i32.store (i32.const 316) (call $sock_set_opt_flag (local.get $udp) (i32.const 2) (i32.const 1)))
+        (i32.store (i32.const 320) (call $sock_bind (local.get $udp) (i32.const 200)))
+        (i32.store (i32.const 324) (call $sock_connect (local.get $udp) (i32.const 240)))
+
+        ;; The address the TCP socket ended up bound to
+        (i32.store (i32.const 328) (call $sock_addr_local (local.get $tcp) (i32.const 332)))
+
+        ;; Write the error codes and the address to stdout
+        (i32.store (i32.const 0) (i32.const 300))
+        (i32.store (i32.const 4) (i32.const 51))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+const DUP_WAT: &str = r#"
+(module
+    (import "wasix_32v1" "fd_dup" (func $fd_dup (param i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_close" (func $fd_close (param i32) (result i32)))
+    (import "wasix_32v1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (data (i32.const 200) "hello\n")
+
+    (func $main (export "_start")
+        (local $fd i32)
+
+        ;; Write to a duplicate of stdout and close it again
+        (drop (call $fd_dup (i32.const 1) (i32.const 100)))
+        (local.set $fd (i32.load (i32.const 100)))
+        (i32.store (i32.const 0) (i32.const 200))
+        (i32.store (i32.const 4) (i32.const 6))
+        (drop (call $fd_write (local.get $fd) (i32.const 0) (i32.const 1) (i32.const 20)))
+        (drop (call $fd_close (local.get $fd)))
+
+        ;; Writing to the closed descriptor fails
+        (call $proc_exit (call $fd_write (local.get $fd) (i32.const 0) (i32.const 1) (i32.const 20)))
+    )
+)
+"#;
+
+const READ_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $main (export "_start")
+        ;; Read stdin into two buffers far apart from each other
+        (i32.store (i32.const 0) (i32.const 1000))
+        (i32.store (i32.const 4) (i32.const 3))
+        (i32.store (i32.const 8) (i32.const 40000))
+        (i32.store (i32.const 12) (i32.const 16))
+        (drop (call $fd_read (i32.const 0) (i32.const 0) (i32.const 2) (i32.const 20)))
+
+        ;; Write the first buffer and then the second one to stdout
+        (i32.store (i32.const 12) (i32.sub (i32.load (i32.const 20)) (i32.const 3)))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 2) (i32.const 24)))
+
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+/// Runs the module and returns its output and its exit code
+fn run(wat: &str, journal: SyscallJournal) -> Result<(Vec<u8>, u32), wasmer::RuntimeError> {
+    run_with_stdin(wat, b"", journal)
+}
+
+/// Runs the module with the given input and returns its output and its exit code
+fn run_with_stdin(
+    wat: &str,
+    input: &[u8],
+    journal: SyscallJournal,
+) -> Result<(Vec<u8>, u32), wasmer::RuntimeError> {
+    let mut store = Store::default();
+    let module = Module::new(&store, wat).unwrap();
+
+    let mut stdin = Pipe::new();
+    stdin.write_all(input).unwrap();
+    let mut stdout = Pipe::new();
+    let wasi_env = WasiState::new("command-name")
+        .stdin(Box::new(stdin))
+        .stdout(Box::new(stdout.clone()))
+        .journal(journal)
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    let code = match err.downcast::<WasiError>() {
+        Ok(WasiError::Exit(code)) => code,
+        Ok(err) => panic!("unexpected error: {}", err),
+        Err(err) => return Err(err),
+    };
+
+    let mut output = Vec::new();
+    stdout.read_to_end(&mut output).unwrap();
+    Ok((output, code))
+}
+
+#[test]
+fn test_record_and_replay() {
+    let mut trace = Pipe::new();
+    let recorded = run(
+        NONDETERMINISTIC_WAT,
+        SyscallJournal::record(trace.clone()).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(recorded.0.len(), 24);
+
+    let mut data = Vec::new();
+    trace.read_to_end(&mut data).unwrap();
+    for _ in 0..2 {
+        let replayed = run(
+            NONDETERMINISTIC_WAT,
+            SyscallJournal::replay(&data[..]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(replayed, recorded);
+    }
+}
+
+#[test]
+fn test_record_and_replay_sockets() {
+    let mut trace = Pipe::new();
+    let recorded = run(SOCKET_WAT, SyscallJournal::record(trace.clone()).unwrap()).unwrap();
+    assert_eq!(recorded.0.len(), 51);
+
+    // The sockets are not opened again while replaying, the syscalls that
+    // use them must come from the journal rather than fail with EBADF
+    let mut data = Vec::new();
+    trace.read_to_end(&mut data).unwrap();
+    let replayed = run(SOCKET_WAT, SyscallJournal::replay(&data[..]).unwrap()).unwrap();
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+fn test_record_and_replay_fd_table() {
+    let mut trace = Pipe::new();
+    let recorded = run(DUP_WAT, SyscallJournal::record(trace.clone()).unwrap()).unwrap();
+    assert_eq!(recorded.0, b"hello\n");
+    assert_ne!(recorded.1, 0);
+
+    // The duplicate is rebuilt from the journal, hence the write to it is
+    // recognized as a write to stdout and performed again
+    let mut data = Vec::new();
+    trace.read_to_end(&mut data).unwrap();
+    let replayed = run(DUP_WAT, SyscallJournal::replay(&data[..]).unwrap()).unwrap();
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+fn test_record_and_replay_reads() {
+    let mut trace = Pipe::new();
+    let recorded = run_with_stdin(
+        READ_WAT,
+        b"abcdefgh",
+        SyscallJournal::record(trace.clone()).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(recorded.0, b"abcdefgh");
+
+    // The data read into each iovec is replayed without any input
+    let mut data = Vec::new();
+    trace.read_to_end(&mut data).unwrap();
+    let replayed = run(READ_WAT, SyscallJournal::replay(&data[..]).unwrap()).unwrap();
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+fn test_replay_divergence() {
+    let mut trace = Pipe::new();
+    run(
+        NONDETERMINISTIC_WAT,
+        SyscallJournal::record(trace.clone()).unwrap(),
+    )
+    .unwrap();
+
+    let mut data = Vec::new();
+    trace.read_to_end(&mut data).unwrap();
+    let err = run(
+        &NONDETERMINISTIC_WAT.replace(
+            "(i32.const 32) (i32.const 16)",
+            "(i32.const 32) (i32.const 8)",
+        ),
+        SyscallJournal::replay(&data[..]).unwrap(),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast::<JournalError>(),
+        Ok(JournalError::Diverged { index: 0, .. })
+    ));
+}
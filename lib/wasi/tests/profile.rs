@@ -0,0 +1,236 @@
+#![cfg(feature = "sandbox-profile")]
+
+use std::io::Read;
+
+use wasmer::{Cranelift, Instance, Module, Pages, Store};
+use wasmer_wasi::{Pipe, SandboxProfile, WasiError, WasiState, WasiStateCreationError};
+
+const SANDBOXED_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (data (i32.const 64) "a.txt")
+
+    (func $main (export "_start")
+        ;; Store the errno of the syscalls in the first 3 bytes
+        (i32.store8 (i32.const 100) (call $clock_time_get (i32.const 0) (i64.const 1) (i32.const 48)))
+        (i32.store8 (i32.const 101) (call $path_open (i32.const 4) (i32.const 0) (i32.const 64) (i32.const 5)
+            (i32.const 1) (i64.const 0x7ffffff) (i64.const 0x7ffffff) (i32.const 0) (i32.const 32)))
+        (i32.store8 (i32.const 102) (call $path_open (i32.const 4) (i32.const 0) (i32.const 64) (i32.const 5)
+            (i32.const 1) (i64.const 0x7ffffff) (i64.const 0x7ffffff) (i32.const 0) (i32.const 32)))
+
+        (i32.store (i32.const 0) (i32.const 100))
+        (i32.store (i32.const 4) (i32.const 3))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+const NETWORK_WAT: &str = r#"
+(module
+    (import "wasix_32v1" "http_request" (func $http_request (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (data (i32.const 64) "http://10.0.0.2/")
+    (data (i32.const 96) "http://10.0.0.1/")
+    (data (i32.const 128) "GET")
+
+    (func $main (export "_start")
+        ;; Store the errno of the requests in the first 2 bytes
+        (i32.store8 (i32.const 100) (call $http_request (i32.const 64) (i32.const 16)
+            (i32.const 128) (i32.const 3) (i32.const 132) (i32.const 0) (i32.const 0) (i32.const 160)))
+        (i32.store8 (i32.const 101) (call $http_request (i32.const 96) (i32.const 16)
+            (i32.const 128) (i32.const 3) (i32.const 132) (i32.const 0) (i32.const 0) (i32.const 160)))
+
+        (i32.store (i32.const 0) (i32.const 100))
+        (i32.store (i32.const 4) (i32.const 2))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+const UDP_WAT: &str = r#"
+(module
+    (import "wasix_32v1" "sock_open" (func $sock_open (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_bind" (func $sock_bind (param i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_send_to" (func $sock_send_to (param i32 i32 i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "sock_connect" (func $sock_connect (param i32 i32) (result i32)))
+    (import "wasix_32v1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+    (import "wasix_32v1" "proc_exit" (func $proc_exit (param i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    ;; 127.0.0.1:0, 127.0.0.1:2313 and 10.0.0.3:2313
+    (data (i32.const 200) "\01\00\00\7f\00\00\01")
+    (data (i32.const 240) "\01\09\09\7f\00\00\01")
+    (data (i32.const 280) "\01\09\09\0a\00\00\03")
+    (data (i32.const 320) "\40\00\00\00\01\00\00\00")
+
+    (func $main (export "_start")
+        (local $fd i32)
+        (drop (call $sock_open (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 340)))
+        (local.set $fd (i32.load (i32.const 340)))
+
+        ;; Store the errno of the syscalls in the first 4 bytes
+        (i32.store8 (i32.const 100) (call $sock_bind (local.get $fd) (i32.const 200)))
+        (i32.store8 (i32.const 101) (call $sock_send_to (local.get $fd) (i32.const 320) (i32.const 1)
+            (i32.const 0) (i32.const 280) (i32.const 344)))
+        (i32.store8 (i32.const 102) (call $sock_send_to (local.get $fd) (i32.const 320) (i32.const 1)
+            (i32.const 0) (i32.const 240) (i32.const 344)))
+        (i32.store8 (i32.const 103) (call $sock_connect (local.get $fd) (i32.const 280)))
+
+        (i32.store (i32.const 0) (i32.const 100))
+        (i32.store (i32.const 4) (i32.const 4))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+        (call $proc_exit (i32.const 0))
+    )
+)
+"#;
+
+fn run(profile: &str) -> Result<Vec<u8>, String> {
+    run_module(SANDBOXED_WAT, profile)
+}
+
+fn run_module(wat: &str, profile: &str) -> Result<Vec<u8>, String> {
+    let mut store = Store::default();
+    let module = Module::new(&store, wat).unwrap();
+
+    let mut stdout = Pipe::new();
+    let wasi_env = WasiState::new("command-name")
+        .stdout(Box::new(stdout.clone()))
+        .profile(&profile.parse::<SandboxProfile>().unwrap())
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    let err = start.call(&mut store, &[]).unwrap_err();
+    match err.downcast::<WasiError>() {
+        Ok(WasiError::Exit(0)) => {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).unwrap();
+            Ok(output)
+        }
+        Ok(err) => Err(err.to_string()),
+        Err(err) => Err(err.message()),
+    }
+}
+
+#[test]
+fn test_profile_restrictions() {
+    let dir = std::env::temp_dir().join("wasmer-wasi-profile-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let preopen = format!(
+        "[[preopen]]\nhost = {:?}\nwrite = true\ncreate = true\n",
+        dir.display().to_string()
+    );
+
+    // Everything allowed: the second open works as well
+    let output = run(&preopen).unwrap();
+    assert_eq!(output, vec![0, 0, 0]);
+
+    // stdio, the virtual root, the preopened directory and the first file
+    // take 6 descriptors
+    let output = run(&format!("{}\n[limits]\nopen_files = 6\n", preopen)).unwrap();
+    assert_eq!(output, vec![0, 0, __WASI_EMFILE]);
+
+    // The clock can not be read
+    let output = run(&format!(
+        "{}\n[syscalls]\nallow = [\"fs\", \"process\"]\n",
+        preopen
+    ))
+    .unwrap();
+    assert_eq!(output, vec![__WASI_ENOTCAPABLE, 0, 0]);
+
+    // The program can not exit
+    let err = run(&format!("{}\n[syscalls]\nallow = [\"fs\"]\n", preopen)).unwrap_err();
+    assert!(err.contains("proc_exit"), "{}", err);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_profile_network_allowlist() {
+    // Only the second host can be reached, by a runtime that doesn't
+    // support HTTP requests
+    let output = run_module(
+        NETWORK_WAT,
+        "[network]\nenabled = true\nconnect = [\"10.0.0.1:80\"]\n",
+    )
+    .unwrap();
+    assert_eq!(output, vec![__WASI_EPERM, __WASI_ENOTSUP]);
+
+    // Without an allowlist, any host can be reached
+    let output = run_module(NETWORK_WAT, "[network]\nenabled = true\n").unwrap();
+    assert_eq!(output, vec![__WASI_ENOTSUP, __WASI_ENOTSUP]);
+}
+
+#[test]
+fn test_profile_udp_allowlist() {
+    // The datagrams and the connection to the other host are denied by
+    // the socket, as they don't go through the networking
+    let output = run_module(
+        UDP_WAT,
+        "[network]\nenabled = true\nconnect = [\"127.0.0.1\"]\nbind = [\"127.0.0.1\"]\n",
+    )
+    .unwrap();
+    assert_eq!(output, vec![0, __WASI_EPERM, 0, __WASI_EPERM]);
+
+    // The socket can not be bound to the loopback interface
+    let output = run_module(
+        UDP_WAT,
+        "[network]\nenabled = true\nbind = [\"10.0.0.1\"]\n",
+    )
+    .unwrap();
+    assert_eq!(output[0], __WASI_EPERM);
+}
+
+#[test]
+fn test_profile_memory_limit() {
+    let profile = "[limits]\nmemory_pages = 2\n"
+        .parse::<SandboxProfile>()
+        .unwrap();
+
+    // The default tunables let the memories grow past the limit
+    let mut store = Store::default();
+    let err = WasiState::new("command-name")
+        .profile(&profile)
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap_err();
+    assert_eq!(err, WasiStateCreationError::MemoryLimitRequired(2));
+
+    let mut store = profile.store(Cranelift::default());
+    WasiState::new("command-name")
+        .profile(&profile)
+        .unwrap()
+        .finalize(&mut store)
+        .unwrap();
+
+    let module = Module::new(&store, "(module (memory (export \"memory\") 1))").unwrap();
+    let instance = Instance::new(&mut store, &module, &wasmer::imports! {}).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    assert_eq!(memory.ty(&store).maximum, Some(Pages(2)));
+    assert!(memory.grow(&mut store, 1).is_ok());
+    assert!(memory.grow(&mut store, 1).is_err());
+}
+
+const __WASI_EMFILE: u8 = wasmer_wasi::types::__WASI_EMFILE as u8;
+const __WASI_ENOTCAPABLE: u8 = wasmer_wasi::types::__WASI_ENOTCAPABLE as u8;
+const __WASI_ENOTSUP: u8 = wasmer_wasi::types::__WASI_ENOTSUP as u8;
+const __WASI_EPERM: u8 = wasmer_wasi::types::__WASI_EPERM as u8;
@@ -0,0 +1,53 @@
+#![cfg(feature = "host-vnet")]
+
+use wasmer::Store;
+use wasmer_wasi::{
+    PluggableRuntimeImplementation, UnsupportedVirtualBus, UnsupportedVirtualNetworking,
+    VirtualBus, VirtualNetworking, WasiRuntimeImplementation, WasiState, WasiThreadId,
+};
+
+#[derive(Debug, Default)]
+struct TestRuntime {
+    inner: PluggableRuntimeImplementation,
+    bus: UnsupportedVirtualBus,
+}
+
+impl WasiRuntimeImplementation for TestRuntime {
+    fn bus(&self) -> &(dyn VirtualBus) {
+        &self.bus
+    }
+
+    fn networking(&self) -> &(dyn VirtualNetworking) {
+        self.inner.networking()
+    }
+
+    fn thread_generate_id(&self) -> WasiThreadId {
+        self.inner.thread_generate_id()
+    }
+
+    fn getpid(&self) -> Option<u32> {
+        Some(42)
+    }
+}
+
+#[test]
+fn test_networking_keeps_runtime() {
+    // The order in which they are set doesn't matter
+    for networking_first in [false, true] {
+        let mut store = Store::default();
+        let mut builder = WasiState::new("command-name");
+        if networking_first {
+            builder.networking(UnsupportedVirtualNetworking::default());
+            builder.runtime(TestRuntime::default());
+        } else {
+            builder.runtime(TestRuntime::default());
+            builder.networking(UnsupportedVirtualNetworking::default());
+        }
+        let wasi_env = builder.finalize(&mut store).unwrap();
+
+        let env = wasi_env.data_mut(&mut store);
+        assert_eq!(env.runtime().getpid(), Some(42));
+        // The host networking of the runtime would resolve the name
+        assert!(env.net().resolve("localhost", None, None).is_err());
+    }
+}
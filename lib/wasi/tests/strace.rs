@@ -0,0 +1,82 @@
+use std::io::Read;
+
+use wasmer::{Instance, Module, Store};
+use wasmer_wasi::{Pipe, StraceFormat, SyscallFamily, SyscallTracer, WasiState};
+
+const HELLO_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+    (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+    (data (i32.const 64) "missing.txt")
+    (data (i32.const 80) "hello\n")
+
+    (func $main (export "_start")
+        (drop (call $path_open (i32.const 9) (i32.const 1) (i32.const 64) (i32.const 11)
+            (i32.const 1) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 32)))
+        (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 48)))
+
+        (i32.store (i32.const 0) (i32.const 80))
+        (i32.store (i32.const 4) (i32.const 6))
+        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 20)))
+    )
+)
+"#;
+
+/// Runs the module and returns the trace
+fn run(tracer: impl FnOnce(Pipe) -> SyscallTracer) -> String {
+    let mut store = Store::default();
+    let module = Module::new(&store, HELLO_WAT).unwrap();
+
+    let mut trace = Pipe::new();
+    let wasi_env = WasiState::new("command-name")
+        .stdout(Box::new(Pipe::new()))
+        .strace(tracer(trace.clone()))
+        .finalize(&mut store)
+        .unwrap();
+
+    let import_object = wasi_env.import_object(&mut store, &module).unwrap();
+    let instance = Instance::new(&mut store, &module, &import_object).unwrap();
+    let memory = instance.exports.get_memory("memory").unwrap();
+    wasi_env.data_mut(&mut store).set_memory(memory.clone());
+
+    let start = instance.exports.get_function("_start").unwrap();
+    start.call(&mut store, &[]).unwrap();
+
+    let mut output = String::new();
+    trace.read_to_string(&mut output).unwrap();
+    output
+}
+
+#[test]
+fn test_strace_text() {
+    let trace = run(SyscallTracer::new);
+    let lines = trace.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "{}", trace);
+    assert!(
+        lines[0].starts_with(
+            "path_open(9, SYMLINK_FOLLOW, \"missing.txt\", 11, CREAT, FD_READ, 0, 0, 0x20) = 8 EBADF <"
+        ),
+        "{}",
+        lines[0]
+    );
+    assert!(lines[1].starts_with("clock_time_get(MONOTONIC, 1, ["));
+    assert!(lines[2].starts_with("fd_write(1, \"hello\\n\", 1, [6]) = 0 <"));
+}
+
+#[test]
+fn test_strace_filter_and_json() {
+    let trace = run(|output| {
+        SyscallTracer::new(output)
+            .format(StraceFormat::JsonLines)
+            .families([SyscallFamily::Clock])
+    });
+    let lines = trace.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1, "{}", trace);
+    assert!(lines[0].contains("\"syscall\":\"clock_time_get\",\"family\":\"clock\""));
+    assert!(lines[0].contains("\"args\":{\"clock_id\":\"MONOTONIC\",\"precision\":1,\"time\":"));
+    assert!(lines[0].contains("\"result\":0,\"errno\":\"ESUCCESS\""));
+}
@@ -0,0 +1,75 @@
+#! /bin/sh
+
+# Sets up the toolchains that `make package-create-exe-runtime` packages
+# the runtimes of `wasmer create-exe --self-contained` with. It runs on
+# Alpine Linux (see `make package-create-exe-runtime-docker` for other
+# hosts) and puts in `<dir>/bin`:
+#
+# - `ld.lld`, statically linked so that it can be shipped on its own,
+#   built from an LLVM release whose commit is checked;
+# - `<arch>-linux-musl-gcc` and `<arch>-linux-musl-ar` for every target,
+#   whose C library and compiler runtime come from the signed packages of
+#   the Alpine release of the host.
+#
+# Usage: create-exe-toolchains.sh <dir> <target>...
+
+set -eu
+
+LLVM_TAG='llvmorg-15.0.7'
+LLVM_COMMIT='8dfdcc7b7bf66834a761bd8de445840ef68e4d1a'
+
+if [ ! -f /etc/alpine-release ]; then
+    echo "The create-exe toolchains are made of Alpine Linux packages, run \`make package-create-exe-runtime-docker\` on other hosts." >&2
+    exit 1
+fi
+
+out="$1"
+shift
+mkdir -p "$out/bin" "$out/src"
+
+apk add --no-cache build-base clang lld llvm cmake ninja git python3 linux-headers
+
+if [ ! -x "$out/bin/ld.lld" ]; then
+    src="$out/src/llvm-project"
+    rm -rf "$src" "$out/src/lld-build"
+    git clone --depth 1 --branch "$LLVM_TAG" https://github.com/llvm/llvm-project.git "$src"
+    commit="$(git -C "$src" rev-parse HEAD)"
+    if [ "$commit" != "$LLVM_COMMIT" ]; then
+        echo "$LLVM_TAG is at commit $commit instead of $LLVM_COMMIT." >&2
+        exit 1
+    fi
+
+    cmake -S "$src/llvm" -B "$out/src/lld-build" -G Ninja \
+        -DCMAKE_BUILD_TYPE=Release \
+        -DLLVM_ENABLE_PROJECTS=lld \
+        -DLLVM_TARGETS_TO_BUILD='X86;AArch64' \
+        -DLLVM_ENABLE_ZLIB=OFF \
+        -DLLVM_ENABLE_ZSTD=OFF \
+        -DLLVM_ENABLE_TERMINFO=OFF \
+        -DLLVM_ENABLE_LIBXML2=OFF \
+        -DCMAKE_EXE_LINKER_FLAGS=-static
+    ninja -C "$out/src/lld-build" lld
+    cp "$out/src/lld-build/bin/lld" "$out/bin/ld.lld"
+    rm -rf "$src" "$out/src/lld-build"
+fi
+
+host="$(uname -m)"
+for target in "$@"; do
+    arch="${target%%-*}"
+    cc="$out/bin/$arch-linux-musl-gcc"
+    if [ "$arch" = "$host" ]; then
+        printf '#! /bin/sh\nexec gcc "$@"\n' > "$cc"
+    else
+        # The C library and the compiler runtime of the target come from
+        # the repositories of its architecture, signed with its keys
+        sysroot="$out/sysroot/$arch"
+        apk add --no-cache --no-scripts --initdb --root "$sysroot" --arch "$arch" \
+            --keys-dir "/usr/share/apk/keys/$arch" \
+            --repositories-file /etc/apk/repositories \
+            musl-dev libgcc gcc
+        printf '#! /bin/sh\nexec clang --target=%s-alpine-linux-musl --sysroot=%s -fuse-ld=lld "$@"\n' \
+            "$arch" "$sysroot" > "$cc"
+    fi
+    chmod +x "$cc"
+    ln -sf "$(command -v llvm-ar)" "$out/bin/$arch-linux-musl-ar"
+done
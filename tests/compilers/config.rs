@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use wasmer::{CompilerConfig, Features, ModuleMiddleware, Store};
+use wasmer::{CompilerConfig, Features, FunctionCache, ModuleMiddleware, Store};
 use wasmer_compiler::Engine;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -15,6 +15,8 @@ pub struct Config {
     pub features: Option<Features>,
     pub middlewares: Vec<Arc<dyn ModuleMiddleware>>,
     pub canonicalize_nans: bool,
+    pub function_cache: Option<FunctionCache>,
+    pub lazy_compilation: bool,
 }
 
 impl Config {
@@ -24,6 +26,8 @@ impl Config {
             features: None,
             canonicalize_nans: false,
             middlewares: vec![],
+            function_cache: None,
+            lazy_compilation: false,
         }
     }
 
@@ -39,6 +43,14 @@ impl Config {
         self.canonicalize_nans = canonicalize_nans;
     }
 
+    pub fn set_function_cache(&mut self, function_cache: FunctionCache) {
+        self.function_cache = Some(function_cache);
+    }
+
+    pub fn set_lazy_compilation(&mut self, lazy_compilation: bool) {
+        self.lazy_compilation = lazy_compilation;
+    }
+
     pub fn store(&self) -> Store {
         let compiler_config = self.compiler_config(self.canonicalize_nans);
         let engine = self.engine(compiler_config);
@@ -55,6 +67,7 @@ impl Config {
         if let Some(ref features) = self.features {
             engine = engine.set_features(Some(features.clone()));
         }
+        engine = engine.set_lazy_compilation(self.lazy_compilation);
         engine.engine()
     }
 
@@ -72,6 +85,9 @@ impl Config {
                 let mut compiler = wasmer_compiler_cranelift::Cranelift::new();
                 compiler.canonicalize_nans(canonicalize_nans);
                 compiler.enable_verifier();
+                if let Some(ref function_cache) = self.function_cache {
+                    compiler.function_cache(function_cache.clone());
+                }
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
             }
@@ -80,6 +96,9 @@ impl Config {
                 let mut compiler = wasmer_compiler_llvm::LLVM::new();
                 compiler.canonicalize_nans(canonicalize_nans);
                 compiler.enable_verifier();
+                if let Some(ref function_cache) = self.function_cache {
+                    compiler.function_cache(function_cache.clone());
+                }
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
             }
@@ -88,6 +107,9 @@ impl Config {
                 let mut compiler = wasmer_compiler_singlepass::Singlepass::new();
                 compiler.canonicalize_nans(canonicalize_nans);
                 compiler.enable_verifier();
+                if let Some(ref function_cache) = self.function_cache {
+                    compiler.function_cache(function_cache.clone());
+                }
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
             }
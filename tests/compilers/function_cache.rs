@@ -0,0 +1,61 @@
+use anyhow::Result;
+use wasmer::*;
+
+const ORIGINAL: &str = r#"
+    (module
+      (func (export "run") (result i32) (call $helper))
+      (func $helper (result i32) (i32.const 1))
+      (func (export "trap") (unreachable)))
+"#;
+
+// `$helper` changed and `trap` moved further in the module
+const CHANGED: &str = r#"
+    (module
+      (func (export "run") (result i32) (call $helper))
+      (func $helper (result i32) (i32.add (i32.const 1) (i32.const 1)))
+      (func (export "trap") (unreachable)))
+"#;
+
+fn trap_offset(store: &mut Store, instance: &Instance) -> Result<usize> {
+    let trap = instance.exports.get_function("trap")?;
+    let error = trap.call(store, &[]).unwrap_err();
+    Ok(error.trace()[0].module_offset())
+}
+
+#[compiler_test(function_cache)]
+fn recompiles_changed_functions_only(mut config: crate::Config) -> Result<()> {
+    let cache = FunctionCache::new();
+    let expected_offset = {
+        let mut store = config.store();
+        let module = Module::new(&store, CHANGED)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        trap_offset(&mut store, &instance)?
+    };
+
+    config.set_function_cache(cache.clone());
+    let mut store = config.store();
+    Module::new(&store, ORIGINAL)?;
+    assert_eq!(cache.len(), 3);
+
+    let module = Module::new(&store, CHANGED)?;
+    assert_eq!(cache.len(), 4);
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&mut store, "run")?;
+    assert_eq!(run.call(&mut store)?, 2);
+    // The trap of the cached function is reported at its new offset
+    assert_eq!(trap_offset(&mut store, &instance)?, expected_offset);
+    Ok(())
+}
+
+#[compiler_test(function_cache)]
+fn depends_on_the_compiler_config(mut config: crate::Config) -> Result<()> {
+    let cache = FunctionCache::new();
+    config.set_function_cache(cache.clone());
+    Module::new(&config.store(), ORIGINAL)?;
+    assert_eq!(cache.len(), 3);
+
+    config.set_nan_canonicalization(!config.canonicalize_nans);
+    Module::new(&config.store(), ORIGINAL)?;
+    assert_eq!(cache.len(), 6);
+    Ok(())
+}
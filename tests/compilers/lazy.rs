@@ -0,0 +1,293 @@
+use anyhow::Result;
+use wasmer::*;
+use wasmer_types::TrapCode;
+
+const WAT: &str = r#"
+    (module
+      (import "env" "double" (func $double (param i64) (result i64)))
+      (type $binary (func (param i32 i32) (result i32)))
+      (table 2 funcref)
+      (elem (i32.const 0) $add $sub)
+      (func $add (type $binary) (i32.add (local.get 0) (local.get 1)))
+      (func $sub (type $binary) (i32.sub (local.get 0) (local.get 1)))
+      (func (export "apply") (param i32 i32 i32) (result i32)
+        (call_indirect (type $binary) (local.get 1) (local.get 2) (local.get 0)))
+      (func $fib (export "fib") (param i32) (result i32)
+        (if (result i32) (i32.lt_u (local.get 0) (i32.const 2))
+          (then (local.get 0))
+          (else
+            (i32.add
+              (call $fib (i32.sub (local.get 0) (i32.const 1)))
+              (call $fib (i32.sub (local.get 0) (i32.const 2)))))))
+      ;; More arguments than fit in registers
+      (func $sum (param i64 f64 i64 f64 i64 f64 i64 f64 i64 f64
+                        i64 f64 i64 f64 i64 f64 i64 f64 i64 f64) (result f64)
+        (local $total f64)
+        (local.set $total
+          (f64.add
+            (f64.convert_i64_s
+              (i64.add (i64.add (i64.add (local.get 0) (local.get 2))
+                                (i64.add (local.get 4) (local.get 6)))
+                       (i64.add (i64.add (local.get 8) (local.get 10))
+                                (i64.add (local.get 12) (local.get 14)))))
+            (f64.convert_i64_s (i64.add (local.get 16) (local.get 18)))))
+        (f64.add
+          (local.get $total)
+          (f64.add
+            (f64.add (f64.add (local.get 1) (local.get 3))
+                     (f64.add (local.get 5) (local.get 7)))
+            (f64.add (f64.add (f64.add (local.get 9) (local.get 11))
+                              (f64.add (local.get 13) (local.get 15)))
+                     (f64.add (local.get 17) (local.get 19))))))
+      (func (export "sum") (result f64)
+        (call $sum (i64.const 1) (f64.const 0.5) (i64.const 2) (f64.const 0.5)
+                   (i64.const 3) (f64.const 0.5) (i64.const 4) (f64.const 0.5)
+                   (i64.const 5) (f64.const 0.5) (i64.const 6) (f64.const 0.5)
+                   (i64.const 7) (f64.const 0.5) (i64.const 8) (f64.const 0.5)
+                   (i64.const 9) (f64.const 0.5) (i64.const 10) (f64.const 0.5)))
+      (func (export "call_host") (param i64) (result i64)
+        (call $double (i64.add (local.get 0) (i64.const 1))))
+      (func (export "trap") (unreachable)))
+"#;
+
+fn instantiate(store: &mut Store, module: &Module) -> Result<Instance> {
+    let double = Function::new_typed(store, |x: i64| x * 2);
+    let imports = imports! {
+        "env" => {
+            "double" => double,
+        },
+    };
+    Ok(Instance::new(store, module, &imports)?)
+}
+
+#[compiler_test(lazy)]
+fn compiles_functions_on_first_call(mut config: crate::Config) -> Result<()> {
+    let cache = FunctionCache::new();
+    config.set_function_cache(cache.clone());
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = instantiate(&mut store, &module)?;
+    assert_eq!(cache.len(), 0);
+
+    let fib: TypedFunction<i32, i32> = instance.exports.get_typed_function(&mut store, "fib")?;
+    assert_eq!(fib.call(&mut store, 20)?, 6765);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(fib.call(&mut store, 10)?, 55);
+    assert_eq!(cache.len(), 1);
+
+    let apply: TypedFunction<(i32, i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "apply")?;
+    assert_eq!(apply.call(&mut store, 1, 7, 3)?, 4);
+    assert_eq!(cache.len(), 3);
+    assert_eq!(apply.call(&mut store, 0, 7, 3)?, 10);
+    assert_eq!(cache.len(), 4);
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn passes_arguments_and_results(mut config: crate::Config) -> Result<()> {
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = instantiate(&mut store, &module)?;
+
+    let sum: TypedFunction<(), f64> = instance.exports.get_typed_function(&mut store, "sum")?;
+    assert_eq!(sum.call(&mut store)?, 60.0);
+
+    let call_host: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "call_host")?;
+    assert_eq!(call_host.call(&mut store, 20)?, 42);
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn returns_multiple_values(mut config: crate::Config) -> Result<()> {
+    // Singlepass doesn't support multiple return values
+    if config.compiler == crate::Compiler::Singlepass {
+        return Ok(());
+    }
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let wat = r#"
+        (module
+          (func $swap (param i32 i64) (result i64 i32)
+            (local.get 1) (local.get 0))
+          (func (export "swap") (param i32 i64) (result i64 i32)
+            (call $swap (local.get 0) (local.get 1))))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let swap = instance.exports.get_function("swap")?;
+    assert_eq!(
+        &*swap.call(&mut store, &[Value::I32(1), Value::I64(2)])?,
+        &[Value::I64(2), Value::I32(1)]
+    );
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn reports_traps(mut config: crate::Config) -> Result<()> {
+    let eager_error = {
+        let mut store = config.store();
+        let module = Module::new(&store, WAT)?;
+        let instance = instantiate(&mut store, &module)?;
+        let trap = instance.exports.get_function("trap")?;
+        trap.call(&mut store, &[]).unwrap_err()
+    };
+
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = instantiate(&mut store, &module)?;
+    let trap = instance.exports.get_function("trap")?;
+    let error = trap.call(&mut store, &[]).unwrap_err();
+    let (frame, eager_frame) = (&error.trace()[0], &eager_error.trace()[0]);
+    assert_eq!(frame.func_index(), eager_frame.func_index());
+    assert_eq!(frame.module_offset(), eager_frame.module_offset());
+    assert_eq!(error.to_trap(), Some(TrapCode::UnreachableCodeReached));
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn cannot_be_serialized(mut config: crate::Config) -> Result<()> {
+    config.set_lazy_compilation(true);
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    assert!(module.serialize().is_err());
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn meters_modules_sharing_a_middleware(mut config: crate::Config) -> Result<()> {
+    use wasmer_middlewares::metering::{get_remaining_points, MeteringPoints};
+
+    // Each call costs 4 points, `second` has globals of its own, so the
+    // metering globals of the two modules have different indexes
+    let cost = |_: &wasmer::wasmparser::Operator| -> u64 { 1 };
+    config.set_middlewares(vec![std::sync::Arc::new(
+        wasmer_middlewares::Metering::new(100, cost),
+    )]);
+    config.set_lazy_compilation(true);
+    let mut store = config.store();
+    let first = Module::new(
+        &store,
+        r#"(module
+          (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))"#,
+    )?;
+    let second = Module::new(
+        &store,
+        r#"(module
+          (global $a (mut i64) (i64.const 7))
+          (global $b (mut i32) (i32.const 0))
+          (func (export "sub") (param i32 i32) (result i32)
+            (i32.sub (local.get 0) (local.get 1)))
+          (func (export "globals") (result i64)
+            (i64.add (global.get $a) (i64.extend_i32_u (global.get $b)))))"#,
+    )?;
+
+    // Both modules were transformed before any of their functions is
+    // compiled
+    let first = Instance::new(&mut store, &first, &imports! {})?;
+    let second = Instance::new(&mut store, &second, &imports! {})?;
+    let add: TypedFunction<(i32, i32), i32> =
+        first.exports.get_typed_function(&mut store, "add")?;
+    let sub: TypedFunction<(i32, i32), i32> =
+        second.exports.get_typed_function(&mut store, "sub")?;
+    let globals: TypedFunction<(), i64> =
+        second.exports.get_typed_function(&mut store, "globals")?;
+
+    assert_eq!(add.call(&mut store, 1, 2)?, 3);
+    assert_eq!(add.call(&mut store, 3, 4)?, 7);
+    assert_eq!(sub.call(&mut store, 5, 2)?, 3);
+    assert_eq!(
+        get_remaining_points(&mut store, &first),
+        MeteringPoints::Remaining(92)
+    );
+    assert_eq!(
+        get_remaining_points(&mut store, &second),
+        MeteringPoints::Remaining(96)
+    );
+    // The globals of the module are left alone
+    assert_eq!(globals.call(&mut store)?, 7);
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn tiers_up_hot_functions(mut config: crate::Config) -> Result<()> {
+    let mut tier_config = config.clone();
+    // Recompile with Cranelift when it is enabled, like an engine that
+    // compiles with Singlepass first
+    #[cfg(feature = "cranelift")]
+    {
+        tier_config.compiler = crate::Compiler::Cranelift;
+    }
+    let tier_cache = FunctionCache::new();
+    tier_config.set_function_cache(tier_cache.clone());
+    let cache = FunctionCache::new();
+    config.set_function_cache(cache.clone());
+    let engine = wasmer_compiler::EngineBuilder::new(config.compiler_config(false))
+        .set_tier_up(tier_config.compiler_config(false), 3)
+        .engine();
+    let mut store = Store::new(engine.clone());
+    let module = Module::new(&store, WAT)?;
+    let instance = instantiate(&mut store, &module)?;
+
+    let call_host: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "call_host")?;
+    assert_eq!(call_host.call(&mut store, 1)?, 4);
+    assert_eq!(call_host.call(&mut store, 2)?, 6);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(tier_cache.len(), 0);
+
+    // The third call makes the function hot
+    assert_eq!(call_host.call(&mut store, 3)?, 8);
+    let start = std::time::Instant::now();
+    while tier_cache.len() == 0 {
+        assert!(start.elapsed() < std::time::Duration::from_secs(30));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(cache.len(), 1);
+    for i in 4..10 {
+        assert_eq!(call_host.call(&mut store, i)?, (i + 1) * 2);
+    }
+    assert_eq!(tier_cache.len(), 1);
+    assert!(engine.take_tier_up_errors().is_empty());
+    Ok(())
+}
+
+#[compiler_test(lazy)]
+fn does_not_tier_up_with_middlewares(mut config: crate::Config) -> Result<()> {
+    let mut tier_config = config.clone();
+    let tier_cache = FunctionCache::new();
+    tier_config.set_function_cache(tier_cache.clone());
+    // Each compiler has its own instance of the middleware, only the one
+    // of the engine's compiler transformed the module
+    let cost = |_: &wasmer::wasmparser::Operator| -> u64 { 1 };
+    config.set_middlewares(vec![std::sync::Arc::new(
+        wasmer_middlewares::Metering::new(1000, cost).with_cost_function_id("cost"),
+    )]);
+    tier_config.set_middlewares(vec![std::sync::Arc::new(
+        wasmer_middlewares::Metering::new(1000, cost).with_cost_function_id("cost"),
+    )]);
+    let engine = wasmer_compiler::EngineBuilder::new(config.compiler_config(false))
+        .set_tier_up(tier_config.compiler_config(false), 1)
+        .engine();
+    let mut store = Store::new(engine.clone());
+    let module = Module::new(&store, WAT)?;
+    let instance = instantiate(&mut store, &module)?;
+
+    let call_host: TypedFunction<i64, i64> = instance
+        .exports
+        .get_typed_function(&mut store, "call_host")?;
+    for i in 1..5 {
+        assert_eq!(call_host.call(&mut store, i)?, (i + 1) * 2);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert_eq!(tier_cache.len(), 0);
+    assert!(engine.take_tier_up_errors().is_empty());
+    Ok(())
+}
@@ -7,11 +7,14 @@ extern crate compiler_test_derive;
 
 mod config;
 mod deterministic;
+mod function_cache;
 mod imports;
 mod issues;
+mod lazy;
 mod metering;
 mod middlewares;
 // mod multi_value_imports;
+mod relaxed_simd;
 mod serialize;
 mod traps;
 mod typed_functions;
@@ -170,3 +170,70 @@ fn complex_loop(mut config: crate::Config) -> Result<()> {
     f.call(&mut store, 10_000_000, 4).unwrap_err();
     Ok(())
 }
+
+#[compiler_test(metering)]
+fn streaming_compilation(mut config: crate::Config) -> Result<()> {
+    config
+        .middlewares
+        .push(Arc::new(Metering::new(10, cost_always_one)));
+    let mut store = config.store();
+    let wasm = wat2wasm(
+        br#"(module
+        (memory 1)
+        (func (export "add") (param i32 i32) (result i32)
+           (i32.add (local.get 0)
+                    (local.get 1)))
+        (data (i32.const 0) "\2a"))"#,
+    )?;
+
+    // The functions are compiled while the module is read, with the
+    // middlewares that were applied to the module once.
+    let mut streaming = Module::streaming(&store);
+    for byte in wasm.chunks(1) {
+        streaming.push(byte)?;
+    }
+    let module = streaming.finish()?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let f: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "add")?;
+    assert_eq!(f.call(&mut store, 4, 6)?, 10);
+    assert_eq!(
+        wasmer_middlewares::metering::get_remaining_points(&mut store, &instance),
+        wasmer_middlewares::metering::MeteringPoints::Remaining(6)
+    );
+    Ok(())
+}
+
+#[compiler_test(metering)]
+fn interruption(mut config: crate::Config) -> Result<()> {
+    config.middlewares.push(Arc::new(
+        Metering::new(u64::MAX, cost_always_one).with_interruption(),
+    ));
+    let mut store = config.store();
+    let wat = r#"(module
+        (func (export "spin") (local i32)
+           (loop
+            (local.set 0 (i32.add (local.get 0) (i32.const 1)))
+            (br 0)
+           )
+        )
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let f: TypedFunction<(), ()> = instance.exports.get_typed_function(&mut store, "spin")?;
+
+    // The flag is read on every iteration, even when the compiler
+    // optimizes the loop.
+    let handle = wasmer_middlewares::metering::interrupt_handle(&mut store, &instance);
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        unsafe { handle.interrupt() };
+    });
+    assert!(f.call(&mut store).is_err());
+    interrupter.join().unwrap();
+    assert!(wasmer_middlewares::metering::is_interrupted(
+        &mut store, &instance
+    ));
+    Ok(())
+}
@@ -16,7 +16,11 @@ struct Add2Mul {
 }
 
 impl ModuleMiddleware for Add2MulGen {
-    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+    fn generate_function_middleware(
+        &self,
+        _: &ModuleInfo,
+        _: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
         Box::new(Add2Mul {
             value_off: self.value_off,
         })
@@ -56,7 +60,11 @@ struct Fusion {
 }
 
 impl ModuleMiddleware for FusionGen {
-    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+    fn generate_function_middleware(
+        &self,
+        _: &ModuleInfo,
+        _: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
         Box::new(Fusion { state: 0 })
     }
 }
@@ -0,0 +1,140 @@
+//! Relaxed SIMD operators, with inputs for which every implementation
+//! allowed by the proposal gives the same result.
+
+use anyhow::Result;
+use wasmer::*;
+
+fn f32x4(lanes: [f32; 4]) -> Value {
+    let mut bytes = [0; 16];
+    for (i, lane) in lanes.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&lane.to_le_bytes());
+    }
+    Value::V128(u128::from_le_bytes(bytes))
+}
+
+fn f64x2(lanes: [f64; 2]) -> Value {
+    let mut bytes = [0; 16];
+    for (i, lane) in lanes.iter().enumerate() {
+        bytes[i * 8..i * 8 + 8].copy_from_slice(&lane.to_le_bytes());
+    }
+    Value::V128(u128::from_le_bytes(bytes))
+}
+
+fn i32x4(lanes: [i32; 4]) -> Value {
+    let mut bytes = [0; 16];
+    for (i, lane) in lanes.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&lane.to_le_bytes());
+    }
+    Value::V128(u128::from_le_bytes(bytes))
+}
+
+fn i8x16(lanes: [u8; 16]) -> Value {
+    Value::V128(u128::from_le_bytes(lanes))
+}
+
+#[compiler_test(relaxed_simd)]
+fn relaxed_simd_operators(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::default();
+    features.relaxed_simd(true);
+    config.set_features(features);
+    let mut store = config.store();
+    let wat = r#"(module
+        (func (export "f32x4.fma") (param v128 v128 v128) (result v128)
+            (f32x4.fma (local.get 0) (local.get 1) (local.get 2)))
+        (func (export "f64x2.fms") (param v128 v128 v128) (result v128)
+            (f64x2.fms (local.get 0) (local.get 1) (local.get 2)))
+        (func (export "i32x4.laneselect") (param v128 v128 v128) (result v128)
+            (i32x4.laneselect (local.get 0) (local.get 1) (local.get 2)))
+        (func (export "i8x16.relaxed_swizzle") (param v128 v128) (result v128)
+            (i8x16.relaxed_swizzle (local.get 0) (local.get 1)))
+        (func (export "i32x4.relaxed_trunc_f32x4_s") (param v128) (result v128)
+            (i32x4.relaxed_trunc_f32x4_s (local.get 0)))
+        (func (export "i32x4.relaxed_trunc_f64x2_u_zero") (param v128) (result v128)
+            (i32x4.relaxed_trunc_f64x2_u_zero (local.get 0)))
+        (func (export "f32x4.relaxed_min") (param v128 v128) (result v128)
+            (f32x4.relaxed_min (local.get 0) (local.get 1)))
+        (func (export "f64x2.relaxed_max") (param v128 v128) (result v128)
+            (f64x2.relaxed_max (local.get 0) (local.get 1)))
+    )"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let mut call = |name: &str, args: &[Value]| -> Result<Value> {
+        let function = instance.exports.get_function(name)?;
+        Ok(function.call(&mut store, args)?[0].clone())
+    };
+
+    assert_eq!(
+        call(
+            "f32x4.fma",
+            &[
+                f32x4([1.0, 2.0, 3.0, 4.0]),
+                f32x4([2.0; 4]),
+                f32x4([1.0; 4])
+            ]
+        )?,
+        f32x4([3.0, 5.0, 7.0, 9.0])
+    );
+    assert_eq!(
+        call(
+            "f64x2.fms",
+            &[f64x2([1.0, 2.0]), f64x2([2.0; 2]), f64x2([10.0; 2])]
+        )?,
+        f64x2([8.0, 6.0])
+    );
+    assert_eq!(
+        call(
+            "i32x4.laneselect",
+            &[
+                i32x4([1, 2, 3, 4]),
+                i32x4([5, 6, 7, 8]),
+                i32x4([-1, 0, -1, 0])
+            ]
+        )?,
+        i32x4([1, 6, 3, 8])
+    );
+    let bytes = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+    let mut reversed = bytes;
+    reversed.reverse();
+    assert_eq!(
+        call("i8x16.relaxed_swizzle", &[i8x16(bytes), i8x16(reversed)])?,
+        i8x16(reversed)
+    );
+    assert_eq!(
+        call(
+            "i32x4.relaxed_trunc_f32x4_s",
+            &[f32x4([1.5, -2.5, 100.0, 0.0])]
+        )?,
+        i32x4([1, -2, 100, 0])
+    );
+    assert_eq!(
+        call("i32x4.relaxed_trunc_f64x2_u_zero", &[f64x2([1.5, 42.0])])?,
+        i32x4([1, 42, 0, 0])
+    );
+    assert_eq!(
+        call(
+            "f32x4.relaxed_min",
+            &[f32x4([1.0, 2.0, 3.0, 4.0]), f32x4([4.0, 3.0, 2.0, 1.0])]
+        )?,
+        f32x4([1.0, 2.0, 2.0, 1.0])
+    );
+    assert_eq!(
+        call(
+            "f64x2.relaxed_max",
+            &[f64x2([-1.0, 2.0]), f64x2([1.0, -2.0])]
+        )?,
+        f64x2([1.0, 2.0])
+    );
+
+    Ok(())
+}
+
+#[compiler_test(relaxed_simd)]
+fn relaxed_simd_requires_the_feature(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"(module
+        (func (param v128 v128) (result v128)
+            (f32x4.relaxed_min (local.get 0) (local.get 1)))
+    )"#;
+    assert!(Module::new(&store, wat).is_err());
+    Ok(())
+}
@@ -28,6 +28,9 @@ pub fn run_wast(mut config: crate::Config, wast_path: &str) -> anyhow::Result<()
     if is_simd {
         features.simd(true);
     }
+    if wast_path.contains("extended-const") {
+        features.extended_const(true);
+    }
     if config.compiler == crate::Compiler::Singlepass {
         features.multi_value(false);
     }
@@ -265,6 +265,126 @@ fn create_exe_serialized_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+// Needs the runtime objects installed by `make package-create-exe-runtime`
+#[cfg(target_os = "linux")]
+#[test]
+fn create_exe_self_contained_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    let output: Vec<u8> = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--self-contained"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    let output_str = String::from_utf8_lossy(&output);
+    assert!(
+        output_str.contains("Using linker"),
+        "create-exe output doesn't mention the linker shipped with Wasmer:\n{}",
+        output_str
+    );
+
+    Ok(())
+}
+
+// Needs the runtime objects installed by `make package-create-exe-runtime`
+#[cfg(target_os = "linux")]
+#[test]
+fn create_exe_self_contained_for_another_target_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+    // The `e_machine` field of the ELF header of the executable
+    let (target, machine) = if cfg!(target_arch = "aarch64") {
+        ("x86_64-unknown-linux-musl", 62u16)
+    } else {
+        ("aarch64-unknown-linux-musl", 183u16)
+    };
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--self-contained", "--target", target],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let executable = fs::read(&executable_path)?;
+    assert_eq!(&executable[..4], b"\x7fELF");
+    assert_eq!(
+        u16::from_le_bytes([executable[18], executable[19]]),
+        machine,
+        "the executable wasn't linked for {}",
+        target
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_with_commands_and_filesystem_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    fs::copy(&wasm_path, operating_dir.join("qjs.wasm"))?;
+    fs::create_dir_all(operating_dir.join("fs/scripts"))?;
+    fs::write(operating_dir.join("fs/scripts/test.js"), JS_TEST_SRC_CODE)?;
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--command", "js=qjs.wasm", "--fs", "fs"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &[
+            "--command".to_string(),
+            "js".to_string(),
+            "/scripts/test.js".to_string(),
+        ],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
 fn create_obj(args: Vec<&'static str>, keyword_needle: &str, keyword: &str) -> anyhow::Result<()> {
     let temp_dir = tempfile::tempdir()?;
     let operating_dir: PathBuf = temp_dir.path().to_owned();
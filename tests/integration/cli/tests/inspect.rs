@@ -0,0 +1,52 @@
+//! Basic tests for the `inspect` subcommand
+
+use anyhow::bail;
+use std::process::Command;
+use wasmer_integration_tests_cli::{get_wasmer_path, C_ASSET_PATH};
+
+fn wasi_test_wasm_path() -> String {
+    format!("{}/{}", C_ASSET_PATH, "qjs.wasm")
+}
+
+fn inspect(args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new(get_wasmer_path())
+        .arg("inspect")
+        .arg(wasi_test_wasm_path())
+        .args(args)
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "inspect failed with: stdout: {}\n\nstderr: {}",
+            std::str::from_utf8(&output.stdout)
+                .expect("stdout is not utf8! need to handle arbitrary bytes"),
+            std::str::from_utf8(&output.stderr)
+                .expect("stderr is not utf8! need to handle arbitrary bytes")
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn inspect_works() -> anyhow::Result<()> {
+    let stdout_output = inspect(&[])?;
+    assert!(stdout_output.starts_with("Type: wasm\n"));
+    assert!(stdout_output.contains("\nFunctions: 24 imported, 931 defined, "));
+    assert!(stdout_output.contains("\nWASI version: wasi_unstable\n"));
+    assert!(stdout_output.contains("    \"wasi_unstable\".\"fd_write\": "));
+
+    Ok(())
+}
+
+#[test]
+fn inspect_json_works() -> anyhow::Result<()> {
+    let stdout_output = inspect(&["--format", "json"])?;
+    assert!(stdout_output.starts_with("{\n  \"type\": \"wasm\",\n"));
+    assert!(stdout_output.contains("\n    \"imported\": 24,\n    \"defined\": 931,\n"));
+    assert!(stdout_output.contains("\n  \"wasi_version\": \"wasi_unstable\",\n"));
+    assert!(stdout_output.contains("\n      \"name\": \"code\",\n"));
+    assert!(stdout_output.contains("\n  \"producers\": {\n    \"language\": [\n"));
+
+    Ok(())
+}
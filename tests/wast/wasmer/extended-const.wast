@@ -0,0 +1,35 @@
+;; Extended constant expressions in the initializers of globals and in the
+;; offsets of element and data segments.
+
+(module
+  (import "spectest" "global_i32" (global $base i32))
+  (import "spectest" "global_i64" (global $base64 i64))
+
+  (memory 1)
+  (table 10 funcref)
+
+  ;; 666 + 2 * 3
+  (global (export "g") i32
+    global.get $base i32.const 2 i32.const 3 i32.mul i32.add)
+  ;; 666 - 1000
+  (global (export "g64") i64 global.get $base64 i64.const 1000 i64.sub)
+  (global $wrap i32 i32.const 0x7fffffff i32.const 1 i32.add)
+
+  ;; 666 - 650
+  (data (offset global.get $base i32.const 650 i32.sub) "\2a")
+  ;; 666 - 663
+  (elem (offset global.get $base i32.const 663 i32.sub) $seven)
+
+  (func $seven (result i32) (i32.const 7))
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (result i32) (local.get 0)))
+  (func (export "wrap") (result i32) (global.get $wrap))
+)
+
+(assert_return (get "g") (i32.const 672))
+(assert_return (get "g64") (i64.const -334))
+(assert_return (invoke "wrap") (i32.const -2147483648))
+(assert_return (invoke "load" (i32.const 16)) (i32.const 42))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "call" (i32.const 3)) (i32.const 7))